@@ -12,8 +12,14 @@ async fn main() -> Result<(), WalletError> {
 
     // 2. Get wallet information
     println!("🔑 Wallet Information:");
-    let mnemonic = wallet.get_mnemonic()?;
-    println!("   Mnemonic: {} words", mnemonic.split_whitespace().count());
+    let words = wallet
+        .reveal_mnemonic(
+            None,
+            |_| true,
+            |mnemonic| mnemonic.split_whitespace().count(),
+        )
+        .await?;
+    println!("   Mnemonic: {} words", words);
 
     let address = wallet.get_owner_public_key().await?;
     println!("   Address: {}", address);
@@ -0,0 +1,111 @@
+//! Several logically separate wallets backed by one mnemonic.
+//!
+//! Account 0 is the mnemonic's standard key tree, so every existing wallet is account 0.
+//! Account `n` takes the hardened key `m/12381/8444/12/n` as its master key and derives an
+//! otherwise standard tree below it: its own addresses, synthetic keys, farming keys and
+//! datastore keys. Accounts share the keyring entry, but keep their receive addresses in
+//! separate metadata and their file caches under separate names.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::keys::hardened_key;
+use crate::wallet::Wallet;
+use chia::bls::SecretKey;
+
+/// Hardened purpose index of account master keys, `m/12381/8444/12/<account>`
+pub const ACCOUNT_KEY_PURPOSE: u32 = 12;
+
+/// Master key of `account`, derived from the mnemonic's master key
+pub(crate) fn account_secret_key(master_secret_key: &SecretKey, account: u32) -> SecretKey {
+    hardened_key(master_secret_key, ACCOUNT_KEY_PURPOSE, account)
+}
+
+impl Wallet {
+    /// Load the key tree of `account_index` from the mnemonic of the keyring wallet `name`
+    ///
+    /// Account 0 is the same wallet [`Wallet::load`] returns.
+    pub async fn load_account(name: &str, account_index: u32) -> Result<Self, WalletError> {
+        Self::load_account_with_config(name, account_index, &WalletConfig::default()).await
+    }
+
+    /// Load an account using an explicit configuration
+    pub async fn load_account_with_config(
+        name: &str,
+        account_index: u32,
+        config: &WalletConfig,
+    ) -> Result<Self, WalletError> {
+        let wallet = Self::load_with_config(Some(name.to_string()), false, config).await?;
+        // Account keys are hardened, so a public key alone cannot derive them
+        if wallet.is_watch_only() {
+            return Err(WalletError::WatchOnly);
+        }
+        Ok(wallet.with_account(account_index))
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_accounts_have_separate_key_trees_and_addresses() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("main", &config)
+            .await
+            .unwrap();
+        let default = Wallet::load_with_config(Some("main".to_string()), false, &config)
+            .await
+            .unwrap();
+        let first = Wallet::load_account_with_config("main", 0, &config)
+            .await
+            .unwrap();
+        let savings = Wallet::load_account_with_config("main", 1, &config)
+            .await
+            .unwrap();
+        let operations = Wallet::load_account_with_config("main", 2, &config)
+            .await
+            .unwrap();
+        assert_eq!(savings.account_index(), 1);
+
+        let default_ph = default.get_owner_puzzle_hash().await.unwrap();
+        assert_eq!(first.get_owner_puzzle_hash().await.unwrap(), default_ph);
+        let savings_ph = savings.get_owner_puzzle_hash().await.unwrap();
+        let operations_ph = operations.get_owner_puzzle_hash().await.unwrap();
+        assert_ne!(savings_ph, default_ph);
+        assert_ne!(savings_ph, operations_ph);
+        assert_eq!(
+            Wallet::load_account_with_config("main", 1, &config)
+                .await
+                .unwrap()
+                .get_owner_puzzle_hash()
+                .await
+                .unwrap(),
+            savings_ph
+        );
+
+        let (public_key, signature) = savings.sign_message_at(0, b"hello").await.unwrap();
+        assert_eq!(
+            public_key,
+            savings.get_public_synthetic_key().await.unwrap()
+        );
+        assert!(crate::keys::verify_message(
+            b"hello",
+            &public_key,
+            &signature
+        ));
+
+        assert_eq!(savings.get_new_address().await.unwrap().index, 1);
+        assert_eq!(savings.get_new_address().await.unwrap().index, 2);
+        assert_eq!(default.receive_index().await.unwrap(), 0);
+        assert_eq!(savings.receive_index().await.unwrap(), 2);
+        assert_eq!(savings.issued_puzzle_hashes().await.unwrap()[0], savings_ph);
+
+        #[cfg(feature = "cache")]
+        assert_eq!(default.cache_name(), "main");
+        #[cfg(feature = "cache")]
+        assert_eq!(savings.cache_name(), "main@1");
+    }
+}
@@ -0,0 +1,335 @@
+//! Successive receive addresses derived from the master key.
+//!
+//! Index 0 is the wallet's default address. [`Wallet::get_new_address`] hands out the
+//! next unhardened index and records the highest one issued in the keyring metadata, per
+//! account, so balance and coin queries know which puzzle hashes to include.
+//!
+//! Seeds imported from another wallet may already have used later indexes;
+//! [`Wallet::scan_addresses`] finds them by walking indexes against a peer until a gap of
+//! unused ones.
+
+#[cfg(feature = "peer")]
+use crate::chain::fetch_puzzle_history;
+use crate::error::WalletError;
+#[cfg(feature = "peer")]
+use crate::keys::synthetic_secret_key_at;
+use crate::keys::{standard_puzzle_hash, synthetic_public_key_at};
+use crate::metadata::ChainProfile;
+use crate::wallet::Wallet;
+use chia::bls::PublicKey;
+#[cfg(feature = "peer")]
+use chia::bls::SecretKey;
+use chia::protocol::Bytes32;
+#[cfg(feature = "peer")]
+use datalayer_driver::Peer;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Consecutive unused indexes after which [`Wallet::scan_addresses`] stops, unless
+/// configured otherwise
+pub const DEFAULT_ADDRESS_GAP_LIMIT: u32 = 20;
+
+/// A receive address and the derivation index it was derived from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiveAddress {
+    pub index: u32,
+    /// Puzzle hash as hex
+    pub puzzle_hash: String,
+    pub address: String,
+}
+
+impl ReceiveAddress {
+    fn new(index: u32, puzzle_hash: Bytes32, chain: &ChainProfile) -> Result<Self, WalletError> {
+        Ok(Self {
+            index,
+            puzzle_hash: hex::encode(puzzle_hash),
+            address: chain.encode_address(puzzle_hash)?,
+        })
+    }
+}
+
+/// Derivation indexes with on-chain history, found by [`Wallet::scan_addresses`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressScan {
+    /// Indexes whose puzzle hash has received coins, in order
+    pub used_indexes: Vec<u32>,
+    /// Number of indexes checked, starting at 0
+    pub scanned: u32,
+}
+
+impl AddressScan {
+    /// Indexes to check next: up to `gap_limit` past the last used one
+    #[cfg_attr(not(feature = "peer"), allow(dead_code))]
+    fn next_window(&self, gap_limit: u32) -> Option<Range<u32>> {
+        let end = self
+            .used_indexes
+            .last()
+            .map_or(0, |index| index.saturating_add(1))
+            .saturating_add(gap_limit.max(1));
+        (self.scanned < end).then_some(self.scanned..end)
+    }
+}
+
+/// Standard puzzle hash of the unhardened wallet key at `index`
+pub(crate) fn puzzle_hash_at_index(master_public_key: &PublicKey, index: u32) -> Bytes32 {
+    standard_puzzle_hash(&synthetic_public_key_at(master_public_key, index))
+}
+
+impl Wallet {
+    /// Issue the next receive address
+    ///
+    /// The first call returns index 1, as index 0 is the default address. Issuing is
+    /// serialized through the keyring, so concurrent callers never get the same index.
+    pub async fn get_new_address(&self) -> Result<ReceiveAddress, WalletError> {
+        self.require_key_tree()?;
+        let master_pk = self.get_master_public_key().await?;
+
+        let index = Self::modify_metadata(self.get_wallet_name(), self.get_config(), |metadata| {
+            let addresses = metadata.account_addresses_mut(self.account_index());
+            let index = addresses
+                .receive_index
+                .unwrap_or(0)
+                .checked_add(1)
+                .ok_or_else(|| {
+                    WalletError::CryptoError("Derivation indexes exhausted".to_string())
+                })?;
+            addresses.receive_index = Some(index);
+            Ok(index)
+        })
+        .await?;
+
+        ReceiveAddress::new(
+            index,
+            puzzle_hash_at_index(&master_pk, index),
+            &self.chain_profile().await?,
+        )
+    }
+
+    /// Receive addresses at every index of `range`, issued or not
+    pub async fn get_addresses(
+        &self,
+        range: Range<u32>,
+    ) -> Result<Vec<ReceiveAddress>, WalletError> {
+        let chain = self.chain_profile().await?;
+        let master_pk = if range.end > 1 {
+            self.require_key_tree()?;
+            Some(self.get_master_public_key().await?)
+        } else {
+            None
+        };
+
+        let mut addresses = Vec::with_capacity(range.len());
+        for index in range {
+            let puzzle_hash = match &master_pk {
+                Some(master_pk) if index > 0 => puzzle_hash_at_index(master_pk, index),
+                _ => self.get_owner_puzzle_hash().await?,
+            };
+            addresses.push(ReceiveAddress::new(index, puzzle_hash, &chain)?);
+        }
+        Ok(addresses)
+    }
+
+    /// Highest receive address index issued so far; 0 until [`Wallet::get_new_address`]
+    /// is first called and for wallets that are not in the keyring
+    pub async fn receive_index(&self) -> Result<u32, WalletError> {
+        match self.get_metadata().await {
+            Ok(metadata) => Ok(metadata
+                .account_addresses(self.account_index())
+                .receive_index
+                .unwrap_or(0)),
+            Err(WalletError::WalletNotFound(_)) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Store the used indexes of `scan` and raise the receive index past them
+    #[cfg_attr(not(feature = "peer"), allow(dead_code))]
+    pub(crate) async fn record_address_scan(&self, scan: &AddressScan) -> Result<(), WalletError> {
+        let highest_used = scan.used_indexes.last().copied().unwrap_or(0);
+        Self::modify_metadata(self.get_wallet_name(), self.get_config(), |metadata| {
+            let addresses = metadata.account_addresses_mut(self.account_index());
+            addresses.used_indexes = scan.used_indexes.clone();
+            if highest_used > addresses.receive_index.unwrap_or(0) {
+                addresses.receive_index = Some(highest_used);
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Puzzle hashes of every issued receive address, the default one first
+    pub async fn issued_puzzle_hashes(&self) -> Result<Vec<Bytes32>, WalletError> {
+        Ok(self
+            .issued_synthetic_keys()
+            .await?
+            .iter()
+            .map(standard_puzzle_hash)
+            .collect())
+    }
+
+    /// Synthetic public keys of every issued receive address, the default one first
+    pub(crate) async fn issued_synthetic_keys(&self) -> Result<Vec<PublicKey>, WalletError> {
+        let mut keys = vec![self.get_public_synthetic_key().await?];
+        let receive_index = self.receive_index().await?;
+        if receive_index > 0 {
+            let master_pk = self.get_master_public_key().await?;
+            keys.extend(
+                (1..=receive_index).map(|index| synthetic_public_key_at(&master_pk, index)),
+            );
+        }
+        Ok(keys)
+    }
+
+    /// Synthetic secret keys of every issued receive address, the default one first
+    #[cfg(feature = "peer")]
+    pub(crate) async fn issued_synthetic_secret_keys(&self) -> Result<Vec<SecretKey>, WalletError> {
+        let mut keys = vec![self.get_private_synthetic_key().await?];
+        let receive_index = self.receive_index().await?;
+        if receive_index > 0 {
+            let master_sk = self.get_master_secret_key().await?;
+            keys.extend(
+                (1..=receive_index).map(|index| synthetic_secret_key_at(&master_sk, index)),
+            );
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(feature = "peer")]
+impl Wallet {
+    /// Find the derivation indexes with on-chain history, e.g. after importing a seed used
+    /// by another wallet
+    ///
+    /// Walks indexes from 0 until [`WalletConfig::address_gap_limit`] consecutive ones
+    /// have neither received coins nor been hinted CATs. The used indexes are recorded in
+    /// the keyring metadata and the receive index is raised to the highest of them, so
+    /// balance and coin queries cover every address that holds funds.
+    ///
+    /// [`WalletConfig::address_gap_limit`]: crate::WalletConfig::address_gap_limit
+    pub async fn scan_addresses(&self, peer: &Peer) -> Result<AddressScan, WalletError> {
+        self.require_key_tree()?;
+        let master_pk = self.get_master_public_key().await?;
+        let gap_limit = self.get_config().address_gap_limit();
+
+        let mut scan = AddressScan::default();
+        while let Some(window) = scan.next_window(gap_limit) {
+            for index in window {
+                let puzzle_hash = puzzle_hash_at_index(&master_pk, index);
+                if !fetch_puzzle_history(peer, puzzle_hash).await?.is_empty() {
+                    scan.used_indexes.push(index);
+                }
+                scan.scanned = index + 1;
+            }
+        }
+
+        self.record_address_scan(&scan).await?;
+        Ok(scan)
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_new_addresses_are_successive_and_persisted() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("shop", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("shop".to_string()), false, &config)
+            .await
+            .unwrap();
+        assert_eq!(wallet.receive_index().await.unwrap(), 0);
+        assert_eq!(
+            wallet.issued_puzzle_hashes().await.unwrap(),
+            vec![wallet.get_owner_puzzle_hash().await.unwrap()]
+        );
+
+        let first = wallet.get_new_address().await.unwrap();
+        let second = wallet.get_new_address().await.unwrap();
+        assert_eq!((first.index, second.index), (1, 2));
+        assert_ne!(first.puzzle_hash, second.puzzle_hash);
+        assert!(first.address.starts_with("xch1"));
+        assert_eq!(
+            first.puzzle_hash,
+            hex::encode(wallet.get_puzzle_hash_at(1).await.unwrap())
+        );
+
+        let reloaded = Wallet::load_with_config(Some("shop".to_string()), false, &config)
+            .await
+            .unwrap();
+        assert_eq!(reloaded.receive_index().await.unwrap(), 2);
+        let issued = reloaded.issued_puzzle_hashes().await.unwrap();
+        assert_eq!(issued.len(), 3);
+        assert_eq!(issued[0], wallet.get_owner_puzzle_hash().await.unwrap());
+        assert_eq!(hex::encode(issued[2]), second.puzzle_hash);
+
+        let listed = reloaded.get_addresses(0..4).await.unwrap();
+        assert_eq!(listed.len(), 4);
+        assert_eq!(listed[1], first);
+        assert_eq!(listed[2], second);
+        assert_eq!(reloaded.receive_index().await.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_scan_windows_extend_past_used_indexes() {
+        let mut scan = AddressScan::default();
+        assert_eq!(scan.next_window(5), Some(0..5));
+
+        scan.scanned = 5;
+        scan.used_indexes = vec![0, 3];
+        assert_eq!(scan.next_window(5), Some(5..9));
+
+        scan.scanned = 9;
+        assert_eq!(scan.next_window(5), None);
+        scan.used_indexes.push(8);
+        assert_eq!(scan.next_window(5), Some(9..14));
+        assert_eq!(AddressScan::default().next_window(0), Some(0..1));
+    }
+
+    #[tokio::test]
+    async fn test_recorded_scan_extends_issued_addresses() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("imported", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("imported".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        let scan = AddressScan {
+            used_indexes: vec![0, 4, 7],
+            scanned: 28,
+        };
+        wallet.record_address_scan(&scan).await.unwrap();
+        assert_eq!(wallet.receive_index().await.unwrap(), 7);
+        assert_eq!(
+            wallet.get_metadata().await.unwrap().addresses.used_indexes,
+            vec![0, 4, 7]
+        );
+        let issued = wallet.issued_puzzle_hashes().await.unwrap();
+        assert_eq!(issued.len(), 8);
+        assert_eq!(issued[7], wallet.get_puzzle_hash_at(7).await.unwrap());
+        assert_eq!(wallet.get_new_address().await.unwrap().index, 8);
+
+        // A later scan never lowers the receive index
+        wallet
+            .record_address_scan(&AddressScan::default())
+            .await
+            .unwrap();
+        assert_eq!(wallet.receive_index().await.unwrap(), 8);
+        assert!(wallet
+            .get_metadata()
+            .await
+            .unwrap()
+            .addresses
+            .used_indexes
+            .is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+//! BLS signature aggregation for multi-input spend bundles and multi-party protocols.
+//!
+//! Chia signs with the augmented BLS scheme, where every message is prefixed with the
+//! signer's public key, so signatures over the same message by different keys aggregate
+//! safely and an aggregate verifies against its `(public key, message)` pairs in any order.
+//!
+//! Parties that need one signature valid for a single combined key, such as an AGG_SIG
+//! condition naming the sum of their keys, each sign with [`sign_for_aggregate_key`]
+//! instead; the aggregate of those verifies against the combined key alone.
+
+use chia::bls::{aggregate, aggregate_verify, sign_raw, PublicKey, SecretKey, Signature};
+use std::borrow::Borrow;
+
+/// Aggregate signatures into one; the identity signature if there are none
+pub fn aggregate_signatures(
+    signatures: impl IntoIterator<Item = impl Borrow<Signature>>,
+) -> Signature {
+    aggregate(signatures)
+}
+
+/// Sum public keys into one combined key
+pub fn aggregate_public_keys(
+    public_keys: impl IntoIterator<Item = impl Borrow<PublicKey>>,
+) -> PublicKey {
+    public_keys
+        .into_iter()
+        .fold(PublicKey::default(), |sum, key| sum + key.borrow())
+}
+
+/// Whether `signature` aggregates a signature of each message by its public key
+pub fn verify_aggregate(
+    signature: &Signature,
+    pairs: impl IntoIterator<Item = (impl Borrow<PublicKey>, impl Borrow<[u8]>)>,
+) -> bool {
+    aggregate_verify(signature, pairs)
+}
+
+/// Sign `message` as one party of `aggregate_public_key`
+///
+/// The message is augmented with the combined key rather than the signer's own, so the
+/// aggregate of every party's signature verifies with `chia::bls::verify` against
+/// `aggregate_public_key`.
+pub fn sign_for_aggregate_key(
+    secret_key: &SecretKey,
+    aggregate_public_key: &PublicKey,
+    message: &[u8],
+) -> Signature {
+    let mut augmented = aggregate_public_key.to_bytes().to_vec();
+    augmented.extend_from_slice(message);
+    sign_raw(secret_key, augmented)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chia::bls::{sign, verify, SecretKey};
+
+    #[test]
+    fn test_aggregate_and_verify() {
+        let keys: Vec<SecretKey> = (1u8..=3).map(|i| SecretKey::from_seed(&[i; 32])).collect();
+        let messages: [&[u8]; 3] = [b"first", b"second", b"first"];
+        let signatures: Vec<Signature> = keys
+            .iter()
+            .zip(messages)
+            .map(|(key, message)| sign(key, message))
+            .collect();
+        let pairs: Vec<(PublicKey, &[u8])> = keys
+            .iter()
+            .zip(messages)
+            .map(|(key, message)| (key.public_key(), message))
+            .collect();
+
+        let signature = aggregate_signatures(&signatures);
+        assert!(verify_aggregate(&signature, pairs.clone()));
+        assert!(verify_aggregate(&signature, pairs.iter().rev().cloned()));
+        assert!(!verify_aggregate(&signature, pairs[..2].to_vec()));
+        assert!(!verify_aggregate(
+            &aggregate_signatures(&signatures[..2]),
+            pairs.clone()
+        ));
+
+        assert_eq!(
+            aggregate_signatures(Vec::<Signature>::new()),
+            Signature::default()
+        );
+        assert!(verify_aggregate(
+            &Signature::default(),
+            Vec::<(PublicKey, &[u8])>::new()
+        ));
+
+        let shared: Vec<Signature> = keys.iter().map(|key| sign(key, b"shared")).collect();
+        let public_key = aggregate_public_keys(keys.iter().map(SecretKey::public_key));
+        assert_eq!(
+            public_key,
+            keys[0].public_key() + &keys[1].public_key() + &keys[2].public_key()
+        );
+        // Augmented messages differ per key, so the summed key does not verify the aggregate
+        assert!(!verify(
+            &aggregate_signatures(&shared),
+            &public_key,
+            b"shared"
+        ));
+        let combined: Vec<Signature> = keys
+            .iter()
+            .map(|key| sign_for_aggregate_key(key, &public_key, b"shared"))
+            .collect();
+        assert!(verify(
+            &aggregate_signatures(&combined),
+            &public_key,
+            b"shared"
+        ));
+        assert!(!verify(
+            &aggregate_signatures(&combined[..2]),
+            &public_key,
+            b"shared"
+        ));
+    }
+}
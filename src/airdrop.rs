@@ -0,0 +1,463 @@
+//! Batched DIG distributions to many recipients, resumable after interruption.
+
+use crate::asset::Asset;
+use crate::builder::TransactionBuilder;
+use crate::coin_proof::SerializedCoin;
+use crate::config::WalletConfig;
+use crate::encoding::{bytes32_from_hex, bytes_from_hex};
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::keys::decode_address;
+use crate::wallet::{Wallet, DEFAULT_FEE_COIN_COST};
+use chia::clvm_utils::tree_hash_atom;
+use chia::traits::Streamable;
+use chia_wallet_sdk::driver::Cat;
+use datalayer_driver::{Bytes32, Coin, Peer, SpendBundle};
+use serde::{Deserialize, Serialize};
+
+/// Default cost ceiling of one airdrop batch, half of the maximum block cost
+pub const DEFAULT_AIRDROP_BATCH_COST: u64 = 5_500_000_000;
+/// Conservative cost of one hinted CAT output
+const ESTIMATED_OUTPUT_COST: u64 = 4_000_000;
+/// Conservative cost of spending one CAT coin
+const ESTIMATED_CAT_SPEND_COST: u64 = 60_000_000;
+/// Cost set aside in each batch for the CAT and fee coin spends funding it
+const RESERVED_SPEND_COST: u64 = 1_000_000_000;
+
+/// How the fee of each airdrop batch is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeePolicy {
+    /// The same fee in mojos for every batch
+    Fixed(u64),
+    /// Mojos per unit of estimated batch cost, including the fee coin spend
+    PerCost(u64),
+}
+
+impl FeePolicy {
+    fn fee_for(&self, estimated_cost: u64) -> u64 {
+        match self {
+            FeePolicy::Fixed(fee) => *fee,
+            FeePolicy::PerCost(mojos_per_cost) => estimated_cost.saturating_mul(*mojos_per_cost),
+        }
+    }
+}
+
+/// Progress of one airdrop batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    /// Not built yet
+    Pending,
+    /// Built and signed; the stored spend bundle is rebroadcast verbatim on resume
+    Signed,
+    /// Accepted by the mempool or already confirmed
+    Broadcast,
+    /// Rejected before its coins were spent; rebuilt on resume
+    Failed,
+}
+
+/// One airdrop payment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AirdropRecipient {
+    pub address: String,
+    /// Puzzle hash as hex
+    pub puzzle_hash: String,
+    pub amount: u64,
+}
+
+/// A cost-bounded group of payments sent in one spend bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AirdropBatch {
+    pub index: usize,
+    pub recipients: Vec<AirdropRecipient>,
+    pub status: BatchStatus,
+    /// Spend bundle name as hex, once signed
+    pub tx_id: Option<String>,
+    pub fee: u64,
+    pub spent_coins: Vec<SerializedCoin>,
+    /// Serialized spend bundle as hex, once signed
+    pub spend_bundle: Option<String>,
+    /// Last error hit while sending this batch
+    pub error: Option<String>,
+}
+
+impl AirdropBatch {
+    /// DIG mojos paid out by this batch
+    pub fn total(&self) -> u64 {
+        self.recipients
+            .iter()
+            .map(|recipient| recipient.amount)
+            .sum()
+    }
+
+    fn decode_spend_bundle(&self) -> Result<SpendBundle, WalletError> {
+        let encoded = self.spend_bundle.as_deref().ok_or_else(|| {
+            WalletError::SerializationError(format!("Batch {} has no spend bundle", self.index))
+        })?;
+        SpendBundle::from_bytes(&bytes_from_hex(encoded)?).map_err(|e| {
+            WalletError::SerializationError(format!("Failed to deserialize spend bundle: {}", e))
+        })
+    }
+}
+
+/// Persisted plan and progress of one airdrop
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Airdrop {
+    /// Content hash of the recipient list as hex; the same list always resumes the same airdrop
+    pub id: String,
+    pub created_at: u64,
+    pub batches: Vec<AirdropBatch>,
+}
+
+impl Airdrop {
+    /// Validate recipients and split them into batches that stay under `max_batch_cost`
+    pub fn plan(
+        recipients: &[(String, u64)],
+        max_batch_cost: u64,
+        created_at: u64,
+    ) -> Result<Self, WalletError> {
+        if recipients.is_empty() {
+            return Err(WalletError::InvalidConfig(
+                "Airdrop has no recipients".to_string(),
+            ));
+        }
+
+        let outputs_per_batch =
+            (max_batch_cost.saturating_sub(RESERVED_SPEND_COST) / ESTIMATED_OUTPUT_COST) as usize;
+        if outputs_per_batch == 0 {
+            return Err(WalletError::InvalidConfig(format!(
+                "Batch cost limit {} leaves no room for outputs",
+                max_batch_cost
+            )));
+        }
+
+        let mut parsed = Vec::with_capacity(recipients.len());
+        let mut preimage = Vec::with_capacity(recipients.len() * 40);
+        for (address, amount) in recipients {
+            if *amount == 0 {
+                return Err(WalletError::InvalidConfig(format!(
+                    "Airdrop amount for {} is zero",
+                    address
+                )));
+            }
+            let puzzle_hash = decode_address(address)?;
+            preimage.extend_from_slice(puzzle_hash.as_ref());
+            preimage.extend_from_slice(&amount.to_be_bytes());
+            parsed.push(AirdropRecipient {
+                address: address.clone(),
+                puzzle_hash: hex::encode(puzzle_hash),
+                amount: *amount,
+            });
+        }
+
+        let batches = parsed
+            .chunks(outputs_per_batch)
+            .enumerate()
+            .map(|(index, chunk)| AirdropBatch {
+                index,
+                recipients: chunk.to_vec(),
+                status: BatchStatus::Pending,
+                tx_id: None,
+                fee: 0,
+                spent_coins: Vec::new(),
+                spend_bundle: None,
+                error: None,
+            })
+            .collect();
+
+        Ok(Self {
+            id: hex::encode(tree_hash_atom(&preimage).to_bytes()),
+            created_at,
+            batches,
+        })
+    }
+
+    /// DIG mojos paid out across all batches
+    pub fn total_amount(&self) -> u64 {
+        self.batches.iter().map(AirdropBatch::total).sum()
+    }
+
+    /// Whether every batch has been broadcast
+    pub fn is_complete(&self) -> bool {
+        self.batches
+            .iter()
+            .all(|batch| batch.status == BatchStatus::Broadcast)
+    }
+
+    fn unsigned_batches(&self) -> impl Iterator<Item = &AirdropBatch> {
+        self.batches
+            .iter()
+            .filter(|batch| matches!(batch.status, BatchStatus::Pending | BatchStatus::Failed))
+    }
+
+    /// Coins consumed by signed or broadcast batches, which later batches must not reuse
+    fn coins_in_flight(&self) -> Result<Vec<Coin>, WalletError> {
+        self.batches
+            .iter()
+            .filter(|batch| matches!(batch.status, BatchStatus::Signed | BatchStatus::Broadcast))
+            .flat_map(|batch| batch.spent_coins.iter())
+            .map(SerializedCoin::to_coin)
+            .collect()
+    }
+}
+
+fn airdrop_cache(
+    wallet_name: &str,
+    config: &WalletConfig,
+) -> Result<FileCache<Airdrop>, WalletError> {
+    FileCache::from_config(&format!("airdrops/{}", wallet_name), config)
+}
+
+impl Wallet {
+    /// A persisted airdrop of this wallet by id
+    pub fn get_airdrop(&self, airdrop_id: &str) -> Result<Option<Airdrop>, WalletError> {
+        airdrop_cache(&self.cache_name(), self.get_config())?.get(airdrop_id)
+    }
+
+    /// Pay DIG to many `(address, amount)` recipients in cost-bounded batches
+    ///
+    /// Progress is persisted after every step; calling again with the same recipient
+    /// list resumes the airdrop, skipping batches that were already broadcast.
+    pub async fn airdrop_dig(
+        &self,
+        peer: &Peer,
+        recipients: Vec<(String, u64)>,
+        fee_policy: FeePolicy,
+    ) -> Result<Airdrop, WalletError> {
+        self.airdrop_dig_with_max_cost(peer, recipients, fee_policy, DEFAULT_AIRDROP_BATCH_COST)
+            .await
+    }
+
+    /// [`Wallet::airdrop_dig`] with an explicit batch cost ceiling
+    ///
+    /// The ceiling only shapes new airdrops; a resumed airdrop keeps its original batches.
+    pub async fn airdrop_dig_with_max_cost(
+        &self,
+        peer: &Peer,
+        recipients: Vec<(String, u64)>,
+        fee_policy: FeePolicy,
+        max_batch_cost: u64,
+    ) -> Result<Airdrop, WalletError> {
+        let chain = self.chain_profile().await?;
+        for (address, _) in &recipients {
+            chain.decode_address(address)?;
+        }
+        let config = self.get_config();
+        let planned = Airdrop::plan(&recipients, max_batch_cost, config.unix_timestamp())?;
+        let cache = airdrop_cache(&self.cache_name(), config)?;
+        let mut airdrop = cache.get(&planned.id)?.unwrap_or(planned);
+
+        self.check_airdrop_balance(peer, &airdrop, fee_policy)
+            .await?;
+        cache.set(&airdrop.id, &airdrop).await?;
+
+        for index in 0..airdrop.batches.len() {
+            let resumed = airdrop.batches[index].status == BatchStatus::Signed;
+            let mut result = Ok(());
+            if matches!(
+                airdrop.batches[index].status,
+                BatchStatus::Pending | BatchStatus::Failed
+            ) {
+                let omit_coins = airdrop.coins_in_flight()?;
+                result = self
+                    .sign_airdrop_batch(peer, &mut airdrop.batches[index], omit_coins, fee_policy)
+                    .await;
+                // The signed bundle must be on disk before it can reach the mempool
+                cache.set(&airdrop.id, &airdrop).await?;
+            }
+            if result.is_ok() && airdrop.batches[index].status == BatchStatus::Signed {
+                result = self
+                    .broadcast_airdrop_batch(peer, &mut airdrop.batches[index], resumed)
+                    .await;
+            }
+
+            let batch = &mut airdrop.batches[index];
+            if let Err(e) = result {
+                if batch.status == BatchStatus::Pending {
+                    batch.status = BatchStatus::Failed;
+                }
+                batch.error = Some(e.to_string());
+                cache.set(&airdrop.id, &airdrop).await?;
+                return Err(e);
+            }
+            cache.set(&airdrop.id, &airdrop).await?;
+        }
+
+        Ok(airdrop)
+    }
+
+    /// Fail early if the unsent part of an airdrop exceeds the wallet's balances
+    async fn check_airdrop_balance(
+        &self,
+        peer: &Peer,
+        airdrop: &Airdrop,
+        fee_policy: FeePolicy,
+    ) -> Result<(), WalletError> {
+        let unsigned: Vec<&AirdropBatch> = airdrop.unsigned_batches().collect();
+        if unsigned.is_empty() {
+            return Ok(());
+        }
+
+        let required: u64 = unsigned.iter().map(|batch| batch.total()).sum();
+        let available = self.get_dig_balance(peer, false).await?;
+        if required > available {
+            return Err(WalletError::InsufficientBalance {
+                asset: Asset::Dig,
+                required,
+                available,
+            });
+        }
+
+        if let FeePolicy::Fixed(fee) = fee_policy {
+            let required = fee.saturating_mul(unsigned.len() as u64);
+            let available = self.get_xch_balance(peer).await?;
+            if required > available {
+                return Err(WalletError::InsufficientBalance {
+                    asset: Asset::Xch,
+                    required,
+                    available,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Select coins for a batch, build its hinted CAT outputs and sign it
+    async fn sign_airdrop_batch(
+        &self,
+        peer: &Peer,
+        batch: &mut AirdropBatch,
+        omit_coins: Vec<Coin>,
+        fee_policy: FeePolicy,
+    ) -> Result<(), WalletError> {
+        let outputs = batch
+            .recipients
+            .iter()
+            .map(|recipient| Ok((bytes32_from_hex(&recipient.puzzle_hash)?, recipient.amount)))
+            .collect::<Result<Vec<(Bytes32, u64)>, WalletError>>()?;
+
+        let dig_coins = self
+            .select_unspent_dig_coins(peer, batch.total(), omit_coins.clone(), false)
+            .await?;
+        let cats: Vec<Cat> = dig_coins.iter().map(|dig_coin| dig_coin.cat()).collect();
+
+        let estimated_cost = cats.len() as u64 * ESTIMATED_CAT_SPEND_COST
+            + outputs.len() as u64 * ESTIMATED_OUTPUT_COST
+            + DEFAULT_FEE_COIN_COST;
+        let fee = fee_policy.fee_for(estimated_cost);
+        Self::check_fee_floor(peer, fee, self.get_config()).await?;
+
+        let mut builder = TransactionBuilder::new()
+            .add_cats(cats)
+            .with_fee(fee)
+            .bind_fee_spend(false);
+        for (puzzle_hash, amount) in outputs {
+            builder = builder.pay_cat(puzzle_hash, amount);
+        }
+        if fee > 0 {
+            builder = builder.add_coins(self.select_unspent_coins(peer, 0, fee, omit_coins).await?);
+        }
+        let unsigned = self.build_transaction(builder).await?;
+        let spent_coins = unsigned.spent_coins;
+        let spend_bundle = self.sign_spend_bundle(unsigned.coin_spends).await?;
+        let encoded = spend_bundle.to_bytes().map_err(|e| {
+            WalletError::SerializationError(format!("Failed to serialize spend bundle: {}", e))
+        })?;
+
+        batch.tx_id = Some(hex::encode(spend_bundle.name()));
+        batch.fee = fee;
+        batch.spent_coins = spent_coins.iter().map(SerializedCoin::from).collect();
+        batch.spend_bundle = Some(hex::encode(encoded));
+        batch.status = BatchStatus::Signed;
+        batch.error = None;
+        Ok(())
+    }
+
+    /// Broadcast a signed batch
+    ///
+    /// When `resumed`, a rejection with already spent inputs means an interrupted
+    /// earlier run got the batch confirmed, so it counts as broadcast.
+    async fn broadcast_airdrop_batch(
+        &self,
+        peer: &Peer,
+        batch: &mut AirdropBatch,
+        resumed: bool,
+    ) -> Result<(), WalletError> {
+        let spend_bundle = batch.decode_spend_bundle()?;
+        let result = self.broadcast_transaction(peer, &spend_bundle).await;
+
+        let error = match result {
+            Ok(_) => None,
+            Err(e) => match batch.spent_coins.first().filter(|_| resumed) {
+                Some(coin)
+                    if !Self::is_coin_spendable(peer, &coin.to_coin()?.coin_id()).await? =>
+                {
+                    None
+                }
+                _ => Some(e),
+            },
+        };
+
+        match error {
+            None => {
+                batch.status = BatchStatus::Broadcast;
+                batch.error = None;
+                Ok(())
+            }
+            Some(e) => {
+                if matches!(e, WalletError::TransactionFailed(_)) {
+                    batch.status = BatchStatus::Failed;
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::encode_address;
+
+    fn address(byte: u8) -> String {
+        encode_address(Bytes32::new([byte; 32]), "xch").unwrap()
+    }
+
+    #[test]
+    fn test_plan_batches_by_cost() {
+        let recipients: Vec<(String, u64)> = (1..=5).map(|i| (address(i), i as u64 * 10)).collect();
+        let max_cost = RESERVED_SPEND_COST + 2 * ESTIMATED_OUTPUT_COST;
+
+        let airdrop = Airdrop::plan(&recipients, max_cost, 0).unwrap();
+        let sizes: Vec<usize> = airdrop.batches.iter().map(|b| b.recipients.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(airdrop.total_amount(), 150);
+        assert!(!airdrop.is_complete());
+        assert_eq!(
+            airdrop.batches[0].recipients[0].puzzle_hash,
+            hex::encode([1u8; 32])
+        );
+
+        let replanned = Airdrop::plan(&recipients, DEFAULT_AIRDROP_BATCH_COST, 99).unwrap();
+        assert_eq!(replanned.id, airdrop.id);
+        assert_eq!(replanned.batches.len(), 1);
+
+        let mut changed = recipients.clone();
+        changed[4].1 += 1;
+        assert_ne!(Airdrop::plan(&changed, max_cost, 0).unwrap().id, airdrop.id);
+    }
+
+    #[test]
+    fn test_plan_rejects_invalid_recipients() {
+        assert!(Airdrop::plan(&[], DEFAULT_AIRDROP_BATCH_COST, 0).is_err());
+        assert!(Airdrop::plan(&[(address(1), 0)], DEFAULT_AIRDROP_BATCH_COST, 0).is_err());
+        assert!(Airdrop::plan(
+            &[("xch1nope".to_string(), 5)],
+            DEFAULT_AIRDROP_BATCH_COST,
+            0
+        )
+        .is_err());
+        assert!(Airdrop::plan(&[(address(1), 5)], RESERVED_SPEND_COST, 0).is_err());
+    }
+}
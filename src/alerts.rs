@@ -0,0 +1,294 @@
+//! Balance threshold alerts.
+//!
+//! Rules registered with [`Wallet::watch_balance`] are evaluated whenever balances are
+//! checked, by [`Wallet::check_balance_alerts`] or the background syncer started with
+//! [`Wallet::spawn_balance_syncer`]. Alerts are published to every subscriber of
+//! [`Wallet::balance_alerts`] and to every callback registered with
+//! [`Wallet::on_balance_alert`].
+
+use crate::asset::Asset;
+use crate::cancel::CancellationToken;
+use crate::error::WalletError;
+use crate::wallet::Wallet;
+use datalayer_driver::Peer;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Alerts buffered per subscriber before the slowest one starts missing alerts
+const ALERT_CHANNEL_CAPACITY: usize = 64;
+
+/// When a balance alert fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceCondition {
+    /// The balance drops below this many mojos
+    Below(u64),
+    /// The balance rises above this many mojos
+    Above(u64),
+    /// The balance moves at least this many mojos away from where it was when the
+    /// rule last fired (or was first evaluated)
+    ChangedBy(u64),
+}
+
+impl BalanceCondition {
+    /// Whether `balance` triggers the condition, and the reference to keep for the next check
+    ///
+    /// Thresholds fire once when crossed rather than on every check below or above them.
+    fn evaluate(&self, reference: Option<u64>, balance: u64) -> (bool, Option<u64>) {
+        match *self {
+            BalanceCondition::Below(threshold) => {
+                let crossed = balance < threshold && reference.map_or(true, |r| r >= threshold);
+                (crossed, Some(balance))
+            }
+            BalanceCondition::Above(threshold) => {
+                let crossed = balance > threshold && reference.map_or(true, |r| r <= threshold);
+                (crossed, Some(balance))
+            }
+            BalanceCondition::ChangedBy(delta) => match reference {
+                None => (false, Some(balance)),
+                Some(baseline) if balance.abs_diff(baseline) >= delta => (true, Some(balance)),
+                Some(baseline) => (false, Some(baseline)),
+            },
+        }
+    }
+}
+
+/// A triggered balance rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceAlert {
+    /// Id returned by [`Wallet::watch_balance`]
+    pub watch_id: u64,
+    pub asset: Asset,
+    pub condition: BalanceCondition,
+    /// Balance the rule last compared against, if any
+    pub previous_balance: Option<u64>,
+    pub balance: u64,
+}
+
+type AlertCallback = Arc<dyn Fn(&BalanceAlert) + Send + Sync>;
+
+struct BalanceRule {
+    id: u64,
+    asset: Asset,
+    condition: BalanceCondition,
+    reference: Option<u64>,
+}
+
+/// Balance rules and alert subscribers shared by all clones of a wallet
+pub(crate) struct BalanceWatcher {
+    next_id: AtomicU64,
+    rules: Mutex<Vec<BalanceRule>>,
+    callbacks: Mutex<Vec<AlertCallback>>,
+    events: broadcast::Sender<BalanceAlert>,
+}
+
+impl Default for BalanceWatcher {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(ALERT_CHANNEL_CAPACITY);
+        Self {
+            next_id: AtomicU64::new(1),
+            rules: Mutex::new(Vec::new()),
+            callbacks: Mutex::new(Vec::new()),
+            events,
+        }
+    }
+}
+
+impl fmt::Debug for BalanceWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BalanceWatcher")
+            .field("rules", &lock(&self.rules).len())
+            .field("callbacks", &lock(&self.callbacks).len())
+            .finish()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl BalanceWatcher {
+    fn watch(&self, asset: Asset, condition: BalanceCondition) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        lock(&self.rules).push(BalanceRule {
+            id,
+            asset,
+            condition,
+            reference: None,
+        });
+        id
+    }
+
+    fn unwatch(&self, id: u64) -> bool {
+        let mut rules = lock(&self.rules);
+        let before = rules.len();
+        rules.retain(|rule| rule.id != id);
+        rules.len() != before
+    }
+
+    fn watched_assets(&self) -> Vec<Asset> {
+        let mut assets: Vec<Asset> = Vec::new();
+        for rule in lock(&self.rules).iter() {
+            if !assets.contains(&rule.asset) {
+                assets.push(rule.asset);
+            }
+        }
+        assets
+    }
+
+    /// Evaluate every rule of `asset` against a fresh balance and publish what fires
+    fn evaluate(&self, asset: Asset, balance: u64) -> Vec<BalanceAlert> {
+        let alerts: Vec<BalanceAlert> = lock(&self.rules)
+            .iter_mut()
+            .filter(|rule| rule.asset == asset)
+            .filter_map(|rule| {
+                let previous_balance = rule.reference;
+                let (triggered, reference) = rule.condition.evaluate(rule.reference, balance);
+                rule.reference = reference;
+                triggered.then_some(BalanceAlert {
+                    watch_id: rule.id,
+                    asset,
+                    condition: rule.condition,
+                    previous_balance,
+                    balance,
+                })
+            })
+            .collect();
+
+        let callbacks = lock(&self.callbacks).clone();
+        for alert in &alerts {
+            // No subscribers is not an error; callbacks may be the only consumers
+            let _ = self.events.send(alert.clone());
+            for callback in &callbacks {
+                callback(alert);
+            }
+        }
+
+        alerts
+    }
+}
+
+impl Wallet {
+    /// Alert when the balance of `asset` meets `condition`; returns the rule's id
+    pub fn watch_balance(&self, asset: Asset, condition: BalanceCondition) -> u64 {
+        self.balance_watcher().watch(asset, condition)
+    }
+
+    /// Remove a rule registered with [`Wallet::watch_balance`]
+    pub fn unwatch_balance(&self, watch_id: u64) -> bool {
+        self.balance_watcher().unwatch(watch_id)
+    }
+
+    /// Subscribe to balance alerts raised from now on
+    pub fn balance_alerts(&self) -> broadcast::Receiver<BalanceAlert> {
+        self.balance_watcher().events.subscribe()
+    }
+
+    /// Call `callback` for every balance alert raised from now on
+    pub fn on_balance_alert(&self, callback: impl Fn(&BalanceAlert) + Send + Sync + 'static) {
+        lock(&self.balance_watcher().callbacks).push(Arc::new(callback));
+    }
+
+    /// Fetch the balance of every watched asset and evaluate the rules against it
+    pub async fn check_balance_alerts(
+        &self,
+        peer: &Peer,
+    ) -> Result<Vec<BalanceAlert>, WalletError> {
+        let mut alerts = Vec::new();
+
+        for asset in self.balance_watcher().watched_assets() {
+            let balance = match asset {
+                Asset::Xch => self.get_xch_balance(peer).await?,
+                #[cfg(feature = "cat")]
+                Asset::Dig => self.get_dig_balance(peer, false).await?,
+                #[cfg(feature = "cat")]
+                Asset::Cat(asset_id) => self.get_cat_balance(peer, asset_id).await?,
+                #[cfg(not(feature = "cat"))]
+                Asset::Dig | Asset::Cat(_) => continue,
+            };
+            alerts.extend(self.balance_watcher().evaluate(asset, balance));
+        }
+
+        Ok(alerts)
+    }
+
+    /// Check balance alerts every `interval` on the configured runtime until `cancellation` fires
+    ///
+    /// Failed checks are skipped; the next tick tries again.
+    pub fn spawn_balance_syncer(
+        &self,
+        peer: Peer,
+        interval: Duration,
+        cancellation: CancellationToken,
+    ) -> Result<(), WalletError> {
+        let runtime = self.get_config().runtime()?;
+        let wallet = self.clone();
+
+        runtime.spawn(async move {
+            while !cancellation.is_cancelled() {
+                let _ = cancellation.run(wallet.check_balance_alerts(&peer)).await;
+                let _ = cancellation
+                    .run(async {
+                        tokio::time::sleep(interval).await;
+                        Ok(())
+                    })
+                    .await;
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditions_fire_on_crossing() {
+        let below = BalanceCondition::Below(100);
+        assert_eq!(below.evaluate(None, 50), (true, Some(50)));
+        assert_eq!(below.evaluate(Some(50), 40), (false, Some(40)));
+        assert_eq!(below.evaluate(Some(150), 99), (true, Some(99)));
+
+        let above = BalanceCondition::Above(100);
+        assert_eq!(above.evaluate(None, 50), (false, Some(50)));
+        assert_eq!(above.evaluate(Some(50), 101), (true, Some(101)));
+
+        let changed = BalanceCondition::ChangedBy(10);
+        assert_eq!(changed.evaluate(None, 100), (false, Some(100)));
+        assert_eq!(changed.evaluate(Some(100), 95), (false, Some(100)));
+        assert_eq!(changed.evaluate(Some(100), 90), (true, Some(90)));
+    }
+
+    #[tokio::test]
+    async fn test_alerts_reach_subscribers_and_callbacks() {
+        let watcher = Arc::new(BalanceWatcher::default());
+        let low = watcher.watch(Asset::Xch, BalanceCondition::Below(1_000));
+        watcher.watch(Asset::Dig, BalanceCondition::Below(1_000));
+
+        let mut events = watcher.events.subscribe();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        lock(&watcher.callbacks).push(Arc::new(move |alert: &BalanceAlert| {
+            lock(&sink).push(alert.watch_id)
+        }));
+
+        assert!(watcher.evaluate(Asset::Xch, 5_000).is_empty());
+        let alerts = watcher.evaluate(Asset::Xch, 500);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].previous_balance, Some(5_000));
+
+        assert_eq!(events.recv().await.unwrap().watch_id, low);
+        assert_eq!(*lock(&seen), vec![low]);
+
+        assert!(watcher.unwatch(low));
+        assert_eq!(watcher.watched_assets(), vec![Asset::Dig]);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::error::WalletError;
+use chia::protocol::Bytes32;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Assets the wallet can hold and move
+///
+/// Serialized as `xch`, `dig` or `cat:<asset id as hex>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Asset {
+    /// Native XCH
+    Xch,
+    /// The DIG CAT
+    Dig,
+    /// Any other CAT, by asset id (the hash of its TAIL)
+    Cat(Bytes32),
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Asset::Xch => write!(f, "xch"),
+            Asset::Dig => write!(f, "dig"),
+            Asset::Cat(asset_id) => write!(f, "cat:{}", hex::encode(asset_id)),
+        }
+    }
+}
+
+impl FromStr for Asset {
+    type Err = WalletError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xch" => Ok(Asset::Xch),
+            "dig" => Ok(Asset::Dig),
+            _ => {
+                let asset_id = s
+                    .strip_prefix("cat:")
+                    .and_then(|asset_id| hex::decode(asset_id).ok())
+                    .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                    .ok_or_else(|| {
+                        WalletError::SerializationError(format!("Unknown asset: {}", s))
+                    })?;
+                Ok(Asset::Cat(Bytes32::new(asset_id)))
+            }
+        }
+    }
+}
+
+impl From<Asset> for String {
+    fn from(asset: Asset) -> Self {
+        asset.to_string()
+    }
+}
+
+impl TryFrom<String> for Asset {
+    type Error = WalletError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_string_form() {
+        let cat = Asset::Cat(Bytes32::new([0xab; 32]));
+        for asset in [Asset::Xch, Asset::Dig, cat] {
+            let json = serde_json::to_string(&asset).unwrap();
+            assert_eq!(serde_json::from_str::<Asset>(&json).unwrap(), asset);
+        }
+        assert_eq!(serde_json::to_string(&Asset::Xch).unwrap(), "\"xch\"");
+        assert_eq!(cat.to_string(), format!("cat:{}", "ab".repeat(32)));
+        assert!("cat:abcd".parse::<Asset>().is_err());
+        assert!("btc".parse::<Asset>().is_err());
+    }
+}
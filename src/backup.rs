@@ -0,0 +1,254 @@
+//! Password-encrypted backups of every wallet in a keyring.
+//!
+//! A backup is a single JSON file holding the mnemonics, fingerprints and metadata of all
+//! wallets, encrypted with a key derived from the backup password (Argon2id + AES-256-GCM).
+//! It is independent of the keyring format and of any keyring passphrase, so it can be
+//! restored on another machine into a keyring protected differently.
+
+use crate::config::WalletConfig;
+use crate::crypto::{aes_decrypt, aes_encrypt, derive_password_key, KDF_ARGON2ID};
+use crate::error::WalletError;
+use crate::metadata::WalletMetadata;
+use crate::wallet::Wallet;
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Identifies backup files
+const BACKUP_FORMAT: &str = "dig-wallet-backup";
+/// Version of the backup layout
+pub const BACKUP_VERSION: u32 = 1;
+
+/// The encrypted backup file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BackupFile {
+    format: String,
+    version: u32,
+    kdf: String,
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+/// The plaintext inside a backup
+#[derive(Serialize, Deserialize)]
+struct BackupContents {
+    created_at: u64,
+    crate_version: String,
+    wallets: Vec<BackupEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupEntry {
+    name: String,
+    fingerprint: u32,
+    mnemonic: String,
+    #[serde(default)]
+    metadata: WalletMetadata,
+}
+
+impl Drop for BackupEntry {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.mnemonic);
+    }
+}
+
+impl Wallet {
+    /// Write every wallet of the keyring to an encrypted backup at `path`; returns how many
+    pub async fn export_backup(path: &Path, password: &str) -> Result<usize, WalletError> {
+        Self::export_backup_with_config(path, password, &WalletConfig::default()).await
+    }
+
+    /// Write an encrypted backup of the keyring of `config`
+    ///
+    /// Passphrase-protected wallets need the keyring passphrase of `config`. Watch-only
+    /// wallets hold no secret and are not included.
+    pub async fn export_backup_with_config(
+        path: &Path,
+        password: &str,
+        config: &WalletConfig,
+    ) -> Result<usize, WalletError> {
+        let mut wallets = Vec::new();
+        for info in Self::list_wallet_info_with_config(config).await? {
+            if info.watch_only {
+                continue;
+            }
+            let wallet = Self::load_with_config(Some(info.name.clone()), false, config).await?;
+            wallets.push(BackupEntry {
+                fingerprint: wallet.get_fingerprint().await?,
+                mnemonic: wallet.mnemonic_phrase()?.to_string(),
+                name: info.name,
+                metadata: info.metadata,
+            });
+        }
+        let count = wallets.len();
+
+        let contents = BackupContents {
+            created_at: config.unix_timestamp(),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            wallets,
+        };
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(&contents)
+                .map_err(|e| WalletError::SerializationError(e.to_string()))?,
+        );
+
+        let salt = config.random_bytes::<16>()?;
+        let key_bytes = derive_password_key(password, &salt)?;
+        let (ciphertext, nonce) = aes_encrypt(&key_bytes, &plaintext, config)?;
+        let file = BackupFile {
+            format: BACKUP_FORMAT.to_string(),
+            version: BACKUP_VERSION,
+            kdf: KDF_ARGON2ID.to_string(),
+            salt: general_purpose::STANDARD.encode(salt),
+            nonce: general_purpose::STANDARD.encode(nonce),
+            data: general_purpose::STANDARD.encode(ciphertext),
+        };
+        let content = serde_json::to_vec_pretty(&file)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, content)
+            .map_err(|e| WalletError::FileSystemError(format!("Failed to write backup: {}", e)))?;
+        fs::rename(&temp_path, path)
+            .map_err(|e| WalletError::FileSystemError(format!("Failed to write backup: {}", e)))?;
+
+        Ok(count)
+    }
+
+    /// Restore the wallets of a backup written by [`Wallet::export_backup`]
+    ///
+    /// Returns the names of the wallets added; wallets already present with the same
+    /// mnemonic are left alone. Nothing is restored if any name is taken by a different
+    /// wallet.
+    pub async fn import_backup(path: &Path, password: &str) -> Result<Vec<String>, WalletError> {
+        Self::import_backup_with_config(path, password, &WalletConfig::default()).await
+    }
+
+    /// Restore a backup into the keyring of `config`, encrypting entries as it is configured
+    pub async fn import_backup_with_config(
+        path: &Path,
+        password: &str,
+        config: &WalletConfig,
+    ) -> Result<Vec<String>, WalletError> {
+        let content = fs::read(path)
+            .map_err(|e| WalletError::FileSystemError(format!("Failed to read backup: {}", e)))?;
+        let file: BackupFile = serde_json::from_slice(&content)
+            .map_err(|e| WalletError::SerializationError(format!("Invalid backup: {}", e)))?;
+        if file.format != BACKUP_FORMAT || file.version != BACKUP_VERSION {
+            return Err(WalletError::SerializationError(format!(
+                "Unsupported backup {} version {}",
+                file.format, file.version
+            )));
+        }
+        if file.kdf != KDF_ARGON2ID {
+            return Err(WalletError::CryptoError(format!(
+                "Unsupported key derivation function: {}",
+                file.kdf
+            )));
+        }
+
+        let decode = |value: &str, what: &str| {
+            general_purpose::STANDARD
+                .decode(value)
+                .map_err(|e| WalletError::CryptoError(format!("Failed to decode {}: {}", what, e)))
+        };
+        let key_bytes = derive_password_key(password, &decode(&file.salt, "salt")?)?;
+        let plaintext = aes_decrypt(
+            &key_bytes,
+            &decode(&file.nonce, "nonce")?,
+            &decode(&file.data, "ciphertext")?,
+        )
+        .map_err(|_| WalletError::InvalidPassphrase)?;
+        let contents: BackupContents = serde_json::from_slice(&plaintext)
+            .map_err(|e| WalletError::SerializationError(format!("Invalid backup: {}", e)))?;
+
+        let mut missing = Vec::new();
+        for entry in &contents.wallets {
+            let wallet = Wallet::new(Some(entry.mnemonic.clone()), entry.name.clone());
+            if wallet.get_fingerprint().await? != entry.fingerprint {
+                return Err(WalletError::FingerprintError);
+            }
+            match Self::get_wallet_from_keyring(&entry.name, config).await? {
+                Some(existing) if existing.as_str() == entry.mnemonic => {}
+                Some(_) => return Err(WalletError::WalletExists(entry.name.clone())),
+                None => missing.push(entry),
+            }
+        }
+
+        // Restore the backed-up keyring as it was, even if it held a mnemonic twice
+        let import_config = config.clone().with_allow_duplicate_mnemonics(true);
+        let mut restored = Vec::new();
+        for entry in missing {
+            Self::import_wallet_with_config(&entry.name, Some(&entry.mnemonic), &import_config)
+                .await?;
+            Self::update_metadata(&entry.name, config, |metadata| {
+                *metadata = WalletMetadata {
+                    fingerprint: Some(entry.fingerprint),
+                    ..entry.metadata.clone()
+                }
+            })
+            .await?;
+            restored.push(entry.name.clone());
+        }
+        Ok(restored)
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_backup_moves_wallets_between_installations() {
+        let source_home = TempDir::new().unwrap();
+        let target_home = TempDir::new().unwrap();
+        let source =
+            WalletConfig::new().with_environment(SandboxEnvironment::new(source_home.path()));
+        let target = WalletConfig::new()
+            .with_environment(SandboxEnvironment::new(target_home.path()))
+            .with_keyring_passphrase("target passphrase");
+
+        let alpha = Wallet::create_new_wallet_with_config("alpha", &source)
+            .await
+            .unwrap()
+            .into_string();
+        Wallet::create_new_wallet_with_config("beta", &source)
+            .await
+            .unwrap();
+
+        let path = source_home.path().join("wallets.backup");
+        assert_eq!(
+            Wallet::export_backup_with_config(&path, "backup password", &source)
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(!fs::read_to_string(&path).unwrap().contains(&alpha));
+
+        let wrong = Wallet::import_backup_with_config(&path, "guess", &target).await;
+        assert!(matches!(wrong, Err(WalletError::InvalidPassphrase)));
+
+        let restored = Wallet::import_backup_with_config(&path, "backup password", &target)
+            .await
+            .unwrap();
+        assert_eq!(restored, vec!["alpha".to_string(), "beta".to_string()]);
+        let loaded = Wallet::load_with_config(Some("alpha".to_string()), false, &target)
+            .await
+            .unwrap();
+        assert_eq!(loaded.mnemonic_phrase().unwrap(), alpha);
+
+        // Restoring twice is a no-op
+        assert!(
+            Wallet::import_backup_with_config(&path, "backup password", &target)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Reference command-line client built on the dig-wallet library API.
+//!
+//! Build with `cargo run --features examples-cli --bin dig-wallet-cli -- <command>`.
+//! Every command accepts `--json` for machine-readable output.
+
+use dig_wallet::{Asset, SigningMode, Wallet, WalletError};
+use serde_json::{json, Value};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: dig-wallet-cli [--json] <command> [args]
+
+Commands:
+  create <wallet>                       Create a wallet with a new mnemonic
+  import <wallet> <mnemonic words...>   Import a wallet from a mnemonic
+  list                                  List wallets in the keyring
+  balance <wallet> [--env <name>]       Show XCH and DIG balances in mojos
+  send <wallet> <address> <amount>      Send mojos to an address
+       [--asset xch|dig] [--fee <mojos>] [--env <name>]
+  sign <wallet> <nonce>                 Sign a key ownership nonce; `--json` output
+                                        is a `chia rpc wallet verify_signature` request
+
+`--env` names an environment from ~/.dig/peers.toml (default: mainnet).";
+
+/// Parsed command line: positional arguments plus the recognised flags
+struct Args {
+    json: bool,
+    env: String,
+    asset: Asset,
+    fee: u64,
+    positional: Vec<String>,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, WalletError> {
+        let mut parsed = Args {
+            json: false,
+            env: "mainnet".to_string(),
+            asset: Asset::Xch,
+            fee: 0,
+            positional: Vec::new(),
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| {
+                args.next()
+                    .ok_or_else(|| invalid(format!("{} requires a value", flag)))
+            };
+            match arg.as_str() {
+                "--json" => parsed.json = true,
+                "--env" => parsed.env = value("--env")?,
+                "--fee" => parsed.fee = parse_mojos(&value("--fee")?)?,
+                "--asset" => {
+                    parsed.asset = match value("--asset")?.as_str() {
+                        "xch" => Asset::Xch,
+                        "dig" => Asset::Dig,
+                        other => return Err(invalid(format!("Unknown asset {}", other))),
+                    }
+                }
+                flag if flag.starts_with("--") => {
+                    return Err(invalid(format!("Unknown flag {}", flag)))
+                }
+                _ => parsed.positional.push(arg),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// The positional argument at `index`, named `name` in errors
+    fn arg(&self, index: usize, name: &str) -> Result<&str, WalletError> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| invalid(format!("Missing <{}>\n\n{}", name, USAGE)))
+    }
+}
+
+fn invalid(message: String) -> WalletError {
+    WalletError::InvalidConfig(message)
+}
+
+fn parse_mojos(value: &str) -> Result<u64, WalletError> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("Invalid mojo amount {}", value)))
+}
+
+async fn load(wallet_name: &str) -> Result<Wallet, WalletError> {
+    Wallet::load(Some(wallet_name.to_string()), false).await
+}
+
+/// Run a command and return its result as JSON plus a human-readable rendering
+async fn run(args: &Args) -> Result<(Value, String), WalletError> {
+    match args.arg(0, "command")? {
+        "create" => {
+            let name = args.arg(1, "wallet")?;
+            // The CLI prints the words for the user to write down, so it keeps them
+            let mnemonic = Wallet::create_new_wallet(name).await?.into_string();
+            let text = format!("Created wallet {}\nMnemonic: {}", name, mnemonic);
+            Ok((json!({ "wallet": name, "mnemonic": mnemonic }), text))
+        }
+        "import" => {
+            let name = args.arg(1, "wallet")?;
+            let mnemonic = args.positional[2..].join(" ");
+            Wallet::import_wallet(name, Some(mnemonic.as_str()).filter(|m| !m.is_empty())).await?;
+            let address = load(name).await?.get_owner_public_key().await?;
+            let text = format!("Imported wallet {}\nAddress: {}", name, address);
+            Ok((json!({ "wallet": name, "address": address }), text))
+        }
+        "list" => {
+            let wallets = Wallet::list_wallets().await?;
+            let text = wallets.join("\n");
+            Ok((json!({ "wallets": wallets }), text))
+        }
+        "balance" => {
+            let wallet = load(args.arg(1, "wallet")?).await?;
+            let pool = Wallet::connect(&args.env).await?;
+            let xch = wallet.get_xch_balance(pool.peer()).await?;
+            let dig = wallet.get_dig_balance(pool.peer(), false).await?;
+            let text = format!("XCH: {} mojos\nDIG: {} mojos", xch, dig);
+            Ok((json!({ "xch": xch, "dig": dig }), text))
+        }
+        "send" => {
+            let wallet = load(args.arg(1, "wallet")?).await?;
+            let address = args.arg(2, "address")?;
+            let amount = parse_mojos(args.arg(3, "amount")?)?;
+            let destination = Wallet::address_to_puzzle_hash(address)?;
+
+            let pool = Wallet::connect(&args.env).await?;
+            let queued = wallet
+                .queue_transfer(pool.peer(), args.asset, destination, amount, args.fee)
+                .await?;
+            let tx_id = hex::encode(wallet.broadcast_queued(pool.peer(), &queued.tx_id).await?);
+
+            let text = format!(
+                "Sent {} {} mojos to {}\nTransaction: {}",
+                amount, args.asset, address, tx_id
+            );
+            Ok((
+                json!({
+                    "tx_id": tx_id,
+                    "asset": args.asset,
+                    "amount": amount,
+                    "fee": args.fee,
+                    "destination": address,
+                }),
+                text,
+            ))
+        }
+        "sign" => {
+            let wallet = load(args.arg(1, "wallet")?).await?;
+            let nonce = args.arg(2, "nonce")?;
+            let message = format!(
+                "Signing this message to prove ownership of key.\n\nNonce: {}",
+                nonce
+            );
+            let signed = wallet
+                .sign_message_with_mode(&message, SigningMode::Chip0002)
+                .await?;
+            let text = format!(
+                "Signature: {}\nPublic key: {}",
+                signed.signature, signed.pubkey
+            );
+            // The `verify_signature` RPC fields, plus the nonce and the original key name
+            let mut value = serde_json::to_value(&signed)
+                .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+            value["nonce"] = json!(nonce);
+            value["public_key"] = json!(signed.pubkey);
+            Ok((value, text))
+        }
+        "help" | "-h" => Ok((json!({ "usage": USAGE }), USAGE.to_string())),
+        other => Err(invalid(format!("Unknown command {}\n\n{}", other, USAGE))),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(&args).await {
+        Ok((value, text)) => {
+            if args.json {
+                println!("{}", value);
+            } else {
+                println!("{}", text);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            if args.json {
+                println!("{}", json!({ "error": e.to_string() }));
+            } else {
+                eprintln!("{}", e);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,566 @@
+//! Assembly of coin spends from payments, a fee, announcements and arbitrary spends.
+//!
+//! A [`TransactionBuilder`] only describes a transaction: the coins to spend, who gets paid
+//! and what else the spends must say. The wallet turns it into coin spends with the standard
+//! layers of its issued addresses, and optionally signs them. Transfers, CAT sends, airdrops
+//! and sweeps are all assembled this way.
+
+use crate::change::{ChangeDestination, ChangePolicy};
+use crate::encoding::bytes32_from_hex;
+use crate::error::WalletError;
+use crate::transfer::{
+    driver_error, fee_binding_conditions, spend_cats_with_conditions, spend_xch_coins, SpendKeys,
+    UnsignedTransaction,
+};
+use crate::wallet::Wallet;
+use chia::puzzles::Memos;
+use chia_wallet_sdk::driver::{Cat, SpendContext};
+use chia_wallet_sdk::types::Conditions;
+use datalayer_driver::{Bytes, Bytes32, Coin, CoinSpend, Peer, SpendBundle};
+
+/// `amount` mojos paid to `puzzle_hash`, with memos attached to the created coin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payment {
+    pub puzzle_hash: Bytes32,
+    pub amount: u64,
+    pub memos: Vec<Bytes>,
+}
+
+impl Payment {
+    pub fn new(puzzle_hash: Bytes32, amount: u64) -> Self {
+        Self {
+            puzzle_hash,
+            amount,
+            memos: Vec::new(),
+        }
+    }
+
+    /// Attach `memos` to the created coin
+    pub fn with_memos(mut self, memos: Vec<Bytes>) -> Self {
+        self.memos = memos;
+        self
+    }
+
+    /// CREATE_COIN memos: the puzzle hash as a hint followed by the memos
+    ///
+    /// Without memos and unless `hint` is set, the coin gets none.
+    pub(crate) fn create_coin_memos(
+        &self,
+        ctx: &mut SpendContext,
+        hint: bool,
+    ) -> Result<Memos, WalletError> {
+        if !hint && self.memos.is_empty() {
+            return Ok(Memos::None);
+        }
+        let mut memos = vec![Bytes::new(self.puzzle_hash.to_vec())];
+        memos.extend(self.memos.iter().cloned());
+        ctx.memos(&memos).map_err(driver_error)
+    }
+}
+
+/// Description of a transaction, built up before the wallet assembles its coin spends
+///
+/// XCH payments, the fee and any announcements are made by the first XCH coin; CAT
+/// payments by the first CAT coin, which also makes the announcements when there is no XCH
+/// coin. Change of either kind is paid out by the [`ChangePolicy`] of the builder, or of the
+/// wallet's configuration unless set. When both kinds of coin are spent, the XCH and CAT
+/// spends are bound to each other as in [`crate::DigSendOptions::bind_fee_spend`].
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    coins: Vec<Coin>,
+    payments: Vec<Payment>,
+    cats: Vec<Cat>,
+    cat_payments: Vec<Payment>,
+    fee: u64,
+    conditions: Conditions,
+    coin_spends: Vec<CoinSpend>,
+    change_policy: Option<ChangePolicy>,
+    bind_fee_spend: bool,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        Self {
+            coins: Vec::new(),
+            payments: Vec::new(),
+            cats: Vec::new(),
+            cat_payments: Vec::new(),
+            fee: 0,
+            conditions: Conditions::new(),
+            coin_spends: Vec::new(),
+            change_policy: None,
+            bind_fee_spend: true,
+        }
+    }
+}
+
+impl TransactionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spend standard XCH coins of the wallet
+    pub fn add_coins(mut self, coins: impl IntoIterator<Item = Coin>) -> Self {
+        self.coins.extend(coins);
+        self
+    }
+
+    /// Spend CAT coins of the wallet, all of one asset
+    pub fn add_cats(mut self, cats: impl IntoIterator<Item = Cat>) -> Self {
+        self.cats.extend(cats);
+        self
+    }
+
+    /// Pay `amount` mojos of XCH to `puzzle_hash`
+    pub fn pay(mut self, puzzle_hash: Bytes32, amount: u64) -> Self {
+        self.payments.push(Payment::new(puzzle_hash, amount));
+        self
+    }
+
+    /// Pay `amount` mojos of XCH to `puzzle_hash` with `memos` after the puzzle hash hint
+    pub fn pay_with_memos(mut self, puzzle_hash: Bytes32, amount: u64, memos: Vec<Bytes>) -> Self {
+        self.payments
+            .push(Payment::new(puzzle_hash, amount).with_memos(memos));
+        self
+    }
+
+    /// Pay `amount` mojos of the spent CAT to `puzzle_hash`, hinted so the recipient finds it
+    pub fn pay_cat(mut self, puzzle_hash: Bytes32, amount: u64) -> Self {
+        self.cat_payments.push(Payment::new(puzzle_hash, amount));
+        self
+    }
+
+    /// Pay `amount` mojos of the spent CAT to `puzzle_hash` with `memos` after the hint
+    pub fn pay_cat_with_memos(
+        mut self,
+        puzzle_hash: Bytes32,
+        amount: u64,
+        memos: Vec<Bytes>,
+    ) -> Self {
+        self.cat_payments
+            .push(Payment::new(puzzle_hash, amount).with_memos(memos));
+        self
+    }
+
+    /// Leave `fee` mojos of XCH to the farmer
+    pub fn with_fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Pay change out by `policy` instead of the wallet's configured [`ChangePolicy`]
+    pub fn with_change_policy(mut self, policy: ChangePolicy) -> Self {
+        self.change_policy = Some(policy);
+        self
+    }
+
+    /// Send change to `puzzle_hash`, as `with_change_policy(ChangePolicy::address(..))`
+    pub fn with_change_puzzle_hash(self, puzzle_hash: Bytes32) -> Self {
+        self.with_change_policy(ChangePolicy::address(puzzle_hash))
+    }
+
+    /// Whether XCH and CAT spends announce and assert each other (the default)
+    ///
+    /// Unbound, only the XCH spend asserts the CAT spend.
+    pub fn bind_fee_spend(mut self, bind: bool) -> Self {
+        self.bind_fee_spend = bind;
+        self
+    }
+
+    /// Create a coin announcement of `message`
+    pub fn announce(mut self, message: impl Into<Bytes>) -> Self {
+        self.conditions = self.conditions.create_coin_announcement(message.into());
+        self
+    }
+
+    /// Require the coin announcement `announcement_id` in the same block
+    pub fn assert_coin_announcement(mut self, announcement_id: Bytes32) -> Self {
+        self.conditions = self.conditions.assert_coin_announcement(announcement_id);
+        self
+    }
+
+    /// Require the puzzle announcement `announcement_id` in the same block
+    pub fn assert_puzzle_announcement(mut self, announcement_id: Bytes32) -> Self {
+        self.conditions = self.conditions.assert_puzzle_announcement(announcement_id);
+        self
+    }
+
+    /// Include a coin spend built elsewhere, such as an offer's settlement or a singleton
+    ///
+    /// The wallet adds it as it is and signs it only if it needs one of the wallet's keys.
+    pub fn add_coin_spend(mut self, coin_spend: CoinSpend) -> Self {
+        self.coin_spends.push(coin_spend);
+        self
+    }
+
+    /// XCH the spent coins must cover: the payments plus the fee
+    pub fn xch_required(&self) -> Result<u64, WalletError> {
+        self.payments
+            .iter()
+            .try_fold(self.fee, |sum, payment| sum.checked_add(payment.amount))
+            .ok_or_else(|| WalletError::InvalidAmount("XCH payments overflow u64".to_string()))
+    }
+
+    /// Ids of the XCH and CAT coins the builder spends, not counting added coin spends
+    #[cfg(feature = "offers")]
+    pub(crate) fn input_coin_ids(&self) -> Vec<Bytes32> {
+        self.coins
+            .iter()
+            .map(Coin::coin_id)
+            .chain(self.cats.iter().map(|cat| cat.coin.coin_id()))
+            .collect()
+    }
+
+    /// Puzzle hash of the first spent XCH coin, or else of the first CAT's inner puzzle
+    fn first_spent_puzzle_hash(&self) -> Option<Bytes32> {
+        self.coins
+            .first()
+            .map(|coin| coin.puzzle_hash)
+            .or_else(|| self.cats.first().map(|cat| cat.info.p2_puzzle_hash))
+    }
+
+    /// Whether building creates a change coin when XCH change below `dust_threshold` is
+    /// left to the fee
+    fn makes_change(&self, dust_threshold: u64) -> Result<bool, WalletError> {
+        let xch_total: u64 = self.coins.iter().map(|coin| coin.amount).sum();
+        let xch_change = xch_total.saturating_sub(self.xch_required()?);
+        let cat_total: u64 = self.cats.iter().map(|cat| cat.coin.amount).sum();
+        let cat_paid: u64 = self.cat_payments.iter().map(|payment| payment.amount).sum();
+        Ok((xch_change > 0 && xch_change >= dust_threshold) || cat_total > cat_paid)
+    }
+
+    /// Assemble the coin spends with the wallet's standard layers
+    pub(crate) fn build(
+        self,
+        keys: &SpendKeys,
+        change_puzzle_hash: Bytes32,
+        dust_threshold: u64,
+    ) -> Result<UnsignedTransaction, WalletError> {
+        let xch_paid = self.xch_required()? - self.fee;
+        if xch_paid + self.fee > 0 && self.coins.is_empty() {
+            return Err(WalletError::NoUnspentCoins);
+        }
+        if !self.cat_payments.is_empty() && self.cats.is_empty() {
+            return Err(WalletError::NoUnspentCoins);
+        }
+        if !self.conditions.is_empty() && self.coins.is_empty() && self.cats.is_empty() {
+            return Err(WalletError::TransactionFailed(
+                "Announcements need a coin of the wallet to make them".to_string(),
+            ));
+        }
+
+        let (mut cat_conditions, mut xch_conditions) = match (self.cats.first(), self.coins.first())
+        {
+            (Some(cat), Some(coin)) if self.bind_fee_spend => {
+                fee_binding_conditions(cat.coin.coin_id(), coin.coin_id())
+            }
+            (Some(cat), Some(_)) => (
+                Conditions::new(),
+                Conditions::new().assert_concurrent_spend(cat.coin.coin_id()),
+            ),
+            _ => (Conditions::new(), Conditions::new()),
+        };
+        if self.coins.is_empty() {
+            cat_conditions = cat_conditions.extend(self.conditions);
+        } else {
+            xch_conditions = xch_conditions.extend(self.conditions);
+        }
+
+        let mut ctx = SpendContext::new();
+        let mut spent_coins = Vec::new();
+
+        if !self.cats.is_empty() {
+            spend_cats_with_conditions(
+                &mut ctx,
+                keys,
+                &self.cats,
+                &self.cat_payments,
+                change_puzzle_hash,
+                cat_conditions,
+            )?;
+            spent_coins.extend(self.cats.iter().map(|cat| cat.coin));
+        }
+
+        if !self.coins.is_empty() {
+            for payment in &self.payments {
+                let memos = payment.create_coin_memos(&mut ctx, false)?;
+                xch_conditions =
+                    xch_conditions.create_coin(payment.puzzle_hash, payment.amount, memos);
+            }
+            spend_xch_coins(
+                &mut ctx,
+                keys,
+                &self.coins,
+                xch_conditions,
+                xch_paid,
+                self.fee,
+                change_puzzle_hash,
+                dust_threshold,
+            )?;
+            spent_coins.extend(self.coins.iter().copied());
+        }
+
+        for coin_spend in self.coin_spends {
+            spent_coins.push(coin_spend.coin);
+            ctx.insert(coin_spend);
+        }
+
+        Ok(UnsignedTransaction {
+            coin_spends: ctx.take(),
+            spent_coins,
+            cats: self.cats,
+            change_puzzle_hash,
+        })
+    }
+}
+
+impl Wallet {
+    /// Add XCH coins of the wallet until the builder's payments and fee are covered
+    ///
+    /// Coins already added count towards the total, and `omit_coins` are never picked.
+    pub async fn fund_transaction(
+        &self,
+        peer: &Peer,
+        builder: TransactionBuilder,
+        omit_coins: Vec<Coin>,
+    ) -> Result<TransactionBuilder, WalletError> {
+        let required = builder.xch_required()?;
+        let added: u64 = builder.coins.iter().map(|coin| coin.amount).sum();
+        if added >= required {
+            return Ok(builder);
+        }
+
+        let mut omit_coins = omit_coins;
+        omit_coins.extend(builder.coins.iter().copied());
+        let coins = self
+            .select_unspent_coins(peer, required - added, 0, omit_coins)
+            .await?;
+        Ok(builder.add_coins(coins))
+    }
+
+    /// Assemble the coin spends of `builder` without signing them
+    ///
+    /// Only needs the wallet's public keys, so it also works for watch-only and
+    /// signer-backed wallets.
+    pub async fn build_unsigned_transaction(
+        &self,
+        builder: TransactionBuilder,
+    ) -> Result<Vec<CoinSpend>, WalletError> {
+        Ok(self.build_transaction(builder).await?.coin_spends)
+    }
+
+    /// Assemble the coin spends of `builder` and sign them into a spend bundle
+    pub async fn sign_transaction(
+        &self,
+        builder: TransactionBuilder,
+    ) -> Result<SpendBundle, WalletError> {
+        let unsigned = self.build_transaction(builder).await?;
+        self.sign_spend_bundle(unsigned.coin_spends).await
+    }
+
+    pub(crate) async fn build_transaction(
+        &self,
+        builder: TransactionBuilder,
+    ) -> Result<UnsignedTransaction, WalletError> {
+        let keys = self.spend_keys().await?;
+        let policy = builder
+            .change_policy
+            .unwrap_or_else(|| self.get_config().change_policy());
+        let change_puzzle_hash = match (policy.destination(), builder.first_spent_puzzle_hash()) {
+            (ChangeDestination::Address(puzzle_hash), _) => puzzle_hash,
+            (ChangeDestination::SamePuzzleHash, Some(puzzle_hash)) => puzzle_hash,
+            // An address is only issued when there is change to send to it
+            (ChangeDestination::NextAddress, _)
+                if builder.makes_change(policy.dust_threshold())? =>
+            {
+                bytes32_from_hex(&self.get_new_address().await?.puzzle_hash)?
+            }
+            _ => self.get_owner_puzzle_hash().await?,
+        };
+        builder.build(&keys, change_puzzle_hash, policy.dust_threshold())
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::coin_records::extract_create_coin_memos;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use crate::transfer::effective_fee;
+    use tempfile::TempDir;
+
+    async fn test_wallet(temp_dir: &TempDir) -> Wallet {
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("builder", &config)
+            .await
+            .unwrap();
+        Wallet::load_with_config(Some("builder".to_string()), false, &config)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_builder_pays_change_and_fee() {
+        let temp_dir = TempDir::new().unwrap();
+        let wallet = test_wallet(&temp_dir).await;
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+        let coin = Coin::new(Bytes32::new([1; 32]), owner, 1_000);
+        let other = Coin::new(Bytes32::new([2; 32]), owner, 500);
+        let recipient = Bytes32::new([3; 32]);
+
+        let builder = TransactionBuilder::new()
+            .add_coins([coin, other])
+            .pay(recipient, 700)
+            .with_fee(50)
+            .announce(b"hello".to_vec());
+        assert_eq!(builder.xch_required().unwrap(), 750);
+
+        let coin_spends = wallet.build_unsigned_transaction(builder).await.unwrap();
+        assert_eq!(coin_spends.len(), 2);
+        assert_eq!(effective_fee(&coin_spends).unwrap(), 50);
+
+        let bundle = wallet
+            .sign_transaction(
+                TransactionBuilder::new()
+                    .add_coins([coin])
+                    .pay(recipient, 1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(bundle.coin_spends.len(), 1);
+        assert_eq!(effective_fee(&bundle.coin_spends).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_builder_rejects_unfunded_and_foreign_coins() {
+        let temp_dir = TempDir::new().unwrap();
+        let wallet = test_wallet(&temp_dir).await;
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+        let recipient = Bytes32::new([3; 32]);
+
+        let unfunded = TransactionBuilder::new().pay(recipient, 1);
+        assert!(matches!(
+            wallet.build_unsigned_transaction(unfunded).await,
+            Err(WalletError::NoUnspentCoins)
+        ));
+
+        let short = TransactionBuilder::new()
+            .add_coins([Coin::new(Bytes32::new([1; 32]), owner, 10)])
+            .pay(recipient, 20);
+        assert!(matches!(
+            wallet.build_unsigned_transaction(short).await,
+            Err(WalletError::NoUnspentCoins)
+        ));
+
+        let foreign = TransactionBuilder::new()
+            .add_coins([Coin::new(Bytes32::new([1; 32]), recipient, 10)])
+            .pay(recipient, 5);
+        assert!(matches!(
+            wallet.build_unsigned_transaction(foreign).await,
+            Err(WalletError::TransactionFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_builder_passes_arbitrary_spends_through() {
+        let temp_dir = TempDir::new().unwrap();
+        let wallet = test_wallet(&temp_dir).await;
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+
+        let built = wallet
+            .build_unsigned_transaction(
+                TransactionBuilder::new()
+                    .add_coins([Coin::new(Bytes32::new([1; 32]), owner, 10)])
+                    .pay(owner, 10),
+            )
+            .await
+            .unwrap();
+        let external = built[0].clone();
+
+        let coin_spends = wallet
+            .build_unsigned_transaction(TransactionBuilder::new().add_coin_spend(external.clone()))
+            .await
+            .unwrap();
+        assert_eq!(coin_spends, vec![external]);
+    }
+
+    #[tokio::test]
+    async fn test_builder_attaches_memos() {
+        let temp_dir = TempDir::new().unwrap();
+        let wallet = test_wallet(&temp_dir).await;
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+        let coin = Coin::new(Bytes32::new([1; 32]), owner, 100);
+        let first = Bytes32::new([3; 32]);
+        let second = Bytes32::new([4; 32]);
+        let reference = Bytes::new(b"payout-42".to_vec());
+
+        let coin_spends = wallet
+            .build_unsigned_transaction(
+                TransactionBuilder::new()
+                    .add_coins([coin])
+                    .pay_with_memos(first, 30, vec![reference.clone()])
+                    .pay(second, 20),
+            )
+            .await
+            .unwrap();
+        let memos = |puzzle_hash, amount| {
+            extract_create_coin_memos(
+                &coin_spends[0],
+                &Coin::new(coin.coin_id(), puzzle_hash, amount),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            memos(first, 30),
+            vec![Bytes::new(first.to_vec()), reference]
+        );
+        assert!(memos(second, 20).is_empty());
+        assert!(memos(owner, 50).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_builder_applies_change_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let wallet = test_wallet(&temp_dir).await;
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+        let coin = Coin::new(Bytes32::new([1; 32]), owner, 1_000);
+        let recipient = Bytes32::new([3; 32]);
+        let creates = |coin_spends: &[CoinSpend], puzzle_hash, amount| {
+            extract_create_coin_memos(
+                &coin_spends[0],
+                &Coin::new(coin.coin_id(), puzzle_hash, amount),
+            )
+            .is_ok()
+        };
+
+        let dust = wallet
+            .build_unsigned_transaction(
+                TransactionBuilder::new()
+                    .add_coins([coin])
+                    .pay(recipient, 990)
+                    .with_fee(5)
+                    .with_change_policy(ChangePolicy::address(recipient).burn_dust(10)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(effective_fee(&dust).unwrap(), 10);
+        assert!(!creates(&dust, recipient, 5));
+
+        // Without change, no address is issued for it
+        let exact = TransactionBuilder::new()
+            .add_coins([coin])
+            .pay(recipient, 1_000)
+            .with_change_policy(ChangePolicy::next_address());
+        wallet.build_unsigned_transaction(exact).await.unwrap();
+        assert_eq!(wallet.receive_index().await.unwrap(), 0);
+        let next = TransactionBuilder::new()
+            .add_coins([coin])
+            .pay(recipient, 600)
+            .with_change_policy(ChangePolicy::next_address());
+        let coin_spends = wallet.build_unsigned_transaction(next).await.unwrap();
+        let issued = wallet.get_puzzle_hash_at(1).await.unwrap();
+        assert!(creates(&coin_spends, issued, 400));
+        assert_eq!(wallet.receive_index().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+//! Cooperative cancellation of long-running sync and selection calls.
+
+use crate::context::within_current;
+use crate::error::WalletError;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Signal shared between a caller and the operations it may want to abort
+///
+/// Clones observe the same signal. Operations given a token stop at the next
+/// peer request or loop iteration once [`cancel`](Self::cancel) is called and
+/// return [`WalletError::Cancelled`] without writing partial results to caches.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationState>,
+}
+
+#[derive(Debug, Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel every operation observing this token
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fail with [`WalletError::Cancelled`] if the token has been cancelled
+    pub fn check(&self) -> Result<(), WalletError> {
+        if self.is_cancelled() {
+            Err(WalletError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Resolve once the token is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking so a concurrent `cancel` cannot be missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Run `future` unless the token is cancelled first, in which case it is dropped
+    ///
+    /// The deadline and cancellation of the current
+    /// [`OpContext`](crate::context::OpContext) apply as well.
+    pub async fn run<T>(
+        &self,
+        future: impl Future<Output = Result<T, WalletError>>,
+    ) -> Result<T, WalletError> {
+        within_current(self.run_unscoped(future)).await
+    }
+
+    /// [`CancellationToken::run`] ignoring the current [`OpContext`](crate::context::OpContext)
+    pub(crate) async fn run_unscoped<T>(
+        &self,
+        future: impl Future<Output = Result<T, WalletError>>,
+    ) -> Result<T, WalletError> {
+        self.check()?;
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err(WalletError::Cancelled),
+            result = future => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_aborts_pending_future() {
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+
+        let hung = token.run(async {
+            std::future::pending::<()>().await;
+            Ok(())
+        });
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            canceller.cancel();
+        };
+
+        let (result, _) = tokio::join!(hung, cancel);
+        assert!(matches!(result, Err(WalletError::Cancelled)));
+        assert!(token.check().is_err());
+        assert!(matches!(
+            token.run(async { Ok(1) }).await,
+            Err(WalletError::Cancelled)
+        ));
+    }
+}
@@ -0,0 +1,398 @@
+//! Unsigned CAT spends for external CAT-aware signers.
+//!
+//! [`Wallet::build_unsigned_dig_send`] assembles a DIG transfer without signing it. The
+//! resulting [`UnsignedCatSpend`] lists every AGG_SIG_ME signature the bundle needs,
+//! together with the CAT context a signing service (an HSM, say) should check before
+//! signing: the asset id, each CAT coin's lineage proof and inner puzzle hash, and the
+//! CAT outputs the spend creates. Its JSON form (schema `dig-wallet/unsigned-cat-spend`,
+//! version 1) is:
+//!
+//! ```text
+//! {
+//!   "schema": "dig-wallet/unsigned-cat-spend",
+//!   "version": 1,
+//!   "asset_id": hex,
+//!   "agg_sig_me_data": hex,            // genesis challenge the signatures commit to
+//!   "fee": mojos,
+//!   "cat_coins": [{ "coin": coin, "inner_puzzle_hash": hex,
+//!                   "lineage": { "parent_parent_coin_info": hex,
+//!                                "parent_inner_puzzle_hash": hex,
+//!                                "parent_amount": mojos } | null }],
+//!   "cat_outputs": [{ "puzzle_hash": hex, "amount": mojos, "change": bool }],
+//!   "coin_spends": [{ "coin": coin, "puzzle_reveal": hex, "solution": hex }],
+//!   "signing_targets": [{ "coin_id": hex, "public_key": hex,
+//!                         "message": hex, "signed_message": hex }]
+//! }
+//! ```
+//!
+//! where `coin` is `{ "parent_coin_info": hex, "puzzle_hash": hex, "amount": mojos }`.
+//! `message` is the AGG_SIG_ME message of the inner puzzle, and `signed_message` is
+//! `message || coin_id || agg_sig_me_data`, the bytes to BLS-sign with `public_key`.
+//! Signatures come back in the order of `signing_targets`.
+
+use crate::asset::Asset;
+use crate::coin_proof::SerializedCoin;
+use crate::coin_records::run_coin_spend;
+use crate::encoding::{bytes32_from_hex, bytes_from_hex};
+use crate::error::WalletError;
+use crate::history::{TransactionDirection, TransactionRecord};
+use crate::signer::SignerFuture;
+use crate::transfer::{effective_fee, DigSendOptions, UnsignedTransaction};
+use crate::wallet::Wallet;
+use chia::bls::{aggregate, verify, PublicKey, Signature};
+use chia_wallet_sdk::driver::SpendContext;
+use chia_wallet_sdk::types::Condition;
+use datalayer_driver::{Bytes32, CoinSpend, Peer, SpendBundle};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+pub use crate::cold_signing::{SerializedCoinSpend, SigningTarget};
+
+/// Schema identifier of [`UnsignedCatSpend`] JSON
+pub const UNSIGNED_CAT_SPEND_SCHEMA: &str = "dig-wallet/unsigned-cat-spend";
+/// Version of the [`UnsignedCatSpend`] layout
+pub const UNSIGNED_CAT_SPEND_VERSION: u32 = 1;
+
+/// An unsigned CAT transfer and what signing it authorizes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsignedCatSpend {
+    pub schema: String,
+    pub version: u32,
+    pub asset_id: String,
+    /// AGG_SIG_ME additional data (the genesis challenge) the signatures commit to
+    pub agg_sig_me_data: String,
+    /// XCH fee paid by the bundle
+    pub fee: u64,
+    pub cat_coins: Vec<CatCoinContext>,
+    pub cat_outputs: Vec<CatOutput>,
+    /// Every coin spend of the bundle, XCH fee spends included
+    pub coin_spends: Vec<SerializedCoinSpend>,
+    pub signing_targets: Vec<SigningTarget>,
+}
+
+/// A CAT coin being spent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatCoinContext {
+    pub coin: SerializedCoin,
+    pub inner_puzzle_hash: String,
+    /// `None` for an eve coin
+    pub lineage: Option<CatLineage>,
+}
+
+/// Lineage proof of a CAT coin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatLineage {
+    pub parent_parent_coin_info: String,
+    pub parent_inner_puzzle_hash: String,
+    pub parent_amount: u64,
+}
+
+/// A CAT coin the spend creates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatOutput {
+    pub puzzle_hash: String,
+    pub amount: u64,
+    /// Whether the output returns to the sending wallet
+    pub change: bool,
+}
+
+/// A signer that signs the targets of an [`UnsignedCatSpend`]
+pub trait CatSigner: Send + Sync + Debug {
+    /// One signature per entry of `spend.signing_targets`, in order
+    fn sign_cat_spend<'a>(
+        &'a self,
+        spend: &'a UnsignedCatSpend,
+    ) -> SignerFuture<'a, Vec<Signature>>;
+}
+
+/// The AGG_SIG_ME signatures `coin_spends` need
+fn signing_targets(
+    coin_spends: &[CoinSpend],
+    agg_sig_me_data: Bytes32,
+) -> Result<Vec<SigningTarget>, WalletError> {
+    let mut targets = Vec::new();
+    for coin_spend in coin_spends {
+        let coin_id = coin_spend.coin.coin_id();
+        let mut ctx = SpendContext::new();
+        for condition in run_coin_spend(&mut ctx, coin_spend)? {
+            let agg_sig = match condition {
+                Condition::AggSigMe(agg_sig) => agg_sig,
+                other if other.clone().into_agg_sig().is_some() => {
+                    return Err(WalletError::TransactionFailed(format!(
+                        "Coin {} needs a signature other than AGG_SIG_ME",
+                        coin_id
+                    )))
+                }
+                _ => continue,
+            };
+            let mut signed_message = agg_sig.message.to_vec();
+            signed_message.extend_from_slice(coin_id.as_ref());
+            signed_message.extend_from_slice(agg_sig_me_data.as_ref());
+            targets.push(SigningTarget {
+                coin_id: hex::encode(coin_id),
+                public_key: hex::encode(agg_sig.public_key.to_bytes()),
+                message: hex::encode(agg_sig.message.as_ref()),
+                signed_message: hex::encode(signed_message),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+impl UnsignedCatSpend {
+    /// Serialize to the documented JSON schema
+    pub fn to_json(&self) -> Result<String, WalletError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))
+    }
+
+    /// Parse JSON written by [`UnsignedCatSpend::to_json`]
+    pub fn from_json(json: &str) -> Result<Self, WalletError> {
+        let spend: Self = serde_json::from_str(json).map_err(|e| {
+            WalletError::SerializationError(format!("Invalid unsigned CAT spend: {}", e))
+        })?;
+        if spend.schema != UNSIGNED_CAT_SPEND_SCHEMA || spend.version != UNSIGNED_CAT_SPEND_VERSION
+        {
+            return Err(WalletError::SerializationError(format!(
+                "Unsupported unsigned CAT spend {} version {}",
+                spend.schema, spend.version
+            )));
+        }
+        Ok(spend)
+    }
+
+    /// The AGG_SIG_ME data, which a signing service can compare with its network's
+    pub fn agg_sig_me_data(&self) -> Result<Bytes32, WalletError> {
+        bytes32_from_hex(&self.agg_sig_me_data)
+    }
+
+    /// Check one signature per signing target and assemble the spend bundle
+    ///
+    /// Fails with [`WalletError::CryptoError`] if the count is wrong or any signature
+    /// does not verify against its target.
+    pub fn into_spend_bundle(&self, signatures: &[Signature]) -> Result<SpendBundle, WalletError> {
+        if signatures.len() != self.signing_targets.len() {
+            return Err(WalletError::CryptoError(format!(
+                "Expected {} signatures, got {}",
+                self.signing_targets.len(),
+                signatures.len()
+            )));
+        }
+        for (target, signature) in self.signing_targets.iter().zip(signatures) {
+            let public_key_bytes: [u8; 48] = bytes_from_hex(&target.public_key)?
+                .try_into()
+                .map_err(|_| {
+                    WalletError::SerializationError("Public key must be 48 bytes".to_string())
+                })?;
+            let public_key = PublicKey::from_bytes(&public_key_bytes)
+                .map_err(|e| WalletError::CryptoError(format!("Invalid public key: {}", e)))?;
+            if !verify(
+                signature,
+                &public_key,
+                bytes_from_hex(&target.signed_message)?,
+            ) {
+                return Err(WalletError::CryptoError(format!(
+                    "Signature for coin {} does not verify",
+                    target.coin_id
+                )));
+            }
+        }
+
+        let coin_spends = self
+            .coin_spends
+            .iter()
+            .map(SerializedCoinSpend::to_coin_spend)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SpendBundle::new(coin_spends, aggregate(signatures)))
+    }
+}
+
+impl Wallet {
+    /// Build a DIG transfer for an external signer to authorize
+    ///
+    /// Works for signer-backed and watch-only wallets, since only the public key is used.
+    /// Like [`Wallet::send_dig`], the fee is paid from a separate XCH coin and bound to the
+    /// CAT spend.
+    pub async fn build_unsigned_dig_send(
+        &self,
+        peer: &Peer,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+    ) -> Result<UnsignedCatSpend, WalletError> {
+        let unsigned = self
+            .build_unsigned_transfer(
+                peer,
+                Asset::Dig,
+                destination,
+                amount,
+                fee,
+                vec![],
+                &DigSendOptions::default(),
+            )
+            .await?;
+        Self::describe_cat_spend(&unsigned, destination, amount)
+    }
+
+    fn describe_cat_spend(
+        unsigned: &UnsignedTransaction,
+        destination: Bytes32,
+        amount: u64,
+    ) -> Result<UnsignedCatSpend, WalletError> {
+        let asset_id = unsigned
+            .cats
+            .first()
+            .map(|cat| cat.info.asset_id)
+            .ok_or(WalletError::NoUnspentCoins)?;
+        let total: u64 = unsigned.cats.iter().map(|cat| cat.coin.amount).sum();
+        let mut cat_outputs = vec![CatOutput {
+            puzzle_hash: hex::encode(destination),
+            amount,
+            change: false,
+        }];
+        if total > amount {
+            cat_outputs.push(CatOutput {
+                puzzle_hash: hex::encode(unsigned.change_puzzle_hash),
+                amount: total - amount,
+                change: true,
+            });
+        }
+        let agg_sig_me_data = crate::chain::genesis_challenge();
+
+        Ok(UnsignedCatSpend {
+            schema: UNSIGNED_CAT_SPEND_SCHEMA.to_string(),
+            version: UNSIGNED_CAT_SPEND_VERSION,
+            asset_id: hex::encode(asset_id),
+            agg_sig_me_data: hex::encode(agg_sig_me_data),
+            fee: effective_fee(&unsigned.coin_spends)?,
+            cat_coins: unsigned
+                .cats
+                .iter()
+                .map(|cat| CatCoinContext {
+                    coin: SerializedCoin::from(&cat.coin),
+                    inner_puzzle_hash: hex::encode(cat.info.p2_puzzle_hash),
+                    lineage: cat.lineage_proof.map(|proof| CatLineage {
+                        parent_parent_coin_info: hex::encode(proof.parent_parent_coin_info),
+                        parent_inner_puzzle_hash: hex::encode(proof.parent_inner_puzzle_hash),
+                        parent_amount: proof.parent_amount,
+                    }),
+                })
+                .collect(),
+            cat_outputs,
+            coin_spends: unsigned
+                .coin_spends
+                .iter()
+                .map(SerializedCoinSpend::from)
+                .collect(),
+            signing_targets: signing_targets(&unsigned.coin_spends, agg_sig_me_data)?,
+        })
+    }
+
+    /// Send DIG, having `signer` sign the spend instead of the wallet's own key
+    ///
+    /// Every returned signature is checked before the bundle is broadcast.
+    pub async fn send_dig_with_cat_signer(
+        &self,
+        peer: &Peer,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+        signer: &dyn CatSigner,
+    ) -> Result<TransactionRecord, WalletError> {
+        let config = self.get_config();
+        let unsigned = self
+            .build_unsigned_dig_send(peer, destination, amount, fee)
+            .await?;
+        let signatures = signer.sign_cat_spend(&unsigned).await?;
+        let spend_bundle = unsigned.into_spend_bundle(&signatures)?;
+        let tx_id = self.broadcast_transaction(peer, &spend_bundle).await?;
+
+        let record = TransactionRecord {
+            tx_id: hex::encode(tx_id),
+            asset: Asset::Dig,
+            direction: TransactionDirection::Outgoing,
+            amount,
+            fee: unsigned.fee,
+            counterparty_puzzle_hash: Some(hex::encode(destination)),
+            counterparty_wallet: None,
+            spent_coin_ids: spend_bundle
+                .coin_spends
+                .iter()
+                .map(|coin_spend| hex::encode(coin_spend.coin.coin_id()))
+                .collect(),
+            height: None,
+            timestamp: config.unix_timestamp(),
+        };
+        self.transaction_history()?.record(&record).await?;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chia::bls::{sign, SecretKey};
+    use datalayer_driver::Coin;
+
+    fn spend_for(public_key: &PublicKey) -> UnsignedCatSpend {
+        let coin = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 1000);
+        let message = vec![9u8; 32];
+        let mut signed_message = message.clone();
+        signed_message.extend_from_slice(coin.coin_id().as_ref());
+        signed_message.extend_from_slice(&[3; 32]);
+        UnsignedCatSpend {
+            schema: UNSIGNED_CAT_SPEND_SCHEMA.to_string(),
+            version: UNSIGNED_CAT_SPEND_VERSION,
+            asset_id: hex::encode([4; 32]),
+            agg_sig_me_data: hex::encode([3; 32]),
+            fee: 0,
+            cat_coins: vec![CatCoinContext {
+                coin: SerializedCoin::from(&coin),
+                inner_puzzle_hash: hex::encode([5; 32]),
+                lineage: None,
+            }],
+            cat_outputs: vec![CatOutput {
+                puzzle_hash: hex::encode([6; 32]),
+                amount: 1000,
+                change: false,
+            }],
+            coin_spends: vec![SerializedCoinSpend {
+                coin: SerializedCoin::from(&coin),
+                puzzle_reveal: "ff0180".to_string(),
+                solution: "80".to_string(),
+            }],
+            signing_targets: vec![SigningTarget {
+                coin_id: hex::encode(coin.coin_id()),
+                public_key: hex::encode(public_key.to_bytes()),
+                message: hex::encode(message),
+                signed_message: hex::encode(signed_message),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_external_signatures_are_checked_before_assembly() {
+        let secret_key = SecretKey::from_seed(&[8; 32]);
+        let spend = spend_for(&secret_key.public_key());
+
+        let parsed = UnsignedCatSpend::from_json(&spend.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, spend);
+        assert_eq!(parsed.agg_sig_me_data().unwrap(), Bytes32::new([3; 32]));
+
+        let target = &parsed.signing_targets[0];
+        let good = sign(&secret_key, hex::decode(&target.signed_message).unwrap());
+        let bundle = parsed.into_spend_bundle(&[good]).unwrap();
+        assert_eq!(bundle.coin_spends.len(), 1);
+
+        let wrong = sign(&secret_key, hex::decode(&target.message).unwrap());
+        assert!(matches!(
+            parsed.into_spend_bundle(&[wrong]),
+            Err(WalletError::CryptoError(_))
+        ));
+        assert!(parsed.into_spend_bundle(&[]).is_err());
+
+        let mut other_schema = serde_json::to_value(&spend).unwrap();
+        other_schema["version"] = 2.into();
+        assert!(UnsignedCatSpend::from_json(&other_schema.to_string()).is_err());
+    }
+}
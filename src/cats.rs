@@ -0,0 +1,236 @@
+//! Issuance, discovery and transfer of any CAT by asset id.
+//!
+//! Coins are looked up at the CAT puzzle hash of each issued address. A coin only counts
+//! once its parent spend is fetched and shown to create it as a CAT of the same asset,
+//! which is also where the lineage proof its own spend needs comes from.
+//!
+//! New CATs are issued from the wallet's XCH under a [`CatTail`], whose hash becomes the
+//! asset id.
+
+use crate::asset::Asset;
+use crate::chain::{fetch_coin_spend, fetch_coin_state, fetch_unspent_coins};
+use crate::error::WalletError;
+use crate::history::TransactionRecord;
+use crate::transfer::{driver_error, DigSendOptions};
+use crate::wallet::Wallet;
+use chia::puzzles::cat::EverythingWithSignatureTailArgs;
+use chia_wallet_sdk::driver::{Action, Cat, CatInfo, Id, Puzzle, Spend, SpendContext, Spends};
+use chia_wallet_sdk::prelude::NodePtr;
+use datalayer_driver::{get_coin_id, Bytes32, Coin, CoinState, Peer};
+use std::collections::HashSet;
+
+/// The TAIL program that governs the supply of a CAT issued by [`Wallet::issue_cat`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CatTail {
+    /// Single issuance: the TAIL is curried with the id of the XCH coin the CAT is issued
+    /// from, and that coin can only be spent once, so the supply is fixed
+    #[default]
+    GenesisByCoinId,
+    /// Issuance by signature of the wallet's default synthetic key, which can issue more of
+    /// the same asset later
+    EverythingWithSignature,
+}
+
+/// The CAT of `asset_id` that `coin_state` holds, with its lineage proof
+///
+/// `Ok(None)` if the parent spend does not create the coin as such a CAT.
+pub(crate) async fn prove_cat(
+    peer: &Peer,
+    coin_state: &CoinState,
+    asset_id: Bytes32,
+) -> Result<Option<Cat>, WalletError> {
+    let parent = fetch_coin_state(peer, coin_state.coin.parent_coin_info).await?;
+    let spent_height = parent
+        .spent_height
+        .ok_or_else(|| WalletError::CoinSetError("Parent coin is not spent".to_string()))?;
+    let parent_spend = fetch_coin_spend(peer, parent.coin, spent_height).await?;
+
+    let mut ctx = SpendContext::new();
+    let puzzle = ctx
+        .alloc(&parent_spend.puzzle_reveal)
+        .map_err(driver_error)?;
+    let solution = ctx.alloc(&parent_spend.solution).map_err(driver_error)?;
+    let puzzle = Puzzle::parse(&ctx, puzzle);
+    let children =
+        Cat::parse_children(&mut ctx, parent.coin, puzzle, solution).map_err(driver_error)?;
+
+    let coin_id = coin_state.coin.coin_id();
+    Ok(children
+        .into_iter()
+        .flatten()
+        .find(|cat| cat.coin.coin_id() == coin_id && cat.info.asset_id == asset_id))
+}
+
+impl Wallet {
+    /// Get all unspent coins of the CAT `asset_id` at the wallet's issued addresses
+    ///
+    /// Coins whose parent spend does not prove them to be that CAT are left out, as are
+    /// coins spent by a pending transaction.
+    pub async fn get_all_unspent_cats(
+        &self,
+        peer: &Peer,
+        asset_id: Bytes32,
+        omit_coins: Vec<Coin>,
+    ) -> Result<Vec<Cat>, WalletError> {
+        let mut omit_coin_ids: Vec<Bytes32> = omit_coins.iter().map(get_coin_id).collect();
+        omit_coin_ids.extend(self.in_flight_coin_ids()?);
+
+        let mut cats = Vec::new();
+        for puzzle_hash in self.issued_puzzle_hashes().await? {
+            let cat_puzzle_hash = CatInfo::new(asset_id, None, puzzle_hash).puzzle_hash();
+            let (coin_states, _) = fetch_unspent_coins(peer, cat_puzzle_hash.into()).await?;
+            for coin_state in coin_states {
+                if omit_coin_ids.contains(&coin_state.coin.coin_id()) {
+                    continue;
+                }
+                if let Some(cat) = prove_cat(peer, &coin_state, asset_id).await? {
+                    cats.push(cat);
+                }
+            }
+        }
+        Ok(cats)
+    }
+
+    /// Select unspent coins of the CAT `asset_id` covering `amount`
+    pub async fn select_unspent_cats(
+        &self,
+        peer: &Peer,
+        asset_id: Bytes32,
+        amount: u64,
+        omit_coins: Vec<Coin>,
+    ) -> Result<Vec<Cat>, WalletError> {
+        let available = self
+            .get_all_unspent_cats(peer, asset_id, omit_coins)
+            .await?;
+        let coins: Vec<Coin> = available.iter().map(|cat| cat.coin).collect();
+
+        let selected = datalayer_driver::select_coins(&coins, amount)
+            .map_err(|e| WalletError::DataLayerError(format!("Coin selection failed: {}", e)))?;
+        if selected.is_empty() {
+            return Err(WalletError::NoUnspentCoins);
+        }
+
+        let selected_ids: HashSet<Bytes32> = selected.iter().map(get_coin_id).collect();
+        Ok(available
+            .into_iter()
+            .filter(|cat| selected_ids.contains(&cat.coin.coin_id()))
+            .collect())
+    }
+
+    /// Balance of the CAT `asset_id` in mojos
+    pub async fn get_cat_balance(
+        &self,
+        peer: &Peer,
+        asset_id: Bytes32,
+    ) -> Result<u64, WalletError> {
+        let cats = self.get_all_unspent_cats(peer, asset_id, vec![]).await?;
+        Ok(cats.iter().map(|cat| cat.coin.amount).sum())
+    }
+
+    /// Issue `amount` mojos of a new CAT under `tail` to the wallet's default address,
+    /// returning its asset id
+    ///
+    /// The CAT is issued from the wallet's XCH, which also pays `fee`. Each issuance under
+    /// [`CatTail::GenesisByCoinId`] creates a different asset.
+    pub async fn issue_cat(
+        &self,
+        peer: &Peer,
+        tail: CatTail,
+        amount: u64,
+        fee: u64,
+    ) -> Result<Bytes32, WalletError> {
+        if amount == 0 {
+            return Err(WalletError::InvalidAmount(
+                "CAT issuance amount is zero".to_string(),
+            ));
+        }
+        let config = self.get_config();
+        Self::check_fee_floor(peer, fee, config).await?;
+
+        let owner_puzzle_hash = self.get_owner_puzzle_hash().await?;
+        let mut ctx = SpendContext::new();
+        let issue = match tail {
+            CatTail::GenesisByCoinId => Action::single_issue_cat(None, amount),
+            CatTail::EverythingWithSignature => {
+                let public_key = self.get_public_synthetic_key().await?;
+                let puzzle = ctx
+                    .curry(EverythingWithSignatureTailArgs::new(public_key))
+                    .map_err(driver_error)?;
+                Action::issue_cat(Spend::new(puzzle, NodePtr::NIL), None, amount)
+            }
+        };
+        let hint = ctx.hint(owner_puzzle_hash).map_err(driver_error)?;
+        let mut spends = Spends::new(owner_puzzle_hash);
+        for coin in self.select_unspent_coins(peer, amount, fee, vec![]).await? {
+            spends.add(coin);
+        }
+
+        let mut actions = vec![
+            issue,
+            Action::send(Id::New(0), owner_puzzle_hash, amount, hint),
+        ];
+        if fee > 0 {
+            actions.push(Action::fee(fee));
+        }
+        let deltas = spends.apply(&mut ctx, &actions).map_err(driver_error)?;
+        let outputs = self
+            .spend_keys()
+            .await?
+            .finish_spends(&mut ctx, spends, &deltas)?;
+        let asset_id = outputs
+            .cats
+            .get(&Id::New(0))
+            .and_then(|cats| cats.first())
+            .map(|cat| cat.info.asset_id)
+            .ok_or_else(|| WalletError::TransactionFailed("Issuance created no CAT".to_string()))?;
+
+        let spend_bundle = self.sign_spend_bundle(ctx.take()).await?;
+        self.broadcast_transaction(peer, &spend_bundle).await?;
+        Ok(asset_id)
+    }
+
+    /// Send `amount` mojos of the CAT `asset_id` to an address of the wallet's chain
+    ///
+    /// Change returns to the wallet's default address, and `fee` is paid from a separate
+    /// XCH coin bound to the CAT spend as in [`Wallet::send_dig`].
+    pub async fn send_cat(
+        &self,
+        peer: &Peer,
+        asset_id: Bytes32,
+        to_address: &str,
+        amount: u64,
+        fee: u64,
+    ) -> Result<TransactionRecord, WalletError> {
+        self.send_cat_with_options(
+            peer,
+            asset_id,
+            to_address,
+            amount,
+            fee,
+            &DigSendOptions::default(),
+        )
+        .await
+    }
+
+    /// Send the CAT `asset_id` to an address, assembling the spends according to `options`
+    pub async fn send_cat_with_options(
+        &self,
+        peer: &Peer,
+        asset_id: Bytes32,
+        to_address: &str,
+        amount: u64,
+        fee: u64,
+        options: &DigSendOptions,
+    ) -> Result<TransactionRecord, WalletError> {
+        let destination = self.validate_address(to_address).await?;
+        self.send_asset(
+            peer,
+            Asset::Cat(asset_id),
+            destination,
+            amount,
+            fee,
+            options,
+        )
+        .await
+    }
+}
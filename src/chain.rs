@@ -0,0 +1,280 @@
+//! Thin helpers over peer wallet-protocol requests, mapping rejections to [`WalletError`].
+//!
+//! Every request goes through [`crate::recording`], so it can be recorded and replayed.
+
+use crate::context::within_current;
+use crate::encoding::{bytes32_from_hex, bytes_from_hex};
+use crate::error::WalletError;
+use crate::recording::{exchange, PeerRequest, PeerResponse, RecordedCoinState};
+use chia::protocol::{
+    CoinStateFilters, RejectHeaderRequest, RequestBlockHeader, RespondBlockHeader,
+};
+use datalayer_driver::{Bytes32, Coin, CoinSpend, CoinState, Peer, Program};
+
+/// Genesis challenge of the network this crate currently operates on
+pub(crate) fn genesis_challenge() -> Bytes32 {
+    datalayer_driver::constants::get_mainnet_genesis_challenge()
+}
+
+fn unexpected(response: PeerResponse) -> WalletError {
+    WalletError::SerializationError(format!("Unexpected recorded response: {:?}", response))
+}
+
+fn coin_states(recorded: &[RecordedCoinState]) -> Result<Vec<CoinState>, WalletError> {
+    recorded
+        .iter()
+        .map(RecordedCoinState::to_coin_state)
+        .collect()
+}
+
+/// Fetch the current state of a set of coins
+pub(crate) async fn fetch_coin_states(
+    peer: &Peer,
+    coin_ids: Vec<Bytes32>,
+) -> Result<Vec<CoinState>, WalletError> {
+    let request = PeerRequest::CoinStates {
+        coin_ids: coin_ids.iter().map(hex::encode).collect(),
+    };
+    let response = exchange(
+        request,
+        within_current(async {
+            let response = peer
+                .request_coin_state(coin_ids, None, genesis_challenge(), false)
+                .await
+                .map_err(|e| {
+                    WalletError::NetworkError(format!("Failed to request coin state: {}", e))
+                })?
+                .map_err(|e| {
+                    WalletError::NetworkError(format!("Coin state request rejected: {:?}", e))
+                })?;
+            Ok(PeerResponse::CoinStates {
+                coin_states: response.coin_states.iter().map(Into::into).collect(),
+            })
+        }),
+    )
+    .await?;
+
+    match response {
+        PeerResponse::CoinStates {
+            coin_states: states,
+        } => coin_states(&states),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Fetch the current state of a single coin, failing if the peer does not know it
+pub(crate) async fn fetch_coin_state(
+    peer: &Peer,
+    coin_id: Bytes32,
+) -> Result<CoinState, WalletError> {
+    fetch_coin_states(peer, vec![coin_id])
+        .await?
+        .into_iter()
+        .find(|state| state.coin.coin_id() == coin_id)
+        .ok_or_else(|| WalletError::CoinSetError(format!("Coin {} not found", coin_id)))
+}
+
+/// Fetch the puzzle reveal and solution a coin was spent with
+pub(crate) async fn fetch_coin_spend(
+    peer: &Peer,
+    coin: Coin,
+    spent_height: u32,
+) -> Result<CoinSpend, WalletError> {
+    request_coin_spend(peer, coin, spent_height)
+        .await?
+        .map_err(|detail| {
+            WalletError::NetworkError(format!("Puzzle and solution request rejected: {}", detail))
+        })
+}
+
+/// Fetch a coin spend, returning the peer's rejection separately from transport failures
+///
+/// Peers reject the request when they no longer hold the block, as pruned nodes do.
+pub(crate) async fn request_coin_spend(
+    peer: &Peer,
+    coin: Coin,
+    spent_height: u32,
+) -> Result<Result<CoinSpend, String>, WalletError> {
+    let request = PeerRequest::PuzzleAndSolution {
+        coin_id: hex::encode(coin.coin_id()),
+        height: spent_height,
+    };
+    let response = exchange(
+        request,
+        within_current(async {
+            let response = peer
+                .request_puzzle_and_solution(coin.coin_id(), spent_height)
+                .await
+                .map_err(|e| {
+                    WalletError::NetworkError(format!(
+                        "Failed to request puzzle and solution: {}",
+                        e
+                    ))
+                })?;
+            Ok(match response {
+                Ok(response) => PeerResponse::CoinSpend {
+                    puzzle_reveal: hex::encode(response.puzzle.as_ref()),
+                    solution: hex::encode(response.solution.as_ref()),
+                },
+                Err(rejection) => PeerResponse::Rejected {
+                    detail: format!("{:?}", rejection),
+                },
+            })
+        }),
+    )
+    .await?;
+
+    match response {
+        PeerResponse::CoinSpend {
+            puzzle_reveal,
+            solution,
+        } => Ok(Ok(CoinSpend::new(
+            coin,
+            Program::from(bytes_from_hex(&puzzle_reveal)?),
+            Program::from(bytes_from_hex(&solution)?),
+        ))),
+        PeerResponse::Rejected { detail } => Ok(Err(detail)),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Header hash and (for transaction blocks) timestamp at a height
+pub(crate) async fn fetch_block_info(
+    peer: &Peer,
+    height: u32,
+) -> Result<(Bytes32, Option<u64>), WalletError> {
+    let response = exchange(
+        PeerRequest::BlockHeader { height },
+        within_current(async {
+            let response = peer
+                .request_fallible::<RespondBlockHeader, RejectHeaderRequest, _>(
+                    RequestBlockHeader::new(height),
+                )
+                .await
+                .map_err(|e| {
+                    WalletError::NetworkError(format!("Failed to request block header: {}", e))
+                })?
+                .map_err(|e| {
+                    WalletError::NetworkError(format!("Block header request rejected: {:?}", e))
+                })?;
+
+            let header_block = response.header_block;
+            Ok(PeerResponse::BlockHeader {
+                header_hash: hex::encode(header_block.header_hash()),
+                timestamp: header_block
+                    .foliage_transaction_block
+                    .as_ref()
+                    .map(|block| block.timestamp),
+            })
+        }),
+    )
+    .await?;
+
+    match response {
+        PeerResponse::BlockHeader {
+            header_hash,
+            timestamp,
+        } => Ok((bytes32_from_hex(&header_hash)?, timestamp)),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Unspent coins at a puzzle hash and the peer's height when it answered
+pub(crate) async fn fetch_unspent_coins(
+    peer: &Peer,
+    puzzle_hash: Bytes32,
+) -> Result<(Vec<CoinState>, u32), WalletError> {
+    let request = PeerRequest::UnspentCoins {
+        puzzle_hash: hex::encode(puzzle_hash),
+    };
+    let response = exchange(
+        request,
+        within_current(async {
+            let unspent = datalayer_driver::async_api::get_all_unspent_coins(
+                peer,
+                puzzle_hash,
+                None,
+                genesis_challenge(),
+            )
+            .await
+            .map_err(|e| {
+                WalletError::NetworkError(format!("Failed to get unspent coins: {}", e))
+            })?;
+            Ok(PeerResponse::UnspentCoins {
+                coin_states: unspent.coin_states.iter().map(Into::into).collect(),
+                last_height: unspent.last_height,
+            })
+        }),
+    )
+    .await?;
+
+    match response {
+        PeerResponse::UnspentCoins {
+            coin_states: states,
+            last_height,
+        } => Ok((coin_states(&states)?, last_height)),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Every coin ever created at or hinted to a puzzle hash, spent or not
+pub(crate) async fn fetch_puzzle_history(
+    peer: &Peer,
+    puzzle_hash: Bytes32,
+) -> Result<Vec<CoinState>, WalletError> {
+    let request = PeerRequest::PuzzleHistory {
+        puzzle_hash: hex::encode(puzzle_hash),
+    };
+    let response = exchange(
+        request,
+        within_current(async {
+            let filters = CoinStateFilters::new(true, true, true, 0);
+            let mut recorded = Vec::new();
+            let mut previous_height = None;
+            let mut header_hash = genesis_challenge();
+            loop {
+                let response = peer
+                    .request_puzzle_state(
+                        vec![puzzle_hash],
+                        previous_height,
+                        header_hash,
+                        filters.clone(),
+                        false,
+                    )
+                    .await
+                    .map_err(|e| {
+                        WalletError::NetworkError(format!("Failed to request puzzle state: {}", e))
+                    })?
+                    .map_err(|e| {
+                        WalletError::NetworkError(format!("Puzzle state request rejected: {:?}", e))
+                    })?;
+                recorded.extend(response.coin_states.iter().map(Into::into));
+                if response.is_finished {
+                    break;
+                }
+                // Large histories come in pages, each resuming where the last one ended
+                previous_height = Some(response.height);
+                header_hash = response.header_hash;
+            }
+            Ok(PeerResponse::CoinStates {
+                coin_states: recorded,
+            })
+        }),
+    )
+    .await?;
+
+    match response {
+        PeerResponse::CoinStates {
+            coin_states: states,
+        } => coin_states(&states),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// The peer's current peak height
+///
+/// Probes an unused puzzle hash so the response carries only the sync height.
+pub(crate) async fn fetch_peak_height(peer: &Peer) -> Result<u32, WalletError> {
+    let (_, last_height) = fetch_unspent_coins(peer, Bytes32::default()).await?;
+    Ok(last_height)
+}
@@ -0,0 +1,305 @@
+//! Transaction history reconstructed from the chain.
+//!
+//! The local [`crate::TransactionHistory`] only holds what this installation sent. Here the
+//! coins of every issued address are walked on a peer instead, so receipts and sends made
+//! from another copy of the wallet show up too. Coins of the wallet spent at the same
+//! height count as one send; their spends are run to tell payments from change and fee.
+//! Every coin created for the wallet by someone else's spend is a receipt.
+//!
+//! The chain does not keep spend bundle names, so a reconstructed record's `tx_id` is the
+//! id of the first coin it spent, or of the coin it received.
+
+use crate::asset::Asset;
+use crate::chain::{fetch_block_info, fetch_coin_spend, fetch_coin_state, fetch_puzzle_history};
+use crate::coin_records::run_coin_spend;
+use crate::error::WalletError;
+use crate::history::{TransactionDirection, TransactionRecord};
+use crate::wallet::Wallet;
+use chia_wallet_sdk::driver::SpendContext;
+use chia_wallet_sdk::types::Condition;
+use datalayer_driver::{Bytes32, Coin, CoinSpend, CoinState, Peer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[cfg(feature = "cat")]
+use crate::cats::prove_cat;
+#[cfg(feature = "cat")]
+use crate::dig::DIG_COIN_ASSET_ID;
+#[cfg(feature = "cat")]
+use crate::transfer::driver_error;
+#[cfg(feature = "cat")]
+use chia_wallet_sdk::driver::{Cat, CatInfo, Puzzle};
+
+/// Puzzle hash at which the wallet holds `asset` for its address `puzzle_hash`
+fn asset_puzzle_hash(asset: Asset, puzzle_hash: Bytes32) -> Bytes32 {
+    match asset {
+        Asset::Xch => puzzle_hash,
+        #[cfg(feature = "cat")]
+        Asset::Dig => CatInfo::new(DIG_COIN_ASSET_ID, None, puzzle_hash)
+            .puzzle_hash()
+            .into(),
+        #[cfg(feature = "cat")]
+        Asset::Cat(asset_id) => CatInfo::new(asset_id, None, puzzle_hash)
+            .puzzle_hash()
+            .into(),
+        #[cfg(not(feature = "cat"))]
+        Asset::Dig | Asset::Cat(_) => puzzle_hash,
+    }
+}
+
+/// Coins `coin_spend` creates, as (puzzle hash, amount), unwrapped to the inner puzzle
+/// hash for CATs
+fn created_coins(asset: Asset, coin_spend: &CoinSpend) -> Result<Vec<(Bytes32, u64)>, WalletError> {
+    let mut ctx = SpendContext::new();
+    match asset {
+        Asset::Xch => Ok(run_coin_spend(&mut ctx, coin_spend)?
+            .into_iter()
+            .filter_map(Condition::into_create_coin)
+            .map(|create_coin| (create_coin.puzzle_hash, create_coin.amount))
+            .collect()),
+        #[cfg(feature = "cat")]
+        Asset::Dig | Asset::Cat(_) => {
+            let puzzle = ctx.alloc(&coin_spend.puzzle_reveal).map_err(driver_error)?;
+            let solution = ctx.alloc(&coin_spend.solution).map_err(driver_error)?;
+            let puzzle = Puzzle::parse(&ctx, puzzle);
+            let children = Cat::parse_children(&mut ctx, coin_spend.coin, puzzle, solution)
+                .map_err(driver_error)?;
+            Ok(children
+                .into_iter()
+                .flatten()
+                .map(|cat| (cat.info.p2_puzzle_hash, cat.coin.amount))
+                .collect())
+        }
+        #[cfg(not(feature = "cat"))]
+        Asset::Dig | Asset::Cat(_) => Ok(Vec::new()),
+    }
+}
+
+/// The outgoing or internal record of wallet coins spent together, from what they created
+fn send_record(
+    asset: Asset,
+    spent: &[Coin],
+    created: &[(Bytes32, u64)],
+    own_puzzle_hashes: &HashSet<Bytes32>,
+    height: u32,
+) -> TransactionRecord {
+    let spent_total: u64 = spent.iter().map(|coin| coin.amount).sum();
+    let created_total: u64 = created.iter().map(|(_, amount)| amount).sum();
+    let payments: Vec<&(Bytes32, u64)> = created
+        .iter()
+        .filter(|(puzzle_hash, _)| !own_puzzle_hashes.contains(puzzle_hash))
+        .collect();
+    let (direction, amount) = if payments.is_empty() {
+        (TransactionDirection::Internal, created_total)
+    } else {
+        (
+            TransactionDirection::Outgoing,
+            payments.iter().map(|(_, amount)| amount).sum(),
+        )
+    };
+
+    TransactionRecord {
+        tx_id: spent
+            .first()
+            .map(|coin| hex::encode(coin.coin_id()))
+            .unwrap_or_default(),
+        asset,
+        direction,
+        amount,
+        // CAT fees are paid by an XCH spend, which has its own record
+        fee: if asset == Asset::Xch {
+            spent_total.saturating_sub(created_total)
+        } else {
+            0
+        },
+        counterparty_puzzle_hash: payments
+            .first()
+            .map(|(puzzle_hash, _)| hex::encode(puzzle_hash)),
+        counterparty_wallet: None,
+        spent_coin_ids: spent
+            .iter()
+            .map(|coin| hex::encode(coin.coin_id()))
+            .collect(),
+        height: Some(height),
+        timestamp: 0,
+    }
+}
+
+/// Puzzle hash of whoever created the received `coin_state`, the inner one for CATs
+async fn sender_puzzle_hash(
+    peer: &Peer,
+    asset: Asset,
+    coin_state: &CoinState,
+) -> Result<Option<Bytes32>, WalletError> {
+    match asset {
+        Asset::Xch => {
+            let parent = fetch_coin_state(peer, coin_state.coin.parent_coin_info).await?;
+            Ok(Some(parent.coin.puzzle_hash))
+        }
+        #[cfg(feature = "cat")]
+        Asset::Dig | Asset::Cat(_) => {
+            let asset_id = match asset {
+                Asset::Cat(asset_id) => asset_id,
+                _ => DIG_COIN_ASSET_ID,
+            };
+            Ok(prove_cat(peer, coin_state, asset_id)
+                .await?
+                .and_then(|cat| cat.lineage_proof)
+                .map(|proof| proof.parent_inner_puzzle_hash))
+        }
+        #[cfg(not(feature = "cat"))]
+        Asset::Dig | Asset::Cat(_) => Ok(None),
+    }
+}
+
+/// Sends and receipts of `asset` at `own_puzzle_hashes` from `from_height` on
+async fn asset_history(
+    peer: &Peer,
+    asset: Asset,
+    own_puzzle_hashes: &[Bytes32],
+    from_height: u32,
+) -> Result<Vec<TransactionRecord>, WalletError> {
+    let mut coin_states = Vec::new();
+    for &puzzle_hash in own_puzzle_hashes {
+        let asset_puzzle_hash = asset_puzzle_hash(asset, puzzle_hash);
+        // Coins hinted to the address but locked elsewhere are not the wallet's
+        coin_states.extend(
+            fetch_puzzle_history(peer, asset_puzzle_hash)
+                .await?
+                .into_iter()
+                .filter(|coin_state| coin_state.coin.puzzle_hash == asset_puzzle_hash),
+        );
+    }
+    let own_coin_ids: HashSet<Bytes32> = coin_states
+        .iter()
+        .map(|coin_state| coin_state.coin.coin_id())
+        .collect();
+    let own: HashSet<Bytes32> = own_puzzle_hashes.iter().copied().collect();
+
+    let mut records = Vec::new();
+    let mut spent_by_height: BTreeMap<u32, Vec<Coin>> = BTreeMap::new();
+    for coin_state in &coin_states {
+        if let Some(height) = coin_state
+            .spent_height
+            .filter(|&height| height >= from_height)
+        {
+            spent_by_height
+                .entry(height)
+                .or_default()
+                .push(coin_state.coin);
+        }
+        let Some(height) = coin_state
+            .created_height
+            .filter(|&height| height >= from_height)
+        else {
+            continue;
+        };
+        // Change and moves between the wallet's own addresses come from its own coins
+        if own_coin_ids.contains(&coin_state.coin.parent_coin_info) {
+            continue;
+        }
+        let coin = coin_state.coin;
+        records.push(TransactionRecord {
+            tx_id: hex::encode(coin.coin_id()),
+            asset,
+            direction: TransactionDirection::Incoming,
+            amount: coin.amount,
+            fee: 0,
+            counterparty_puzzle_hash: sender_puzzle_hash(peer, asset, coin_state)
+                .await?
+                .map(hex::encode),
+            counterparty_wallet: None,
+            spent_coin_ids: vec![],
+            height: Some(height),
+            timestamp: 0,
+        });
+    }
+
+    for (height, spent) in spent_by_height {
+        let mut created = Vec::new();
+        for &coin in &spent {
+            let coin_spend = fetch_coin_spend(peer, coin, height).await?;
+            created.extend(created_coins(asset, &coin_spend)?);
+        }
+        records.push(send_record(asset, &spent, &created, &own, height));
+    }
+
+    Ok(records)
+}
+
+impl Wallet {
+    /// Reconstruct the wallet's XCH and DIG sends and receipts from `from_height` on, oldest
+    /// first
+    ///
+    /// Walks the coins of the wallet's issued addresses on `peer`, so it also covers
+    /// transactions this installation never recorded. Records carry the block height and
+    /// timestamp; nothing is written to the local history.
+    pub async fn get_transaction_history(
+        &self,
+        peer: &Peer,
+        from_height: u32,
+    ) -> Result<Vec<TransactionRecord>, WalletError> {
+        let own_puzzle_hashes = self.issued_puzzle_hashes().await?;
+        let mut records = asset_history(peer, Asset::Xch, &own_puzzle_hashes, from_height).await?;
+        #[cfg(feature = "cat")]
+        records.extend(asset_history(peer, Asset::Dig, &own_puzzle_hashes, from_height).await?);
+
+        let mut timestamps: HashMap<u32, u64> = HashMap::new();
+        for record in &mut records {
+            let Some(height) = record.height else {
+                continue;
+            };
+            record.timestamp = match timestamps.get(&height) {
+                Some(&timestamp) => timestamp,
+                None => {
+                    let (_, timestamp) = fetch_block_info(peer, height).await?;
+                    let timestamp = timestamp.unwrap_or_default();
+                    timestamps.insert(height, timestamp);
+                    timestamp
+                }
+            };
+        }
+        records.sort_by_key(|record| (record.height, record.timestamp));
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_record() {
+        let own_puzzle_hash = Bytes32::new([1; 32]);
+        let other = Bytes32::new([2; 32]);
+        let own = HashSet::from([own_puzzle_hash]);
+        let spent = [
+            Coin::new(Bytes32::new([3; 32]), own_puzzle_hash, 600),
+            Coin::new(Bytes32::new([4; 32]), own_puzzle_hash, 500),
+        ];
+
+        let record = send_record(
+            Asset::Xch,
+            &spent,
+            &[(other, 700), (own_puzzle_hash, 390)],
+            &own,
+            42,
+        );
+        assert_eq!(record.direction, TransactionDirection::Outgoing);
+        assert_eq!(record.amount, 700);
+        assert_eq!(record.fee, 10);
+        assert_eq!(record.counterparty_puzzle_hash, Some(hex::encode(other)));
+        assert_eq!(record.tx_id, hex::encode(spent[0].coin_id()));
+        assert_eq!(record.spent_coin_ids.len(), 2);
+        assert_eq!(record.height, Some(42));
+
+        // Merging coins pays no one but the fee
+        let record = send_record(Asset::Xch, &spent, &[(own_puzzle_hash, 1_000)], &own, 42);
+        assert_eq!(record.direction, TransactionDirection::Internal);
+        assert_eq!(record.amount, 1_000);
+        assert_eq!(record.fee, 100);
+        assert_eq!(record.counterparty_puzzle_hash, None);
+
+        let record = send_record(Asset::Dig, &spent, &[(other, 1_100)], &own, 42);
+        assert_eq!(record.fee, 0);
+    }
+}
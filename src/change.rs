@@ -0,0 +1,112 @@
+//! Where the change of a transaction goes.
+//!
+//! Change returns to the wallet's default address unless a [`ChangePolicy`] on the
+//! [`WalletConfig`](crate::WalletConfig) or on a [`TransactionBuilder`](crate::TransactionBuilder)
+//! says otherwise. Every transfer, CAT send, payout and airdrop is built through the
+//! builder, so the policy applies to all of them. XCH change too small to be worth a coin
+//! can be left to the farmer instead of creating dust.
+
+use datalayer_driver::Bytes32;
+
+/// Puzzle hash that receives a transaction's change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChangeDestination {
+    /// The wallet's default address, derivation index 0
+    #[default]
+    DefaultAddress,
+    /// The puzzle hash of the first spent coin, so change stays where the funds were
+    SamePuzzleHash,
+    /// A receive address issued for the transaction, as by `Wallet::get_new_address`
+    NextAddress,
+    /// A fixed puzzle hash, not necessarily the wallet's
+    Address(Bytes32),
+}
+
+/// How the change of a transaction is paid out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangePolicy {
+    destination: ChangeDestination,
+    dust_threshold: u64,
+}
+
+impl ChangePolicy {
+    /// Change to the wallet's default address, however small
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change back to the puzzle hash of the first spent coin
+    pub fn same_puzzle_hash() -> Self {
+        Self::to(ChangeDestination::SamePuzzleHash)
+    }
+
+    /// Change to a newly issued receive address of the wallet
+    pub fn next_address() -> Self {
+        Self::to(ChangeDestination::NextAddress)
+    }
+
+    /// Change to `puzzle_hash`
+    pub fn address(puzzle_hash: Bytes32) -> Self {
+        Self::to(ChangeDestination::Address(puzzle_hash))
+    }
+
+    /// Change to `destination`
+    pub fn to(destination: ChangeDestination) -> Self {
+        Self {
+            destination,
+            dust_threshold: 0,
+        }
+    }
+
+    /// Add XCH change below `threshold` mojos to the fee instead of creating a coin
+    ///
+    /// CAT change is always paid out, since CAT mojos cannot go to the farmer.
+    pub fn burn_dust(mut self, threshold: u64) -> Self {
+        self.dust_threshold = threshold;
+        self
+    }
+
+    pub fn destination(&self) -> ChangeDestination {
+        self.destination
+    }
+
+    /// XCH change below this many mojos goes to the fee
+    pub fn dust_threshold(&self) -> u64 {
+        self.dust_threshold
+    }
+}
+
+/// The fee and XCH change once change below `dust_threshold` is added to the fee
+pub(crate) fn burn_dust(fee: u64, change: u64, dust_threshold: u64) -> (u64, u64) {
+    if change < dust_threshold {
+        (fee + change, 0)
+    } else {
+        (fee, change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_policy() {
+        assert_eq!(
+            ChangePolicy::default().destination(),
+            ChangeDestination::DefaultAddress
+        );
+        let puzzle_hash = Bytes32::new([7; 32]);
+        let policy = ChangePolicy::address(puzzle_hash).burn_dust(1_000);
+        assert_eq!(
+            policy.destination(),
+            ChangeDestination::Address(puzzle_hash)
+        );
+        assert_eq!(policy.dust_threshold(), 1_000);
+        assert_eq!(ChangePolicy::next_address().dust_threshold(), 0);
+
+        assert_eq!(burn_dust(50, 999, 1_000), (1_049, 0));
+        assert_eq!(burn_dust(50, 1_000, 1_000), (50, 1_000));
+        assert_eq!(burn_dust(50, 0, 1_000), (50, 0));
+        assert_eq!(burn_dust(50, 1, 0), (50, 1));
+    }
+}
@@ -0,0 +1,205 @@
+//! Fork-aware sync checkpoints.
+//!
+//! Each successful coin set sync records the peak it was taken at as a `(height,
+//! header_hash)` checkpoint. Before syncing again, [`Wallet::resume_sync`] asks the peer
+//! for the header at each checkpoint, newest first. Checkpoints the peer's chain no longer
+//! contains were reorged away: they are dropped together with the warm caches built on
+//! them, and a [`ForkEvent`] is published to [`Wallet::fork_events`] subscribers.
+
+use crate::chain::{fetch_block_info, fetch_peak_height};
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::wallet::Wallet;
+use datalayer_driver::Peer;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Checkpoints kept per wallet; older ones are discarded first
+pub const MAX_SYNC_CHECKPOINTS: usize = 64;
+/// Fork events buffered per subscriber
+pub(crate) const FORK_EVENT_CAPACITY: usize = 16;
+
+const CHECKPOINTS_KEY: &str = "chain";
+
+/// Peak a sync was taken at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncCheckpoint {
+    pub height: u32,
+    /// Header hash at `height`, as hex
+    pub header_hash: String,
+    pub recorded_at: u64,
+}
+
+/// Checkpoints abandoned because the peer's chain no longer contains them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForkEvent {
+    /// Dropped checkpoints, oldest first
+    pub abandoned: Vec<SyncCheckpoint>,
+    /// Newest checkpoint still on the peer's chain, if any
+    pub common_ancestor: Option<SyncCheckpoint>,
+}
+
+impl ForkEvent {
+    /// Height to resync from; `None` means from genesis
+    pub fn resync_height(&self) -> Option<u32> {
+        self.common_ancestor
+            .as_ref()
+            .map(|checkpoint| checkpoint.height)
+    }
+}
+
+/// Persisted sync checkpoints of one wallet, oldest first
+pub struct CheckpointLog {
+    cache: FileCache<Vec<SyncCheckpoint>>,
+}
+
+impl CheckpointLog {
+    /// Open the checkpoint log of a wallet under the configured `.dig` directory
+    pub fn open(wallet_name: &str, config: &WalletConfig) -> Result<Self, WalletError> {
+        Ok(Self {
+            cache: FileCache::from_config(&format!("checkpoints/{}", wallet_name), config)?,
+        })
+    }
+
+    /// All checkpoints, oldest first
+    pub fn list(&self) -> Result<Vec<SyncCheckpoint>, WalletError> {
+        Ok(self.cache.get(CHECKPOINTS_KEY)?.unwrap_or_default())
+    }
+
+    /// The newest checkpoint
+    pub fn latest(&self) -> Result<Option<SyncCheckpoint>, WalletError> {
+        Ok(self.list()?.pop())
+    }
+
+    /// Append a checkpoint, replacing any at the same or a greater height
+    pub async fn record(&self, checkpoint: SyncCheckpoint) -> Result<(), WalletError> {
+        self.cache
+            .update(CHECKPOINTS_KEY, |checkpoints| {
+                let mut checkpoints = checkpoints.unwrap_or_default();
+                checkpoints.retain(|existing| existing.height < checkpoint.height);
+                checkpoints.push(checkpoint);
+                let excess = checkpoints.len().saturating_sub(MAX_SYNC_CHECKPOINTS);
+                checkpoints.drain(..excess);
+                Ok((checkpoints, ()))
+            })
+            .await
+    }
+
+    /// Drop checkpoints above `height` (all of them for `None`), returning them oldest first
+    pub async fn rewind_to(&self, height: Option<u32>) -> Result<Vec<SyncCheckpoint>, WalletError> {
+        self.cache
+            .update(CHECKPOINTS_KEY, |checkpoints| {
+                let (kept, abandoned) = checkpoints
+                    .unwrap_or_default()
+                    .into_iter()
+                    .partition(|checkpoint| height.is_some_and(|h| checkpoint.height <= h));
+                Ok((kept, abandoned))
+            })
+            .await
+    }
+}
+
+impl Wallet {
+    /// Open the sync checkpoint log of this wallet
+    pub fn sync_checkpoints(&self) -> Result<CheckpointLog, WalletError> {
+        CheckpointLog::open(&self.cache_name(), self.get_config())
+    }
+
+    /// Subscribe to forks detected by [`Wallet::resume_sync`] from now on
+    pub fn fork_events(&self) -> broadcast::Receiver<ForkEvent> {
+        self.fork_event_sender().subscribe()
+    }
+
+    /// Find the newest checkpoint still on the peer's chain, rewinding past any fork
+    ///
+    /// Returns the checkpoint to resync from, or `None` to resync from genesis. Fails with
+    /// [`WalletError::StalePeer`] when the peer has not reached the latest checkpoint, since
+    /// a lagging peer cannot tell a fork from a chain it has not seen yet.
+    pub async fn resume_sync(&self, peer: &Peer) -> Result<Option<SyncCheckpoint>, WalletError> {
+        let log = self.sync_checkpoints()?;
+        let checkpoints = log.list()?;
+        let Some(latest) = checkpoints.last() else {
+            return Ok(None);
+        };
+
+        let peer_height = fetch_peak_height(peer).await?;
+        if peer_height < latest.height {
+            return Err(WalletError::StalePeer {
+                peer_height,
+                required_height: latest.height,
+            });
+        }
+
+        let mut common_ancestor = None;
+        for checkpoint in checkpoints.iter().rev() {
+            let (header_hash, _) = fetch_block_info(peer, checkpoint.height).await?;
+            if hex::encode(header_hash) == checkpoint.header_hash {
+                common_ancestor = Some(checkpoint.clone());
+                break;
+            }
+        }
+
+        if common_ancestor.as_ref() == Some(latest) {
+            return Ok(common_ancestor);
+        }
+
+        let resync_height = common_ancestor.as_ref().map(|c| c.height);
+        let abandoned = log.rewind_to(resync_height).await?;
+        // Cached coin sets, lineages and spends describe the abandoned branch
+        self.warm_cache()?.invalidate().await?;
+        self.spent_tombstones()?.rewind_to(resync_height).await?;
+
+        // No subscribers is not an error
+        let _ = self.fork_event_sender().send(ForkEvent {
+            abandoned,
+            common_ancestor: common_ancestor.clone(),
+        });
+
+        Ok(common_ancestor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    fn checkpoint(height: u32, fill: u8) -> SyncCheckpoint {
+        SyncCheckpoint {
+            height,
+            header_hash: hex::encode([fill; 32]),
+            recorded_at: u64::from(height),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_log_record_and_rewind() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let log = CheckpointLog::open("fork_test", &config).unwrap();
+        assert!(log.latest().unwrap().is_none());
+
+        for height in [10, 20, 30] {
+            log.record(checkpoint(height, 1)).await.unwrap();
+        }
+        // A checkpoint below the tip replaces everything above it
+        log.record(checkpoint(25, 2)).await.unwrap();
+        let heights: Vec<u32> = log.list().unwrap().iter().map(|c| c.height).collect();
+        assert_eq!(heights, vec![10, 20, 25]);
+
+        let abandoned = log.rewind_to(Some(10)).await.unwrap();
+        assert_eq!(abandoned, vec![checkpoint(20, 1), checkpoint(25, 2)]);
+        assert_eq!(log.latest().unwrap(), Some(checkpoint(10, 1)));
+
+        assert_eq!(log.rewind_to(None).await.unwrap(), vec![checkpoint(10, 1)]);
+        assert!(log.list().unwrap().is_empty());
+
+        let event = ForkEvent {
+            abandoned,
+            common_ancestor: Some(checkpoint(10, 1)),
+        };
+        assert_eq!(event.resync_height(), Some(10));
+    }
+}
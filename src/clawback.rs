@@ -0,0 +1,435 @@
+//! XCH sends the sender can take back until a deadline.
+//!
+//! A clawback coin is locked to a puzzle with two paths. Before the deadline, the sender
+//! can recover it; from the deadline on, the receiver can claim it. The coin is hinted to
+//! the receiver, with the sender and deadline in its memo, so the receiver discovers it
+//! like any received coin. Senders keep their own clawbacks in a local cache, since no
+//! hint points back at them.
+
+use crate::asset::Asset;
+use crate::builder::TransactionBuilder;
+use crate::chain::{fetch_coin_spend, fetch_coin_state, fetch_coin_states, fetch_puzzle_history};
+use crate::coin_records::run_coin_spend;
+use crate::encoding::bytes32_from_hex;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::history::{TransactionDirection, TransactionRecord};
+use crate::transfer::{driver_error, effective_fee};
+use crate::wallet::Wallet;
+use chia::clvm_traits::clvm_list;
+use chia::clvm_utils::ToTreeHash;
+use chia::puzzles::Memos;
+use chia_wallet_sdk::driver::{Action, ClawbackV2, Id, SpendContext, Spends};
+use chia_wallet_sdk::prelude::NodePtr;
+use chia_wallet_sdk::types::{Condition, Conditions};
+use datalayer_driver::{Bytes32, Coin, CoinSpend, Peer};
+use serde::{Deserialize, Serialize};
+
+/// A clawback coin that is neither recovered nor claimed yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClawbackRecord {
+    pub coin_id: String,
+    pub parent_coin_info: String,
+    pub amount: u64,
+    pub sender_puzzle_hash: String,
+    pub receiver_puzzle_hash: String,
+    /// Unix time in seconds at which recovery ends and claiming begins
+    pub expires_at: u64,
+    /// Outgoing if the wallet sent it, incoming if it receives it, internal if both
+    pub direction: TransactionDirection,
+}
+
+impl ClawbackRecord {
+    fn new(coin: Coin, clawback: &ClawbackV2, direction: TransactionDirection) -> Self {
+        Self {
+            coin_id: hex::encode(coin.coin_id()),
+            parent_coin_info: hex::encode(coin.parent_coin_info),
+            amount: coin.amount,
+            sender_puzzle_hash: hex::encode(clawback.sender_puzzle_hash),
+            receiver_puzzle_hash: hex::encode(clawback.receiver_puzzle_hash),
+            expires_at: clawback.seconds,
+            direction,
+        }
+    }
+
+    /// Whether the sender can still recover the coin at unix time `now`
+    pub fn is_recoverable(&self, now: u64) -> bool {
+        now < self.expires_at
+    }
+
+    /// Whether the receiver can claim the coin at unix time `now`
+    pub fn is_claimable(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    pub(crate) fn clawback(&self) -> Result<ClawbackV2, WalletError> {
+        Ok(ClawbackV2::new(
+            bytes32_from_hex(&self.sender_puzzle_hash)?,
+            bytes32_from_hex(&self.receiver_puzzle_hash)?,
+            self.expires_at,
+            self.amount,
+            true,
+        ))
+    }
+
+    /// The clawback coin
+    pub fn coin(&self) -> Result<Coin, WalletError> {
+        Ok(Coin::new(
+            bytes32_from_hex(&self.parent_coin_info)?,
+            self.clawback()?.tree_hash().into(),
+            self.amount,
+        ))
+    }
+}
+
+/// The clawback that `parent_spend` creates `coin` as, read from the coin's memos
+///
+/// `None` if the coin is not a clawback or its parent spend does not run.
+fn parse_clawback(parent_spend: &CoinSpend, coin: &Coin) -> Option<ClawbackV2> {
+    let mut ctx = SpendContext::new();
+    let conditions = run_coin_spend(&mut ctx, parent_spend).ok()?;
+
+    conditions
+        .into_iter()
+        .filter_map(Condition::into_create_coin)
+        .filter(|create_coin| {
+            create_coin.puzzle_hash == coin.puzzle_hash && create_coin.amount == coin.amount
+        })
+        .find_map(|create_coin| {
+            let Memos::Some(memos) = create_coin.memos else {
+                return None;
+            };
+            // The receiver's hint comes first, then the sender and the deadline
+            let (receiver_puzzle_hash, (memo, _)) =
+                ctx.extract::<(Bytes32, (NodePtr, NodePtr))>(memos).ok()?;
+            [true, false].into_iter().find_map(|hinted| {
+                ClawbackV2::from_memo(
+                    &ctx,
+                    memo,
+                    receiver_puzzle_hash,
+                    coin.amount,
+                    hinted,
+                    coin.puzzle_hash,
+                )
+            })
+        })
+}
+
+impl Wallet {
+    /// Locally persisted clawbacks the wallet sent, keyed by coin id
+    fn sent_clawbacks(&self) -> Result<FileCache<ClawbackRecord>, WalletError> {
+        FileCache::from_config(
+            &format!("clawbacks/{}", self.cache_name()),
+            self.get_config(),
+        )
+    }
+
+    /// Send `amount` mojos of XCH that the wallet can recover for `timeout_secs` seconds,
+    /// after which only the receiver can claim them
+    ///
+    /// The clawback is kept in the wallet's cache until it is spent, and the history
+    /// records an outgoing transaction as for [`Wallet::send_xch`].
+    pub async fn send_xch_clawback(
+        &self,
+        peer: &Peer,
+        to_address: &str,
+        amount: u64,
+        timeout_secs: u64,
+        fee: u64,
+    ) -> Result<ClawbackRecord, WalletError> {
+        if amount == 0 {
+            return Err(WalletError::InvalidAmount(
+                "Clawback amount is zero".to_string(),
+            ));
+        }
+        let config = self.get_config();
+        Self::check_fee_floor(peer, fee, config).await?;
+
+        let receiver_puzzle_hash = self.chain_profile().await?.decode_address(to_address)?;
+        let sender_puzzle_hash = self.get_owner_puzzle_hash().await?;
+        let expires_at = config.unix_timestamp().saturating_add(timeout_secs);
+        let clawback = ClawbackV2::new(
+            sender_puzzle_hash,
+            receiver_puzzle_hash,
+            expires_at,
+            amount,
+            true,
+        );
+        let clawback_puzzle_hash: Bytes32 = clawback.tree_hash().into();
+
+        let mut ctx = SpendContext::new();
+        let memos = ctx
+            .memos(&clvm_list!(receiver_puzzle_hash, clawback.memo()))
+            .map_err(driver_error)?;
+        let mut spends = Spends::new(sender_puzzle_hash);
+        let spent_coins = self.select_unspent_coins(peer, amount, fee, vec![]).await?;
+        for &coin in &spent_coins {
+            spends.add(coin);
+        }
+        let mut actions = vec![Action::send(Id::Xch, clawback_puzzle_hash, amount, memos)];
+        if fee > 0 {
+            actions.push(Action::fee(fee));
+        }
+        let deltas = spends.apply(&mut ctx, &actions).map_err(driver_error)?;
+        let outputs = self
+            .spend_keys()
+            .await?
+            .finish_spends(&mut ctx, spends, &deltas)?;
+        let coin = outputs
+            .xch
+            .iter()
+            .find(|coin| coin.puzzle_hash == clawback_puzzle_hash)
+            .copied()
+            .ok_or_else(|| {
+                WalletError::TransactionFailed("Send created no clawback coin".to_string())
+            })?;
+
+        let spend_bundle = self.sign_spend_bundle(ctx.take()).await?;
+        let paid_fee = effective_fee(&spend_bundle.coin_spends)?;
+        let tx_id = self.broadcast_transaction(peer, &spend_bundle).await?;
+
+        let record = ClawbackRecord::new(coin, &clawback, TransactionDirection::Outgoing);
+        self.sent_clawbacks()?.set(&record.coin_id, &record).await?;
+        self.transaction_history()?
+            .record(&TransactionRecord {
+                tx_id: hex::encode(tx_id),
+                asset: Asset::Xch,
+                direction: TransactionDirection::Outgoing,
+                amount,
+                fee: paid_fee,
+                counterparty_puzzle_hash: Some(hex::encode(receiver_puzzle_hash)),
+                counterparty_wallet: None,
+                spent_coin_ids: spent_coins
+                    .iter()
+                    .map(|coin| hex::encode(coin.coin_id()))
+                    .collect(),
+                height: None,
+                timestamp: config.unix_timestamp(),
+            })
+            .await?;
+
+        Ok(record)
+    }
+
+    /// List the unspent clawbacks the wallet sent or receives
+    ///
+    /// Received clawbacks are found through the hints of the wallet's issued addresses.
+    /// Sent ones that were spent are dropped from the cache.
+    pub async fn list_pending_clawbacks(
+        &self,
+        peer: &Peer,
+    ) -> Result<Vec<ClawbackRecord>, WalletError> {
+        let cache = self.sent_clawbacks()?;
+        let mut pending: Vec<ClawbackRecord> = Vec::new();
+        let sent: Vec<ClawbackRecord> = cache
+            .get_cached_keys()?
+            .iter()
+            .filter_map(|coin_id| cache.get(coin_id).transpose())
+            .collect::<Result<_, _>>()?;
+        if !sent.is_empty() {
+            let coin_ids = sent
+                .iter()
+                .map(|record| bytes32_from_hex(&record.coin_id))
+                .collect::<Result<Vec<_>, _>>()?;
+            let states = fetch_coin_states(peer, coin_ids).await?;
+            for record in sent {
+                let state = states
+                    .iter()
+                    .find(|state| hex::encode(state.coin.coin_id()) == record.coin_id);
+                match state {
+                    Some(state) if state.spent_height.is_some() => {
+                        cache.delete(&record.coin_id).await?;
+                    }
+                    Some(_) => pending.push(record),
+                    // Still in the mempool, or dropped from it
+                    None => {}
+                }
+            }
+        }
+
+        for puzzle_hash in self.issued_puzzle_hashes().await? {
+            for coin_state in fetch_puzzle_history(peer, puzzle_hash).await? {
+                let coin = coin_state.coin;
+                if coin_state.spent_height.is_some() || coin.puzzle_hash == puzzle_hash {
+                    continue;
+                }
+                let coin_id = hex::encode(coin.coin_id());
+                if let Some(record) = pending.iter_mut().find(|record| record.coin_id == coin_id) {
+                    record.direction = TransactionDirection::Internal;
+                    continue;
+                }
+                let Some(clawback) = self.prove_clawback(peer, coin).await? else {
+                    continue;
+                };
+                if clawback.receiver_puzzle_hash == puzzle_hash {
+                    pending.push(ClawbackRecord::new(
+                        coin,
+                        &clawback,
+                        TransactionDirection::Incoming,
+                    ));
+                }
+            }
+        }
+
+        Ok(pending)
+    }
+
+    /// Claim a received clawback once its deadline has passed, returning the transaction id
+    ///
+    /// The coin goes to the receiving address; `fee` is paid from the wallet's XCH.
+    pub async fn claim_clawback(
+        &self,
+        peer: &Peer,
+        coin_id: Bytes32,
+        fee: u64,
+    ) -> Result<Bytes32, WalletError> {
+        self.spend_clawback(peer, coin_id, fee, false).await
+    }
+
+    /// Recover a sent clawback before its deadline, returning the transaction id
+    ///
+    /// The coin returns to the sending address; `fee` is paid from the wallet's XCH.
+    pub async fn clawback_refund(
+        &self,
+        peer: &Peer,
+        coin_id: Bytes32,
+        fee: u64,
+    ) -> Result<Bytes32, WalletError> {
+        self.spend_clawback(peer, coin_id, fee, true).await
+    }
+
+    /// The clawback `coin` is, from its parent spend
+    async fn prove_clawback(
+        &self,
+        peer: &Peer,
+        coin: Coin,
+    ) -> Result<Option<ClawbackV2>, WalletError> {
+        let parent = fetch_coin_state(peer, coin.parent_coin_info).await?;
+        let spent_height = parent
+            .spent_height
+            .ok_or_else(|| WalletError::CoinSetError("Parent coin is not spent".to_string()))?;
+        let parent_spend = fetch_coin_spend(peer, parent.coin, spent_height).await?;
+        Ok(parse_clawback(&parent_spend, &coin))
+    }
+
+    async fn spend_clawback(
+        &self,
+        peer: &Peer,
+        coin_id: Bytes32,
+        fee: u64,
+        recover: bool,
+    ) -> Result<Bytes32, WalletError> {
+        let config = self.get_config();
+        Self::check_fee_floor(peer, fee, config).await?;
+
+        let state = fetch_coin_state(peer, coin_id).await?;
+        if state.spent_height.is_some() {
+            return Err(WalletError::CoinSetError(format!(
+                "Clawback coin {} is already spent",
+                hex::encode(coin_id)
+            )));
+        }
+        let clawback = self
+            .prove_clawback(peer, state.coin)
+            .await?
+            .ok_or_else(|| {
+                WalletError::CoinSetError(format!(
+                    "Coin {} is not a clawback",
+                    hex::encode(coin_id)
+                ))
+            })?;
+
+        let now = config.unix_timestamp();
+        let mut ctx = SpendContext::new();
+        let keys = self.spend_keys().await?;
+        let spend = if recover {
+            if now >= clawback.seconds {
+                return Err(WalletError::TransactionFailed(
+                    "Clawback deadline has passed; only the receiver can claim it".to_string(),
+                ));
+            }
+            let layer = keys.layer(clawback.sender_puzzle_hash)?;
+            clawback.recover_spend(&mut ctx, layer, Conditions::new())
+        } else {
+            if now < clawback.seconds {
+                return Err(WalletError::TransactionFailed(format!(
+                    "Clawback can be claimed {} seconds from now",
+                    clawback.seconds - now
+                )));
+            }
+            let layer = keys.layer(clawback.receiver_puzzle_hash)?;
+            clawback.finish_spend(&mut ctx, layer, Conditions::new())
+        }
+        .map_err(driver_error)?;
+        ctx.spend(state.coin, spend).map_err(driver_error)?;
+
+        let mut builder = TransactionBuilder::new().with_fee(fee);
+        for coin_spend in ctx.take() {
+            builder = builder.add_coin_spend(coin_spend);
+        }
+        let builder = self.fund_transaction(peer, builder, vec![]).await?;
+        let spend_bundle = self.sign_transaction(builder).await?;
+        let tx_id = self.broadcast_transaction(peer, &spend_bundle).await?;
+
+        if recover {
+            self.sent_clawbacks()?.delete(&hex::encode(coin_id)).await?;
+        }
+        Ok(tx_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clawback_record() {
+        let clawback = ClawbackV2::new(
+            Bytes32::new([1; 32]),
+            Bytes32::new([2; 32]),
+            1_700_000_000,
+            1_000,
+            true,
+        );
+        let coin = Coin::new(Bytes32::new([3; 32]), clawback.tree_hash().into(), 1_000);
+        let record = ClawbackRecord::new(coin, &clawback, TransactionDirection::Outgoing);
+
+        assert_eq!(record.coin().unwrap(), coin);
+        assert_eq!(record.clawback().unwrap(), clawback);
+        assert!(record.is_recoverable(1_699_999_999));
+        assert!(!record.is_claimable(1_699_999_999));
+        assert!(record.is_claimable(1_700_000_000));
+        assert!(!record.is_recoverable(1_700_000_000));
+
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ClawbackRecord>(&json).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    fn test_parse_clawback_memos() {
+        let sender = Bytes32::new([1; 32]);
+        let receiver = Bytes32::new([2; 32]);
+        let clawback = ClawbackV2::new(sender, receiver, 1_700_000_000, 1_000, true);
+        let clawback_puzzle_hash: Bytes32 = clawback.tree_hash().into();
+
+        // A parent whose puzzle simply returns its solution as conditions
+        let mut ctx = SpendContext::new();
+        let memos = ctx.memos(&clvm_list!(receiver, clawback.memo())).unwrap();
+        let conditions = Conditions::new().create_coin(clawback_puzzle_hash, 1_000, memos);
+        let parent = Coin::new(Bytes32::new([3; 32]), Bytes32::new([4; 32]), 1_000);
+        let parent_spend = CoinSpend::new(
+            parent,
+            ctx.serialize(&1u8).unwrap(),
+            ctx.serialize(&conditions).unwrap(),
+        );
+
+        let coin = Coin::new(parent.coin_id(), clawback_puzzle_hash, 1_000);
+        assert_eq!(parse_clawback(&parent_spend, &coin), Some(clawback));
+
+        let other = Coin::new(parent.coin_id(), Bytes32::new([5; 32]), 1_000);
+        assert_eq!(parse_clawback(&parent_spend, &other), None);
+    }
+}
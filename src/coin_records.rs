@@ -0,0 +1,265 @@
+use crate::asset::Asset;
+use crate::chain::{fetch_coin_spend, fetch_coin_state};
+use crate::config::WalletConfig;
+use crate::encoding::bytes_from_hex;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::wallet::Wallet;
+use chia::puzzles::Memos;
+use chia_wallet_sdk::driver::{DriverError, SpendContext};
+use chia_wallet_sdk::types::Condition;
+use datalayer_driver::{Bytes, Bytes32, Coin, CoinSpend, Peer};
+use serde::{Deserialize, Serialize};
+
+/// A received coin together with the memos its creating spend attached to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinRecord {
+    pub coin_id: String,
+    pub parent_coin_info: String,
+    pub puzzle_hash: String,
+    pub amount: u64,
+    pub created_height: Option<u32>,
+    /// CREATE_COIN memos as hex, in order
+    pub memos: Vec<String>,
+}
+
+impl CoinRecord {
+    /// The memos as raw bytes
+    pub fn memo_bytes(&self) -> Result<Vec<Bytes>, WalletError> {
+        self.memos
+            .iter()
+            .map(|memo| bytes_from_hex(memo).map(Bytes::from))
+            .collect()
+    }
+}
+
+/// Locally persisted coin records of one wallet, keyed by coin id
+pub struct CoinRecordCache {
+    cache: FileCache<CoinRecord>,
+}
+
+impl CoinRecordCache {
+    /// Open the coin record cache of a wallet under the configured `.dig` directory
+    pub fn open(wallet_name: &str, config: &WalletConfig) -> Result<Self, WalletError> {
+        Ok(Self {
+            cache: FileCache::from_config(&format!("coin_records/{}", wallet_name), config)?,
+        })
+    }
+
+    /// Insert or replace a record
+    pub async fn record(&self, record: &CoinRecord) -> Result<(), WalletError> {
+        self.cache.set(&record.coin_id, record).await
+    }
+
+    /// Get a record by coin id
+    pub fn get(&self, coin_id: Bytes32) -> Result<Option<CoinRecord>, WalletError> {
+        self.cache.get(&hex::encode(coin_id))
+    }
+}
+
+fn clvm_error(e: DriverError) -> WalletError {
+    WalletError::DataLayerError(format!("Failed to run coin spend: {}", e))
+}
+
+/// Run a coin spend and return the conditions it outputs
+pub(crate) fn run_coin_spend(
+    ctx: &mut SpendContext,
+    coin_spend: &CoinSpend,
+) -> Result<Vec<Condition>, WalletError> {
+    let puzzle = ctx.alloc(&coin_spend.puzzle_reveal).map_err(clvm_error)?;
+    let solution = ctx.alloc(&coin_spend.solution).map_err(clvm_error)?;
+    let output = ctx.run(puzzle, solution).map_err(clvm_error)?;
+    ctx.extract(output).map_err(clvm_error)
+}
+
+/// Run a parent spend and return the memos of the CREATE_COIN that produced `coin`
+pub(crate) fn extract_create_coin_memos(
+    parent_spend: &CoinSpend,
+    coin: &Coin,
+) -> Result<Vec<Bytes>, WalletError> {
+    let mut ctx = SpendContext::new();
+    let conditions = run_coin_spend(&mut ctx, parent_spend)?;
+
+    for condition in conditions {
+        let Some(create_coin) = condition.into_create_coin() else {
+            continue;
+        };
+        if create_coin.puzzle_hash != coin.puzzle_hash || create_coin.amount != coin.amount {
+            continue;
+        }
+
+        return match create_coin.memos {
+            Memos::None => Ok(vec![]),
+            // Memos that are not a plain list of atoms carry nothing reconcilable
+            Memos::Some(node) => Ok(ctx.extract::<Vec<Bytes>>(node).unwrap_or_default()),
+        };
+    }
+
+    Err(WalletError::CoinSetError(format!(
+        "Parent spend does not create coin {}",
+        coin.coin_id()
+    )))
+}
+
+impl Wallet {
+    /// Open the locally persisted coin record cache of this wallet
+    pub fn coin_records(&self) -> Result<CoinRecordCache, WalletError> {
+        CoinRecordCache::open(&self.cache_name(), self.get_config())
+    }
+
+    /// Get the CREATE_COIN memos of a coin, fetching and caching them on first use
+    pub async fn get_coin_memos(
+        &self,
+        peer: &Peer,
+        coin_id: Bytes32,
+    ) -> Result<Vec<Bytes>, WalletError> {
+        self.get_coin_record(peer, coin_id).await?.memo_bytes()
+    }
+
+    /// Get the record of a coin, fetching and caching it on first use
+    pub async fn get_coin_record(
+        &self,
+        peer: &Peer,
+        coin_id: Bytes32,
+    ) -> Result<CoinRecord, WalletError> {
+        let cache = self.coin_records()?;
+        if let Some(record) = cache.get(coin_id)? {
+            return Ok(record);
+        }
+
+        let record = Self::fetch_coin_record(peer, coin_id).await?;
+        cache.record(&record).await?;
+        Ok(record)
+    }
+
+    /// Unspent coins of `asset` at the wallet's issued addresses, with the memos they were
+    /// created with
+    ///
+    /// A hinted coin's first memo is the puzzle hash it was sent to, and any memos the
+    /// sender attached follow it. Records are cached, so only new coins cost peer requests.
+    pub async fn list_received_coins(
+        &self,
+        peer: &Peer,
+        asset: Asset,
+    ) -> Result<Vec<CoinRecord>, WalletError> {
+        let coins: Vec<Coin> = match asset {
+            Asset::Xch => self.get_all_unspent_xch_coins(peer, vec![]).await?,
+            #[cfg(not(feature = "cat"))]
+            Asset::Dig | Asset::Cat(_) => {
+                return Err(WalletError::FeatureDisabled("cat".to_string()))
+            }
+            #[cfg(feature = "cat")]
+            Asset::Dig => self
+                .get_all_unspent_dig_coins(peer, vec![], false)
+                .await?
+                .proved
+                .iter()
+                .map(|dig_coin| dig_coin.cat().coin)
+                .collect(),
+            #[cfg(feature = "cat")]
+            Asset::Cat(asset_id) => self
+                .get_all_unspent_cats(peer, asset_id, vec![])
+                .await?
+                .iter()
+                .map(|cat| cat.coin)
+                .collect(),
+        };
+
+        let mut records = Vec::with_capacity(coins.len());
+        for coin in coins {
+            records.push(self.get_coin_record(peer, coin.coin_id()).await?);
+        }
+        Ok(records)
+    }
+
+    /// Fetch and cache memos for every unspent XCH and DIG coin not yet in the cache
+    ///
+    /// Returns the number of newly cached records.
+    pub async fn sync_coin_memos(&self, peer: &Peer) -> Result<usize, WalletError> {
+        let cache = self.coin_records()?;
+
+        #[cfg_attr(not(feature = "cat"), allow(unused_mut))]
+        let mut coins = self.get_all_unspent_xch_coins(peer, vec![]).await?;
+        #[cfg(feature = "cat")]
+        coins.extend(
+            self.get_all_unspent_dig_coins(peer, vec![], false)
+                .await?
+                .proved
+                .iter()
+                .map(|dig_coin| dig_coin.cat().coin),
+        );
+
+        let mut synced = 0;
+        for coin in coins {
+            let coin_id = coin.coin_id();
+            if cache.get(coin_id)?.is_some() {
+                continue;
+            }
+            cache
+                .record(&Self::fetch_coin_record(peer, coin_id).await?)
+                .await?;
+            synced += 1;
+        }
+
+        Ok(synced)
+    }
+
+    async fn fetch_coin_record(peer: &Peer, coin_id: Bytes32) -> Result<CoinRecord, WalletError> {
+        let coin_state = fetch_coin_state(peer, coin_id).await?;
+        let coin = coin_state.coin;
+
+        // Farming rewards and coins whose parent is unknown carry no memos
+        let memos = match fetch_coin_state(peer, coin.parent_coin_info).await {
+            Ok(parent_state) => match parent_state.spent_height {
+                Some(spent_height) => {
+                    let parent_spend =
+                        fetch_coin_spend(peer, parent_state.coin, spent_height).await?;
+                    extract_create_coin_memos(&parent_spend, &coin)?
+                }
+                None => vec![],
+            },
+            Err(WalletError::CoinSetError(_)) => vec![],
+            Err(e) => return Err(e),
+        };
+
+        Ok(CoinRecord {
+            coin_id: hex::encode(coin_id),
+            parent_coin_info: hex::encode(coin.parent_coin_info),
+            puzzle_hash: hex::encode(coin.puzzle_hash),
+            amount: coin.amount,
+            created_height: coin_state.created_height,
+            memos: memos.iter().map(hex::encode).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_coin_record_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let cache = CoinRecordCache::open("memo_test", &config).unwrap();
+
+        let coin = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 42);
+        let record = CoinRecord {
+            coin_id: hex::encode(coin.coin_id()),
+            parent_coin_info: hex::encode(coin.parent_coin_info),
+            puzzle_hash: hex::encode(coin.puzzle_hash),
+            amount: coin.amount,
+            created_height: Some(10),
+            memos: vec![hex::encode(coin.puzzle_hash), hex::encode(b"invoice-17")],
+        };
+        cache.record(&record).await.unwrap();
+
+        let cached = cache.get(coin.coin_id()).unwrap().unwrap();
+        let memos = cached.memo_bytes().unwrap();
+        assert_eq!(memos.len(), 2);
+        assert_eq!(memos[1].as_ref(), b"invoice-17");
+        assert!(cache.get(Bytes32::new([9; 32])).unwrap().is_none());
+    }
+}
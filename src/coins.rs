@@ -0,0 +1,785 @@
+//! Coin discovery, selection and balances over a connected peer.
+
+use crate::cancel::CancellationToken;
+use crate::chain::fetch_unspent_coins;
+#[cfg(feature = "cat")]
+use crate::chain::{fetch_coin_state, request_coin_spend};
+use crate::error::WalletError;
+use crate::fees::FeeTarget;
+#[cfg(feature = "cat")]
+use crate::provenance::puzzle_mod_hash;
+use crate::wallet::Wallet;
+use datalayer_driver::{get_coin_id, Bytes32, Coin, CoinSpend, Peer};
+#[cfg(feature = "cat")]
+use datalayer_driver::{CoinState, DigCoin};
+#[cfg(feature = "cat")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+#[cfg(feature = "cat")]
+use std::collections::{BTreeMap, HashSet};
+#[cfg(feature = "cat")]
+use std::fmt;
+
+/// Limits applied on top of the default coin selection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SelectionOptions {
+    max_inputs: Option<usize>,
+}
+
+impl SelectionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse selections that need more than `n` inputs
+    ///
+    /// Oversized selections are first retried with the largest coins; if even those need
+    /// more than `n` inputs, selection fails with [`WalletError::SelectionTooFragmented`].
+    pub fn max_inputs(mut self, n: usize) -> Self {
+        self.max_inputs = Some(n);
+        self
+    }
+
+    fn limit_inputs(
+        &self,
+        selected: Vec<Coin>,
+        available: &[Coin],
+        target: u64,
+    ) -> Result<Vec<Coin>, WalletError> {
+        let max_inputs = match self.max_inputs {
+            Some(max_inputs) if selected.len() > max_inputs => max_inputs,
+            _ => return Ok(selected),
+        };
+
+        let largest_first = largest_first(available, target);
+        if largest_first.len() > max_inputs {
+            return Err(WalletError::SelectionTooFragmented {
+                needed_inputs: largest_first.len(),
+                max_inputs,
+            });
+        }
+
+        Ok(largest_first)
+    }
+}
+
+/// The fewest largest coins covering `target`, or all coins if they fall short
+fn largest_first(available: &[Coin], target: u64) -> Vec<Coin> {
+    let mut largest_first = available.to_vec();
+    largest_first.sort_by_key(|coin| Reverse(coin.amount));
+
+    let mut total = 0u64;
+    let needed_inputs = largest_first
+        .iter()
+        .position(|coin| {
+            total = total.saturating_add(coin.amount);
+            total >= target
+        })
+        .map_or(largest_first.len(), |index| index + 1);
+
+    largest_first.truncate(needed_inputs);
+    largest_first
+}
+
+/// How a candidate selection was built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// `datalayer_driver::select_coins`, used unless an input limit intervenes
+    Default,
+    /// Largest coins first, the fallback when the default needs too many inputs
+    LargestFirst,
+}
+
+/// Coins a strategy would spend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionCandidate {
+    pub strategy: SelectionStrategy,
+    pub coins: Vec<Coin>,
+    pub total: u64,
+    /// Amount returned as change; zero when the coins fall short
+    pub change: u64,
+}
+
+impl SelectionCandidate {
+    fn new(strategy: SelectionStrategy, coins: Vec<Coin>, target: u64) -> Self {
+        let total = coins.iter().map(|coin| coin.amount).sum::<u64>();
+        Self {
+            strategy,
+            coins,
+            total,
+            change: total.saturating_sub(target),
+        }
+    }
+}
+
+/// Dry run of coin selection, returned by [`Wallet::explain_selection`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionExplanation {
+    /// Amount plus fee
+    pub target: u64,
+    pub available_coins: usize,
+    pub available_amount: u64,
+    /// The selection the wallet would make; `None` when selection would fail
+    pub chosen: Option<SelectionCandidate>,
+    /// Why selection would fail
+    pub failure: Option<String>,
+    /// The decisions taken, in order
+    pub trace: Vec<String>,
+    /// Candidates considered but not chosen
+    pub alternatives: Vec<SelectionCandidate>,
+}
+
+impl SelectionExplanation {
+    /// Replay the selection of [`Wallet::select_unspent_coins_with_options`] over `available`
+    fn explain(available: &[Coin], target: u64, options: &SelectionOptions) -> Self {
+        let available_amount = available.iter().map(|coin| coin.amount).sum::<u64>();
+        let mut explanation = Self {
+            target,
+            available_coins: available.len(),
+            available_amount,
+            chosen: None,
+            failure: None,
+            trace: vec![format!(
+                "{} unspent coins hold {} mojos for a target of {} mojos",
+                available.len(),
+                available_amount,
+                target
+            )],
+            alternatives: Vec::new(),
+        };
+
+        let fallback = SelectionCandidate::new(
+            SelectionStrategy::LargestFirst,
+            largest_first(available, target),
+            target,
+        );
+        let default = match datalayer_driver::select_coins(available, target) {
+            Ok(coins) if !coins.is_empty() => {
+                SelectionCandidate::new(SelectionStrategy::Default, coins, target)
+            }
+            Ok(_) => {
+                explanation.fail(WalletError::NoUnspentCoins.to_string());
+                explanation.alternatives.push(fallback);
+                return explanation;
+            }
+            Err(e) => {
+                explanation.fail(format!("Coin selection failed: {}", e));
+                explanation.alternatives.push(fallback);
+                return explanation;
+            }
+        };
+        explanation.trace.push(format!(
+            "default strategy picked {} coins totalling {} mojos, {} mojos change",
+            default.coins.len(),
+            default.total,
+            default.change
+        ));
+
+        match options.max_inputs {
+            Some(max_inputs) if default.coins.len() > max_inputs => {
+                explanation.trace.push(format!(
+                    "{} inputs exceed the limit of {}; retrying with the largest coins first",
+                    default.coins.len(),
+                    max_inputs
+                ));
+                explanation.alternatives.push(default);
+                if fallback.coins.len() > max_inputs {
+                    explanation.fail(
+                        WalletError::SelectionTooFragmented {
+                            needed_inputs: fallback.coins.len(),
+                            max_inputs,
+                        }
+                        .to_string(),
+                    );
+                    explanation.alternatives.push(fallback);
+                } else {
+                    explanation.trace.push(format!(
+                        "largest coins first picked {} coins totalling {} mojos",
+                        fallback.coins.len(),
+                        fallback.total
+                    ));
+                    explanation.chosen = Some(fallback);
+                }
+            }
+            _ => {
+                explanation.chosen = Some(default);
+                explanation.alternatives.push(fallback);
+            }
+        }
+
+        explanation
+    }
+
+    fn fail(&mut self, reason: String) {
+        self.trace.push(format!("selection fails: {}", reason));
+        self.failure = Some(reason);
+    }
+}
+
+impl Wallet {
+    pub async fn get_all_unspent_xch_coins(
+        &self,
+        peer: &Peer,
+        omit_coins: Vec<Coin>,
+    ) -> Result<Vec<Coin>, WalletError> {
+        self.get_all_unspent_xch_coins_with_cancellation(
+            peer,
+            omit_coins,
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// Get all unspent XCH coins at the wallet's issued addresses, aborting the peer
+    /// requests once `cancellation` fires
+    ///
+    /// Coins spent by a pending transaction are left out, see [`Wallet::pending_transactions`].
+    pub async fn get_all_unspent_xch_coins_with_cancellation(
+        &self,
+        peer: &Peer,
+        omit_coins: Vec<Coin>,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<Coin>, WalletError> {
+        let mut coin_states = Vec::new();
+        for puzzle_hash in self.issued_puzzle_hashes().await? {
+            let (states, _) = cancellation
+                .run(fetch_unspent_coins(peer, puzzle_hash))
+                .await?;
+            coin_states.extend(states);
+        }
+
+        // Convert coin states to coins and filter out omitted and in-flight coins
+        let mut omit_coin_ids: Vec<Bytes32> = omit_coins.iter().map(get_coin_id).collect();
+        omit_coin_ids.extend(self.in_flight_coin_ids()?);
+
+        Ok(coin_states
+            .into_iter()
+            .map(|cs| cs.coin)
+            .filter(|coin| !omit_coin_ids.contains(&get_coin_id(coin)))
+            .collect())
+    }
+
+    /// Select unspent coins for spending
+    pub async fn select_unspent_coins(
+        &self,
+        peer: &Peer,
+        coin_amount: u64,
+        fee: u64,
+        omit_coins: Vec<Coin>,
+    ) -> Result<Vec<Coin>, WalletError> {
+        self.select_unspent_coins_with_cancellation(
+            peer,
+            coin_amount,
+            fee,
+            omit_coins,
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// Select unspent coins for spending, aborting once `cancellation` fires
+    pub async fn select_unspent_coins_with_cancellation(
+        &self,
+        peer: &Peer,
+        coin_amount: u64,
+        fee: u64,
+        omit_coins: Vec<Coin>,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<Coin>, WalletError> {
+        self.select_unspent_coins_with_options(
+            peer,
+            coin_amount,
+            fee,
+            omit_coins,
+            &SelectionOptions::default(),
+            cancellation,
+        )
+        .await
+    }
+
+    /// Select unspent coins for spending within the limits of `options`
+    pub async fn select_unspent_coins_with_options(
+        &self,
+        peer: &Peer,
+        coin_amount: u64,
+        fee: u64,
+        omit_coins: Vec<Coin>,
+        options: &SelectionOptions,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<Coin>, WalletError> {
+        let total_needed = coin_amount + fee;
+
+        let available_coins = self
+            .get_all_unspent_xch_coins_with_cancellation(peer, omit_coins, cancellation)
+            .await?;
+
+        // Use the DataLayer-Driver's select_coins function
+        let selected_coins = datalayer_driver::select_coins(&available_coins, total_needed)
+            .map_err(|e| WalletError::DataLayerError(format!("Coin selection failed: {}", e)))?;
+
+        if selected_coins.is_empty() {
+            return Err(WalletError::NoUnspentCoins);
+        }
+
+        options.limit_inputs(selected_coins, &available_coins, total_needed)
+    }
+
+    /// Dry-run XCH coin selection for `amount` plus `fee`, reporting the coins that would be
+    /// chosen, why, and the alternatives considered
+    ///
+    /// Nothing is reserved or spent. A selection that would fail is reported through
+    /// [`SelectionExplanation::failure`] rather than an error.
+    pub async fn explain_selection(
+        &self,
+        peer: &Peer,
+        amount: u64,
+        fee: u64,
+        options: &SelectionOptions,
+    ) -> Result<SelectionExplanation, WalletError> {
+        let available_coins = self.get_all_unspent_xch_coins(peer, vec![]).await?;
+        Ok(SelectionExplanation::explain(
+            &available_coins,
+            amount + fee,
+            options,
+        ))
+    }
+
+    pub async fn get_xch_balance(&self, peer: &Peer) -> Result<u64, WalletError> {
+        let xch_coins = self.get_all_unspent_xch_coins(peer, vec![]).await?;
+        let xch_balance = xch_coins.iter().map(|c| c.amount).sum::<u64>();
+        Ok(xch_balance)
+    }
+
+    /// Calculate fee for coin spends
+    ///
+    /// The cost of the spends priced at the peer's current rate for inclusion within
+    /// [`DEFAULT_FEE_TARGET_SECS`](crate::fees::DEFAULT_FEE_TARGET_SECS); see
+    /// [`Wallet::estimate_fee`] to choose the target.
+    pub async fn calculate_fee_for_coin_spends(
+        peer: &Peer,
+        coin_spends: Option<&[CoinSpend]>,
+    ) -> Result<u64, WalletError> {
+        let estimate = Self::estimate_fee(peer, coin_spends, FeeTarget::default()).await?;
+        Ok(estimate.fee)
+    }
+
+    /// Check if a coin is spendable
+    pub async fn is_coin_spendable(peer: &Peer, coin_id: &Bytes32) -> Result<bool, WalletError> {
+        // Check if coin is spent using the DataLayer-Driver API
+        let is_spent = datalayer_driver::is_coin_spent(
+            peer,
+            *coin_id,
+            None,                                                         // last_height
+            datalayer_driver::constants::get_mainnet_genesis_challenge(), // Use mainnet for now
+        )
+        .await
+        .map_err(|e| WalletError::NetworkError(format!("Failed to check coin status: {}", e)))?;
+
+        // Return true if coin is NOT spent (i.e., is spendable)
+        Ok(!is_spent)
+    }
+}
+
+/// Why a coin at the DIG puzzle hash was left out of a sync
+#[cfg(feature = "cat")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "lowercase")]
+pub enum SkipReason {
+    /// The parent coin or its spend could not be fetched; retrying may recover the coin
+    Network(String),
+    /// The peer does not hold the parent coin or its spend, as with pruned nodes
+    Pruned(String),
+    /// The parent spend was fetched but does not prove a DIG CAT lineage
+    Lineage(String),
+    /// The parent spend could not be parsed
+    Parse(String),
+}
+
+/// Broad class of a [`SkipReason`], for aggregating sync failures
+#[cfg(feature = "cat")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipCategory {
+    PeerTransient,
+    PrunedNode,
+    LineageInvalid,
+    ParseError,
+}
+
+#[cfg(feature = "cat")]
+impl SkipReason {
+    pub fn category(&self) -> SkipCategory {
+        match self {
+            SkipReason::Network(_) => SkipCategory::PeerTransient,
+            SkipReason::Pruned(_) => SkipCategory::PrunedNode,
+            SkipReason::Lineage(_) => SkipCategory::LineageInvalid,
+            SkipReason::Parse(_) => SkipCategory::ParseError,
+        }
+    }
+}
+
+#[cfg(feature = "cat")]
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Network(detail) => write!(f, "network: {}", detail),
+            SkipReason::Pruned(detail) => write!(f, "pruned: {}", detail),
+            SkipReason::Lineage(detail) => write!(f, "lineage: {}", detail),
+            SkipReason::Parse(detail) => write!(f, "parse: {}", detail),
+        }
+    }
+}
+
+/// Result of a DIG coin sync: coins with a proven lineage and the coins that were skipped
+#[cfg(feature = "cat")]
+#[derive(Default)]
+pub struct SyncReport {
+    pub proved: Vec<DigCoin>,
+    pub skipped: Vec<(Bytes32, SkipReason)>,
+    /// Coins retried after a transient failure in the first pass
+    pub retried: usize,
+    /// Retried coins proved in the second pass
+    pub recovered: usize,
+}
+
+#[cfg(feature = "cat")]
+impl SyncReport {
+    /// DIG mojos held in proved coins
+    pub fn proved_amount(&self) -> u64 {
+        self.proved
+            .iter()
+            .map(|dig_coin| dig_coin.cat().coin.amount)
+            .sum()
+    }
+
+    /// Whether any coin was skipped for a reason a retry might fix
+    pub fn has_network_skips(&self) -> bool {
+        self.skipped
+            .iter()
+            .any(|(_, reason)| reason.category() == SkipCategory::PeerTransient)
+    }
+
+    /// Number of skipped coins in each category
+    pub fn skips_by_category(&self) -> BTreeMap<SkipCategory, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, reason) in &self.skipped {
+            *counts.entry(reason.category()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn skip(&mut self, coin_id: Bytes32, reason: SkipReason, verbose: bool) {
+        if verbose {
+            eprintln!("ERROR: coin_id {} | {}", coin_id, reason);
+        }
+        self.skipped.push((coin_id, reason));
+    }
+}
+
+/// Classify a lineage failure by fetching and parsing the parent spend directly
+#[cfg(feature = "cat")]
+async fn classify_skip(
+    peer: &Peer,
+    coin_state: &CoinState,
+    error: impl fmt::Display,
+    cancellation: &CancellationToken,
+) -> Result<SkipReason, WalletError> {
+    let parent_spend = cancellation.run(async {
+        let parent = fetch_coin_state(peer, coin_state.coin.parent_coin_info).await?;
+        let spent_height = parent
+            .spent_height
+            .ok_or_else(|| WalletError::CoinSetError("Parent coin is not spent".to_string()))?;
+        request_coin_spend(peer, parent.coin, spent_height).await
+    });
+
+    Ok(match parent_spend.await {
+        Err(WalletError::Cancelled) => return Err(WalletError::Cancelled),
+        Err(WalletError::NetworkError(detail)) => SkipReason::Network(detail),
+        Err(WalletError::CoinSetError(detail)) if detail.ends_with("not found") => {
+            SkipReason::Pruned(detail)
+        }
+        Err(_) => SkipReason::Lineage(error.to_string()),
+        Ok(Err(rejection)) => SkipReason::Pruned(format!(
+            "Puzzle and solution request rejected: {}",
+            rejection
+        )),
+        Ok(Ok(coin_spend)) => match puzzle_mod_hash(&coin_spend) {
+            Err(parse_error) => SkipReason::Parse(parse_error.to_string()),
+            Ok(_) => SkipReason::Lineage(error.to_string()),
+        },
+    })
+}
+
+/// Prove one coin's lineage, classifying the failure if it cannot be proved
+#[cfg(feature = "cat")]
+async fn prove_dig_coin(
+    peer: &Peer,
+    coin_state: &CoinState,
+    cancellation: &CancellationToken,
+) -> Result<Result<DigCoin, SkipReason>, WalletError> {
+    let parsed = cancellation
+        .run(async { Ok(DigCoin::from_coin_state(peer, coin_state).await) })
+        .await?;
+    match parsed {
+        Ok(parsed_cat) => Ok(Ok(parsed_cat)),
+        Err(error) => Ok(Err(
+            classify_skip(peer, coin_state, error, cancellation).await?
+        )),
+    }
+}
+
+#[cfg(feature = "cat")]
+impl Wallet {
+    /// Get all unspent DIG Token coins at the wallet's issued addresses, proving each
+    /// coin's lineage
+    ///
+    /// Coins whose lineage cannot be proved are reported in [`SyncReport::skipped`]
+    /// instead of failing the whole sync; `verbose` also prints them to stderr. Coins that
+    /// failed for a transient peer error are retried once before being reported.
+    pub async fn get_all_unspent_dig_coins(
+        &self,
+        peer: &Peer,
+        omit_coins: Vec<Coin>,
+        verbose: bool,
+    ) -> Result<SyncReport, WalletError> {
+        self.get_all_unspent_dig_coins_with_cancellation(
+            peer,
+            omit_coins,
+            verbose,
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// Get all unspent DIG coins, stopping before the next peer request once
+    /// `cancellation` fires; a cancelled sync returns no partial report
+    pub async fn get_all_unspent_dig_coins_with_cancellation(
+        &self,
+        peer: &Peer,
+        omit_coins: Vec<Coin>,
+        verbose: bool,
+        cancellation: &CancellationToken,
+    ) -> Result<SyncReport, WalletError> {
+        let mut unspent_coin_states = Vec::new();
+        for puzzle_hash in self.issued_puzzle_hashes().await? {
+            let dig_ph = DigCoin::puzzle_hash(puzzle_hash);
+            let (states, _) = cancellation.run(fetch_unspent_coins(peer, dig_ph)).await?;
+            unspent_coin_states.extend(states);
+        }
+
+        // Filter out omitted and in-flight coins
+        let mut omit_coin_ids: Vec<Bytes32> = omit_coins.iter().map(get_coin_id).collect();
+        omit_coin_ids.extend(self.in_flight_coin_ids()?);
+        let available_coin_states: Vec<CoinState> = unspent_coin_states
+            .into_iter()
+            .filter(|coin_state| !omit_coin_ids.contains(&get_coin_id(&coin_state.coin)))
+            .collect();
+
+        let mut report = SyncReport::default();
+        let mut transient = Vec::new();
+
+        for coin_state in &available_coin_states {
+            match prove_dig_coin(peer, coin_state, cancellation).await? {
+                Ok(parsed_cat) => report.proved.push(parsed_cat),
+                Err(reason) if reason.category() == SkipCategory::PeerTransient => {
+                    transient.push(coin_state)
+                }
+                Err(reason) => report.skip(coin_state.coin.coin_id(), reason, verbose),
+            }
+        }
+
+        // One flaky request should not hide a coin, so retry transient failures once
+        report.retried = transient.len();
+        for coin_state in transient {
+            match prove_dig_coin(peer, coin_state, cancellation).await? {
+                Ok(parsed_cat) => {
+                    report.proved.push(parsed_cat);
+                    report.recovered += 1;
+                }
+                Err(reason) => report.skip(coin_state.coin.coin_id(), reason, verbose),
+            }
+        }
+
+        Ok(report)
+    }
+
+    pub async fn select_unspent_dig_coins(
+        &self,
+        peer: &Peer,
+        coin_amount: u64,
+        omit_coins: Vec<Coin>,
+        verbose: bool,
+    ) -> Result<Vec<DigCoin>, WalletError> {
+        self.select_unspent_dig_coins_with_cancellation(
+            peer,
+            coin_amount,
+            omit_coins,
+            verbose,
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// Select unspent DIG coins, aborting once `cancellation` fires
+    pub async fn select_unspent_dig_coins_with_cancellation(
+        &self,
+        peer: &Peer,
+        coin_amount: u64,
+        omit_coins: Vec<Coin>,
+        verbose: bool,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<DigCoin>, WalletError> {
+        self.select_unspent_dig_coins_with_options(
+            peer,
+            coin_amount,
+            omit_coins,
+            verbose,
+            &SelectionOptions::default(),
+            cancellation,
+        )
+        .await
+    }
+
+    /// Select unspent DIG coins within the limits of `options`
+    pub async fn select_unspent_dig_coins_with_options(
+        &self,
+        peer: &Peer,
+        coin_amount: u64,
+        omit_coins: Vec<Coin>,
+        verbose: bool,
+        options: &SelectionOptions,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<DigCoin>, WalletError> {
+        let available_dig_cats = self
+            .get_all_unspent_dig_coins_with_cancellation(peer, omit_coins, verbose, cancellation)
+            .await?
+            .proved;
+
+        let dig_coins = available_dig_cats
+            .iter()
+            .map(|dig_coin| dig_coin.cat().coin)
+            .collect::<Vec<_>>();
+
+        // Use the DataLayer-Driver's select_coins function
+        let selected_coins = datalayer_driver::select_coins(&dig_coins, coin_amount)
+            .map_err(|e| WalletError::DataLayerError(format!("Coin selection failed: {}", e)))?;
+
+        if selected_coins.is_empty() {
+            return Err(WalletError::NoUnspentCoins);
+        }
+        let selected_coins = options.limit_inputs(selected_coins, &dig_coins, coin_amount)?;
+
+        let selected_coins_ids: HashSet<Bytes32> = selected_coins.iter().map(get_coin_id).collect();
+        let dig_coin = available_dig_cats
+            .into_iter()
+            .filter(|dig_coin| selected_coins_ids.contains(&dig_coin.cat().coin.coin_id()))
+            .collect::<Vec<_>>();
+
+        Ok(dig_coin)
+    }
+
+    pub async fn get_dig_balance(&self, peer: &Peer, verbose: bool) -> Result<u64, WalletError> {
+        let report = self
+            .get_all_unspent_dig_coins(peer, vec![], verbose)
+            .await?;
+        Ok(report.proved_amount())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "cat")]
+    #[test]
+    fn test_skip_reason_serialization() {
+        let reason = SkipReason::Lineage("parent is not a CAT".to_string());
+        let json = serde_json::to_string(&reason).unwrap();
+        assert_eq!(json, r#"{"kind":"lineage","detail":"parent is not a CAT"}"#);
+        assert_eq!(serde_json::from_str::<SkipReason>(&json).unwrap(), reason);
+        assert_eq!(reason.to_string(), "lineage: parent is not a CAT");
+
+        let report = SyncReport {
+            proved: vec![],
+            skipped: vec![
+                (
+                    Bytes32::default(),
+                    SkipReason::Network("timeout".to_string()),
+                ),
+                (
+                    Bytes32::default(),
+                    SkipReason::Pruned("rejected".to_string()),
+                ),
+                (
+                    Bytes32::default(),
+                    SkipReason::Pruned("not found".to_string()),
+                ),
+            ],
+            retried: 1,
+            recovered: 0,
+        };
+        assert!(report.has_network_skips());
+        assert_eq!(report.proved_amount(), 0);
+        assert_eq!(
+            report.skips_by_category(),
+            BTreeMap::from([
+                (SkipCategory::PeerTransient, 1),
+                (SkipCategory::PrunedNode, 2)
+            ])
+        );
+        assert_eq!(
+            serde_json::to_string(&SkipCategory::LineageInvalid).unwrap(),
+            r#""lineage-invalid""#
+        );
+    }
+
+    #[test]
+    fn test_max_inputs_prefers_largest_coins() {
+        let coin =
+            |amount: u64| Coin::new(Bytes32::new([amount as u8; 32]), Bytes32::default(), amount);
+        let dust: Vec<Coin> = (1..=5).map(coin).collect();
+        let mut available = dust.clone();
+        available.push(coin(20));
+
+        let options = SelectionOptions::new().max_inputs(2);
+        let selected = options.limit_inputs(dust.clone(), &available, 15).unwrap();
+        assert_eq!(selected, vec![coin(20)]);
+
+        match options.limit_inputs(dust.clone(), &dust, 12) {
+            Err(WalletError::SelectionTooFragmented {
+                needed_inputs,
+                max_inputs,
+            }) => assert_eq!((needed_inputs, max_inputs), (3, 2)),
+            other => panic!("expected a fragmented selection, got {:?}", other),
+        }
+
+        let unlimited = SelectionOptions::default();
+        assert_eq!(
+            unlimited.limit_inputs(dust.clone(), &dust, 15).unwrap(),
+            dust
+        );
+    }
+
+    #[test]
+    fn test_explain_selection_reports_choice_and_failure() {
+        let coin =
+            |amount: u64| Coin::new(Bytes32::new([amount as u8; 32]), Bytes32::default(), amount);
+        let available: Vec<Coin> = [1, 2, 3, 4, 20].into_iter().map(coin).collect();
+
+        let unlimited = SelectionExplanation::explain(&available, 15, &SelectionOptions::new());
+        let chosen = unlimited.chosen.unwrap();
+        assert_eq!(chosen.strategy, SelectionStrategy::Default);
+        assert_eq!(chosen.change, chosen.total - 15);
+        assert_eq!(unlimited.alternatives.len(), 1);
+
+        let explanation =
+            SelectionExplanation::explain(&[coin(1), coin(2)], 15, &SelectionOptions::new());
+        assert!(explanation.chosen.is_none());
+        assert!(explanation.failure.is_some());
+        assert!(explanation
+            .trace
+            .last()
+            .unwrap()
+            .starts_with("selection fails"));
+    }
+}
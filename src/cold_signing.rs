@@ -0,0 +1,497 @@
+//! Unsigned spend bundles for keys kept on an offline machine.
+//!
+//! An online wallet, watch-only or not, assembles a transaction with
+//! [`Wallet::export_unsigned_transaction`] and writes the resulting [`UnsignedSpendBundle`]
+//! to a file. Its JSON form (schema `dig-wallet/unsigned-spend-bundle`, version 1) is:
+//!
+//! ```text
+//! {
+//!   "schema": "dig-wallet/unsigned-spend-bundle",
+//!   "version": 1,
+//!   "agg_sig_me_data": hex,            // genesis challenge the signatures commit to
+//!   "fee": mojos,
+//!   "coin_spends": [{ "coin": coin, "puzzle_reveal": hex, "solution": hex }],
+//!   "signing_targets": [{ "coin_id": hex, "public_key": hex,
+//!                         "message": hex, "signed_message": hex }]
+//! }
+//! ```
+//!
+//! with `coin` as in [`crate::cat_signing`]. The binary form is the magic `DIGU` followed by
+//! the Chia streamable encoding of `(version: u32, agg_sig_me_data: bytes32,
+//! coin_spends: List[CoinSpend])`. Either way, the fee and signing targets are derived
+//! again from the coin spends when a bundle is read, so a file cannot show less than its
+//! spends would authorize.
+//!
+//! The offline wallet signs it with [`Wallet::sign_unsigned_transaction`], needing no
+//! peer, and writes back a [`SignedSpendBundle`]: JSON with the schema
+//! `dig-wallet/signed-spend-bundle`, version 1, replacing `fee` and `signing_targets` with
+//! the aggregate `signature`, or `DIGS` followed by the streamable
+//! `(version: u32, agg_sig_me_data: bytes32, spend_bundle: SpendBundle)`. The online wallet
+//! checks the signature and broadcasts it with [`Wallet::submit_signed_transaction`].
+
+use crate::builder::TransactionBuilder;
+use crate::coin_proof::SerializedCoin;
+use crate::encoding::{bytes32_from_hex, bytes_from_hex};
+use crate::error::WalletError;
+use crate::spend_signing::required_signatures;
+use crate::transfer::effective_fee;
+use crate::wallet::Wallet;
+use chia::bls::{aggregate_verify, PublicKey, Signature};
+use chia::traits::Streamable;
+use datalayer_driver::{Bytes32, CoinSpend, Peer, Program, SpendBundle};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Schema identifier of [`UnsignedSpendBundle`] JSON
+pub const UNSIGNED_SPEND_BUNDLE_SCHEMA: &str = "dig-wallet/unsigned-spend-bundle";
+/// Version of the [`UnsignedSpendBundle`] layout, in JSON and binary
+pub const UNSIGNED_SPEND_BUNDLE_VERSION: u32 = 1;
+/// Leading bytes of the binary form of an [`UnsignedSpendBundle`]
+const UNSIGNED_SPEND_BUNDLE_MAGIC: &[u8; 4] = b"DIGU";
+/// Schema identifier of [`SignedSpendBundle`] JSON
+pub const SIGNED_SPEND_BUNDLE_SCHEMA: &str = "dig-wallet/signed-spend-bundle";
+/// Version of the [`SignedSpendBundle`] layout, in JSON and binary
+pub const SIGNED_SPEND_BUNDLE_VERSION: u32 = 1;
+/// Leading bytes of the binary form of a [`SignedSpendBundle`]
+const SIGNED_SPEND_BUNDLE_MAGIC: &[u8; 4] = b"DIGS";
+
+/// A coin spend with hex-encoded CLVM
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SerializedCoinSpend {
+    pub coin: SerializedCoin,
+    pub puzzle_reveal: String,
+    pub solution: String,
+}
+
+impl From<&CoinSpend> for SerializedCoinSpend {
+    fn from(coin_spend: &CoinSpend) -> Self {
+        Self {
+            coin: SerializedCoin::from(&coin_spend.coin),
+            puzzle_reveal: hex::encode(coin_spend.puzzle_reveal.as_ref()),
+            solution: hex::encode(coin_spend.solution.as_ref()),
+        }
+    }
+}
+
+impl SerializedCoinSpend {
+    /// Convert back into a protocol coin spend
+    pub fn to_coin_spend(&self) -> Result<CoinSpend, WalletError> {
+        Ok(CoinSpend::new(
+            self.coin.to_coin()?,
+            Program::from(bytes_from_hex(&self.puzzle_reveal)?),
+            Program::from(bytes_from_hex(&self.solution)?),
+        ))
+    }
+}
+
+/// One AGG_SIG signature a bundle needs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningTarget {
+    pub coin_id: String,
+    pub public_key: String,
+    /// The message of the AGG_SIG condition
+    pub message: String,
+    /// `message` completed with the condition's additional data, the bytes to sign; for
+    /// AGG_SIG_ME that is `message || coin_id || agg_sig_me_data`
+    pub signed_message: String,
+}
+
+/// The signatures `coin_spends` need, with messages completed for `agg_sig_me_data`
+fn signing_targets(
+    coin_spends: &[CoinSpend],
+    agg_sig_me_data: Bytes32,
+) -> Result<Vec<SigningTarget>, WalletError> {
+    let mut targets = Vec::new();
+    for coin_spend in coin_spends {
+        let coin_id = hex::encode(coin_spend.coin.coin_id());
+        for required in required_signatures(std::slice::from_ref(coin_spend), agg_sig_me_data)? {
+            targets.push(SigningTarget {
+                coin_id: coin_id.clone(),
+                public_key: hex::encode(required.public_key.to_bytes()),
+                message: hex::encode(required.raw_message.as_ref()),
+                signed_message: hex::encode(required.message()),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Coin spends awaiting signatures, in a form that can be carried to an offline signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsignedSpendBundle {
+    pub schema: String,
+    pub version: u32,
+    /// AGG_SIG_ME additional data (the genesis challenge) the signatures commit to
+    pub agg_sig_me_data: String,
+    /// XCH fee paid by the bundle
+    pub fee: u64,
+    pub coin_spends: Vec<SerializedCoinSpend>,
+    pub signing_targets: Vec<SigningTarget>,
+}
+
+impl UnsignedSpendBundle {
+    /// Describe `coin_spends` for signing on the network of `agg_sig_me_data`
+    pub fn new(coin_spends: &[CoinSpend], agg_sig_me_data: Bytes32) -> Result<Self, WalletError> {
+        Ok(Self {
+            schema: UNSIGNED_SPEND_BUNDLE_SCHEMA.to_string(),
+            version: UNSIGNED_SPEND_BUNDLE_VERSION,
+            agg_sig_me_data: hex::encode(agg_sig_me_data),
+            fee: effective_fee(coin_spends)?,
+            coin_spends: coin_spends.iter().map(SerializedCoinSpend::from).collect(),
+            signing_targets: signing_targets(coin_spends, agg_sig_me_data)?,
+        })
+    }
+
+    /// The AGG_SIG_ME data, which a signer can compare with its network's
+    pub fn agg_sig_me_data(&self) -> Result<Bytes32, WalletError> {
+        bytes32_from_hex(&self.agg_sig_me_data)
+    }
+
+    /// The protocol coin spends
+    pub fn coin_spends(&self) -> Result<Vec<CoinSpend>, WalletError> {
+        self.coin_spends
+            .iter()
+            .map(SerializedCoinSpend::to_coin_spend)
+            .collect()
+    }
+
+    /// Serialize to the documented JSON schema
+    pub fn to_json(&self) -> Result<String, WalletError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))
+    }
+
+    /// Parse JSON written by [`UnsignedSpendBundle::to_json`]
+    ///
+    /// Fails if the fee or signing targets differ from what the coin spends need.
+    pub fn from_json(json: &str) -> Result<Self, WalletError> {
+        let bundle: Self = serde_json::from_str(json).map_err(|e| {
+            WalletError::SerializationError(format!("Invalid unsigned spend bundle: {}", e))
+        })?;
+        if bundle.schema != UNSIGNED_SPEND_BUNDLE_SCHEMA
+            || bundle.version != UNSIGNED_SPEND_BUNDLE_VERSION
+        {
+            return Err(WalletError::SerializationError(format!(
+                "Unsupported unsigned spend bundle {} version {}",
+                bundle.schema, bundle.version
+            )));
+        }
+        if Self::new(&bundle.coin_spends()?, bundle.agg_sig_me_data()?)? != bundle {
+            return Err(WalletError::SerializationError(
+                "Unsigned spend bundle does not match its coin spends".to_string(),
+            ));
+        }
+        Ok(bundle)
+    }
+
+    /// Serialize to the documented binary form
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WalletError> {
+        let body = (self.version, self.agg_sig_me_data()?, self.coin_spends()?)
+            .to_bytes()
+            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+        Ok([UNSIGNED_SPEND_BUNDLE_MAGIC.as_slice(), &body].concat())
+    }
+
+    /// Parse bytes written by [`UnsignedSpendBundle::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WalletError> {
+        let body = bytes
+            .strip_prefix(UNSIGNED_SPEND_BUNDLE_MAGIC.as_slice())
+            .ok_or_else(|| {
+                WalletError::SerializationError("Not an unsigned spend bundle".to_string())
+            })?;
+        let (version, agg_sig_me_data, coin_spends) =
+            <(u32, Bytes32, Vec<CoinSpend>)>::from_bytes(body).map_err(|e| {
+                WalletError::SerializationError(format!("Invalid unsigned spend bundle: {}", e))
+            })?;
+        if version != UNSIGNED_SPEND_BUNDLE_VERSION {
+            return Err(WalletError::SerializationError(format!(
+                "Unsupported unsigned spend bundle version {}",
+                version
+            )));
+        }
+        Self::new(&coin_spends, agg_sig_me_data)
+    }
+}
+
+/// Coin spends signed on an offline machine, on their way back to be broadcast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedSpendBundle {
+    pub schema: String,
+    pub version: u32,
+    /// AGG_SIG_ME additional data (the genesis challenge) the signature commits to
+    pub agg_sig_me_data: String,
+    pub coin_spends: Vec<SerializedCoinSpend>,
+    /// Aggregate BLS signature of every signing target
+    pub signature: String,
+}
+
+impl SignedSpendBundle {
+    fn new(spend_bundle: &SpendBundle, agg_sig_me_data: Bytes32) -> Self {
+        Self {
+            schema: SIGNED_SPEND_BUNDLE_SCHEMA.to_string(),
+            version: SIGNED_SPEND_BUNDLE_VERSION,
+            agg_sig_me_data: hex::encode(agg_sig_me_data),
+            coin_spends: spend_bundle
+                .coin_spends
+                .iter()
+                .map(SerializedCoinSpend::from)
+                .collect(),
+            signature: hex::encode(spend_bundle.aggregated_signature.to_bytes()),
+        }
+    }
+
+    /// The AGG_SIG_ME data, which the broadcasting wallet compares with its network's
+    pub fn agg_sig_me_data(&self) -> Result<Bytes32, WalletError> {
+        bytes32_from_hex(&self.agg_sig_me_data)
+    }
+
+    /// Check the signature covers every signature the coin spends need and assemble the
+    /// spend bundle
+    ///
+    /// Fails with [`WalletError::CryptoError`] if it does not verify.
+    pub fn into_spend_bundle(&self) -> Result<SpendBundle, WalletError> {
+        let signature_bytes: [u8; 96] =
+            bytes_from_hex(&self.signature)?.try_into().map_err(|_| {
+                WalletError::SerializationError("Signature must be 96 bytes".to_string())
+            })?;
+        let signature = Signature::from_bytes(&signature_bytes)
+            .map_err(|e| WalletError::CryptoError(format!("Invalid signature: {}", e)))?;
+        let coin_spends = self
+            .coin_spends
+            .iter()
+            .map(SerializedCoinSpend::to_coin_spend)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let required = required_signatures(&coin_spends, self.agg_sig_me_data()?)?;
+        if !aggregate_verify(
+            &signature,
+            required
+                .iter()
+                .map(|required| (&required.public_key, required.message())),
+        ) {
+            return Err(WalletError::CryptoError(
+                "Signature does not cover the coin spends".to_string(),
+            ));
+        }
+        Ok(SpendBundle::new(coin_spends, signature))
+    }
+
+    /// Serialize to the documented JSON schema
+    pub fn to_json(&self) -> Result<String, WalletError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))
+    }
+
+    /// Parse JSON written by [`SignedSpendBundle::to_json`]
+    pub fn from_json(json: &str) -> Result<Self, WalletError> {
+        let bundle: Self = serde_json::from_str(json).map_err(|e| {
+            WalletError::SerializationError(format!("Invalid signed spend bundle: {}", e))
+        })?;
+        if bundle.schema != SIGNED_SPEND_BUNDLE_SCHEMA
+            || bundle.version != SIGNED_SPEND_BUNDLE_VERSION
+        {
+            return Err(WalletError::SerializationError(format!(
+                "Unsupported signed spend bundle {} version {}",
+                bundle.schema, bundle.version
+            )));
+        }
+        Ok(bundle)
+    }
+
+    /// Serialize to the documented binary form, once the signature is checked as by
+    /// [`SignedSpendBundle::into_spend_bundle`]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WalletError> {
+        let body = (
+            self.version,
+            self.agg_sig_me_data()?,
+            self.into_spend_bundle()?,
+        )
+            .to_bytes()
+            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+        Ok([SIGNED_SPEND_BUNDLE_MAGIC.as_slice(), &body].concat())
+    }
+
+    /// Parse bytes written by [`SignedSpendBundle::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WalletError> {
+        let body = bytes
+            .strip_prefix(SIGNED_SPEND_BUNDLE_MAGIC.as_slice())
+            .ok_or_else(|| {
+                WalletError::SerializationError("Not a signed spend bundle".to_string())
+            })?;
+        let (version, agg_sig_me_data, spend_bundle) =
+            <(u32, Bytes32, SpendBundle)>::from_bytes(body).map_err(|e| {
+                WalletError::SerializationError(format!("Invalid signed spend bundle: {}", e))
+            })?;
+        if version != SIGNED_SPEND_BUNDLE_VERSION {
+            return Err(WalletError::SerializationError(format!(
+                "Unsupported signed spend bundle version {}",
+                version
+            )));
+        }
+        Ok(Self::new(&spend_bundle, agg_sig_me_data))
+    }
+}
+
+impl Wallet {
+    /// Assemble the coin spends of `builder` for signing elsewhere
+    ///
+    /// Only needs the wallet's public keys, so a watch-only wallet can prepare transactions
+    /// for the offline wallet holding its keys. Fund the builder first, as with
+    /// [`Wallet::fund_transaction`]; signatures commit to the network of the wallet's chain.
+    pub async fn export_unsigned_transaction(
+        &self,
+        builder: TransactionBuilder,
+    ) -> Result<UnsignedSpendBundle, WalletError> {
+        let coin_spends = self.build_unsigned_transaction(builder).await?;
+        UnsignedSpendBundle::new(&coin_spends, self.genesis_challenge().await?)
+    }
+
+    /// Sign an exported transaction with the keys of the wallet's issued addresses, without
+    /// contacting a peer
+    ///
+    /// Only the coin spends are trusted: the signatures they need are derived again, and
+    /// all of them must be for the wallet's keys and the network of its chain.
+    pub async fn sign_unsigned_transaction(
+        &self,
+        unsigned: &UnsignedSpendBundle,
+    ) -> Result<SignedSpendBundle, WalletError> {
+        let agg_sig_me_data = unsigned.agg_sig_me_data()?;
+        if agg_sig_me_data != self.genesis_challenge().await? {
+            return Err(WalletError::CryptoError(
+                "Transaction is for another network than the wallet's chain".to_string(),
+            ));
+        }
+        let coin_spends = unsigned.coin_spends()?;
+        let held: HashSet<PublicKey> = self.issued_synthetic_keys().await?.into_iter().collect();
+        let missing = required_signatures(&coin_spends, agg_sig_me_data)?
+            .iter()
+            .filter(|required| !held.contains(&required.public_key))
+            .count();
+        if missing > 0 {
+            return Err(WalletError::CryptoError(format!(
+                "Wallet holds no key for {} of the required signatures",
+                missing
+            )));
+        }
+
+        let spend_bundle = self.sign_spend_bundle(coin_spends).await?;
+        Ok(SignedSpendBundle::new(&spend_bundle, agg_sig_me_data))
+    }
+
+    /// Check a transaction signed offline and broadcast it, returning its transaction id
+    pub async fn submit_signed_transaction(
+        &self,
+        peer: &Peer,
+        signed: &SignedSpendBundle,
+    ) -> Result<Bytes32, WalletError> {
+        if signed.agg_sig_me_data()? != self.genesis_challenge().await? {
+            return Err(WalletError::CryptoError(
+                "Transaction is for another network than the wallet's chain".to_string(),
+            ));
+        }
+        let spend_bundle = signed.into_spend_bundle()?;
+        self.push_transaction(peer, &spend_bundle).await
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use crate::metadata::WalletNetwork;
+    use datalayer_driver::Coin;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_unsigned_spend_bundle_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("cold", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("cold".to_string()), false, &config)
+            .await
+            .unwrap();
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+        let unsigned = wallet
+            .export_unsigned_transaction(
+                TransactionBuilder::new()
+                    .add_coins([Coin::new(Bytes32::new([1; 32]), owner, 100)])
+                    .pay(Bytes32::new([2; 32]), 90)
+                    .with_fee(10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(unsigned.fee, 10);
+        assert_eq!(unsigned.signing_targets.len(), 1);
+        assert_eq!(
+            unsigned.agg_sig_me_data().unwrap(),
+            WalletNetwork::Mainnet.genesis_challenge()
+        );
+
+        let json = unsigned.to_json().unwrap();
+        assert_eq!(UnsignedSpendBundle::from_json(&json).unwrap(), unsigned);
+        let bytes = unsigned.to_bytes().unwrap();
+        assert_eq!(UnsignedSpendBundle::from_bytes(&bytes).unwrap(), unsigned);
+        assert!(UnsignedSpendBundle::from_bytes(&bytes[4..]).is_err());
+
+        // A file hiding what its spends pay is rejected
+        let mut tampered = unsigned.clone();
+        tampered.fee = 0;
+        assert!(UnsignedSpendBundle::from_json(&tampered.to_json().unwrap()).is_err());
+        let mut tampered = unsigned;
+        tampered.signing_targets.clear();
+        assert!(UnsignedSpendBundle::from_json(&tampered.to_json().unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_offline_signing() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        for name in ["offline", "other"] {
+            Wallet::create_new_wallet_with_config(name, &config)
+                .await
+                .unwrap();
+        }
+        let wallet = Wallet::load_with_config(Some("offline".to_string()), false, &config)
+            .await
+            .unwrap();
+        let other = Wallet::load_with_config(Some("other".to_string()), false, &config)
+            .await
+            .unwrap();
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+        let unsigned = wallet
+            .export_unsigned_transaction(
+                TransactionBuilder::new()
+                    .add_coins([Coin::new(Bytes32::new([1; 32]), owner, 100)])
+                    .pay(Bytes32::new([2; 32]), 100),
+            )
+            .await
+            .unwrap();
+
+        let signed = wallet.sign_unsigned_transaction(&unsigned).await.unwrap();
+        let spend_bundle = signed.into_spend_bundle().unwrap();
+        assert_eq!(spend_bundle.coin_spends, unsigned.coin_spends().unwrap());
+        assert_eq!(
+            SignedSpendBundle::from_json(&signed.to_json().unwrap()).unwrap(),
+            signed
+        );
+        assert_eq!(
+            SignedSpendBundle::from_bytes(&signed.to_bytes().unwrap()).unwrap(),
+            signed
+        );
+
+        // Another wallet's keys cannot sign it, and a wrong signature is caught
+        assert!(matches!(
+            other.sign_unsigned_transaction(&unsigned).await,
+            Err(WalletError::CryptoError(_))
+        ));
+        let mut forged = signed;
+        forged.signature = hex::encode(Signature::default().to_bytes());
+        assert!(matches!(
+            forged.into_spend_bundle(),
+            Err(WalletError::CryptoError(_))
+        ));
+    }
+}
@@ -0,0 +1,302 @@
+//! Signed attestations that a storage node holds confirmed DIG collateral.
+//!
+//! [`Wallet::attest_collateral`] picks proven DIG coins buried at least `min_confirmations`
+//! deep at the peer's peak and signs their ids together with the height and header hash.
+//! Validators check the attestation offline with [`verify_collateral_attestation`] and,
+//! since coins may have been spent since, against the chain with
+//! [`verify_collateral_attestation_on_chain`].
+
+use crate::asset::Asset;
+use crate::chain::{fetch_block_info, fetch_coin_states, fetch_peak_height, genesis_challenge};
+use crate::coin_proof::SerializedCoin;
+use crate::encoding::{bytes32_from_hex, bytes_from_hex};
+use crate::error::WalletError;
+use crate::keys::{standard_puzzle_hash, verify_message};
+use crate::wallet::Wallet;
+use chia::bls::{PublicKey, Signature};
+use datalayer_driver::{Bytes32, DigCoin, Peer};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+/// Version of the [`CollateralAttestation`] layout
+pub const COLLATERAL_ATTESTATION_VERSION: u8 = 1;
+
+/// A DIG coin backing an attestation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollateralCoin {
+    pub coin_id: String,
+    pub coin: SerializedCoin,
+    pub created_height: u32,
+}
+
+/// Claim, signed by the owner, that confirmed DIG coins were unspent at `height`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollateralAttestation {
+    pub version: u8,
+    pub genesis_challenge: String,
+    /// Peak height the confirmations are counted at
+    pub height: u32,
+    /// Header hash of the block at `height`
+    pub header_hash: String,
+    pub min_amount: u64,
+    pub min_confirmations: u32,
+    /// Sum of the coin amounts, at least `min_amount`
+    pub total_amount: u64,
+    pub coins: Vec<CollateralCoin>,
+    /// Inner puzzle hash the DIG coins are locked to
+    pub owner_puzzle_hash: String,
+    /// Synthetic public key controlling `owner_puzzle_hash`, as hex
+    pub public_key: String,
+    /// Signature over [`CollateralAttestation::signing_message`], as hex
+    pub signature: String,
+}
+
+impl CollateralAttestation {
+    /// The message the owner signs: every field except the key and signature
+    pub fn signing_message(&self) -> String {
+        let mut message = format!(
+            "DIG collateral attestation v{}\nnetwork: {}\nheight: {}\nheader hash: {}\nowner: {}\nminimum: {} mojos at {} confirmations\ntotal: {} mojos\n",
+            self.version,
+            self.genesis_challenge,
+            self.height,
+            self.header_hash,
+            self.owner_puzzle_hash,
+            self.min_amount,
+            self.min_confirmations,
+            self.total_amount,
+        );
+        for coin in &self.coins {
+            let _ = writeln!(
+                message,
+                "coin: {} {} {} {} @{}",
+                coin.coin_id,
+                coin.coin.parent_coin_info,
+                coin.coin.puzzle_hash,
+                coin.coin.amount,
+                coin.created_height
+            );
+        }
+        message
+    }
+}
+
+impl Wallet {
+    /// Attest that this wallet holds at least `min_amount` DIG mojos in coins with at least
+    /// `min_confirmations` confirmations at the peer's peak
+    ///
+    /// The largest qualifying coins are included until they cover `min_amount`. Fails with
+    /// [`WalletError::InsufficientBalance`] if the confirmed DIG falls short.
+    pub async fn attest_collateral(
+        &self,
+        peer: &Peer,
+        min_amount: u64,
+        min_confirmations: u32,
+    ) -> Result<CollateralAttestation, WalletError> {
+        let report = self.get_all_unspent_dig_coins(peer, vec![], false).await?;
+        let coin_ids = report
+            .proved
+            .iter()
+            .map(|dig_coin| dig_coin.cat().coin.coin_id())
+            .collect();
+        let height = fetch_peak_height(peer).await?;
+
+        let mut confirmed: Vec<CollateralCoin> = fetch_coin_states(peer, coin_ids)
+            .await?
+            .into_iter()
+            .filter(|state| state.spent_height.is_none())
+            .filter_map(|state| {
+                let created_height = state.created_height?;
+                is_confirmed(created_height, height, min_confirmations).then(|| CollateralCoin {
+                    coin_id: hex::encode(state.coin.coin_id()),
+                    coin: SerializedCoin::from(&state.coin),
+                    created_height,
+                })
+            })
+            .collect();
+        confirmed.sort_by_key(|coin| Reverse(coin.coin.amount));
+
+        let mut coins = Vec::new();
+        let mut total_amount = 0u64;
+        for coin in confirmed {
+            if total_amount >= min_amount && !coins.is_empty() {
+                break;
+            }
+            total_amount += coin.coin.amount;
+            coins.push(coin);
+        }
+        if total_amount < min_amount {
+            return Err(WalletError::InsufficientBalance {
+                asset: Asset::Dig,
+                required: min_amount,
+                available: total_amount,
+            });
+        }
+
+        let (header_hash, _) = fetch_block_info(peer, height).await?;
+        let public_key = self.get_public_synthetic_key().await?;
+        let mut attestation = CollateralAttestation {
+            version: COLLATERAL_ATTESTATION_VERSION,
+            genesis_challenge: hex::encode(genesis_challenge()),
+            height,
+            header_hash: hex::encode(header_hash),
+            min_amount,
+            min_confirmations,
+            total_amount,
+            coins,
+            owner_puzzle_hash: hex::encode(self.get_owner_puzzle_hash().await?),
+            public_key: hex::encode(public_key.to_bytes()),
+            signature: String::new(),
+        };
+        let (_, signature) = self
+            .sign_with_synthetic_key(attestation.signing_message().as_bytes())
+            .await?;
+        attestation.signature = hex::encode(signature.to_bytes());
+
+        Ok(attestation)
+    }
+}
+
+/// Whether a coin created at `created_height` has `min_confirmations` at `height`
+fn is_confirmed(created_height: u32, height: u32, min_confirmations: u32) -> bool {
+    height >= created_height && height - created_height + 1 >= min_confirmations
+}
+
+/// Verify an attestation without contacting the chain
+///
+/// Checks the network, that the key controls the owner puzzle hash, that every coin is a
+/// DIG coin of that owner with enough confirmations, that the amounts add up to at least
+/// the minimum, and the signature. Returns `Ok(false)` for a well-formed but invalid
+/// attestation. Whether the coins are still unspent needs
+/// [`verify_collateral_attestation_on_chain`].
+pub fn verify_collateral_attestation(
+    attestation: &CollateralAttestation,
+    genesis_challenge: Bytes32,
+) -> Result<bool, WalletError> {
+    if attestation.version != COLLATERAL_ATTESTATION_VERSION {
+        return Err(WalletError::SerializationError(format!(
+            "Unsupported collateral attestation version: {}",
+            attestation.version
+        )));
+    }
+    if bytes32_from_hex(&attestation.genesis_challenge)? != genesis_challenge {
+        return Ok(false);
+    }
+
+    let public_key: [u8; 48] = bytes_from_hex(&attestation.public_key)?
+        .try_into()
+        .map_err(|_| WalletError::CryptoError("Invalid public key length".to_string()))?;
+    let public_key =
+        PublicKey::from_bytes(&public_key).map_err(|e| WalletError::CryptoError(e.to_string()))?;
+    let owner_puzzle_hash = bytes32_from_hex(&attestation.owner_puzzle_hash)?;
+    if standard_puzzle_hash(&public_key) != owner_puzzle_hash {
+        return Ok(false);
+    }
+
+    let dig_puzzle_hash = DigCoin::puzzle_hash(owner_puzzle_hash);
+    let mut total_amount = 0u64;
+    for collateral in &attestation.coins {
+        let coin = collateral.coin.to_coin()?;
+        if coin.coin_id() != bytes32_from_hex(&collateral.coin_id)?
+            || coin.puzzle_hash != dig_puzzle_hash
+            || !is_confirmed(
+                collateral.created_height,
+                attestation.height,
+                attestation.min_confirmations,
+            )
+        {
+            return Ok(false);
+        }
+        total_amount = total_amount.saturating_add(coin.amount);
+    }
+    if total_amount != attestation.total_amount || total_amount < attestation.min_amount {
+        return Ok(false);
+    }
+
+    let signature: [u8; 96] = bytes_from_hex(&attestation.signature)?
+        .try_into()
+        .map_err(|_| WalletError::CryptoError("Invalid signature length".to_string()))?;
+    let signature =
+        Signature::from_bytes(&signature).map_err(|e| WalletError::CryptoError(e.to_string()))?;
+    Ok(verify_message(
+        attestation.signing_message().as_bytes(),
+        &public_key,
+        &signature,
+    ))
+}
+
+/// Verify an attestation and that its coins were created where claimed and are still unspent
+pub async fn verify_collateral_attestation_on_chain(
+    peer: &Peer,
+    attestation: &CollateralAttestation,
+) -> Result<bool, WalletError> {
+    if !verify_collateral_attestation(attestation, genesis_challenge())? {
+        return Ok(false);
+    }
+
+    let coin_ids = attestation
+        .coins
+        .iter()
+        .map(|coin| bytes32_from_hex(&coin.coin_id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let states = fetch_coin_states(peer, coin_ids.clone()).await?;
+
+    Ok(coin_ids.iter().zip(&attestation.coins).all(|(id, coin)| {
+        states.iter().any(|state| {
+            state.coin.coin_id() == *id
+                && state.created_height == Some(coin.created_height)
+                && state.spent_height.is_none()
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::sign_message;
+    use chia::bls::SecretKey;
+    use datalayer_driver::Coin;
+
+    #[test]
+    fn test_attestations_verify_offline() {
+        let secret_key = SecretKey::from_seed(&[3; 32]);
+        let public_key = secret_key.public_key();
+        let owner = standard_puzzle_hash(&public_key);
+        let coin = Coin::new(Bytes32::new([5; 32]), DigCoin::puzzle_hash(owner), 5_000);
+
+        let mut attestation = CollateralAttestation {
+            version: COLLATERAL_ATTESTATION_VERSION,
+            genesis_challenge: hex::encode(genesis_challenge()),
+            height: 1_000,
+            header_hash: hex::encode([1; 32]),
+            min_amount: 4_000,
+            min_confirmations: 32,
+            total_amount: 5_000,
+            coins: vec![CollateralCoin {
+                coin_id: hex::encode(coin.coin_id()),
+                coin: SerializedCoin::from(&coin),
+                created_height: 900,
+            }],
+            owner_puzzle_hash: hex::encode(owner),
+            public_key: hex::encode(public_key.to_bytes()),
+            signature: String::new(),
+        };
+        let sign = |attestation: &mut CollateralAttestation| {
+            let signature = sign_message(attestation.signing_message().as_bytes(), &secret_key);
+            attestation.signature = hex::encode(signature.to_bytes());
+        };
+        sign(&mut attestation);
+        assert!(verify_collateral_attestation(&attestation, genesis_challenge()).unwrap());
+
+        // A coin confirmed too recently, even when re-signed
+        let mut shallow = attestation.clone();
+        shallow.coins[0].created_height = 990;
+        sign(&mut shallow);
+        assert!(!verify_collateral_attestation(&shallow, genesis_challenge()).unwrap());
+
+        // An inflated claim without a fresh signature
+        let mut inflated = attestation;
+        inflated.min_amount = 5_000;
+        assert!(!verify_collateral_attestation(&inflated, genesis_challenge()).unwrap());
+    }
+}
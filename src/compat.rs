@@ -0,0 +1,152 @@
+//! Versioned facades over [`Wallet`] for downstream services.
+//!
+//! Services that program against [`WalletV1`] or [`WalletV2`] instead of the inherent
+//! `Wallet` methods keep compiling while the inherent API evolves. A facade version is
+//! frozen once released: new methods go into the next version, and a superseded facade
+//! is kept, marked `#[deprecated]`, for at least one major release before removal.
+
+#[cfg(feature = "peer")]
+use crate::asset::Asset;
+#[cfg(feature = "peer")]
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::wallet::Wallet;
+use chia::bls::PublicKey;
+use chia::protocol::Bytes32;
+#[cfg(feature = "peer")]
+use datalayer_driver::Peer;
+use std::future::Future;
+use std::pin::Pin;
+
+/// Newest facade version implemented by [`Wallet`]
+pub const CURRENT_API_VERSION: u32 = 2;
+/// Oldest facade version still implemented by [`Wallet`]
+pub const MIN_SUPPORTED_API_VERSION: u32 = 1;
+
+/// Boxed future returned by facade methods
+pub type CompatFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, WalletError>> + Send + 'a>>;
+
+/// Whether this build still implements facade `version`
+pub fn supports_api_version(version: u32) -> bool {
+    (MIN_SUPPORTED_API_VERSION..=CURRENT_API_VERSION).contains(&version)
+}
+
+/// Version 1: key material, addresses and ownership signatures
+pub trait WalletV1: Send + Sync {
+    fn wallet_name(&self) -> &str;
+    fn mnemonic(&self) -> Result<&str, WalletError>;
+    fn owner_puzzle_hash(&self) -> CompatFuture<'_, Bytes32>;
+    /// The owner puzzle hash as an `xch` address
+    fn owner_address(&self) -> CompatFuture<'_, String>;
+    fn public_synthetic_key(&self) -> CompatFuture<'_, PublicKey>;
+    /// Sign a key ownership nonce, returning the signature as hex
+    fn sign_ownership_nonce<'a>(&'a self, nonce: &'a str) -> CompatFuture<'a, String>;
+}
+
+/// Version 2: adds the configuration, balances and queued transfers
+#[cfg(feature = "peer")]
+pub trait WalletV2: WalletV1 {
+    fn config(&self) -> &WalletConfig;
+    /// Balance of `asset` in mojos; DIG fails with [`WalletError::FeatureDisabled`] without `cat`
+    fn balance<'a>(&'a self, peer: &'a Peer, asset: Asset) -> CompatFuture<'a, u64>;
+    /// Build, sign and queue a transfer, returning the queued transaction id
+    fn queue_transfer<'a>(
+        &'a self,
+        peer: &'a Peer,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+    ) -> CompatFuture<'a, String>;
+    /// Broadcast a queued transaction, returning the spend bundle id
+    fn broadcast_queued<'a>(&'a self, peer: &'a Peer, tx_id: &'a str) -> CompatFuture<'a, Bytes32>;
+}
+
+impl WalletV1 for Wallet {
+    fn wallet_name(&self) -> &str {
+        self.get_wallet_name()
+    }
+
+    fn mnemonic(&self) -> Result<&str, WalletError> {
+        self.mnemonic_phrase()
+    }
+
+    fn owner_puzzle_hash(&self) -> CompatFuture<'_, Bytes32> {
+        Box::pin(self.get_owner_puzzle_hash())
+    }
+
+    fn owner_address(&self) -> CompatFuture<'_, String> {
+        Box::pin(self.get_owner_public_key())
+    }
+
+    fn public_synthetic_key(&self) -> CompatFuture<'_, PublicKey> {
+        Box::pin(self.get_public_synthetic_key())
+    }
+
+    fn sign_ownership_nonce<'a>(&'a self, nonce: &'a str) -> CompatFuture<'a, String> {
+        Box::pin(self.create_key_ownership_signature(nonce))
+    }
+}
+
+#[cfg(feature = "peer")]
+impl WalletV2 for Wallet {
+    fn config(&self) -> &WalletConfig {
+        self.get_config()
+    }
+
+    fn balance<'a>(&'a self, peer: &'a Peer, asset: Asset) -> CompatFuture<'a, u64> {
+        Box::pin(async move {
+            match asset {
+                Asset::Xch => self.get_xch_balance(peer).await,
+                #[cfg(feature = "cat")]
+                Asset::Dig => self.get_dig_balance(peer, false).await,
+                #[cfg(feature = "cat")]
+                Asset::Cat(asset_id) => self.get_cat_balance(peer, asset_id).await,
+                #[cfg(not(feature = "cat"))]
+                Asset::Dig | Asset::Cat(_) => Err(WalletError::FeatureDisabled("cat".to_string())),
+            }
+        })
+    }
+
+    fn queue_transfer<'a>(
+        &'a self,
+        peer: &'a Peer,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+    ) -> CompatFuture<'a, String> {
+        Box::pin(async move {
+            let queued =
+                Wallet::queue_transfer(self, peer, asset, destination, amount, fee).await?;
+            Ok(queued.tx_id)
+        })
+    }
+
+    fn broadcast_queued<'a>(&'a self, peer: &'a Peer, tx_id: &'a str) -> CompatFuture<'a, Bytes32> {
+        Box::pin(Wallet::broadcast_queued(self, peer, tx_id))
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+
+    async fn address_via_v1(wallet: &dyn WalletV1) -> String {
+        wallet.owner_address().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_wallet_implements_v1_facade() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let wallet = Wallet::new(Some(mnemonic.to_string()), "compat".to_string());
+
+        assert_eq!(WalletV1::wallet_name(&wallet), "compat");
+        assert_eq!(
+            address_via_v1(&wallet).await,
+            wallet.get_owner_public_key().await.unwrap()
+        );
+        assert!(supports_api_version(1));
+        assert!(!supports_api_version(CURRENT_API_VERSION + 1));
+    }
+}
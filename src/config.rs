@@ -0,0 +1,468 @@
+#[cfg(feature = "peer")]
+use crate::addresses::DEFAULT_ADDRESS_GAP_LIMIT;
+#[cfg(feature = "peer")]
+use crate::change::ChangePolicy;
+#[cfg(feature = "peer")]
+use crate::discovery::{builtin_plugins, CoinDiscovery};
+use crate::environment::{Environment, SystemEnvironment};
+use crate::error::WalletError;
+#[cfg(feature = "peer")]
+use crate::fee_floor::FeeFloor;
+#[cfg(feature = "peer")]
+use crate::fees::FeeTarget;
+#[cfg(feature = "cache")]
+use crate::file_cache::CacheLimits;
+#[cfg(feature = "peer")]
+use crate::freshness::PeakFreshness;
+use crate::keyring::KeyringBackend;
+#[cfg(feature = "keyring-file")]
+use crate::keyring::{FileKeyringBackend, KeyringLockOptions};
+use crate::mnemonic::MnemonicLanguage;
+use crate::rng::{OsRngProvider, RngProvider};
+use crate::runtime::{self, RuntimeHandle};
+use crate::session::DEFAULT_SESSION_IDLE_TIMEOUT;
+#[cfg(feature = "peer")]
+use datalayer_driver::NetworkType;
+#[cfg(feature = "cache")]
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+const DIG_DIR: &str = ".dig";
+const KEYRING_FILE: &str = "keyring.json";
+#[cfg(feature = "peer")]
+const PEERS_FILE: &str = "peers.toml";
+
+/// Keyring passphrase, kept out of `Debug` output
+#[derive(Clone)]
+struct Passphrase(Arc<Zeroizing<String>>);
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// Identity of a keyring, to find state unlocked from it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum KeyringId {
+    /// The default keyring file at this path
+    File(PathBuf),
+    /// A configured backend, by address
+    Backend(usize),
+}
+
+/// Configuration shared by wallet, keyring, cache and connection code
+#[derive(Debug, Clone)]
+pub struct WalletConfig {
+    environment: Arc<dyn Environment>,
+    rng: Arc<dyn RngProvider>,
+    keyring_backend: Option<Arc<dyn KeyringBackend>>,
+    keyring_path: Option<PathBuf>,
+    keyring_passphrase: Option<Passphrase>,
+    #[cfg(feature = "keyring-file")]
+    keyring_lock_options: KeyringLockOptions,
+    session_idle_timeout: Duration,
+    allow_duplicate_mnemonics: bool,
+    mnemonic_language: MnemonicLanguage,
+    runtime: Option<RuntimeHandle>,
+    #[cfg(feature = "cache")]
+    cache_limits: BTreeMap<String, CacheLimits>,
+    #[cfg(feature = "peer")]
+    peak_freshness: Option<PeakFreshness>,
+    #[cfg(feature = "peer")]
+    coin_discovery: Vec<Arc<dyn CoinDiscovery>>,
+    #[cfg(feature = "peer")]
+    fee_floor: Option<FeeFloor>,
+    #[cfg(feature = "peer")]
+    fee_target: FeeTarget,
+    #[cfg(feature = "peer")]
+    change_policy: ChangePolicy,
+    #[cfg(feature = "peer")]
+    address_gap_limit: u32,
+}
+
+impl Default for WalletConfig {
+    fn default() -> Self {
+        Self {
+            environment: Arc::new(SystemEnvironment),
+            rng: Arc::new(OsRngProvider),
+            keyring_backend: None,
+            keyring_path: None,
+            keyring_passphrase: None,
+            #[cfg(feature = "keyring-file")]
+            keyring_lock_options: KeyringLockOptions::default(),
+            session_idle_timeout: DEFAULT_SESSION_IDLE_TIMEOUT,
+            allow_duplicate_mnemonics: false,
+            mnemonic_language: MnemonicLanguage::English,
+            runtime: None,
+            #[cfg(feature = "cache")]
+            cache_limits: BTreeMap::new(),
+            #[cfg(feature = "peer")]
+            peak_freshness: None,
+            #[cfg(feature = "peer")]
+            coin_discovery: Vec::new(),
+            #[cfg(feature = "peer")]
+            fee_floor: None,
+            #[cfg(feature = "peer")]
+            fee_target: FeeTarget::default(),
+            #[cfg(feature = "peer")]
+            change_policy: ChangePolicy::default(),
+            #[cfg(feature = "peer")]
+            address_gap_limit: DEFAULT_ADDRESS_GAP_LIMIT,
+        }
+    }
+}
+
+impl WalletConfig {
+    /// Create a configuration backed by the real process environment
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given environment instead of the process environment
+    pub fn with_environment(mut self, environment: impl Environment + 'static) -> Self {
+        self.environment = Arc::new(environment);
+        self
+    }
+
+    /// Use the given entropy source instead of the operating system RNG
+    pub fn with_rng(mut self, rng: impl RngProvider + 'static) -> Self {
+        self.rng = Arc::new(rng);
+        self
+    }
+
+    /// The entropy source used for mnemonics, salts and nonces
+    pub fn rng(&self) -> &dyn RngProvider {
+        self.rng.as_ref()
+    }
+
+    /// `N` random bytes from the configured entropy source
+    pub fn random_bytes<const N: usize>(&self) -> Result<[u8; N], WalletError> {
+        let mut bytes = [0u8; N];
+        self.rng.fill_bytes(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Store the keyring in the given backend instead of the local keyring file
+    pub fn with_keyring_backend(self, backend: impl KeyringBackend + 'static) -> Self {
+        self.with_shared_keyring_backend(Arc::new(backend))
+    }
+
+    /// Store the keyring in a backend shared with other configurations
+    pub fn with_shared_keyring_backend(mut self, backend: Arc<dyn KeyringBackend>) -> Self {
+        self.keyring_backend = Some(backend);
+        self
+    }
+
+    /// Keep the keyring file at `path` instead of `~/.dig/keyring.json`
+    ///
+    /// Ignored when a keyring backend is set.
+    pub fn with_keyring_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.keyring_path = Some(path.into());
+        self
+    }
+
+    /// How the default keyring file waits for writers in other processes
+    #[cfg(feature = "keyring-file")]
+    pub fn with_keyring_lock_options(mut self, options: KeyringLockOptions) -> Self {
+        self.keyring_lock_options = options;
+        self
+    }
+
+    /// The configured keyring backend, defaulting to the file at [`Self::keyring_path`]
+    pub fn keyring_backend(&self) -> Result<Arc<dyn KeyringBackend>, WalletError> {
+        match &self.keyring_backend {
+            Some(backend) => Ok(backend.clone()),
+            #[cfg(feature = "keyring-file")]
+            None => Ok(Arc::new(
+                FileKeyringBackend::new(self.keyring_path()?)
+                    .with_lock_options(self.keyring_lock_options),
+            )),
+            #[cfg(not(feature = "keyring-file"))]
+            None => Err(WalletError::InvalidConfig(
+                "no keyring backend configured and the `keyring-file` feature is disabled"
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// Which keyring this configuration reads
+    pub(crate) fn keyring_id(&self) -> Result<KeyringId, WalletError> {
+        match &self.keyring_backend {
+            Some(backend) => Ok(KeyringId::Backend(
+                Arc::as_ptr(backend) as *const () as usize
+            )),
+            None => Ok(KeyringId::File(self.keyring_path()?)),
+        }
+    }
+
+    /// Encrypt keyring entries under `passphrase` (Argon2id + AES-256-GCM) and use it to
+    /// decrypt passphrase-protected entries
+    pub fn with_keyring_passphrase(mut self, passphrase: &str) -> Self {
+        self.keyring_passphrase =
+            Some(Passphrase(Arc::new(Zeroizing::new(passphrase.to_string()))));
+        self
+    }
+
+    /// This configuration without a keyring passphrase
+    pub(crate) fn without_keyring_passphrase(mut self) -> Self {
+        self.keyring_passphrase = None;
+        self
+    }
+
+    /// The keyring passphrase, if one was supplied
+    pub(crate) fn keyring_passphrase(&self) -> Option<&str> {
+        self.keyring_passphrase.as_ref().map(|p| p.0.as_str())
+    }
+
+    /// Lock session wallets after `timeout` without use; see [`crate::Wallet::open_session`]
+    pub fn with_session_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.session_idle_timeout = timeout;
+        self
+    }
+
+    /// Idle time after which session wallets lock
+    pub fn session_idle_timeout(&self) -> Duration {
+        self.session_idle_timeout
+    }
+
+    /// Let imports store a mnemonic already held by another keyring wallet, e.g. to use
+    /// one key on several chains
+    pub fn with_allow_duplicate_mnemonics(mut self, allow: bool) -> Self {
+        self.allow_duplicate_mnemonics = allow;
+        self
+    }
+
+    /// Whether imports may store a mnemonic held by another keyring wallet
+    pub fn allow_duplicate_mnemonics(&self) -> bool {
+        self.allow_duplicate_mnemonics
+    }
+
+    /// Generate new mnemonics from the wordlist of `language` instead of English
+    pub fn with_mnemonic_language(mut self, language: MnemonicLanguage) -> Self {
+        self.mnemonic_language = language;
+        self
+    }
+
+    /// Wordlist of newly generated mnemonics
+    pub fn mnemonic_language(&self) -> MnemonicLanguage {
+        self.mnemonic_language
+    }
+
+    /// Spawn background tasks on this runtime instead of the one installed with
+    /// [`crate::init`]
+    pub fn with_runtime(mut self, runtime: RuntimeHandle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// The configured runtime, falling back to [`runtime::current`]
+    pub fn runtime(&self) -> Result<RuntimeHandle, WalletError> {
+        match &self.runtime {
+            Some(runtime) => Ok(runtime.clone()),
+            None => runtime::current(),
+        }
+    }
+
+    /// Refuse to broadcast through peers whose peak falls outside `freshness`
+    #[cfg(feature = "peer")]
+    pub fn with_peak_freshness(mut self, freshness: PeakFreshness) -> Self {
+        self.peak_freshness = Some(freshness);
+        self
+    }
+
+    /// The peak freshness requirement checked before broadcasting, if any
+    #[cfg(feature = "peer")]
+    pub fn peak_freshness(&self) -> Option<&PeakFreshness> {
+        self.peak_freshness.as_ref()
+    }
+
+    /// Also sync the coins found by `plugin` in [`crate::Wallet::discover_coins`]
+    #[cfg(feature = "peer")]
+    pub fn with_coin_discovery(mut self, plugin: impl CoinDiscovery + 'static) -> Self {
+        self.coin_discovery.push(Arc::new(plugin));
+        self
+    }
+
+    /// The built-in discovery plugins followed by the registered ones
+    #[cfg(feature = "peer")]
+    pub fn coin_discovery_plugins(&self) -> Vec<Arc<dyn CoinDiscovery>> {
+        let mut plugins = builtin_plugins();
+        plugins.extend(self.coin_discovery.iter().cloned());
+        plugins
+    }
+
+    /// Refuse to build transactions whose fee falls below `floor`
+    #[cfg(feature = "peer")]
+    pub fn with_fee_floor(mut self, floor: FeeFloor) -> Self {
+        self.fee_floor = Some(floor);
+        self
+    }
+
+    /// The minimum fee checked before building transactions, if any
+    #[cfg(feature = "peer")]
+    pub fn fee_floor(&self) -> Option<&FeeFloor> {
+        self.fee_floor.as_ref()
+    }
+
+    /// Estimate fees with [`crate::Wallet::estimate_fee_for`] for inclusion within `target`
+    #[cfg(feature = "peer")]
+    pub fn with_fee_target(mut self, target: FeeTarget) -> Self {
+        self.fee_target = target;
+        self
+    }
+
+    /// Confirmation target of the wallet's fee estimates
+    #[cfg(feature = "peer")]
+    pub fn fee_target(&self) -> FeeTarget {
+        self.fee_target
+    }
+
+    /// Pay out the change of every transaction the wallet builds by `policy`
+    #[cfg(feature = "peer")]
+    pub fn with_change_policy(mut self, policy: ChangePolicy) -> Self {
+        self.change_policy = policy;
+        self
+    }
+
+    /// Where the change of the wallet's transactions goes
+    #[cfg(feature = "peer")]
+    pub fn change_policy(&self) -> ChangePolicy {
+        self.change_policy
+    }
+
+    /// Stop [`crate::Wallet::scan_addresses`] after `limit` consecutive unused indexes
+    #[cfg(feature = "peer")]
+    pub fn with_address_gap_limit(mut self, limit: u32) -> Self {
+        self.address_gap_limit = limit;
+        self
+    }
+
+    /// Consecutive unused indexes that end an address scan
+    #[cfg(feature = "peer")]
+    pub fn address_gap_limit(&self) -> u32 {
+        self.address_gap_limit
+    }
+
+    /// Bound the caches stored under `~/.dig/<cache>`, e.g. `coin_records` or `warm`
+    ///
+    /// Each wallet's cache directory is bounded separately. Limits are enforced by
+    /// [`FileCache::compact`](crate::FileCache::compact) and
+    /// [`Wallet::compact_caches`](crate::Wallet::compact_caches).
+    #[cfg(feature = "cache")]
+    pub fn with_cache_limits(mut self, cache: &str, limits: CacheLimits) -> Self {
+        self.cache_limits.insert(cache.to_string(), limits);
+        self
+    }
+
+    /// The limits of the cache at `relative_path` below the `.dig` directory
+    #[cfg(feature = "cache")]
+    pub(crate) fn cache_limits(&self, relative_path: &str) -> CacheLimits {
+        let cache = relative_path.split('/').next().unwrap_or(relative_path);
+        self.cache_limits.get(cache).copied().unwrap_or_default()
+    }
+
+    /// Caches with configured limits
+    #[cfg(feature = "cache")]
+    pub(crate) fn limited_caches(&self) -> impl Iterator<Item = (&str, CacheLimits)> {
+        self.cache_limits
+            .iter()
+            .map(|(cache, limits)| (cache.as_str(), *limits))
+    }
+
+    /// The environment used for home directory, variable and clock lookups
+    pub fn environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+
+    /// The user's home directory
+    pub fn home_dir(&self) -> Result<PathBuf, WalletError> {
+        self.environment.home_dir().ok_or_else(|| {
+            WalletError::FileSystemError("Could not find home directory".to_string())
+        })
+    }
+
+    /// The dig-wallet state directory (`~/.dig`)
+    pub fn dig_dir(&self) -> Result<PathBuf, WalletError> {
+        Ok(self.home_dir()?.join(DIG_DIR))
+    }
+
+    /// The keyring file location: the configured path, else `~/.dig/keyring.json`
+    pub fn keyring_path(&self) -> Result<PathBuf, WalletError> {
+        if let Some(path) = &self.keyring_path {
+            return Ok(path.clone());
+        }
+
+        Ok(self.dig_dir()?.join(KEYRING_FILE))
+    }
+
+    /// The named peer environments file (`~/.dig/peers.toml`)
+    #[cfg(feature = "peer")]
+    pub fn peers_path(&self) -> Result<PathBuf, WalletError> {
+        Ok(self.dig_dir()?.join(PEERS_FILE))
+    }
+
+    /// The default Chia wallet SSL directory for a network (`~/.chia/<network>/config/ssl/wallet`)
+    #[cfg(feature = "peer")]
+    pub fn chia_ssl_dir(&self, network: NetworkType) -> Result<PathBuf, WalletError> {
+        let network_dir = match network {
+            NetworkType::Mainnet => "mainnet",
+            NetworkType::Testnet11 => "testnet11",
+        };
+
+        Ok(self
+            .home_dir()?
+            .join(".chia")
+            .join(network_dir)
+            .join("config")
+            .join("ssl")
+            .join("wallet"))
+    }
+
+    /// Seconds since the Unix epoch according to the configured clock
+    pub fn unix_timestamp(&self) -> u64 {
+        self.environment
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+
+    #[test]
+    fn test_paths_follow_environment() {
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new("/home/dig"));
+        assert_eq!(
+            config.keyring_path().unwrap(),
+            PathBuf::from("/home/dig/.dig/keyring.json")
+        );
+        #[cfg(feature = "peer")]
+        assert_eq!(
+            config.chia_ssl_dir(NetworkType::Testnet11).unwrap(),
+            PathBuf::from("/home/dig/.chia/testnet11/config/ssl/wallet")
+        );
+
+        // The process environment no longer relocates the keyring
+        let ignored = WalletConfig::new().with_environment(
+            SandboxEnvironment::new("/home/dig").with_var("TEST_KEYRING_PATH", "/tmp/k.json"),
+        );
+        assert_eq!(
+            ignored.keyring_path().unwrap(),
+            PathBuf::from("/home/dig/.dig/keyring.json")
+        );
+        let explicit = ignored.with_keyring_path("/srv/dig/keyring.json");
+        assert_eq!(
+            explicit.keyring_path().unwrap(),
+            PathBuf::from("/srv/dig/keyring.json")
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Broadcasting spend bundles and waiting until they are buried in the chain.
+//!
+//! A spend is confirmed once a coin it consumes is recorded as spent. Waiting polls the
+//! coin's state and the peer's peak, so a spend that a reorg undoes simply goes back to
+//! pending. Transaction ids are resolved through the wallet's queue and history, whose
+//! records list the consumed coins; any other id is taken to be a coin id.
+
+use crate::chain::{fetch_coin_states, fetch_peak_height};
+use crate::context::OpContext;
+use crate::encoding::bytes32_from_hex;
+use crate::error::WalletError;
+use crate::wallet::Wallet;
+use datalayer_driver::{Bytes32, Peer, SpendBundle};
+use std::time::Duration;
+
+/// Time between two checks of a spend's coin state
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Whether a spend included at `spent_height` has `min_confirmations` at `peak_height`
+///
+/// Inclusion is the first confirmation, so 0 and 1 both mean included.
+fn is_confirmed(spent_height: u32, peak_height: u32, min_confirmations: u32) -> bool {
+    peak_height >= spent_height && peak_height - spent_height + 1 >= min_confirmations.max(1)
+}
+
+impl Wallet {
+    /// Submit a spend bundle to the mempool, returning its transaction id
+    pub async fn push_transaction(
+        &self,
+        peer: &Peer,
+        spend_bundle: &SpendBundle,
+    ) -> Result<Bytes32, WalletError> {
+        self.broadcast_transaction(peer, spend_bundle).await
+    }
+
+    /// Wait until a transaction or coin spend has `min_confirmations`, returning the
+    /// height it was included at
+    ///
+    /// `tx_or_coin_id` is a transaction of the wallet's queue or history, or a coin id.
+    /// Fails with [`WalletError::DeadlineExceeded`] if `timeout` passes first. A confirmed
+    /// history record has its height filled in.
+    pub async fn wait_for_confirmation(
+        &self,
+        peer: &Peer,
+        tx_or_coin_id: Bytes32,
+        min_confirmations: u32,
+        timeout: Duration,
+    ) -> Result<u32, WalletError> {
+        let tx_id = hex::encode(tx_or_coin_id);
+        let history = self.transaction_history()?;
+        let recorded = history.get(&tx_id)?;
+        let coin_id = match (self.tx_queue()?.get(&tx_id)?, &recorded) {
+            (Some(queued), _) => queued.spent_coin_ids()?.first().cloned(),
+            (None, Some(record)) => record.spent_coin_ids.first().cloned(),
+            (None, None) => Some(tx_id.clone()),
+        }
+        .ok_or_else(|| {
+            WalletError::TransactionFailed(format!("Transaction {} spends no coins", tx_id))
+        })?;
+
+        let height = self
+            .wait_for_coin_spend(
+                peer,
+                bytes32_from_hex(&coin_id)?,
+                min_confirmations,
+                timeout,
+            )
+            .await?;
+        if let Some(mut record) = recorded {
+            record.height = Some(height);
+            history.record(&record).await?;
+        }
+        Ok(height)
+    }
+
+    /// Wait until `spend_bundle` has `min_confirmations`, returning its inclusion height
+    pub async fn wait_for_spend_bundle(
+        &self,
+        peer: &Peer,
+        spend_bundle: &SpendBundle,
+        min_confirmations: u32,
+        timeout: Duration,
+    ) -> Result<u32, WalletError> {
+        let coin_spend = spend_bundle.coin_spends.first().ok_or_else(|| {
+            WalletError::TransactionFailed("Spend bundle spends no coins".to_string())
+        })?;
+        self.wait_for_coin_spend(peer, coin_spend.coin.coin_id(), min_confirmations, timeout)
+            .await
+    }
+
+    async fn wait_for_coin_spend(
+        &self,
+        peer: &Peer,
+        coin_id: Bytes32,
+        min_confirmations: u32,
+        timeout: Duration,
+    ) -> Result<u32, WalletError> {
+        let context =
+            OpContext::new(format!("confirm:{}", hex::encode(coin_id))).with_timeout(timeout);
+        let poll = async {
+            loop {
+                let spent_height = fetch_coin_states(peer, vec![coin_id])
+                    .await?
+                    .into_iter()
+                    .find(|state| state.coin.coin_id() == coin_id)
+                    .and_then(|state| state.spent_height);
+                if let Some(spent_height) = spent_height {
+                    let peak_height = fetch_peak_height(peer).await?;
+                    if is_confirmed(spent_height, peak_height, min_confirmations) {
+                        return Ok(spent_height);
+                    }
+                }
+                tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+            }
+        };
+        context.clone().scope(context.run(poll)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmation_depth() {
+        assert!(is_confirmed(100, 100, 0));
+        assert!(is_confirmed(100, 100, 1));
+        assert!(!is_confirmed(100, 100, 2));
+        assert!(is_confirmed(100, 131, 32));
+        assert!(!is_confirmed(100, 130, 32));
+        // A peer behind the spend has not seen it yet
+        assert!(!is_confirmed(100, 99, 1));
+    }
+}
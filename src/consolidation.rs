@@ -0,0 +1,153 @@
+//! Merging many small XCH coins into few, to keep the coin set and future fees down.
+//!
+//! Coins are taken smallest first and split into batches of at most `max_inputs`, so no
+//! spend bundle grows past what a block can fit. Each batch becomes one spend bundle whose
+//! coins are merged into a single coin, placed by the wallet's [`crate::ChangePolicy`].
+//! Batches do not depend on each other and are broadcast one after another.
+
+use crate::asset::Asset;
+use crate::builder::TransactionBuilder;
+use crate::error::WalletError;
+use crate::history::{TransactionDirection, TransactionRecord};
+use crate::transfer::effective_fee;
+use crate::wallet::Wallet;
+use datalayer_driver::{Coin, Peer};
+use serde::{Deserialize, Serialize};
+
+/// Outcome of [`Wallet::consolidate_coins`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsolidationSummary {
+    /// One history record per broadcast spend bundle
+    pub transactions: Vec<TransactionRecord>,
+    /// Coins merged across all spend bundles
+    pub coins_consolidated: usize,
+    /// Coins left alone: a lone coin, or a batch worth no more than its fee
+    pub coins_skipped: usize,
+}
+
+impl ConsolidationSummary {
+    /// Fees paid across all spend bundles
+    pub fn total_fee(&self) -> u64 {
+        self.transactions.iter().map(|record| record.fee).sum()
+    }
+}
+
+/// Split `coins` smallest first into batches of at most `max_inputs` worth merging
+///
+/// Returns the batches and the number of coins left out. A batch needs at least two coins
+/// and a total above `fee`.
+fn plan_batches(mut coins: Vec<Coin>, max_inputs: usize, fee: u64) -> (Vec<Vec<Coin>>, usize) {
+    coins.sort_by_key(|coin| coin.amount);
+
+    let mut batches = Vec::new();
+    let mut skipped = 0;
+    for batch in coins.chunks(max_inputs) {
+        let total = batch
+            .iter()
+            .try_fold(0u64, |sum, coin| sum.checked_add(coin.amount));
+        if batch.len() < 2 || total.map_or(true, |total| total <= fee) {
+            skipped += batch.len();
+        } else {
+            batches.push(batch.to_vec());
+        }
+    }
+    (batches, skipped)
+}
+
+impl Wallet {
+    /// Merge the wallet's unspent XCH coins, at most `max_inputs` per spend bundle, paying
+    /// `fee` for each bundle
+    ///
+    /// With more coins than `max_inputs`, one coin per batch remains; calling again merges
+    /// those. Each bundle is recorded as an internal move. A failed broadcast stops the
+    /// run, leaving earlier bundles in the mempool.
+    pub async fn consolidate_coins(
+        &self,
+        peer: &Peer,
+        max_inputs: usize,
+        fee: u64,
+    ) -> Result<ConsolidationSummary, WalletError> {
+        if max_inputs < 2 {
+            return Err(WalletError::InvalidAmount(
+                "Consolidation needs at least 2 inputs per transaction".to_string(),
+            ));
+        }
+        let config = self.get_config();
+        Self::check_fee_floor(peer, fee, config).await?;
+
+        let coins = self.get_all_unspent_xch_coins(peer, vec![]).await?;
+        let (batches, coins_skipped) = plan_batches(coins, max_inputs, fee);
+
+        let mut summary = ConsolidationSummary {
+            coins_skipped,
+            ..ConsolidationSummary::default()
+        };
+        for batch in batches {
+            let total: u64 = batch.iter().map(|coin| coin.amount).sum();
+            let coin_count = batch.len();
+            let unsigned = self
+                .build_transaction(TransactionBuilder::new().add_coins(batch).with_fee(fee))
+                .await?;
+            let spent_coin_ids = unsigned
+                .spent_coins
+                .iter()
+                .map(|coin| hex::encode(coin.coin_id()))
+                .collect();
+            let spend_bundle = self.sign_spend_bundle(unsigned.coin_spends).await?;
+            // Dust burning by the change policy can raise the fee
+            let paid_fee = effective_fee(&spend_bundle.coin_spends)?;
+            let tx_id = self.broadcast_transaction(peer, &spend_bundle).await?;
+
+            let record = TransactionRecord {
+                tx_id: hex::encode(tx_id),
+                asset: Asset::Xch,
+                direction: TransactionDirection::Internal,
+                amount: total - paid_fee,
+                fee: paid_fee,
+                counterparty_puzzle_hash: Some(hex::encode(unsigned.change_puzzle_hash)),
+                counterparty_wallet: None,
+                spent_coin_ids,
+                height: None,
+                timestamp: config.unix_timestamp(),
+            };
+            self.transaction_history()?.record(&record).await?;
+            summary.transactions.push(record);
+            summary.coins_consolidated += coin_count;
+        }
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datalayer_driver::Bytes32;
+
+    #[test]
+    fn test_plan_batches() {
+        let coins: Vec<Coin> = (1u8..=7)
+            .map(|i| Coin::new(Bytes32::new([i; 32]), Bytes32::new([0; 32]), 100 * i as u64))
+            .rev()
+            .collect();
+
+        let (batches, skipped) = plan_batches(coins.clone(), 3, 10);
+        let amounts: Vec<Vec<u64>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(|coin| coin.amount).collect())
+            .collect();
+        assert_eq!(amounts, vec![vec![100, 200, 300], vec![400, 500, 600]]);
+        // The lone 700 coin has nothing to merge with
+        assert_eq!(skipped, 1);
+
+        let (batches, skipped) = plan_batches(coins.clone(), 10, 10);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), 7);
+        assert_eq!(skipped, 0);
+
+        // Dust worth no more than the fee is left alone
+        let (batches, skipped) = plan_batches(coins, 2, 300);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(skipped, 2 + 1);
+    }
+}
@@ -0,0 +1,153 @@
+//! Per-operation deadlines shared by every peer request an operation makes.
+//!
+//! An [`OpContext`] carries a total time budget, a cancellation token and a trace id.
+//! Run an operation inside [`OpContext::scope`] and every peer request, connection
+//! attempt and [`CancellationToken::run`] call it makes observes the same deadline, so
+//! retries and fallbacks cannot stretch a 10 second budget into minutes.
+
+use crate::cancel::CancellationToken;
+use crate::error::WalletError;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static CURRENT: OpContext;
+}
+
+/// Deadline, cancellation and trace id of one caller-level operation
+#[derive(Debug, Clone)]
+pub struct OpContext {
+    deadline: Option<Instant>,
+    cancellation: CancellationToken,
+    trace_id: String,
+}
+
+impl OpContext {
+    /// A context without a deadline, identified by `trace_id` in errors
+    pub fn new(trace_id: impl Into<String>) -> Self {
+        Self {
+            deadline: None,
+            cancellation: CancellationToken::new(),
+            trace_id: trace_id.into(),
+        }
+    }
+
+    /// Give the whole operation `budget` from now
+    pub fn with_timeout(self, budget: Duration) -> Self {
+        self.with_deadline(Instant::now() + budget)
+    }
+
+    /// Fail the operation once `deadline` passes
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Abort the operation when `cancellation` fires
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Budget left before the deadline; `None` without one
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Fail if the operation was cancelled or ran out of time
+    pub fn check(&self) -> Result<(), WalletError> {
+        self.cancellation.check()?;
+        if self.remaining() == Some(Duration::ZERO) {
+            return Err(self.deadline_exceeded());
+        }
+        Ok(())
+    }
+
+    /// Run `future` until it completes, the deadline passes or the context is cancelled
+    pub async fn run<T>(
+        &self,
+        future: impl Future<Output = Result<T, WalletError>>,
+    ) -> Result<T, WalletError> {
+        self.check()?;
+        let Some(deadline) = self.deadline else {
+            return self.cancellation.run_unscoped(future).await;
+        };
+        tokio::select! {
+            biased;
+            _ = tokio::time::sleep_until(deadline) => Err(self.deadline_exceeded()),
+            result = self.cancellation.run_unscoped(future) => result,
+        }
+    }
+
+    /// Run `future` with this context as the current one
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// The context installed by the innermost enclosing [`OpContext::scope`]
+    pub fn current() -> Option<OpContext> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    fn deadline_exceeded(&self) -> WalletError {
+        WalletError::DeadlineExceeded {
+            trace_id: self.trace_id.clone(),
+        }
+    }
+}
+
+/// Run `future` under the current [`OpContext`], if any
+pub(crate) async fn within_current<T>(
+    future: impl Future<Output = Result<T, WalletError>>,
+) -> Result<T, WalletError> {
+    match OpContext::current() {
+        Some(context) => context.run(future).await,
+        None => future.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_nested_calls_share_the_deadline() {
+        let context = OpContext::new("req-42").with_timeout(Duration::from_millis(20));
+
+        let result = context
+            .scope(async {
+                // Each call alone fits the budget; together they do not
+                for _ in 0..5 {
+                    CancellationToken::new()
+                        .run(async {
+                            tokio::time::sleep(Duration::from_millis(10)).await;
+                            Ok(())
+                        })
+                        .await?;
+                }
+                Ok(())
+            })
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(WalletError::DeadlineExceeded { trace_id }) if trace_id == "req-42"
+        ));
+        assert!(OpContext::current().is_none());
+        assert!(within_current(async { Ok(1) }).await.is_ok());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use argon2::Argon2;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// Identifier recorded alongside password-encrypted payloads
+pub(crate) const KDF_ARGON2ID: &str = "argon2id";
+
+/// Derive a 256-bit AES key from a user supplied password using Argon2id
+pub(crate) fn derive_password_key(
+    password: &str,
+    salt: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, WalletError> {
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, key_bytes.as_mut())
+        .map_err(|e| WalletError::CryptoError(format!("Key derivation failed: {}", e)))?;
+    Ok(key_bytes)
+}
+
+/// Encrypt with AES-256-GCM under a random nonce, returning `(ciphertext, nonce)`
+pub(crate) fn aes_encrypt(
+    key_bytes: &[u8; 32],
+    plaintext: &[u8],
+    config: &WalletConfig,
+) -> Result<(Vec<u8>, Vec<u8>), WalletError> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key_bytes));
+    let nonce = config.random_bytes::<12>()?;
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|e| WalletError::CryptoError(format!("Encryption failed: {}", e)))?;
+
+    Ok((ciphertext, nonce.to_vec()))
+}
+
+/// Decrypt an AES-256-GCM ciphertext produced by [`aes_encrypt`]; the plaintext is wiped on drop
+pub(crate) fn aes_decrypt(
+    key_bytes: &[u8; 32],
+    nonce: &[u8],
+    ciphertext: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, WalletError> {
+    if nonce.len() != 12 {
+        return Err(WalletError::CryptoError("Invalid nonce length".to_string()));
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key_bytes));
+
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map(Zeroizing::new)
+        .map_err(|e| WalletError::CryptoError(format!("Decryption failed: {}", e)))
+}
+
+fn hmac_sha256_state(key: &[u8], data: &[u8]) -> Result<Hmac<Sha256>, WalletError> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+        .map_err(|e| WalletError::CryptoError(format!("Invalid HMAC key: {}", e)))?;
+    mac.update(data);
+    Ok(mac)
+}
+
+/// HMAC-SHA256 of `data` under `key`
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<[u8; 32], WalletError> {
+    Ok(hmac_sha256_state(key, data)?.finalize().into_bytes().into())
+}
+
+/// Whether `tag` is the HMAC-SHA256 of `data` under `key`, compared in constant time
+pub(crate) fn verify_hmac_sha256(key: &[u8], data: &[u8], tag: &[u8]) -> Result<bool, WalletError> {
+    Ok(hmac_sha256_state(key, data)?.verify_slice(tag).is_ok())
+}
+
+/// Take ownership of decrypted bytes as a string that is wiped on drop
+pub(crate) fn zeroizing_utf8(
+    mut plaintext: Zeroizing<Vec<u8>>,
+) -> Result<Zeroizing<String>, WalletError> {
+    // Move the buffer out so the only copy ends up inside the returned string
+    let bytes = std::mem::take(&mut *plaintext);
+    String::from_utf8(bytes).map(Zeroizing::new).map_err(|e| {
+        let error = e.utf8_error();
+        Zeroizing::new(e.into_bytes());
+        WalletError::CryptoError(format!(
+            "Failed to convert decrypted data to string: {}",
+            error
+        ))
+    })
+}
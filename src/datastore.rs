@@ -0,0 +1,138 @@
+//! Keys for DataLayer stores owned by this wallet.
+//!
+//! The owner is the wallet's own synthetic key, so a store minted with
+//! [`DatastoreOwnerKeys::owner_puzzle_hash`] is spendable by the same key that pays its fees.
+//! Admin and writer keys live on a dedicated hardened branch so they can be handed to
+//! delegates without exposing the owner key.
+
+use crate::error::WalletError;
+use crate::keys::{hardened_key, standard_puzzle_hash, synthetic_public_key, synthetic_secret_key};
+use crate::wallet::Wallet;
+#[cfg(feature = "peer")]
+use chia::bls::Signature;
+use chia::bls::{PublicKey, SecretKey};
+use chia::protocol::Bytes32;
+use chia::puzzles::DeriveSynthetic;
+#[cfg(feature = "peer")]
+use datalayer_driver::CoinSpend;
+use serde::{Deserialize, Serialize};
+
+/// Hardened purpose index of the datastore delegate branch, `m/12381/8444/11/<role>`
+pub const DATASTORE_KEY_PURPOSE: u32 = 11;
+
+/// Role a key plays in a DataLayer store's delegation layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatastoreRole {
+    /// Spends the store singleton and changes its delegated puzzles
+    Owner,
+    /// May update metadata and the set of writers
+    Admin,
+    /// May update metadata only
+    Writer,
+}
+
+/// Synthetic public keys and inner puzzle hashes of a store's owner, admin and writer
+///
+/// The puzzle hashes are what DataLayer store creation expects: the owner puzzle hash and
+/// the inner puzzle hashes of the admin and writer delegated puzzles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatastoreOwnerKeys {
+    pub owner_public_key: PublicKey,
+    pub owner_puzzle_hash: Bytes32,
+    pub admin_public_key: PublicKey,
+    pub admin_inner_puzzle_hash: Bytes32,
+    pub writer_public_key: PublicKey,
+    pub writer_inner_puzzle_hash: Bytes32,
+}
+
+/// Synthetic secret key of a delegate role, `m/12381/8444/11/<role>`
+fn delegate_secret_key(master_secret_key: &SecretKey, role: DatastoreRole) -> SecretKey {
+    let index = match role {
+        DatastoreRole::Owner => return synthetic_secret_key(master_secret_key),
+        DatastoreRole::Admin => 0,
+        DatastoreRole::Writer => 1,
+    };
+
+    hardened_key(master_secret_key, DATASTORE_KEY_PURPOSE, index).derive_synthetic()
+}
+
+impl Wallet {
+    /// Get the owner, admin and writer keys for DataLayer stores owned by this wallet
+    pub async fn get_datastore_owner_keys(&self) -> Result<DatastoreOwnerKeys, WalletError> {
+        let master_sk = self.get_master_secret_key().await?;
+        let owner_public_key = synthetic_public_key(&master_sk.public_key());
+        let admin_public_key = delegate_secret_key(&master_sk, DatastoreRole::Admin).public_key();
+        let writer_public_key = delegate_secret_key(&master_sk, DatastoreRole::Writer).public_key();
+
+        Ok(DatastoreOwnerKeys {
+            owner_public_key,
+            owner_puzzle_hash: standard_puzzle_hash(&owner_public_key),
+            admin_public_key,
+            admin_inner_puzzle_hash: standard_puzzle_hash(&admin_public_key),
+            writer_public_key,
+            writer_inner_puzzle_hash: standard_puzzle_hash(&writer_public_key),
+        })
+    }
+
+    /// Get the synthetic secret key of a datastore role
+    pub async fn get_datastore_secret_key(
+        &self,
+        role: DatastoreRole,
+    ) -> Result<SecretKey, WalletError> {
+        let master_sk = self.get_master_secret_key().await?;
+        Ok(delegate_secret_key(&master_sk, role))
+    }
+
+    /// Sign store spends, such as a delegation update, as the given role
+    #[cfg(feature = "peer")]
+    pub async fn sign_datastore_spends(
+        &self,
+        coin_spends: Vec<CoinSpend>,
+        role: DatastoreRole,
+        for_testnet: bool,
+    ) -> Result<Signature, WalletError> {
+        let secret_key = self.get_datastore_secret_key(role).await?;
+        datalayer_driver::sign_coin_spends(&coin_spends, &[secret_key], for_testnet)
+            .map_err(|e| WalletError::CryptoError(format!("Failed to sign store spends: {}", e)))
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+    #[tokio::test]
+    async fn test_datastore_keys_are_distinct_and_consistent() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::import_wallet_with_config("store", Some(TEST_MNEMONIC), &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("store".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        let keys = wallet.get_datastore_owner_keys().await.unwrap();
+        assert_eq!(
+            keys.owner_puzzle_hash,
+            wallet.get_owner_puzzle_hash().await.unwrap()
+        );
+        assert_ne!(keys.admin_public_key, keys.owner_public_key);
+        assert_ne!(keys.admin_public_key, keys.writer_public_key);
+
+        for (role, public_key) in [
+            (DatastoreRole::Owner, keys.owner_public_key),
+            (DatastoreRole::Admin, keys.admin_public_key),
+            (DatastoreRole::Writer, keys.writer_public_key),
+        ] {
+            let secret_key = wallet.get_datastore_secret_key(role).await.unwrap();
+            assert_eq!(secret_key.public_key(), public_key);
+        }
+    }
+}
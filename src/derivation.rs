@@ -0,0 +1,186 @@
+use crate::addresses::puzzle_hash_at_index;
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::wallet::Wallet;
+use chia::protocol::Bytes32;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Purpose of an allocated derivation index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DerivationKind {
+    Receive,
+    Change,
+}
+
+/// A single handed-out derivation index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexAllocation {
+    pub index: u32,
+    pub kind: DerivationKind,
+    /// Caller supplied label, e.g. an invoice or customer id
+    pub label: Option<String>,
+    pub allocated_at: u64,
+}
+
+/// Persisted derivation index allocation table of one wallet
+///
+/// Receive and change allocations share one index space so they never collide,
+/// and index 0 stays reserved for the wallet's default address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivationState {
+    pub next_index: u32,
+    pub allocations: BTreeMap<u32, IndexAllocation>,
+}
+
+impl Default for DerivationState {
+    fn default() -> Self {
+        Self {
+            next_index: 1,
+            allocations: BTreeMap::new(),
+        }
+    }
+}
+
+impl DerivationState {
+    /// Allocations of one kind, in index order
+    pub fn allocations_of(&self, kind: DerivationKind) -> Vec<&IndexAllocation> {
+        self.allocations
+            .values()
+            .filter(|allocation| allocation.kind == kind)
+            .collect()
+    }
+}
+
+/// An allocated address and the index it was derived from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocatedAddress {
+    pub index: u32,
+    pub kind: DerivationKind,
+    pub label: Option<String>,
+    /// Puzzle hash as hex
+    pub puzzle_hash: String,
+    pub address: String,
+}
+
+fn derivation_cache(config: &WalletConfig) -> Result<FileCache<DerivationState>, WalletError> {
+    FileCache::from_config("derivation", config)
+}
+
+impl Wallet {
+    /// Puzzle hash of the standard wallet key at a derivation index
+    ///
+    /// Same as [`Wallet::get_puzzle_hash_at`].
+    pub async fn get_puzzle_hash_at_index(&self, index: u32) -> Result<Bytes32, WalletError> {
+        self.get_puzzle_hash_at(index).await
+    }
+
+    /// The persisted derivation index allocation table of this wallet
+    pub fn derivation_state(&self) -> Result<DerivationState, WalletError> {
+        Ok(derivation_cache(self.get_config())?
+            .get(&self.cache_name())?
+            .unwrap_or_default())
+    }
+
+    /// Atomically allocate a fresh receive address, e.g. for an invoice
+    ///
+    /// Allocation is serialized across threads and processes sharing the `.dig`
+    /// directory, so no two callers are ever handed the same index.
+    pub async fn allocate_receive_address(
+        &self,
+        label: &str,
+    ) -> Result<AllocatedAddress, WalletError> {
+        self.allocate_index(DerivationKind::Receive, Some(label.to_string()))
+            .await
+    }
+
+    /// Atomically allocate a fresh change address
+    pub async fn allocate_change_address(&self) -> Result<AllocatedAddress, WalletError> {
+        self.allocate_index(DerivationKind::Change, None).await
+    }
+
+    async fn allocate_index(
+        &self,
+        kind: DerivationKind,
+        label: Option<String>,
+    ) -> Result<AllocatedAddress, WalletError> {
+        let master_pk = self.get_master_public_key().await?;
+        let allocated_at = self.get_config().unix_timestamp();
+
+        let index = derivation_cache(self.get_config())?
+            .update(&self.cache_name(), |state| {
+                let mut state = state.unwrap_or_default();
+                let index = state.next_index;
+                state.next_index = index.checked_add(1).ok_or_else(|| {
+                    WalletError::CryptoError("Derivation indexes exhausted".to_string())
+                })?;
+                state.allocations.insert(
+                    index,
+                    IndexAllocation {
+                        index,
+                        kind,
+                        label: label.clone(),
+                        allocated_at,
+                    },
+                );
+                Ok((state, index))
+            })
+            .await?;
+
+        let puzzle_hash = puzzle_hash_at_index(&master_pk, index);
+        let address = self.chain_profile().await?.encode_address(puzzle_hash)?;
+
+        Ok(AllocatedAddress {
+            index,
+            kind,
+            label,
+            puzzle_hash: hex::encode(puzzle_hash),
+            address,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use std::collections::HashSet;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_allocations_are_unique_and_persisted() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("shop", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("shop".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            wallet.get_puzzle_hash_at_index(0).await.unwrap(),
+            wallet.get_owner_puzzle_hash().await.unwrap()
+        );
+
+        let (a, b, c) = tokio::join!(
+            wallet.allocate_receive_address("invoice-1"),
+            wallet.allocate_receive_address("invoice-2"),
+            wallet.allocate_change_address(),
+        );
+        let allocated = [a.unwrap(), b.unwrap(), c.unwrap()];
+
+        let indexes: HashSet<u32> = allocated.iter().map(|a| a.index).collect();
+        assert_eq!(indexes, HashSet::from([1, 2, 3]));
+        let puzzle_hashes: HashSet<&str> =
+            allocated.iter().map(|a| a.puzzle_hash.as_str()).collect();
+        assert_eq!(puzzle_hashes.len(), 3);
+
+        let state = wallet.derivation_state().unwrap();
+        assert_eq!(state.next_index, 4);
+        assert_eq!(state.allocations_of(DerivationKind::Receive).len(), 2);
+        assert_eq!(state.allocations_of(DerivationKind::Change).len(), 1);
+    }
+}
@@ -0,0 +1,130 @@
+//! Key-free helpers for locating DIG tokens on chain.
+
+use crate::error::WalletError;
+use crate::keys::{decode_address, first_puzzle_hash};
+use datalayer_driver::{Bytes32, DigCoin, PublicKey};
+
+/// Asset id (TAIL hash) of the DIG CAT
+pub const DIG_COIN_ASSET_ID: Bytes32 = Bytes32::new([
+    0xa4, 0x06, 0xd3, 0xa9, 0xde, 0x98, 0x4d, 0x03, 0xc9, 0x59, 0x1c, 0x10, 0xd9, 0x17, 0x59, 0x3b,
+    0x43, 0x4d, 0x52, 0x63, 0xca, 0xbe, 0x2b, 0x42, 0xf6, 0xb3, 0x67, 0xdf, 0x16, 0x83, 0x2f, 0x81,
+]);
+
+/// Decimal places of DIG, as of every CAT
+pub const DIG_DECIMALS: u32 = 3;
+/// Mojos in one DIG
+pub const MOJOS_PER_DIG: u64 = 10u64.pow(DIG_DECIMALS);
+
+/// Convert a decimal DIG amount such as `"12.5"` to mojos
+///
+/// Fails on anything that is not a plain non-negative decimal with at most
+/// [`DIG_DECIMALS`] fractional digits, or that overflows `u64`.
+pub fn dig_to_mojos(amount: &str) -> Result<u64, WalletError> {
+    let invalid = || WalletError::InvalidAmount(format!("{} is not a DIG amount", amount));
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > DIG_DECIMALS as usize
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| invalid())?
+    };
+    let fraction: u64 = format!("{:0<width$}", fraction, width = DIG_DECIMALS as usize)
+        .parse()
+        .map_err(|_| invalid())?;
+    whole
+        .checked_mul(MOJOS_PER_DIG)
+        .and_then(|mojos| mojos.checked_add(fraction))
+        .ok_or_else(invalid)
+}
+
+/// Format a mojo amount as DIG with all [`DIG_DECIMALS`] places, e.g. `12.500`
+pub fn mojos_to_dig(mojos: u64) -> String {
+    format!(
+        "{}.{:0width$}",
+        mojos / MOJOS_PER_DIG,
+        mojos % MOJOS_PER_DIG,
+        width = DIG_DECIMALS as usize
+    )
+}
+
+/// DIG CAT outer puzzle hash wrapping a standard inner puzzle hash
+pub fn dig_outer_puzzle_hash_for_inner(inner_puzzle_hash: Bytes32) -> Bytes32 {
+    DigCoin::puzzle_hash(inner_puzzle_hash)
+}
+
+/// DIG CAT outer puzzle hash of a wallet's first address, from its master public key
+pub fn dig_outer_puzzle_hash_for(master_public_key: &PublicKey) -> Bytes32 {
+    dig_outer_puzzle_hash_for_inner(first_puzzle_hash(master_public_key))
+}
+
+/// DIG CAT outer puzzle hash of the coins an XCH address receives DIG at
+pub fn dig_outer_puzzle_hash_for_address(address: &str) -> Result<Bytes32, WalletError> {
+    Ok(dig_outer_puzzle_hash_for_inner(decode_address(address)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use crate::wallet::Wallet;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_outer_puzzle_hash_matches_wallet() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("indexed", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("indexed".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        let master_pk = wallet.get_master_secret_key().await.unwrap().public_key();
+        let expected = DigCoin::puzzle_hash(wallet.get_owner_puzzle_hash().await.unwrap());
+        assert_eq!(dig_outer_puzzle_hash_for(&master_pk), expected);
+
+        let address = wallet.get_owner_public_key().await.unwrap();
+        assert_eq!(
+            dig_outer_puzzle_hash_for_address(&address).unwrap(),
+            expected
+        );
+        assert!(dig_outer_puzzle_hash_for_address("not-an-address").is_err());
+    }
+
+    #[test]
+    fn test_dig_decimal_conversion() {
+        assert_eq!(dig_to_mojos("12.5").unwrap(), 12_500);
+        assert_eq!(dig_to_mojos("1").unwrap(), MOJOS_PER_DIG);
+        assert_eq!(dig_to_mojos(".001").unwrap(), 1);
+        assert_eq!(dig_to_mojos("0.120").unwrap(), 120);
+        for invalid in [
+            "",
+            ".",
+            "1.0001",
+            "-1",
+            "1e3",
+            "1.2.3",
+            "99999999999999999999",
+        ] {
+            assert!(matches!(
+                dig_to_mojos(invalid),
+                Err(WalletError::InvalidAmount(_))
+            ));
+        }
+
+        assert_eq!(mojos_to_dig(12_500), "12.500");
+        assert_eq!(mojos_to_dig(1), "0.001");
+        assert_eq!(dig_to_mojos(&mojos_to_dig(u64::MAX)).unwrap(), u64::MAX);
+    }
+}
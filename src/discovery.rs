@@ -0,0 +1,219 @@
+//! Pluggable coin discovery.
+//!
+//! Each [`CoinDiscovery`] plugin declares the puzzle hashes it watches for a wallet and
+//! interprets the coins found there. [`Wallet::discover_coins`] runs the built-in plugins
+//! plus any registered with [`crate::WalletConfig::with_coin_discovery`], so new coin types
+//! can be synced without changing the wallet's sync loop.
+
+use crate::chain::fetch_unspent_coins;
+use crate::error::WalletError;
+use crate::wallet::Wallet;
+#[cfg(feature = "cat")]
+use datalayer_driver::DigCoin;
+use datalayer_driver::{Bytes32, Coin, CoinState, Peer};
+use serde_json::Value;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Boxed future returned by [`CoinDiscovery::interpret`]
+pub type DiscoveryFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, WalletError>> + Send + 'a>>;
+
+/// A coin type the wallet can find and recognise
+pub trait CoinDiscovery: Send + Sync + Debug {
+    /// Stable name, reported on every coin the plugin discovers
+    fn name(&self) -> &str;
+
+    /// Puzzle hashes to watch for a wallet with the given owner puzzle hash
+    fn puzzle_hashes(&self, owner_puzzle_hash: Bytes32) -> Vec<Bytes32>;
+
+    /// Interpret an unspent coin found at one of the watched puzzle hashes
+    ///
+    /// `Ok(None)` ignores the coin; `Err` reports it in [`DiscoveryReport::failures`].
+    fn interpret<'a>(
+        &'a self,
+        peer: &'a Peer,
+        coin_state: &'a CoinState,
+    ) -> DiscoveryFuture<'a, Option<DiscoveredCoin>>;
+}
+
+/// A coin recognised by a discovery plugin
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredCoin {
+    /// Name of the plugin that found the coin
+    pub plugin: String,
+    pub coin: Coin,
+    /// Plugin-specific interpretation, such as an asset id or NFT metadata
+    pub details: Value,
+}
+
+impl DiscoveredCoin {
+    pub fn new(plugin: &str, coin: Coin) -> Self {
+        Self {
+            plugin: plugin.to_string(),
+            coin,
+            details: Value::Null,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// Coins found by every plugin, and the coins or puzzle hashes that could not be processed
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryReport {
+    pub coins: Vec<DiscoveredCoin>,
+    /// `(plugin, coin id or puzzle hash, error)`
+    pub failures: Vec<(String, Bytes32, String)>,
+}
+
+impl DiscoveryReport {
+    /// Coins found by the named plugin
+    pub fn coins_of<'a>(&'a self, plugin: &'a str) -> impl Iterator<Item = &'a DiscoveredCoin> {
+        self.coins.iter().filter(move |coin| coin.plugin == plugin)
+    }
+}
+
+/// Standard XCH coins at the owner puzzle hash
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XchDiscovery;
+
+impl CoinDiscovery for XchDiscovery {
+    fn name(&self) -> &str {
+        "xch"
+    }
+
+    fn puzzle_hashes(&self, owner_puzzle_hash: Bytes32) -> Vec<Bytes32> {
+        vec![owner_puzzle_hash]
+    }
+
+    fn interpret<'a>(
+        &'a self,
+        _peer: &'a Peer,
+        coin_state: &'a CoinState,
+    ) -> DiscoveryFuture<'a, Option<DiscoveredCoin>> {
+        Box::pin(async move { Ok(Some(DiscoveredCoin::new(self.name(), coin_state.coin))) })
+    }
+}
+
+/// DIG CAT coins whose lineage can be proved
+#[cfg(feature = "cat")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DigDiscovery;
+
+#[cfg(feature = "cat")]
+impl CoinDiscovery for DigDiscovery {
+    fn name(&self) -> &str {
+        "dig"
+    }
+
+    fn puzzle_hashes(&self, owner_puzzle_hash: Bytes32) -> Vec<Bytes32> {
+        vec![DigCoin::puzzle_hash(owner_puzzle_hash)]
+    }
+
+    fn interpret<'a>(
+        &'a self,
+        peer: &'a Peer,
+        coin_state: &'a CoinState,
+    ) -> DiscoveryFuture<'a, Option<DiscoveredCoin>> {
+        Box::pin(async move {
+            let dig_coin = DigCoin::from_coin_state(peer, coin_state)
+                .await
+                .map_err(|e| WalletError::DataLayerError(format!("Invalid DIG lineage: {}", e)))?;
+            Ok(Some(DiscoveredCoin::new(self.name(), dig_coin.cat().coin)))
+        })
+    }
+}
+
+impl Wallet {
+    /// Find unspent coins with the built-in and registered discovery plugins
+    pub async fn discover_coins(&self, peer: &Peer) -> Result<DiscoveryReport, WalletError> {
+        let owner_puzzle_hash = self.get_owner_puzzle_hash().await?;
+        let mut report = DiscoveryReport::default();
+
+        for plugin in self.get_config().coin_discovery_plugins() {
+            for puzzle_hash in plugin.puzzle_hashes(owner_puzzle_hash) {
+                let coin_states = match fetch_unspent_coins(peer, puzzle_hash).await {
+                    Ok((coin_states, _)) => coin_states,
+                    Err(e) => {
+                        report.failures.push((
+                            plugin.name().to_string(),
+                            puzzle_hash,
+                            e.to_string(),
+                        ));
+                        continue;
+                    }
+                };
+
+                for coin_state in &coin_states {
+                    match plugin.interpret(peer, coin_state).await {
+                        Ok(Some(coin)) => report.coins.push(coin),
+                        Ok(None) => {}
+                        Err(e) => report.failures.push((
+                            plugin.name().to_string(),
+                            coin_state.coin.coin_id(),
+                            e.to_string(),
+                        )),
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// The plugins every wallet runs before the registered ones
+pub(crate) fn builtin_plugins() -> Vec<Arc<dyn CoinDiscovery>> {
+    #[cfg_attr(not(feature = "cat"), allow(unused_mut))]
+    let mut plugins: Vec<Arc<dyn CoinDiscovery>> = vec![Arc::new(XchDiscovery)];
+    #[cfg(feature = "cat")]
+    plugins.push(Arc::new(DigDiscovery));
+    plugins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+
+    #[derive(Debug)]
+    struct ClawbackDiscovery;
+
+    impl CoinDiscovery for ClawbackDiscovery {
+        fn name(&self) -> &str {
+            "clawback"
+        }
+
+        fn puzzle_hashes(&self, owner_puzzle_hash: Bytes32) -> Vec<Bytes32> {
+            vec![Bytes32::new([owner_puzzle_hash.as_ref()[0] ^ 1; 32])]
+        }
+
+        fn interpret<'a>(
+            &'a self,
+            _peer: &'a Peer,
+            _coin_state: &'a CoinState,
+        ) -> DiscoveryFuture<'a, Option<DiscoveredCoin>> {
+            Box::pin(async { Ok(None) })
+        }
+    }
+
+    #[test]
+    fn test_registered_plugins_run_after_builtins() {
+        let config = WalletConfig::new().with_coin_discovery(ClawbackDiscovery);
+        let names: Vec<String> = config
+            .coin_discovery_plugins()
+            .iter()
+            .map(|plugin| plugin.name().to_string())
+            .collect();
+
+        assert_eq!(names.first().map(String::as_str), Some("xch"));
+        assert_eq!(names.last().map(String::as_str), Some("clawback"));
+        #[cfg(feature = "cat")]
+        assert!(names.contains(&"dig".to_string()));
+    }
+}
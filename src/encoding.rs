@@ -0,0 +1,20 @@
+use crate::error::WalletError;
+use chia::protocol::Bytes32;
+
+/// Parse a 32-byte value from hex, accepting an optional `0x` prefix
+pub(crate) fn bytes32_from_hex(value: &str) -> Result<Bytes32, WalletError> {
+    let bytes = hex::decode(value.trim_start_matches("0x"))
+        .map_err(|e| WalletError::SerializationError(format!("Invalid hex: {}", e)))?;
+
+    let array: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| WalletError::SerializationError("Expected 32 bytes of hex".to_string()))?;
+
+    Ok(Bytes32::new(array))
+}
+
+/// Parse arbitrary bytes from hex, accepting an optional `0x` prefix
+pub(crate) fn bytes_from_hex(value: &str) -> Result<Vec<u8>, WalletError> {
+    hex::decode(value.trim_start_matches("0x"))
+        .map_err(|e| WalletError::SerializationError(format!("Invalid hex: {}", e)))
+}
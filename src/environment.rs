@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Source of process-level context (home directory, environment variables, clock)
+///
+/// Everything the crate would otherwise read from global process state goes through
+/// this trait, so tests and embedders can run fully sandboxed without mutating
+/// `std::env` from multiple threads.
+pub trait Environment: Send + Sync + Debug {
+    /// The user's home directory
+    fn home_dir(&self) -> Option<PathBuf>;
+
+    /// Read an environment variable
+    fn var(&self, key: &str) -> Option<String>;
+
+    /// The current wall clock time
+    fn now(&self) -> SystemTime;
+}
+
+/// The real process environment
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemEnvironment;
+
+impl Environment for SystemEnvironment {
+    fn home_dir(&self) -> Option<PathBuf> {
+        dirs::home_dir()
+    }
+
+    fn var(&self, key: &str) -> Option<String> {
+        env::var(key).ok()
+    }
+
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// An in-memory environment with an explicit home directory, variables and optional fixed clock
+#[derive(Debug, Clone, Default)]
+pub struct SandboxEnvironment {
+    home_dir: Option<PathBuf>,
+    vars: HashMap<String, String>,
+    fixed_time: Option<SystemTime>,
+}
+
+impl SandboxEnvironment {
+    /// Create a sandbox rooted at the given home directory
+    pub fn new(home_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            home_dir: Some(home_dir.into()),
+            vars: HashMap::new(),
+            fixed_time: None,
+        }
+    }
+
+    /// Set a variable visible through [`Environment::var`]
+    pub fn with_var(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.insert(key.into(), value.into());
+        self
+    }
+
+    /// Freeze the clock at the given time
+    pub fn with_fixed_time(mut self, time: SystemTime) -> Self {
+        self.fixed_time = Some(time);
+        self
+    }
+}
+
+impl Environment for SandboxEnvironment {
+    fn home_dir(&self) -> Option<PathBuf> {
+        self.home_dir.clone()
+    }
+
+    fn var(&self, key: &str) -> Option<String> {
+        self.vars.get(key).cloned()
+    }
+
+    fn now(&self) -> SystemTime {
+        self.fixed_time.unwrap_or_else(SystemTime::now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sandbox_environment_is_isolated() {
+        let fixed = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let sandbox = SandboxEnvironment::new("/sandbox/home")
+            .with_var("TEST_KEYRING_PATH", "/sandbox/keyring.json")
+            .with_fixed_time(fixed);
+
+        assert_eq!(sandbox.home_dir(), Some(PathBuf::from("/sandbox/home")));
+        assert_eq!(
+            sandbox.var("TEST_KEYRING_PATH").as_deref(),
+            Some("/sandbox/keyring.json")
+        );
+        assert_eq!(sandbox.var("HOME"), None);
+        assert_eq!(sandbox.now(), fixed);
+    }
+}
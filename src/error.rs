@@ -1,3 +1,4 @@
+use crate::asset::Asset;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -40,4 +41,123 @@ pub enum WalletError {
 
     #[error("CoinSetError: {0}")]
     CoinSetError(String),
+
+    #[error("Transaction rejected: {0}")]
+    TransactionFailed(String),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Expected {expected} but got {found}; the wallet is used on another chain")]
+    ChainMismatch { expected: String, found: String },
+
+    #[error("Coin {coin_id} is already spent by queued transaction {other_tx}")]
+    ConflictingSpend { coin_id: String, other_tx: String },
+
+    #[error("Remote wallet error: {0}")]
+    RemoteError(String),
+
+    #[error("Keyring conflict: {0}")]
+    KeyringConflict(String),
+
+    #[error("Keyring is held by another process: {0}")]
+    KeyringBusy(String),
+
+    #[error("Keyring is corrupted: {0}")]
+    KeyringCorrupted(String),
+
+    #[error("A different wallet is already stored as {0}")]
+    WalletExists(String),
+
+    #[error("Mnemonic with fingerprint {fingerprint} is already stored as {}", existing.join(", "))]
+    DuplicateMnemonic {
+        fingerprint: u32,
+        existing: Vec<String>,
+    },
+
+    #[error("Invalid message template: {0}")]
+    InvalidMessageTemplate(String),
+
+    #[error("Invalid mnemonic share: {0}")]
+    InvalidMnemonicShare(String),
+
+    #[error("Invalid threshold key: {0}")]
+    InvalidThresholdKey(String),
+
+    #[error("Wallet is watch-only and holds no secret keys")]
+    WatchOnly,
+
+    #[error("Peer peak height {peer_height} is below the required height {required_height}")]
+    StalePeer {
+        peer_height: u32,
+        required_height: u32,
+    },
+
+    #[error("Insufficient {asset} balance: {required} required, {available} available")]
+    InsufficientBalance {
+        asset: Asset,
+        required: u64,
+        available: u64,
+    },
+
+    #[error("Feature `{0}` is not enabled")]
+    FeatureDisabled(String),
+
+    #[error("Operation was cancelled")]
+    Cancelled,
+
+    #[error("Peer {peer} presented certificate {fingerprint}, which is not pinned")]
+    PeerCertificateMismatch { peer: String, fingerprint: String },
+
+    #[error(
+        "Selection needs at least {needed_inputs} inputs, more than the {max_inputs} allowed; consolidate small coins first"
+    )]
+    SelectionTooFragmented {
+        needed_inputs: usize,
+        max_inputs: usize,
+    },
+
+    #[error("Coin {coin_id} is reserved by {owner}")]
+    CoinReserved { coin_id: String, owner: String },
+
+    #[error("Fee of {fee} mojos is below the minimum of {minimum} mojos")]
+    FeeBelowMinimum { fee: u64, minimum: u64 },
+
+    #[error("Keyring format version {found} is newer than the supported version {supported}")]
+    UnsupportedKeyringVersion { found: u32, supported: u32 },
+
+    #[error(
+        "Keyring format version {found} must be migrated to version {current} before it is written"
+    )]
+    KeyringMigrationRequired { found: u32, current: u32 },
+
+    #[error("Wallet {0} is passphrase-protected; unlock it with its passphrase")]
+    KeyringLocked(String),
+
+    #[error("Incorrect keyring passphrase")]
+    InvalidPassphrase,
+
+    #[error("Keyring checksum is not keyed by the keyring passphrase; adopt the keyring with Wallet::migrate_keyring")]
+    KeyringUnkeyed,
+
+    #[error("Mnemonic reveal was not confirmed")]
+    RevealDeclined,
+
+    #[error("Wallet session is locked; unlock it again to sign")]
+    WalletLocked,
+
+    #[error("Operation {trace_id} ran out of time before completing")]
+    DeadlineExceeded { trace_id: String },
+
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+
+    #[error("Invalid offer: {0}")]
+    InvalidOffer(String),
+
+    #[error("NFT {0} is not held by this wallet")]
+    NftNotFound(String),
+
+    #[error("Nonce store is full with {0} unexpired nonces")]
+    NonceStoreFull(usize),
 }
@@ -0,0 +1,108 @@
+//! Minimum fees enforced before building transactions.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::wallet::Wallet;
+use datalayer_driver::Peer;
+
+/// Confirmation target, in seconds, whose fee estimate serves as the mempool minimum
+pub const MEMPOOL_MINIMUM_TARGET_SECS: u64 = 600;
+
+/// Lowest fee the wallet will build a transaction with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeFloor {
+    min_fee: u64,
+    allow_zero_fee: bool,
+    check_mempool: bool,
+}
+
+impl FeeFloor {
+    /// Refuse fees below `min_fee` mojos
+    pub fn new(min_fee: u64) -> Self {
+        Self {
+            min_fee,
+            ..Self::default()
+        }
+    }
+
+    /// Still accept an explicit zero fee, e.g. for free transactions in an empty mempool
+    pub fn allow_zero_fee(mut self) -> Self {
+        self.allow_zero_fee = true;
+        self
+    }
+
+    /// Also refuse fees below the peer's current estimate for [`MEMPOOL_MINIMUM_TARGET_SECS`]
+    pub fn with_mempool_check(mut self) -> Self {
+        self.check_mempool = true;
+        self
+    }
+
+    /// Check a fee against the floor and, when known, the mempool minimum
+    pub fn evaluate(&self, fee: u64, mempool_minimum: Option<u64>) -> Result<(), WalletError> {
+        if fee == 0 && self.allow_zero_fee {
+            return Ok(());
+        }
+
+        let minimum = self.min_fee.max(mempool_minimum.unwrap_or(0));
+        if fee < minimum {
+            return Err(WalletError::FeeBelowMinimum { fee, minimum });
+        }
+
+        Ok(())
+    }
+}
+
+impl Wallet {
+    /// Fail with [`WalletError::FeeBelowMinimum`] if `fee` is under the configured floor
+    ///
+    /// A no-op unless the configuration carries a [`FeeFloor`]. The mempool preflight
+    /// is skipped when the peer cannot produce an estimate.
+    pub async fn check_fee_floor(
+        peer: &Peer,
+        fee: u64,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        let Some(floor) = config.fee_floor() else {
+            return Ok(());
+        };
+
+        let mempool_minimum = if floor.check_mempool && !(fee == 0 && floor.allow_zero_fee) {
+            datalayer_driver::get_fee_estimate(peer, MEMPOOL_MINIMUM_TARGET_SECS)
+                .await
+                .ok()
+        } else {
+            None
+        };
+
+        floor.evaluate(fee, mempool_minimum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_applies_floor_and_mempool_minimum() {
+        let floor = FeeFloor::new(1_000);
+        assert!(floor.evaluate(1_000, None).is_ok());
+        assert!(matches!(
+            floor.evaluate(0, None),
+            Err(WalletError::FeeBelowMinimum {
+                fee: 0,
+                minimum: 1_000
+            })
+        ));
+        assert!(floor.allow_zero_fee().evaluate(0, None).is_ok());
+        assert!(floor.allow_zero_fee().evaluate(500, None).is_err());
+
+        assert!(matches!(
+            floor.evaluate(2_000, Some(5_000)),
+            Err(WalletError::FeeBelowMinimum {
+                fee: 2_000,
+                minimum: 5_000
+            })
+        ));
+        assert!(floor.evaluate(2_000, Some(10)).is_ok());
+    }
+}
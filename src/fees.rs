@@ -0,0 +1,117 @@
+//! Fee estimation from the cost of a transaction and the mempool of a peer.
+//!
+//! A full node estimates a fee rate, in mojos per unit of CLVM cost, that gets a transaction
+//! included by a target time given what is waiting in its mempool. The fee of a transaction
+//! is that rate times its cost, which is measured by running its coin spends.
+
+use crate::error::WalletError;
+use crate::wallet::{Wallet, DEFAULT_FEE_COIN_COST};
+use datalayer_driver::{CoinSpend, Peer};
+
+/// Confirmation target of fee estimates unless configured otherwise, in seconds
+pub const DEFAULT_FEE_TARGET_SECS: u64 = 300;
+
+/// How soon a transaction should be included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeTarget {
+    /// Within this many seconds
+    Seconds(u64),
+    /// Within this many blocks, at the average block time of 18.75 seconds
+    Blocks(u32),
+}
+
+impl FeeTarget {
+    /// The target as seconds from now, as asked of the peer
+    pub fn seconds(&self) -> u64 {
+        match self {
+            FeeTarget::Seconds(seconds) => *seconds,
+            FeeTarget::Blocks(blocks) => (u64::from(*blocks) * 75 + 3) / 4,
+        }
+    }
+}
+
+impl Default for FeeTarget {
+    fn default() -> Self {
+        FeeTarget::Seconds(DEFAULT_FEE_TARGET_SECS)
+    }
+}
+
+/// Fee for a transaction and what it was derived from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimate {
+    /// CLVM cost of the transaction
+    pub cost: u64,
+    /// Rate the peer estimates for the target
+    pub mojos_per_cost: u64,
+    /// Fee in mojos, `cost` times `mojos_per_cost`
+    pub fee: u64,
+}
+
+impl FeeEstimate {
+    /// The fee for `cost` at `mojos_per_cost`
+    pub fn new(cost: u64, mojos_per_cost: u64) -> Self {
+        Self {
+            cost,
+            mojos_per_cost,
+            fee: cost.saturating_mul(mojos_per_cost),
+        }
+    }
+}
+
+/// CLVM cost of running `coin_spends` in one block generator
+///
+/// Without coin spends, the cost of spending one standard coin stands in.
+pub fn spend_cost(coin_spends: Option<&[CoinSpend]>) -> Result<u64, WalletError> {
+    match coin_spends {
+        Some(coin_spends) => datalayer_driver::get_cost(coin_spends).map_err(|e| {
+            WalletError::TransactionFailed(format!("Failed to compute spend cost: {}", e))
+        }),
+        None => Ok(DEFAULT_FEE_COIN_COST),
+    }
+}
+
+impl Wallet {
+    /// Estimate the fee that gets `coin_spends` included within `target`
+    ///
+    /// The rate comes from the peer's mempool; an empty mempool yields a zero fee.
+    pub async fn estimate_fee(
+        peer: &Peer,
+        coin_spends: Option<&[CoinSpend]>,
+        target: FeeTarget,
+    ) -> Result<FeeEstimate, WalletError> {
+        let cost = spend_cost(coin_spends)?;
+        let mojos_per_cost = datalayer_driver::get_fee_estimate(peer, target.seconds())
+            .await
+            .map_err(|e| WalletError::NetworkError(format!("Failed to get fee estimate: {}", e)))?;
+        Ok(FeeEstimate::new(cost, mojos_per_cost))
+    }
+
+    /// Estimate the fee for `coin_spends` at the configured [`FeeTarget`]
+    pub async fn estimate_fee_for(
+        &self,
+        peer: &Peer,
+        coin_spends: Option<&[CoinSpend]>,
+    ) -> Result<FeeEstimate, WalletError> {
+        Self::estimate_fee(peer, coin_spends, self.get_config().fee_target()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_target_and_estimate() {
+        assert_eq!(FeeTarget::Seconds(60).seconds(), 60);
+        assert_eq!(FeeTarget::Blocks(4).seconds(), 75);
+        assert_eq!(FeeTarget::Blocks(1).seconds(), 19);
+        assert_eq!(FeeTarget::Blocks(0).seconds(), 0);
+        assert_eq!(FeeTarget::default().seconds(), DEFAULT_FEE_TARGET_SECS);
+
+        let estimate = FeeEstimate::new(11_000_000, 5);
+        assert_eq!(estimate.fee, 55_000_000);
+        assert_eq!(FeeEstimate::new(11_000_000, 0).fee, 0);
+        assert_eq!(FeeEstimate::new(u64::MAX, 2).fee, u64::MAX);
+        assert_eq!(spend_cost(None).unwrap(), DEFAULT_FEE_COIN_COST);
+    }
+}
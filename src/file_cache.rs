@@ -1,8 +1,90 @@
+#[cfg(feature = "peer")]
+use crate::cancel::CancellationToken;
+use crate::config::WalletConfig;
 use crate::error::WalletError;
+use crate::file_lock::with_file_lock;
+use crate::wallet::Wallet;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+#[cfg(feature = "peer")]
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+/// Bounds on one cache directory, enforced by [`FileCache::compact`]
+///
+/// The least recently written entries are evicted first. Unbounded by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheLimits {
+    pub max_entries: Option<usize>,
+    pub max_bytes: Option<u64>,
+}
+
+impl CacheLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    fn allows(&self, entries: usize, bytes: u64) -> bool {
+        self.max_entries.map_or(true, |max| entries <= max)
+            && self.max_bytes.map_or(true, |max| bytes <= max)
+    }
+}
+
+/// Outcome of [`FileCache::compact`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Temporary files left behind by interrupted writes
+    pub removed_temp_files: usize,
+    /// Entries evicted to get within the limits
+    pub evicted: usize,
+    /// Entries left afterwards
+    pub entries: usize,
+    /// Bytes of entries left afterwards
+    pub bytes: u64,
+}
+
+impl CompactionReport {
+    fn absorb(&mut self, other: CompactionReport) {
+        self.removed_temp_files += other.removed_temp_files;
+        self.evicted += other.evicted;
+        self.entries += other.entries;
+        self.bytes += other.bytes;
+    }
+}
+
+/// In-process locks of the cache keys being written, keyed by lock file path
+///
+/// Shared by every [`FileCache`] of the process, so two instances opened on the same
+/// directory still take turns.
+static KEY_LOCKS: Mutex<BTreeMap<PathBuf, Weak<tokio::sync::Mutex<()>>>> =
+    Mutex::new(BTreeMap::new());
+
+/// The async lock of `lock_path`, created on first use and dropped with its last holder
+fn key_mutex(lock_path: &Path) -> Arc<tokio::sync::Mutex<()>> {
+    let mut locks = KEY_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(lock) = locks.get(lock_path).and_then(Weak::upgrade) {
+        return lock;
+    }
+
+    locks.retain(|_, lock| lock.strong_count() > 0);
+    let lock = Arc::new(tokio::sync::Mutex::new(()));
+    locks.insert(lock_path.to_path_buf(), Arc::downgrade(&lock));
+    lock
+}
 
 /// A simple file-based cache implementation similar to the TypeScript FileCache
 pub struct FileCache<T>
@@ -10,6 +92,7 @@ where
     T: Serialize + for<'de> Deserialize<'de>,
 {
     cache_dir: PathBuf,
+    limits: CacheLimits,
     _phantom: PhantomData<T>,
 }
 
@@ -21,17 +104,25 @@ where
     pub fn new(relative_file_path: &str, base_dir: Option<&Path>) -> Result<Self, WalletError> {
         let base_path = match base_dir {
             Some(dir) => dir.to_path_buf(),
-            None => dirs::home_dir()
-                .ok_or_else(|| {
-                    WalletError::FileSystemError("Could not find home directory".to_string())
-                })?
-                .join(".dig"),
+            None => WalletConfig::default().dig_dir()?,
         };
 
-        let cache_dir = base_path.join(relative_file_path);
+        Self::at_path(base_path.join(relative_file_path))
+    }
+
+    /// Create a new FileCache instance under the configured `.dig` directory
+    pub fn from_config(
+        relative_file_path: &str,
+        config: &WalletConfig,
+    ) -> Result<Self, WalletError> {
+        Ok(Self::at_path(config.dig_dir()?.join(relative_file_path))?
+            .with_limits(config.cache_limits(relative_file_path)))
+    }
 
+    fn at_path(cache_dir: PathBuf) -> Result<Self, WalletError> {
         let cache = Self {
             cache_dir,
+            limits: CacheLimits::default(),
             _phantom: PhantomData,
         };
         cache.ensure_directory_exists()?;
@@ -39,6 +130,12 @@ where
         Ok(cache)
     }
 
+    /// Bound this cache; the limits take effect on the next [`FileCache::compact`]
+    pub fn with_limits(mut self, limits: CacheLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Ensure the cache directory exists
     fn ensure_directory_exists(&self) -> Result<(), WalletError> {
         if !self.cache_dir.exists() {
@@ -74,29 +171,110 @@ where
     }
 
     /// Save data to the cache
-    pub fn set(&self, key: &str, data: &T) -> Result<(), WalletError> {
-        let cache_file_path = self.get_cache_file_path(key);
+    ///
+    /// The value is written to a temporary file and renamed into place while holding
+    /// the key's lock, so readers never see partially written JSON.
+    pub async fn set(&self, key: &str, data: &T) -> Result<(), WalletError> {
+        self.with_key_lock(key, || self.write_atomic(key, data))
+            .await
+    }
 
-        let serialized_data = serde_json::to_string_pretty(data).map_err(|e| {
-            WalletError::SerializationError(format!("Failed to serialize cache data: {}", e))
-        })?;
+    /// Save data to the cache and return the value it replaced
+    pub async fn replace(&self, key: &str, data: &T) -> Result<Option<T>, WalletError> {
+        self.with_key_lock(key, || {
+            let previous = self.get(key)?;
+            self.write_atomic(key, data)?;
+            Ok(previous)
+        })
+        .await
+    }
 
-        fs::write(&cache_file_path, serialized_data).map_err(|e| {
-            WalletError::FileSystemError(format!("Failed to write cache file: {}", e))
-        })?;
+    /// Save `data` only if the current value equals `expected` (`None` meaning absent)
+    ///
+    /// Returns whether the value was written.
+    pub async fn compare_and_set(
+        &self,
+        key: &str,
+        expected: Option<&T>,
+        data: &T,
+    ) -> Result<bool, WalletError>
+    where
+        T: PartialEq,
+    {
+        self.with_key_lock(key, || {
+            if self.get(key)?.as_ref() != expected {
+                return Ok(false);
+            }
+            self.write_atomic(key, data)?;
+            Ok(true)
+        })
+        .await
+    }
 
-        Ok(())
+    /// Atomically read, modify and write a cached value
+    ///
+    /// Writers of the same key are serialized through a per-key async lock and a lock
+    /// file, so concurrent tasks and processes never observe or overwrite each other's
+    /// partial updates.
+    pub async fn update<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(Option<T>) -> Result<(T, R), WalletError>,
+    ) -> Result<R, WalletError> {
+        self.with_key_lock(key, || {
+            self.get(key).and_then(f).and_then(|(data, result)| {
+                self.write_atomic(key, &data)?;
+                Ok(result)
+            })
+        })
+        .await
     }
 
     /// Delete cached data by key
-    pub fn delete(&self, key: &str) -> Result<(), WalletError> {
+    pub async fn delete(&self, key: &str) -> Result<(), WalletError> {
+        self.with_key_lock(key, || {
+            let cache_file_path = self.get_cache_file_path(key);
+
+            if cache_file_path.exists() {
+                fs::remove_file(&cache_file_path).map_err(|e| {
+                    WalletError::FileSystemError(format!("Failed to delete cache file: {}", e))
+                })?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Run `f` while holding the lock of `key`, shared across tasks and processes
+    ///
+    /// Tasks of this process queue on the key's async lock; only its holder takes the
+    /// lock file that excludes other processes.
+    async fn with_key_lock<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce() -> Result<R, WalletError>,
+    ) -> Result<R, WalletError> {
+        let lock_path = self.cache_dir.join(format!("{}.lock", key));
+        let key_mutex = key_mutex(&lock_path);
+        let _guard = key_mutex.lock().await;
+        with_file_lock(&lock_path, f).await
+    }
+
+    /// Write a value through a temporary file and rename it into place; callers hold the key lock
+    fn write_atomic(&self, key: &str, data: &T) -> Result<(), WalletError> {
         let cache_file_path = self.get_cache_file_path(key);
+        let temp_path = self.cache_dir.join(format!("{}.json.tmp", key));
 
-        if cache_file_path.exists() {
-            fs::remove_file(&cache_file_path).map_err(|e| {
-                WalletError::FileSystemError(format!("Failed to delete cache file: {}", e))
-            })?;
-        }
+        let serialized_data = serde_json::to_string_pretty(data).map_err(|e| {
+            WalletError::SerializationError(format!("Failed to serialize cache data: {}", e))
+        })?;
+        fs::write(&temp_path, serialized_data).map_err(|e| {
+            WalletError::FileSystemError(format!("Failed to write cache file: {}", e))
+        })?;
+        fs::rename(&temp_path, &cache_file_path).map_err(|e| {
+            WalletError::FileSystemError(format!("Failed to replace cache file: {}", e))
+        })?;
 
         Ok(())
     }
@@ -130,15 +308,143 @@ where
     }
 
     /// Clear all cached data
-    pub fn clear(&self) -> Result<(), WalletError> {
+    pub async fn clear(&self) -> Result<(), WalletError> {
         let keys = self.get_cached_keys()?;
 
         for key in keys {
-            self.delete(&key)?;
+            self.delete(&key).await?;
         }
 
         Ok(())
     }
+
+    /// Remove leftovers of interrupted writes and evict entries beyond the limits
+    pub async fn compact(&self) -> Result<CompactionReport, WalletError> {
+        let mut report = CompactionReport::default();
+        if !self.cache_dir.exists() {
+            return Ok(report);
+        }
+
+        let entries = fs::read_dir(&self.cache_dir).map_err(|e| {
+            WalletError::FileSystemError(format!("Failed to read cache directory: {}", e))
+        })?;
+
+        let mut cached = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| {
+                WalletError::FileSystemError(format!("Failed to read directory entry: {}", e))
+            })?;
+            let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+
+            if let Some(key) = file_name.strip_suffix(".json.tmp") {
+                // A live writer holds the key lock for as long as its temporary file exists
+                let temp_path = entry.path();
+                if self
+                    .with_key_lock(key, || Ok(fs::remove_file(&temp_path).is_ok()))
+                    .await?
+                {
+                    report.removed_temp_files += 1;
+                }
+            } else if let Some(key) = file_name.strip_suffix(".json") {
+                let metadata = entry.metadata().map_err(|e| {
+                    WalletError::FileSystemError(format!("Failed to read cache file: {}", e))
+                })?;
+                let written = metadata.modified().unwrap_or(UNIX_EPOCH);
+                cached.push((written, metadata.len(), key.to_string()));
+            }
+        }
+
+        cached.sort();
+        report.entries = cached.len();
+        report.bytes = cached.iter().map(|(_, size, _)| size).sum();
+        for (_, size, key) in cached {
+            if self.limits.allows(report.entries, report.bytes) {
+                break;
+            }
+            self.delete(&key).await?;
+            report.entries -= 1;
+            report.bytes -= size;
+            report.evicted += 1;
+        }
+
+        Ok(report)
+    }
+}
+
+/// Compact `dir` and every cache directory below it under `limits`
+async fn compact_tree(dir: &Path, limits: CacheLimits) -> Result<CompactionReport, WalletError> {
+    let mut report = CompactionReport::default();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir).map_err(|e| {
+            WalletError::FileSystemError(format!("Failed to read cache directory: {}", e))
+        })?;
+        for entry in entries {
+            let entry = entry.map_err(|e| {
+                WalletError::FileSystemError(format!("Failed to read directory entry: {}", e))
+            })?;
+            if entry.path().is_dir() {
+                pending.push(entry.path());
+            }
+        }
+
+        report.absorb(
+            FileCache::<serde_json::Value>::at_path(dir)?
+                .with_limits(limits)
+                .compact()
+                .await?,
+        );
+    }
+
+    Ok(report)
+}
+
+impl Wallet {
+    /// Compact every cache with limits configured through [`WalletConfig::with_cache_limits`]
+    ///
+    /// Covers the caches of all wallets sharing this wallet's `.dig` directory.
+    pub async fn compact_caches(&self) -> Result<CompactionReport, WalletError> {
+        let dig_dir = self.get_config().dig_dir()?;
+        let mut report = CompactionReport::default();
+        for (cache, limits) in self.get_config().limited_caches() {
+            let dir = dig_dir.join(cache);
+            if dir.is_dir() {
+                report.absorb(compact_tree(&dir, limits).await?);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Run [`Wallet::compact_caches`] every `interval` on the configured runtime until
+    /// `cancellation` fires
+    ///
+    /// Failed compactions are skipped; the next tick tries again.
+    #[cfg(feature = "peer")]
+    pub fn spawn_cache_maintenance(
+        &self,
+        interval: Duration,
+        cancellation: CancellationToken,
+    ) -> Result<(), WalletError> {
+        let runtime = self.get_config().runtime()?;
+        let wallet = self.clone();
+
+        runtime.spawn(async move {
+            while !cancellation.is_cancelled() {
+                let _ = wallet.compact_caches().await;
+                let _ = cancellation
+                    .run(async {
+                        tokio::time::sleep(interval).await;
+                        Ok(())
+                    })
+                    .await;
+            }
+        });
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -159,8 +465,8 @@ mod tests {
         number: i32,
     }
 
-    #[test]
-    fn test_file_cache_operations() {
+    #[tokio::test]
+    async fn test_file_cache_operations() {
         let temp_dir = TempDir::new().unwrap();
         let cache = FileCache::<TestData>::new("test_cache", Some(temp_dir.path())).unwrap();
 
@@ -170,7 +476,7 @@ mod tests {
         };
 
         // Test set and get
-        cache.set("test_key", &test_data).unwrap();
+        cache.set("test_key", &test_data).await.unwrap();
         let retrieved = cache.get("test_key").unwrap().unwrap();
         assert_eq!(retrieved, test_data);
 
@@ -183,8 +489,79 @@ mod tests {
         assert_eq!(keys, vec!["test_key"]);
 
         // Test delete
-        cache.delete("test_key").unwrap();
+        cache.delete("test_key").await.unwrap();
         let deleted = cache.get("test_key").unwrap();
         assert!(deleted.is_none());
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_update_is_atomic_across_tasks() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = FileCache::<u32>::new("counter", Some(temp_dir.path())).unwrap();
+
+        let cache = Arc::new(cache);
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                tokio::spawn(async move {
+                    for _ in 0..10 {
+                        cache
+                            .update("count", |current| {
+                                let next = current.unwrap_or(0) + 1;
+                                Ok((next, ()))
+                            })
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        assert_eq!(cache.get("count").unwrap(), Some(80));
+        assert_eq!(cache.get_cached_keys().unwrap(), vec!["count"]);
+    }
+
+    #[tokio::test]
+    async fn test_replace_and_compare_and_set() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = FileCache::<u32>::new("reservations", Some(temp_dir.path())).unwrap();
+
+        assert_eq!(cache.replace("coin", &1).await.unwrap(), None);
+        assert_eq!(cache.replace("coin", &2).await.unwrap(), Some(1));
+
+        assert!(!cache.compare_and_set("coin", Some(&1), &3).await.unwrap());
+        assert!(cache.compare_and_set("coin", Some(&2), &3).await.unwrap());
+        assert!(!cache.compare_and_set("coin", None, &4).await.unwrap());
+        assert!(cache.compare_and_set("other", None, &4).await.unwrap());
+
+        assert_eq!(cache.get("coin").unwrap(), Some(3));
+        assert_eq!(cache.get("other").unwrap(), Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_compact_evicts_least_recently_written() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = FileCache::<u32>::new("coins", Some(temp_dir.path()))
+            .unwrap()
+            .with_limits(CacheLimits::new().with_max_entries(2));
+
+        for (i, key) in ["a", "b", "c"].into_iter().enumerate() {
+            cache.set(key, &(i as u32)).await.unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        cache.set("a", &9).await.unwrap();
+        fs::write(temp_dir.path().join("coins/d.json.tmp"), "{").unwrap();
+
+        let report = cache.compact().await.unwrap();
+        assert_eq!(report.removed_temp_files, 1);
+        assert_eq!(report.evicted, 1);
+        assert_eq!(report.entries, 2);
+
+        let mut keys = cache.get_cached_keys().unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["a", "c"]);
+    }
 }
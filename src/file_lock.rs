@@ -0,0 +1,153 @@
+//! Cross-process exclusive locks guarding read-modify-write updates.
+//!
+//! Locks are OS advisory locks (`flock` on Unix, `LockFileEx` on Windows) on a lock file
+//! next to the guarded data. The OS releases them when their holder exits, so a crashed
+//! writer never leaves a lock behind and lock files are never deleted.
+
+use crate::error::WalletError;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long a writer waits for a concurrent writer before giving up
+pub(crate) const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+pub(crate) const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How [`with_file_lock_policy`] waits for a concurrent writer
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LockPolicy {
+    pub(crate) timeout: Duration,
+    pub(crate) retry_interval: Duration,
+    /// Error reported when `timeout` passes
+    pub(crate) busy: fn(String) -> WalletError,
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: LOCK_TIMEOUT,
+            retry_interval: LOCK_RETRY_INTERVAL,
+            busy: WalletError::FileSystemError,
+        }
+    }
+}
+
+/// Run `f` while holding an exclusive lock on `lock_path`, shared across processes
+#[cfg(feature = "cache")]
+pub(crate) async fn with_file_lock<R>(
+    lock_path: &Path,
+    f: impl FnOnce() -> Result<R, WalletError>,
+) -> Result<R, WalletError> {
+    with_file_lock_policy(lock_path, &LockPolicy::default(), f).await
+}
+
+/// [`with_file_lock`] waiting as `policy` says
+///
+/// Waiting blocks, so it runs on tokio's blocking pool when called from a tokio runtime.
+pub(crate) async fn with_file_lock_policy<R>(
+    lock_path: &Path,
+    policy: &LockPolicy,
+    f: impl FnOnce() -> Result<R, WalletError>,
+) -> Result<R, WalletError> {
+    let (lock_path, policy) = (lock_path.to_path_buf(), *policy);
+    let lock_file = match tokio::runtime::Handle::try_current() {
+        Ok(handle) => handle
+            .spawn_blocking(move || acquire(&lock_path, &policy))
+            .await
+            .map_err(|e| WalletError::FileSystemError(format!("Lock task failed: {}", e)))??,
+        Err(_) => acquire(&lock_path, &policy)?,
+    };
+
+    let result = f();
+    drop(lock_file);
+    result
+}
+
+/// Open `lock_path` and lock it, retrying until `policy.timeout` passes
+fn acquire(lock_path: &Path, policy: &LockPolicy) -> Result<File, WalletError> {
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)
+        .map_err(|e| WalletError::FileSystemError(format!("Failed to open lock file: {}", e)))?;
+
+    let started = Instant::now();
+    loop {
+        match try_lock(&file) {
+            Ok(true) => return Ok(file),
+            Ok(false) => {
+                if started.elapsed() > policy.timeout {
+                    return Err((policy.busy)(format!(
+                        "Timed out waiting for lock {}",
+                        lock_path.display()
+                    )));
+                }
+                std::thread::sleep(policy.retry_interval);
+            }
+            Err(e) => {
+                return Err(WalletError::FileSystemError(format!(
+                    "Failed to lock {}: {}",
+                    lock_path.display(),
+                    e
+                )))
+            }
+        }
+    }
+}
+
+/// Take an exclusive lock on `file` without waiting, returning `false` if another holds it
+///
+/// The lock is released when the file is closed.
+#[cfg(unix)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the descriptor belongs to `file`, which outlives the call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => Ok(false),
+        _ => Err(error),
+    }
+}
+
+#[cfg(windows)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::ERROR_LOCK_VIOLATION;
+    use windows_sys::Win32::Storage::FileSystem::{
+        LockFileEx, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY,
+    };
+    use windows_sys::Win32::System::IO::OVERLAPPED;
+
+    // SAFETY: the handle belongs to `file`, which outlives the call, and OVERLAPPED is
+    // plain data whose zeroed offset locks from the start of the file
+    let locked = unsafe {
+        let mut overlapped: OVERLAPPED = std::mem::zeroed();
+        LockFileEx(
+            file.as_raw_handle() as _,
+            LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY,
+            0,
+            u32::MAX,
+            u32::MAX,
+            &mut overlapped,
+        )
+    };
+    if locked != 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(code) if code == ERROR_LOCK_VIOLATION as i32 => Ok(false),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn try_lock(_file: &File) -> io::Result<bool> {
+    Ok(true)
+}
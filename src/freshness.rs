@@ -0,0 +1,128 @@
+use crate::chain::fetch_peak_height;
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::wallet::Wallet;
+use datalayer_driver::Peer;
+
+/// Default number of blocks a peer may trail the best reference peer
+pub const DEFAULT_MAX_PEAK_LAG: u32 = 10;
+
+/// Requirements a peer's peak must meet before the wallet broadcasts through it
+#[derive(Debug, Clone)]
+pub struct PeakFreshness {
+    max_lag: u32,
+    min_height: Option<u32>,
+    reference_peers: Vec<Peer>,
+}
+
+impl Default for PeakFreshness {
+    fn default() -> Self {
+        Self {
+            max_lag: DEFAULT_MAX_PEAK_LAG,
+            min_height: None,
+            reference_peers: Vec::new(),
+        }
+    }
+}
+
+impl PeakFreshness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Blocks the peer may trail the highest reference peer
+    pub fn with_max_lag(mut self, max_lag: u32) -> Self {
+        self.max_lag = max_lag;
+        self
+    }
+
+    /// Absolute height the peer must have reached
+    pub fn with_min_height(mut self, min_height: u32) -> Self {
+        self.min_height = Some(min_height);
+        self
+    }
+
+    /// Peers whose peaks the broadcasting peer is compared against, e.g. [`crate::PeerPool::peers`]
+    pub fn with_reference_peers(mut self, peers: impl IntoIterator<Item = Peer>) -> Self {
+        self.reference_peers = peers.into_iter().collect();
+        self
+    }
+
+    /// Check a peer height against the minimum and the best reference height
+    ///
+    /// Unreachable reference peers are skipped by the caller, so `reference_heights`
+    /// may be empty, in which case only the minimum applies.
+    pub fn evaluate(&self, peer_height: u32, reference_heights: &[u32]) -> Result<(), WalletError> {
+        if let Some(min_height) = self.min_height {
+            if peer_height < min_height {
+                return Err(WalletError::StalePeer {
+                    peer_height,
+                    required_height: min_height,
+                });
+            }
+        }
+
+        if let Some(best) = reference_heights.iter().copied().max() {
+            let required_height = best.saturating_sub(self.max_lag);
+            if peer_height < required_height {
+                return Err(WalletError::StalePeer {
+                    peer_height,
+                    required_height,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Wallet {
+    /// Fail with [`WalletError::StalePeer`] if `peer` lags the configured freshness window
+    ///
+    /// A no-op unless the configuration carries a [`PeakFreshness`].
+    pub async fn check_peer_freshness(
+        peer: &Peer,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        let Some(freshness) = config.peak_freshness() else {
+            return Ok(());
+        };
+
+        let peer_height = fetch_peak_height(peer).await?;
+        let mut reference_heights = Vec::with_capacity(freshness.reference_peers.len());
+        for reference in &freshness.reference_peers {
+            if let Ok(height) = fetch_peak_height(reference).await {
+                reference_heights.push(height);
+            }
+        }
+
+        freshness.evaluate(peer_height, &reference_heights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_applies_minimum_and_lag() {
+        let freshness = PeakFreshness::new().with_max_lag(5).with_min_height(100);
+
+        assert!(freshness.evaluate(120, &[]).is_ok());
+        assert!(freshness.evaluate(120, &[125, 90]).is_ok());
+        assert!(matches!(
+            freshness.evaluate(99, &[]),
+            Err(WalletError::StalePeer {
+                peer_height: 99,
+                required_height: 100
+            })
+        ));
+        assert!(matches!(
+            freshness.evaluate(120, &[126]),
+            Err(WalletError::StalePeer {
+                peer_height: 120,
+                required_height: 121
+            })
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::chain::{fetch_block_info, genesis_challenge};
+use crate::error::WalletError;
+use crate::mnemonic::parse_mnemonic;
+use crate::wallet::Wallet;
+use datalayer_driver::Peer;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Clock skew against the latest transaction block that triggers a warning
+pub const CLOCK_SKEW_WARN_SECS: u64 = 5 * 60;
+/// Clock skew against the latest transaction block that fails the check
+pub const CLOCK_SKEW_FAIL_SECS: u64 = 60 * 60;
+/// How many blocks below the peak to search for a transaction block timestamp
+const TIMESTAMP_SEARCH_DEPTH: u32 = 10;
+
+/// Severity of a single health check result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthLevel {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Result of one health check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthCheck {
+    pub name: String,
+    pub level: HealthLevel,
+    pub detail: String,
+}
+
+/// Structured outcome of [`Wallet::health_check`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    /// The worst level across all checks
+    pub fn overall(&self) -> HealthLevel {
+        self.checks
+            .iter()
+            .map(|check| check.level)
+            .max()
+            .unwrap_or(HealthLevel::Pass)
+    }
+
+    /// Whether no check failed; warnings do not affect readiness
+    pub fn is_ready(&self) -> bool {
+        self.overall() != HealthLevel::Fail
+    }
+
+    /// Look up a check by name
+    pub fn check(&self, name: &str) -> Option<&HealthCheck> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+
+    fn push(&mut self, name: &str, level: HealthLevel, detail: impl Into<String>) {
+        self.checks.push(HealthCheck {
+            name: name.to_string(),
+            level,
+            detail: detail.into(),
+        });
+    }
+}
+
+impl Wallet {
+    /// Validate keyring, mnemonic, cache directory, file permissions and, given a peer,
+    /// peer reachability and clock skew against chain timestamps
+    pub async fn health_check(&self, peer: Option<&Peer>) -> HealthReport {
+        let mut report = HealthReport { checks: vec![] };
+
+        self.check_keyring(&mut report).await;
+        self.check_mnemonic(&mut report);
+        self.check_cache_dir(&mut report);
+        self.check_permissions(&mut report);
+
+        if let Some(peer) = peer {
+            self.check_peer(peer, &mut report).await;
+        }
+
+        report
+    }
+
+    async fn check_keyring(&self, report: &mut HealthReport) {
+        if self.is_watch_only() {
+            return report.push("keyring", HealthLevel::Pass, "watch-only entry");
+        }
+        match Self::get_wallet_from_keyring(self.get_wallet_name(), self.get_config()).await {
+            Ok(Some(stored))
+                if self
+                    .with_mnemonic(|m| m == stored.as_str())
+                    .unwrap_or(false) =>
+            {
+                report.push("keyring", HealthLevel::Pass, "keyring entry decrypts")
+            }
+            Ok(Some(_)) => report.push(
+                "keyring",
+                HealthLevel::Warn,
+                "keyring entry decrypts but differs from the loaded mnemonic",
+            ),
+            Ok(None) => report.push(
+                "keyring",
+                HealthLevel::Fail,
+                format!("wallet {} is not in the keyring", self.get_wallet_name()),
+            ),
+            Err(e) => report.push("keyring", HealthLevel::Fail, e.to_string()),
+        }
+    }
+
+    fn check_mnemonic(&self, report: &mut HealthReport) {
+        if self.is_watch_only() {
+            return report.push("mnemonic", HealthLevel::Pass, "watch-only wallet has none");
+        }
+        let parsed = self.with_mnemonic(|mnemonic| parse_mnemonic(mnemonic).map(|_| ()));
+        match parsed {
+            Ok(Ok(())) => report.push("mnemonic", HealthLevel::Pass, "checksum valid"),
+            Ok(Err(e)) => report.push("mnemonic", HealthLevel::Fail, e.to_string()),
+            Err(e) => report.push("mnemonic", HealthLevel::Fail, e.to_string()),
+        }
+    }
+
+    fn check_cache_dir(&self, report: &mut HealthReport) {
+        let probe = || -> Result<(), WalletError> {
+            let dig_dir = self.get_config().dig_dir()?;
+            fs::create_dir_all(&dig_dir)
+                .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+            let probe_path = dig_dir.join(".health_probe");
+            fs::write(&probe_path, b"ok")
+                .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+            fs::remove_file(&probe_path).map_err(|e| WalletError::FileSystemError(e.to_string()))
+        };
+
+        match probe() {
+            Ok(()) => report.push("cache_dir", HealthLevel::Pass, "writable"),
+            Err(e) => report.push("cache_dir", HealthLevel::Fail, e.to_string()),
+        }
+    }
+
+    #[cfg(unix)]
+    fn check_permissions(&self, report: &mut HealthReport) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let keyring_path = match self.get_config().keyring_path() {
+            Ok(path) => path,
+            Err(e) => return report.push("permissions", HealthLevel::Fail, e.to_string()),
+        };
+
+        match fs::metadata(&keyring_path) {
+            Ok(metadata) if metadata.permissions().mode() & 0o077 != 0 => report.push(
+                "permissions",
+                HealthLevel::Warn,
+                format!(
+                    "{} is accessible by other users (mode {:o})",
+                    keyring_path.display(),
+                    metadata.permissions().mode() & 0o777
+                ),
+            ),
+            Ok(_) => report.push("permissions", HealthLevel::Pass, "keyring is owner-only"),
+            Err(e) => report.push("permissions", HealthLevel::Fail, e.to_string()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn check_permissions(&self, report: &mut HealthReport) {
+        report.push(
+            "permissions",
+            HealthLevel::Pass,
+            "not checked on this platform",
+        );
+    }
+
+    async fn check_peer(&self, peer: &Peer, report: &mut HealthReport) {
+        let owner_puzzle_hash = match self.get_owner_puzzle_hash().await {
+            Ok(puzzle_hash) => puzzle_hash,
+            Err(e) => return report.push("peer", HealthLevel::Fail, e.to_string()),
+        };
+
+        let peak = match datalayer_driver::async_api::get_all_unspent_coins(
+            peer,
+            owner_puzzle_hash,
+            None,
+            genesis_challenge(),
+        )
+        .await
+        {
+            Ok(unspent) => {
+                report.push(
+                    "peer",
+                    HealthLevel::Pass,
+                    format!("reachable, synced to height {}", unspent.last_height),
+                );
+                unspent.last_height
+            }
+            Err(e) => return report.push("peer", HealthLevel::Fail, e.to_string()),
+        };
+
+        let mut chain_timestamp = None;
+        for height in (peak.saturating_sub(TIMESTAMP_SEARCH_DEPTH)..=peak).rev() {
+            if let Ok((_, Some(timestamp))) = fetch_block_info(peer, height).await {
+                chain_timestamp = Some(timestamp);
+                break;
+            }
+        }
+
+        let Some(chain_timestamp) = chain_timestamp else {
+            return report.push(
+                "clock_skew",
+                HealthLevel::Warn,
+                "no recent transaction block timestamp available",
+            );
+        };
+
+        let now = self.get_config().unix_timestamp();
+        let skew = now.abs_diff(chain_timestamp);
+        let level = if skew >= CLOCK_SKEW_FAIL_SECS {
+            HealthLevel::Fail
+        } else if skew >= CLOCK_SKEW_WARN_SECS {
+            HealthLevel::Warn
+        } else {
+            HealthLevel::Pass
+        };
+        report.push(
+            "clock_skew",
+            level,
+            format!("local clock differs from chain by {}s", skew),
+        );
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_health_check_without_peer() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("healthy", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("healthy".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let keyring_path = config.keyring_path().unwrap();
+            fs::set_permissions(&keyring_path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        let report = wallet.health_check(None).await;
+        assert_eq!(report.overall(), HealthLevel::Pass, "{:?}", report);
+        assert!(report.check("peer").is_none());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let keyring_path = config.keyring_path().unwrap();
+            fs::set_permissions(&keyring_path, fs::Permissions::from_mode(0o644)).unwrap();
+            let report = wallet.health_check(None).await;
+            assert_eq!(
+                report.check("permissions").unwrap().level,
+                HealthLevel::Warn
+            );
+            assert!(report.is_ready());
+        }
+
+        Wallet::delete_wallet_with_config("healthy", &config)
+            .await
+            .unwrap();
+        let report = wallet.health_check(None).await;
+        assert_eq!(report.check("keyring").unwrap().level, HealthLevel::Fail);
+        assert!(!report.is_ready());
+    }
+}
@@ -0,0 +1,418 @@
+use crate::asset::Asset;
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::wallet::Wallet;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::ops::RangeInclusive;
+
+/// Direction of a transaction relative to the wallet that recorded it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionDirection {
+    Incoming,
+    Outgoing,
+    /// A move between two wallets of the same keyring; neither income nor expense
+    Internal,
+}
+
+/// A single entry in a wallet's transaction history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    /// Spend bundle name as hex
+    pub tx_id: String,
+    pub asset: Asset,
+    pub direction: TransactionDirection,
+    /// Amount moved, in mojos of `asset`
+    pub amount: u64,
+    /// Network fee actually paid (spent minus created value), always in XCH mojos
+    pub fee: u64,
+    /// Puzzle hash of the other side of the transaction as hex
+    pub counterparty_puzzle_hash: Option<String>,
+    /// Name of the other wallet for internal moves
+    pub counterparty_wallet: Option<String>,
+    /// Coin ids consumed by the transaction as hex
+    pub spent_coin_ids: Vec<String>,
+    /// Confirmation height, once known
+    pub height: Option<u32>,
+    /// Unix timestamp (seconds) at which the record was created
+    pub timestamp: u64,
+}
+
+/// Locally persisted transaction history of one wallet, keyed by transaction id
+pub struct TransactionHistory {
+    cache: FileCache<TransactionRecord>,
+}
+
+impl TransactionHistory {
+    /// Open the history of a wallet under the configured `.dig` directory
+    pub fn open(wallet_name: &str, config: &WalletConfig) -> Result<Self, WalletError> {
+        Ok(Self {
+            cache: FileCache::from_config(&format!("history/{}", wallet_name), config)?,
+        })
+    }
+
+    /// Insert or replace a record
+    pub async fn record(&self, record: &TransactionRecord) -> Result<(), WalletError> {
+        self.cache.set(&record.tx_id, record).await
+    }
+
+    /// Get a record by transaction id
+    pub fn get(&self, tx_id: &str) -> Result<Option<TransactionRecord>, WalletError> {
+        self.cache.get(tx_id)
+    }
+
+    /// All records, oldest first
+    pub fn list(&self) -> Result<Vec<TransactionRecord>, WalletError> {
+        let mut records = Vec::new();
+        for key in self.cache.get_cached_keys()? {
+            if let Some(record) = self.cache.get(&key)? {
+                records.push(record);
+            }
+        }
+        records.sort_by(|a, b| record_key(a).cmp(&record_key(b)));
+        Ok(records)
+    }
+
+    /// A page of records matching `filter`, starting after `cursor`
+    ///
+    /// Only `page_size + 1` records are held in memory at a time, regardless of
+    /// history size.
+    pub fn query(
+        &self,
+        filter: &TxFilter,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<TransactionPage, WalletError> {
+        let after = cursor.map(decode_cursor).transpose()?;
+        let capacity = page_size + 1;
+        let mut heap = BinaryHeap::with_capacity(capacity + 1);
+        let mut total_matching = 0;
+
+        for key in self.cache.get_cached_keys()? {
+            let Some(record) = self.cache.get(&key)? else {
+                continue;
+            };
+            if !filter.matches(&record) {
+                continue;
+            }
+            total_matching += 1;
+
+            if let Some((timestamp, tx_id)) = &after {
+                if record_key(&record) <= (*timestamp, tx_id.as_str()) {
+                    continue;
+                }
+            }
+
+            heap.push(OrderedRecord(record));
+            if heap.len() > capacity {
+                heap.pop();
+            }
+        }
+
+        let mut records: Vec<TransactionRecord> = heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|ordered| ordered.0)
+            .collect();
+
+        let next_cursor = if records.len() > page_size {
+            records.truncate(page_size);
+            records.last().map(encode_cursor)
+        } else {
+            None
+        };
+
+        Ok(TransactionPage {
+            records,
+            next_cursor,
+            total_matching,
+        })
+    }
+}
+
+/// Criteria for [`Wallet::query_transactions`]; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxFilter {
+    pub asset: Option<Asset>,
+    pub direction: Option<TransactionDirection>,
+    /// Only confirmed records within this height range
+    pub height_range: Option<RangeInclusive<u32>>,
+    pub min_amount: Option<u64>,
+}
+
+impl TxFilter {
+    /// Whether a record satisfies every set criterion
+    pub fn matches(&self, record: &TransactionRecord) -> bool {
+        self.asset.map_or(true, |asset| record.asset == asset)
+            && self
+                .direction
+                .map_or(true, |direction| record.direction == direction)
+            && self.height_range.as_ref().map_or(true, |range| {
+                record.height.is_some_and(|height| range.contains(&height))
+            })
+            && self.min_amount.map_or(true, |min| record.amount >= min)
+    }
+}
+
+/// One page of [`Wallet::query_transactions`] results, oldest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionPage {
+    pub records: Vec<TransactionRecord>,
+    /// Cursor for the following page, if there is one
+    pub next_cursor: Option<String>,
+    /// Number of records matching the filter across all pages
+    pub total_matching: usize,
+}
+
+/// Sort key shared by history listing and pagination: timestamp, then transaction id
+fn record_key(record: &TransactionRecord) -> (u64, &str) {
+    (record.timestamp, record.tx_id.as_str())
+}
+
+fn encode_cursor(record: &TransactionRecord) -> String {
+    format!("{}:{}", record.timestamp, record.tx_id)
+}
+
+fn decode_cursor(cursor: &str) -> Result<(u64, String), WalletError> {
+    cursor
+        .split_once(':')
+        .and_then(|(timestamp, tx_id)| Some((timestamp.parse().ok()?, tx_id.to_string())))
+        .ok_or_else(|| WalletError::SerializationError(format!("Invalid cursor: {}", cursor)))
+}
+
+/// Fees paid by this wallet, skipping the receiving side of transactions
+fn total_fees(records: &[TransactionRecord], range: &RangeInclusive<u64>) -> u64 {
+    records
+        .iter()
+        .filter(|record| record.direction != TransactionDirection::Incoming)
+        .filter(|record| range.contains(&record.timestamp))
+        .map(|record| record.fee)
+        .sum()
+}
+
+/// Max-heap entry ordered by [`record_key`]
+struct OrderedRecord(TransactionRecord);
+
+impl PartialEq for OrderedRecord {
+    fn eq(&self, other: &Self) -> bool {
+        record_key(&self.0) == record_key(&other.0)
+    }
+}
+
+impl Eq for OrderedRecord {}
+
+impl PartialOrd for OrderedRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        record_key(&self.0).cmp(&record_key(&other.0))
+    }
+}
+
+/// Income/expense view over a wallet's history for a single asset
+///
+/// Internal moves are listed but never counted as income or expense.
+/// Fees are always XCH mojos and are totalled separately.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ledger {
+    pub asset: Asset,
+    pub records: Vec<TransactionRecord>,
+    pub total_income: u64,
+    pub total_expense: u64,
+    pub total_fees: u64,
+}
+
+impl Ledger {
+    /// Build a ledger for `asset` from a set of history records
+    pub fn from_records(asset: Asset, records: Vec<TransactionRecord>) -> Self {
+        let records: Vec<TransactionRecord> =
+            records.into_iter().filter(|r| r.asset == asset).collect();
+
+        let mut total_income = 0u64;
+        let mut total_expense = 0u64;
+        let mut total_fees = 0u64;
+
+        for record in &records {
+            match record.direction {
+                TransactionDirection::Incoming => total_income += record.amount,
+                TransactionDirection::Outgoing => total_expense += record.amount,
+                TransactionDirection::Internal => {}
+            }
+            total_fees += record.fee;
+        }
+
+        Self {
+            asset,
+            records,
+            total_income,
+            total_expense,
+            total_fees,
+        }
+    }
+
+    /// Render the ledger as CSV, one row per record
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "tx_id,timestamp,height,asset,direction,amount,fee,counterparty_puzzle_hash,counterparty_wallet\n",
+        );
+
+        for record in &self.records {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                record.tx_id,
+                record.timestamp,
+                record.height.map(|h| h.to_string()).unwrap_or_default(),
+                record.asset,
+                match record.direction {
+                    TransactionDirection::Incoming => "incoming",
+                    TransactionDirection::Outgoing => "outgoing",
+                    TransactionDirection::Internal => "internal",
+                },
+                record.amount,
+                record.fee,
+                record
+                    .counterparty_puzzle_hash
+                    .as_deref()
+                    .unwrap_or_default(),
+                record.counterparty_wallet.as_deref().unwrap_or_default(),
+            ));
+        }
+
+        csv
+    }
+}
+
+impl Wallet {
+    /// Open the locally persisted transaction history of this wallet
+    pub fn transaction_history(&self) -> Result<TransactionHistory, WalletError> {
+        TransactionHistory::open(&self.cache_name(), self.get_config())
+    }
+
+    /// Query this wallet's history page by page; pass the returned `next_cursor` as `page`
+    pub fn query_transactions(
+        &self,
+        filter: TxFilter,
+        page: Option<&str>,
+        page_size: usize,
+    ) -> Result<TransactionPage, WalletError> {
+        self.transaction_history()?.query(&filter, page, page_size)
+    }
+
+    /// Total XCH fees this wallet paid for transactions recorded within a timestamp range
+    pub fn total_fees_paid(&self, range: RangeInclusive<u64>) -> Result<u64, WalletError> {
+        let records = self.transaction_history()?.list()?;
+        Ok(total_fees(&records, &range))
+    }
+
+    /// Export the income/expense ledger of this wallet for an asset
+    pub fn export_ledger(&self, asset: Asset) -> Result<Ledger, WalletError> {
+        let records = self.transaction_history()?.list()?;
+        Ok(Ledger::from_records(asset, records))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    fn record(tx_id: &str, direction: TransactionDirection, amount: u64) -> TransactionRecord {
+        TransactionRecord {
+            tx_id: tx_id.to_string(),
+            asset: Asset::Xch,
+            direction,
+            amount,
+            fee: 10,
+            counterparty_puzzle_hash: None,
+            counterparty_wallet: None,
+            spent_coin_ids: vec![],
+            height: None,
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ledger_excludes_internal_moves() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let history = TransactionHistory::open("ledger_test", &config).unwrap();
+
+        history
+            .record(&record("a1", TransactionDirection::Incoming, 500))
+            .await
+            .unwrap();
+        history
+            .record(&record("b2", TransactionDirection::Outgoing, 200))
+            .await
+            .unwrap();
+        history
+            .record(&record("c3", TransactionDirection::Internal, 1_000))
+            .await
+            .unwrap();
+
+        let mut dig = record("d4", TransactionDirection::Incoming, 7);
+        dig.asset = Asset::Dig;
+        history.record(&dig).await.unwrap();
+
+        let ledger = Ledger::from_records(Asset::Xch, history.list().unwrap());
+        assert_eq!(ledger.records.len(), 3);
+        assert_eq!(ledger.total_income, 500);
+        assert_eq!(ledger.total_expense, 200);
+        assert_eq!(ledger.total_fees, 30);
+
+        let csv = ledger.to_csv();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.contains("c3,1700000000,,xch,internal,1000,10,,"));
+
+        // Incoming transactions were paid for by the sender
+        let records = history.list().unwrap();
+        assert_eq!(total_fees(&records, &(0..=u64::MAX)), 20);
+        assert_eq!(total_fees(&records, &(0..=1_600_000_000)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_pages_with_cursor() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let history = TransactionHistory::open("query_test", &config).unwrap();
+
+        for i in 0..5u32 {
+            let mut incoming = record(&format!("in{}", i), TransactionDirection::Incoming, 100);
+            incoming.timestamp = 1_000 + u64::from(i);
+            incoming.height = Some(10 + i);
+            history.record(&incoming).await.unwrap();
+        }
+        history
+            .record(&record("out", TransactionDirection::Outgoing, 100))
+            .await
+            .unwrap();
+
+        let filter = TxFilter {
+            direction: Some(TransactionDirection::Incoming),
+            height_range: Some(11..=14),
+            ..Default::default()
+        };
+
+        let first = history.query(&filter, None, 2).unwrap();
+        assert_eq!(first.total_matching, 4);
+        let ids: Vec<&str> = first.records.iter().map(|r| r.tx_id.as_str()).collect();
+        assert_eq!(ids, vec!["in1", "in2"]);
+
+        let second = history
+            .query(&filter, first.next_cursor.as_deref(), 2)
+            .unwrap();
+        let ids: Vec<&str> = second.records.iter().map(|r| r.tx_id.as_str()).collect();
+        assert_eq!(ids, vec!["in3", "in4"]);
+        assert!(second.next_cursor.is_none());
+
+        assert!(history.query(&filter, Some("garbage"), 2).is_err());
+    }
+}
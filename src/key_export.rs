@@ -0,0 +1,334 @@
+use crate::config::WalletConfig;
+use crate::crypto::{aes_decrypt, aes_encrypt, derive_password_key, zeroizing_utf8, KDF_ARGON2ID};
+use crate::error::WalletError;
+use crate::keys::{farmer_secret_key, first_puzzle_hash, pool_secret_key, synthetic_public_key};
+use crate::wallet::Wallet;
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// Derivation path of the first observer wallet key, as reported by `chia keys show`
+pub const WALLET_DERIVATION_PATH: &str = "m/12381/8444/2/0";
+/// Derivation path of the farmer key
+pub const FARMER_DERIVATION_PATH: &str = "m/12381/8444/0/0";
+/// Derivation path of the pool key
+pub const POOL_DERIVATION_PATH: &str = "m/12381/8444/1/0";
+
+/// Formats supported by [`Wallet::export_key_material`] and [`Wallet::import_key_material`]
+#[derive(Clone, PartialEq, Eq)]
+pub enum KeyExportFormat {
+    /// The mnemonic as space separated words, as accepted by `chia keys add`
+    Mnemonic,
+    /// The master secret key as hex, as printed by `chia keys show --show-mnemonic-seed`
+    MasterSecretKeyHex,
+    /// Fingerprint and derivation summary as JSON. Contains no secret material.
+    Summary,
+    /// The mnemonic encrypted under a password (Argon2id + AES-256-GCM), as JSON
+    EncryptedMnemonic { password: Zeroizing<String> },
+}
+
+impl KeyExportFormat {
+    /// Shorthand for [`KeyExportFormat::EncryptedMnemonic`]
+    pub fn encrypted_mnemonic(password: impl Into<String>) -> Self {
+        KeyExportFormat::EncryptedMnemonic {
+            password: Zeroizing::new(password.into()),
+        }
+    }
+}
+
+impl fmt::Debug for KeyExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyExportFormat::Mnemonic => f.write_str("Mnemonic"),
+            KeyExportFormat::MasterSecretKeyHex => f.write_str("MasterSecretKeyHex"),
+            KeyExportFormat::Summary => f.write_str("Summary"),
+            KeyExportFormat::EncryptedMnemonic { .. } => f
+                .debug_struct("EncryptedMnemonic")
+                .field("password", &"<redacted>")
+                .finish(),
+        }
+    }
+}
+
+/// Public description of a wallet key, mirroring the fields shown by `chia keys show`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeySummary {
+    pub wallet_name: String,
+    pub fingerprint: u32,
+    pub master_public_key: String,
+    pub derivation_path: String,
+    pub synthetic_public_key: String,
+    pub first_puzzle_hash: String,
+    pub first_address: String,
+    pub farmer_public_key: String,
+    pub pool_public_key: String,
+}
+
+/// Password-encrypted mnemonic as produced by [`KeyExportFormat::EncryptedMnemonic`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedKeyExport {
+    kdf: String,
+    fingerprint: u32,
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+impl Wallet {
+    /// Export the key material of a stored wallet in the requested format
+    pub async fn export_key_material(
+        wallet_name: &str,
+        format: KeyExportFormat,
+    ) -> Result<String, WalletError> {
+        Self::export_key_material_with_config(wallet_name, format, &WalletConfig::default()).await
+    }
+
+    /// Export the key material of a stored wallet using an explicit configuration
+    pub async fn export_key_material_with_config(
+        wallet_name: &str,
+        format: KeyExportFormat,
+        config: &WalletConfig,
+    ) -> Result<String, WalletError> {
+        let wallet = Wallet::load_with_config(Some(wallet_name.to_string()), false, config).await?;
+
+        match format {
+            KeyExportFormat::Mnemonic => Ok(wallet.mnemonic_phrase()?.to_string()),
+            KeyExportFormat::MasterSecretKeyHex => {
+                let master_sk = wallet.get_master_secret_key().await?;
+                Ok(hex::encode(master_sk.to_bytes()))
+            }
+            KeyExportFormat::Summary => {
+                let summary = wallet.get_key_summary().await?;
+                serde_json::to_string_pretty(&summary)
+                    .map_err(|e| WalletError::SerializationError(e.to_string()))
+            }
+            KeyExportFormat::EncryptedMnemonic { password } => {
+                let fingerprint = wallet.get_fingerprint().await?;
+                let salt = config.random_bytes::<16>()?;
+                let key_bytes = derive_password_key(&password, &salt)?;
+                let (ciphertext, nonce) =
+                    aes_encrypt(&key_bytes, wallet.mnemonic_phrase()?.as_bytes(), config)?;
+
+                let export = EncryptedKeyExport {
+                    kdf: KDF_ARGON2ID.to_string(),
+                    fingerprint,
+                    salt: general_purpose::STANDARD.encode(salt),
+                    nonce: general_purpose::STANDARD.encode(nonce),
+                    data: general_purpose::STANDARD.encode(ciphertext),
+                };
+                serde_json::to_string_pretty(&export)
+                    .map_err(|e| WalletError::SerializationError(e.to_string()))
+            }
+        }
+    }
+
+    /// Import a wallet from material produced by [`Wallet::export_key_material`]
+    ///
+    /// Only formats that carry the mnemonic can be imported; a bare master secret key
+    /// or a summary cannot be turned back into a seed phrase.
+    pub async fn import_key_material(
+        wallet_name: &str,
+        format: KeyExportFormat,
+        material: &str,
+    ) -> Result<String, WalletError> {
+        Self::import_key_material_with_config(
+            wallet_name,
+            format,
+            material,
+            &WalletConfig::default(),
+        )
+        .await
+    }
+
+    /// Import a wallet from exported key material using an explicit configuration
+    pub async fn import_key_material_with_config(
+        wallet_name: &str,
+        format: KeyExportFormat,
+        material: &str,
+        config: &WalletConfig,
+    ) -> Result<String, WalletError> {
+        match format {
+            KeyExportFormat::Mnemonic => {
+                Self::import_wallet_with_config(wallet_name, Some(material.trim()), config).await
+            }
+            KeyExportFormat::EncryptedMnemonic { password } => {
+                let export: EncryptedKeyExport = serde_json::from_str(material)
+                    .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+
+                if export.kdf != KDF_ARGON2ID {
+                    return Err(WalletError::CryptoError(format!(
+                        "Unsupported key derivation function: {}",
+                        export.kdf
+                    )));
+                }
+
+                let decode = |value: &str, what: &str| {
+                    general_purpose::STANDARD.decode(value).map_err(|e| {
+                        WalletError::CryptoError(format!("Failed to decode {}: {}", what, e))
+                    })
+                };
+                let salt = decode(&export.salt, "salt")?;
+                let nonce = decode(&export.nonce, "nonce")?;
+                let ciphertext = decode(&export.data, "ciphertext")?;
+
+                let key_bytes = derive_password_key(&password, &salt)?;
+                let plaintext = aes_decrypt(&key_bytes, &nonce, &ciphertext)?;
+                let mnemonic = zeroizing_utf8(plaintext)?;
+
+                let wallet = Wallet::new(Some(mnemonic.to_string()), wallet_name.to_string());
+                if wallet.get_fingerprint().await? != export.fingerprint {
+                    return Err(WalletError::FingerprintError);
+                }
+
+                Self::import_wallet_with_config(wallet_name, Some(&mnemonic), config).await
+            }
+            KeyExportFormat::MasterSecretKeyHex | KeyExportFormat::Summary => {
+                Err(WalletError::MnemonicRequired)
+            }
+        }
+    }
+
+    /// Get the fingerprint of the master public key, as used by the Chia reference wallet
+    pub async fn get_fingerprint(&self) -> Result<u32, WalletError> {
+        Ok(self.get_master_public_key().await?.get_fingerprint())
+    }
+
+    /// Get the public fingerprint and derivation summary of this wallet
+    pub async fn get_key_summary(&self) -> Result<KeySummary, WalletError> {
+        let master_sk = self.get_master_secret_key().await?;
+        let master_pk = master_sk.public_key();
+        let synthetic_pk = synthetic_public_key(&master_pk);
+        let first_puzzle_hash = first_puzzle_hash(&master_pk);
+        let first_address = self
+            .chain_profile()
+            .await?
+            .encode_address(first_puzzle_hash)?;
+
+        Ok(KeySummary {
+            wallet_name: self.get_wallet_name().to_string(),
+            fingerprint: master_pk.get_fingerprint(),
+            master_public_key: hex::encode(master_pk.to_bytes()),
+            derivation_path: WALLET_DERIVATION_PATH.to_string(),
+            synthetic_public_key: hex::encode(synthetic_pk.to_bytes()),
+            first_puzzle_hash: hex::encode(first_puzzle_hash.as_ref()),
+            first_address,
+            farmer_public_key: hex::encode(farmer_secret_key(&master_sk).public_key().to_bytes()),
+            pool_public_key: hex::encode(pool_secret_key(&master_sk).public_key().to_bytes()),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+    fn test_config(temp_dir: &TempDir) -> WalletConfig {
+        WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()))
+    }
+
+    #[tokio::test]
+    async fn test_export_plain_formats() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        Wallet::import_wallet_with_config("export_test", Some(TEST_MNEMONIC), &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("export_test".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        let mnemonic = Wallet::export_key_material_with_config(
+            "export_test",
+            KeyExportFormat::Mnemonic,
+            &config,
+        )
+        .await
+        .unwrap();
+        assert_eq!(mnemonic, TEST_MNEMONIC);
+
+        let sk_hex = Wallet::export_key_material_with_config(
+            "export_test",
+            KeyExportFormat::MasterSecretKeyHex,
+            &config,
+        )
+        .await
+        .unwrap();
+        let master_sk = wallet.get_master_secret_key().await.unwrap();
+        assert_eq!(sk_hex, hex::encode(master_sk.to_bytes()));
+
+        let summary_json = Wallet::export_key_material_with_config(
+            "export_test",
+            KeyExportFormat::Summary,
+            &config,
+        )
+        .await
+        .unwrap();
+        let summary: KeySummary = serde_json::from_str(&summary_json).unwrap();
+        assert_eq!(summary.fingerprint, wallet.get_fingerprint().await.unwrap());
+        assert_eq!(
+            summary.first_address,
+            wallet.get_owner_public_key().await.unwrap()
+        );
+        let farmer_pk = wallet.get_farmer_public_key().await.unwrap();
+        let pool_pk = wallet.get_pool_public_key().await.unwrap();
+        assert_eq!(summary.farmer_public_key, hex::encode(farmer_pk.to_bytes()));
+        assert_eq!(summary.pool_public_key, hex::encode(pool_pk.to_bytes()));
+        assert_ne!(farmer_pk, pool_pk);
+        assert_ne!(farmer_pk, wallet.get_local_public_key().await.unwrap());
+        assert!(!summary_json.contains("abandon"));
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_export_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        Wallet::import_wallet_with_config("encrypted_source", Some(TEST_MNEMONIC), &config)
+            .await
+            .unwrap();
+
+        let format = KeyExportFormat::encrypted_mnemonic("correct horse battery staple");
+        assert!(!format!("{:?}", format).contains("horse"));
+        let exported =
+            Wallet::export_key_material_with_config("encrypted_source", format.clone(), &config)
+                .await
+                .unwrap();
+        assert!(!exported.contains("abandon"));
+
+        // Wrong password must not import anything
+        let result = Wallet::import_key_material_with_config(
+            "encrypted_target",
+            KeyExportFormat::encrypted_mnemonic("wrong"),
+            &exported,
+            &config,
+        )
+        .await;
+        assert!(matches!(result, Err(WalletError::CryptoError(_))));
+
+        // The same mnemonic may not be stored under two names
+        assert!(
+            Wallet::delete_wallet_with_config("encrypted_source", &config)
+                .await
+                .unwrap()
+        );
+        let imported =
+            Wallet::import_key_material_with_config("encrypted_target", format, &exported, &config)
+                .await
+                .unwrap();
+        assert_eq!(imported, TEST_MNEMONIC);
+
+        // Summaries carry no seed and cannot be imported
+        let result = Wallet::import_key_material_with_config(
+            "summary_target",
+            KeyExportFormat::Summary,
+            "{}",
+            &config,
+        )
+        .await;
+        assert!(matches!(result, Err(WalletError::MnemonicRequired)));
+    }
+}
@@ -0,0 +1,407 @@
+use crate::error::WalletError;
+#[cfg(feature = "keyring-file")]
+use crate::file_lock::{with_file_lock_policy, LockPolicy, LOCK_RETRY_INTERVAL, LOCK_TIMEOUT};
+#[cfg(feature = "keyring-file")]
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Debug;
+#[cfg(feature = "keyring-file")]
+use std::fs;
+use std::future::Future;
+#[cfg(feature = "keyring-file")]
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "keyring-file")]
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Mutex;
+#[cfg(feature = "keyring-file")]
+use std::time::Duration;
+
+/// Boxed future returned by [`KeyringBackend`] and [`KvStore`] methods
+pub type KeyringFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, WalletError>> + Send + 'a>>;
+
+/// The serialized keyring and the version token it was read at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionedBlob {
+    pub data: Vec<u8>,
+    pub version: String,
+}
+
+/// Storage for the keyring blob; wallet entries inside it are individually encrypted
+///
+/// Writes use optimistic concurrency: `store` only succeeds if the stored version
+/// still equals `expected_version` (`None` meaning "must not exist yet") and fails
+/// with [`WalletError::KeyringConflict`] otherwise.
+pub trait KeyringBackend: Send + Sync + Debug {
+    fn load(&self) -> KeyringFuture<'_, Option<VersionedBlob>>;
+    fn store<'a>(
+        &'a self,
+        data: Vec<u8>,
+        expected_version: Option<&'a str>,
+    ) -> KeyringFuture<'a, String>;
+
+    /// Keep a copy of the keyring blob in keyring format `format_version` before it is
+    /// upgraded; the default keeps none
+    fn backup<'a>(&'a self, data: &'a [u8], format_version: u32) -> KeyringFuture<'a, ()> {
+        let _ = (data, format_version);
+        Box::pin(async { Ok(()) })
+    }
+
+    /// The copy kept by [`Self::backup`] for keyring format `format_version`, if any
+    fn load_backup(&self, format_version: u32) -> KeyringFuture<'_, Option<Vec<u8>>> {
+        let _ = format_version;
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// How writers of a keyring file wait for each other across processes
+#[cfg(feature = "keyring-file")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyringLockOptions {
+    /// How long to wait for another writer before failing with [`WalletError::KeyringBusy`]
+    pub timeout: Duration,
+    /// Pause between attempts to take the lock
+    pub retry_interval: Duration,
+}
+
+#[cfg(feature = "keyring-file")]
+impl Default for KeyringLockOptions {
+    fn default() -> Self {
+        Self {
+            timeout: LOCK_TIMEOUT,
+            retry_interval: LOCK_RETRY_INTERVAL,
+        }
+    }
+}
+
+#[cfg(feature = "keyring-file")]
+impl KeyringLockOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    fn policy(&self) -> LockPolicy {
+        LockPolicy {
+            timeout: self.timeout,
+            retry_interval: self.retry_interval,
+            busy: WalletError::KeyringBusy,
+        }
+    }
+}
+
+/// Keyring stored in a local file; the version token is a digest of the file contents
+///
+/// Writes hold an OS advisory lock on `keyring.json.lock` and replace the keyring through
+/// a rename, so concurrent processes neither overwrite each other's entries nor read a
+/// partially written file. The OS drops the lock of a crashed writer.
+#[cfg(feature = "keyring-file")]
+#[derive(Debug, Clone)]
+pub struct FileKeyringBackend {
+    path: PathBuf,
+    lock_options: KeyringLockOptions,
+}
+
+#[cfg(feature = "keyring-file")]
+impl FileKeyringBackend {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock_options: KeyringLockOptions::default(),
+        }
+    }
+
+    /// Wait for other writers as `options` says
+    pub fn with_lock_options(mut self, options: KeyringLockOptions) -> Self {
+        self.lock_options = options;
+        self
+    }
+
+    fn content_version(data: &[u8]) -> String {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    fn read(&self) -> Result<Option<VersionedBlob>, WalletError> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(&self.path).map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+        Ok(Some(VersionedBlob {
+            version: Self::content_version(&data),
+            data,
+        }))
+    }
+
+    fn backup_path(&self, format_version: u32) -> PathBuf {
+        let mut backup_path = self.path.clone().into_os_string();
+        backup_path.push(format!(".v{}.bak", format_version));
+        PathBuf::from(backup_path)
+    }
+}
+
+#[cfg(feature = "keyring-file")]
+impl KeyringBackend for FileKeyringBackend {
+    fn load(&self) -> KeyringFuture<'_, Option<VersionedBlob>> {
+        Box::pin(async move { self.read() })
+    }
+
+    fn store<'a>(
+        &'a self,
+        data: Vec<u8>,
+        expected_version: Option<&'a str>,
+    ) -> KeyringFuture<'a, String> {
+        Box::pin(async move {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+            }
+
+            let mut lock_path = self.path.clone().into_os_string();
+            lock_path.push(".lock");
+
+            let policy = self.lock_options.policy();
+            with_file_lock_policy(&PathBuf::from(lock_path), &policy, || {
+                let current = self.read()?.map(|blob| blob.version);
+                if current.as_deref() != expected_version {
+                    return Err(WalletError::KeyringConflict(
+                        "keyring file changed since it was read".to_string(),
+                    ));
+                }
+
+                let mut temp_path = self.path.clone().into_os_string();
+                temp_path.push(".tmp");
+                fs::write(&temp_path, &data)
+                    .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+                fs::rename(&temp_path, &self.path)
+                    .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+                Ok(Self::content_version(&data))
+            })
+            .await
+        })
+    }
+
+    /// Writes `keyring.json.v<format_version>.bak` next to the keyring, keeping an existing one
+    fn backup<'a>(&'a self, data: &'a [u8], format_version: u32) -> KeyringFuture<'a, ()> {
+        Box::pin(async move {
+            let backup_path = self.backup_path(format_version);
+            if !backup_path.exists() {
+                fs::write(&backup_path, data)
+                    .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+            }
+            Ok(())
+        })
+    }
+
+    fn load_backup(&self, format_version: u32) -> KeyringFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async move {
+            match fs::read(self.backup_path(format_version)) {
+                Ok(data) => Ok(Some(data)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(WalletError::FileSystemError(e.to_string())),
+            }
+        })
+    }
+}
+
+/// Minimal versioned key-value store, e.g. S3 with ETags, etcd revisions or Redis `WATCH`
+pub trait KvStore: Send + Sync + Debug {
+    fn get<'a>(&'a self, key: &'a str) -> KeyringFuture<'a, Option<VersionedBlob>>;
+    /// Write `data` if the current version equals `expected_version`, returning the new version
+    fn compare_and_swap<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        expected_version: Option<&'a str>,
+    ) -> KeyringFuture<'a, String>;
+}
+
+/// Keyring shared by a fleet of nodes through a remote [`KvStore`]
+#[derive(Debug)]
+pub struct RemoteKeyringBackend<S: KvStore> {
+    store: S,
+    key: String,
+}
+
+impl<S: KvStore> RemoteKeyringBackend<S> {
+    /// Store the keyring blob under `key` in `store`
+    pub fn new(store: S, key: impl Into<String>) -> Self {
+        Self {
+            store,
+            key: key.into(),
+        }
+    }
+}
+
+impl<S: KvStore> KeyringBackend for RemoteKeyringBackend<S> {
+    fn load(&self) -> KeyringFuture<'_, Option<VersionedBlob>> {
+        self.store.get(&self.key)
+    }
+
+    fn store<'a>(
+        &'a self,
+        data: Vec<u8>,
+        expected_version: Option<&'a str>,
+    ) -> KeyringFuture<'a, String> {
+        self.store
+            .compare_and_swap(&self.key, data, expected_version)
+    }
+
+    /// Stores the blob under `<key>.v<format_version>.bak`, keeping an existing one
+    fn backup<'a>(&'a self, data: &'a [u8], format_version: u32) -> KeyringFuture<'a, ()> {
+        Box::pin(async move {
+            let backup_key = format!("{}.v{}.bak", self.key, format_version);
+            match self
+                .store
+                .compare_and_swap(&backup_key, data.to_vec(), None)
+                .await
+            {
+                Ok(_) | Err(WalletError::KeyringConflict(_)) => Ok(()),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn load_backup(&self, format_version: u32) -> KeyringFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async move {
+            let backup_key = format!("{}.v{}.bak", self.key, format_version);
+            Ok(self.store.get(&backup_key).await?.map(|blob| blob.data))
+        })
+    }
+}
+
+/// In-process [`KvStore`] with counter versions, for tests and single-host setups
+#[derive(Debug, Default)]
+pub struct InMemoryKvStore {
+    entries: Mutex<HashMap<String, (Vec<u8>, u64)>>,
+}
+
+impl InMemoryKvStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvStore for InMemoryKvStore {
+    fn get<'a>(&'a self, key: &'a str) -> KeyringFuture<'a, Option<VersionedBlob>> {
+        Box::pin(async move {
+            let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            Ok(entries.get(key).map(|(data, version)| VersionedBlob {
+                data: data.clone(),
+                version: version.to_string(),
+            }))
+        })
+    }
+
+    fn compare_and_swap<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        expected_version: Option<&'a str>,
+    ) -> KeyringFuture<'a, String> {
+        Box::pin(async move {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            let current = entries.get(key).map(|(_, version)| *version);
+            if current.map(|version| version.to_string()).as_deref() != expected_version {
+                return Err(WalletError::KeyringConflict(format!(
+                    "key {} changed since it was read",
+                    key
+                )));
+            }
+
+            let next = current.unwrap_or(0) + 1;
+            entries.insert(key.to_string(), (data, next));
+            Ok(next.to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use crate::wallet::Wallet;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_stale_version_is_rejected() {
+        let store = InMemoryKvStore::new();
+        let v1 = store
+            .compare_and_swap("k", b"a".to_vec(), None)
+            .await
+            .unwrap();
+        store
+            .compare_and_swap("k", b"b".to_vec(), Some(&v1))
+            .await
+            .unwrap();
+
+        let stale = store.compare_and_swap("k", b"c".to_vec(), Some(&v1)).await;
+        assert!(matches!(stale, Err(WalletError::KeyringConflict(_))));
+        assert_eq!(store.get("k").await.unwrap().unwrap().data, b"b".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_nodes_share_remote_keyring() {
+        let shared: Arc<dyn KeyringBackend> = Arc::new(RemoteKeyringBackend::new(
+            InMemoryKvStore::new(),
+            "fleet/keyring",
+        ));
+        let node_a_home = TempDir::new().unwrap();
+        let node_b_home = TempDir::new().unwrap();
+        let node_a = WalletConfig::new()
+            .with_environment(SandboxEnvironment::new(node_a_home.path()))
+            .with_shared_keyring_backend(shared.clone());
+        let node_b = WalletConfig::new()
+            .with_environment(SandboxEnvironment::new(node_b_home.path()))
+            .with_shared_keyring_backend(shared);
+
+        let (a, b) = tokio::join!(
+            Wallet::create_new_wallet_with_config("alpha", &node_a),
+            Wallet::create_new_wallet_with_config("beta", &node_b),
+        );
+        let alpha_mnemonic = a.unwrap().into_string();
+        b.unwrap();
+
+        let mut wallets = Wallet::list_wallets_with_config(&node_b).await.unwrap();
+        wallets.sort();
+        assert_eq!(wallets, vec!["alpha".to_string(), "beta".to_string()]);
+
+        let loaded = Wallet::load_with_config(Some("alpha".to_string()), false, &node_b)
+            .await
+            .unwrap();
+        assert_eq!(loaded.mnemonic_phrase().unwrap(), alpha_mnemonic);
+        assert!(!node_a.keyring_path().unwrap().exists());
+    }
+
+    #[cfg(feature = "keyring-file")]
+    #[tokio::test]
+    async fn test_held_keyring_lock_reports_busy() {
+        let temp_dir = TempDir::new().unwrap();
+        let keyring_path = temp_dir.path().join("keyring.json");
+        let backend = FileKeyringBackend::new(&keyring_path)
+            .with_lock_options(KeyringLockOptions::new().with_timeout(Duration::from_millis(50)));
+
+        // Another process is mid-update
+        let holder = fs::File::create(temp_dir.path().join("keyring.json.lock")).unwrap();
+        holder.lock().unwrap();
+        let result = backend.store(b"{}".to_vec(), None).await;
+        assert!(matches!(result, Err(WalletError::KeyringBusy(_))));
+        assert!(!keyring_path.exists());
+
+        drop(holder);
+        backend.store(b"{}".to_vec(), None).await.unwrap();
+        assert_eq!(backend.load().await.unwrap().unwrap().data, b"{}".to_vec());
+    }
+}
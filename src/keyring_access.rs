@@ -0,0 +1,117 @@
+//! Least-privilege handles on the keyring.
+//!
+//! A service that only enumerates wallets can be handed a [`KeyringReader`], which has no
+//! way to create, overwrite or delete entries and never exposes a mnemonic. Mutations
+//! need a [`KeyringWriter`], which can always be narrowed to a reader but not the other
+//! way round.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::key_export::KeySummary;
+use crate::memlock::MnemonicHandle;
+use crate::wallet::Wallet;
+
+/// Read-only access to the wallets of a keyring
+#[derive(Debug, Clone)]
+pub struct KeyringReader {
+    config: WalletConfig,
+}
+
+impl KeyringReader {
+    /// Read the keyring of `config`
+    pub fn new(config: &WalletConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// Names of the stored wallets
+    pub async fn list(&self) -> Result<Vec<String>, WalletError> {
+        Wallet::list_wallets_with_config(&self.config).await
+    }
+
+    /// Fingerprint of the master public key of a stored wallet
+    pub async fn fingerprint(&self, wallet_name: &str) -> Result<u32, WalletError> {
+        self.load(wallet_name).await?.get_fingerprint().await
+    }
+
+    /// Public keys and first address of a stored wallet
+    pub async fn public_keys(&self, wallet_name: &str) -> Result<KeySummary, WalletError> {
+        self.load(wallet_name).await?.get_key_summary().await
+    }
+
+    async fn load(&self, wallet_name: &str) -> Result<Wallet, WalletError> {
+        Wallet::load_with_config(Some(wallet_name.to_string()), false, &self.config).await
+    }
+}
+
+/// Access to create, import and delete the wallets of a keyring
+#[derive(Debug, Clone)]
+pub struct KeyringWriter {
+    config: WalletConfig,
+}
+
+impl KeyringWriter {
+    /// Modify the keyring of `config`
+    pub fn new(config: &WalletConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// A reader of the same keyring
+    pub fn reader(&self) -> KeyringReader {
+        KeyringReader::new(&self.config)
+    }
+
+    /// Store a new wallet with a generated mnemonic, returning the mnemonic for backup
+    pub async fn create(&self, wallet_name: &str) -> Result<MnemonicHandle, WalletError> {
+        Wallet::create_new_wallet_with_config(wallet_name, &self.config).await
+    }
+
+    /// Store a wallet from an existing mnemonic
+    pub async fn import(&self, wallet_name: &str, mnemonic: &str) -> Result<(), WalletError> {
+        Wallet::import_wallet_with_config(wallet_name, Some(mnemonic), &self.config).await?;
+        Ok(())
+    }
+
+    /// Delete a wallet; returns whether it existed
+    pub async fn delete(&self, wallet_name: &str) -> Result<bool, WalletError> {
+        Wallet::delete_wallet_with_config(wallet_name, &self.config).await
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_reader_sees_writer_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let writer = KeyringWriter::new(&config);
+        let reader = writer.reader();
+
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        writer.import("imported", mnemonic).await.unwrap();
+        writer.create("created").await.unwrap();
+
+        let mut names = reader.list().await.unwrap();
+        names.sort();
+        assert_eq!(names, ["created", "imported"]);
+
+        let summary = reader.public_keys("imported").await.unwrap();
+        assert_eq!(
+            reader.fingerprint("imported").await.unwrap(),
+            summary.fingerprint
+        );
+
+        assert!(writer.delete("imported").await.unwrap());
+        assert!(matches!(
+            reader.fingerprint("imported").await,
+            Err(WalletError::WalletNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+//! Standard Chia key derivation, addresses and message signing on top of `chia` alone,
+//! so none of it depends on the peer protocol stack.
+
+use crate::error::WalletError;
+use bech32::{FromBase32, ToBase32, Variant};
+use chia::bls::{master_to_wallet_unhardened, sign, verify, PublicKey, SecretKey, Signature};
+use chia::clvm_utils::{tree_hash_atom, tree_hash_pair};
+use chia::protocol::Bytes32;
+use chia::puzzles::standard::StandardArgs;
+use chia::puzzles::DeriveSynthetic;
+
+/// Prefix the Chia reference wallet hashes together with signed messages
+const SIGNED_MESSAGE_PREFIX: &[u8] = b"Chia Signed Message";
+
+/// Synthetic public key of the first unhardened wallet key
+pub(crate) fn synthetic_public_key(master_public_key: &PublicKey) -> PublicKey {
+    synthetic_public_key_at(master_public_key, 0)
+}
+
+/// Synthetic secret key of the first unhardened wallet key
+pub(crate) fn synthetic_secret_key(master_secret_key: &SecretKey) -> SecretKey {
+    synthetic_secret_key_at(master_secret_key, 0)
+}
+
+/// Synthetic public key of the unhardened wallet key at `index`
+pub(crate) fn synthetic_public_key_at(master_public_key: &PublicKey, index: u32) -> PublicKey {
+    master_to_wallet_unhardened(master_public_key, index).derive_synthetic()
+}
+
+/// Synthetic secret key of the unhardened wallet key at `index`
+pub(crate) fn synthetic_secret_key_at(master_secret_key: &SecretKey, index: u32) -> SecretKey {
+    master_to_wallet_unhardened(master_secret_key, index).derive_synthetic()
+}
+
+/// Farmer key at `m/12381/8444/0/0`, as derived by `chia keys`
+pub(crate) fn farmer_secret_key(master_secret_key: &SecretKey) -> SecretKey {
+    hardened_key(master_secret_key, 0, 0)
+}
+
+/// Pool key at `m/12381/8444/1/0`, as derived by `chia keys`
+pub(crate) fn pool_secret_key(master_secret_key: &SecretKey) -> SecretKey {
+    hardened_key(master_secret_key, 1, 0)
+}
+
+/// Local (harvester/full node) key at `m/12381/8444/3/0`, as derived by `chia keys`
+pub(crate) fn local_secret_key(master_secret_key: &SecretKey) -> SecretKey {
+    hardened_key(master_secret_key, 3, 0)
+}
+
+/// Hardened key at `m/12381/8444/<purpose>/<index>`
+pub(crate) fn hardened_key(master_secret_key: &SecretKey, purpose: u32, index: u32) -> SecretKey {
+    master_secret_key
+        .derive_hardened(12381)
+        .derive_hardened(8444)
+        .derive_hardened(purpose)
+        .derive_hardened(index)
+}
+
+/// Standard transaction puzzle hash for a synthetic public key
+pub(crate) fn standard_puzzle_hash(synthetic_key: &PublicKey) -> Bytes32 {
+    StandardArgs::curry_tree_hash(*synthetic_key).into()
+}
+
+/// Puzzle hash of the first wallet address
+pub(crate) fn first_puzzle_hash(master_public_key: &PublicKey) -> Bytes32 {
+    standard_puzzle_hash(&synthetic_public_key(master_public_key))
+}
+
+/// Encode a puzzle hash as a bech32m address
+pub(crate) fn encode_address(puzzle_hash: Bytes32, prefix: &str) -> Result<String, WalletError> {
+    bech32::encode(prefix, puzzle_hash.to_vec().to_base32(), Variant::Bech32m)
+        .map_err(|e| WalletError::CryptoError(format!("Failed to encode address: {}", e)))
+}
+
+/// Decode a bech32m address into its puzzle hash
+pub(crate) fn decode_address(address: &str) -> Result<Bytes32, WalletError> {
+    let decode_error =
+        |e: String| WalletError::CryptoError(format!("Failed to decode address: {}", e));
+
+    let (_, data, variant) = bech32::decode(address).map_err(|e| decode_error(e.to_string()))?;
+    if variant != Variant::Bech32m {
+        return Err(decode_error("address is not bech32m".to_string()));
+    }
+
+    let bytes = Vec::<u8>::from_base32(&data).map_err(|e| decode_error(e.to_string()))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| decode_error("puzzle hash must be 32 bytes".to_string()))?;
+    Ok(Bytes32::new(bytes))
+}
+
+/// Hash actually signed for `message`: the tree hash of `("Chia Signed Message" . message)`
+pub(crate) fn signed_message_hash(message: &[u8]) -> Bytes32 {
+    tree_hash_pair(
+        tree_hash_atom(SIGNED_MESSAGE_PREFIX),
+        tree_hash_atom(message),
+    )
+    .into()
+}
+
+/// Sign a message the way the Chia wallet's `sign_message` does
+pub(crate) fn sign_message(message: &[u8], secret_key: &SecretKey) -> Signature {
+    sign(secret_key, signed_message_hash(message))
+}
+
+/// Verify a signature produced by [`sign_message`]
+pub(crate) fn verify_message(
+    message: &[u8],
+    public_key: &PublicKey,
+    signature: &Signature,
+) -> bool {
+    verify(signature, public_key, signed_message_hash(message))
+}
+
+#[cfg(all(test, feature = "peer"))]
+mod tests {
+    use super::*;
+    use chia::protocol::Bytes;
+
+    #[test]
+    fn test_matches_datalayer_driver() {
+        let master_sk = SecretKey::from_seed(&[7u8; 32]);
+        let master_pk = master_sk.public_key();
+
+        assert_eq!(
+            first_puzzle_hash(&master_pk),
+            datalayer_driver::master_public_key_to_first_puzzle_hash(&master_pk)
+        );
+        assert_eq!(
+            synthetic_secret_key(&master_sk).to_bytes(),
+            datalayer_driver::master_secret_key_to_wallet_synthetic_secret_key(&master_sk)
+                .to_bytes()
+        );
+
+        let puzzle_hash = first_puzzle_hash(&master_pk);
+        let address = encode_address(puzzle_hash, "xch").unwrap();
+        assert_eq!(
+            address,
+            datalayer_driver::puzzle_hash_to_address(puzzle_hash, "xch").unwrap()
+        );
+        assert_eq!(decode_address(&address).unwrap(), puzzle_hash);
+
+        let message = b"ownership";
+        let signature = sign_message(message, &master_sk);
+        assert_eq!(
+            signature,
+            datalayer_driver::sign_message(&Bytes::from(message.to_vec()), &master_sk).unwrap()
+        );
+        assert!(verify_message(message, &master_pk, &signature));
+    }
+}
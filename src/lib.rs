@@ -35,30 +35,329 @@
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # #[cfg(feature = "peer")]
+//! # {
 //!     // Connect to a random mainnet peer
 //!     let peer = Wallet::connect_mainnet_peer().await?;
 //!     
 //!     // Use peer for blockchain operations
 //!     let wallet = Wallet::load(Some("my_wallet".to_string()), true).await?;
 //!     let coins = wallet.select_unspent_coins(&peer, 1000000, 1000, vec![]).await?;
+//! # }
 //!     
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ## Cargo features
+//!
+//! Everything but `offers`, `remote`, `mlock` and `plain-mnemonic` is enabled by default;
+//! `offers` needs `cmake` to build zlib-ng for offer compression. Consumers that only
+//! derive keys and sign, such as WASM identity tooling, can opt out of the peer protocol
+//! stack:
+//!
+//! ```toml
+//! dig-wallet = { version = "2", default-features = false, features = ["crypto-core"] }
+//! ```
+//!
+//! - `crypto-core`: mnemonics, key derivation, addresses, signing and keyring encryption
+//! - `keyring-file`: the local `~/.dig/keyring.json` keyring backend
+//! - `cache`: file caches, transaction history and derivation index allocation
+//! - `peer`: peer connections, coin selection, transfers and everything else that talks to a node
+//! - `cat`: DIG CAT discovery and spending
+//! - `offers`: Chia offer files trading XCH and CATs (not on by default)
+//! - `nft`: NFT1 minting, discovery and transfer
+//! - `remote`: the wallet daemon and `RemoteWallet` client
+
+#[cfg(not(feature = "crypto-core"))]
+compile_error!("dig-wallet requires the `crypto-core` feature");
 
+pub mod accounts;
+pub mod addresses;
+pub mod aggregation;
+#[cfg(feature = "cat")]
+pub mod airdrop;
+#[cfg(feature = "peer")]
+pub mod alerts;
+pub mod asset;
+pub mod backup;
+#[cfg(feature = "peer")]
+pub mod builder;
+#[cfg(feature = "peer")]
+pub mod cancel;
+#[cfg(feature = "cat")]
+pub mod cat_signing;
+#[cfg(feature = "cat")]
+pub mod cats;
+#[cfg(feature = "peer")]
+mod chain;
+#[cfg(feature = "peer")]
+pub mod chain_history;
+#[cfg(feature = "peer")]
+pub mod change;
+#[cfg(feature = "peer")]
+pub mod checkpoint;
+#[cfg(feature = "peer")]
+pub mod clawback;
+#[cfg(feature = "peer")]
+pub mod coin_proof;
+#[cfg(feature = "peer")]
+pub mod coin_records;
+#[cfg(feature = "peer")]
+pub mod coins;
+#[cfg(feature = "peer")]
+pub mod cold_signing;
+#[cfg(feature = "cat")]
+pub mod collateral;
+pub mod compat;
+pub mod config;
+#[cfg(feature = "peer")]
+pub mod confirmation;
+#[cfg(feature = "peer")]
+pub mod consolidation;
+#[cfg(feature = "peer")]
+pub mod context;
+mod crypto;
+pub mod datastore;
+#[cfg(feature = "cache")]
+pub mod derivation;
+#[cfg(feature = "cat")]
+pub mod dig;
+#[cfg(feature = "peer")]
+pub mod discovery;
+#[cfg(feature = "peer")]
+mod encoding;
+pub mod environment;
 pub mod error;
+#[cfg(feature = "peer")]
+pub mod fee_floor;
+#[cfg(feature = "peer")]
+pub mod fees;
+#[cfg(feature = "cache")]
 pub mod file_cache;
+#[cfg(any(feature = "cache", feature = "keyring-file"))]
+mod file_lock;
+#[cfg(feature = "peer")]
+pub mod freshness;
+#[cfg(feature = "peer")]
+pub mod health;
+#[cfg(feature = "cache")]
+pub mod history;
+pub mod key_export;
+pub mod keyring;
+pub mod keyring_access;
+mod keys;
+mod memlock;
+pub mod metadata;
+pub mod migrations;
+pub mod mnemonic;
+#[cfg(feature = "nft")]
+pub mod nft;
+#[cfg(feature = "offers")]
+pub mod offers;
+pub mod ownership;
+#[cfg(feature = "peer")]
+pub mod peers;
+#[cfg(feature = "peer")]
+pub mod pending;
+#[cfg(feature = "peer")]
+pub mod pinning;
+#[cfg(feature = "peer")]
+pub mod provenance;
+#[cfg(feature = "peer")]
+pub mod recording;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "cache")]
+pub mod reservations;
+pub mod reveal;
+pub mod rng;
+pub mod runtime;
+pub mod session;
+pub mod shamir;
+pub mod signer;
+pub mod signing;
+#[cfg(feature = "peer")]
+pub mod spend_signing;
+#[cfg(feature = "peer")]
+pub mod sweep;
+pub mod templates;
+pub mod threshold;
+#[cfg(feature = "peer")]
+pub mod tombstones;
+#[cfg(feature = "peer")]
+pub mod transfer;
+#[cfg(feature = "peer")]
+pub mod tx_queue;
 pub mod wallet;
+#[cfg(feature = "peer")]
+pub mod warmup;
+pub mod watch_only;
 
 // Core exports
+pub use accounts::ACCOUNT_KEY_PURPOSE;
+pub use addresses::{AddressScan, ReceiveAddress, DEFAULT_ADDRESS_GAP_LIMIT};
+pub use aggregation::{
+    aggregate_public_keys, aggregate_signatures, sign_for_aggregate_key, verify_aggregate,
+};
+#[cfg(feature = "cat")]
+pub use airdrop::{
+    Airdrop, AirdropBatch, AirdropRecipient, BatchStatus, FeePolicy, DEFAULT_AIRDROP_BATCH_COST,
+};
+#[cfg(feature = "peer")]
+pub use alerts::{BalanceAlert, BalanceCondition};
+pub use asset::Asset;
+pub use backup::BACKUP_VERSION;
+#[cfg(feature = "peer")]
+pub use builder::{Payment, TransactionBuilder};
+#[cfg(feature = "peer")]
+pub use cancel::CancellationToken;
+#[cfg(feature = "cat")]
+pub use cat_signing::{
+    CatCoinContext, CatLineage, CatOutput, CatSigner, UnsignedCatSpend, UNSIGNED_CAT_SPEND_SCHEMA,
+    UNSIGNED_CAT_SPEND_VERSION,
+};
+#[cfg(feature = "cat")]
+pub use cats::CatTail;
+#[cfg(feature = "peer")]
+pub use change::{ChangeDestination, ChangePolicy};
+#[cfg(feature = "peer")]
+pub use checkpoint::{CheckpointLog, ForkEvent, SyncCheckpoint, MAX_SYNC_CHECKPOINTS};
+#[cfg(feature = "peer")]
+pub use clawback::ClawbackRecord;
+#[cfg(feature = "peer")]
+pub use coin_proof::{verify_coin_proof, CoinProof, SerializedCoin};
+#[cfg(feature = "peer")]
+pub use coin_records::{CoinRecord, CoinRecordCache};
+#[cfg(feature = "peer")]
+pub use coins::{SelectionCandidate, SelectionExplanation, SelectionOptions, SelectionStrategy};
+#[cfg(feature = "cat")]
+pub use coins::{SkipCategory, SkipReason, SyncReport};
+#[cfg(feature = "peer")]
+pub use cold_signing::{
+    SerializedCoinSpend, SignedSpendBundle, SigningTarget, UnsignedSpendBundle,
+    SIGNED_SPEND_BUNDLE_SCHEMA, SIGNED_SPEND_BUNDLE_VERSION, UNSIGNED_SPEND_BUNDLE_SCHEMA,
+    UNSIGNED_SPEND_BUNDLE_VERSION,
+};
+#[cfg(feature = "cat")]
+pub use collateral::{
+    verify_collateral_attestation, verify_collateral_attestation_on_chain, CollateralAttestation,
+    CollateralCoin, COLLATERAL_ATTESTATION_VERSION,
+};
+#[cfg(feature = "peer")]
+pub use compat::WalletV2;
+pub use compat::{
+    supports_api_version, CompatFuture, WalletV1, CURRENT_API_VERSION, MIN_SUPPORTED_API_VERSION,
+};
+pub use config::WalletConfig;
+#[cfg(feature = "peer")]
+pub use consolidation::ConsolidationSummary;
+#[cfg(feature = "peer")]
+pub use context::OpContext;
+pub use datastore::{DatastoreOwnerKeys, DatastoreRole, DATASTORE_KEY_PURPOSE};
+#[cfg(feature = "cache")]
+pub use derivation::{AllocatedAddress, DerivationKind, DerivationState, IndexAllocation};
+#[cfg(feature = "cat")]
+pub use dig::{
+    dig_outer_puzzle_hash_for, dig_outer_puzzle_hash_for_address, dig_outer_puzzle_hash_for_inner,
+    dig_to_mojos, mojos_to_dig, DIG_COIN_ASSET_ID, DIG_DECIMALS, MOJOS_PER_DIG,
+};
+#[cfg(feature = "cat")]
+pub use discovery::DigDiscovery;
+#[cfg(feature = "peer")]
+pub use discovery::{
+    CoinDiscovery, DiscoveredCoin, DiscoveryFuture, DiscoveryReport, XchDiscovery,
+};
+pub use environment::{Environment, SandboxEnvironment, SystemEnvironment};
 pub use error::WalletError;
-pub use file_cache::{FileCache, ReservedCoinCache};
-pub use wallet::Wallet;
-
-// Re-export commonly used types from DataLayer-Driver
-pub use datalayer_driver::{
-    Bytes32, Coin, CoinSpend, NetworkType, Peer, PublicKey, SecretKey, Signature,
+#[cfg(feature = "peer")]
+pub use fee_floor::{FeeFloor, MEMPOOL_MINIMUM_TARGET_SECS};
+#[cfg(feature = "peer")]
+pub use fees::{spend_cost, FeeEstimate, FeeTarget, DEFAULT_FEE_TARGET_SECS};
+#[cfg(feature = "cache")]
+pub use file_cache::{CacheLimits, CompactionReport, FileCache, ReservedCoinCache};
+#[cfg(feature = "peer")]
+pub use freshness::{PeakFreshness, DEFAULT_MAX_PEAK_LAG};
+#[cfg(feature = "peer")]
+pub use health::{HealthCheck, HealthLevel, HealthReport};
+#[cfg(feature = "cache")]
+pub use history::{
+    Ledger, TransactionDirection, TransactionHistory, TransactionPage, TransactionRecord, TxFilter,
+};
+pub use key_export::{KeyExportFormat, KeySummary};
+#[cfg(feature = "keyring-file")]
+pub use keyring::{FileKeyringBackend, KeyringLockOptions};
+pub use keyring::{InMemoryKvStore, KeyringBackend, KvStore, RemoteKeyringBackend, VersionedBlob};
+pub use keyring_access::{KeyringReader, KeyringWriter};
+pub use memlock::MnemonicHandle;
+pub use metadata::{AddressState, ChainProfile, WalletInfo, WalletMetadata, WalletNetwork};
+pub use migrations::{
+    migrate_json_file, rollback_json_file, MigrationPlan, MigrationStep, Migrations,
+};
+pub use mnemonic::{detect_mnemonic_language, validate_mnemonic, MnemonicLanguage, MnemonicLength};
+#[cfg(feature = "nft")]
+pub use nft::{NftMintOptions, NftRecord, MAX_ROYALTY_BASIS_POINTS};
+#[cfg(feature = "offers")]
+pub use offers::{parse_offer, settlement_puzzle_hash, OfferSummary};
+pub use ownership::{
+    Challenger, MemoryNonceStore, NonceStore, OwnershipChallenge, OwnershipNonce, OwnershipProof,
+    OwnershipResponse, OwnershipVerifier, Responder,
 };
+#[cfg(feature = "peer")]
+pub use peers::{PeerEnvironment, PeerPool, PeersFile};
+#[cfg(feature = "peer")]
+pub use pending::{
+    PendingStatus, PendingTransaction, PendingTransactions, PENDING_CONFIRMATION_DEPTH,
+};
+#[cfg(feature = "peer")]
+pub use pinning::certificate_fingerprint;
+#[cfg(feature = "peer")]
+pub use provenance::{ProvenanceEdge, ProvenanceGraph, ProvenanceNode, PuzzleKind};
+#[cfg(feature = "peer")]
+pub use recording::{
+    PeerExchange, PeerRecording, PeerRequest, PeerResponse, PeerSession, RecordedCoinState,
+    PEER_RECORDING_VERSION,
+};
+#[cfg(feature = "remote")]
+pub use remote::{LocalWallet, RemoteWallet, WalletApi, WalletDaemon};
+#[cfg(feature = "cache")]
+pub use reservations::{
+    CoinReservation, CoinReservationManager, ReservationGcStats, ReservationLease,
+    DEFAULT_LEASE_GRACE_SECS,
+};
+pub use reveal::{RevealAuditEntry, RevealOutcome, RevealRequest};
+pub use rng::{OsRngProvider, RngProvider};
+#[cfg(feature = "peer")]
+pub use runtime::TokioSpawner;
+pub use runtime::{init, RuntimeHandle, SpawnFuture, Spawner};
+pub use session::DEFAULT_SESSION_IDLE_TIMEOUT;
+pub use shamir::MAX_MNEMONIC_SHARES;
+pub use signer::{DeviceSigner, Signer, SignerFuture, SigningDevice};
+pub use signing::{SignedMessage, SigningMode};
+#[cfg(feature = "peer")]
+pub use spend_signing::required_signatures;
+#[cfg(feature = "peer")]
+pub use sweep::SweepSummary;
+pub use templates::{
+    MessageTemplate, MessageTemplateId, MessageTemplates, TemplateParams, TemplateSignature,
+};
+pub use threshold::{KeyShare, PartialSignature, ThresholdKey, MAX_THRESHOLD_PARTICIPANTS};
+#[cfg(feature = "peer")]
+pub use tombstones::{SpentCheck, SpentTombstone, SpentTombstones, TOMBSTONE_RETENTION_BLOCKS};
+#[cfg(feature = "peer")]
+pub use transfer::DigSendOptions;
+#[cfg(feature = "peer")]
+pub use tx_queue::{ConflictStrategy, QueueStatus, QueuedTransaction, TransferIntent, TxQueue};
+pub use wallet::{KeyringVerification, Wallet, KEYRING_FORMAT_VERSION};
+#[cfg(feature = "peer")]
+pub use warmup::{WarmCache, WarmupReport};
+pub use watch_only::WatchOnlyKey;
+
+// Re-export commonly used types
+pub use chia::bls::{PublicKey, SecretKey, Signature};
+pub use chia::protocol::{Bytes32, Coin, CoinSpend};
+#[cfg(feature = "peer")]
+pub use datalayer_driver::{NetworkType, Peer};
 
 // Version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,237 @@
+//! Keeping decrypted mnemonics out of swap.
+//!
+//! With the `mlock` feature, [`SecretString`] locks the pages holding its buffer into RAM
+//! (`mlock` on Unix, `VirtualLock` on Windows) so the secret is never written to a swap
+//! file. The OS may refuse the lock, typically once the locked-memory limit is used up;
+//! the secret is then kept unlocked and [`SecretString::is_locked`] reports it. The master
+//! secret key is never stored in a [`crate::Wallet`], only derived from the mnemonic on
+//! demand.
+//!
+//! Locks do not nest, and small secrets often share a page, so pages are locked with
+//! their first holder and unlocked only when the last one is dropped.
+
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+use std::sync::Mutex;
+use zeroize::{Zeroize, Zeroizing};
+
+/// A string wiped on drop and, where possible, locked into RAM
+pub(crate) struct SecretString {
+    value: Zeroizing<String>,
+    locked: bool,
+}
+
+impl SecretString {
+    pub(crate) fn new(value: String) -> Self {
+        let value = Zeroizing::new(value);
+        let locked = value.capacity() > 0 && lock(value.as_ptr(), value.capacity());
+        Self { value, locked }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        self.value.as_str()
+    }
+
+    /// Whether the buffer is locked into RAM
+    pub(crate) fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        Self::new(self.value.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(..)")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        if self.locked {
+            let (ptr, len) = (self.value.as_ptr(), self.value.capacity());
+            // Wipe before unlocking so the secret cannot be paged out in between
+            self.value.zeroize();
+            unlock(ptr, len);
+        }
+    }
+}
+
+/// A freshly generated mnemonic, shown to the caller once and wiped afterwards
+///
+/// Returned by the wallet creation flows so the words do not outlive the moment they are
+/// shown. Callers that must keep the words can take them with
+/// [`MnemonicHandle::into_string`], and become responsible for wiping them.
+pub struct MnemonicHandle {
+    mnemonic: SecretString,
+}
+
+impl MnemonicHandle {
+    pub(crate) fn new(mnemonic: String) -> Self {
+        Self {
+            mnemonic: SecretString::new(mnemonic),
+        }
+    }
+
+    /// Pass the words to `f`, then wipe them
+    pub fn reveal<R>(self, f: impl FnOnce(&str) -> R) -> R {
+        f(self.mnemonic.as_str())
+    }
+
+    /// Take the words as an owned string that is not wiped on drop
+    pub fn into_string(self) -> String {
+        self.mnemonic.as_str().to_string()
+    }
+}
+
+impl fmt::Debug for MnemonicHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MnemonicHandle(..)")
+    }
+}
+
+/// Number of live locks on each locked page, keyed by page address
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+/// Addresses of the pages spanned by `len` bytes at `ptr`
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+fn pages(ptr: *const u8, len: usize) -> impl Iterator<Item = usize> {
+    let page_size = page_size();
+    let start = ptr as usize / page_size * page_size;
+    (start..ptr as usize + len).step_by(page_size)
+}
+
+/// Lock the pages spanned by `len` bytes at `ptr`, returning whether all of them are locked
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+fn lock(ptr: *const u8, len: usize) -> bool {
+    let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut newly_locked = Vec::new();
+    for page in pages(ptr, len) {
+        if locked.contains_key(&page) {
+            continue;
+        }
+        if !lock_page(page) {
+            newly_locked.into_iter().for_each(unlock_page);
+            return false;
+        }
+        newly_locked.push(page);
+    }
+
+    for page in pages(ptr, len) {
+        *locked.entry(page).or_insert(0) += 1;
+    }
+    true
+}
+
+/// Release a lock taken by [`lock`], unlocking the pages nobody else holds
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+fn unlock(ptr: *const u8, len: usize) {
+    let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+    for page in pages(ptr, len) {
+        if let Some(count) = locked.get_mut(&page) {
+            *count -= 1;
+            if *count == 0 {
+                locked.remove(&page);
+                unlock_page(page);
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "mlock", unix))]
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(all(feature = "mlock", unix))]
+fn lock_page(page: usize) -> bool {
+    // SAFETY: the page belongs to a live allocation owned by a caller of `lock`
+    unsafe { libc::mlock(page as *const libc::c_void, page_size()) == 0 }
+}
+
+#[cfg(all(feature = "mlock", unix))]
+fn unlock_page(page: usize) {
+    // SAFETY: the page was locked by `lock_page` and is still allocated
+    unsafe {
+        libc::munlock(page as *const libc::c_void, page_size());
+    }
+}
+
+#[cfg(all(feature = "mlock", windows))]
+fn page_size() -> usize {
+    use windows_sys::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
+
+    // SAFETY: SYSTEM_INFO is plain data, filled in by GetSystemInfo
+    unsafe {
+        let mut info: SYSTEM_INFO = std::mem::zeroed();
+        GetSystemInfo(&mut info);
+        info.dwPageSize as usize
+    }
+}
+
+#[cfg(all(feature = "mlock", windows))]
+fn lock_page(page: usize) -> bool {
+    // SAFETY: the page belongs to a live allocation owned by a caller of `lock`
+    unsafe { windows_sys::Win32::System::Memory::VirtualLock(page as *const _, page_size()) != 0 }
+}
+
+#[cfg(all(feature = "mlock", windows))]
+fn unlock_page(page: usize) {
+    // SAFETY: the page was locked by `lock_page` and is still allocated
+    unsafe {
+        windows_sys::Win32::System::Memory::VirtualUnlock(page as *const _, page_size());
+    }
+}
+
+#[cfg(not(all(feature = "mlock", any(unix, windows))))]
+fn lock(_ptr: *const u8, _len: usize) -> bool {
+    false
+}
+
+#[cfg(not(all(feature = "mlock", any(unix, windows))))]
+fn unlock(_ptr: *const u8, _len: usize) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_string_clones_and_redacts() {
+        let secret = SecretString::new("abandon art".to_string());
+        let copy = secret.clone();
+        drop(secret);
+
+        assert_eq!(copy.as_str(), "abandon art");
+        assert_eq!(format!("{:?}", copy), "SecretString(..)");
+        if !cfg!(feature = "mlock") {
+            assert!(!copy.is_locked());
+        }
+        assert!(!SecretString::new(String::new()).is_locked());
+    }
+
+    #[cfg(all(feature = "mlock", any(unix, windows)))]
+    #[test]
+    fn test_shared_pages_stay_locked_until_last_holder_unlocks() {
+        let buffer = [0u8; 64];
+        let (first, second) = (buffer.as_ptr(), buffer[32..].as_ptr());
+        if !lock(first, 32) {
+            // The OS refused the lock, e.g. RLIMIT_MEMLOCK is zero
+            return;
+        }
+        assert!(lock(second, 32));
+
+        let page = pages(second, 32).next().unwrap();
+        unlock(first, 32);
+        assert!(LOCKED_PAGES.lock().unwrap().contains_key(&page));
+        unlock(second, 32);
+    }
+}
@@ -0,0 +1,380 @@
+//! Per-wallet metadata kept next to each keyring entry.
+//!
+//! Metadata is stored unencrypted, so it can be listed without a keyring passphrase; it
+//! never holds key material.
+//!
+//! The [`ChainProfile`] recorded here is what lets one keyring hold keys used on several
+//! Chia forks: each wallet encodes addresses with its own prefix, rejects addresses of
+//! other chains and connects on its own default port.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::keys::{decode_address, encode_address};
+use crate::wallet::Wallet;
+use chia::protocol::Bytes32;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MAINNET_PEER_PORT: u16 = 8444;
+const TESTNET11_PEER_PORT: u16 = 58444;
+
+/// Network a wallet is intended for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WalletNetwork {
+    Mainnet,
+    Testnet11,
+}
+
+impl WalletNetwork {
+    /// Name as used by `peers.toml`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WalletNetwork::Mainnet => "mainnet",
+            WalletNetwork::Testnet11 => "testnet11",
+        }
+    }
+}
+
+#[cfg(feature = "peer")]
+impl From<WalletNetwork> for datalayer_driver::NetworkType {
+    fn from(network: WalletNetwork) -> Self {
+        match network {
+            WalletNetwork::Mainnet => datalayer_driver::NetworkType::Mainnet,
+            WalletNetwork::Testnet11 => datalayer_driver::NetworkType::Testnet11,
+        }
+    }
+}
+
+/// Optional descriptive data of a stored wallet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletMetadata {
+    /// Display label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<WalletNetwork>,
+    /// Address prefix of a fork; defaults to the one of `network`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_prefix: Option<String>,
+    /// Full node port of a fork; defaults to the one of `network`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_port: Option<u16>,
+    /// Unix seconds; absent for wallets stored before metadata existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    /// Unix seconds of the last [`Wallet::mark_used`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+    /// Fingerprint of the master public key; absent for wallets stored before it was
+    /// recorded and for watch-only wallets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<u32>,
+    /// Receive addresses of account 0
+    #[serde(flatten)]
+    pub addresses: AddressState,
+    /// Receive addresses of the other accounts used through [`Wallet::load_account`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub accounts: BTreeMap<u32, AddressState>,
+}
+
+/// Receive addresses issued and found in use for one account
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressState {
+    /// Highest receive address index handed out by [`Wallet::get_new_address`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive_index: Option<u32>,
+    /// Derivation indexes found with on-chain history by the last [`Wallet::scan_addresses`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub used_indexes: Vec<u32>,
+}
+
+impl WalletMetadata {
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Receive addresses of `account`
+    pub fn account_addresses(&self, account: u32) -> AddressState {
+        match account {
+            0 => self.addresses.clone(),
+            account => self.accounts.get(&account).cloned().unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn account_addresses_mut(&mut self, account: u32) -> &mut AddressState {
+        match account {
+            0 => &mut self.addresses,
+            account => self.accounts.entry(account).or_default(),
+        }
+    }
+
+    /// The chain the wallet is used on; mainnet unless set
+    pub fn chain(&self) -> ChainProfile {
+        let mut chain = ChainProfile::for_network(self.network.unwrap_or(WalletNetwork::Mainnet));
+        if let Some(prefix) = &self.address_prefix {
+            chain.address_prefix = prefix.clone();
+        }
+        if let Some(port) = self.peer_port {
+            chain.peer_port = port;
+        }
+        chain
+    }
+}
+
+/// Address prefix and connection defaults of the chain a wallet is used on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainProfile {
+    /// Consensus network the chain follows
+    pub network: WalletNetwork,
+    /// Bech32m prefix of the chain's addresses, such as `xch`
+    pub address_prefix: String,
+    /// Default full node port
+    pub peer_port: u16,
+}
+
+impl ChainProfile {
+    /// Chia mainnet: `xch` addresses on port 8444
+    pub fn mainnet() -> Self {
+        Self::for_network(WalletNetwork::Mainnet)
+    }
+
+    /// Chia testnet11: `txch` addresses on port 58444
+    pub fn testnet11() -> Self {
+        Self::for_network(WalletNetwork::Testnet11)
+    }
+
+    fn for_network(network: WalletNetwork) -> Self {
+        let (address_prefix, peer_port) = match network {
+            WalletNetwork::Mainnet => ("xch", MAINNET_PEER_PORT),
+            WalletNetwork::Testnet11 => ("txch", TESTNET11_PEER_PORT),
+        };
+        Self {
+            network,
+            address_prefix: address_prefix.to_string(),
+            peer_port,
+        }
+    }
+
+    /// A fork following `network` with its own address prefix and port
+    pub fn fork(
+        network: WalletNetwork,
+        address_prefix: &str,
+        peer_port: u16,
+    ) -> Result<Self, WalletError> {
+        let valid = !address_prefix.is_empty()
+            && address_prefix
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+        if !valid {
+            return Err(WalletError::InvalidConfig(format!(
+                "Invalid address prefix: {:?}",
+                address_prefix
+            )));
+        }
+        Ok(Self {
+            network,
+            address_prefix: address_prefix.to_string(),
+            peer_port,
+        })
+    }
+
+    /// Encode a puzzle hash as an address of this chain
+    pub fn encode_address(&self, puzzle_hash: Bytes32) -> Result<String, WalletError> {
+        encode_address(puzzle_hash, &self.address_prefix)
+    }
+
+    /// Decode an address, failing with [`WalletError::ChainMismatch`] if it belongs to
+    /// another chain
+    pub fn decode_address(&self, address: &str) -> Result<Bytes32, WalletError> {
+        let puzzle_hash = decode_address(address)?;
+        // Bech32 prefixes end at the last separator
+        let prefix = address.rsplit_once('1').map_or("", |(prefix, _)| prefix);
+        if !prefix.eq_ignore_ascii_case(&self.address_prefix) {
+            return Err(WalletError::ChainMismatch {
+                expected: self.address_prefix.clone(),
+                found: prefix.to_ascii_lowercase(),
+            });
+        }
+        Ok(puzzle_hash)
+    }
+}
+
+/// A stored wallet as listed by [`Wallet::list_wallet_info`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletInfo {
+    pub name: String,
+    pub metadata: WalletMetadata,
+    /// Whether opening the wallet needs the keyring passphrase
+    pub password_protected: bool,
+    /// Whether the wallet is stored as a public key only
+    pub watch_only: bool,
+}
+
+impl Wallet {
+    /// Every stored wallet with its metadata, sorted by name
+    pub async fn list_wallet_info() -> Result<Vec<WalletInfo>, WalletError> {
+        Self::list_wallet_info_with_config(&WalletConfig::default()).await
+    }
+
+    /// Every stored wallet with its metadata, using an explicit configuration
+    pub async fn list_wallet_info_with_config(
+        config: &WalletConfig,
+    ) -> Result<Vec<WalletInfo>, WalletError> {
+        Self::keyring_entries(config).await
+    }
+
+    /// Metadata of this wallet's keyring entry
+    pub async fn get_metadata(&self) -> Result<WalletMetadata, WalletError> {
+        Self::keyring_entries(self.get_config())
+            .await?
+            .into_iter()
+            .find(|info| info.name == self.get_wallet_name())
+            .map(|info| info.metadata)
+            .ok_or_else(|| WalletError::WalletNotFound(self.get_wallet_name().to_string()))
+    }
+
+    /// Set or clear the display label
+    pub async fn set_label(&self, label: Option<&str>) -> Result<(), WalletError> {
+        let label = label.map(str::to_string);
+        self.update_own_metadata(|metadata| metadata.label = label.clone())
+            .await
+    }
+
+    /// Set or clear the intended network
+    pub async fn set_network(&self, network: Option<WalletNetwork>) -> Result<(), WalletError> {
+        self.update_own_metadata(|metadata| metadata.network = network)
+            .await
+    }
+
+    /// The chain this wallet is used on
+    ///
+    /// A chain given to [`Wallet::with_chain`] wins; otherwise it comes from the keyring
+    /// metadata, and wallets without any are mainnet wallets.
+    pub async fn chain_profile(&self) -> Result<ChainProfile, WalletError> {
+        if let Some(chain) = self.chain_override() {
+            return Ok(chain.clone());
+        }
+        Ok(Self::keyring_entries(self.get_config())
+            .await?
+            .into_iter()
+            .find(|info| info.name == self.get_wallet_name())
+            .map(|info| info.metadata.chain())
+            .unwrap_or_else(ChainProfile::mainnet))
+    }
+
+    /// Record the chain the wallet is used on, or clear it to return to mainnet
+    pub async fn set_chain(&self, chain: Option<&ChainProfile>) -> Result<(), WalletError> {
+        self.update_own_metadata(|metadata| {
+            metadata.network = chain.map(|chain| chain.network);
+            metadata.address_prefix = chain.map(|chain| chain.address_prefix.clone());
+            metadata.peer_port = chain.map(|chain| chain.peer_port);
+        })
+        .await
+    }
+
+    /// Decode `address`, rejecting addresses of chains other than this wallet's
+    pub async fn validate_address(&self, address: &str) -> Result<Bytes32, WalletError> {
+        self.chain_profile().await?.decode_address(address)
+    }
+
+    /// Record that the wallet was used now
+    pub async fn mark_used(&self) -> Result<(), WalletError> {
+        let now = self.get_config().unix_timestamp();
+        self.update_own_metadata(|metadata| metadata.last_used = Some(now))
+            .await
+    }
+
+    async fn update_own_metadata(
+        &self,
+        f: impl Fn(&mut WalletMetadata),
+    ) -> Result<(), WalletError> {
+        Self::update_metadata(self.get_wallet_name(), self.get_config(), f).await
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_metadata_survives_listing_and_reencryption() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(
+            SandboxEnvironment::new(temp_dir.path())
+                .with_fixed_time(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        );
+        Wallet::create_new_wallet_with_config("node", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("node".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        wallet.set_label(Some("Storage node")).await.unwrap();
+        wallet
+            .set_network(Some(WalletNetwork::Testnet11))
+            .await
+            .unwrap();
+        wallet.mark_used().await.unwrap();
+        Wallet::migrate_keyring_with_config("passphrase", &config)
+            .await
+            .unwrap();
+
+        let infos = Wallet::list_wallet_info_with_config(&config).await.unwrap();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].name, "node");
+        assert!(infos[0].password_protected);
+        let metadata = &infos[0].metadata;
+        assert_eq!(metadata.label.as_deref(), Some("Storage node"));
+        assert_eq!(metadata.network, Some(WalletNetwork::Testnet11));
+        assert_eq!(metadata.created_at, Some(1_700_000_000));
+        assert_eq!(metadata.last_used, Some(1_700_000_000));
+
+        let missing = Wallet::new_with_config(None, "missing".to_string(), config);
+        assert!(matches!(
+            missing.set_label(Some("x")).await,
+            Err(WalletError::WalletNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chain_profile_drives_addresses_and_validation() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("fork", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("fork".to_string()), false, &config)
+            .await
+            .unwrap();
+        let mainnet_address = wallet.get_owner_public_key().await.unwrap();
+        assert!(mainnet_address.starts_with("xch1"));
+
+        let fork = ChainProfile::fork(WalletNetwork::Mainnet, "xfk", 9444).unwrap();
+        wallet.set_chain(Some(&fork)).await.unwrap();
+        let fork_address = wallet.get_owner_public_key().await.unwrap();
+        assert!(fork_address.starts_with("xfk1"));
+        assert_eq!(wallet.chain_profile().await.unwrap().peer_port, 9444);
+
+        assert_eq!(
+            wallet.validate_address(&fork_address).await.unwrap(),
+            wallet.get_owner_puzzle_hash().await.unwrap()
+        );
+        assert!(matches!(
+            wallet.validate_address(&mainnet_address).await,
+            Err(WalletError::ChainMismatch { .. })
+        ));
+        assert!(ChainProfile::fork(WalletNetwork::Mainnet, "X-1", 9444).is_err());
+
+        let testnet = wallet.clone().with_chain(ChainProfile::testnet11());
+        assert!(testnet
+            .get_owner_public_key()
+            .await
+            .unwrap()
+            .starts_with("txch1"));
+    }
+}
@@ -0,0 +1,337 @@
+//! Ordered, versioned upgrades of stored JSON data.
+//!
+//! A [`Migrations`] list holds one [`MigrationStep`] per format version, each upgrading
+//! a document from its version to the next. Documents record their format in a top-level
+//! `version` field (absent meaning 0). [`Migrations::plan`] is the dry run: it reports the
+//! steps an upgrade would take without changing anything. [`Migrations::apply`] runs
+//! them on a copy, so a failing step leaves the original as it was, and upgrading a
+//! document already at the current version does nothing.
+//!
+//! [`migrate_json_file`] upgrades a file in place: it keeps a `<file>.v<version>.bak`
+//! copy of the old data before writing and replaces the file atomically, and
+//! [`rollback_json_file`] puts such a copy back. The keyring is upgraded the same way by
+//! [`Wallet::apply_keyring_migration`], with the backup kept by its
+//! [`crate::KeyringBackend`]; [`Wallet::plan_keyring_migration`] and
+//! [`Wallet::rollback_keyring`] are its dry run and rollback. Loading an older keyring
+//! only upgrades it in memory, and writing one fails until it has been migrated.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::wallet::{Wallet, KEYRING_MIGRATIONS, KEYRING_WRITE_ATTEMPTS};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Upgrade of a document from `from_version` to the next version
+#[derive(Debug, Clone, Copy)]
+pub struct MigrationStep {
+    pub from_version: u32,
+    pub description: &'static str,
+    apply: fn(&mut Value) -> Result<(), WalletError>,
+}
+
+impl MigrationStep {
+    pub const fn new(
+        from_version: u32,
+        description: &'static str,
+        apply: fn(&mut Value) -> Result<(), WalletError>,
+    ) -> Self {
+        Self {
+            from_version,
+            description,
+            apply,
+        }
+    }
+}
+
+/// The upgrades of one kind of stored document, in version order
+#[derive(Debug, Clone, Copy)]
+pub struct Migrations {
+    name: &'static str,
+    steps: &'static [MigrationStep],
+}
+
+/// What upgrading a document involves; see [`Migrations::plan`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationPlan {
+    pub name: String,
+    pub from_version: u32,
+    pub to_version: u32,
+    /// Descriptions of the steps, in the order they run
+    pub steps: Vec<String>,
+}
+
+impl MigrationPlan {
+    /// Whether the document is already at the current version
+    pub fn is_noop(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl Migrations {
+    /// `steps[i]` must upgrade from version `i`
+    pub const fn new(name: &'static str, steps: &'static [MigrationStep]) -> Self {
+        Self { name, steps }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The version documents are upgraded to
+    pub fn current_version(&self) -> u32 {
+        self.steps.len() as u32
+    }
+
+    /// The format version recorded in `document`
+    pub fn version_of(&self, document: &Value) -> Result<u32, WalletError> {
+        match document.get("version") {
+            None => Ok(0),
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| {
+                    WalletError::SerializationError(format!(
+                        "Invalid {} version {}",
+                        self.name, version
+                    ))
+                }),
+        }
+    }
+
+    /// The steps upgrading `document` would take, without running them
+    ///
+    /// Fails with [`WalletError::UnsupportedKeyringVersion`] for documents written by a
+    /// newer build.
+    pub fn plan(&self, document: &Value) -> Result<MigrationPlan, WalletError> {
+        let from_version = self.version_of(document)?;
+        if from_version > self.current_version() {
+            return Err(WalletError::UnsupportedKeyringVersion {
+                found: from_version,
+                supported: self.current_version(),
+            });
+        }
+        Ok(MigrationPlan {
+            name: self.name.to_string(),
+            from_version,
+            to_version: self.current_version(),
+            steps: self.steps[from_version as usize..]
+                .iter()
+                .map(|step| step.description.to_string())
+                .collect(),
+        })
+    }
+
+    /// Upgrade `document` to the current version, returning it and the version it had
+    ///
+    /// The steps run on a copy; if one fails, the error is returned and nothing is kept.
+    pub fn apply(&self, document: &Value) -> Result<(Value, u32), WalletError> {
+        let from_version = self.plan(document)?.from_version;
+        let mut upgraded = document.clone();
+        for step in &self.steps[from_version as usize..] {
+            debug_assert_eq!(self.version_of(&upgraded)?, step.from_version);
+            (step.apply)(&mut upgraded).map_err(|e| {
+                WalletError::SerializationError(format!(
+                    "{} migration from version {} failed: {}",
+                    self.name, step.from_version, e
+                ))
+            })?;
+            upgraded["version"] = (step.from_version + 1).into();
+        }
+        Ok((upgraded, from_version))
+    }
+}
+
+fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    PathBuf::from(backup)
+}
+
+fn write_atomically(path: &Path, content: &[u8]) -> Result<(), WalletError> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    fs::write(&temp_path, content).map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+    fs::rename(&temp_path, path).map_err(|e| WalletError::FileSystemError(e.to_string()))
+}
+
+/// Upgrade the JSON file at `path` with `migrations`, returning the plan that was run
+///
+/// With `dry_run` nothing is written. Otherwise the old content is first copied to
+/// `<path>.v<version>.bak` (an existing copy is kept) and the upgraded document then
+/// replaces the file atomically, so the file is never left half converted.
+pub fn migrate_json_file(
+    path: &Path,
+    migrations: &Migrations,
+    dry_run: bool,
+) -> Result<MigrationPlan, WalletError> {
+    let content = fs::read(path).map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+    let document: Value = serde_json::from_slice(&content)
+        .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+    let plan = migrations.plan(&document)?;
+    if dry_run || plan.is_noop() {
+        return Ok(plan);
+    }
+
+    let (upgraded, from_version) = migrations.apply(&document)?;
+    let backup = backup_path(path, from_version);
+    if !backup.exists() {
+        write_atomically(&backup, &content)?;
+    }
+    let upgraded = serde_json::to_vec_pretty(&upgraded)
+        .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+    write_atomically(path, &upgraded)?;
+    Ok(plan)
+}
+
+/// Restore the copy of `path` kept before it was upgraded from `version`
+///
+/// Meant for going back to a build that only reads that version.
+pub fn rollback_json_file(path: &Path, version: u32) -> Result<(), WalletError> {
+    let backup = backup_path(path, version);
+    let content = fs::read(&backup).map_err(|e| {
+        WalletError::FileSystemError(format!("No backup at {}: {}", backup.display(), e))
+    })?;
+    write_atomically(path, &content)
+}
+
+impl Wallet {
+    /// The upgrade [`Self::apply_keyring_migration`] would perform, without performing it
+    pub async fn plan_keyring_migration() -> Result<MigrationPlan, WalletError> {
+        Self::plan_keyring_migration_with_config(&WalletConfig::default()).await
+    }
+
+    /// Plan the keyring upgrade using an explicit configuration
+    pub async fn plan_keyring_migration_with_config(
+        config: &WalletConfig,
+    ) -> Result<MigrationPlan, WalletError> {
+        let document = match config.keyring_backend()?.load().await? {
+            Some(blob) => serde_json::from_slice(&blob.data)
+                .map_err(|e| WalletError::SerializationError(e.to_string()))?,
+            None => serde_json::json!({ "version": KEYRING_MIGRATIONS.current_version() }),
+        };
+        KEYRING_MIGRATIONS.plan(&document)
+    }
+
+    /// Upgrade a keyring stored in an older format, returning the plan that was run
+    ///
+    /// The backend keeps a copy of the old keyring first, and the upgraded keyring
+    /// replaces it in one write. Running it on a current keyring does nothing.
+    pub async fn apply_keyring_migration() -> Result<MigrationPlan, WalletError> {
+        Self::apply_keyring_migration_with_config(&WalletConfig::default()).await
+    }
+
+    /// Upgrade the keyring using an explicit configuration
+    pub async fn apply_keyring_migration_with_config(
+        config: &WalletConfig,
+    ) -> Result<MigrationPlan, WalletError> {
+        Self::upgrade_keyring(config).await
+    }
+
+    /// Restore the keyring kept before it was upgraded from `format_version`
+    pub async fn rollback_keyring(format_version: u32) -> Result<(), WalletError> {
+        Self::rollback_keyring_with_config(format_version, &WalletConfig::default()).await
+    }
+
+    /// Restore a keyring backup using an explicit configuration
+    ///
+    /// Wallets added since the upgrade are lost. Meant for going back to a build that
+    /// only reads `format_version`; this build then reads the keyring but does not write
+    /// it until [`Self::apply_keyring_migration`] runs again.
+    pub async fn rollback_keyring_with_config(
+        format_version: u32,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        let backend = config.keyring_backend()?;
+        let backup = backend.load_backup(format_version).await?.ok_or_else(|| {
+            WalletError::InvalidConfig(format!(
+                "No keyring backup for format version {}",
+                format_version
+            ))
+        })?;
+
+        for _ in 0..KEYRING_WRITE_ATTEMPTS {
+            let current = backend.load().await?.map(|blob| blob.version);
+            match backend.store(backup.clone(), current.as_deref()).await {
+                Ok(_) => return Ok(()),
+                Err(WalletError::KeyringConflict(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(WalletError::KeyringConflict(format!(
+            "gave up restoring the keyring after {} concurrent updates",
+            KEYRING_WRITE_ATTEMPTS
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn rename_label(document: &mut Value) -> Result<(), WalletError> {
+        if let Some(label) = document.as_object_mut().and_then(|o| o.remove("label")) {
+            document["name"] = label;
+        }
+        Ok(())
+    }
+
+    fn require_name(document: &mut Value) -> Result<(), WalletError> {
+        match document.get("name") {
+            Some(_) => Ok(()),
+            None => Err(WalletError::SerializationError("missing name".to_string())),
+        }
+    }
+
+    static STEPS: [MigrationStep; 2] = [
+        MigrationStep::new(0, "rename label to name", rename_label),
+        MigrationStep::new(1, "require a name", require_name),
+    ];
+    static TEST_MIGRATIONS: Migrations = Migrations::new("test", &STEPS);
+
+    #[test]
+    fn test_migration_runs_steps_in_order_and_rolls_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("doc.json");
+        fs::write(&path, json!({ "label": "alpha" }).to_string()).unwrap();
+
+        let plan = migrate_json_file(&path, &TEST_MIGRATIONS, true).unwrap();
+        assert_eq!(
+            (plan.from_version, plan.to_version, plan.steps.len()),
+            (0, 2, 2)
+        );
+        assert!(fs::read_to_string(&path).unwrap().contains("label"));
+
+        migrate_json_file(&path, &TEST_MIGRATIONS, false).unwrap();
+        let upgraded: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(upgraded, json!({ "name": "alpha", "version": 2 }));
+        // Running again is a no-op
+        assert!(migrate_json_file(&path, &TEST_MIGRATIONS, false)
+            .unwrap()
+            .is_noop());
+
+        rollback_json_file(&path, 0).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("label"));
+    }
+
+    #[test]
+    fn test_failing_step_leaves_file_untouched() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("doc.json");
+        let original = json!({ "other": 1 }).to_string();
+        fs::write(&path, &original).unwrap();
+
+        assert!(migrate_json_file(&path, &TEST_MIGRATIONS, false).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(!backup_path(&path, 0).exists());
+
+        let newer = json!({ "name": "x", "version": 3 });
+        assert!(matches!(
+            TEST_MIGRATIONS.plan(&newer),
+            Err(WalletError::UnsupportedKeyringVersion { .. })
+        ));
+    }
+}
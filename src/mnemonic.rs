@@ -0,0 +1,263 @@
+//! BIP39 mnemonic lengths and wordlists.
+//!
+//! Chia accepts BIP39 mnemonics of 12, 15, 18, 21 or 24 words. New wallets get 24 words
+//! unless created with [`Wallet::create_new_wallet_of_length`];
+//! [`validate_mnemonic`] reports the length of a phrase before it is imported.
+//!
+//! Mnemonics in any BIP39 wordlist are accepted and their language is detected from the
+//! words; [`detect_mnemonic_language`] reports it. Keys are derived from the normalized
+//! words themselves, so a Japanese or Spanish mnemonic gives the same keys as in any
+//! other BIP39 wallet without translating it. New mnemonics are English unless
+//! [`crate::WalletConfig::with_mnemonic_language`] picks another wordlist.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::memlock::MnemonicHandle;
+use crate::wallet::Wallet;
+use bip39::{Language, Mnemonic};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Number of words of a BIP39 mnemonic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "usize", into = "usize")]
+pub enum MnemonicLength {
+    Words12,
+    Words15,
+    Words18,
+    Words21,
+    #[default]
+    Words24,
+}
+
+impl MnemonicLength {
+    /// Every supported length, shortest first
+    pub const ALL: [MnemonicLength; 5] = [
+        MnemonicLength::Words12,
+        MnemonicLength::Words15,
+        MnemonicLength::Words18,
+        MnemonicLength::Words21,
+        MnemonicLength::Words24,
+    ];
+
+    pub fn word_count(&self) -> usize {
+        match self {
+            MnemonicLength::Words12 => 12,
+            MnemonicLength::Words15 => 15,
+            MnemonicLength::Words18 => 18,
+            MnemonicLength::Words21 => 21,
+            MnemonicLength::Words24 => 24,
+        }
+    }
+
+    /// Bytes of entropy encoded by mnemonics of this length
+    pub fn entropy_bytes(&self) -> usize {
+        self.word_count() * 4 / 3
+    }
+
+    /// The length with `word_count` words, if it is a supported one
+    pub fn from_word_count(word_count: usize) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|length| length.word_count() == word_count)
+    }
+}
+
+impl TryFrom<usize> for MnemonicLength {
+    type Error = String;
+
+    fn try_from(word_count: usize) -> Result<Self, Self::Error> {
+        Self::from_word_count(word_count)
+            .ok_or_else(|| format!("unsupported mnemonic length of {} words", word_count))
+    }
+}
+
+impl From<MnemonicLength> for usize {
+    fn from(length: MnemonicLength) -> Self {
+        length.word_count()
+    }
+}
+
+/// Wordlist of a BIP39 mnemonic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MnemonicLanguage {
+    #[default]
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+impl MnemonicLanguage {
+    /// Every supported wordlist
+    pub const ALL: [MnemonicLanguage; 10] = [
+        MnemonicLanguage::English,
+        MnemonicLanguage::ChineseSimplified,
+        MnemonicLanguage::ChineseTraditional,
+        MnemonicLanguage::Czech,
+        MnemonicLanguage::French,
+        MnemonicLanguage::Italian,
+        MnemonicLanguage::Japanese,
+        MnemonicLanguage::Korean,
+        MnemonicLanguage::Portuguese,
+        MnemonicLanguage::Spanish,
+    ];
+
+    pub(crate) fn to_bip39(self) -> Language {
+        match self {
+            MnemonicLanguage::English => Language::English,
+            MnemonicLanguage::ChineseSimplified => Language::SimplifiedChinese,
+            MnemonicLanguage::ChineseTraditional => Language::TraditionalChinese,
+            MnemonicLanguage::Czech => Language::Czech,
+            MnemonicLanguage::French => Language::French,
+            MnemonicLanguage::Italian => Language::Italian,
+            MnemonicLanguage::Japanese => Language::Japanese,
+            MnemonicLanguage::Korean => Language::Korean,
+            MnemonicLanguage::Portuguese => Language::Portuguese,
+            MnemonicLanguage::Spanish => Language::Spanish,
+        }
+    }
+
+    pub(crate) fn from_bip39(language: Language) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.to_bip39() == language)
+            .unwrap_or_default()
+    }
+}
+
+/// Parse `phrase` in whichever wordlist it is written in
+///
+/// Phrases whose words appear in several wordlists take the first, in
+/// [`MnemonicLanguage::ALL`] order, in which their checksum is valid.
+pub(crate) fn parse_mnemonic(phrase: &str) -> Result<Mnemonic, WalletError> {
+    match Mnemonic::parse(phrase) {
+        Ok(mnemonic) => Ok(mnemonic),
+        Err(bip39::Error::AmbiguousLanguages(languages)) => languages
+            .iter()
+            .find_map(|language| Mnemonic::parse_in(language, phrase).ok())
+            .ok_or(WalletError::InvalidMnemonic),
+        Err(_) => Err(WalletError::InvalidMnemonic),
+    }
+}
+
+/// Check that `phrase` is a valid BIP39 mnemonic, returning its length
+pub fn validate_mnemonic(phrase: &str) -> Result<MnemonicLength, WalletError> {
+    let mnemonic = parse_mnemonic(phrase)?;
+    MnemonicLength::from_word_count(mnemonic.word_count()).ok_or(WalletError::InvalidMnemonic)
+}
+
+/// The wordlist of the valid BIP39 mnemonic `phrase`
+pub fn detect_mnemonic_language(phrase: &str) -> Result<MnemonicLanguage, WalletError> {
+    Ok(MnemonicLanguage::from_bip39(
+        parse_mnemonic(phrase)?.language(),
+    ))
+}
+
+impl Wallet {
+    /// Create a new wallet with a generated mnemonic of `length` words
+    pub async fn create_new_wallet_of_length(
+        wallet_name: &str,
+        length: MnemonicLength,
+    ) -> Result<MnemonicHandle, WalletError> {
+        Self::create_new_wallet_of_length_with_config(wallet_name, length, &WalletConfig::default())
+            .await
+    }
+
+    /// Create a wallet with a mnemonic of `length` words using an explicit configuration
+    pub async fn create_new_wallet_of_length_with_config(
+        wallet_name: &str,
+        length: MnemonicLength,
+        config: &WalletConfig,
+    ) -> Result<MnemonicHandle, WalletError> {
+        let entropy = Zeroizing::new(config.random_bytes::<32>()?);
+        let mnemonic = Mnemonic::from_entropy_in(
+            config.mnemonic_language().to_bip39(),
+            &entropy[..length.entropy_bytes()],
+        )
+        .map_err(|_| WalletError::CryptoError("Failed to generate mnemonic".to_string()))?;
+        let mnemonic_str = mnemonic.to_string();
+        Self::save_wallet_to_keyring(wallet_name, &mnemonic_str, config).await?;
+        Ok(MnemonicHandle::new(mnemonic_str))
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_create_and_validate_every_length() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+
+        for length in MnemonicLength::ALL {
+            let name = format!("words{}", length.word_count());
+            let mnemonic = Wallet::create_new_wallet_of_length_with_config(&name, length, &config)
+                .await
+                .unwrap()
+                .into_string();
+            assert_eq!(mnemonic.split_whitespace().count(), length.word_count());
+            assert_eq!(validate_mnemonic(&mnemonic).unwrap(), length);
+        }
+
+        assert_eq!(
+            serde_json::to_string(&MnemonicLength::Words15).unwrap(),
+            "15"
+        );
+        assert!(serde_json::from_str::<MnemonicLength>("13").is_err());
+        assert!(matches!(
+            validate_mnemonic("abandon abandon abandon"),
+            Err(WalletError::InvalidMnemonic)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_non_english_mnemonics_are_detected_and_derive_the_reference_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+
+        // BIP39 test vector for all-zero entropy in the Japanese wordlist
+        let japanese = "あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あおぞら";
+        assert_eq!(
+            detect_mnemonic_language(japanese).unwrap(),
+            MnemonicLanguage::Japanese
+        );
+        Wallet::import_wallet_with_config("japanese", Some(japanese), &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("japanese".to_string()), false, &config)
+            .await
+            .unwrap();
+        let expected = Mnemonic::parse_in(Language::Japanese, japanese)
+            .unwrap()
+            .to_seed("");
+        assert_eq!(
+            wallet.get_master_secret_key().await.unwrap(),
+            chia::bls::SecretKey::from_seed(&expected)
+        );
+
+        let config = config.with_mnemonic_language(MnemonicLanguage::Spanish);
+        let spanish = Wallet::create_new_wallet_of_length_with_config(
+            "spanish",
+            MnemonicLength::Words12,
+            &config,
+        )
+        .await
+        .unwrap()
+        .into_string();
+        assert_eq!(
+            detect_mnemonic_language(&spanish).unwrap(),
+            MnemonicLanguage::Spanish
+        );
+    }
+}
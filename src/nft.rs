@@ -0,0 +1,324 @@
+//! NFT1 minting, discovery and transfer.
+//!
+//! An NFT is a singleton: its launcher coin gives it a permanent id, and every spend
+//! recreates the coin with the same layers around the next owner's puzzle. Minting and
+//! transfers hint the new coin with the owner's puzzle hash, so the wallet finds its NFTs
+//! among the coins hinted to its issued addresses. A coin only counts once its parent spend
+//! parses as that NFT, which is also where the lineage proof its next spend needs comes
+//! from. DataLayer projects tie a store to an NFT by its launcher id.
+
+use crate::chain::{fetch_coin_spend, fetch_coin_state, fetch_puzzle_history};
+use crate::error::WalletError;
+use crate::transfer::driver_error;
+use crate::wallet::Wallet;
+use chia::puzzles::nft::NftMetadata;
+use chia_wallet_sdk::driver::{Action, Id, Nft, Puzzle, SpendContext, Spends};
+use chia_wallet_sdk::types::{puzzles::NftMetadataUpdater, Mod};
+use datalayer_driver::{Bytes32, Coin, CoinState, Peer};
+use std::collections::HashSet;
+
+/// Highest royalty an NFT can carry, in basis points: 100%
+pub const MAX_ROYALTY_BASIS_POINTS: u16 = 10_000;
+
+/// What to mint: the NFT's metadata, its royalty and its first owner
+///
+/// Without a royalty address or owner, the wallet's default address is used.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NftMintOptions {
+    metadata: NftMetadata,
+    royalty_puzzle_hash: Option<Bytes32>,
+    royalty_basis_points: u16,
+    owner_puzzle_hash: Option<Bytes32>,
+}
+
+impl NftMintOptions {
+    /// An edition 1 of 1 NFT without URIs or royalty
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// URIs of the NFT's content and the hash of that content
+    pub fn with_data(mut self, uris: Vec<String>, hash: Option<Bytes32>) -> Self {
+        self.metadata.data_uris = uris;
+        self.metadata.data_hash = hash;
+        self
+    }
+
+    /// URIs of the off-chain metadata JSON and the hash of that JSON
+    pub fn with_metadata(mut self, uris: Vec<String>, hash: Option<Bytes32>) -> Self {
+        self.metadata.metadata_uris = uris;
+        self.metadata.metadata_hash = hash;
+        self
+    }
+
+    /// URIs of the NFT's license and the hash of that license
+    pub fn with_license(mut self, uris: Vec<String>, hash: Option<Bytes32>) -> Self {
+        self.metadata.license_uris = uris;
+        self.metadata.license_hash = hash;
+        self
+    }
+
+    pub fn with_edition(mut self, number: u64, total: u64) -> Self {
+        self.metadata.edition_number = number;
+        self.metadata.edition_total = total;
+        self
+    }
+
+    /// Pay `basis_points` of the price of every offer trading the NFT to `puzzle_hash`
+    ///
+    /// Basis points are hundredths of a percent, so 300 is 3%.
+    pub fn with_royalty(mut self, puzzle_hash: Bytes32, basis_points: u16) -> Self {
+        self.royalty_puzzle_hash = Some(puzzle_hash);
+        self.royalty_basis_points = basis_points;
+        self
+    }
+
+    /// Mint the NFT to `puzzle_hash` instead of the wallet
+    pub fn with_owner(mut self, puzzle_hash: Bytes32) -> Self {
+        self.owner_puzzle_hash = Some(puzzle_hash);
+        self
+    }
+
+    pub fn metadata(&self) -> &NftMetadata {
+        &self.metadata
+    }
+
+    pub fn royalty_basis_points(&self) -> u16 {
+        self.royalty_basis_points
+    }
+}
+
+/// An NFT held by the wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NftRecord {
+    /// Id of the NFT, the coin id of its launcher
+    pub launcher_id: Bytes32,
+    /// The current NFT coin
+    pub coin: Coin,
+    /// Puzzle hash of the issued address holding the NFT
+    pub owner_puzzle_hash: Bytes32,
+    pub royalty_puzzle_hash: Bytes32,
+    /// Royalty in basis points, hundredths of a percent
+    pub royalty_basis_points: u16,
+    /// `None` if the on-chain metadata does not follow the NFT1 format
+    pub metadata: Option<NftMetadata>,
+}
+
+impl NftRecord {
+    fn new(ctx: &SpendContext, nft: &Nft) -> Self {
+        Self {
+            launcher_id: nft.info.launcher_id,
+            coin: nft.coin,
+            owner_puzzle_hash: nft.info.p2_puzzle_hash,
+            royalty_puzzle_hash: nft.info.royalty_puzzle_hash,
+            royalty_basis_points: nft.info.royalty_basis_points,
+            metadata: ctx.extract(nft.info.metadata.ptr()).ok(),
+        }
+    }
+}
+
+/// Whether `options` can be minted
+fn check_mint_options(options: &NftMintOptions) -> Result<(), WalletError> {
+    if options.royalty_basis_points > MAX_ROYALTY_BASIS_POINTS {
+        return Err(WalletError::InvalidAmount(format!(
+            "Royalty of {} basis points is above {}",
+            options.royalty_basis_points, MAX_ROYALTY_BASIS_POINTS
+        )));
+    }
+    Ok(())
+}
+
+/// The NFT that `coin_state` holds, parsed into `ctx` with its lineage proof
+///
+/// `Ok(None)` if the parent spend does not create the coin as an NFT.
+async fn prove_nft(
+    peer: &Peer,
+    ctx: &mut SpendContext,
+    coin_state: &CoinState,
+) -> Result<Option<Nft>, WalletError> {
+    let parent = fetch_coin_state(peer, coin_state.coin.parent_coin_info).await?;
+    let spent_height = parent
+        .spent_height
+        .ok_or_else(|| WalletError::CoinSetError("Parent coin is not spent".to_string()))?;
+    let parent_spend = fetch_coin_spend(peer, parent.coin, spent_height).await?;
+
+    let puzzle = ctx
+        .alloc(&parent_spend.puzzle_reveal)
+        .map_err(driver_error)?;
+    let solution = ctx.alloc(&parent_spend.solution).map_err(driver_error)?;
+    let puzzle = Puzzle::parse(ctx, puzzle);
+    // Anyone can hint a coin to the wallet, so a parent that fails to parse is skipped
+    let coin_id = coin_state.coin.coin_id();
+    Ok(Nft::parse_child(ctx, parent.coin, puzzle, solution)
+        .ok()
+        .flatten()
+        .filter(|nft| nft.coin.coin_id() == coin_id))
+}
+
+impl Wallet {
+    /// Mint an NFT, returning its launcher id
+    ///
+    /// One mojo of the wallet's XCH becomes the NFT coin, and `fee` is paid on top. The
+    /// launcher id is also a coin id for [`Wallet::wait_for_confirmation`].
+    pub async fn mint_nft(
+        &self,
+        peer: &Peer,
+        options: NftMintOptions,
+        fee: u64,
+    ) -> Result<Bytes32, WalletError> {
+        check_mint_options(&options)?;
+        let config = self.get_config();
+        Self::check_fee_floor(peer, fee, config).await?;
+
+        let default_puzzle_hash = self.get_owner_puzzle_hash().await?;
+        let owner_puzzle_hash = options.owner_puzzle_hash.unwrap_or(default_puzzle_hash);
+        let royalty_puzzle_hash = options.royalty_puzzle_hash.unwrap_or(default_puzzle_hash);
+
+        let mut ctx = SpendContext::new();
+        let metadata = ctx.alloc_hashed(&options.metadata).map_err(driver_error)?;
+        let hint = ctx.hint(owner_puzzle_hash).map_err(driver_error)?;
+        let mut spends = Spends::new(default_puzzle_hash);
+        for coin in self.select_unspent_coins(peer, 1, fee, vec![]).await? {
+            spends.add(coin);
+        }
+
+        let mut actions = vec![
+            Action::mint_nft(
+                metadata,
+                NftMetadataUpdater::mod_hash().into(),
+                royalty_puzzle_hash,
+                options.royalty_basis_points,
+                1,
+            ),
+            Action::send(Id::New(0), owner_puzzle_hash, 1, hint),
+        ];
+        if fee > 0 {
+            actions.push(Action::fee(fee));
+        }
+        let deltas = spends.apply(&mut ctx, &actions).map_err(driver_error)?;
+        let outputs = self
+            .spend_keys()
+            .await?
+            .finish_spends(&mut ctx, spends, &deltas)?;
+        let launcher_id = outputs
+            .nfts
+            .get(&Id::New(0))
+            .map(|nft| nft.info.launcher_id)
+            .ok_or_else(|| WalletError::TransactionFailed("Mint created no NFT".to_string()))?;
+
+        let spend_bundle = self.sign_spend_bundle(ctx.take()).await?;
+        self.broadcast_transaction(peer, &spend_bundle).await?;
+        Ok(launcher_id)
+    }
+
+    /// List the NFTs hinted to the wallet's issued addresses and still held by one of them
+    pub async fn list_nfts(&self, peer: &Peer) -> Result<Vec<NftRecord>, WalletError> {
+        let mut ctx = SpendContext::new();
+        let nfts = self.find_nfts(peer, &mut ctx).await?;
+        Ok(nfts.iter().map(|nft| NftRecord::new(&ctx, nft)).collect())
+    }
+
+    /// Transfer the NFT `launcher_id` to an address of the wallet's chain, returning the
+    /// transaction id
+    ///
+    /// `fee` is paid from the wallet's XCH. The NFT keeps its royalty and metadata.
+    pub async fn transfer_nft(
+        &self,
+        peer: &Peer,
+        launcher_id: Bytes32,
+        to_address: &str,
+        fee: u64,
+    ) -> Result<Bytes32, WalletError> {
+        let config = self.get_config();
+        Self::check_fee_floor(peer, fee, config).await?;
+        let puzzle_hash = self.chain_profile().await?.decode_address(to_address)?;
+
+        let mut ctx = SpendContext::new();
+        let nft = self
+            .find_nfts(peer, &mut ctx)
+            .await?
+            .into_iter()
+            .find(|nft| nft.info.launcher_id == launcher_id)
+            .ok_or_else(|| WalletError::NftNotFound(hex::encode(launcher_id)))?;
+
+        let hint = ctx.hint(puzzle_hash).map_err(driver_error)?;
+        let mut spends = Spends::new(self.get_owner_puzzle_hash().await?);
+        spends.add(nft);
+        let mut actions = vec![Action::send(
+            Id::Existing(launcher_id),
+            puzzle_hash,
+            nft.coin.amount,
+            hint,
+        )];
+        if fee > 0 {
+            for coin in self.select_unspent_coins(peer, 0, fee, vec![]).await? {
+                spends.add(coin);
+            }
+            actions.push(Action::fee(fee));
+        }
+        let deltas = spends.apply(&mut ctx, &actions).map_err(driver_error)?;
+        self.spend_keys()
+            .await?
+            .finish_spends(&mut ctx, spends, &deltas)?;
+
+        let spend_bundle = self.sign_spend_bundle(ctx.take()).await?;
+        self.broadcast_transaction(peer, &spend_bundle).await
+    }
+
+    /// Unspent NFTs owned by an issued address, parsed into `ctx`
+    async fn find_nfts(
+        &self,
+        peer: &Peer,
+        ctx: &mut SpendContext,
+    ) -> Result<Vec<Nft>, WalletError> {
+        let issued = self.issued_puzzle_hashes().await?;
+        let owners: HashSet<Bytes32> = issued.iter().copied().collect();
+
+        let mut seen = HashSet::new();
+        let mut nfts = Vec::new();
+        for puzzle_hash in issued {
+            for coin_state in fetch_puzzle_history(peer, puzzle_hash).await? {
+                // NFT coins are hinted to their owner and always have an odd amount
+                let coin = coin_state.coin;
+                if coin_state.spent_height.is_some()
+                    || coin_state.created_height.is_none()
+                    || coin.puzzle_hash == puzzle_hash
+                    || coin.amount % 2 == 0
+                    || !seen.insert(coin.coin_id())
+                {
+                    continue;
+                }
+                if let Some(nft) = prove_nft(peer, ctx, &coin_state).await? {
+                    if owners.contains(&nft.info.p2_puzzle_hash) {
+                        nfts.push(nft);
+                    }
+                }
+            }
+        }
+        Ok(nfts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nft_mint_options() {
+        let royalty = Bytes32::new([4; 32]);
+        let options = NftMintOptions::new()
+            .with_data(vec!["https://example.com/a.png".to_string()], None)
+            .with_royalty(royalty, 300)
+            .with_edition(2, 5);
+        assert_eq!(options.metadata().data_uris.len(), 1);
+        assert_eq!(options.metadata().edition_number, 2);
+        assert_eq!(options.royalty_basis_points(), 300);
+        assert!(check_mint_options(&options).is_ok());
+        assert!(check_mint_options(&NftMintOptions::new().with_royalty(royalty, 10_000)).is_ok());
+
+        assert!(matches!(
+            check_mint_options(&NftMintOptions::new().with_royalty(royalty, 10_001)),
+            Err(WalletError::InvalidAmount(_))
+        ));
+    }
+}
@@ -0,0 +1,408 @@
+//! Chia offer files for trading XCH and CATs, DIG included, without a trusted party.
+//!
+//! An offer is a partial spend bundle. The maker's coins pay the offered amounts to the
+//! settlement payments puzzle and assert the puzzle announcements that only the requested
+//! payments can make, each notarized with a nonce of the offered coins. Whoever completes
+//! the bundle with those payments claims the offered coins, so the maker receives exactly
+//! what was asked or nothing. Offers travel as compressed bech32m `offer1...` text.
+//!
+//! Taking an offer pays its requested payments from the wallet's coins and claims the
+//! offered coins to the wallet, in a spend bundle joined with the maker's.
+
+use crate::asset::Asset;
+use crate::builder::TransactionBuilder;
+use crate::chain::fetch_coin_states;
+use crate::dig::DIG_COIN_ASSET_ID;
+use crate::error::WalletError;
+use crate::transfer::driver_error;
+use crate::wallet::Wallet;
+use chia::puzzles::offer::{NotarizedPayment, Payment as OfferPayment};
+use chia_wallet_sdk::driver::{
+    decode_offer, encode_offer, Action, AssetInfo, Cat, Offer, OfferAmounts, RequestedPayments,
+    SpendContext, Spends,
+};
+use chia_wallet_sdk::types::{puzzles::SettlementPayment, Mod};
+use datalayer_driver::{Bytes32, Peer};
+
+/// What an offer trades, from the maker's side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OfferSummary {
+    /// Assets the maker gives and the taker receives, in mojos
+    pub offered: Vec<(Asset, u64)>,
+    /// Assets the maker asks for and the taker pays, in mojos
+    pub requested: Vec<(Asset, u64)>,
+    /// Launcher ids of the NFTs the maker gives
+    pub offered_nfts: Vec<Bytes32>,
+    /// Launcher ids of the NFTs the maker asks for
+    pub requested_nfts: Vec<Bytes32>,
+    /// Royalties the maker pays to the creators of requested NFTs
+    pub offered_royalties: Vec<(Asset, u64)>,
+    /// Royalties the taker pays on top of `requested` to the creators of offered NFTs
+    pub requested_royalties: Vec<(Asset, u64)>,
+    /// Fee the maker's spends already pay, in XCH mojos
+    pub fee: u64,
+    /// Coins on chain the offer spends; it can be taken while all of them are unspent
+    pub coin_ids: Vec<Bytes32>,
+}
+
+/// Puzzle hash of the settlement payments puzzle, which offered coins are paid to
+pub fn settlement_puzzle_hash() -> Bytes32 {
+    SettlementPayment::mod_hash().into()
+}
+
+/// CAT asset id of `asset`, `None` for XCH
+pub(crate) fn cat_asset_id(asset: Asset) -> Option<Bytes32> {
+    match asset {
+        Asset::Xch => None,
+        Asset::Dig => Some(DIG_COIN_ASSET_ID),
+        Asset::Cat(asset_id) => Some(asset_id),
+    }
+}
+
+/// Asset of the CAT with `asset_id`
+pub(crate) fn cat_asset(asset_id: Bytes32) -> Asset {
+    if asset_id == DIG_COIN_ASSET_ID {
+        Asset::Dig
+    } else {
+        Asset::Cat(asset_id)
+    }
+}
+
+/// The non-zero amounts, XCH first
+fn asset_amounts(amounts: &OfferAmounts) -> Vec<(Asset, u64)> {
+    std::iter::once((Asset::Xch, amounts.xch))
+        .chain(
+            amounts
+                .cats
+                .iter()
+                .map(|(&asset_id, &amount)| (cat_asset(asset_id), amount)),
+        )
+        .filter(|&(_, amount)| amount > 0)
+        .collect()
+}
+
+/// Decode `offer1...` text, allocating its puzzles in `ctx`
+fn decode(ctx: &mut SpendContext, offer: &str) -> Result<Offer, WalletError> {
+    let spend_bundle = decode_offer(offer.trim())
+        .map_err(|e| WalletError::InvalidOffer(format!("Failed to decode offer: {}", e)))?;
+    Offer::from_spend_bundle(ctx, &spend_bundle)
+        .map_err(|e| WalletError::InvalidOffer(format!("Failed to parse offer: {}", e)))
+}
+
+fn summarize(offer: &Offer) -> Result<OfferSummary, WalletError> {
+    let coin_ids = offer
+        .cancellable_coin_spends()
+        .map_err(|e| WalletError::InvalidOffer(format!("Failed to run offer spends: {}", e)))?
+        .iter()
+        .map(|coin_spend| coin_spend.coin.coin_id())
+        .collect();
+    Ok(OfferSummary {
+        offered: asset_amounts(&offer.offered_coins().amounts()),
+        requested: asset_amounts(&offer.requested_payments().amounts()),
+        offered_nfts: offer.offered_coins().nfts.keys().copied().collect(),
+        requested_nfts: offer.requested_payments().nfts.keys().copied().collect(),
+        offered_royalties: asset_amounts(&offer.offered_royalty_amounts()),
+        requested_royalties: asset_amounts(&offer.requested_royalty_amounts()),
+        fee: offer.offered_coins().fee,
+        coin_ids,
+    })
+}
+
+/// Summarize `offer1...` text without contacting a peer
+///
+/// Parsing does not show that the offer can still be taken; see
+/// [`Wallet::validate_offer`].
+pub fn parse_offer(offer: &str) -> Result<OfferSummary, WalletError> {
+    let mut ctx = SpendContext::new();
+    summarize(&decode(&mut ctx, offer)?)
+}
+
+/// Fail unless every coin in `coin_ids` exists and is unspent
+async fn check_offer_coins(peer: &Peer, coin_ids: &[Bytes32]) -> Result<(), WalletError> {
+    if coin_ids.is_empty() {
+        return Err(WalletError::InvalidOffer(
+            "Offer spends no coins".to_string(),
+        ));
+    }
+    let states = fetch_coin_states(peer, coin_ids.to_vec()).await?;
+    for &coin_id in coin_ids {
+        let state = states.iter().find(|state| state.coin.coin_id() == coin_id);
+        match state {
+            Some(state) if state.spent_height.is_some() => {
+                return Err(WalletError::InvalidOffer(format!(
+                    "Coin {} is spent; the offer was taken or cancelled",
+                    hex::encode(coin_id)
+                )))
+            }
+            Some(state) if state.created_height.is_some() => {}
+            _ => {
+                return Err(WalletError::InvalidOffer(format!(
+                    "Coin {} does not exist",
+                    hex::encode(coin_id)
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sum the amounts of each asset, in order of first appearance
+fn tally(assets: &[(Asset, u64)]) -> Result<Vec<(Asset, u64)>, WalletError> {
+    let mut totals: Vec<(Asset, u64)> = Vec::new();
+    for &(asset, amount) in assets {
+        if amount == 0 {
+            return Err(WalletError::InvalidAmount(format!(
+                "Offer amount of {} is zero",
+                asset
+            )));
+        }
+        match totals.iter_mut().find(|(seen, _)| *seen == asset) {
+            Some((_, total)) => {
+                *total = total.checked_add(amount).ok_or_else(|| {
+                    WalletError::InvalidAmount(format!("Offer amount of {} overflows u64", asset))
+                })?;
+            }
+            None => totals.push((asset, amount)),
+        }
+    }
+    Ok(totals)
+}
+
+impl Wallet {
+    /// Create an offer of `offered` assets for `requested` ones, returning its `offer1...`
+    /// text
+    ///
+    /// Amounts are in mojos, and an asset listed twice counts once with the sum. Requested
+    /// assets are paid to the wallet's default address. The offered coins are spent with
+    /// change and `fee` as in a transfer, and the offer is signed, so it can be published
+    /// as it is; at most one CAT can be offered per offer. Until it is taken, the offer
+    /// stays valid for as long as its coins are unspent.
+    pub async fn create_offer(
+        &self,
+        peer: &Peer,
+        requested: Vec<(Asset, u64)>,
+        offered: Vec<(Asset, u64)>,
+        fee: u64,
+    ) -> Result<String, WalletError> {
+        let requested = tally(&requested)?;
+        let offered = tally(&offered)?;
+        if requested.is_empty() || offered.is_empty() {
+            return Err(WalletError::InvalidAmount(
+                "An offer needs both offered and requested assets".to_string(),
+            ));
+        }
+        let offered_cats = offered
+            .iter()
+            .filter(|(asset, _)| cat_asset_id(*asset).is_some())
+            .count();
+        if offered_cats > 1 {
+            return Err(WalletError::TransactionFailed(
+                "An offer can offer at most one CAT".to_string(),
+            ));
+        }
+        Self::check_fee_floor(peer, fee, self.get_config()).await?;
+
+        let settlement = settlement_puzzle_hash();
+        let mut builder = TransactionBuilder::new().with_fee(fee);
+        for &(asset, amount) in &offered {
+            builder = match asset {
+                Asset::Xch => builder.pay(settlement, amount),
+                Asset::Dig => {
+                    let cats: Vec<Cat> = self
+                        .select_unspent_dig_coins(peer, amount, vec![], false)
+                        .await?
+                        .iter()
+                        .map(|dig_coin| dig_coin.cat())
+                        .collect();
+                    builder.add_cats(cats).pay_cat(settlement, amount)
+                }
+                Asset::Cat(asset_id) => {
+                    let cats = self
+                        .select_unspent_cats(peer, asset_id, amount, vec![])
+                        .await?;
+                    builder.add_cats(cats).pay_cat(settlement, amount)
+                }
+            };
+        }
+        let mut builder = self.fund_transaction(peer, builder, vec![]).await?;
+
+        let nonce = Offer::nonce(builder.input_coin_ids());
+        let receive_puzzle_hash = self.get_owner_puzzle_hash().await?;
+        let mut ctx = SpendContext::new();
+        let mut requested_payments = RequestedPayments::new();
+        for (asset, amount) in requested {
+            let memos = ctx.hint(receive_puzzle_hash).map_err(driver_error)?;
+            let payment = NotarizedPayment::new(
+                nonce,
+                vec![OfferPayment::new(receive_puzzle_hash, amount, memos)],
+            );
+            match cat_asset_id(asset) {
+                None => requested_payments.xch.push(payment),
+                Some(asset_id) => requested_payments
+                    .cats
+                    .entry(asset_id)
+                    .or_default()
+                    .push(payment),
+            }
+        }
+        let asset_info = AssetInfo::new();
+        for assertion in requested_payments
+            .assertions(&mut ctx, &asset_info)
+            .map_err(driver_error)?
+        {
+            builder = builder.assert_puzzle_announcement(assertion.announcement_id);
+        }
+
+        let unsigned = self.build_transaction(builder).await?;
+        let spend_bundle = self.sign_spend_bundle(unsigned.coin_spends).await?;
+        let offer =
+            Offer::from_input_spend_bundle(&mut ctx, spend_bundle, requested_payments, asset_info)
+                .map_err(driver_error)?;
+        let spend_bundle = offer.to_spend_bundle(&mut ctx).map_err(driver_error)?;
+        encode_offer(&spend_bundle)
+            .map_err(|e| WalletError::SerializationError(format!("Failed to encode offer: {}", e)))
+    }
+
+    /// Summarize `offer1...` text and check with the peer that its coins are unspent
+    pub async fn validate_offer(peer: &Peer, offer: &str) -> Result<OfferSummary, WalletError> {
+        let summary = parse_offer(offer)?;
+        check_offer_coins(peer, &summary.coin_ids).await?;
+        Ok(summary)
+    }
+
+    /// Take an offer of XCH and CATs, broadcasting the completed trade and returning its
+    /// transaction id
+    ///
+    /// The wallet's coins pay the requested assets and `fee`; the offered assets and any
+    /// change go to the default address. Offers with NFTs or options are refused. The
+    /// trade is not recorded in the transaction history.
+    pub async fn take_offer(
+        &self,
+        peer: &Peer,
+        offer: &str,
+        fee: u64,
+    ) -> Result<Bytes32, WalletError> {
+        let config = self.get_config();
+        Self::check_fee_floor(peer, fee, config).await?;
+
+        let mut ctx = SpendContext::new();
+        let offer = decode(&mut ctx, offer)?;
+        let offered_coins = offer.offered_coins();
+        let requested_payments = offer.requested_payments();
+        if !offered_coins.nfts.is_empty()
+            || !offered_coins.options.is_empty()
+            || !requested_payments.nfts.is_empty()
+            || !requested_payments.options.is_empty()
+        {
+            return Err(WalletError::InvalidOffer(
+                "Only offers of XCH and CATs can be taken".to_string(),
+            ));
+        }
+        check_offer_coins(peer, &summarize(&offer)?.coin_ids).await?;
+
+        // The offered coins count towards what the wallet pays, and its coins the fee
+        let offered = offered_coins.amounts();
+        let requested = requested_payments.amounts();
+        let receive_puzzle_hash = self.get_owner_puzzle_hash().await?;
+        let mut spends = Spends::new(receive_puzzle_hash);
+        spends.add(offered_coins.clone());
+
+        let xch_needed = requested
+            .xch
+            .saturating_sub(offered.xch)
+            .saturating_add(fee);
+        if xch_needed > 0 {
+            for coin in self
+                .select_unspent_coins(peer, xch_needed, 0, vec![])
+                .await?
+            {
+                spends.add(coin);
+            }
+        }
+        for (&asset_id, &amount) in &requested.cats {
+            let needed = amount.saturating_sub(offered.cats.get(&asset_id).copied().unwrap_or(0));
+            if needed == 0 {
+                continue;
+            }
+            let cats: Vec<Cat> = match cat_asset(asset_id) {
+                Asset::Dig => self
+                    .select_unspent_dig_coins(peer, needed, vec![], false)
+                    .await?
+                    .iter()
+                    .map(|dig_coin| dig_coin.cat())
+                    .collect(),
+                _ => {
+                    self.select_unspent_cats(peer, asset_id, needed, vec![])
+                        .await?
+                }
+            };
+            for cat in cats {
+                spends.add(cat);
+            }
+        }
+
+        let mut actions = requested_payments.actions();
+        if fee > 0 {
+            actions.push(Action::fee(fee));
+        }
+        let deltas = spends.apply(&mut ctx, &actions).map_err(driver_error)?;
+        self.spend_keys()
+            .await?
+            .finish_spends(&mut ctx, spends, &deltas)?;
+
+        let spend_bundle = self.sign_spend_bundle(ctx.take()).await?;
+        self.broadcast_transaction(peer, &offer.take(spend_bundle))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tally_offer_assets() {
+        let cat = Asset::Cat(Bytes32::new([5; 32]));
+        let totals = tally(&[(Asset::Xch, 10), (cat, 3), (Asset::Xch, 5)]).unwrap();
+        assert_eq!(totals, vec![(Asset::Xch, 15), (cat, 3)]);
+        assert!(tally(&[]).unwrap().is_empty());
+        assert!(matches!(
+            tally(&[(Asset::Dig, 0)]),
+            Err(WalletError::InvalidAmount(_))
+        ));
+        assert!(matches!(
+            tally(&[(Asset::Xch, u64::MAX), (Asset::Xch, 1)]),
+            Err(WalletError::InvalidAmount(_))
+        ));
+
+        assert_eq!(cat_asset_id(Asset::Xch), None);
+        assert_eq!(cat_asset(DIG_COIN_ASSET_ID), Asset::Dig);
+        assert_eq!(
+            cat_asset_id(cat_asset(Bytes32::new([5; 32]))),
+            Some(Bytes32::new([5; 32]))
+        );
+        assert_eq!(cat_asset_id(cat), Some(Bytes32::new([5; 32])));
+        assert_eq!(
+            hex::encode(settlement_puzzle_hash()),
+            "cfbfdeed5c4ca2de3d0bf520b9cb4bb7743a359bd2e6a188d19ce7dffc21d3e7"
+        );
+    }
+
+    #[test]
+    fn test_offer_asset_amounts() {
+        let mut amounts = OfferAmounts::new();
+        amounts.xch = 1_000;
+        amounts.cats.insert(DIG_COIN_ASSET_ID, 500);
+        amounts.cats.insert(Bytes32::new([9; 32]), 0);
+        assert_eq!(
+            asset_amounts(&amounts),
+            vec![(Asset::Xch, 1_000), (Asset::Dig, 500)]
+        );
+        assert!(asset_amounts(&OfferAmounts::new()).is_empty());
+
+        assert!(matches!(
+            parse_offer("offer1notanoffer"),
+            Err(WalletError::InvalidOffer(_))
+        ));
+    }
+}
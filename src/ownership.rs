@@ -0,0 +1,813 @@
+//! Proofs that a wallet holds a key, for services that need one.
+//!
+//! A service's [`OwnershipVerifier`] issues an [`OwnershipChallenge`] naming the service,
+//! a random nonce and when the challenge is valid; [`Wallet::prove_ownership`] signs it
+//! and the verifier checks the resulting [`OwnershipProof`]. The verifier keeps no record
+//! of issued challenges: the signed message carries the audience and timestamps, and
+//! replays are caught by the verifier's [`NonceStore`].
+//!
+//! [`Challenger`] and [`Responder`] are the same handshake with the verifier id as the
+//! audience. An [`OwnershipNonce`] is the audience-free form for
+//! [`Wallet::create_key_ownership_signature`], checked against a [`NonceStore`] by
+//! [`Wallet::verify_ownership_challenge`] or [`OwnershipVerifier::verify_nonce`].
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::keys::verify_message;
+use crate::templates::{
+    MessageTemplate, MessageTemplateId, TemplateParams, OWNERSHIP_CHALLENGE_TEMPLATE,
+};
+use crate::wallet::Wallet;
+use chia::bls::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Default lifetime of an issued challenge in seconds
+pub const DEFAULT_CHALLENGE_TTL_SECS: u64 = 300;
+/// Unexpired nonces a [`MemoryNonceStore`] remembers by default
+pub const REPLAY_CACHE_CAPACITY: usize = 4096;
+/// How far a challenge may be issued ahead of the verifier's clock
+pub const CHALLENGE_CLOCK_SKEW_SECS: u64 = 30;
+/// Longest lifetime accepted for an [`OwnershipChallenge`] or [`OwnershipNonce`]
+pub const MAX_CHALLENGE_TTL_SECS: u64 = 60 * 60;
+
+/// Expiry of a challenge issued at `issued_at` and valid for `ttl_secs`
+fn challenge_expiry(issued_at: u64, ttl_secs: u64) -> Result<u64, WalletError> {
+    issued_at
+        .checked_add(ttl_secs)
+        .ok_or_else(|| WalletError::InvalidConfig(format!("Challenge TTL {} overflows", ttl_secs)))
+}
+
+/// Record of nonces already accepted, so a signature over one is only accepted once
+///
+/// A nonce only needs remembering until it expires. Verifiers running as several
+/// processes can share a store backed by a database.
+pub trait NonceStore: Send + Sync {
+    /// Record `nonce` as used until `expires_at`, returning `false` if it already was
+    fn record(&self, nonce: &str, expires_at: u64, now: u64) -> Result<bool, WalletError>;
+}
+
+/// In-memory [`NonceStore`]
+///
+/// Forgetting a nonce before it expires would let it be replayed, so once the store
+/// holds `capacity` unexpired nonces it refuses new ones with
+/// [`WalletError::NonceStoreFull`] until some expire.
+#[derive(Debug)]
+pub struct MemoryNonceStore {
+    capacity: usize,
+    used: Mutex<HashMap<String, u64>>,
+}
+
+impl MemoryNonceStore {
+    /// A store remembering up to [`REPLAY_CACHE_CAPACITY`] nonces
+    pub fn new() -> Self {
+        Self::with_capacity(REPLAY_CACHE_CAPACITY)
+    }
+
+    /// A store remembering up to `capacity` nonces
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            used: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for MemoryNonceStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NonceStore for MemoryNonceStore {
+    fn record(&self, nonce: &str, expires_at: u64, now: u64) -> Result<bool, WalletError> {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        used.retain(|_, expires_at| now <= *expires_at);
+        if used.contains_key(nonce) {
+            return Ok(false);
+        }
+
+        if used.len() >= self.capacity {
+            return Err(WalletError::NonceStoreFull(self.capacity));
+        }
+        used.insert(nonce.to_string(), expires_at);
+        Ok(true)
+    }
+}
+
+/// A challenge naming the service it is meant for and when it is valid
+///
+/// The signed message is the [`MessageTemplateId::OwnershipChallenge`] template, so a
+/// proof made for one service is refused by every other.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipChallenge {
+    /// Service the proof is meant for, e.g. `storage.example.com`
+    pub audience: String,
+    /// Random 32-byte nonce as hex
+    pub nonce: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+impl OwnershipChallenge {
+    /// A fresh challenge for `audience`, valid for `ttl_secs` by the configured clock
+    ///
+    /// `ttl_secs` may be at most [`MAX_CHALLENGE_TTL_SECS`].
+    pub fn new(audience: &str, ttl_secs: u64, config: &WalletConfig) -> Result<Self, WalletError> {
+        if ttl_secs > MAX_CHALLENGE_TTL_SECS {
+            return Err(WalletError::InvalidConfig(format!(
+                "Challenge TTL {} exceeds the maximum of {} seconds",
+                ttl_secs, MAX_CHALLENGE_TTL_SECS
+            )));
+        }
+
+        let issued_at = config.unix_timestamp();
+        let challenge = Self {
+            audience: audience.to_string(),
+            nonce: hex::encode(config.random_bytes::<32>()?),
+            issued_at,
+            expires_at: challenge_expiry(issued_at, ttl_secs)?,
+        };
+        challenge.message()?;
+        Ok(challenge)
+    }
+
+    /// The exact message signed for this challenge
+    pub fn message(&self) -> Result<String, WalletError> {
+        MessageTemplate::new(
+            MessageTemplateId::OwnershipChallenge,
+            1,
+            OWNERSHIP_CHALLENGE_TEMPLATE,
+        )?
+        .render(&TemplateParams::ownership_challenge(
+            &self.audience,
+            &self.nonce,
+            self.issued_at,
+            self.expires_at,
+        ))
+    }
+
+    /// Whether the challenge has expired at `now` (Unix seconds)
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expires_at
+    }
+
+    /// Whether a verifier whose clock reads `now` should accept the challenge's timestamps
+    ///
+    /// The timestamps travel with the proof, so besides the expiry this refuses
+    /// challenges issued more than [`CHALLENGE_CLOCK_SKEW_SECS`] in the future or valid
+    /// for longer than `max_ttl_secs`.
+    pub fn is_valid_at(&self, now: u64, max_ttl_secs: u64) -> bool {
+        !self.is_expired(now)
+            && self.issued_at <= now.saturating_add(CHALLENGE_CLOCK_SKEW_SECS)
+            && self.expires_at >= self.issued_at
+            && self.expires_at - self.issued_at <= max_ttl_secs
+    }
+}
+
+/// A self-describing, timestamped nonce for [`Wallet::create_key_ownership_signature`]
+///
+/// Unlike an [`OwnershipChallenge`], it names no audience and the verifier keeps no
+/// record of issued nonces: the expiry travels inside the nonce string and replays are
+/// caught by recording verified nonces in a [`NonceStore`] until they expire.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipNonce {
+    /// The string to sign, `<issued_at>:<expires_at>:<random hex>`
+    pub nonce: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+impl OwnershipNonce {
+    /// Parse the timestamps back out of a nonce string
+    pub fn parse(nonce: &str) -> Result<Self, WalletError> {
+        let malformed = || WalletError::CryptoError(format!("Malformed ownership nonce {}", nonce));
+        let mut parts = nonce.splitn(3, ':');
+        let issued_at = parts
+            .next()
+            .and_then(|p| p.parse().ok())
+            .ok_or_else(malformed)?;
+        let expires_at = parts
+            .next()
+            .and_then(|p| p.parse().ok())
+            .ok_or_else(malformed)?;
+        match parts.next() {
+            Some(random) if !random.is_empty() && hex::decode(random).is_ok() => {}
+            _ => return Err(malformed()),
+        }
+
+        Ok(Self {
+            nonce: nonce.to_string(),
+            issued_at,
+            expires_at,
+        })
+    }
+
+    /// Whether the nonce has expired at `now` (Unix seconds)
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expires_at
+    }
+
+    /// Whether a verifier whose clock reads `now` should accept the nonce's timestamps
+    ///
+    /// The timestamps are chosen by whoever made the nonce, so besides the expiry this
+    /// refuses nonces issued more than [`CHALLENGE_CLOCK_SKEW_SECS`] in the future or
+    /// valid for longer than [`MAX_CHALLENGE_TTL_SECS`].
+    pub fn is_valid_at(&self, now: u64) -> bool {
+        !self.is_expired(now)
+            && self.issued_at <= now.saturating_add(CHALLENGE_CLOCK_SKEW_SECS)
+            && self.expires_at >= self.issued_at
+            && self.expires_at - self.issued_at <= MAX_CHALLENGE_TTL_SECS
+    }
+}
+
+/// A wallet's signature over an [`OwnershipChallenge`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipProof {
+    pub challenge: OwnershipChallenge,
+    /// Synthetic public key as hex
+    pub public_key: String,
+    /// Signature over [`OwnershipChallenge::message`] as hex
+    pub signature: String,
+}
+
+/// Verifier of [`OwnershipProof`]s addressed to one service
+///
+/// Each nonce is accepted once: the verifier records it in its [`NonceStore`], which
+/// is a [`MemoryNonceStore`] unless one shared by several verifiers is supplied.
+pub struct OwnershipVerifier {
+    audience: String,
+    max_ttl_secs: u64,
+    config: WalletConfig,
+    nonces: Arc<dyn NonceStore>,
+}
+
+impl fmt::Debug for OwnershipVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnershipVerifier")
+            .field("audience", &self.audience)
+            .field("max_ttl_secs", &self.max_ttl_secs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OwnershipVerifier {
+    /// Verify proofs for `audience`, remembering used nonces in memory
+    pub fn new(audience: impl Into<String>) -> Self {
+        Self {
+            audience: audience.into(),
+            max_ttl_secs: DEFAULT_CHALLENGE_TTL_SECS,
+            config: WalletConfig::default(),
+            nonces: Arc::new(MemoryNonceStore::new()),
+        }
+    }
+
+    /// Issue challenges valid for `max_ttl_secs` and refuse longer-lived ones
+    ///
+    /// Capped at [`MAX_CHALLENGE_TTL_SECS`].
+    pub fn with_max_ttl(mut self, max_ttl_secs: u64) -> Self {
+        self.max_ttl_secs = max_ttl_secs.min(MAX_CHALLENGE_TTL_SECS);
+        self
+    }
+
+    /// Use the clock and entropy source of the given configuration
+    pub fn with_config(mut self, config: WalletConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Remember used nonces in `nonces`, e.g. one shared by several verifiers
+    pub fn with_nonce_store(mut self, nonces: Arc<dyn NonceStore>) -> Self {
+        self.nonces = nonces;
+        self
+    }
+
+    /// A fresh challenge for this verifier's audience, valid for its maximum lifetime
+    pub fn issue(&self) -> Result<OwnershipChallenge, WalletError> {
+        OwnershipChallenge::new(&self.audience, self.max_ttl_secs, &self.config)
+    }
+
+    /// Verify a proof, recording its nonce
+    ///
+    /// Returns `Ok(false)` for proofs addressed to another audience, for expired,
+    /// future-dated, overly long-lived or replayed challenges and for bad signatures;
+    /// malformed keys or signatures are errors. Nonces are only recorded once the
+    /// signature verifies, so forged proofs cannot use them up.
+    pub async fn verify(&self, proof: &OwnershipProof) -> Result<bool, WalletError> {
+        let challenge = &proof.challenge;
+        let now = self.config.unix_timestamp();
+        if challenge.audience != self.audience || !challenge.is_valid_at(now, self.max_ttl_secs) {
+            return Ok(false);
+        }
+
+        let public_key: [u8; 48] = hex::decode(&proof.public_key)
+            .map_err(|e| WalletError::CryptoError(e.to_string()))?
+            .try_into()
+            .map_err(|_| WalletError::CryptoError("Invalid public key length".to_string()))?;
+        let public_key = PublicKey::from_bytes(&public_key)
+            .map_err(|e| WalletError::CryptoError(e.to_string()))?;
+        let signature: [u8; 96] = hex::decode(&proof.signature)
+            .map_err(|e| WalletError::CryptoError(e.to_string()))?
+            .try_into()
+            .map_err(|_| WalletError::CryptoError("Invalid signature length".to_string()))?;
+        let signature = Signature::from_bytes(&signature)
+            .map_err(|e| WalletError::CryptoError(e.to_string()))?;
+        if !verify_message(challenge.message()?.as_bytes(), &public_key, &signature) {
+            return Ok(false);
+        }
+
+        self.nonces.record(
+            &format!("{}:{}", challenge.audience, challenge.nonce),
+            challenge.expires_at,
+            now,
+        )
+    }
+
+    /// Verify a signature over an [`OwnershipNonce`], recording the nonce in this
+    /// verifier's store
+    ///
+    /// See [`Wallet::verify_ownership_challenge`].
+    pub async fn verify_nonce(
+        &self,
+        nonce: &str,
+        signature: &str,
+        public_key: &str,
+    ) -> Result<bool, WalletError> {
+        Wallet::verify_ownership_challenge_with_config(
+            nonce,
+            signature,
+            public_key,
+            self.nonces.as_ref(),
+            &self.config,
+        )
+        .await
+    }
+}
+
+/// A wallet's signed answer to a [`Challenger`]'s challenge
+pub type OwnershipResponse = OwnershipProof;
+
+/// Verifier side of the ownership handshake
+///
+/// Wraps an [`OwnershipVerifier`] whose audience is the verifier id, so a response
+/// made for one verifier is refused by every other and each challenge is answered once.
+#[derive(Debug)]
+pub struct Challenger {
+    verifier: OwnershipVerifier,
+}
+
+impl Challenger {
+    /// Create a challenger identified by `verifier_id`
+    pub fn new(verifier_id: impl Into<String>) -> Self {
+        Self {
+            verifier: OwnershipVerifier::new(verifier_id),
+        }
+    }
+
+    /// Set how long issued challenges stay valid, at most [`MAX_CHALLENGE_TTL_SECS`]
+    pub fn with_ttl(self, ttl_secs: u64) -> Self {
+        Self {
+            verifier: self.verifier.with_max_ttl(ttl_secs),
+        }
+    }
+
+    /// Use the clock and entropy source of the given configuration
+    pub fn with_config(self, config: WalletConfig) -> Self {
+        Self {
+            verifier: self.verifier.with_config(config),
+        }
+    }
+
+    /// Remember answered challenges in `nonces`, e.g. one shared by several challengers
+    pub fn with_nonce_store(self, nonces: Arc<dyn NonceStore>) -> Self {
+        Self {
+            verifier: self.verifier.with_nonce_store(nonces),
+        }
+    }
+
+    /// Issue a fresh challenge naming this verifier
+    pub fn issue(&self) -> Result<OwnershipChallenge, WalletError> {
+        self.verifier.issue()
+    }
+
+    /// Verify a response, accepting each challenge once
+    ///
+    /// Returns `Ok(false)` for challenges naming another verifier, for expired,
+    /// replayed or altered challenges and for bad signatures; malformed keys or
+    /// signatures are errors.
+    pub async fn verify(&self, response: &OwnershipResponse) -> Result<bool, WalletError> {
+        self.verifier.verify(response).await
+    }
+}
+
+/// Wallet side of the ownership handshake
+#[derive(Debug)]
+pub struct Responder<'a> {
+    wallet: &'a Wallet,
+}
+
+impl<'a> Responder<'a> {
+    /// Answer challenges with the given wallet's synthetic key
+    pub fn new(wallet: &'a Wallet) -> Self {
+        Self { wallet }
+    }
+
+    /// Sign a challenge, refusing ones that have already expired
+    pub async fn respond(
+        &self,
+        challenge: &OwnershipChallenge,
+    ) -> Result<OwnershipResponse, WalletError> {
+        self.wallet.prove_ownership(challenge).await
+    }
+}
+
+impl Wallet {
+    /// Issue a random nonce valid for [`DEFAULT_CHALLENGE_TTL_SECS`]
+    pub fn new_ownership_challenge(&self) -> Result<OwnershipNonce, WalletError> {
+        self.new_ownership_challenge_with_ttl(DEFAULT_CHALLENGE_TTL_SECS)
+    }
+
+    /// Issue a random nonce valid for `ttl_secs`, at most [`MAX_CHALLENGE_TTL_SECS`]
+    pub fn new_ownership_challenge_with_ttl(
+        &self,
+        ttl_secs: u64,
+    ) -> Result<OwnershipNonce, WalletError> {
+        if ttl_secs > MAX_CHALLENGE_TTL_SECS {
+            return Err(WalletError::InvalidConfig(format!(
+                "Challenge TTL {} exceeds the maximum of {} seconds",
+                ttl_secs, MAX_CHALLENGE_TTL_SECS
+            )));
+        }
+
+        let config = self.get_config();
+        let random = config.random_bytes::<32>()?;
+        let issued_at = config.unix_timestamp();
+        let expires_at = challenge_expiry(issued_at, ttl_secs)?;
+
+        Ok(OwnershipNonce {
+            nonce: format!("{}:{}:{}", issued_at, expires_at, hex::encode(random)),
+            issued_at,
+            expires_at,
+        })
+    }
+
+    /// Verify a [`Wallet::create_key_ownership_signature`] over an [`OwnershipNonce`],
+    /// accepting each nonce once
+    ///
+    /// Verified nonces are recorded in `nonces` until they expire, so the caller keeps
+    /// one store per verifier (or one shared by several). Returns `Ok(false)` for
+    /// expired, future-dated, overly long-lived or replayed nonces and for bad
+    /// signatures.
+    pub async fn verify_ownership_challenge(
+        nonce: &str,
+        signature: &str,
+        public_key: &str,
+        nonces: &dyn NonceStore,
+    ) -> Result<bool, WalletError> {
+        Self::verify_ownership_challenge_with_config(
+            nonce,
+            signature,
+            public_key,
+            nonces,
+            &WalletConfig::default(),
+        )
+        .await
+    }
+
+    /// Verify a signature over an [`OwnershipNonce`] against the configured clock
+    pub async fn verify_ownership_challenge_with_config(
+        nonce: &str,
+        signature: &str,
+        public_key: &str,
+        nonces: &dyn NonceStore,
+        config: &WalletConfig,
+    ) -> Result<bool, WalletError> {
+        let nonce = OwnershipNonce::parse(nonce)?;
+        let now = config.unix_timestamp();
+        if !nonce.is_valid_at(now) {
+            return Ok(false);
+        }
+
+        if !Self::verify_key_ownership_signature(&nonce.nonce, signature, public_key).await? {
+            return Ok(false);
+        }
+
+        nonces.record(&nonce.nonce, nonce.expires_at, now)
+    }
+
+    /// Sign `challenge` with the synthetic key, refusing ones that have already expired
+    pub async fn prove_ownership(
+        &self,
+        challenge: &OwnershipChallenge,
+    ) -> Result<OwnershipProof, WalletError> {
+        if challenge.is_expired(self.get_config().unix_timestamp()) {
+            return Err(WalletError::CryptoError("Challenge expired".to_string()));
+        }
+
+        let (public_key, signature) = self
+            .sign_with_synthetic_key(challenge.message()?.as_bytes())
+            .await?;
+
+        Ok(OwnershipProof {
+            challenge: challenge.clone(),
+            public_key: hex::encode(public_key.to_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+    fn config_at(home: &std::path::Path, secs: u64) -> WalletConfig {
+        WalletConfig::new().with_environment(
+            SandboxEnvironment::new(home).with_fixed_time(UNIX_EPOCH + Duration::from_secs(secs)),
+        )
+    }
+
+    async fn test_wallet(config: &WalletConfig) -> Wallet {
+        Wallet::import_wallet_with_config("prover", Some(TEST_MNEMONIC), config)
+            .await
+            .unwrap();
+        Wallet::load_with_config(Some("prover".to_string()), false, config)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_challenge_response_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config_at(temp_dir.path(), 1_000);
+        let wallet = test_wallet(&config).await;
+
+        let challenger = Challenger::new("node-1").with_config(config.clone());
+        let challenge = challenger.issue().unwrap();
+        assert_eq!(challenge.audience, "node-1");
+        assert_eq!(challenge.expires_at, 1_000 + DEFAULT_CHALLENGE_TTL_SECS);
+
+        let response = Responder::new(&wallet).respond(&challenge).await.unwrap();
+        assert!(challenger.verify(&response).await.unwrap());
+
+        // Challenges are single use
+        assert!(!challenger.verify(&response).await.unwrap());
+
+        // A response re-bound to another verifier does not verify there or here
+        let other = Challenger::new("node-2").with_config(config.clone());
+        let challenge = challenger.issue().unwrap();
+        let mut forged = Responder::new(&wallet).respond(&challenge).await.unwrap();
+        assert!(!other.verify(&forged).await.unwrap());
+        forged.challenge.audience = "node-2".to_string();
+        assert!(!other.verify(&forged).await.unwrap());
+        assert!(!challenger.verify(&forged).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_expired_challenge_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let late_config = config_at(temp_dir.path(), 2_000);
+        let wallet = test_wallet(&late_config).await;
+
+        let challenge = Challenger::new("node-1")
+            .with_ttl(60)
+            .with_config(config_at(temp_dir.path(), 1_000))
+            .issue()
+            .unwrap();
+        assert_eq!(challenge.expires_at, 1_060);
+        assert!(Responder::new(&wallet).respond(&challenge).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proofs_are_bound_to_audience_and_single_use() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config_at(temp_dir.path(), 10_000);
+        let wallet = test_wallet(&config).await;
+
+        let nonces: Arc<dyn NonceStore> = Arc::new(MemoryNonceStore::new());
+        let verifier = OwnershipVerifier::new("storage.example.com")
+            .with_config(config.clone())
+            .with_nonce_store(nonces.clone());
+        let challenge = verifier.issue().unwrap();
+        assert_eq!(challenge.expires_at, 10_000 + DEFAULT_CHALLENGE_TTL_SECS);
+        assert!(challenge
+            .message()
+            .unwrap()
+            .contains("audience: storage.example.com"));
+
+        let proof = wallet.prove_ownership(&challenge).await.unwrap();
+        assert!(verifier.verify(&proof).await.unwrap());
+        assert!(!verifier.verify(&proof).await.unwrap());
+
+        // A second verifier sharing the store also refuses the replay
+        let replica = OwnershipVerifier::new("storage.example.com")
+            .with_config(config.clone())
+            .with_nonce_store(nonces);
+        assert!(!replica.verify(&proof).await.unwrap());
+
+        // Another service refuses a proof made for this one, even re-addressed
+        let other = OwnershipVerifier::new("auth.example.com").with_config(config.clone());
+        let challenge = verifier.issue().unwrap();
+        let proof = wallet.prove_ownership(&challenge).await.unwrap();
+        assert!(!other.verify(&proof).await.unwrap());
+        let mut readdressed = proof.clone();
+        readdressed.challenge.audience = "auth.example.com".to_string();
+        assert!(!other.verify(&readdressed).await.unwrap());
+
+        assert!(OwnershipChallenge::new("bad\naudience", 60, &config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expired_future_dated_and_long_lived_challenges_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config_at(temp_dir.path(), 5_000);
+        let wallet = test_wallet(&config).await;
+        let verifier = OwnershipVerifier::new("storage.example.com").with_config(config.clone());
+
+        let expired = OwnershipChallenge::new(
+            "storage.example.com",
+            60,
+            &config_at(temp_dir.path(), 1_000),
+        )
+        .unwrap();
+        assert!(wallet.prove_ownership(&expired).await.is_err());
+
+        let challenge = verifier.issue().unwrap();
+        let proof = wallet.prove_ownership(&challenge).await.unwrap();
+        let late = OwnershipVerifier::new("storage.example.com")
+            .with_config(config_at(temp_dir.path(), challenge.expires_at + 1));
+        assert!(!late.verify(&proof).await.unwrap());
+
+        // Timestamps are chosen by the prover, so never-expiring, future-dated and
+        // long-lived challenges are refused even when correctly signed
+        for (issued_at, expires_at) in [
+            (5_000, u64::MAX),
+            (5_000 + 3_600, 5_000 + 3_900),
+            (5_000, 5_000 + MAX_CHALLENGE_TTL_SECS),
+        ] {
+            let mut challenge = verifier.issue().unwrap();
+            challenge.issued_at = issued_at;
+            challenge.expires_at = expires_at;
+            let proof = wallet.prove_ownership(&challenge).await.unwrap();
+            assert!(!verifier.verify(&proof).await.unwrap());
+        }
+        assert!(OwnershipChallenge::new(
+            "storage.example.com",
+            MAX_CHALLENGE_TTL_SECS + 1,
+            &config
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ownership_nonce_rejects_replay_and_expiry() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config_at(temp_dir.path(), 5_000);
+        let wallet = test_wallet(&config).await;
+        let nonces = MemoryNonceStore::new();
+
+        let challenge = wallet.new_ownership_challenge().unwrap();
+        assert_eq!(OwnershipNonce::parse(&challenge.nonce).unwrap(), challenge);
+        assert_eq!(challenge.expires_at, 5_000 + DEFAULT_CHALLENGE_TTL_SECS);
+
+        let signature = wallet
+            .create_key_ownership_signature(&challenge.nonce)
+            .await
+            .unwrap();
+        let public_key = hex::encode(wallet.get_public_synthetic_key().await.unwrap().to_bytes());
+        let verify = |config: WalletConfig| {
+            let (nonce, signature, public_key, nonces) = (
+                challenge.nonce.clone(),
+                signature.clone(),
+                public_key.clone(),
+                &nonces,
+            );
+            async move {
+                Wallet::verify_ownership_challenge_with_config(
+                    &nonce,
+                    &signature,
+                    &public_key,
+                    nonces,
+                    &config,
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        let late = config_at(temp_dir.path(), challenge.expires_at + 1);
+        assert!(!verify(late).await);
+        assert!(verify(config.clone()).await);
+        assert!(!verify(config.clone()).await);
+
+        assert!(OwnershipNonce::parse("not-a-nonce").is_err());
+
+        // Timestamps are chosen by the prover, so never-expiring and future-dated
+        // nonces are refused even when correctly signed
+        for nonce in [
+            format!("5000:{}:ab", u64::MAX),
+            format!("{}:{}:ab", 5_000 + 3_600, 5_000 + 3_900),
+        ] {
+            let signature = wallet.create_key_ownership_signature(&nonce).await.unwrap();
+            assert!(!Wallet::verify_ownership_challenge_with_config(
+                &nonce,
+                &signature,
+                &public_key,
+                &nonces,
+                &config
+            )
+            .await
+            .unwrap());
+        }
+        assert!(wallet
+            .new_ownership_challenge_with_ttl(MAX_CHALLENGE_TTL_SECS + 1)
+            .is_err());
+
+        // A verifier records nonces in its own store
+        let verifier = OwnershipVerifier::new("storage.example.com").with_config(config.clone());
+        let challenge = wallet.new_ownership_challenge().unwrap();
+        let signature = wallet
+            .create_key_ownership_signature(&challenge.nonce)
+            .await
+            .unwrap();
+        assert!(verifier
+            .verify_nonce(&challenge.nonce, &signature, &public_key)
+            .await
+            .unwrap());
+        assert!(!verifier
+            .verify_nonce(&challenge.nonce, &signature, &public_key)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_one_nonce_store_serves_every_ownership_api() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config_at(temp_dir.path(), 20_000);
+        let wallet = test_wallet(&config).await;
+        let public_key = hex::encode(wallet.get_public_synthetic_key().await.unwrap().to_bytes());
+
+        let nonces: Arc<dyn NonceStore> = Arc::new(MemoryNonceStore::with_capacity(2));
+        let challenger = Challenger::new("node-1")
+            .with_config(config.clone())
+            .with_nonce_store(nonces.clone());
+        let verifier = OwnershipVerifier::new("storage.example.com")
+            .with_config(config.clone())
+            .with_nonce_store(nonces.clone());
+
+        let challenge = challenger.issue().unwrap();
+        let response = Responder::new(&wallet).respond(&challenge).await.unwrap();
+        assert!(challenger.verify(&response).await.unwrap());
+
+        let token = wallet.new_ownership_challenge().unwrap();
+        let signature = wallet
+            .create_key_ownership_signature(&token.nonce)
+            .await
+            .unwrap();
+        assert!(verifier
+            .verify_nonce(&token.nonce, &signature, &public_key)
+            .await
+            .unwrap());
+        assert!(!Wallet::verify_ownership_challenge_with_config(
+            &token.nonce,
+            &signature,
+            &public_key,
+            nonces.as_ref(),
+            &config
+        )
+        .await
+        .unwrap());
+
+        // Both APIs filled the same store
+        let challenge = verifier.issue().unwrap();
+        let proof = wallet.prove_ownership(&challenge).await.unwrap();
+        assert!(matches!(
+            verifier.verify(&proof).await,
+            Err(WalletError::NonceStoreFull(2))
+        ));
+    }
+
+    #[test]
+    fn test_memory_nonce_store_refuses_new_nonces_when_full() {
+        let store = MemoryNonceStore::with_capacity(2);
+        assert!(store.record("a", 100, 50).unwrap());
+        assert!(!store.record("a", 100, 60).unwrap());
+        assert!(store.record("b", 200, 60).unwrap());
+        assert!(matches!(
+            store.record("c", 200, 60),
+            Err(WalletError::NonceStoreFull(2))
+        ));
+        // Replays are still caught while full
+        assert!(!store.record("b", 200, 60).unwrap());
+
+        // Expired nonces are forgotten, making room again
+        assert!(store.record("c", 200, 150).unwrap());
+        assert!(store.record("a", 200, 150).is_err());
+    }
+}
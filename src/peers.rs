@@ -0,0 +1,486 @@
+use crate::config::WalletConfig;
+use crate::context::within_current;
+use crate::error::WalletError;
+use crate::pinning::{fetch_peer_certificate, verify_pinned_certificate};
+use crate::wallet::Wallet;
+use chia_wallet_sdk::client::Connector;
+use datalayer_driver::{connect_peer, connect_random, create_tls_connector, NetworkType, Peer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PEER_PORT: u16 = 8444;
+const DEFAULT_TESTNET_PEER_PORT: u16 = 58444;
+/// Upper bound on peers opened when bootstrapping from introducers
+const MAX_INTRODUCED_PEERS: usize = 3;
+
+/// A named connection environment from `peers.toml`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerEnvironment {
+    /// `mainnet` or `testnet11`
+    pub network: String,
+    /// DNS introducer hosts resolving to full node addresses
+    #[serde(default)]
+    pub introducers: Vec<String>,
+    /// Full nodes to connect to directly, as `host` or `host:port`
+    #[serde(default)]
+    pub trusted_nodes: Vec<String>,
+    /// Port used for introduced peers and trusted nodes without an explicit port
+    pub port: Option<u16>,
+    /// SSL certificate; defaults to the Chia wallet certificate under the home directory
+    pub ssl_cert: Option<PathBuf>,
+    /// SSL key; defaults to the Chia wallet key under the home directory
+    pub ssl_key: Option<PathBuf>,
+    /// SHA-256 fingerprints of the DER certificates trusted nodes may present
+    #[serde(default)]
+    pub pinned_certificates: Vec<String>,
+    /// Refuse trusted nodes that cannot be verified against `pinned_certificates`,
+    /// and never fall back to unverifiable introduced peers
+    #[serde(default)]
+    pub require_verified_certificates: bool,
+}
+
+impl PeerEnvironment {
+    /// The network this environment connects to
+    pub fn network_type(&self) -> Result<NetworkType, WalletError> {
+        match self.network.as_str() {
+            "mainnet" => Ok(NetworkType::Mainnet),
+            "testnet11" => Ok(NetworkType::Testnet11),
+            other => Err(WalletError::InvalidConfig(format!(
+                "Unknown network: {}",
+                other
+            ))),
+        }
+    }
+
+    /// The default port for this environment
+    pub fn port(&self) -> Result<u16, WalletError> {
+        Ok(match self.port {
+            Some(port) => port,
+            None => match self.network_type()? {
+                NetworkType::Mainnet => DEFAULT_PEER_PORT,
+                NetworkType::Testnet11 => DEFAULT_TESTNET_PEER_PORT,
+            },
+        })
+    }
+
+    /// Resolve the SSL certificate and key paths for this environment
+    pub fn ssl_paths(&self, config: &WalletConfig) -> Result<(PathBuf, PathBuf), WalletError> {
+        let ssl_dir = config.chia_ssl_dir(self.network_type()?)?;
+        let cert = self
+            .ssl_cert
+            .clone()
+            .unwrap_or_else(|| ssl_dir.join("wallet_node.crt"));
+        let key = self
+            .ssl_key
+            .clone()
+            .unwrap_or_else(|| ssl_dir.join("wallet_node.key"));
+        Ok((cert, key))
+    }
+
+    /// Check the certificate of a trusted node against the pins, if any are configured
+    async fn verify_trusted_node(
+        &self,
+        addr: SocketAddr,
+        cert: &Path,
+        key: &Path,
+    ) -> Result<(), WalletError> {
+        if self.pinned_certificates.is_empty() {
+            return Ok(());
+        }
+        let der = fetch_peer_certificate(addr, cert, key).await?;
+        verify_pinned_certificate(addr, &der, &self.pinned_certificates)
+    }
+}
+
+/// Contents of `peers.toml`: named environments keyed by name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeersFile {
+    #[serde(default)]
+    pub environments: BTreeMap<String, PeerEnvironment>,
+}
+
+impl Default for PeersFile {
+    fn default() -> Self {
+        let mut environments = BTreeMap::new();
+        environments.insert(
+            "mainnet".to_string(),
+            PeerEnvironment {
+                network: "mainnet".to_string(),
+                introducers: vec!["dns-introducer.chia.net".to_string()],
+                trusted_nodes: vec![],
+                port: None,
+                ssl_cert: None,
+                ssl_key: None,
+                pinned_certificates: vec![],
+                require_verified_certificates: false,
+            },
+        );
+        environments.insert(
+            "testnet11".to_string(),
+            PeerEnvironment {
+                network: "testnet11".to_string(),
+                introducers: vec!["dns-introducer-testnet11.chia.net".to_string()],
+                trusted_nodes: vec![],
+                port: None,
+                ssl_cert: None,
+                ssl_key: None,
+                pinned_certificates: vec![],
+                require_verified_certificates: false,
+            },
+        );
+        Self { environments }
+    }
+}
+
+impl PeersFile {
+    /// Parse a `peers.toml` document
+    pub fn parse(contents: &str) -> Result<Self, WalletError> {
+        toml::from_str(contents)
+            .map_err(|e| WalletError::InvalidConfig(format!("Invalid peers.toml: {}", e)))
+    }
+
+    /// Load `peers.toml` from a path
+    pub fn load(path: &Path) -> Result<Self, WalletError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            WalletError::FileSystemError(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        Self::parse(&contents)
+    }
+
+    /// Load the configured `peers.toml`, falling back to the built-in environments when absent
+    pub fn load_or_default(config: &WalletConfig) -> Result<Self, WalletError> {
+        let path = config.peers_path()?;
+        if path.exists() {
+            Self::load(&path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Look up an environment by name
+    pub fn environment(&self, name: &str) -> Result<&PeerEnvironment, WalletError> {
+        self.environments.get(name).ok_or_else(|| {
+            WalletError::InvalidConfig(format!("Unknown peer environment: {}", name))
+        })
+    }
+}
+
+/// A set of connected peers for one named environment
+#[derive(Debug, Clone)]
+pub struct PeerPool {
+    environment_name: String,
+    network: NetworkType,
+    peers: Vec<Peer>,
+}
+
+impl PeerPool {
+    /// Name of the environment this pool was bootstrapped from
+    pub fn environment_name(&self) -> &str {
+        &self.environment_name
+    }
+
+    /// The network the pooled peers belong to
+    pub fn network(&self) -> NetworkType {
+        self.network
+    }
+
+    /// All connected peers
+    pub fn peers(&self) -> &[Peer] {
+        &self.peers
+    }
+
+    /// The preferred peer; trusted nodes come first
+    pub fn peer(&self) -> &Peer {
+        &self.peers[0]
+    }
+
+    /// Number of connected peers
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Whether the pool holds no peers; never true for a bootstrapped pool
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+/// Open a connection to a single full node
+async fn connect_to(
+    network: NetworkType,
+    tls_connector: &Connector,
+    addr: SocketAddr,
+) -> Result<Peer, WalletError> {
+    connect_peer(network, tls_connector.clone(), addr)
+        .await
+        .map_err(|e| WalletError::NetworkError(e.to_string()))
+}
+
+/// Resolve `host` or `host:port` to socket addresses
+async fn resolve_host(host: &str, default_port: u16) -> Result<Vec<SocketAddr>, WalletError> {
+    let target = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:{}", host, default_port)
+    };
+
+    tokio::net::lookup_host(&target)
+        .await
+        .map(|addrs| addrs.collect())
+        .map_err(|e| WalletError::NetworkError(format!("Failed to resolve {}: {}", target, e)))
+}
+
+impl Wallet {
+    /// Connect to a named environment from `~/.dig/peers.toml`
+    pub async fn connect(environment_name: &str) -> Result<PeerPool, WalletError> {
+        Self::connect_with_config(environment_name, &WalletConfig::default()).await
+    }
+
+    /// Connect to a named environment from the configured `peers.toml`
+    ///
+    /// Trusted nodes are connected first; introducers are only consulted when
+    /// no trusted node is reachable and the environment does not require verified
+    /// certificates. A trusted node presenting an unpinned certificate aborts the
+    /// connection with [`WalletError::PeerCertificateMismatch`].
+    pub async fn connect_with_config(
+        environment_name: &str,
+        config: &WalletConfig,
+    ) -> Result<PeerPool, WalletError> {
+        let peers_file = PeersFile::load_or_default(config)?;
+        let environment = peers_file.environment(environment_name)?;
+        Self::connect_environment(environment_name, environment, config).await
+    }
+
+    /// Connect to a named environment of the wallet's configuration for this wallet's chain
+    ///
+    /// Fails with [`WalletError::ChainMismatch`] if the environment follows another network;
+    /// nodes and introducers without an explicit port use the chain's peer port unless the
+    /// environment sets one.
+    pub async fn connect_chain(&self, environment_name: &str) -> Result<PeerPool, WalletError> {
+        let chain = self.chain_profile().await?;
+        let peers_file = PeersFile::load_or_default(self.get_config())?;
+        let mut environment = peers_file.environment(environment_name)?.clone();
+        if environment.network != chain.network.as_str() {
+            return Err(WalletError::ChainMismatch {
+                expected: chain.network.as_str().to_string(),
+                found: environment.network,
+            });
+        }
+        environment.port.get_or_insert(chain.peer_port);
+        Self::connect_environment(environment_name, &environment, self.get_config()).await
+    }
+
+    async fn connect_environment(
+        environment_name: &str,
+        environment: &PeerEnvironment,
+        config: &WalletConfig,
+    ) -> Result<PeerPool, WalletError> {
+        let network = environment.network_type()?;
+        let port = environment.port()?;
+        let (cert_path, key_path) = environment.ssl_paths(config)?;
+        let cert = cert_path
+            .to_str()
+            .ok_or_else(|| WalletError::FileSystemError("Invalid cert path".to_string()))?;
+        let key = key_path
+            .to_str()
+            .ok_or_else(|| WalletError::FileSystemError("Invalid key path".to_string()))?;
+        let tls_connector = create_tls_connector(cert, key).map_err(|e| {
+            WalletError::NetworkError(format!("Failed to load peer TLS identity: {}", e))
+        })?;
+
+        if environment.require_verified_certificates && environment.pinned_certificates.is_empty() {
+            return Err(WalletError::InvalidConfig(format!(
+                "Environment {} requires verified certificates but pins none",
+                environment_name
+            )));
+        }
+
+        let mut peers = Vec::new();
+        let mut last_error = None;
+
+        for node in &environment.trusted_nodes {
+            match resolve_host(node, port).await {
+                Ok(addrs) => {
+                    for addr in addrs.into_iter().take(1) {
+                        // A pin mismatch means a possible MITM: abort instead of trying the next node
+                        match environment
+                            .verify_trusted_node(addr, &cert_path, &key_path)
+                            .await
+                        {
+                            Err(e @ WalletError::PeerCertificateMismatch { .. }) => return Err(e),
+                            Err(e) => {
+                                last_error = Some(e.to_string());
+                                continue;
+                            }
+                            Ok(()) => {}
+                        }
+                        match within_current(connect_to(network, &tls_connector, addr)).await {
+                            Ok(peer) => peers.push(peer),
+                            Err(e @ WalletError::DeadlineExceeded { .. }) => return Err(e),
+                            Err(e @ WalletError::Cancelled) => return Err(e),
+                            Err(e) => last_error = Some(e.to_string()),
+                        }
+                    }
+                }
+                Err(e) => last_error = Some(e.to_string()),
+            }
+        }
+
+        if peers.is_empty() && !environment.require_verified_certificates {
+            'introducers: for introducer in &environment.introducers {
+                let addrs = match resolve_host(introducer, port).await {
+                    Ok(addrs) => addrs,
+                    Err(e) => {
+                        last_error = Some(e.to_string());
+                        continue;
+                    }
+                };
+                for addr in addrs {
+                    match within_current(connect_to(network, &tls_connector, addr)).await {
+                        Ok(peer) => peers.push(peer),
+                        Err(e @ WalletError::DeadlineExceeded { .. }) => return Err(e),
+                        Err(e @ WalletError::Cancelled) => return Err(e),
+                        Err(e) => last_error = Some(e.to_string()),
+                    }
+                    if peers.len() >= MAX_INTRODUCED_PEERS {
+                        break 'introducers;
+                    }
+                }
+            }
+        }
+
+        if peers.is_empty() {
+            return Err(WalletError::NetworkError(format!(
+                "Failed to connect to any peer in environment {}: {}",
+                environment_name,
+                last_error
+                    .unwrap_or_else(|| "no trusted nodes or introducers configured".to_string())
+            )));
+        }
+
+        Ok(PeerPool {
+            environment_name: environment_name.to_string(),
+            network,
+            peers,
+        })
+    }
+
+    /// Connect to a random peer on the specified network
+    pub async fn connect_random_peer(
+        network: NetworkType,
+        cert_path: &str,
+        key_path: &str,
+    ) -> Result<Peer, WalletError> {
+        within_current(async {
+            connect_random(network, cert_path, key_path)
+                .await
+                .map_err(|e| WalletError::NetworkError(format!("Failed to connect to peer: {}", e)))
+        })
+        .await
+    }
+
+    /// Connect to a random mainnet peer using default Chia SSL paths
+    pub async fn connect_mainnet_peer() -> Result<Peer, WalletError> {
+        Self::connect_default_peer(NetworkType::Mainnet, &WalletConfig::default()).await
+    }
+
+    /// Connect to a random testnet peer using default Chia SSL paths
+    pub async fn connect_testnet_peer() -> Result<Peer, WalletError> {
+        Self::connect_default_peer(NetworkType::Testnet11, &WalletConfig::default()).await
+    }
+
+    /// Connect to a random peer using the SSL paths of the network's `peers.toml` environment
+    pub async fn connect_default_peer(
+        network: NetworkType,
+        config: &WalletConfig,
+    ) -> Result<Peer, WalletError> {
+        let environment_name = match network {
+            NetworkType::Mainnet => "mainnet",
+            NetworkType::Testnet11 => "testnet11",
+        };
+        let peers_file = PeersFile::load_or_default(config)?;
+        let (cert_path, key_path) = match peers_file.environment(environment_name) {
+            Ok(environment) => environment.ssl_paths(config)?,
+            Err(_) => {
+                let ssl_dir = config.chia_ssl_dir(network)?;
+                (
+                    ssl_dir.join("wallet_node.crt"),
+                    ssl_dir.join("wallet_node.key"),
+                )
+            }
+        };
+
+        Self::connect_random_peer(
+            network,
+            cert_path
+                .to_str()
+                .ok_or_else(|| WalletError::FileSystemError("Invalid cert path".to_string()))?,
+            key_path
+                .to_str()
+                .ok_or_else(|| WalletError::FileSystemError("Invalid key path".to_string()))?,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_peers_file() {
+        let peers_file = PeersFile::parse(
+            r#"
+            [environments.staging]
+            network = "testnet11"
+            trusted_nodes = ["10.0.0.5", "10.0.0.6:9000"]
+            port = 18444
+            ssl_cert = "/certs/wallet.crt"
+            ssl_key = "/certs/wallet.key"
+            "#,
+        )
+        .unwrap();
+
+        let staging = peers_file.environment("staging").unwrap();
+        assert_eq!(staging.network_type().unwrap(), NetworkType::Testnet11);
+        assert_eq!(staging.port().unwrap(), 18444);
+        assert!(staging.introducers.is_empty());
+
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new("/home/dig"));
+        let (cert, key) = staging.ssl_paths(&config).unwrap();
+        assert_eq!(cert, PathBuf::from("/certs/wallet.crt"));
+        assert_eq!(key, PathBuf::from("/certs/wallet.key"));
+
+        assert!(matches!(
+            peers_file.environment("mainnet"),
+            Err(WalletError::InvalidConfig(_))
+        ));
+        assert!(PeersFile::parse("[environments.bad]\nnetwork = \"moon\"")
+            .unwrap()
+            .environment("bad")
+            .unwrap()
+            .network_type()
+            .is_err());
+    }
+
+    #[test]
+    fn test_default_environments_when_file_missing() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+
+        let peers_file = PeersFile::load_or_default(&config).unwrap();
+        let mainnet = peers_file.environment("mainnet").unwrap();
+        assert_eq!(mainnet.port().unwrap(), DEFAULT_PEER_PORT);
+        assert_eq!(
+            mainnet.ssl_paths(&config).unwrap().0,
+            temp_dir
+                .path()
+                .join(".chia/mainnet/config/ssl/wallet/wallet_node.crt")
+        );
+        assert!(peers_file.environment("testnet11").is_ok());
+    }
+}
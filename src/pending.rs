@@ -0,0 +1,389 @@
+//! Spend bundles broadcast but not yet confirmed.
+//!
+//! Every bundle the wallet broadcasts is recorded here with the coins it consumes and
+//! creates. Until it confirms, its coins are left out of coin listings, so balances and coin
+//! selection do not count them twice. [`Wallet::sync_pending_transactions`] follows each
+//! bundle on a peer: confirmed ones are kept until buried [`PENDING_CONFIRMATION_DEPTH`]
+//! blocks deep, so a reorg puts them back in flight, and ones whose coins were spent by
+//! another bundle are marked failed. A bundle evicted from the mempool stays pending until it
+//! is rebroadcast with [`Wallet::rebroadcast_pending`] or given up with
+//! [`PendingTransactions::mark_failed`].
+
+use crate::chain::{fetch_coin_states, fetch_peak_height};
+use crate::coin_proof::SerializedCoin;
+use crate::coin_records::run_coin_spend;
+use crate::config::WalletConfig;
+use crate::encoding::bytes_from_hex;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::wallet::Wallet;
+use chia::traits::Streamable;
+use chia_wallet_sdk::driver::SpendContext;
+use chia_wallet_sdk::types::Condition;
+use datalayer_driver::{Bytes32, Coin, Peer, SpendBundle};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Blocks a confirmed bundle is kept for, in case a reorg drops it from the chain
+pub const PENDING_CONFIRMATION_DEPTH: u32 = 32;
+
+/// Lifecycle of a broadcast spend bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PendingStatus {
+    /// Broadcast and not seen on chain, its coins are in flight
+    Pending,
+    /// Included in a block that is not yet buried
+    Confirmed,
+    /// Given up on, or its coins were spent by another bundle
+    Failed,
+}
+
+/// A spend bundle the wallet broadcast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingTransaction {
+    /// Spend bundle name as hex
+    pub tx_id: String,
+    pub spent_coins: Vec<SerializedCoin>,
+    /// Coins the bundle creates, without those it also spends
+    pub created_coins: Vec<SerializedCoin>,
+    /// Serialized spend bundle as hex
+    pub spend_bundle: String,
+    pub status: PendingStatus,
+    /// Unix time of the latest broadcast
+    pub broadcast_at: u64,
+    /// Height the bundle was included at, while [`PendingStatus::Confirmed`]
+    pub confirmed_height: Option<u32>,
+}
+
+impl PendingTransaction {
+    /// Record `spend_bundle` as broadcast at `broadcast_at`
+    pub fn new(spend_bundle: &SpendBundle, broadcast_at: u64) -> Result<Self, WalletError> {
+        let spent: Vec<Coin> = spend_bundle.coin_spends.iter().map(|cs| cs.coin).collect();
+        let spent_ids: HashSet<Bytes32> = spent.iter().map(Coin::coin_id).collect();
+
+        let mut ctx = SpendContext::new();
+        let mut created = Vec::new();
+        for coin_spend in &spend_bundle.coin_spends {
+            let parent_coin_info = coin_spend.coin.coin_id();
+            for create_coin in run_coin_spend(&mut ctx, coin_spend)?
+                .into_iter()
+                .filter_map(Condition::into_create_coin)
+            {
+                let coin = Coin::new(
+                    parent_coin_info,
+                    create_coin.puzzle_hash,
+                    create_coin.amount,
+                );
+                // Coins created and spent within the bundle never reach the chain
+                if !spent_ids.contains(&coin.coin_id()) {
+                    created.push(coin);
+                }
+            }
+        }
+
+        let bytes = spend_bundle.to_bytes().map_err(|e| {
+            WalletError::SerializationError(format!("Failed to serialize spend bundle: {}", e))
+        })?;
+        Ok(Self {
+            tx_id: hex::encode(spend_bundle.name()),
+            spent_coins: spent.iter().map(SerializedCoin::from).collect(),
+            created_coins: created.iter().map(SerializedCoin::from).collect(),
+            spend_bundle: hex::encode(bytes),
+            status: PendingStatus::Pending,
+            broadcast_at,
+            confirmed_height: None,
+        })
+    }
+
+    /// Decode the stored spend bundle
+    pub fn spend_bundle(&self) -> Result<SpendBundle, WalletError> {
+        SpendBundle::from_bytes(&bytes_from_hex(&self.spend_bundle)?).map_err(|e| {
+            WalletError::SerializationError(format!("Failed to deserialize spend bundle: {}", e))
+        })
+    }
+
+    fn coin_ids(coins: &[SerializedCoin]) -> Result<Vec<Bytes32>, WalletError> {
+        coins
+            .iter()
+            .map(|coin| Ok(coin.to_coin()?.coin_id()))
+            .collect()
+    }
+}
+
+/// Locally persisted broadcast spend bundles of one wallet, keyed by transaction id
+pub struct PendingTransactions {
+    cache: FileCache<PendingTransaction>,
+}
+
+impl PendingTransactions {
+    /// Open the store of a wallet under the configured `.dig` directory
+    pub fn open(wallet_name: &str, config: &WalletConfig) -> Result<Self, WalletError> {
+        Ok(Self {
+            cache: FileCache::from_config(&format!("pending/{}", wallet_name), config)?,
+        })
+    }
+
+    /// Get a broadcast transaction by id
+    pub fn get(&self, tx_id: &str) -> Result<Option<PendingTransaction>, WalletError> {
+        self.cache.get(tx_id)
+    }
+
+    /// All recorded transactions, oldest broadcast first
+    pub fn list(&self) -> Result<Vec<PendingTransaction>, WalletError> {
+        let mut transactions = Vec::new();
+        for key in self.cache.get_cached_keys()? {
+            if let Some(transaction) = self.cache.get(&key)? {
+                transactions.push(transaction);
+            }
+        }
+        transactions.sort_by(|a, b| {
+            a.broadcast_at
+                .cmp(&b.broadcast_at)
+                .then_with(|| a.tx_id.cmp(&b.tx_id))
+        });
+        Ok(transactions)
+    }
+
+    /// Record a broadcast, replacing an earlier record of the same bundle
+    pub async fn track(&self, transaction: &PendingTransaction) -> Result<(), WalletError> {
+        self.cache.set(&transaction.tx_id, transaction).await
+    }
+
+    /// Ids of the coins spent by [`PendingStatus::Pending`] transactions
+    pub fn in_flight_coin_ids(&self) -> Result<HashSet<Bytes32>, WalletError> {
+        let mut coin_ids = HashSet::new();
+        for transaction in self.list()? {
+            if transaction.status == PendingStatus::Pending {
+                coin_ids.extend(PendingTransaction::coin_ids(&transaction.spent_coins)?);
+            }
+        }
+        Ok(coin_ids)
+    }
+
+    /// Give up on a transaction, releasing its coins
+    pub async fn mark_failed(&self, tx_id: &str) -> Result<(), WalletError> {
+        let mut transaction = self.require(tx_id)?;
+        transaction.status = PendingStatus::Failed;
+        transaction.confirmed_height = None;
+        self.cache.set(tx_id, &transaction).await
+    }
+
+    /// Forget a transaction entirely
+    pub async fn remove(&self, tx_id: &str) -> Result<(), WalletError> {
+        self.cache.delete(tx_id).await
+    }
+
+    fn require(&self, tx_id: &str) -> Result<PendingTransaction, WalletError> {
+        self.get(tx_id)?.ok_or_else(|| {
+            WalletError::TransactionFailed(format!("Transaction {} is not pending", tx_id))
+        })
+    }
+}
+
+/// Where `transaction` stands on a chain at `peak`, given whether each of its spent coins
+/// is spent there and the height its first created coin appeared at
+///
+/// `None` once the transaction is confirmed deep enough to forget.
+fn next_state(
+    transaction: &PendingTransaction,
+    spent: &[bool],
+    created_height: Option<u32>,
+    spent_height: Option<u32>,
+    peak: u32,
+) -> Option<(PendingStatus, Option<u32>)> {
+    if transaction.status == PendingStatus::Failed {
+        return Some((PendingStatus::Failed, None));
+    }
+    if !spent.iter().all(|&spent| spent) {
+        // Not (or no longer, after a reorg) on chain
+        return Some((PendingStatus::Pending, None));
+    }
+    let height = if transaction.created_coins.is_empty() {
+        spent_height
+    } else {
+        created_height
+    };
+    match height {
+        Some(height) if peak.saturating_sub(height) >= PENDING_CONFIRMATION_DEPTH => None,
+        Some(height) => Some((PendingStatus::Confirmed, Some(height))),
+        // The coins are gone but what the bundle creates is not: another bundle spent them
+        None => Some((PendingStatus::Failed, None)),
+    }
+}
+
+impl Wallet {
+    /// Open the store of this wallet's broadcast transactions
+    pub fn pending_transactions(&self) -> Result<PendingTransactions, WalletError> {
+        PendingTransactions::open(&self.cache_name(), self.get_config())
+    }
+
+    /// Ids of coins spent by this wallet's pending transactions, left out of coin listings
+    pub(crate) fn in_flight_coin_ids(&self) -> Result<HashSet<Bytes32>, WalletError> {
+        self.pending_transactions()?.in_flight_coin_ids()
+    }
+
+    /// Follow the recorded transactions on `peer`, returning those still pending
+    ///
+    /// Confirmed transactions buried [`PENDING_CONFIRMATION_DEPTH`] blocks deep are
+    /// forgotten; a confirmed one whose coins are unspent again goes back to pending.
+    pub async fn sync_pending_transactions(
+        &self,
+        peer: &Peer,
+    ) -> Result<Vec<PendingTransaction>, WalletError> {
+        let store = self.pending_transactions()?;
+        let peak = fetch_peak_height(peer).await?;
+
+        let mut pending = Vec::new();
+        for mut transaction in store.list()? {
+            if transaction.status == PendingStatus::Failed {
+                continue;
+            }
+            let spent_ids = PendingTransaction::coin_ids(&transaction.spent_coins)?;
+            let spent_states = fetch_coin_states(peer, spent_ids.clone()).await?;
+            let spent: Vec<bool> = spent_ids
+                .iter()
+                .map(|coin_id| {
+                    spent_states.iter().any(|state| {
+                        state.coin.coin_id() == *coin_id && state.spent_height.is_some()
+                    })
+                })
+                .collect();
+            let spent_height = spent_states
+                .iter()
+                .filter_map(|state| state.spent_height)
+                .max();
+            let created_height = match transaction.created_coins.first() {
+                Some(coin) => {
+                    let coin_id = coin.to_coin()?.coin_id();
+                    fetch_coin_states(peer, vec![coin_id])
+                        .await?
+                        .into_iter()
+                        .find(|state| state.coin.coin_id() == coin_id)
+                        .and_then(|state| state.created_height)
+                }
+                None => None,
+            };
+
+            match next_state(&transaction, &spent, created_height, spent_height, peak) {
+                None => store.remove(&transaction.tx_id).await?,
+                Some((status, confirmed_height)) => {
+                    transaction.status = status;
+                    transaction.confirmed_height = confirmed_height;
+                    store.track(&transaction).await?;
+                    if status == PendingStatus::Pending {
+                        pending.push(transaction);
+                    }
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Broadcast a recorded transaction again, e.g. after a reorg or mempool eviction
+    ///
+    /// Confirmed transactions are refused; a failed one is pending again if the mempool
+    /// takes it.
+    pub async fn rebroadcast_pending(
+        &self,
+        peer: &Peer,
+        tx_id: &str,
+    ) -> Result<Bytes32, WalletError> {
+        let transaction = self.pending_transactions()?.require(tx_id)?;
+        if transaction.status == PendingStatus::Confirmed {
+            return Err(WalletError::TransactionFailed(format!(
+                "Transaction {} is already confirmed",
+                tx_id
+            )));
+        }
+        self.broadcast_transaction(peer, &transaction.spend_bundle()?)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    fn pending(tx_id: &str, coins: &[Coin], broadcast_at: u64) -> PendingTransaction {
+        PendingTransaction {
+            tx_id: tx_id.to_string(),
+            spent_coins: coins.iter().map(SerializedCoin::from).collect(),
+            created_coins: vec![],
+            spend_bundle: String::new(),
+            status: PendingStatus::Pending,
+            broadcast_at,
+            confirmed_height: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_coins() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let store = PendingTransactions::open("pending_test", &config).unwrap();
+
+        let first = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 10);
+        let second = Coin::new(Bytes32::new([3; 32]), Bytes32::new([2; 32]), 20);
+        store.track(&pending("later", &[second], 20)).await.unwrap();
+        store
+            .track(&pending("earlier", &[first], 10))
+            .await
+            .unwrap();
+
+        let listed: Vec<String> = store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|tx| tx.tx_id)
+            .collect();
+        assert_eq!(listed, ["earlier", "later"]);
+        assert_eq!(
+            store.in_flight_coin_ids().unwrap(),
+            HashSet::from([first.coin_id(), second.coin_id()])
+        );
+
+        store.mark_failed("later").await.unwrap();
+        assert_eq!(
+            store.in_flight_coin_ids().unwrap(),
+            HashSet::from([first.coin_id()])
+        );
+        assert!(store.mark_failed("unknown").await.is_err());
+
+        store.remove("earlier").await.unwrap();
+        assert!(store.in_flight_coin_ids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_next_state() {
+        let coin = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 10);
+        let mut transaction = pending("tx", &[coin], 0);
+        transaction.created_coins = vec![SerializedCoin::from(&coin)];
+
+        assert_eq!(
+            next_state(&transaction, &[false], None, None, 100),
+            Some((PendingStatus::Pending, None))
+        );
+        assert_eq!(
+            next_state(&transaction, &[true], Some(90), Some(90), 100),
+            Some((PendingStatus::Confirmed, Some(90)))
+        );
+        assert_eq!(
+            next_state(&transaction, &[true], Some(60), Some(60), 100),
+            None
+        );
+        assert_eq!(
+            next_state(&transaction, &[true], None, Some(90), 100),
+            Some((PendingStatus::Failed, None))
+        );
+
+        // A reorg that unspends the coins puts a confirmed transaction back in flight
+        transaction.status = PendingStatus::Confirmed;
+        assert_eq!(
+            next_state(&transaction, &[true, false], None, None, 100),
+            Some((PendingStatus::Pending, None))
+        );
+    }
+}
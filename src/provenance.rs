@@ -0,0 +1,234 @@
+//! Parent-lineage graphs for investigating where a coin came from.
+//!
+//! [`Wallet::trace_coin`] walks a coin's ancestors, recording each coin, the spend that
+//! created its child and the puzzle it was locked with. The resulting
+//! [`ProvenanceGraph`] exports as JSON for tooling or as Graphviz DOT for a quick look,
+//! which makes failed CAT lineage proofs inspectable: a DIG coin whose ancestors are not
+//! CAT coins of the same asset cannot be proved.
+
+use crate::chain::{fetch_coin_spend, fetch_coin_state};
+use crate::coin_proof::SerializedCoin;
+use crate::error::WalletError;
+use crate::wallet::Wallet;
+use chia_wallet_sdk::driver::{Puzzle, SpendContext};
+use datalayer_driver::{Bytes32, CoinSpend, Peer};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Mod hash of the CAT v2 outer puzzle
+const CAT_MOD_HASH: &str = "37bef360ee858133b69d595a906dc45d01af50379dad515eb9518abb7c1d2a7a";
+/// Mod hash of the standard `p2_delegated_puzzle_or_hidden_puzzle`
+const STANDARD_MOD_HASH: &str = "e9aaa49f45bad5c889b86ee3341550c155cfdd10c3a6757de618d20612fffd52";
+/// Mod hash of the singleton top layer v1.1
+const SINGLETON_MOD_HASH: &str = "7faa3253bfddd1e0decb0906b2dc6247bbc4cf608f58345d173adb63e9a3e6c5";
+
+/// Outer puzzle a spent coin was locked with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PuzzleKind {
+    Standard,
+    Cat,
+    Singleton,
+    Other,
+}
+
+impl PuzzleKind {
+    pub(crate) fn from_mod_hash(mod_hash: &str) -> Self {
+        match mod_hash {
+            STANDARD_MOD_HASH => PuzzleKind::Standard,
+            CAT_MOD_HASH => PuzzleKind::Cat,
+            SINGLETON_MOD_HASH => PuzzleKind::Singleton,
+            _ => PuzzleKind::Other,
+        }
+    }
+}
+
+/// A coin in a provenance graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvenanceNode {
+    pub coin_id: String,
+    pub coin: SerializedCoin,
+    /// 0 for the traced coin, 1 for its parent, and so on
+    pub depth: usize,
+    pub created_height: Option<u32>,
+    pub spent_height: Option<u32>,
+    /// Known only for spent coins, whose puzzle was revealed
+    pub puzzle: Option<PuzzleKind>,
+    pub mod_hash: Option<String>,
+}
+
+/// A spend of `parent` that created `child`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvenanceEdge {
+    pub parent: String,
+    pub child: String,
+    pub spent_height: u32,
+}
+
+/// Ancestors of a coin, traced by [`Wallet::trace_coin`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvenanceGraph {
+    pub root: String,
+    /// The traced coin first, then its ancestors nearest first
+    pub nodes: Vec<ProvenanceNode>,
+    pub edges: Vec<ProvenanceEdge>,
+    /// Whether the depth limit was reached before the lineage ended
+    pub truncated: bool,
+}
+
+impl ProvenanceGraph {
+    pub fn to_json(&self) -> Result<String, WalletError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))
+    }
+
+    /// Render as a Graphviz digraph, parents pointing at children
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph provenance {\n    rankdir=BT;\n");
+        for node in &self.nodes {
+            let puzzle = match node.puzzle {
+                Some(kind) => format!("{:?}", kind).to_lowercase(),
+                None => "unspent".to_string(),
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" [label=\"{}...\\n{}\\n{} mojos\"{}];",
+                node.coin_id,
+                &node.coin_id[..12.min(node.coin_id.len())],
+                puzzle,
+                node.coin.amount,
+                if node.coin_id == self.root {
+                    ", shape=box"
+                } else {
+                    ""
+                }
+            );
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                edge.parent, edge.child, edge.spent_height
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Mod hash of the outer puzzle revealed by a spend
+pub(crate) fn puzzle_mod_hash(coin_spend: &CoinSpend) -> Result<String, WalletError> {
+    let mut ctx = SpendContext::new();
+    let ptr = ctx
+        .alloc(&coin_spend.puzzle_reveal)
+        .map_err(|e| WalletError::DataLayerError(format!("Failed to parse puzzle: {}", e)))?;
+    let mod_hash: Bytes32 = Puzzle::parse(&ctx, ptr).mod_hash().into();
+    Ok(hex::encode(mod_hash))
+}
+
+impl Wallet {
+    /// Trace the parents of a coin up to `depth` levels
+    ///
+    /// Stops early at coins without a parent on chain, such as farming rewards.
+    pub async fn trace_coin(
+        peer: &Peer,
+        coin_id: Bytes32,
+        depth: usize,
+    ) -> Result<ProvenanceGraph, WalletError> {
+        let mut graph = ProvenanceGraph {
+            root: hex::encode(coin_id),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            truncated: false,
+        };
+
+        let mut state = fetch_coin_state(peer, coin_id).await?;
+        for level in 0..=depth {
+            let id = state.coin.coin_id();
+            let mod_hash = match state.spent_height {
+                Some(spent_height) => Some(puzzle_mod_hash(
+                    &fetch_coin_spend(peer, state.coin, spent_height).await?,
+                )?),
+                None => None,
+            };
+            graph.nodes.push(ProvenanceNode {
+                coin_id: hex::encode(id),
+                coin: SerializedCoin::from(&state.coin),
+                depth: level,
+                created_height: state.created_height,
+                spent_height: state.spent_height,
+                puzzle: mod_hash.as_deref().map(PuzzleKind::from_mod_hash),
+                mod_hash,
+            });
+
+            if level == depth {
+                graph.truncated = true;
+                break;
+            }
+
+            let parent = match fetch_coin_state(peer, state.coin.parent_coin_info).await {
+                Ok(parent) => parent,
+                // Farming rewards have no parent coin
+                Err(WalletError::CoinSetError(_)) => break,
+                Err(e) => return Err(e),
+            };
+            let Some(spent_height) = parent.spent_height else {
+                break;
+            };
+            graph.edges.push(ProvenanceEdge {
+                parent: hex::encode(parent.coin.coin_id()),
+                child: hex::encode(id),
+                spent_height,
+            });
+            state = parent;
+        }
+
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_exports() {
+        let node = |fill: u8, depth: usize, puzzle: Option<PuzzleKind>| ProvenanceNode {
+            coin_id: hex::encode([fill; 32]),
+            coin: SerializedCoin {
+                parent_coin_info: hex::encode([fill + 1; 32]),
+                puzzle_hash: hex::encode([9; 32]),
+                amount: 1_000,
+            },
+            depth,
+            created_height: Some(10),
+            spent_height: puzzle.map(|_| 20),
+            puzzle,
+            mod_hash: None,
+        };
+        let graph = ProvenanceGraph {
+            root: hex::encode([1; 32]),
+            nodes: vec![node(1, 0, None), node(2, 1, Some(PuzzleKind::Cat))],
+            edges: vec![ProvenanceEdge {
+                parent: hex::encode([2; 32]),
+                child: hex::encode([1; 32]),
+                spent_height: 20,
+            }],
+            truncated: true,
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json().unwrap()).unwrap();
+        assert_eq!(json["nodes"][1]["puzzle"], "cat");
+        assert_eq!(json["truncated"], true);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph provenance {"));
+        assert!(dot.contains(&format!(
+            "\"{}\" -> \"{}\"",
+            hex::encode([2; 32]),
+            hex::encode([1; 32])
+        )));
+        assert!(dot.contains("\\ncat\\n"));
+        assert_eq!(PuzzleKind::from_mod_hash(CAT_MOD_HASH), PuzzleKind::Cat);
+    }
+}
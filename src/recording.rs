@@ -0,0 +1,348 @@
+//! Recording and replaying peer sessions for deterministic tests.
+//!
+//! Run an operation inside [`PeerSession::scope`] of a [`PeerSession::record`] session and
+//! every wallet-protocol request made through the crate's chain helpers (coin states,
+//! puzzle and solution lookups, block headers and coins by puzzle hash) is saved
+//! with its response. Save the [`PeerRecording`] as a fixture and run the same operation
+//! under [`PeerSession::replay`] in CI: the recorded responses are returned without
+//! contacting the peer, and a request missing from the fixture fails instead of reaching
+//! the network.
+//!
+//! Fixtures hold only request parameters and on-chain data. Transport errors are recorded
+//! without their underlying message, which can name the peer, and no key material, TLS
+//! identity or peer address is ever stored. Requests made inside `datalayer-driver` itself,
+//! such as the lineage lookups of `DigCoin::from_coin_state`, bypass the chain helpers and
+//! are not captured.
+
+use crate::coin_proof::SerializedCoin;
+use crate::error::WalletError;
+use datalayer_driver::CoinState;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+tokio::task_local! {
+    static CURRENT: PeerSession;
+}
+
+/// Version of the [`PeerRecording`] fixture layout
+pub const PEER_RECORDING_VERSION: u32 = 1;
+
+/// A wallet-protocol request, keyed by its parameters
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum PeerRequest {
+    CoinStates { coin_ids: Vec<String> },
+    PuzzleAndSolution { coin_id: String, height: u32 },
+    BlockHeader { height: u32 },
+    UnspentCoins { puzzle_hash: String },
+    PuzzleHistory { puzzle_hash: String },
+}
+
+/// A coin state as recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedCoinState {
+    pub coin: SerializedCoin,
+    pub spent_height: Option<u32>,
+    pub created_height: Option<u32>,
+}
+
+impl From<&CoinState> for RecordedCoinState {
+    fn from(state: &CoinState) -> Self {
+        Self {
+            coin: SerializedCoin::from(&state.coin),
+            spent_height: state.spent_height,
+            created_height: state.created_height,
+        }
+    }
+}
+
+impl RecordedCoinState {
+    pub(crate) fn to_coin_state(&self) -> Result<CoinState, WalletError> {
+        Ok(CoinState::new(
+            self.coin.to_coin()?,
+            self.spent_height,
+            self.created_height,
+        ))
+    }
+}
+
+/// The peer's answer to a [`PeerRequest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum PeerResponse {
+    CoinStates {
+        coin_states: Vec<RecordedCoinState>,
+    },
+    CoinSpend {
+        puzzle_reveal: String,
+        solution: String,
+    },
+    /// The peer refused the request, as pruned nodes do for old spends
+    Rejected {
+        detail: String,
+    },
+    BlockHeader {
+        header_hash: String,
+        timestamp: Option<u64>,
+    },
+    UnspentCoins {
+        coin_states: Vec<RecordedCoinState>,
+        last_height: u32,
+    },
+    /// The request failed in transport; replays as [`WalletError::NetworkError`]
+    Failed {
+        detail: String,
+    },
+}
+
+/// A request and the response it received
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerExchange {
+    #[serde(flatten)]
+    pub request: PeerRequest,
+    pub response: PeerResponse,
+}
+
+/// The exchanges of a recorded peer session, in request order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerRecording {
+    pub version: u32,
+    pub exchanges: Vec<PeerExchange>,
+}
+
+impl Default for PeerRecording {
+    fn default() -> Self {
+        Self {
+            version: PEER_RECORDING_VERSION,
+            exchanges: Vec::new(),
+        }
+    }
+}
+
+impl PeerRecording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a fixture written by [`PeerRecording::save`]
+    pub fn load(path: &Path) -> Result<Self, WalletError> {
+        let content = fs::read(path).map_err(|e| {
+            WalletError::FileSystemError(format!("Failed to read peer recording: {}", e))
+        })?;
+        let recording: Self = serde_json::from_slice(&content).map_err(|e| {
+            WalletError::SerializationError(format!("Invalid peer recording: {}", e))
+        })?;
+        if recording.version != PEER_RECORDING_VERSION {
+            return Err(WalletError::SerializationError(format!(
+                "Unsupported peer recording version: {}",
+                recording.version
+            )));
+        }
+        Ok(recording)
+    }
+
+    /// Write the recording as pretty-printed JSON
+    pub fn save(&self, path: &Path) -> Result<(), WalletError> {
+        let content = serde_json::to_vec_pretty(self)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+        fs::write(path, content).map_err(|e| {
+            WalletError::FileSystemError(format!("Failed to write peer recording: {}", e))
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug)]
+struct SessionState {
+    mode: Mode,
+    recording: PeerRecording,
+    /// Which exchanges have been replayed
+    replayed: Vec<bool>,
+}
+
+/// A recording or replaying peer session, shared by every clone
+#[derive(Debug, Clone)]
+pub struct PeerSession {
+    state: Arc<Mutex<SessionState>>,
+}
+
+impl PeerSession {
+    /// Record every request and response made in [`PeerSession::scope`]
+    pub fn record() -> Self {
+        Self::with_mode(Mode::Record, PeerRecording::new())
+    }
+
+    /// Answer requests made in [`PeerSession::scope`] from `recording`
+    ///
+    /// Identical requests are answered in recorded order, repeating the last answer once
+    /// all were used, so an operation that polls sees the same sequence it saw live.
+    pub fn replay(recording: PeerRecording) -> Self {
+        Self::with_mode(Mode::Replay, recording)
+    }
+
+    fn with_mode(mode: Mode, recording: PeerRecording) -> Self {
+        let replayed = vec![false; recording.exchanges.len()];
+        Self {
+            state: Arc::new(Mutex::new(SessionState {
+                mode,
+                recording,
+                replayed,
+            })),
+        }
+    }
+
+    /// Run `future` with this session as the current one
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT.scope(self.clone(), future).await
+    }
+
+    /// The exchanges recorded so far, or the fixture being replayed
+    pub fn recording(&self) -> PeerRecording {
+        self.lock().recording.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn replay_response(&self, request: &PeerRequest) -> Option<PeerResponse> {
+        let mut state = self.lock();
+        let state = &mut *state;
+        let matching: Vec<usize> = (0..state.recording.exchanges.len())
+            .filter(|&index| state.recording.exchanges[index].request == *request)
+            .collect();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|&index| !state.replayed[index])
+            .or_else(|| matching.last().copied())?;
+        state.replayed[index] = true;
+        Some(state.recording.exchanges[index].response.clone())
+    }
+}
+
+/// Drop the underlying error from a transport failure, keeping the helper's context
+fn scrub(detail: &str) -> String {
+    match detail.split_once(": ") {
+        Some((context, _)) => format!("{}: (scrubbed)", context),
+        None => detail.to_string(),
+    }
+}
+
+/// Make a peer request through the current [`PeerSession`], if any
+///
+/// `live` performs the request against the peer; it is not polled when replaying.
+pub(crate) async fn exchange(
+    request: PeerRequest,
+    live: impl Future<Output = Result<PeerResponse, WalletError>>,
+) -> Result<PeerResponse, WalletError> {
+    let Ok(session) = CURRENT.try_with(Clone::clone) else {
+        return live.await;
+    };
+
+    let replaying = matches!(session.lock().mode, Mode::Replay);
+    let response = if replaying {
+        session.replay_response(&request).ok_or_else(|| {
+            WalletError::NetworkError(format!("No recorded response for {:?}", request))
+        })?
+    } else {
+        let response = match live.await {
+            Ok(response) => response,
+            Err(WalletError::NetworkError(detail)) => PeerResponse::Failed {
+                detail: scrub(&detail),
+            },
+            // Cancellations and deadlines are the caller's, not the peer's
+            Err(e) => return Err(e),
+        };
+        let mut state = session.lock();
+        state.recording.exchanges.push(PeerExchange {
+            request,
+            response: response.clone(),
+        });
+        state.replayed.push(false);
+        response
+    };
+
+    match response {
+        PeerResponse::Failed { detail } => Err(WalletError::NetworkError(detail)),
+        response => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_recorded_session_replays_without_peer() {
+        let coin_state = RecordedCoinState {
+            coin: SerializedCoin {
+                parent_coin_info: hex::encode([1; 32]),
+                puzzle_hash: hex::encode([2; 32]),
+                amount: 1_000,
+            },
+            spent_height: None,
+            created_height: Some(10),
+        };
+        let unspent = PeerRequest::UnspentCoins {
+            puzzle_hash: hex::encode([2; 32]),
+        };
+        let header = PeerRequest::BlockHeader { height: 10 };
+
+        let session = PeerSession::record();
+        session
+            .scope(async {
+                let live = PeerResponse::UnspentCoins {
+                    coin_states: vec![coin_state.clone()],
+                    last_height: 12,
+                };
+                exchange(unspent.clone(), async { Ok(live) }).await.unwrap();
+                let failed = exchange(header.clone(), async {
+                    Err(WalletError::NetworkError(
+                        "Failed to request block header: 10.0.0.1:8444 reset".to_string(),
+                    ))
+                })
+                .await;
+                assert!(failed.is_err());
+            })
+            .await;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("session.json");
+        session.recording().save(&path).unwrap();
+        let fixture = fs::read_to_string(&path).unwrap();
+        assert!(!fixture.contains("10.0.0.1"));
+
+        let replay = PeerSession::replay(PeerRecording::load(&path).unwrap());
+        replay
+            .scope(async {
+                let never = async { panic!("replay must not reach the peer") };
+                let response = exchange(unspent, never).await.unwrap();
+                assert!(matches!(
+                    response,
+                    PeerResponse::UnspentCoins { last_height: 12, .. }
+                ));
+
+                let failed = exchange(header, async { unreachable!() }).await;
+                assert!(
+                    matches!(failed, Err(WalletError::NetworkError(detail)) if detail.contains("(scrubbed)"))
+                );
+
+                let missing = exchange(PeerRequest::BlockHeader { height: 11 }, async {
+                    unreachable!()
+                })
+                .await;
+                assert!(matches!(missing, Err(WalletError::NetworkError(_))));
+            })
+            .await;
+    }
+}
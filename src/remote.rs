@@ -0,0 +1,1040 @@
+use crate::addresses::ReceiveAddress;
+use crate::asset::Asset;
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::history::TransactionRecord;
+use crate::key_export::KeySummary;
+use crate::metadata::WalletMetadata;
+use crate::pending::PendingTransaction;
+use crate::runtime::{self, RuntimeHandle};
+use crate::tx_queue::QueuedTransaction;
+use crate::wallet::Wallet;
+use datalayer_driver::{Bytes32, Peer};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+use zeroize::Zeroizing;
+
+/// Largest frame, in bytes, either side accepts before dropping the connection
+pub const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+/// Id of the authentication exchange that opens every connection
+const AUTH_CALL_ID: u64 = 0;
+
+/// Boxed future returned by [`WalletApi`] methods
+pub type WalletFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, WalletError>> + Send + 'a>>;
+
+/// High-level wallet operations shared by in-process and remote wallets
+///
+/// Code written against this trait can switch between [`LocalWallet`] and
+/// [`RemoteWallet`] by changing only how the wallet is constructed.
+pub trait WalletApi: Send + Sync {
+    fn get_fingerprint(&self) -> WalletFuture<'_, u32>;
+    fn get_key_summary(&self) -> WalletFuture<'_, KeySummary>;
+    fn get_owner_public_key(&self) -> WalletFuture<'_, String>;
+    fn get_owner_puzzle_hash(&self) -> WalletFuture<'_, Bytes32>;
+    fn get_addresses(&self, range: Range<u32>) -> WalletFuture<'_, Vec<ReceiveAddress>>;
+    fn validate_address<'a>(&'a self, address: &'a str) -> WalletFuture<'a, Bytes32>;
+    fn get_metadata(&self) -> WalletFuture<'_, WalletMetadata>;
+    fn create_key_ownership_signature<'a>(&'a self, nonce: &'a str) -> WalletFuture<'a, String>;
+    fn get_xch_balance(&self) -> WalletFuture<'_, u64>;
+    fn get_dig_balance(&self) -> WalletFuture<'_, u64>;
+    fn get_cat_balance(&self, asset_id: Bytes32) -> WalletFuture<'_, u64>;
+    fn send_xch<'a>(
+        &'a self,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord>;
+    fn send_dig<'a>(
+        &'a self,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord>;
+    fn send_cat<'a>(
+        &'a self,
+        asset_id: Bytes32,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord>;
+    fn queue_transfer(
+        &self,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'_, QueuedTransaction>;
+    fn broadcast_queued<'a>(&'a self, tx_id: &'a str) -> WalletFuture<'a, Bytes32>;
+    fn get_transaction_history(&self, from_height: u32)
+        -> WalletFuture<'_, Vec<TransactionRecord>>;
+    fn sync_pending_transactions(&self) -> WalletFuture<'_, Vec<PendingTransaction>>;
+}
+
+/// An in-process wallet bound to a peer
+pub struct LocalWallet {
+    wallet: Wallet,
+    peer: Peer,
+}
+
+impl LocalWallet {
+    pub fn new(wallet: Wallet, peer: Peer) -> Self {
+        Self { wallet, peer }
+    }
+
+    /// The underlying wallet
+    pub fn wallet(&self) -> &Wallet {
+        &self.wallet
+    }
+}
+
+impl WalletApi for LocalWallet {
+    fn get_fingerprint(&self) -> WalletFuture<'_, u32> {
+        Box::pin(self.wallet.get_fingerprint())
+    }
+
+    fn get_key_summary(&self) -> WalletFuture<'_, KeySummary> {
+        Box::pin(self.wallet.get_key_summary())
+    }
+
+    fn get_owner_public_key(&self) -> WalletFuture<'_, String> {
+        Box::pin(self.wallet.get_owner_public_key())
+    }
+
+    fn get_owner_puzzle_hash(&self) -> WalletFuture<'_, Bytes32> {
+        Box::pin(self.wallet.get_owner_puzzle_hash())
+    }
+
+    fn get_addresses(&self, range: Range<u32>) -> WalletFuture<'_, Vec<ReceiveAddress>> {
+        Box::pin(self.wallet.get_addresses(range))
+    }
+
+    fn validate_address<'a>(&'a self, address: &'a str) -> WalletFuture<'a, Bytes32> {
+        Box::pin(self.wallet.validate_address(address))
+    }
+
+    fn get_metadata(&self) -> WalletFuture<'_, WalletMetadata> {
+        Box::pin(self.wallet.get_metadata())
+    }
+
+    fn create_key_ownership_signature<'a>(&'a self, nonce: &'a str) -> WalletFuture<'a, String> {
+        Box::pin(self.wallet.create_key_ownership_signature(nonce))
+    }
+
+    fn get_xch_balance(&self) -> WalletFuture<'_, u64> {
+        Box::pin(self.wallet.get_xch_balance(&self.peer))
+    }
+
+    fn get_dig_balance(&self) -> WalletFuture<'_, u64> {
+        Box::pin(self.wallet.get_dig_balance(&self.peer, false))
+    }
+
+    fn get_cat_balance(&self, asset_id: Bytes32) -> WalletFuture<'_, u64> {
+        Box::pin(self.wallet.get_cat_balance(&self.peer, asset_id))
+    }
+
+    fn send_xch<'a>(
+        &'a self,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord> {
+        Box::pin(self.wallet.send_xch(&self.peer, to_address, amount, fee))
+    }
+
+    fn send_dig<'a>(
+        &'a self,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord> {
+        Box::pin(self.wallet.send_dig(&self.peer, to_address, amount, fee))
+    }
+
+    fn send_cat<'a>(
+        &'a self,
+        asset_id: Bytes32,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord> {
+        Box::pin(
+            self.wallet
+                .send_cat(&self.peer, asset_id, to_address, amount, fee),
+        )
+    }
+
+    fn queue_transfer(
+        &self,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'_, QueuedTransaction> {
+        Box::pin(
+            self.wallet
+                .queue_transfer(&self.peer, asset, destination, amount, fee),
+        )
+    }
+
+    fn broadcast_queued<'a>(&'a self, tx_id: &'a str) -> WalletFuture<'a, Bytes32> {
+        Box::pin(self.wallet.broadcast_queued(&self.peer, tx_id))
+    }
+
+    fn get_transaction_history(
+        &self,
+        from_height: u32,
+    ) -> WalletFuture<'_, Vec<TransactionRecord>> {
+        Box::pin(self.wallet.get_transaction_history(&self.peer, from_height))
+    }
+
+    fn sync_pending_transactions(&self) -> WalletFuture<'_, Vec<PendingTransaction>> {
+        Box::pin(self.wallet.sync_pending_transactions(&self.peer))
+    }
+}
+
+/// A [`WalletApi`] call carried by a [`RemoteCall`]
+///
+/// Puzzle hashes and asset ids travel as hex.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum RemoteRequest {
+    GetFingerprint,
+    GetKeySummary,
+    GetOwnerPublicKey,
+    GetOwnerPuzzleHash,
+    GetAddresses {
+        start: u32,
+        end: u32,
+    },
+    ValidateAddress {
+        address: String,
+    },
+    GetMetadata,
+    CreateKeyOwnershipSignature {
+        nonce: String,
+    },
+    GetXchBalance,
+    GetDigBalance,
+    GetCatBalance {
+        asset_id: String,
+    },
+    SendXch {
+        to_address: String,
+        amount: u64,
+        fee: u64,
+    },
+    SendDig {
+        to_address: String,
+        amount: u64,
+        fee: u64,
+    },
+    SendCat {
+        asset_id: String,
+        to_address: String,
+        amount: u64,
+        fee: u64,
+    },
+    QueueTransfer {
+        asset: Asset,
+        destination: String,
+        amount: u64,
+        fee: u64,
+    },
+    BroadcastQueued {
+        tx_id: String,
+    },
+    GetTransactionHistory {
+        from_height: u32,
+    },
+    SyncPendingTransactions,
+}
+
+/// The first frame of every connection, proving the client holds the daemon's token
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RemoteAuth {
+    pub token: String,
+}
+
+/// A request frame sent from [`RemoteWallet`] to [`WalletDaemon`]
+///
+/// The daemon echoes `id` in its [`RemoteResponse`], so replies never depend on the
+/// order in which callers on a shared connection wait for them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteCall {
+    pub id: u64,
+    pub request: RemoteRequest,
+}
+
+/// A response frame sent from [`WalletDaemon`] back to [`RemoteWallet`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteResponse {
+    pub id: u64,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+impl RemoteResponse {
+    fn error(id: u64, error: impl Into<String>) -> Self {
+        Self {
+            id,
+            result: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+fn to_response<T: Serialize>(id: u64, result: Result<T, WalletError>) -> RemoteResponse {
+    match result.and_then(|value| {
+        serde_json::to_value(value).map_err(|e| WalletError::SerializationError(e.to_string()))
+    }) {
+        Ok(value) => RemoteResponse {
+            id,
+            result: Some(value),
+            error: None,
+        },
+        Err(e) => RemoteResponse::error(id, e.to_string()),
+    }
+}
+
+/// Generate a random daemon token to share with its clients out of band
+pub fn generate_auth_token(config: &WalletConfig) -> Result<String, WalletError> {
+    Ok(hex::encode(config.random_bytes::<32>()?))
+}
+
+/// Compare tokens without leaking the length of the matching prefix
+fn tokens_match(expected: &str, presented: &str) -> bool {
+    let (expected, presented) = (expected.as_bytes(), presented.as_bytes());
+    expected.len() == presented.len()
+        && expected
+            .iter()
+            .zip(presented)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Read one newline-terminated frame of at most [`MAX_FRAME_LEN`] bytes
+async fn read_frame<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<Vec<u8>>, WalletError> {
+    let mut frame = Vec::new();
+    let read = (&mut *reader)
+        .take(MAX_FRAME_LEN as u64 + 1)
+        .read_until(b'\n', &mut frame)
+        .await
+        .map_err(|e| WalletError::NetworkError(e.to_string()))?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if frame.len() > MAX_FRAME_LEN {
+        return Err(WalletError::NetworkError(format!(
+            "Frame exceeds {} bytes",
+            MAX_FRAME_LEN
+        )));
+    }
+    Ok(Some(frame))
+}
+
+fn encode_frame<T: Serialize>(value: &T) -> Result<Vec<u8>, WalletError> {
+    let mut encoded =
+        serde_json::to_vec(value).map_err(|e| WalletError::SerializationError(e.to_string()))?;
+    encoded.push(b'\n');
+    Ok(encoded)
+}
+
+async fn write_frame<W: AsyncWrite + Unpin, T: Serialize>(
+    writer: &mut W,
+    value: &T,
+) -> Result<(), WalletError> {
+    writer
+        .write_all(&encode_frame(value)?)
+        .await
+        .map_err(|e| WalletError::NetworkError(e.to_string()))
+}
+
+/// Serves a [`WalletApi`] over newline-delimited JSON frames
+///
+/// Clients must open each connection with the daemon's token. Frames travel in plaintext,
+/// so by default only loopback TCP listeners and Unix sockets are served; reach a daemon
+/// on another host through an SSH tunnel or a TLS terminator.
+pub struct WalletDaemon {
+    api: Arc<dyn WalletApi>,
+    auth_token: Arc<Zeroizing<String>>,
+    allow_remote_clients: bool,
+    runtime: Option<RuntimeHandle>,
+}
+
+impl WalletDaemon {
+    /// Serve `api` to clients presenting `auth_token`, see [`generate_auth_token`]
+    pub fn new(api: Arc<dyn WalletApi>, auth_token: impl Into<String>) -> Self {
+        Self {
+            api,
+            auth_token: Arc::new(Zeroizing::new(auth_token.into())),
+            allow_remote_clients: false,
+            runtime: None,
+        }
+    }
+
+    /// Also serve TCP listeners bound to non-loopback addresses
+    pub fn allow_remote_clients(mut self) -> Self {
+        self.allow_remote_clients = true;
+        self
+    }
+
+    /// Spawn connection handlers on `runtime` instead of the one installed with [`crate::init`]
+    pub fn with_runtime(mut self, runtime: RuntimeHandle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    fn runtime(&self) -> Result<RuntimeHandle, WalletError> {
+        match &self.runtime {
+            Some(runtime) => Ok(runtime.clone()),
+            None => runtime::current(),
+        }
+    }
+
+    fn check_auth_token(&self) -> Result<(), WalletError> {
+        if self.auth_token.is_empty() {
+            return Err(WalletError::InvalidConfig(
+                "Wallet daemon auth token must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Accept TCP connections until the listener fails
+    pub async fn serve(&self, listener: TcpListener) -> Result<(), WalletError> {
+        self.check_auth_token()?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| WalletError::NetworkError(e.to_string()))?;
+        if !local_addr.ip().is_loopback() && !self.allow_remote_clients {
+            return Err(WalletError::InvalidConfig(format!(
+                "Refusing to serve the wallet on non-loopback address {}; \
+                 call allow_remote_clients to opt in",
+                local_addr
+            )));
+        }
+        let runtime = self.runtime()?;
+
+        loop {
+            let (stream, _) = listener.accept().await.map_err(|e| {
+                WalletError::NetworkError(format!("Failed to accept connection: {}", e))
+            })?;
+            let (reader, writer) = stream.into_split();
+            let (api, auth_token) = (self.api.clone(), self.auth_token.clone());
+            runtime.spawn(async move {
+                let _ = Self::handle_connection(api, auth_token, reader, writer).await;
+            });
+        }
+    }
+
+    /// Accept Unix socket connections until the listener fails
+    #[cfg(unix)]
+    pub async fn serve_unix(&self, listener: tokio::net::UnixListener) -> Result<(), WalletError> {
+        self.check_auth_token()?;
+        let runtime = self.runtime()?;
+
+        loop {
+            let (stream, _) = listener.accept().await.map_err(|e| {
+                WalletError::NetworkError(format!("Failed to accept connection: {}", e))
+            })?;
+            let (reader, writer) = stream.into_split();
+            let (api, auth_token) = (self.api.clone(), self.auth_token.clone());
+            runtime.spawn(async move {
+                let _ = Self::handle_connection(api, auth_token, reader, writer).await;
+            });
+        }
+    }
+
+    async fn handle_connection<R, W>(
+        api: Arc<dyn WalletApi>,
+        auth_token: Arc<Zeroizing<String>>,
+        reader: R,
+        mut writer: W,
+    ) -> Result<(), WalletError>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut reader = BufReader::new(reader);
+
+        let authenticated = match read_frame(&mut reader).await? {
+            Some(frame) => serde_json::from_slice::<RemoteAuth>(&frame)
+                .map(|auth| tokens_match(&auth_token, &auth.token))
+                .unwrap_or(false),
+            None => return Ok(()),
+        };
+        if !authenticated {
+            let response = RemoteResponse::error(AUTH_CALL_ID, "Authentication failed");
+            return write_frame(&mut writer, &response).await;
+        }
+        write_frame(&mut writer, &to_response(AUTH_CALL_ID, Ok(()))).await?;
+
+        while let Some(frame) = read_frame(&mut reader).await? {
+            let response = match serde_json::from_slice::<RemoteCall>(&frame) {
+                Ok(call) => Self::dispatch(api.as_ref(), call).await,
+                Err(e) => {
+                    // Without a readable id the client cannot be answered; drop the connection
+                    let response = RemoteResponse::error(0, format!("Invalid request: {}", e));
+                    return write_frame(&mut writer, &response).await;
+                }
+            };
+            write_frame(&mut writer, &response).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn dispatch(api: &dyn WalletApi, call: RemoteCall) -> RemoteResponse {
+        use crate::encoding::bytes32_from_hex;
+
+        let id = call.id;
+        match call.request {
+            RemoteRequest::GetFingerprint => to_response(id, api.get_fingerprint().await),
+            RemoteRequest::GetKeySummary => to_response(id, api.get_key_summary().await),
+            RemoteRequest::GetOwnerPublicKey => to_response(id, api.get_owner_public_key().await),
+            RemoteRequest::GetOwnerPuzzleHash => {
+                to_response(id, api.get_owner_puzzle_hash().await.map(hex::encode))
+            }
+            RemoteRequest::GetAddresses { start, end } => {
+                to_response(id, api.get_addresses(start..end).await)
+            }
+            RemoteRequest::ValidateAddress { address } => {
+                to_response(id, api.validate_address(&address).await.map(hex::encode))
+            }
+            RemoteRequest::GetMetadata => to_response(id, api.get_metadata().await),
+            RemoteRequest::CreateKeyOwnershipSignature { nonce } => {
+                to_response(id, api.create_key_ownership_signature(&nonce).await)
+            }
+            RemoteRequest::GetXchBalance => to_response(id, api.get_xch_balance().await),
+            RemoteRequest::GetDigBalance => to_response(id, api.get_dig_balance().await),
+            RemoteRequest::GetCatBalance { asset_id } => match bytes32_from_hex(&asset_id) {
+                Ok(asset_id) => to_response(id, api.get_cat_balance(asset_id).await),
+                Err(e) => to_response::<()>(id, Err(e)),
+            },
+            RemoteRequest::SendXch {
+                to_address,
+                amount,
+                fee,
+            } => to_response(id, api.send_xch(&to_address, amount, fee).await),
+            RemoteRequest::SendDig {
+                to_address,
+                amount,
+                fee,
+            } => to_response(id, api.send_dig(&to_address, amount, fee).await),
+            RemoteRequest::SendCat {
+                asset_id,
+                to_address,
+                amount,
+                fee,
+            } => match bytes32_from_hex(&asset_id) {
+                Ok(asset_id) => {
+                    to_response(id, api.send_cat(asset_id, &to_address, amount, fee).await)
+                }
+                Err(e) => to_response::<()>(id, Err(e)),
+            },
+            RemoteRequest::QueueTransfer {
+                asset,
+                destination,
+                amount,
+                fee,
+            } => match bytes32_from_hex(&destination) {
+                Ok(destination) => to_response(
+                    id,
+                    api.queue_transfer(asset, destination, amount, fee).await,
+                ),
+                Err(e) => to_response::<()>(id, Err(e)),
+            },
+            RemoteRequest::BroadcastQueued { tx_id } => {
+                to_response(id, api.broadcast_queued(&tx_id).await.map(hex::encode))
+            }
+            RemoteRequest::GetTransactionHistory { from_height } => {
+                to_response(id, api.get_transaction_history(from_height).await)
+            }
+            RemoteRequest::SyncPendingTransactions => {
+                to_response(id, api.sync_pending_transactions().await)
+            }
+        }
+    }
+}
+
+/// Callers waiting for a reply, keyed by [`RemoteCall::id`]
+#[derive(Default)]
+struct PendingReplies {
+    replies: HashMap<u64, oneshot::Sender<RemoteResponse>>,
+    closed: bool,
+}
+
+/// A wallet served by a [`WalletDaemon`], exposing the same [`WalletApi`] as [`LocalWallet`]
+///
+/// Calls may run concurrently over the one connection. Frames are written by a background
+/// task and replies are matched by id, so a caller dropping its future mid-call leaves the
+/// connection usable for everyone else.
+pub struct RemoteWallet {
+    outgoing: mpsc::UnboundedSender<Vec<u8>>,
+    pending: Arc<Mutex<PendingReplies>>,
+    next_id: AtomicU64,
+}
+
+impl RemoteWallet {
+    /// Connect to a wallet daemon over TCP
+    pub async fn connect(addr: impl ToSocketAddrs, auth_token: &str) -> Result<Self, WalletError> {
+        let stream = TcpStream::connect(addr).await.map_err(|e| {
+            WalletError::NetworkError(format!("Failed to connect to wallet daemon: {}", e))
+        })?;
+        let (reader, writer) = stream.into_split();
+        Self::start(reader, writer, auth_token).await
+    }
+
+    /// Connect to a wallet daemon over a Unix socket
+    #[cfg(unix)]
+    pub async fn connect_unix(
+        path: impl AsRef<std::path::Path>,
+        auth_token: &str,
+    ) -> Result<Self, WalletError> {
+        let stream = tokio::net::UnixStream::connect(path).await.map_err(|e| {
+            WalletError::NetworkError(format!("Failed to connect to wallet daemon: {}", e))
+        })?;
+        let (reader, writer) = stream.into_split();
+        Self::start(reader, writer, auth_token).await
+    }
+
+    async fn start<R, W>(reader: R, mut writer: W, auth_token: &str) -> Result<Self, WalletError>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let runtime = runtime::current()?;
+        let pending = Arc::new(Mutex::new(PendingReplies::default()));
+        let (outgoing, mut frames) = mpsc::unbounded_channel::<Vec<u8>>();
+
+        let auth = Self {
+            outgoing,
+            pending: pending.clone(),
+            next_id: AtomicU64::new(AUTH_CALL_ID + 1),
+        };
+        let auth_reply = auth.register(AUTH_CALL_ID)?;
+
+        runtime.spawn(Self::read_replies(reader, pending.clone()));
+        runtime.spawn(async move {
+            while let Some(frame) = frames.recv().await {
+                if writer.write_all(&frame).await.is_err() {
+                    break;
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+
+        auth.send(&RemoteAuth {
+            token: auth_token.to_string(),
+        })?;
+        Self::into_result::<()>(auth_reply.await)?;
+        Ok(auth)
+    }
+
+    async fn read_replies<R: AsyncRead + Unpin>(reader: R, pending: Arc<Mutex<PendingReplies>>) {
+        let mut reader = BufReader::new(reader);
+        while let Ok(Some(frame)) = read_frame(&mut reader).await {
+            let Ok(response) = serde_json::from_slice::<RemoteResponse>(&frame) else {
+                break;
+            };
+            let reply = pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .replies
+                .remove(&response.id);
+            if let Some(reply) = reply {
+                let _ = reply.send(response);
+            }
+        }
+
+        // Dropping the senders fails every call still waiting on this connection
+        let mut pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.closed = true;
+        pending.replies.clear();
+    }
+
+    fn register(&self, id: u64) -> Result<oneshot::Receiver<RemoteResponse>, WalletError> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.closed {
+            return Err(WalletError::NetworkError(
+                "Wallet daemon closed the connection".to_string(),
+            ));
+        }
+        let (sender, receiver) = oneshot::channel();
+        pending.replies.insert(id, sender);
+        Ok(receiver)
+    }
+
+    fn send<T: Serialize>(&self, frame: &T) -> Result<(), WalletError> {
+        self.outgoing.send(encode_frame(frame)?).map_err(|_| {
+            WalletError::NetworkError("Wallet daemon closed the connection".to_string())
+        })
+    }
+
+    fn into_result<T: DeserializeOwned>(
+        reply: Result<RemoteResponse, oneshot::error::RecvError>,
+    ) -> Result<T, WalletError> {
+        let response = reply.map_err(|_| {
+            WalletError::NetworkError("Wallet daemon closed the connection".to_string())
+        })?;
+        if let Some(error) = response.error {
+            return Err(WalletError::RemoteError(error));
+        }
+
+        serde_json::from_value(response.result.unwrap_or(serde_json::Value::Null))
+            .map_err(|e| WalletError::SerializationError(format!("Invalid response: {}", e)))
+    }
+
+    async fn call<T: DeserializeOwned>(&self, request: RemoteRequest) -> Result<T, WalletError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let reply = self.register(id)?;
+        if let Err(e) = self.send(&RemoteCall { id, request }) {
+            self.pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .replies
+                .remove(&id);
+            return Err(e);
+        }
+        Self::into_result(reply.await)
+    }
+
+    async fn call_bytes32(&self, request: RemoteRequest) -> Result<Bytes32, WalletError> {
+        let hex_value: String = self.call(request).await?;
+        crate::encoding::bytes32_from_hex(&hex_value)
+    }
+}
+
+impl WalletApi for RemoteWallet {
+    fn get_fingerprint(&self) -> WalletFuture<'_, u32> {
+        Box::pin(self.call(RemoteRequest::GetFingerprint))
+    }
+
+    fn get_key_summary(&self) -> WalletFuture<'_, KeySummary> {
+        Box::pin(self.call(RemoteRequest::GetKeySummary))
+    }
+
+    fn get_owner_public_key(&self) -> WalletFuture<'_, String> {
+        Box::pin(self.call(RemoteRequest::GetOwnerPublicKey))
+    }
+
+    fn get_owner_puzzle_hash(&self) -> WalletFuture<'_, Bytes32> {
+        Box::pin(self.call_bytes32(RemoteRequest::GetOwnerPuzzleHash))
+    }
+
+    fn get_addresses(&self, range: Range<u32>) -> WalletFuture<'_, Vec<ReceiveAddress>> {
+        Box::pin(self.call(RemoteRequest::GetAddresses {
+            start: range.start,
+            end: range.end,
+        }))
+    }
+
+    fn validate_address<'a>(&'a self, address: &'a str) -> WalletFuture<'a, Bytes32> {
+        Box::pin(self.call_bytes32(RemoteRequest::ValidateAddress {
+            address: address.to_string(),
+        }))
+    }
+
+    fn get_metadata(&self) -> WalletFuture<'_, WalletMetadata> {
+        Box::pin(self.call(RemoteRequest::GetMetadata))
+    }
+
+    fn create_key_ownership_signature<'a>(&'a self, nonce: &'a str) -> WalletFuture<'a, String> {
+        Box::pin(self.call(RemoteRequest::CreateKeyOwnershipSignature {
+            nonce: nonce.to_string(),
+        }))
+    }
+
+    fn get_xch_balance(&self) -> WalletFuture<'_, u64> {
+        Box::pin(self.call(RemoteRequest::GetXchBalance))
+    }
+
+    fn get_dig_balance(&self) -> WalletFuture<'_, u64> {
+        Box::pin(self.call(RemoteRequest::GetDigBalance))
+    }
+
+    fn get_cat_balance(&self, asset_id: Bytes32) -> WalletFuture<'_, u64> {
+        Box::pin(self.call(RemoteRequest::GetCatBalance {
+            asset_id: hex::encode(asset_id),
+        }))
+    }
+
+    fn send_xch<'a>(
+        &'a self,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord> {
+        Box::pin(self.call(RemoteRequest::SendXch {
+            to_address: to_address.to_string(),
+            amount,
+            fee,
+        }))
+    }
+
+    fn send_dig<'a>(
+        &'a self,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord> {
+        Box::pin(self.call(RemoteRequest::SendDig {
+            to_address: to_address.to_string(),
+            amount,
+            fee,
+        }))
+    }
+
+    fn send_cat<'a>(
+        &'a self,
+        asset_id: Bytes32,
+        to_address: &'a str,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'a, TransactionRecord> {
+        Box::pin(self.call(RemoteRequest::SendCat {
+            asset_id: hex::encode(asset_id),
+            to_address: to_address.to_string(),
+            amount,
+            fee,
+        }))
+    }
+
+    fn queue_transfer(
+        &self,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+    ) -> WalletFuture<'_, QueuedTransaction> {
+        Box::pin(self.call(RemoteRequest::QueueTransfer {
+            asset,
+            destination: hex::encode(destination),
+            amount,
+            fee,
+        }))
+    }
+
+    fn broadcast_queued<'a>(&'a self, tx_id: &'a str) -> WalletFuture<'a, Bytes32> {
+        Box::pin(self.call_bytes32(RemoteRequest::BroadcastQueued {
+            tx_id: tx_id.to_string(),
+        }))
+    }
+
+    fn get_transaction_history(
+        &self,
+        from_height: u32,
+    ) -> WalletFuture<'_, Vec<TransactionRecord>> {
+        Box::pin(self.call(RemoteRequest::GetTransactionHistory { from_height }))
+    }
+
+    fn sync_pending_transactions(&self) -> WalletFuture<'_, Vec<PendingTransaction>> {
+        Box::pin(self.call(RemoteRequest::SyncPendingTransactions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const TOKEN: &str = "test-token";
+
+    fn unsupported<'a, T: Send + 'a>() -> WalletFuture<'a, T> {
+        Box::pin(async { Err(WalletError::NoUnspentCoins) })
+    }
+
+    struct FixedWallet;
+
+    impl WalletApi for FixedWallet {
+        fn get_fingerprint(&self) -> WalletFuture<'_, u32> {
+            Box::pin(async { Ok(42) })
+        }
+        fn get_key_summary(&self) -> WalletFuture<'_, KeySummary> {
+            unsupported()
+        }
+        fn get_owner_public_key(&self) -> WalletFuture<'_, String> {
+            Box::pin(async { Ok("ab".repeat(48)) })
+        }
+        fn get_owner_puzzle_hash(&self) -> WalletFuture<'_, Bytes32> {
+            Box::pin(async { Ok(Bytes32::new([7; 32])) })
+        }
+        fn get_addresses(&self, _range: Range<u32>) -> WalletFuture<'_, Vec<ReceiveAddress>> {
+            unsupported()
+        }
+        fn validate_address<'a>(&'a self, _address: &'a str) -> WalletFuture<'a, Bytes32> {
+            unsupported()
+        }
+        fn get_metadata(&self) -> WalletFuture<'_, WalletMetadata> {
+            Box::pin(async { Ok(WalletMetadata::default()) })
+        }
+        fn create_key_ownership_signature<'a>(
+            &'a self,
+            nonce: &'a str,
+        ) -> WalletFuture<'a, String> {
+            Box::pin(async move {
+                if nonce == "slow" {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+                Ok(format!("signed:{}", nonce))
+            })
+        }
+        fn get_xch_balance(&self) -> WalletFuture<'_, u64> {
+            Box::pin(async { Ok(1_000) })
+        }
+        fn get_dig_balance(&self) -> WalletFuture<'_, u64> {
+            unsupported()
+        }
+        fn get_cat_balance(&self, asset_id: Bytes32) -> WalletFuture<'_, u64> {
+            Box::pin(async move { Ok(asset_id.as_ref()[0] as u64) })
+        }
+        fn send_xch<'a>(
+            &'a self,
+            _to_address: &'a str,
+            _amount: u64,
+            _fee: u64,
+        ) -> WalletFuture<'a, TransactionRecord> {
+            unsupported()
+        }
+        fn send_dig<'a>(
+            &'a self,
+            _to_address: &'a str,
+            _amount: u64,
+            _fee: u64,
+        ) -> WalletFuture<'a, TransactionRecord> {
+            unsupported()
+        }
+        fn send_cat<'a>(
+            &'a self,
+            _asset_id: Bytes32,
+            _to_address: &'a str,
+            _amount: u64,
+            _fee: u64,
+        ) -> WalletFuture<'a, TransactionRecord> {
+            unsupported()
+        }
+        fn queue_transfer(
+            &self,
+            _asset: Asset,
+            _destination: Bytes32,
+            _amount: u64,
+            _fee: u64,
+        ) -> WalletFuture<'_, QueuedTransaction> {
+            unsupported()
+        }
+        fn broadcast_queued<'a>(&'a self, _tx_id: &'a str) -> WalletFuture<'a, Bytes32> {
+            Box::pin(async { Ok(Bytes32::new([1; 32])) })
+        }
+        fn get_transaction_history(
+            &self,
+            _from_height: u32,
+        ) -> WalletFuture<'_, Vec<TransactionRecord>> {
+            Box::pin(async { Ok(vec![]) })
+        }
+        fn sync_pending_transactions(&self) -> WalletFuture<'_, Vec<PendingTransaction>> {
+            Box::pin(async { Ok(vec![]) })
+        }
+    }
+
+    async fn spawn_daemon() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let daemon = WalletDaemon::new(Arc::new(FixedWallet), TOKEN);
+        tokio::spawn(async move { daemon.serve(listener).await });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_remote_wallet_round_trip() {
+        let addr = spawn_daemon().await;
+
+        let remote = RemoteWallet::connect(addr, TOKEN).await.unwrap();
+        assert_eq!(remote.get_fingerprint().await.unwrap(), 42);
+        assert_eq!(
+            remote.get_owner_puzzle_hash().await.unwrap(),
+            Bytes32::new([7; 32])
+        );
+        assert_eq!(remote.get_xch_balance().await.unwrap(), 1_000);
+        assert_eq!(
+            remote.get_cat_balance(Bytes32::new([9; 32])).await.unwrap(),
+            9
+        );
+        assert_eq!(
+            remote.create_key_ownership_signature("n1").await.unwrap(),
+            "signed:n1"
+        );
+        assert_eq!(
+            remote.broadcast_queued("tx").await.unwrap(),
+            Bytes32::new([1; 32])
+        );
+        assert!(remote.get_transaction_history(0).await.unwrap().is_empty());
+
+        // Daemon-side errors come back as remote errors
+        assert!(matches!(
+            remote.get_dig_balance().await,
+            Err(WalletError::RemoteError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_call_does_not_desync_connection() {
+        let addr = spawn_daemon().await;
+        let remote = RemoteWallet::connect(addr, TOKEN).await.unwrap();
+
+        // Abandon a call before its reply arrives; the next call must get its own reply
+        let abandoned = tokio::time::timeout(
+            Duration::from_millis(20),
+            remote.create_key_ownership_signature("slow"),
+        )
+        .await;
+        assert!(abandoned.is_err());
+        assert_eq!(
+            remote.create_key_ownership_signature("n2").await.unwrap(),
+            "signed:n2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_daemon_rejects_bad_tokens_and_public_binds() {
+        let addr = spawn_daemon().await;
+        assert!(matches!(
+            RemoteWallet::connect(addr, "wrong").await,
+            Err(WalletError::RemoteError(_))
+        ));
+
+        let listener = TcpListener::bind("0.0.0.0:0").await.unwrap();
+        let daemon = WalletDaemon::new(Arc::new(FixedWallet), TOKEN);
+        assert!(matches!(
+            daemon.serve(listener).await,
+            Err(WalletError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_closes_connection() {
+        let addr = spawn_daemon().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let oversized = vec![b'a'; MAX_FRAME_LEN + 1];
+        let _ = stream.write_all(&oversized).await;
+
+        let mut reply = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut reply))
+            .await
+            .unwrap();
+        assert!(read.is_err() || reply.is_empty());
+    }
+}
@@ -0,0 +1,389 @@
+//! Coin reservations shared by services spending from the same wallet.
+//!
+//! A reservation marks a coin as claimed by an owner until it expires, so concurrent
+//! services do not build conflicting spends. Expired reservations are inert and are
+//! removed by [`CoinReservationManager::collect_garbage`], which keeps running totals
+//! for monitoring.
+//!
+//! A process that holds reservations for long can also take a lease for its owner tag
+//! and heartbeat it. When the process dies its heartbeat stops, and any other process can
+//! reclaim the owner's reservations with [`CoinReservationManager::reclaim_dead_leases`]
+//! once the grace period has passed, instead of waiting for every TTL to lapse.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::wallet::Wallet;
+use chia::protocol::Bytes32;
+use serde::{Deserialize, Serialize};
+
+const GC_STATS_KEY: &str = "gc";
+/// Heartbeat silence after which a leaseholder is presumed dead
+pub const DEFAULT_LEASE_GRACE_SECS: u64 = 60;
+
+/// A coin claimed by an owner until `expires_at`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinReservation {
+    /// Coin id as hex
+    pub coin_id: String,
+    /// Tag of the service or task holding the reservation
+    pub owner: String,
+    /// Why the coin is held, e.g. `"transfer"` or `"fee"`
+    pub purpose: String,
+    pub reserved_at: u64,
+    pub expires_at: u64,
+}
+
+impl CoinReservation {
+    /// Whether the reservation has lapsed at `now` (Unix seconds)
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// The process holding the reservations of an owner tag
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReservationLease {
+    pub owner: String,
+    /// Process id of the leaseholder, for operators
+    pub pid: u32,
+    pub started_at: u64,
+    pub heartbeat_at: u64,
+}
+
+impl ReservationLease {
+    /// Whether the leaseholder has been silent for more than `grace_secs` at `now`
+    pub fn is_dead(&self, now: u64, grace_secs: u64) -> bool {
+        now > self.heartbeat_at.saturating_add(grace_secs)
+    }
+}
+
+/// Running totals of stale reservations removed by garbage collection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReservationGcStats {
+    pub runs: u64,
+    pub total_collected: u64,
+    pub last_run_at: Option<u64>,
+    pub last_run_collected: usize,
+    /// Reservations taken back from dead leaseholders
+    #[serde(default)]
+    pub total_reclaimed: u64,
+}
+
+/// Persisted reservation table of one wallet, keyed by coin id
+pub struct CoinReservationManager {
+    reservations: FileCache<CoinReservation>,
+    leases: FileCache<ReservationLease>,
+    stats: FileCache<ReservationGcStats>,
+    config: WalletConfig,
+}
+
+impl CoinReservationManager {
+    /// Open the reservation table of a wallet under the configured `.dig` directory
+    pub fn open(wallet_name: &str, config: &WalletConfig) -> Result<Self, WalletError> {
+        Ok(Self {
+            reservations: FileCache::from_config(&format!("reservations/{}", wallet_name), config)?,
+            leases: FileCache::from_config(&format!("reservation_leases/{}", wallet_name), config)?,
+            stats: FileCache::from_config(&format!("reservation_stats/{}", wallet_name), config)?,
+            config: config.clone(),
+        })
+    }
+
+    /// Reserve a coin for `ttl_secs`, failing if another live reservation holds it
+    ///
+    /// An owner may renew its own reservation.
+    pub async fn reserve(
+        &self,
+        coin_id: Bytes32,
+        owner: &str,
+        purpose: &str,
+        ttl_secs: u64,
+    ) -> Result<CoinReservation, WalletError> {
+        let now = self.config.unix_timestamp();
+        let reservation = CoinReservation {
+            coin_id: hex::encode(coin_id),
+            owner: owner.to_string(),
+            purpose: purpose.to_string(),
+            reserved_at: now,
+            expires_at: now + ttl_secs,
+        };
+
+        self.reservations
+            .update(&reservation.coin_id, |existing| match existing {
+                Some(held) if !held.is_expired(now) && held.owner != owner => {
+                    Err(WalletError::CoinReserved {
+                        coin_id: held.coin_id.clone(),
+                        owner: held.owner,
+                    })
+                }
+                _ => Ok((reservation.clone(), ())),
+            })
+            .await?;
+
+        Ok(reservation)
+    }
+
+    /// The live reservation of a coin, if any
+    pub fn get(&self, coin_id: Bytes32) -> Result<Option<CoinReservation>, WalletError> {
+        let now = self.config.unix_timestamp();
+        Ok(self
+            .reservations
+            .get(&hex::encode(coin_id))?
+            .filter(|reservation| !reservation.is_expired(now)))
+    }
+
+    /// Live reservations, soonest to expire first
+    pub fn list_reservations(&self) -> Result<Vec<CoinReservation>, WalletError> {
+        let now = self.config.unix_timestamp();
+        let mut reservations = Vec::new();
+        for key in self.reservations.get_cached_keys()? {
+            if let Some(reservation) = self.reservations.get(&key)? {
+                if !reservation.is_expired(now) {
+                    reservations.push(reservation);
+                }
+            }
+        }
+
+        reservations.sort_by(|a, b| (a.expires_at, &a.coin_id).cmp(&(b.expires_at, &b.coin_id)));
+        Ok(reservations)
+    }
+
+    /// Drop the reservation of a coin regardless of owner; returns whether one existed
+    pub async fn release(&self, coin_id: Bytes32) -> Result<bool, WalletError> {
+        let key = hex::encode(coin_id);
+        let existed = self.reservations.get(&key)?.is_some();
+        self.reservations.delete(&key).await?;
+        Ok(existed)
+    }
+
+    /// Remove expired reservations and return how many were removed
+    pub async fn collect_garbage(&self) -> Result<usize, WalletError> {
+        let now = self.config.unix_timestamp();
+        let mut collected = 0;
+
+        for key in self.reservations.get_cached_keys()? {
+            match self.reservations.get(&key)? {
+                Some(reservation) if reservation.is_expired(now) => {
+                    self.reservations.delete(&key).await?;
+                    collected += 1;
+                }
+                _ => {}
+            }
+        }
+
+        self.stats
+            .update(GC_STATS_KEY, |stats| {
+                let mut stats = stats.unwrap_or_default();
+                stats.runs += 1;
+                stats.total_collected += collected as u64;
+                stats.last_run_at = Some(now);
+                stats.last_run_collected = collected;
+                Ok((stats, ()))
+            })
+            .await?;
+
+        Ok(collected)
+    }
+
+    /// Take the lease of `owner` for this process, replacing a previous holder's
+    pub async fn acquire_lease(&self, owner: &str) -> Result<ReservationLease, WalletError> {
+        let now = self.config.unix_timestamp();
+        let lease = ReservationLease {
+            owner: owner.to_string(),
+            pid: std::process::id(),
+            started_at: now,
+            heartbeat_at: now,
+        };
+        self.leases.set(owner, &lease).await?;
+        Ok(lease)
+    }
+
+    /// Record that the holder of `owner`'s lease is alive
+    ///
+    /// Returns `false` if the lease was reclaimed meanwhile; the owner's reservations are
+    /// then gone and must be taken again.
+    pub async fn heartbeat(&self, owner: &str) -> Result<bool, WalletError> {
+        let now = self.config.unix_timestamp();
+        let Some(lease) = self.leases.get(owner)? else {
+            return Ok(false);
+        };
+        let renewed = ReservationLease {
+            heartbeat_at: now,
+            ..lease.clone()
+        };
+        // Never bring back a lease reclaimed since it was read
+        Ok(self
+            .leases
+            .compare_and_set(owner, Some(&lease), &renewed)
+            .await?
+            || self.leases.get(owner)?.is_some())
+    }
+
+    /// Give up `owner`'s lease and release its reservations; returns how many
+    pub async fn release_lease(&self, owner: &str) -> Result<usize, WalletError> {
+        let released = self.release_owner(owner).await?;
+        self.leases.delete(owner).await?;
+        Ok(released)
+    }
+
+    /// Release the reservations of leaseholders silent for more than `grace_secs`
+    ///
+    /// Returns how many reservations were reclaimed. Owners without a lease keep their
+    /// reservations until they expire.
+    pub async fn reclaim_dead_leases(&self, grace_secs: u64) -> Result<usize, WalletError> {
+        let now = self.config.unix_timestamp();
+        let mut reclaimed = 0;
+        for owner in self.leases.get_cached_keys()? {
+            match self.leases.get(&owner)? {
+                Some(lease) if lease.is_dead(now, grace_secs) => {
+                    reclaimed += self.release_owner(&lease.owner).await?;
+                    self.leases.delete(&owner).await?;
+                }
+                _ => {}
+            }
+        }
+
+        if reclaimed > 0 {
+            self.stats
+                .update(GC_STATS_KEY, |stats| {
+                    let mut stats = stats.unwrap_or_default();
+                    stats.total_reclaimed += reclaimed as u64;
+                    Ok((stats, ()))
+                })
+                .await?;
+        }
+        Ok(reclaimed)
+    }
+
+    /// Leases currently recorded, dead or alive
+    pub fn list_leases(&self) -> Result<Vec<ReservationLease>, WalletError> {
+        let mut leases = Vec::new();
+        for owner in self.leases.get_cached_keys()? {
+            leases.extend(self.leases.get(&owner)?);
+        }
+        leases.sort_by(|a, b| a.owner.cmp(&b.owner));
+        Ok(leases)
+    }
+
+    async fn release_owner(&self, owner: &str) -> Result<usize, WalletError> {
+        let mut released = 0;
+        for key in self.reservations.get_cached_keys()? {
+            if matches!(self.reservations.get(&key)?, Some(held) if held.owner == owner) {
+                self.reservations.delete(&key).await?;
+                released += 1;
+            }
+        }
+        Ok(released)
+    }
+
+    /// Garbage collection totals since the table was created
+    pub fn gc_stats(&self) -> Result<ReservationGcStats, WalletError> {
+        Ok(self.stats.get(GC_STATS_KEY)?.unwrap_or_default())
+    }
+}
+
+impl Wallet {
+    /// Open the coin reservation table of this wallet
+    pub fn coin_reservations(&self) -> Result<CoinReservationManager, WalletError> {
+        CoinReservationManager::open(&self.cache_name(), self.get_config())
+    }
+
+    /// Release a coin's reservation by hand, whoever holds it
+    pub async fn release_reservation(&self, coin_id: Bytes32) -> Result<bool, WalletError> {
+        self.coin_reservations()?.release(coin_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    fn manager_at(home: &std::path::Path, secs: u64) -> CoinReservationManager {
+        let config = WalletConfig::new().with_environment(
+            SandboxEnvironment::new(home).with_fixed_time(UNIX_EPOCH + Duration::from_secs(secs)),
+        );
+        CoinReservationManager::open("reserving", &config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reservation_lifecycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = manager_at(temp_dir.path(), 1_000);
+        let (short, long) = (Bytes32::new([1; 32]), Bytes32::new([2; 32]));
+
+        manager.reserve(long, "api", "transfer", 600).await.unwrap();
+        manager.reserve(short, "api", "fee", 60).await.unwrap();
+        assert!(matches!(
+            manager.reserve(short, "batch", "transfer", 60).await,
+            Err(WalletError::CoinReserved { .. })
+        ));
+
+        let listed = manager.list_reservations().unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].coin_id, hex::encode(short));
+        assert_eq!(listed[0].purpose, "fee");
+
+        // A lapsed reservation is hidden and can be taken over by another owner
+        let later = manager_at(temp_dir.path(), 1_100);
+        assert_eq!(later.list_reservations().unwrap().len(), 1);
+        assert!(later.get(short).unwrap().is_none());
+        later.reserve(short, "batch", "transfer", 60).await.unwrap();
+        assert!(later.release(short).await.unwrap());
+        assert!(!later.release(short).await.unwrap());
+
+        let expired = manager_at(temp_dir.path(), 2_000);
+        assert_eq!(expired.collect_garbage().await.unwrap(), 1);
+        assert_eq!(expired.collect_garbage().await.unwrap(), 0);
+        let stats = expired.gc_stats().unwrap();
+        assert_eq!((stats.runs, stats.total_collected), (2, 1));
+        assert_eq!(stats.last_run_at, Some(2_000));
+    }
+
+    #[tokio::test]
+    async fn test_dead_leaseholder_reservations_are_reclaimed() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = manager_at(temp_dir.path(), 1_000);
+        let (crashed, alive) = (Bytes32::new([1; 32]), Bytes32::new([2; 32]));
+
+        manager.acquire_lease("worker-1").await.unwrap();
+        manager
+            .reserve(crashed, "worker-1", "transfer", 3_600)
+            .await
+            .unwrap();
+        manager.acquire_lease("worker-2").await.unwrap();
+        manager
+            .reserve(alive, "worker-2", "transfer", 3_600)
+            .await
+            .unwrap();
+
+        // worker-2 keeps heartbeating, worker-1 crashed
+        let later = manager_at(temp_dir.path(), 1_050);
+        assert!(later.heartbeat("worker-2").await.unwrap());
+        assert_eq!(
+            later
+                .reclaim_dead_leases(DEFAULT_LEASE_GRACE_SECS)
+                .await
+                .unwrap(),
+            0
+        );
+
+        let after_grace = manager_at(temp_dir.path(), 1_070);
+        assert_eq!(
+            after_grace
+                .reclaim_dead_leases(DEFAULT_LEASE_GRACE_SECS)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(after_grace.get(crashed).unwrap().is_none());
+        assert!(after_grace.get(alive).unwrap().is_some());
+        assert!(!after_grace.heartbeat("worker-1").await.unwrap());
+        assert_eq!(after_grace.gc_stats().unwrap().total_reclaimed, 1);
+
+        assert_eq!(after_grace.release_lease("worker-2").await.unwrap(), 1);
+        assert!(after_grace.list_leases().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+//! Guarded reveal of a wallet's mnemonic.
+//!
+//! [`Wallet::reveal_mnemonic`] shows the words only after the caller's confirmation
+//! callback agrees and, for passphrase-protected keyring entries, after the keyring
+//! passphrase is given again. Every attempt is appended to an audit log under
+//! `~/.dig/audit/` with its outcome, never with the words themselves. The plain
+//! `Wallet::get_mnemonic` getter is only available with the `plain-mnemonic` feature.
+
+use crate::error::WalletError;
+use crate::wallet::Wallet;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// What the confirmation callback of [`Wallet::reveal_mnemonic`] is asked to approve
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevealRequest<'a> {
+    pub wallet_name: &'a str,
+    /// Whether the keyring entry is passphrase-protected, so the passphrase was checked
+    pub password_protected: bool,
+}
+
+/// How a reveal attempt ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevealOutcome {
+    Revealed,
+    /// The confirmation callback refused
+    Declined,
+    /// The keyring passphrase was missing or wrong
+    Denied,
+}
+
+/// One line of the mnemonic reveal audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevealAuditEntry {
+    /// Unix seconds
+    pub at: u64,
+    pub wallet_name: String,
+    pub outcome: RevealOutcome,
+}
+
+impl Wallet {
+    /// Pass the mnemonic to `reveal` once `confirm` approves, recording the attempt
+    ///
+    /// Passphrase-protected keyring entries also need `passphrase`; a missing one fails
+    /// with [`WalletError::KeyringLocked`] and a wrong one with
+    /// [`WalletError::InvalidPassphrase`]. A refused confirmation fails with
+    /// [`WalletError::RevealDeclined`]. Nothing is revealed if the audit entry cannot be
+    /// written.
+    pub async fn reveal_mnemonic<R>(
+        &self,
+        passphrase: Option<&str>,
+        confirm: impl FnOnce(&RevealRequest<'_>) -> bool,
+        reveal: impl FnOnce(&str) -> R,
+    ) -> Result<R, WalletError> {
+        self.with_mnemonic(|_| ())?;
+        let password_protected = Self::keyring_entries(self.get_config())
+            .await?
+            .into_iter()
+            .any(|info| info.name == self.get_wallet_name() && info.password_protected);
+
+        if password_protected {
+            let checked = match passphrase {
+                None => Err(WalletError::KeyringLocked(
+                    self.get_wallet_name().to_string(),
+                )),
+                Some(passphrase) => {
+                    let config = self
+                        .get_config()
+                        .clone()
+                        .with_keyring_passphrase(passphrase);
+                    Self::get_wallet_from_keyring(self.get_wallet_name(), &config)
+                        .await
+                        .map(|_| ())
+                }
+            };
+            if let Err(e) = checked {
+                self.audit_reveal(RevealOutcome::Denied)?;
+                return Err(e);
+            }
+        }
+
+        let request = RevealRequest {
+            wallet_name: self.get_wallet_name(),
+            password_protected,
+        };
+        if !confirm(&request) {
+            self.audit_reveal(RevealOutcome::Declined)?;
+            return Err(WalletError::RevealDeclined);
+        }
+
+        self.audit_reveal(RevealOutcome::Revealed)?;
+        self.with_mnemonic(reveal)
+    }
+
+    /// The reveal attempts recorded for this wallet, oldest first
+    pub fn mnemonic_reveal_log(&self) -> Result<Vec<RevealAuditEntry>, WalletError> {
+        let path = self.reveal_log_path()?;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(WalletError::FileSystemError(e.to_string())),
+        };
+        content
+            .lines()
+            .map(|line| {
+                serde_json::from_str(line).map_err(|e| {
+                    WalletError::SerializationError(format!("Invalid audit entry: {}", e))
+                })
+            })
+            .collect()
+    }
+
+    fn reveal_log_path(&self) -> Result<PathBuf, WalletError> {
+        Ok(self.get_config().dig_dir()?.join("audit").join(format!(
+            "{}.mnemonic.log",
+            log_file_stem(self.get_wallet_name())
+        )))
+    }
+
+    fn audit_reveal(&self, outcome: RevealOutcome) -> Result<(), WalletError> {
+        let entry = RevealAuditEntry {
+            at: self.get_config().unix_timestamp(),
+            wallet_name: self.get_wallet_name().to_string(),
+            outcome,
+        };
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+        line.push('\n');
+
+        let path = self.reveal_log_path()?;
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            file.write_all(line.as_bytes())?;
+            file.sync_all()
+        };
+        write().map_err(|e| {
+            WalletError::FileSystemError(format!("Failed to write reveal audit log: {}", e))
+        })
+    }
+}
+
+/// `wallet_name` made safe as a file name
+///
+/// Bytes other than ASCII letters, digits, `_` and `-` are percent-encoded, so every
+/// wallet name gets its own log and none can reach outside the audit directory.
+fn log_file_stem(wallet_name: &str) -> String {
+    let mut stem = String::with_capacity(wallet_name.len());
+    for byte in wallet_name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+            stem.push(char::from(byte));
+        } else {
+            stem.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    stem
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_reveal_needs_confirmation_passphrase_and_is_audited() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new()
+            .with_environment(SandboxEnvironment::new(temp_dir.path()))
+            .with_keyring_passphrase("secret");
+        let mnemonic = Wallet::create_new_wallet_with_config("vault", &config)
+            .await
+            .unwrap()
+            .into_string();
+        let wallet = Wallet::load_with_config(Some("vault".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        let declined = wallet
+            .reveal_mnemonic(Some("secret"), |_| false, |words| words.to_string())
+            .await;
+        assert!(matches!(declined, Err(WalletError::RevealDeclined)));
+        let missing = wallet
+            .reveal_mnemonic(None, |_| true, |words| words.to_string())
+            .await;
+        assert!(matches!(missing, Err(WalletError::KeyringLocked(_))));
+        let wrong = wallet
+            .reveal_mnemonic(Some("guess"), |_| true, |words| words.to_string())
+            .await;
+        assert!(matches!(wrong, Err(WalletError::InvalidPassphrase)));
+
+        let revealed = wallet
+            .reveal_mnemonic(
+                Some("secret"),
+                |request| request.wallet_name == "vault" && request.password_protected,
+                |words| words.to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(revealed, mnemonic);
+
+        let outcomes: Vec<RevealOutcome> = wallet
+            .mnemonic_reveal_log()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.outcome)
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                RevealOutcome::Declined,
+                RevealOutcome::Denied,
+                RevealOutcome::Denied,
+                RevealOutcome::Revealed
+            ]
+        );
+        let log = fs::read_to_string(wallet.reveal_log_path().unwrap()).unwrap();
+        assert!(!log.contains(mnemonic.split_whitespace().next().unwrap()));
+    }
+
+    #[test]
+    fn test_reveal_log_stays_in_audit_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let audit_dir = config.dig_dir().unwrap().join("audit");
+
+        for name in ["../../escape", "/etc/passwd", "a/b", "..", "vault"] {
+            let wallet = Wallet::new_with_config(None, name.to_string(), config.clone());
+            let path = wallet.reveal_log_path().unwrap();
+            assert_eq!(path.parent().unwrap(), audit_dir, "{}", name);
+        }
+        assert_eq!(log_file_stem("../a b"), "%2E%2E%2Fa%20b");
+        assert_ne!(log_file_stem("a/b"), log_file_stem("a%2Fb"));
+    }
+}
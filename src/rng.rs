@@ -0,0 +1,75 @@
+use crate::error::WalletError;
+use rand::RngCore;
+use std::fmt::Debug;
+
+/// Source of the entropy used for mnemonics, salts and nonces
+///
+/// Embedders backed by an HSM or a hardware RNG implement this and install it
+/// with [`WalletConfig::with_rng`](crate::WalletConfig::with_rng).
+pub trait RngProvider: Send + Sync + Debug {
+    /// Fill `dest` entirely with random bytes, or fail without partial output being used
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), WalletError>;
+}
+
+/// The operating system's CSPRNG
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsRngProvider;
+
+impl RngProvider for OsRngProvider {
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), WalletError> {
+        rand::rngs::OsRng
+            .try_fill_bytes(dest)
+            .map_err(|e| WalletError::CryptoError(format!("Entropy source failed: {}", e)))
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use crate::wallet::Wallet;
+    use tempfile::TempDir;
+
+    #[derive(Debug)]
+    struct ZeroRng;
+
+    impl RngProvider for ZeroRng {
+        fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), WalletError> {
+            dest.fill(0);
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct FailingRng;
+
+    impl RngProvider for FailingRng {
+        fn fill_bytes(&self, _dest: &mut [u8]) -> Result<(), WalletError> {
+            Err(WalletError::CryptoError("hardware RNG offline".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injected_rng_drives_wallet_creation() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new()
+            .with_environment(SandboxEnvironment::new(temp_dir.path()))
+            .with_rng(ZeroRng);
+
+        let mnemonic = Wallet::create_new_wallet_with_config("zero", &config)
+            .await
+            .unwrap()
+            .into_string();
+        assert!(mnemonic.starts_with("abandon abandon"));
+        assert!(mnemonic.ends_with(" art"));
+
+        let failing = config.clone().with_rng(FailingRng);
+        let result = Wallet::create_new_wallet_with_config("never", &failing).await;
+        assert!(matches!(result, Err(WalletError::CryptoError(_))));
+        assert_eq!(
+            Wallet::list_wallets_with_config(&config).await.unwrap(),
+            vec!["zero".to_string()]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Async runtime integration
+//!
+//! The wallet never spawns onto an assumed global runtime. Background work goes
+//! through a [`RuntimeHandle`]: the one set on the [`WalletConfig`](crate::WalletConfig),
+//! else the one installed with [`init`], else the ambient tokio runtime.
+
+#[cfg(feature = "peer")]
+use crate::cancel::CancellationToken;
+use crate::error::WalletError;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+/// Boxed task handed to a [`Spawner`]
+pub type SpawnFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Executor the wallet spawns its background tasks on
+///
+/// Embedders running actix, async-std or their own executor implement this and
+/// pass it to [`init`] or [`WalletConfig::with_runtime`](crate::WalletConfig::with_runtime).
+pub trait Spawner: Send + Sync + Debug {
+    /// Run `future` to completion in the background
+    fn spawn(&self, future: SpawnFuture);
+}
+
+/// Spawns onto a specific tokio runtime, which need not be the caller's
+#[cfg(feature = "peer")]
+#[derive(Debug, Clone)]
+pub struct TokioSpawner(pub tokio::runtime::Handle);
+
+#[cfg(feature = "peer")]
+impl Spawner for TokioSpawner {
+    fn spawn(&self, future: SpawnFuture) {
+        self.0.spawn(future);
+    }
+}
+
+/// Cheaply cloneable handle to a [`Spawner`]
+#[derive(Debug, Clone)]
+pub struct RuntimeHandle {
+    spawner: Arc<dyn Spawner>,
+}
+
+impl RuntimeHandle {
+    pub fn new(spawner: impl Spawner + 'static) -> Self {
+        Self::from_shared(Arc::new(spawner))
+    }
+
+    pub fn from_shared(spawner: Arc<dyn Spawner>) -> Self {
+        Self { spawner }
+    }
+
+    /// Handle spawning onto the given tokio runtime
+    #[cfg(feature = "peer")]
+    pub fn tokio(handle: tokio::runtime::Handle) -> Self {
+        Self::new(TokioSpawner(handle))
+    }
+
+    pub fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        self.spawner.spawn(Box::pin(future));
+    }
+}
+
+static GLOBAL_RUNTIME: RwLock<Option<RuntimeHandle>> = RwLock::new(None);
+
+/// Install the runtime the crate spawns on when no [`WalletConfig`](crate::WalletConfig)
+/// overrides it; calling again replaces the previous handle
+pub fn init(handle: RuntimeHandle) {
+    let mut global = GLOBAL_RUNTIME
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *global = Some(handle);
+}
+
+/// The runtime installed with [`init`], falling back to the tokio runtime of the caller
+pub fn current() -> Result<RuntimeHandle, WalletError> {
+    let global = GLOBAL_RUNTIME
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(handle) = global.as_ref() {
+        return Ok(handle.clone());
+    }
+
+    #[cfg(feature = "peer")]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return Ok(RuntimeHandle::tokio(handle));
+    }
+
+    Err(WalletError::InvalidConfig(
+        "No async runtime available; call dig_wallet::init with a RuntimeHandle".to_string(),
+    ))
+}
+
+/// Bounded fan-out over a [`RuntimeHandle`], yielding results as tasks finish
+///
+/// Works on any executor: completion is reported over a tokio channel, which needs no tokio runtime.
+/// Dropping the set aborts the tasks still in flight, so no peer request outlives its caller.
+#[cfg(feature = "peer")]
+pub(crate) struct TaskSet<T> {
+    runtime: RuntimeHandle,
+    cancellation: CancellationToken,
+    sender: tokio::sync::mpsc::UnboundedSender<Option<T>>,
+    receiver: tokio::sync::mpsc::UnboundedReceiver<Option<T>>,
+    in_flight: usize,
+}
+
+#[cfg(feature = "peer")]
+impl<T: Send + 'static> TaskSet<T> {
+    pub(crate) fn new(runtime: RuntimeHandle) -> Self {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        Self {
+            runtime,
+            cancellation: CancellationToken::new(),
+            sender,
+            receiver,
+            in_flight: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.in_flight
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.in_flight == 0
+    }
+
+    pub(crate) fn spawn(&mut self, future: impl Future<Output = T> + Send + 'static) {
+        let guard = CompletionGuard {
+            sender: Some(self.sender.clone()),
+        };
+        let cancellation = self.cancellation.clone();
+        self.in_flight += 1;
+        self.runtime.spawn(async move {
+            tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => {}
+                output = future => guard.complete(output),
+            }
+        });
+    }
+
+    /// Next finished task, or `None` once nothing is in flight
+    ///
+    /// A task that panicked or was dropped by its executor yields an error.
+    pub(crate) async fn join_next(&mut self) -> Option<Result<T, WalletError>> {
+        if self.in_flight == 0 {
+            return None;
+        }
+
+        // We hold a sender ourselves, so the channel never closes while tasks are in flight
+        let output = self.receiver.recv().await.flatten();
+        self.in_flight -= 1;
+        Some(output.ok_or_else(|| {
+            WalletError::InvalidConfig("Background task did not run to completion".to_string())
+        }))
+    }
+}
+
+#[cfg(feature = "peer")]
+impl<T> Drop for TaskSet<T> {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
+    }
+}
+
+/// Reports a task's output, or its absence if the task is unwound or dropped first
+#[cfg(feature = "peer")]
+struct CompletionGuard<T> {
+    sender: Option<tokio::sync::mpsc::UnboundedSender<Option<T>>>,
+}
+
+#[cfg(feature = "peer")]
+impl<T> CompletionGuard<T> {
+    fn complete(mut self, output: T) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(Some(output));
+        }
+    }
+}
+
+#[cfg(feature = "peer")]
+impl<T> Drop for CompletionGuard<T> {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(None);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "peer"))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug)]
+    struct CountingSpawner {
+        inner: TokioSpawner,
+        spawned: Arc<AtomicUsize>,
+    }
+
+    impl Spawner for CountingSpawner {
+        fn spawn(&self, future: SpawnFuture) {
+            self.spawned.fetch_add(1, Ordering::SeqCst);
+            self.inner.spawn(future);
+        }
+    }
+
+    fn failing_task() -> u32 {
+        panic!("task failure")
+    }
+
+    #[tokio::test]
+    async fn test_task_set_uses_explicit_spawner() {
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let runtime = RuntimeHandle::new(CountingSpawner {
+            inner: TokioSpawner(tokio::runtime::Handle::current()),
+            spawned: spawned.clone(),
+        });
+
+        let mut tasks = TaskSet::new(runtime);
+        tasks.spawn(async { 1u32 });
+        tasks.spawn(async { 2u32 });
+        tasks.spawn(async { failing_task() });
+        assert_eq!(tasks.len(), 3);
+
+        let mut outputs = Vec::new();
+        let mut failures = 0;
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok(output) => outputs.push(output),
+                Err(_) => failures += 1,
+            }
+        }
+        outputs.sort();
+
+        assert_eq!(outputs, vec![1, 2]);
+        assert_eq!(failures, 1);
+        assert!(tasks.is_empty());
+        assert_eq!(spawned.load(Ordering::SeqCst), 3);
+    }
+}
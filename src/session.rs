@@ -0,0 +1,304 @@
+//! Auto-locking wallet sessions.
+//!
+//! A wallet opened with [`Wallet::open_session`] keeps its decrypted mnemonic only while
+//! the session is unlocked. [`Wallet::lock`] drops it at once, and so does a watcher task
+//! on the configured runtime ([`crate::WalletConfig::runtime`]) once the wallet has gone
+//! unused for the configured idle timeout
+//! ([`crate::WalletConfig::with_session_idle_timeout`]). While locked, every call needing
+//! a secret key fails with [`WalletError::WalletLocked`] until
+//! [`Wallet::unlock_session`] decrypts the keyring entry again. The master public key is
+//! kept, so addresses and balances stay available while locked. Clones of a session
+//! wallet share its lock state.
+
+use crate::config::{KeyringId, WalletConfig};
+use crate::error::WalletError;
+use crate::memlock::SecretString;
+use crate::runtime::RuntimeHandle;
+use crate::wallet::Wallet;
+use chia::bls::PublicKey;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+/// Idle time after which a session locks unless configured otherwise
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Live sessions and the keyring each was unlocked from
+static SESSIONS: Mutex<Vec<(KeyringId, Weak<WalletSession>)>> = Mutex::new(Vec::new());
+
+/// Lock every live session unlocked from the keyring `config` reads
+///
+/// Called once a passphrase change is committed, so no session keeps a mnemonic
+/// decrypted under the old passphrase.
+pub(crate) fn lock_sessions(config: &WalletConfig) -> Result<(), WalletError> {
+    let keyring = config.keyring_id()?;
+    let sessions = SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    for session in sessions
+        .iter()
+        .filter(|(id, _)| *id == keyring)
+        .filter_map(|(_, session)| session.upgrade())
+    {
+        session.lock();
+    }
+    Ok(())
+}
+
+fn register(session: &Arc<WalletSession>, keyring: KeyringId) {
+    let mut sessions = SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    sessions.retain(|(_, session)| session.strong_count() > 0);
+    sessions.push((keyring, Arc::downgrade(session)));
+}
+
+struct SessionState {
+    mnemonic: Option<SecretString>,
+    master_public_key: Option<PublicKey>,
+    last_used: Instant,
+}
+
+/// The lock state shared by clones of a session wallet
+#[derive(Debug)]
+pub(crate) struct WalletSession {
+    state: Mutex<SessionState>,
+    idle_timeout: Duration,
+    /// Where the idle watcher runs; `None` leaves the timeout to be enforced lazily
+    #[cfg_attr(
+        not(any(feature = "cache", feature = "keyring-file")),
+        allow(dead_code)
+    )]
+    runtime: Option<RuntimeHandle>,
+    /// Whether the session's one watcher is running
+    watching: AtomicBool,
+}
+
+impl std::fmt::Debug for SessionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionState")
+            .field("unlocked", &self.mnemonic.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl WalletSession {
+    fn new(idle_timeout: Duration, runtime: Option<RuntimeHandle>) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(SessionState {
+                mnemonic: None,
+                master_public_key: None,
+                last_used: Instant::now(),
+            }),
+            idle_timeout,
+            runtime,
+            watching: AtomicBool::new(false),
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The state, with the mnemonic dropped first if the session sat idle too long
+    fn current_state(&self) -> MutexGuard<'_, SessionState> {
+        let mut state = self.state();
+        if state.last_used.elapsed() >= self.idle_timeout {
+            state.mnemonic = None;
+        }
+        state
+    }
+
+    fn unlock(self: &Arc<Self>, mnemonic: String) {
+        let mut state = self.state();
+        state.mnemonic = Some(SecretString::new(mnemonic));
+        state.last_used = Instant::now();
+        // Checked under the state lock, so a watcher stopping now is seen as stopped
+        if !self.watching.swap(true, Ordering::SeqCst) {
+            self.watch();
+        }
+    }
+
+    /// Spawn the session's watcher, which drops the mnemonic once the session has been
+    /// idle for the timeout and stops when the session locks
+    ///
+    /// Without a runtime the timeout is only enforced when the session is next used.
+    #[cfg(any(feature = "cache", feature = "keyring-file"))]
+    fn watch(self: &Arc<Self>) {
+        let Some(runtime) = &self.runtime else {
+            self.watching.store(false, Ordering::SeqCst);
+            return;
+        };
+        let session = Arc::downgrade(self);
+        runtime.spawn(async move {
+            while let Some(remaining) = session.upgrade().and_then(|session| session.idle_left()) {
+                tokio::time::sleep(remaining).await;
+            }
+        });
+    }
+
+    /// Idle time left before the session locks, locking it and stopping the watcher
+    /// once none is left
+    #[cfg(any(feature = "cache", feature = "keyring-file"))]
+    fn idle_left(&self) -> Option<Duration> {
+        let state = self.current_state();
+        if state.mnemonic.is_none() {
+            self.watching.store(false, Ordering::SeqCst);
+            return None;
+        }
+        Some(self.idle_timeout.saturating_sub(state.last_used.elapsed()))
+    }
+
+    /// Without tokio's timer the idle timeout is only enforced when the session is next used
+    #[cfg(not(any(feature = "cache", feature = "keyring-file")))]
+    fn watch(self: &Arc<Self>) {
+        self.watching.store(false, Ordering::SeqCst);
+    }
+
+    pub(crate) fn lock(&self) {
+        self.state().mnemonic = None;
+    }
+
+    pub(crate) fn master_public_key(&self) -> Option<PublicKey> {
+        self.state().master_public_key
+    }
+
+    pub(crate) fn is_locked(&self) -> bool {
+        self.current_state().mnemonic.is_none()
+    }
+
+    /// Run `f` on the mnemonic and restart the idle timer
+    pub(crate) fn with_secret<R>(
+        &self,
+        f: impl FnOnce(&SecretString) -> R,
+    ) -> Result<R, WalletError> {
+        let mut state = self.current_state();
+        let result = f(state.mnemonic.as_ref().ok_or(WalletError::WalletLocked)?);
+        state.last_used = Instant::now();
+        Ok(result)
+    }
+}
+
+impl Wallet {
+    /// Open `wallet_name` as an auto-locking session
+    ///
+    /// `passphrase` is needed for passphrase-protected keyring entries.
+    pub async fn open_session(
+        wallet_name: &str,
+        passphrase: Option<&str>,
+    ) -> Result<Self, WalletError> {
+        Self::open_session_with_config(wallet_name, passphrase, &WalletConfig::default()).await
+    }
+
+    /// Open a session using an explicit configuration, which sets the idle timeout
+    pub async fn open_session_with_config(
+        wallet_name: &str,
+        passphrase: Option<&str>,
+        config: &WalletConfig,
+    ) -> Result<Self, WalletError> {
+        let session = WalletSession::new(config.session_idle_timeout(), config.runtime().ok());
+        register(&session, config.keyring_id()?);
+        let wallet = Self::new_with_config(None, wallet_name.to_string(), config.clone())
+            .with_session(session);
+        wallet.unlock_session(passphrase).await?;
+        Ok(wallet)
+    }
+
+    /// Decrypt the keyring entry again and restart the idle timer
+    ///
+    /// Fails with [`WalletError::InvalidConfig`] for wallets not opened as a session.
+    pub async fn unlock_session(&self, passphrase: Option<&str>) -> Result<(), WalletError> {
+        let session = self.session().ok_or_else(|| {
+            WalletError::InvalidConfig(format!(
+                "Wallet {} was not opened as a session",
+                self.get_wallet_name()
+            ))
+        })?;
+        let config = match passphrase {
+            Some(passphrase) => self
+                .get_config()
+                .clone()
+                .with_keyring_passphrase(passphrase),
+            None => self.get_config().clone(),
+        };
+        let mnemonic = Self::get_wallet_from_keyring(self.get_wallet_name(), &config)
+            .await?
+            .ok_or_else(|| WalletError::WalletNotFound(self.get_wallet_name().to_string()))?;
+        session.unlock(mnemonic.to_string());
+        let master_public_key = self.get_master_secret_key().await?.public_key();
+        session.state().master_public_key = Some(master_public_key);
+        Ok(())
+    }
+
+    /// Drop the decrypted mnemonic of a session wallet now
+    ///
+    /// Has no effect on wallets not opened as a session.
+    pub fn lock(&self) {
+        if let Some(session) = self.session() {
+            session.lock();
+        }
+    }
+
+    /// Whether this is a session wallet whose mnemonic has been dropped
+    pub fn is_locked(&self) -> bool {
+        self.session().is_some_and(|session| session.is_locked())
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use crate::runtime::{SpawnFuture, Spawner};
+    use tempfile::TempDir;
+
+    #[derive(Debug)]
+    struct TestSpawner;
+
+    impl Spawner for TestSpawner {
+        fn spawn(&self, future: SpawnFuture) {
+            tokio::spawn(future);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_locks_when_idle_and_unlocks_again() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new()
+            .with_environment(SandboxEnvironment::new(temp_dir.path()))
+            .with_keyring_passphrase("secret");
+        Wallet::create_new_wallet_with_config("session", &config)
+            .await
+            .unwrap();
+        let config = WalletConfig::new()
+            .with_environment(SandboxEnvironment::new(temp_dir.path()))
+            .with_runtime(RuntimeHandle::new(TestSpawner))
+            .with_session_idle_timeout(Duration::from_millis(500));
+
+        assert!(matches!(
+            Wallet::open_session_with_config("session", None, &config).await,
+            Err(WalletError::KeyringLocked(_))
+        ));
+        let wallet = Wallet::open_session_with_config("session", Some("secret"), &config)
+            .await
+            .unwrap();
+        let public_key = wallet.get_public_synthetic_key().await.unwrap();
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+        assert!(!wallet.is_locked());
+
+        let clone = wallet.clone();
+        wallet.lock();
+        assert!(clone.is_locked());
+        assert!(matches!(
+            clone.create_key_ownership_signature("nonce").await,
+            Err(WalletError::WalletLocked)
+        ));
+        assert_eq!(clone.get_owner_puzzle_hash().await.unwrap(), owner);
+
+        wallet.unlock_session(Some("secret")).await.unwrap();
+        assert_eq!(wallet.get_public_synthetic_key().await.unwrap(), public_key);
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert!(wallet.session().unwrap().state().mnemonic.is_none());
+        assert!(wallet.is_locked());
+        assert!(matches!(
+            wallet.get_master_secret_key().await,
+            Err(WalletError::WalletLocked)
+        ));
+    }
+}
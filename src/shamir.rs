@@ -0,0 +1,312 @@
+//! Splitting a mnemonic into Shamir shares.
+//!
+//! Like SLIP-39, the mnemonic's entropy is split with Shamir's secret sharing over GF(256)
+//! so that any `threshold` of the shares recover it and fewer reveal nothing. Shares are
+//! not SLIP-39 word lists: each is a bech32m string (`digshare1...`) carrying a random
+//! split identifier, the threshold and its index, so typos and shares of different splits
+//! are detected. As in SLIP-39, a digest of the entropy is shared along with it rather than
+//! stored in the clear, so a share reveals nothing about the secret, and a recovery from
+//! altered shares is detected. Shares of non-English mnemonics also record the wordlist,
+//! since the key is derived from the words rather than the entropy.
+
+use crate::error::WalletError;
+use crate::mnemonic::{parse_mnemonic, MnemonicLanguage};
+use crate::wallet::Wallet;
+use bech32::{FromBase32, ToBase32, Variant};
+use bip39::Mnemonic;
+use chia::sha2::Sha256;
+use zeroize::Zeroizing;
+
+const SHARE_PREFIX: &str = "digshare";
+/// Shares of English mnemonics
+const SHARE_VERSION: u8 = 1;
+/// Shares carrying the mnemonic's wordlist after the version 1 header
+const SHARE_VERSION_WITH_LANGUAGE: u8 = 2;
+/// Most shares a mnemonic can be split into, as in SLIP-39
+pub const MAX_MNEMONIC_SHARES: u8 = 16;
+/// Version, identifier, threshold and index
+const HEADER_LEN: usize = 5;
+/// Length of the digest shared in front of the entropy
+const DIGEST_LEN: usize = 4;
+
+/// A decoded share
+struct Share {
+    identifier: [u8; 2],
+    threshold: u8,
+    index: u8,
+    language: MnemonicLanguage,
+    value: Zeroizing<Vec<u8>>,
+}
+
+impl Share {
+    fn encode(&self) -> Result<String, WalletError> {
+        let mut payload = Zeroizing::new(Vec::with_capacity(HEADER_LEN + 1 + self.value.len()));
+        let language = MnemonicLanguage::ALL
+            .iter()
+            .position(|language| *language == self.language)
+            .unwrap_or_default() as u8;
+        payload.push(if language == 0 {
+            SHARE_VERSION
+        } else {
+            SHARE_VERSION_WITH_LANGUAGE
+        });
+        payload.extend_from_slice(&self.identifier);
+        payload.push(self.threshold);
+        payload.push(self.index);
+        if language != 0 {
+            payload.push(language);
+        }
+        payload.extend_from_slice(&self.value);
+        bech32::encode(SHARE_PREFIX, payload.to_base32(), Variant::Bech32m)
+            .map_err(|e| WalletError::CryptoError(format!("Failed to encode share: {}", e)))
+    }
+
+    fn decode(share: &str) -> Result<Self, WalletError> {
+        let invalid = |detail: String| WalletError::InvalidMnemonicShare(detail);
+        let (prefix, data, variant) =
+            bech32::decode(share.trim()).map_err(|e| invalid(e.to_string()))?;
+        if prefix != SHARE_PREFIX || variant != Variant::Bech32m {
+            return Err(invalid("not a dig-wallet share".to_string()));
+        }
+        let payload =
+            Zeroizing::new(Vec::<u8>::from_base32(&data).map_err(|e| invalid(e.to_string()))?);
+        if payload.len() <= HEADER_LEN {
+            return Err(invalid("share is too short".to_string()));
+        }
+        let (language, header_len) = match payload[0] {
+            SHARE_VERSION => (MnemonicLanguage::English, HEADER_LEN),
+            SHARE_VERSION_WITH_LANGUAGE => {
+                let language = MnemonicLanguage::ALL
+                    .get(usize::from(payload[HEADER_LEN]))
+                    .ok_or_else(|| invalid("unknown mnemonic language".to_string()))?;
+                (*language, HEADER_LEN + 1)
+            }
+            version => return Err(invalid(format!("unsupported share version {}", version))),
+        };
+        if payload.len() <= header_len + DIGEST_LEN {
+            return Err(invalid("share is too short".to_string()));
+        }
+        Ok(Self {
+            identifier: [payload[1], payload[2]],
+            threshold: payload[3],
+            index: payload[4],
+            language,
+            value: Zeroizing::new(payload[header_len..].to_vec()),
+        })
+    }
+}
+
+/// Multiplication in GF(256) with the AES polynomial, without secret-dependent branches
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse in GF(256), as `a^254`
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Digest shared in front of the entropy, checked after recovery
+fn secret_digest(identifier: &[u8; 2], entropy: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"dig-wallet mnemonic share");
+    hasher.update(identifier);
+    hasher.update(entropy);
+    let hash = hasher.finalize();
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+impl Wallet {
+    /// Split the mnemonic into `shares` Shamir shares, any `threshold` of which recover it
+    ///
+    /// Randomness comes from the wallet configuration's RNG.
+    pub fn split_mnemonic(&self, shares: u8, threshold: u8) -> Result<Vec<String>, WalletError> {
+        if shares == 0 || shares > MAX_MNEMONIC_SHARES || threshold == 0 || threshold > shares {
+            return Err(WalletError::InvalidConfig(format!(
+                "Cannot split into {} shares with threshold {}; need 1 <= threshold <= shares <= {}",
+                shares, threshold, MAX_MNEMONIC_SHARES
+            )));
+        }
+        let (entropy, language) = self.with_mnemonic(|mnemonic| {
+            parse_mnemonic(mnemonic).map(|mnemonic| {
+                (
+                    Zeroizing::new(mnemonic.to_entropy()),
+                    MnemonicLanguage::from_bip39(mnemonic.language()),
+                )
+            })
+        })??;
+
+        let config = self.get_config();
+        let identifier = config.random_bytes::<2>()?;
+        let mut secret = Zeroizing::new(secret_digest(&identifier, &entropy).to_vec());
+        secret.extend_from_slice(&entropy);
+        // coefficients[0] is the secret; the rest are random
+        let mut coefficients = vec![secret];
+        for _ in 1..threshold {
+            let random = Zeroizing::new(config.random_bytes::<{ DIGEST_LEN + 32 }>()?);
+            coefficients.push(Zeroizing::new(random[..coefficients[0].len()].to_vec()));
+        }
+
+        (1..=shares)
+            .map(|index| {
+                let value = (0..coefficients[0].len())
+                    .map(|byte| {
+                        coefficients
+                            .iter()
+                            .rev()
+                            .fold(0, |acc, row| gf_mul(acc, index) ^ row[byte])
+                    })
+                    .collect();
+                Share {
+                    identifier,
+                    threshold,
+                    index,
+                    language,
+                    value: Zeroizing::new(value),
+                }
+                .encode()
+            })
+            .collect()
+    }
+
+    /// Recover a mnemonic from shares written by [`Wallet::split_mnemonic`]
+    ///
+    /// Fails with [`WalletError::InvalidMnemonicShare`] if the shares are malformed, come
+    /// from different splits, or are fewer than the threshold.
+    pub fn recover_from_shares(shares: &[String]) -> Result<String, WalletError> {
+        let invalid = |detail: &str| WalletError::InvalidMnemonicShare(detail.to_string());
+        let mut decoded: Vec<Share> = Vec::new();
+        for share in shares {
+            let share = Share::decode(share)?;
+            if let Some(first) = decoded.first() {
+                if share.identifier != first.identifier
+                    || share.threshold != first.threshold
+                    || share.language != first.language
+                    || share.value.len() != first.value.len()
+                {
+                    return Err(invalid("shares come from different splits"));
+                }
+            }
+            match decoded.iter().find(|other| other.index == share.index) {
+                Some(other) if other.value == share.value => {}
+                Some(_) => return Err(invalid("two different shares have the same index")),
+                None => decoded.push(share),
+            }
+        }
+        let first = decoded.first().ok_or_else(|| invalid("no shares given"))?;
+        let threshold = usize::from(first.threshold);
+        if decoded.len() < threshold {
+            return Err(WalletError::InvalidMnemonicShare(format!(
+                "{} of {} required shares given",
+                decoded.len(),
+                threshold
+            )));
+        }
+
+        let used = &decoded[..threshold];
+        let mut secret = Zeroizing::new(vec![0u8; first.value.len()]);
+        for (i, share) in used.iter().enumerate() {
+            // Lagrange basis polynomial of share i, evaluated at zero
+            let basis =
+                used.iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .fold(1, |acc, (_, other)| {
+                        gf_mul(acc, gf_mul(other.index, gf_inv(other.index ^ share.index)))
+                    });
+            for (byte, value) in secret.iter_mut().zip(share.value.iter()) {
+                *byte ^= gf_mul(*value, basis);
+            }
+        }
+
+        let (digest, entropy) = secret.split_at(DIGEST_LEN);
+        if secret_digest(&first.identifier, entropy) != digest {
+            return Err(invalid("shares do not recover a consistent secret"));
+        }
+        let mnemonic = Mnemonic::from_entropy_in(first.language.to_bip39(), entropy)
+            .map_err(|_| invalid("recovered entropy is not a valid mnemonic"))?;
+        Ok(mnemonic.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+    #[test]
+    fn test_any_threshold_of_shares_recovers_mnemonic() {
+        let wallet = Wallet::new(Some(MNEMONIC.to_string()), "shares".to_string());
+        let shares = wallet.split_mnemonic(5, 3).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(shares.iter().all(|share| !share.contains("abandon")));
+
+        let subset = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(Wallet::recover_from_shares(&subset).unwrap(), MNEMONIC);
+        let all_but_one = shares[1..].to_vec();
+        assert_eq!(Wallet::recover_from_shares(&all_but_one).unwrap(), MNEMONIC);
+
+        assert!(matches!(
+            Wallet::recover_from_shares(&shares[..2]),
+            Err(WalletError::InvalidMnemonicShare(_))
+        ));
+        assert!(wallet.split_mnemonic(3, 4).is_err());
+    }
+
+    #[test]
+    fn test_shares_keep_the_mnemonic_language() {
+        let japanese = "あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あおぞら";
+        let wallet = Wallet::new(Some(japanese.to_string()), "shares".to_string());
+        let shares = wallet.split_mnemonic(3, 2).unwrap();
+        let recovered = Wallet::recover_from_shares(&shares[1..]).unwrap();
+        assert_eq!(
+            parse_mnemonic(&recovered).unwrap(),
+            parse_mnemonic(japanese).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_shares_of_different_splits_are_rejected() {
+        let wallet = Wallet::new(Some(MNEMONIC.to_string()), "shares".to_string());
+        let first = wallet.split_mnemonic(3, 2).unwrap();
+        let second = wallet.split_mnemonic(3, 2).unwrap();
+
+        let mixed = vec![first[0].clone(), second[1].clone()];
+        assert!(matches!(
+            Wallet::recover_from_shares(&mixed),
+            Err(WalletError::InvalidMnemonicShare(_))
+        ));
+
+        let mut typo = first[1].clone();
+        let last = typo.pop().unwrap();
+        typo.push(if last == 'q' { 'p' } else { 'q' });
+        assert!(Wallet::recover_from_shares(&[first[0].clone(), typo]).is_err());
+
+        // A share altered but re-encoded with a valid checksum recovers the wrong secret
+        let mut altered = Share::decode(&first[1]).unwrap();
+        altered.value[DIGEST_LEN] ^= 1;
+        let altered = altered.encode().unwrap();
+        assert!(matches!(
+            Wallet::recover_from_shares(&[first[0].clone(), altered]),
+            Err(WalletError::InvalidMnemonicShare(_))
+        ));
+    }
+}
@@ -0,0 +1,225 @@
+//! Delegating signatures to keys held outside the wallet.
+//!
+//! A [`Signer`] owns a synthetic key and signs messages and transactions with it. A wallet
+//! built with [`Wallet::from_signer`] has no mnemonic: its owner address, key ownership
+//! signatures, template signatures, collateral attestations and the signatures of the
+//! coin spends it sends all come from the signer, and nothing about it is written to the
+//! keyring. [`DeviceSigner`] adapts an external [`SigningDevice`], such as a hardware wallet.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::keys::verify_message;
+use crate::wallet::Wallet;
+use chia::bls::{PublicKey, Signature};
+use chia::protocol::{Bytes32, CoinSpend};
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// Boxed future returned by [`Signer`] and [`SigningDevice`] methods
+pub type SignerFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, WalletError>> + Send + 'a>>;
+
+/// Holder of a wallet's synthetic key
+pub trait Signer: Send + Sync + Debug {
+    /// Synthetic public key of the first unhardened wallet key
+    fn synthetic_public_key(&self) -> SignerFuture<'_, PublicKey>;
+
+    /// Sign `message` as the Chia wallet's `sign_message` does: BLS over the hash of
+    /// `("Chia Signed Message" . message)`
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a, Signature>;
+
+    /// Sign `coin_spends` for the network whose genesis challenge is `agg_sig_me_data`,
+    /// returning the aggregate of the AGG_SIG signatures they need from this key
+    ///
+    /// The default refuses, for signers that can only sign messages.
+    fn sign_coin_spends<'a>(
+        &'a self,
+        coin_spends: &'a [CoinSpend],
+        agg_sig_me_data: Bytes32,
+    ) -> SignerFuture<'a, Signature> {
+        let _ = (coin_spends, agg_sig_me_data);
+        Box::pin(async {
+            Err(WalletError::CryptoError(
+                "Signer cannot sign transactions".to_string(),
+            ))
+        })
+    }
+}
+
+/// An external device holding the wallet's keys, reached over the caller's transport
+///
+/// The crate ships no device transport: talking to a hardware wallet (for example a
+/// Ledger over USB/HID) is up to the implementor. This trait is what the wallet needs
+/// from such a device, and [`DeviceSigner`] checks every signature that comes back.
+pub trait SigningDevice: Send + Sync + Debug {
+    /// Synthetic public key of unhardened wallet key `index`
+    fn synthetic_public_key(&self, index: u32) -> SignerFuture<'_, PublicKey>;
+
+    /// Sign `message` with the synthetic key of wallet key `index`, after the user
+    /// confirms on the device
+    fn sign_message<'a>(&'a self, index: u32, message: &'a [u8]) -> SignerFuture<'a, Signature>;
+
+    /// Sign `coin_spends` with the synthetic key of wallet key `index`, after the user
+    /// reviews them on the device; see [`Signer::sign_coin_spends`]
+    ///
+    /// The default refuses, for apps without transaction signing.
+    fn sign_coin_spends<'a>(
+        &'a self,
+        index: u32,
+        coin_spends: &'a [CoinSpend],
+        agg_sig_me_data: Bytes32,
+    ) -> SignerFuture<'a, Signature> {
+        let _ = (index, coin_spends, agg_sig_me_data);
+        Box::pin(async {
+            Err(WalletError::CryptoError(
+                "Signing device cannot sign transactions".to_string(),
+            ))
+        })
+    }
+}
+
+/// [`Signer`] backed by a [`SigningDevice`]
+///
+/// The public key is read from the device once, and every message signature is checked
+/// against it before being returned, so a faulty device or transport cannot produce a
+/// signature that only fails later at a verifier. Transaction signatures are checked the
+/// same way by [`Wallet::sign_coin_spends`].
+#[derive(Debug)]
+pub struct DeviceSigner {
+    device: Arc<dyn SigningDevice>,
+    index: u32,
+    public_key: Mutex<Option<PublicKey>>,
+}
+
+impl DeviceSigner {
+    /// Sign with wallet key 0 of `device`
+    pub fn new(device: Arc<dyn SigningDevice>) -> Self {
+        Self {
+            device,
+            index: 0,
+            public_key: Mutex::new(None),
+        }
+    }
+
+    /// Sign with wallet key `index` instead
+    pub fn with_index(mut self, index: u32) -> Self {
+        self.index = index;
+        self
+    }
+
+    async fn public_key(&self) -> Result<PublicKey, WalletError> {
+        let cached = *self.public_key.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(public_key) = cached {
+            return Ok(public_key);
+        }
+        let public_key = self.device.synthetic_public_key(self.index).await?;
+        *self.public_key.lock().unwrap_or_else(|e| e.into_inner()) = Some(public_key);
+        Ok(public_key)
+    }
+}
+
+impl Signer for DeviceSigner {
+    fn synthetic_public_key(&self) -> SignerFuture<'_, PublicKey> {
+        Box::pin(self.public_key())
+    }
+
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a, Signature> {
+        Box::pin(async move {
+            let public_key = self.public_key().await?;
+            let signature = self.device.sign_message(self.index, message).await?;
+            if !verify_message(message, &public_key, &signature) {
+                return Err(WalletError::CryptoError(
+                    "Signing device returned a signature that does not verify".to_string(),
+                ));
+            }
+            Ok(signature)
+        })
+    }
+
+    fn sign_coin_spends<'a>(
+        &'a self,
+        coin_spends: &'a [CoinSpend],
+        agg_sig_me_data: Bytes32,
+    ) -> SignerFuture<'a, Signature> {
+        self.device
+            .sign_coin_spends(self.index, coin_spends, agg_sig_me_data)
+    }
+}
+
+impl Wallet {
+    /// A wallet whose keys live in `signer`; it has no mnemonic and is never stored
+    pub fn from_signer(wallet_name: &str, signer: Arc<dyn Signer>) -> Self {
+        Self::from_signer_with_config(wallet_name, signer, &WalletConfig::default())
+    }
+
+    /// A signer-backed wallet using an explicit configuration
+    pub fn from_signer_with_config(
+        wallet_name: &str,
+        signer: Arc<dyn Signer>,
+        config: &WalletConfig,
+    ) -> Self {
+        Self::new_with_config(None, wallet_name.to_string(), config.clone()).with_signer(signer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::sign_message;
+    use chia::bls::SecretKey;
+
+    #[derive(Debug)]
+    struct FakeDevice {
+        secret_key: SecretKey,
+        faulty: bool,
+    }
+
+    impl SigningDevice for FakeDevice {
+        fn synthetic_public_key(&self, _index: u32) -> SignerFuture<'_, PublicKey> {
+            Box::pin(async move { Ok(self.secret_key.public_key()) })
+        }
+
+        fn sign_message<'a>(
+            &'a self,
+            _index: u32,
+            message: &'a [u8],
+        ) -> SignerFuture<'a, Signature> {
+            Box::pin(async move {
+                let signed = if self.faulty { b"other" } else { message };
+                Ok(sign_message(signed, &self.secret_key))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_device_wallet_proves_ownership_without_mnemonic() {
+        let secret_key = SecretKey::from_seed(&[7; 32]);
+        let device = FakeDevice {
+            secret_key: secret_key.clone(),
+            faulty: false,
+        };
+        let wallet = Wallet::from_signer("device", Arc::new(DeviceSigner::new(Arc::new(device))));
+        assert!(wallet.mnemonic_phrase().is_err());
+
+        let public_key = wallet.get_public_synthetic_key().await.unwrap();
+        assert_eq!(public_key, secret_key.public_key());
+        let signature = wallet.create_key_ownership_signature("n1").await.unwrap();
+        assert!(Wallet::verify_key_ownership_signature(
+            "n1",
+            &signature,
+            &hex::encode(public_key.to_bytes())
+        )
+        .await
+        .unwrap());
+
+        let faulty = DeviceSigner::new(Arc::new(FakeDevice {
+            secret_key,
+            faulty: true,
+        }));
+        assert!(matches!(
+            faulty.sign_message(b"n1").await,
+            Err(WalletError::CryptoError(_))
+        ));
+    }
+}
@@ -0,0 +1,297 @@
+//! Message signatures in the format of the Chia reference wallet's `verify_signature` RPC.
+//!
+//! A [`SignedMessage`] serializes to exactly the request body `chia rpc wallet
+//! verify_signature` expects, so proofs produced here can be checked with the standard
+//! CLI, and requests prepared for that RPC can be checked here.
+
+use crate::error::WalletError;
+use crate::keys::{decode_address, signed_message_hash, standard_puzzle_hash};
+use crate::wallet::Wallet;
+use chia::bls::{sign, verify, PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How a message is turned into the bytes that are signed, as in CHIP-0002
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SigningMode {
+    /// Tree hash of `("Chia Signed Message" . message)` with a UTF-8 message
+    #[default]
+    #[serde(rename = "CHIP-0002")]
+    Chip0002,
+    /// As [`SigningMode::Chip0002`], with a hex encoded message
+    #[serde(rename = "CHIP-0002_HEX_INPUT")]
+    Chip0002HexInput,
+    /// As [`SigningMode::Chip0002`], for keys behind a `p2_delegated_conditions` puzzle
+    #[serde(rename = "CHIP-0002_P2_DELEGATED_CONDITIONS")]
+    Chip0002P2DelegatedConditions,
+    /// The UTF-8 message itself, with plain BLS message augmentation
+    #[serde(rename = "BLS_MESSAGE_AUGMENTATION_UTF8_INPUT")]
+    BlsMessageAugmentationUtf8Input,
+    /// The hex decoded message itself, with plain BLS message augmentation
+    #[serde(rename = "BLS_MESSAGE_AUGMENTATION_HEX_INPUT")]
+    BlsMessageAugmentationHexInput,
+}
+
+impl SigningMode {
+    /// Name used for the `signing_mode` RPC field
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningMode::Chip0002 => "CHIP-0002",
+            SigningMode::Chip0002HexInput => "CHIP-0002_HEX_INPUT",
+            SigningMode::Chip0002P2DelegatedConditions => "CHIP-0002_P2_DELEGATED_CONDITIONS",
+            SigningMode::BlsMessageAugmentationUtf8Input => "BLS_MESSAGE_AUGMENTATION_UTF8_INPUT",
+            SigningMode::BlsMessageAugmentationHexInput => "BLS_MESSAGE_AUGMENTATION_HEX_INPUT",
+        }
+    }
+
+    /// The bytes actually signed for `message` in this mode
+    fn payload(&self, message: &str) -> Result<Vec<u8>, WalletError> {
+        Ok(match self {
+            SigningMode::Chip0002 | SigningMode::Chip0002P2DelegatedConditions => {
+                signed_message_hash(message.as_bytes()).to_vec()
+            }
+            SigningMode::Chip0002HexInput => signed_message_hash(&decode_hex(message)?).to_vec(),
+            SigningMode::BlsMessageAugmentationUtf8Input => message.as_bytes().to_vec(),
+            SigningMode::BlsMessageAugmentationHexInput => decode_hex(message)?,
+        })
+    }
+}
+
+impl fmt::Display for SigningMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SigningMode {
+    type Err = WalletError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            SigningMode::Chip0002,
+            SigningMode::Chip0002HexInput,
+            SigningMode::Chip0002P2DelegatedConditions,
+            SigningMode::BlsMessageAugmentationUtf8Input,
+            SigningMode::BlsMessageAugmentationHexInput,
+        ]
+        .into_iter()
+        .find(|mode| mode.as_str() == s)
+        .ok_or_else(|| WalletError::CryptoError(format!("Unknown signing mode {}", s)))
+    }
+}
+
+/// A signature as the fields of a `verify_signature` RPC request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedMessage {
+    /// Synthetic public key as hex
+    pub pubkey: String,
+    /// The message as given to the signer: text, or hex for the hex input modes
+    pub message: String,
+    /// Signature as hex
+    pub signature: String,
+    /// Absent in requests means [`SigningMode::Chip0002`], as in the RPC
+    #[serde(default)]
+    pub signing_mode: SigningMode,
+    /// Address the public key must control, if checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, WalletError> {
+    hex::decode(value.strip_prefix("0x").unwrap_or(value))
+        .map_err(|e| WalletError::CryptoError(e.to_string()))
+}
+
+impl Wallet {
+    /// Sign `message` with the synthetic key of the owner address in the given mode
+    pub async fn sign_message_with_mode(
+        &self,
+        message: &str,
+        mode: SigningMode,
+    ) -> Result<SignedMessage, WalletError> {
+        if mode == SigningMode::Chip0002P2DelegatedConditions {
+            return Err(WalletError::CryptoError(format!(
+                "{} signing is not supported by standard wallet keys",
+                mode
+            )));
+        }
+
+        let synthetic_sk = self.get_private_synthetic_key().await?;
+        let signature = sign(&synthetic_sk, mode.payload(message)?);
+
+        Ok(SignedMessage {
+            pubkey: hex::encode(synthetic_sk.public_key().to_bytes()),
+            message: message.to_string(),
+            signature: hex::encode(signature.to_bytes()),
+            signing_mode: mode,
+            address: Some(self.get_owner_public_key().await?),
+        })
+    }
+
+    /// Sign `message` the way the reference wallet's `sign_message_by_address` and Goby do
+    ///
+    /// Shorthand for [`SigningMode::Chip0002`]: the signed bytes are the tree hash of
+    /// `("Chia Signed Message" . message)`, and the result names the owner address the
+    /// key controls.
+    pub async fn sign_message_chip002(&self, message: &str) -> Result<SignedMessage, WalletError> {
+        self.sign_message_with_mode(message, SigningMode::Chip0002)
+            .await
+    }
+
+    /// Verify a [`SigningMode::Chip0002`] signature made by any CHIP-0002 wallet
+    ///
+    /// With `address`, the public key must also control that address through the
+    /// standard puzzle. Returns `Ok(false)` for a wrong signature or address and an
+    /// error for malformed input.
+    pub fn verify_message_chip002(
+        message: &str,
+        signature: &str,
+        public_key: &str,
+        address: Option<&str>,
+    ) -> Result<bool, WalletError> {
+        Self::verify_signed_message(&SignedMessage {
+            pubkey: public_key.to_string(),
+            message: message.to_string(),
+            signature: signature.to_string(),
+            signing_mode: SigningMode::Chip0002,
+            address: address.map(str::to_string),
+        })
+    }
+
+    /// Check a signature the way the `verify_signature` RPC does
+    ///
+    /// Returns `Ok(false)` for a wrong signature or an address the key does not control,
+    /// and an error for malformed input.
+    pub fn verify_signed_message(signed: &SignedMessage) -> Result<bool, WalletError> {
+        let pubkey = decode_hex(&signed.pubkey)?;
+        let pubkey: [u8; 48] = pubkey
+            .try_into()
+            .map_err(|_| WalletError::CryptoError("Invalid public key length".to_string()))?;
+        let pubkey =
+            PublicKey::from_bytes(&pubkey).map_err(|e| WalletError::CryptoError(e.to_string()))?;
+
+        let signature = decode_hex(&signed.signature)?;
+        let signature: [u8; 96] = signature
+            .try_into()
+            .map_err(|_| WalletError::CryptoError("Invalid signature length".to_string()))?;
+        let signature = Signature::from_bytes(&signature)
+            .map_err(|e| WalletError::CryptoError(e.to_string()))?;
+
+        if let Some(address) = &signed.address {
+            if signed.signing_mode == SigningMode::Chip0002P2DelegatedConditions {
+                return Err(WalletError::CryptoError(format!(
+                    "Address checks are not supported for {}",
+                    signed.signing_mode
+                )));
+            }
+            if decode_address(address)? != standard_puzzle_hash(&pubkey) {
+                return Ok(false);
+            }
+        }
+
+        Ok(verify(
+            &signature,
+            &pubkey,
+            signed.signing_mode.payload(&signed.message)?,
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+
+    fn test_wallet() -> Wallet {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        Wallet::new(Some(mnemonic.to_string()), "signing".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_signed_messages_round_trip_in_rpc_format() {
+        let wallet = test_wallet();
+
+        for (mode, message) in [
+            (SigningMode::Chip0002, "hello chia"),
+            (SigningMode::Chip0002HexInput, "68656c6c6f"),
+            (SigningMode::BlsMessageAugmentationUtf8Input, "hello chia"),
+            (SigningMode::BlsMessageAugmentationHexInput, "0x68656c6c6f"),
+        ] {
+            let signed = wallet.sign_message_with_mode(message, mode).await.unwrap();
+            assert!(Wallet::verify_signed_message(&signed).unwrap(), "{}", mode);
+
+            let json = serde_json::to_value(&signed).unwrap();
+            assert_eq!(json["signing_mode"], mode.as_str());
+            assert_eq!(mode.as_str().parse::<SigningMode>().unwrap(), mode);
+
+            let tampered = SignedMessage {
+                signing_mode: SigningMode::Chip0002HexInput,
+                message: "00".to_string(),
+                ..signed
+            };
+            assert!(!Wallet::verify_signed_message(&tampered).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chip002_shorthands_match_the_signing_mode() {
+        let wallet = test_wallet();
+        let signed = wallet.sign_message_chip002("hello chia").await.unwrap();
+        assert_eq!(signed.signing_mode, SigningMode::Chip0002);
+        assert_eq!(
+            signed,
+            wallet
+                .sign_message_with_mode("hello chia", SigningMode::Chip0002)
+                .await
+                .unwrap()
+        );
+
+        let address = signed.address.as_deref();
+        assert!(Wallet::verify_message_chip002(
+            "hello chia",
+            &signed.signature,
+            &signed.pubkey,
+            address
+        )
+        .unwrap());
+        assert!(
+            !Wallet::verify_message_chip002("hello", &signed.signature, &signed.pubkey, None)
+                .unwrap()
+        );
+
+        // Not valid as a plain augmented signature over the same text
+        let plain = SignedMessage {
+            signing_mode: SigningMode::BlsMessageAugmentationUtf8Input,
+            ..signed.clone()
+        };
+        assert!(!Wallet::verify_signed_message(&plain).unwrap());
+
+        let elsewhere = Wallet::puzzle_hash_to_address([0; 32].into(), "xch").unwrap();
+        assert!(!Wallet::verify_message_chip002(
+            "hello chia",
+            &signed.signature,
+            &signed.pubkey,
+            Some(&elsewhere)
+        )
+        .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_default_mode_matches_ownership_signatures() {
+        let wallet = test_wallet();
+        let signed: SignedMessage = serde_json::from_value(serde_json::json!({
+            "pubkey": hex::encode(wallet.get_public_synthetic_key().await.unwrap().to_bytes()),
+            "message": "Signing this message to prove ownership of key.\n\nNonce: abc",
+            "signature": wallet.create_key_ownership_signature("abc").await.unwrap(),
+        }))
+        .unwrap();
+        assert_eq!(signed.signing_mode, SigningMode::Chip0002);
+        assert!(Wallet::verify_signed_message(&signed).unwrap());
+
+        let other = SignedMessage {
+            address: Some(Wallet::puzzle_hash_to_address([0; 32].into(), "xch").unwrap()),
+            ..signed
+        };
+        assert!(!Wallet::verify_signed_message(&other).unwrap());
+    }
+}
@@ -0,0 +1,261 @@
+//! Signing coin spends that were built outside the wallet.
+//!
+//! Each spend is run to find its AGG_SIG conditions, and the message of every one is
+//! completed with the additional data of the wallet's network: the genesis challenge for
+//! AGG_SIG_ME, and its hash with the condition opcode for the other kinds. Conditions whose
+//! public key is the synthetic key of an issued address are signed; the rest are left to
+//! the holders of those keys, whose signatures aggregate with the wallet's.
+
+use crate::coin_records::run_coin_spend;
+use crate::error::WalletError;
+use crate::metadata::WalletNetwork;
+use crate::wallet::Wallet;
+use chia::bls::{aggregate_verify, sign, PublicKey, SecretKey, Signature};
+use chia_wallet_sdk::driver::SpendContext;
+use chia_wallet_sdk::signer::{AggSigConstants, RequiredBlsSignature};
+use datalayer_driver::{Bytes32, CoinSpend};
+use std::collections::HashMap;
+
+impl WalletNetwork {
+    /// Genesis challenge of the network, the AGG_SIG_ME additional data of its spends
+    pub fn genesis_challenge(&self) -> Bytes32 {
+        match self {
+            WalletNetwork::Mainnet => datalayer_driver::constants::get_mainnet_genesis_challenge(),
+            WalletNetwork::Testnet11 => {
+                datalayer_driver::constants::get_testnet11_genesis_challenge()
+            }
+        }
+    }
+}
+
+/// The BLS signatures `coin_spends` need, with messages completed for `genesis_challenge`
+pub fn required_signatures(
+    coin_spends: &[CoinSpend],
+    genesis_challenge: Bytes32,
+) -> Result<Vec<RequiredBlsSignature>, WalletError> {
+    let constants = AggSigConstants::new(genesis_challenge);
+    let mut ctx = SpendContext::new();
+    let mut required = Vec::new();
+    for coin_spend in coin_spends {
+        for condition in run_coin_spend(&mut ctx, coin_spend)? {
+            if let Some(agg_sig) = condition.into_agg_sig() {
+                required.push(RequiredBlsSignature::from_condition(
+                    &coin_spend.coin,
+                    agg_sig,
+                    &constants,
+                ));
+            }
+        }
+    }
+    Ok(required)
+}
+
+/// Sign every required signature whose public key is in `secret_keys`
+fn sign_required(
+    required: &[RequiredBlsSignature],
+    secret_keys: &HashMap<PublicKey, SecretKey>,
+) -> Signature {
+    let mut signature = Signature::default();
+    for required in required {
+        if let Some(secret_key) = secret_keys.get(&required.public_key) {
+            signature += &sign(secret_key, required.message());
+        }
+    }
+    signature
+}
+
+impl Wallet {
+    /// Genesis challenge of the network of the wallet's chain
+    pub async fn genesis_challenge(&self) -> Result<Bytes32, WalletError> {
+        Ok(self.chain_profile().await?.network.genesis_challenge())
+    }
+
+    /// Sign the coin spends with the synthetic keys of the wallet's issued addresses
+    ///
+    /// Messages are completed for the network of the wallet's chain. Signatures needed from
+    /// keys the wallet does not hold are not included; if none of the spends needs the
+    /// wallet, the result is the identity signature. Signer-backed wallets hand the spends
+    /// to their [`crate::Signer`] and check the signature it returns.
+    pub async fn sign_coin_spends(
+        &self,
+        coin_spends: &[CoinSpend],
+    ) -> Result<Signature, WalletError> {
+        let genesis_challenge = self.genesis_challenge().await?;
+        let required = required_signatures(coin_spends, genesis_challenge)?;
+        if let Some(signer) = self.signer() {
+            let public_key = signer.synthetic_public_key().await?;
+            let needed: Vec<_> = required
+                .iter()
+                .filter(|required| required.public_key == public_key)
+                .map(|required| (&required.public_key, required.message()))
+                .collect();
+            if needed.is_empty() {
+                return Ok(Signature::default());
+            }
+
+            let signature = signer
+                .sign_coin_spends(coin_spends, genesis_challenge)
+                .await?;
+            if !aggregate_verify(&signature, needed) {
+                return Err(WalletError::CryptoError(
+                    "Signer returned a signature that does not verify".to_string(),
+                ));
+            }
+            return Ok(signature);
+        }
+
+        let secret_keys: HashMap<PublicKey, SecretKey> = self
+            .issued_synthetic_secret_keys()
+            .await?
+            .into_iter()
+            .map(|secret_key| (secret_key.public_key(), secret_key))
+            .collect();
+        Ok(sign_required(&required, &secret_keys))
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::builder::TransactionBuilder;
+    use crate::config::WalletConfig;
+    use crate::environment::SandboxEnvironment;
+    use crate::keys::standard_puzzle_hash;
+    use crate::metadata::ChainProfile;
+    use crate::signer::{DeviceSigner, SignerFuture, SigningDevice};
+    use chia::puzzles::Memos;
+    use chia_wallet_sdk::driver::StandardLayer;
+    use chia_wallet_sdk::types::Conditions;
+    use datalayer_driver::Coin;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_sign_coin_spends_for_wallet_network() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("signing", &config)
+            .await
+            .unwrap();
+        let wallet = Wallet::load_with_config(Some("signing".to_string()), false, &config)
+            .await
+            .unwrap();
+        let owner = wallet.get_owner_puzzle_hash().await.unwrap();
+        let coin_spends = wallet
+            .build_unsigned_transaction(
+                TransactionBuilder::new()
+                    .add_coins([Coin::new(Bytes32::new([1; 32]), owner, 100)])
+                    .pay(Bytes32::new([2; 32]), 100),
+            )
+            .await
+            .unwrap();
+
+        let mainnet = WalletNetwork::Mainnet.genesis_challenge();
+        let required = required_signatures(&coin_spends, mainnet).unwrap();
+        assert_eq!(required.len(), 1);
+        let signature = wallet.sign_coin_spends(&coin_spends).await.unwrap();
+        assert!(aggregate_verify(
+            &signature,
+            required
+                .iter()
+                .map(|required| (&required.public_key, required.message()))
+        ));
+
+        wallet
+            .set_chain(Some(&ChainProfile::testnet11()))
+            .await
+            .unwrap();
+        let testnet = wallet.sign_coin_spends(&coin_spends).await.unwrap();
+        assert_ne!(testnet, signature);
+        assert_ne!(WalletNetwork::Testnet11.genesis_challenge(), mainnet);
+    }
+
+    #[derive(Debug)]
+    struct SoftDevice {
+        secret_key: SecretKey,
+        network: WalletNetwork,
+    }
+
+    impl SigningDevice for SoftDevice {
+        fn synthetic_public_key(&self, _index: u32) -> SignerFuture<'_, PublicKey> {
+            Box::pin(async move { Ok(self.secret_key.public_key()) })
+        }
+
+        fn sign_message<'a>(
+            &'a self,
+            _index: u32,
+            message: &'a [u8],
+        ) -> SignerFuture<'a, Signature> {
+            Box::pin(async move { Ok(sign(&self.secret_key, message)) })
+        }
+
+        fn sign_coin_spends<'a>(
+            &'a self,
+            _index: u32,
+            coin_spends: &'a [CoinSpend],
+            _agg_sig_me_data: Bytes32,
+        ) -> SignerFuture<'a, Signature> {
+            Box::pin(async move {
+                let required = required_signatures(coin_spends, self.network.genesis_challenge())?;
+                let secret_keys =
+                    HashMap::from([(self.secret_key.public_key(), self.secret_key.clone())]);
+                Ok(sign_required(&required, &secret_keys))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signer_wallet_signs_coin_spends() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let secret_key = SecretKey::from_seed(&[7; 32]);
+        let public_key = secret_key.public_key();
+        let device_wallet = |network| {
+            let device = SoftDevice {
+                secret_key: secret_key.clone(),
+                network,
+            };
+            Wallet::from_signer_with_config(
+                "device",
+                Arc::new(DeviceSigner::new(Arc::new(device))),
+                &config,
+            )
+        };
+
+        let mut ctx = SpendContext::new();
+        let coin = Coin::new(
+            Bytes32::new([1; 32]),
+            standard_puzzle_hash(&public_key),
+            100,
+        );
+        StandardLayer::new(public_key)
+            .spend(
+                &mut ctx,
+                coin,
+                Conditions::new().create_coin(Bytes32::new([2; 32]), 100, Memos::None),
+            )
+            .unwrap();
+        let coin_spends = ctx.take();
+
+        let required =
+            required_signatures(&coin_spends, WalletNetwork::Mainnet.genesis_challenge()).unwrap();
+        let signature = device_wallet(WalletNetwork::Mainnet)
+            .sign_coin_spends(&coin_spends)
+            .await
+            .unwrap();
+        assert!(aggregate_verify(
+            &signature,
+            required
+                .iter()
+                .map(|required| (&required.public_key, required.message()))
+        ));
+
+        // A device signing for another network is caught before broadcast
+        assert!(matches!(
+            device_wallet(WalletNetwork::Testnet11)
+                .sign_coin_spends(&coin_spends)
+                .await,
+            Err(WalletError::CryptoError(_))
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::builder::TransactionBuilder;
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::mnemonic::parse_mnemonic;
+use crate::wallet::Wallet;
+use chia_wallet_sdk::driver::Cat;
+use datalayer_driver::{Bytes32, Peer};
+use serde::{Deserialize, Serialize};
+
+/// Name given to the in-memory wallet holding a swept mnemonic; never written to the keyring
+const SWEEP_WALLET_NAME: &str = "<external sweep>";
+
+/// Outcome of [`Wallet::sweep_from_mnemonic`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SweepSummary {
+    /// Spend bundle name as hex
+    pub tx_id: String,
+    /// Receive puzzle hash of the external key as hex
+    pub source_puzzle_hash: String,
+    /// XCH mojos delivered to the destination, after the fee
+    pub xch_amount: u64,
+    /// DIG mojos delivered to the destination
+    pub dig_amount: u64,
+    pub fee: u64,
+    /// Coin ids consumed by the sweep as hex
+    pub spent_coin_ids: Vec<String>,
+}
+
+impl Wallet {
+    /// Move every XCH and DIG coin of an external mnemonic into a keyring wallet
+    ///
+    /// The external key only ever lives in memory for the duration of the call; it is
+    /// never written to the keyring. The fee is paid from the swept XCH.
+    pub async fn sweep_from_mnemonic(
+        peer: &Peer,
+        external_mnemonic: &str,
+        destination_wallet: &str,
+        fee: u64,
+    ) -> Result<SweepSummary, WalletError> {
+        Self::sweep_from_mnemonic_with_config(
+            peer,
+            external_mnemonic,
+            destination_wallet,
+            fee,
+            &WalletConfig::default(),
+        )
+        .await
+    }
+
+    /// Sweep an external mnemonic into a wallet of the keyring selected by `config`
+    pub async fn sweep_from_mnemonic_with_config(
+        peer: &Peer,
+        external_mnemonic: &str,
+        destination_wallet: &str,
+        fee: u64,
+        config: &WalletConfig,
+    ) -> Result<SweepSummary, WalletError> {
+        let (external, destination) =
+            Self::prepare_sweep(external_mnemonic, destination_wallet, config).await?;
+        Self::check_fee_floor(peer, fee, config).await?;
+
+        let source_puzzle_hash = external.get_owner_puzzle_hash().await?;
+        let xch_coins = external.get_all_unspent_xch_coins(peer, vec![]).await?;
+        #[cfg(feature = "cat")]
+        let cats: Vec<Cat> = external
+            .get_all_unspent_dig_coins(peer, vec![], false)
+            .await?
+            .proved
+            .iter()
+            .map(|dig_coin| dig_coin.cat())
+            .collect();
+        #[cfg(not(feature = "cat"))]
+        let cats: Vec<Cat> = Vec::new();
+
+        if xch_coins.is_empty() && cats.is_empty() {
+            return Err(WalletError::NoUnspentCoins);
+        }
+
+        let xch_total: u64 = xch_coins.iter().map(|coin| coin.amount).sum();
+        let xch_amount = xch_total
+            .checked_sub(fee)
+            .ok_or(WalletError::NoUnspentCoins)?;
+
+        let dig_amount: u64 = cats.iter().map(|cat| cat.coin.amount).sum();
+        let mut builder = TransactionBuilder::new()
+            .add_cats(cats)
+            .add_coins(xch_coins)
+            .with_fee(fee)
+            .with_change_puzzle_hash(source_puzzle_hash)
+            .bind_fee_spend(false);
+        if dig_amount > 0 {
+            builder = builder.pay_cat(destination, dig_amount);
+        }
+        if xch_amount > 0 {
+            builder = builder.pay(destination, xch_amount);
+        }
+
+        let unsigned = external.build_transaction(builder).await?;
+        let spent_coin_ids = unsigned
+            .spent_coins
+            .iter()
+            .map(|coin| hex::encode(coin.coin_id()))
+            .collect();
+        let spend_bundle = external.sign_spend_bundle(unsigned.coin_spends).await?;
+        let tx_id = external.broadcast_transaction(peer, &spend_bundle).await?;
+
+        Ok(SweepSummary {
+            tx_id: hex::encode(tx_id),
+            source_puzzle_hash: hex::encode(source_puzzle_hash),
+            xch_amount,
+            dig_amount,
+            fee,
+            spent_coin_ids,
+        })
+    }
+
+    /// Validate the external mnemonic and resolve the destination before touching the network
+    async fn prepare_sweep(
+        external_mnemonic: &str,
+        destination_wallet: &str,
+        config: &WalletConfig,
+    ) -> Result<(Wallet, Bytes32), WalletError> {
+        let mnemonic = parse_mnemonic(external_mnemonic.trim())?;
+        let destination = Self::resolve_keyring_puzzle_hash(destination_wallet, config).await?;
+
+        let external = Wallet::new_with_config(
+            Some(mnemonic.to_string()),
+            SWEEP_WALLET_NAME.to_string(),
+            config.clone(),
+        );
+        Ok((external, destination))
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+    #[tokio::test]
+    async fn test_prepare_sweep_keeps_external_key_out_of_keyring() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("vault", &config)
+            .await
+            .unwrap();
+
+        let (external, destination) = Wallet::prepare_sweep(TEST_MNEMONIC, "vault", &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            destination,
+            Wallet::resolve_keyring_puzzle_hash("vault", &config)
+                .await
+                .unwrap()
+        );
+        assert_ne!(external.get_owner_puzzle_hash().await.unwrap(), destination);
+        assert_eq!(
+            Wallet::list_wallets_with_config(&config).await.unwrap(),
+            vec!["vault".to_string()]
+        );
+
+        assert!(matches!(
+            Wallet::prepare_sweep("not a mnemonic", "vault", &config).await,
+            Err(WalletError::InvalidMnemonic)
+        ));
+        assert!(matches!(
+            Wallet::prepare_sweep(TEST_MNEMONIC, "missing", &config).await,
+            Err(WalletError::WalletNotFound(_))
+        ));
+    }
+}
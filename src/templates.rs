@@ -0,0 +1,479 @@
+//! Versioned message templates for ownership and authentication signatures.
+//!
+//! DIG services sign a handful of fixed message formats. A [`MessageTemplates`] registry
+//! holds each format as a versioned template string with `{name}` placeholders, renders it
+//! from validated [`TemplateParams`] and signs or verifies the result keyed by template id
+//! and version, so every implementation produces byte-identical messages.
+
+use crate::error::WalletError;
+use crate::keys::verify_message;
+use crate::wallet::Wallet;
+use chia::bls::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Template of the message signed by [`Wallet::create_key_ownership_signature`]
+pub(crate) const KEY_OWNERSHIP_TEMPLATE: &str =
+    "Signing this message to prove ownership of key.\n\nNonce: {nonce}";
+const NODE_HANDSHAKE_TEMPLATE: &str =
+    "DIG node handshake\nnode: {node_id}\npeer: {peer_id}\nnonce: {nonce}\ntimestamp: {timestamp}";
+const STORE_DELEGATION_TEMPLATE: &str =
+    "DIG store delegation\nstore: {store_id}\ndelegate: {delegate}\nexpires: {expires_at}";
+/// Template of the message signed for a [`crate::OwnershipChallenge`]
+pub(crate) const OWNERSHIP_CHALLENGE_TEMPLATE: &str = "DIG key ownership challenge\naudience: {audience}\nnonce: {nonce}\nissued: {issued_at}\nexpires: {expires_at}";
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, WalletError> {
+    hex::decode(value).map_err(|e| WalletError::CryptoError(e.to_string()))
+}
+
+/// Identifies a message format
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum MessageTemplateId {
+    KeyOwnership,
+    NodeHandshake,
+    StoreDelegation,
+    OwnershipChallenge,
+    /// An application-defined format, registered with [`MessageTemplates::register`]
+    Custom(String),
+}
+
+impl fmt::Display for MessageTemplateId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageTemplateId::KeyOwnership => f.write_str("key_ownership"),
+            MessageTemplateId::NodeHandshake => f.write_str("node_handshake"),
+            MessageTemplateId::StoreDelegation => f.write_str("store_delegation"),
+            MessageTemplateId::OwnershipChallenge => f.write_str("ownership_challenge"),
+            MessageTemplateId::Custom(name) => write!(f, "custom:{}", name),
+        }
+    }
+}
+
+impl FromStr for MessageTemplateId {
+    type Err = WalletError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "key_ownership" => Ok(MessageTemplateId::KeyOwnership),
+            "node_handshake" => Ok(MessageTemplateId::NodeHandshake),
+            "store_delegation" => Ok(MessageTemplateId::StoreDelegation),
+            "ownership_challenge" => Ok(MessageTemplateId::OwnershipChallenge),
+            _ => match s.strip_prefix("custom:") {
+                Some(name) if !name.is_empty() => Ok(MessageTemplateId::Custom(name.to_string())),
+                _ => Err(WalletError::InvalidMessageTemplate(format!(
+                    "Unknown template id {}",
+                    s
+                ))),
+            },
+        }
+    }
+}
+
+impl From<MessageTemplateId> for String {
+    fn from(id: MessageTemplateId) -> Self {
+        id.to_string()
+    }
+}
+
+impl TryFrom<String> for MessageTemplateId {
+    type Error = WalletError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Values for the placeholders of a template
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TemplateParams(BTreeMap<String, String>);
+
+impl TemplateParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value of the `{name}` placeholder
+    pub fn with(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.0.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Parameters of [`MessageTemplateId::KeyOwnership`]
+    pub fn key_ownership(nonce: &str) -> Self {
+        Self::new().with("nonce", nonce)
+    }
+
+    /// Parameters of [`MessageTemplateId::NodeHandshake`]
+    pub fn node_handshake(node_id: &str, peer_id: &str, nonce: &str, timestamp: u64) -> Self {
+        Self::new()
+            .with("node_id", node_id)
+            .with("peer_id", peer_id)
+            .with("nonce", nonce)
+            .with("timestamp", timestamp.to_string())
+    }
+
+    /// Parameters of [`MessageTemplateId::StoreDelegation`]
+    pub fn store_delegation(store_id: &str, delegate: &str, expires_at: u64) -> Self {
+        Self::new()
+            .with("store_id", store_id)
+            .with("delegate", delegate)
+            .with("expires_at", expires_at.to_string())
+    }
+
+    /// Parameters of [`MessageTemplateId::OwnershipChallenge`]
+    pub fn ownership_challenge(
+        audience: &str,
+        nonce: &str,
+        issued_at: u64,
+        expires_at: u64,
+    ) -> Self {
+        Self::new()
+            .with("audience", audience)
+            .with("nonce", nonce)
+            .with("issued_at", issued_at.to_string())
+            .with("expires_at", expires_at.to_string())
+    }
+}
+
+/// One version of a message format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageTemplate {
+    id: MessageTemplateId,
+    version: u32,
+    template: String,
+    params: Vec<String>,
+}
+
+impl MessageTemplate {
+    /// Parse `template`, whose placeholders are `{name}` with lowercase ASCII names
+    pub fn new(
+        id: MessageTemplateId,
+        version: u32,
+        template: impl Into<String>,
+    ) -> Result<Self, WalletError> {
+        let template = template.into();
+        let invalid = |reason: &str| {
+            WalletError::InvalidMessageTemplate(format!("{} v{}: {}", id, version, reason))
+        };
+
+        let mut params = Vec::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| invalid("unclosed placeholder"))?;
+            let name = &rest[start + 1..start + end];
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            {
+                return Err(invalid(&format!("invalid placeholder {{{}}}", name)));
+            }
+            if !params.iter().any(|param| param == name) {
+                params.push(name.to_string());
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if rest.contains('}') {
+            return Err(invalid("unmatched }"));
+        }
+
+        Ok(Self {
+            id,
+            version,
+            template,
+            params,
+        })
+    }
+
+    pub fn id(&self) -> &MessageTemplateId {
+        &self.id
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Placeholder names in order of first use
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    /// Fill in the placeholders
+    ///
+    /// Every placeholder needs a non-empty single-line value, and unknown parameters are
+    /// rejected, so a value cannot smuggle extra lines into the signed message.
+    pub fn render(&self, params: &TemplateParams) -> Result<String, WalletError> {
+        let invalid = |reason: String| {
+            WalletError::InvalidMessageTemplate(format!(
+                "{} v{}: {}",
+                self.id, self.version, reason
+            ))
+        };
+        if let Some(unknown) = params.0.keys().find(|name| !self.params.contains(name)) {
+            return Err(invalid(format!("unknown parameter {}", unknown)));
+        }
+
+        for name in &self.params {
+            let value = params
+                .0
+                .get(name)
+                .ok_or_else(|| invalid(format!("missing parameter {}", name)))?;
+            if value.is_empty() || value.contains(['\n', '\r']) {
+                return Err(invalid(format!(
+                    "parameter {} must be a non-empty single line",
+                    name
+                )));
+            }
+        }
+
+        // One pass, so placeholders inside values are left alone
+        let mut message = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = start
+                + rest[start..]
+                    .find('}')
+                    .expect("checked by MessageTemplate::new");
+            message.push_str(&rest[..start]);
+            message.push_str(&params.0[&rest[start + 1..end]]);
+            rest = &rest[end + 1..];
+        }
+        message.push_str(rest);
+        Ok(message)
+    }
+}
+
+/// A signature over a rendered template, carrying what is needed to re-render it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateSignature {
+    pub template: MessageTemplateId,
+    pub version: u32,
+    pub params: TemplateParams,
+    /// Synthetic public key as hex
+    pub public_key: String,
+    /// Signature as hex
+    pub signature: String,
+}
+
+/// Registry of message templates by id and version
+#[derive(Debug, Clone)]
+pub struct MessageTemplates {
+    templates: BTreeMap<(MessageTemplateId, u32), MessageTemplate>,
+}
+
+impl Default for MessageTemplates {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl MessageTemplates {
+    /// The DIG templates: key ownership, node handshake, store delegation and ownership
+    /// challenge, all version 1
+    pub fn builtin() -> Self {
+        let mut templates = Self {
+            templates: BTreeMap::new(),
+        };
+        for (id, template) in [
+            (MessageTemplateId::KeyOwnership, KEY_OWNERSHIP_TEMPLATE),
+            (MessageTemplateId::NodeHandshake, NODE_HANDSHAKE_TEMPLATE),
+            (
+                MessageTemplateId::StoreDelegation,
+                STORE_DELEGATION_TEMPLATE,
+            ),
+            (
+                MessageTemplateId::OwnershipChallenge,
+                OWNERSHIP_CHALLENGE_TEMPLATE,
+            ),
+        ] {
+            let template =
+                MessageTemplate::new(id, 1, template).expect("built-in templates are valid");
+            templates
+                .templates
+                .insert((template.id.clone(), template.version), template);
+        }
+        templates
+    }
+
+    /// Add a template; re-registering an id and version with a different string is an error
+    pub fn register(&mut self, template: MessageTemplate) -> Result<(), WalletError> {
+        let key = (template.id.clone(), template.version);
+        match self.templates.get(&key) {
+            Some(existing) if existing.template != template.template => {
+                Err(WalletError::InvalidMessageTemplate(format!(
+                    "{} v{} is already registered with a different format",
+                    template.id, template.version
+                )))
+            }
+            _ => {
+                self.templates.insert(key, template);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn get(&self, id: &MessageTemplateId, version: u32) -> Option<&MessageTemplate> {
+        self.templates.get(&(id.clone(), version))
+    }
+
+    /// The highest registered version of a template
+    pub fn latest(&self, id: &MessageTemplateId) -> Option<&MessageTemplate> {
+        self.templates
+            .range((id.clone(), 0)..=(id.clone(), u32::MAX))
+            .next_back()
+            .map(|(_, template)| template)
+    }
+
+    fn require(
+        &self,
+        id: &MessageTemplateId,
+        version: u32,
+    ) -> Result<&MessageTemplate, WalletError> {
+        self.get(id, version).ok_or_else(|| {
+            WalletError::InvalidMessageTemplate(format!("{} v{} is not registered", id, version))
+        })
+    }
+
+    /// Render the latest version of a template with `params`
+    pub fn render(
+        &self,
+        id: &MessageTemplateId,
+        params: &TemplateParams,
+    ) -> Result<String, WalletError> {
+        self.latest(id)
+            .ok_or_else(|| {
+                WalletError::InvalidMessageTemplate(format!("{} is not registered", id))
+            })?
+            .render(params)
+    }
+
+    /// Verify a [`TemplateSignature`] against the template version it names
+    ///
+    /// Returns `Ok(false)` for a bad signature; unknown templates, invalid parameters and
+    /// malformed keys or signatures are errors.
+    pub fn verify(&self, signed: &TemplateSignature) -> Result<bool, WalletError> {
+        let message = self
+            .require(&signed.template, signed.version)?
+            .render(&signed.params)?;
+
+        let public_key: [u8; 48] = decode_hex(&signed.public_key)?
+            .try_into()
+            .map_err(|_| WalletError::CryptoError("Invalid public key length".to_string()))?;
+        let public_key = PublicKey::from_bytes(&public_key)
+            .map_err(|e| WalletError::CryptoError(e.to_string()))?;
+        let signature: [u8; 96] = decode_hex(&signed.signature)?
+            .try_into()
+            .map_err(|_| WalletError::CryptoError("Invalid signature length".to_string()))?;
+        let signature = Signature::from_bytes(&signature)
+            .map_err(|e| WalletError::CryptoError(e.to_string()))?;
+
+        Ok(verify_message(message.as_bytes(), &public_key, &signature))
+    }
+}
+
+impl Wallet {
+    /// Sign the latest version of a registered template with the synthetic key
+    ///
+    /// Key ownership signatures are interchangeable with
+    /// [`Wallet::create_key_ownership_signature`].
+    pub async fn sign_template(
+        &self,
+        templates: &MessageTemplates,
+        id: &MessageTemplateId,
+        params: TemplateParams,
+    ) -> Result<TemplateSignature, WalletError> {
+        let template = templates.latest(id).ok_or_else(|| {
+            WalletError::InvalidMessageTemplate(format!("{} is not registered", id))
+        })?;
+        let message = template.render(&params)?;
+
+        let (public_key, signature) = self.sign_with_synthetic_key(message.as_bytes()).await?;
+
+        Ok(TemplateSignature {
+            template: template.id.clone(),
+            version: template.version,
+            params,
+            public_key: hex::encode(public_key.to_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_template_signatures_round_trip() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let wallet = Wallet::new(Some(mnemonic.to_string()), "test".to_string());
+        let templates = MessageTemplates::builtin();
+
+        let signed = wallet
+            .sign_template(
+                &templates,
+                &MessageTemplateId::KeyOwnership,
+                TemplateParams::key_ownership("abc"),
+            )
+            .await
+            .unwrap();
+        assert!(templates.verify(&signed).unwrap());
+        assert_eq!(
+            signed.signature,
+            wallet.create_key_ownership_signature("abc").await.unwrap()
+        );
+
+        let json = serde_json::to_value(&signed).unwrap();
+        assert_eq!(json["template"], "key_ownership");
+        assert_eq!(json["params"]["nonce"], "abc");
+
+        let mut tampered = signed;
+        tampered.params = TemplateParams::key_ownership("abd");
+        assert!(!templates.verify(&tampered).unwrap());
+
+        let handshake = templates
+            .render(
+                &MessageTemplateId::NodeHandshake,
+                &TemplateParams::node_handshake("node", "peer", "n1", 10),
+            )
+            .unwrap();
+        assert_eq!(
+            handshake,
+            "DIG node handshake\nnode: node\npeer: peer\nnonce: n1\ntimestamp: 10"
+        );
+    }
+
+    #[test]
+    fn test_parameters_are_validated() {
+        let mut templates = MessageTemplates::builtin();
+        let id = MessageTemplateId::Custom("lease".to_string());
+        templates
+            .register(MessageTemplate::new(id.clone(), 2, "lease {store} until {until}").unwrap())
+            .unwrap();
+        assert!(templates
+            .register(MessageTemplate::new(id.clone(), 2, "lease {store}").unwrap())
+            .is_err());
+        assert_eq!(templates.latest(&id).unwrap().params(), ["store", "until"]);
+
+        let params = TemplateParams::new().with("store", "s1");
+        assert!(templates.render(&id, &params).is_err());
+        assert!(templates
+            .render(&id, &params.clone().with("until", "1\nexpires: never"))
+            .is_err());
+        assert!(templates
+            .render(&id, &params.clone().with("until", "9").with("extra", "x"))
+            .is_err());
+        assert_eq!(
+            templates.render(&id, &params.with("until", "9")).unwrap(),
+            "lease s1 until 9"
+        );
+
+        assert!(MessageTemplate::new(id, 3, "bad {Name}").is_err());
+    }
+}
@@ -0,0 +1,666 @@
+//! Threshold (m-of-n) BLS wallets.
+//!
+//! [`ThresholdKey::deal`] splits a fresh group secret key into `n` [`KeyShare`]s with
+//! Shamir's secret sharing over the BLS12-381 scalar field. The wallet's synthetic key is
+//! derived from the group public key like any standard wallet's, so its address and its
+//! AGG_SIG conditions are ordinary and nothing on chain reveals the threshold. Any
+//! `threshold` share holders sign a message partially; [`ThresholdKey::combine`] weighs
+//! their [`PartialSignature`]s with Lagrange coefficients into one signature valid for the
+//! synthetic key. The dealer knows the group secret while dealing and must discard it.
+//!
+//! The keyring only keeps the public side: the threshold, the group public key and every
+//! participant's public key, under an entry that loads as a watch-only wallet.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::keys::standard_puzzle_hash;
+use crate::wallet::Wallet;
+use crate::watch_only::WatchOnlyKey;
+use chia::bls::{hash_to_g2, sign_raw, verify, PublicKey, SecretKey, Signature};
+use chia::protocol::Bytes32;
+use chia::puzzles::standard::DEFAULT_HIDDEN_PUZZLE_HASH;
+use chia::puzzles::{mod_by_group_order, DeriveSynthetic};
+use chia::sha2::Sha256;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Most participants a threshold key can have; share indices are 1 to 255
+pub const MAX_THRESHOLD_PARTICIPANTS: u8 = 255;
+
+/// BLS12-381 group order, the modulus of the scalar field
+const GROUP_ORDER: [u8; 32] = [
+    0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05,
+    0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+];
+
+fn group_order() -> BigUint {
+    BigUint::from_bytes_be(&GROUP_ORDER)
+}
+
+/// An element of the scalar field, with the arithmetic sharing and interpolation need
+#[derive(Clone, PartialEq, Eq)]
+struct Scalar(BigUint);
+
+impl Scalar {
+    fn from_bytes(bytes: &[u8; 32]) -> Self {
+        Self(BigUint::from_bytes_be(bytes) % group_order())
+    }
+
+    fn from_u64(value: u64) -> Self {
+        Self(BigUint::from(value) % group_order())
+    }
+
+    fn zero() -> Self {
+        Self::from_u64(0)
+    }
+
+    fn to_bytes(&self) -> [u8; 32] {
+        let digits = self.0.to_bytes_be();
+        let mut bytes = [0u8; 32];
+        bytes[32 - digits.len()..].copy_from_slice(&digits);
+        bytes
+    }
+
+    fn to_secret_key(&self) -> SecretKey {
+        SecretKey::from_bytes(&self.to_bytes()).expect("reduced scalar")
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Self((&self.0 + &other.0) % group_order())
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        Self((&self.0 * &other.0) % group_order())
+    }
+
+    fn neg(&self) -> Self {
+        let order = group_order();
+        Self((&order - &self.0) % order)
+    }
+
+    /// Multiplicative inverse by Fermat's little theorem; zero maps to zero
+    fn inverse(&self) -> Self {
+        let order = group_order();
+        let exponent = &order - BigUint::from(2u8);
+        Self(self.0.modpow(&exponent, &order))
+    }
+}
+
+/// `x - y` in the scalar field for share indices
+fn index_difference(x: u8, y: u8) -> Scalar {
+    let difference = Scalar::from_u64(u64::from(x.abs_diff(y)));
+    if x < y {
+        difference.neg()
+    } else {
+        difference
+    }
+}
+
+/// Lagrange coefficients of shares `indices`, in order, for interpolating at `at`
+///
+/// The denominators are inverted together, with a single field inversion.
+fn lagrange_coefficients(indices: &[u8], at: u8) -> Vec<Scalar> {
+    let (numerators, denominators): (Vec<Scalar>, Vec<Scalar>) = indices
+        .iter()
+        .map(|&index| {
+            indices.iter().filter(|&&other| other != index).fold(
+                (Scalar::from_u64(1), Scalar::from_u64(1)),
+                |(numerator, denominator), &other| {
+                    (
+                        numerator.mul(&index_difference(at, other)),
+                        denominator.mul(&index_difference(index, other)),
+                    )
+                },
+            )
+        })
+        .unzip();
+
+    // prefixes[i] is the product of the first i denominators
+    let mut prefixes = vec![Scalar::from_u64(1)];
+    for denominator in &denominators {
+        let product = prefixes[prefixes.len() - 1].mul(denominator);
+        prefixes.push(product);
+    }
+    let mut inverse = prefixes[denominators.len()].inverse();
+    let mut coefficients = vec![Scalar::zero(); indices.len()];
+    for i in (0..indices.len()).rev() {
+        coefficients[i] = numerators[i].mul(&inverse.mul(&prefixes[i]));
+        inverse = inverse.mul(&denominators[i]);
+    }
+    coefficients
+}
+
+/// `scalar` times `public_key`
+fn scale_public_key(public_key: &PublicKey, scalar: &Scalar) -> PublicKey {
+    let mut scaled = *public_key;
+    scaled.scalar_multiply(&scalar.to_bytes());
+    scaled
+}
+
+/// Offset added to the group secret key by the standard synthetic key derivation
+fn synthetic_offset(group_public_key: &PublicKey) -> SecretKey {
+    let mut hasher = Sha256::new();
+    hasher.update(group_public_key.to_bytes());
+    hasher.update(DEFAULT_HIDDEN_PUZZLE_HASH);
+    // The hash is a signed integer, as in `DeriveSynthetic`
+    SecretKey::from_bytes(&mod_by_group_order(hasher.finalize())).expect("reduced scalar")
+}
+
+fn decode_public_key(value: &str) -> Result<PublicKey, WalletError> {
+    let bytes: [u8; 48] = hex::decode(value)
+        .map_err(|e| WalletError::CryptoError(e.to_string()))?
+        .try_into()
+        .map_err(|_| WalletError::CryptoError("Invalid public key length".to_string()))?;
+    PublicKey::from_bytes(&bytes).map_err(|e| WalletError::CryptoError(e.to_string()))
+}
+
+/// The public side of an m-of-n wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "StoredThresholdKey", try_from = "StoredThresholdKey")]
+pub struct ThresholdKey {
+    threshold: u8,
+    group_public_key: PublicKey,
+    /// Public key of share `i + 1` at position `i`
+    participants: Vec<PublicKey>,
+}
+
+impl ThresholdKey {
+    /// Deal a new `threshold`-of-`participants` key, returning its shares in index order
+    pub fn deal(
+        threshold: u8,
+        participants: u8,
+        config: &WalletConfig,
+    ) -> Result<(Self, Vec<KeyShare>), WalletError> {
+        if threshold == 0 || threshold > participants {
+            return Err(WalletError::InvalidThresholdKey(format!(
+                "threshold {} must be between 1 and the {} participants",
+                threshold, participants
+            )));
+        }
+
+        let coefficients = (0..threshold)
+            .map(|_| Ok(Scalar::from_bytes(&config.random_bytes::<32>()?)))
+            .collect::<Result<Vec<_>, WalletError>>()?;
+        let shares: Vec<KeyShare> = (1..=participants)
+            .map(|index| {
+                let x = Scalar::from_u64(index.into());
+                let value = coefficients
+                    .iter()
+                    .rev()
+                    .fold(Scalar::zero(), |value, coefficient| {
+                        value.mul(&x).add(coefficient)
+                    });
+                KeyShare {
+                    index,
+                    secret_key: value.to_secret_key(),
+                }
+            })
+            .collect();
+
+        let key = Self {
+            threshold,
+            group_public_key: coefficients[0].to_secret_key().public_key(),
+            participants: shares.iter().map(KeyShare::public_key).collect(),
+        };
+        Ok((key, shares))
+    }
+
+    /// Rebuild a threshold key from its public parts, checking they belong together
+    ///
+    /// Every participant key must lie on the sharing polynomial through the first
+    /// `threshold` keys, and that polynomial must give the group public key.
+    pub fn new(
+        threshold: u8,
+        group_public_key: PublicKey,
+        participants: Vec<PublicKey>,
+    ) -> Result<Self, WalletError> {
+        let key = Self::from_parts(threshold, group_public_key, participants)?;
+        key.check_shares()?;
+        Ok(key)
+    }
+
+    /// A threshold key from its public parts, only checking the threshold and counts
+    ///
+    /// Keys read back from the keyring were checked when they were imported.
+    fn from_parts(
+        threshold: u8,
+        group_public_key: PublicKey,
+        participants: Vec<PublicKey>,
+    ) -> Result<Self, WalletError> {
+        if threshold == 0 || usize::from(threshold) > participants.len() {
+            return Err(WalletError::InvalidThresholdKey(format!(
+                "threshold {} must be between 1 and the {} participants",
+                threshold,
+                participants.len()
+            )));
+        }
+        if participants.len() > usize::from(MAX_THRESHOLD_PARTICIPANTS) {
+            return Err(WalletError::InvalidThresholdKey(format!(
+                "at most {} participants are supported",
+                MAX_THRESHOLD_PARTICIPANTS
+            )));
+        }
+
+        Ok(Self {
+            threshold,
+            group_public_key,
+            participants,
+        })
+    }
+
+    /// Check that the participant keys are shares of the group public key
+    fn check_shares(&self) -> Result<(), WalletError> {
+        let basis: Vec<u8> = (1..=self.threshold).collect();
+        let interpolate = |at: u8| {
+            basis.iter().zip(lagrange_coefficients(&basis, at)).fold(
+                PublicKey::default(),
+                |sum, (&index, coefficient)| {
+                    sum + &scale_public_key(
+                        &self.participants[usize::from(index - 1)],
+                        &coefficient,
+                    )
+                },
+            )
+        };
+        if interpolate(0) != self.group_public_key {
+            return Err(WalletError::InvalidThresholdKey(
+                "participant keys do not share the group public key".to_string(),
+            ));
+        }
+        for position in usize::from(self.threshold)..self.participants.len() {
+            let index = position as u8 + 1;
+            if interpolate(index) != self.participants[position] {
+                return Err(WalletError::InvalidThresholdKey(format!(
+                    "participant {} is not a share of the group key",
+                    index
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of partial signatures needed for a signature
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn group_public_key(&self) -> PublicKey {
+        self.group_public_key
+    }
+
+    /// Participant public keys; share index `i` is at position `i - 1`
+    pub fn participants(&self) -> &[PublicKey] {
+        &self.participants
+    }
+
+    /// Synthetic public key the wallet's coins are locked to
+    pub fn synthetic_public_key(&self) -> PublicKey {
+        self.group_public_key.derive_synthetic()
+    }
+
+    /// Standard transaction puzzle hash of the wallet
+    pub fn puzzle_hash(&self) -> Bytes32 {
+        standard_puzzle_hash(&self.synthetic_public_key())
+    }
+
+    /// Watch-only key of the wallet's keyring entry
+    pub(crate) fn watch_only_key(&self) -> WatchOnlyKey {
+        WatchOnlyKey::Synthetic(self.synthetic_public_key())
+    }
+
+    /// The message actually hashed when signing `message`, augmented with the synthetic key
+    fn augmented_message(&self, message: &[u8]) -> Vec<u8> {
+        let mut augmented = self.synthetic_public_key().to_bytes().to_vec();
+        augmented.extend_from_slice(message);
+        augmented
+    }
+
+    fn participant(&self, index: u8) -> Result<&PublicKey, WalletError> {
+        usize::from(index)
+            .checked_sub(1)
+            .and_then(|position| self.participants.get(position))
+            .ok_or_else(|| WalletError::InvalidThresholdKey(format!("no participant {}", index)))
+    }
+
+    /// Whether `partial` is participant `partial.index`'s share of a signature of `message`
+    pub fn verify_partial(
+        &self,
+        message: &[u8],
+        partial: &PartialSignature,
+    ) -> Result<bool, WalletError> {
+        let public_key = self.participant(partial.index)?;
+        let signature = partial.decode()?;
+        let hashed = hash_to_g2(&self.augmented_message(message));
+        Ok(signature.pair(&PublicKey::generator()) == hashed.pair(public_key))
+    }
+
+    /// Combine partial signatures of `message` into a signature for the synthetic key
+    ///
+    /// Duplicates are ignored and any `threshold` valid partial signatures suffice; an
+    /// invalid one fails the whole combination with the participant's index, since it
+    /// means that participant misbehaved or signed something else.
+    pub fn combine(
+        &self,
+        message: &[u8],
+        partials: &[PartialSignature],
+    ) -> Result<Signature, WalletError> {
+        let mut indices = Vec::new();
+        let mut signatures = Vec::new();
+        for partial in partials {
+            if indices.contains(&partial.index) {
+                continue;
+            }
+            if !self.verify_partial(message, partial)? {
+                return Err(WalletError::InvalidThresholdKey(format!(
+                    "partial signature of participant {} is invalid",
+                    partial.index
+                )));
+            }
+            indices.push(partial.index);
+            signatures.push(partial.decode()?);
+            if indices.len() == usize::from(self.threshold) {
+                break;
+            }
+        }
+        if indices.len() < usize::from(self.threshold) {
+            return Err(WalletError::InvalidThresholdKey(format!(
+                "{} of {} required partial signatures",
+                indices.len(),
+                self.threshold
+            )));
+        }
+
+        let augmented = self.augmented_message(message);
+        let mut combined = sign_raw(&synthetic_offset(&self.group_public_key), &augmented);
+        for (coefficient, signature) in lagrange_coefficients(&indices, 0).iter().zip(&signatures) {
+            let mut weighted = signature.clone();
+            weighted.scalar_multiply(&coefficient.to_bytes());
+            combined += &weighted;
+        }
+
+        if !verify(&combined, &self.synthetic_public_key(), message) {
+            return Err(WalletError::CryptoError(
+                "Combined threshold signature does not verify".to_string(),
+            ));
+        }
+        Ok(combined)
+    }
+}
+
+/// Keyring form of a [`ThresholdKey`]
+#[derive(Serialize, Deserialize)]
+struct StoredThresholdKey {
+    threshold: u8,
+    group_public_key: String,
+    participants: Vec<String>,
+}
+
+impl From<ThresholdKey> for StoredThresholdKey {
+    fn from(key: ThresholdKey) -> Self {
+        Self {
+            threshold: key.threshold,
+            group_public_key: hex::encode(key.group_public_key.to_bytes()),
+            participants: key
+                .participants
+                .iter()
+                .map(|public_key| hex::encode(public_key.to_bytes()))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<StoredThresholdKey> for ThresholdKey {
+    type Error = String;
+
+    fn try_from(stored: StoredThresholdKey) -> Result<Self, Self::Error> {
+        let group_public_key =
+            decode_public_key(&stored.group_public_key).map_err(|e| e.to_string())?;
+        let participants = stored
+            .participants
+            .iter()
+            .map(|public_key| decode_public_key(public_key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Self::from_parts(stored.threshold, group_public_key, participants)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// One participant's share of the group secret key
+#[derive(Clone)]
+pub struct KeyShare {
+    index: u8,
+    secret_key: SecretKey,
+}
+
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyShare {
+    /// A share handed out by [`ThresholdKey::deal`], e.g. read back from storage
+    pub fn new(index: u8, secret_key: SecretKey) -> Self {
+        Self { index, secret_key }
+    }
+
+    /// Index of the share, from 1
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.secret_key.public_key()
+    }
+
+    /// Sign this participant's part of a signature of `message` by `key`
+    ///
+    /// `message` is what the signature must cover, e.g. an AGG_SIG_ME message followed
+    /// by the coin id and the network's genesis challenge.
+    pub fn sign_partial(
+        &self,
+        key: &ThresholdKey,
+        message: &[u8],
+    ) -> Result<PartialSignature, WalletError> {
+        if *key.participant(self.index)? != self.public_key() {
+            return Err(WalletError::InvalidThresholdKey(format!(
+                "share {} does not belong to this key",
+                self.index
+            )));
+        }
+
+        let signature = sign_raw(&self.secret_key, key.augmented_message(message));
+        Ok(PartialSignature {
+            index: self.index,
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+}
+
+/// A participant's part of a threshold signature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartialSignature {
+    /// Index of the signing share
+    pub index: u8,
+    /// Signature as hex
+    pub signature: String,
+}
+
+impl PartialSignature {
+    fn decode(&self) -> Result<Signature, WalletError> {
+        let bytes: [u8; 96] = hex::decode(&self.signature)
+            .map_err(|e| WalletError::CryptoError(e.to_string()))?
+            .try_into()
+            .map_err(|_| WalletError::CryptoError("Invalid signature length".to_string()))?;
+        Signature::from_bytes(&bytes).map_err(|e| WalletError::CryptoError(e.to_string()))
+    }
+}
+
+impl Wallet {
+    /// Store the public side of a threshold wallet under `wallet_name`
+    pub async fn import_threshold_key(
+        wallet_name: &str,
+        key: &ThresholdKey,
+    ) -> Result<(), WalletError> {
+        Self::import_threshold_key_with_config(wallet_name, key, &WalletConfig::default()).await
+    }
+
+    /// Store a threshold wallet using an explicit configuration
+    ///
+    /// The entry loads as a watch-only wallet of the synthetic key. Like
+    /// [`Wallet::import_watch_only`], it never overwrites a wallet holding a mnemonic.
+    /// The participant keys are checked to be shares of the group key first, since a
+    /// deserialized [`ThresholdKey`] has not been.
+    pub async fn import_threshold_key_with_config(
+        wallet_name: &str,
+        key: &ThresholdKey,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        key.check_shares()?;
+        Self::save_threshold_key_to_keyring(wallet_name, key, config).await
+    }
+
+    /// The threshold key stored under `wallet_name`, if it is a threshold wallet
+    pub async fn threshold_key(wallet_name: &str) -> Result<Option<ThresholdKey>, WalletError> {
+        Self::threshold_key_with_config(wallet_name, &WalletConfig::default()).await
+    }
+
+    /// Look up a threshold key using an explicit configuration
+    pub async fn threshold_key_with_config(
+        wallet_name: &str,
+        config: &WalletConfig,
+    ) -> Result<Option<ThresholdKey>, WalletError> {
+        Self::get_threshold_key_from_keyring(wallet_name, config).await
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_any_threshold_of_partials_combines_to_a_valid_signature() {
+        let config = WalletConfig::new();
+        let (key, shares) = ThresholdKey::deal(2, 3, &config).unwrap();
+        assert_eq!(key.participants().len(), 3);
+        let message = b"spend message";
+
+        for pair in [[0, 1], [0, 2], [2, 1]] {
+            let partials: Vec<_> = pair
+                .iter()
+                .map(|&i| shares[i].sign_partial(&key, message).unwrap())
+                .collect();
+            assert!(partials
+                .iter()
+                .all(|partial| key.verify_partial(message, partial).unwrap()));
+            let signature = key.combine(message, &partials).unwrap();
+            assert!(verify(&signature, &key.synthetic_public_key(), message));
+        }
+
+        let one = [shares[0].sign_partial(&key, message).unwrap()];
+        assert!(matches!(
+            key.combine(message, &[one[0].clone(), one[0].clone()]),
+            Err(WalletError::InvalidThresholdKey(_))
+        ));
+
+        let wrong = shares[1].sign_partial(&key, b"other message").unwrap();
+        assert!(!key.verify_partial(message, &wrong).unwrap());
+        assert!(key.combine(message, &[one[0].clone(), wrong]).is_err());
+
+        assert!(ThresholdKey::deal(4, 3, &config).is_err());
+    }
+
+    #[test]
+    fn test_synthetic_offset_matches_the_standard_derivation() {
+        // Hashes with the top bit set are negative offsets; cover both signs
+        for seed in 1..=16u64 {
+            let public_key = Scalar::from_u64(seed).to_secret_key().public_key();
+            let derived = public_key + &synthetic_offset(&public_key).public_key();
+            assert_eq!(derived, public_key.derive_synthetic());
+        }
+    }
+
+    #[test]
+    fn test_public_parts_are_checked_on_rebuild() {
+        let config = WalletConfig::new();
+        let (key, _) = ThresholdKey::deal(2, 4, &config).unwrap();
+        let rebuilt = ThresholdKey::new(2, key.group_public_key(), key.participants().to_vec());
+        assert_eq!(rebuilt.unwrap(), key);
+
+        let (other, _) = ThresholdKey::deal(2, 4, &config).unwrap();
+        let mut participants = key.participants().to_vec();
+        participants[3] = other.participants()[3];
+        assert!(ThresholdKey::new(2, key.group_public_key(), participants).is_err());
+        assert!(
+            ThresholdKey::new(2, other.group_public_key(), key.participants().to_vec()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_scalar_field_arithmetic() {
+        let x = Scalar::from_u64(123_456_789);
+        assert!(x.mul(&x.inverse()) == Scalar::from_u64(1));
+        assert!(x.add(&x.neg()) == Scalar::zero());
+        assert!(Scalar::from_bytes(&GROUP_ORDER) == Scalar::zero());
+
+        // Interpolating the shares of a constant polynomial gives the constant back
+        let coefficients = lagrange_coefficients(&[1, 3, 4], 0);
+        let sum = coefficients
+            .iter()
+            .fold(Scalar::zero(), |sum, coefficient| sum.add(coefficient));
+        assert!(sum == Scalar::from_u64(1));
+    }
+
+    #[tokio::test]
+    async fn test_inconsistent_key_deserializes_but_is_not_imported() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let (key, _) = ThresholdKey::deal(2, 3, &config).unwrap();
+        let (other, _) = ThresholdKey::deal(2, 3, &config).unwrap();
+
+        let mut stored = serde_json::to_value(&key).unwrap();
+        stored["group_public_key"] =
+            serde_json::to_value(hex::encode(other.group_public_key().to_bytes())).unwrap();
+        let tampered: ThresholdKey = serde_json::from_value(stored).unwrap();
+        assert!(matches!(
+            Wallet::import_threshold_key_with_config("vault", &tampered, &config).await,
+            Err(WalletError::InvalidThresholdKey(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_threshold_wallet_is_stored_as_watch_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let (key, _) = ThresholdKey::deal(2, 3, &config).unwrap();
+
+        Wallet::import_threshold_key_with_config("vault", &key, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            Wallet::threshold_key_with_config("vault", &config)
+                .await
+                .unwrap(),
+            Some(key.clone())
+        );
+
+        let wallet = Wallet::load_with_config(Some("vault".to_string()), false, &config)
+            .await
+            .unwrap();
+        assert!(wallet.is_watch_only());
+        assert_eq!(
+            wallet.get_owner_puzzle_hash().await.unwrap(),
+            key.puzzle_hash()
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Tombstones for coins known to be spent.
+//!
+//! A spend is final once buried deep enough, so after one peer has reported a coin spent
+//! there is no need to ask again. [`Wallet::check_spent_coins`] answers from the tombstones
+//! first and only requests the state of coins it has no tombstone for. Tombstones above a
+//! fork point are dropped by [`Wallet::resume_sync`], and old ones are compacted away by
+//! [`Wallet::compact_spent_tombstones`] since incremental scans never revisit them.
+
+use crate::chain::{fetch_coin_states, fetch_peak_height};
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::wallet::Wallet;
+use datalayer_driver::{Bytes32, Peer};
+use serde::{Deserialize, Serialize};
+
+/// Blocks (about a week) a tombstone is kept after its spend before compaction drops it
+pub const TOMBSTONE_RETENTION_BLOCKS: u32 = 32_256;
+
+/// A coin spent at `spent_height`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpentTombstone {
+    /// Coin id as hex
+    pub coin_id: String,
+    pub spent_height: u32,
+    pub recorded_at: u64,
+}
+
+/// Outcome of [`Wallet::check_spent_coins`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpentCheck {
+    /// Spent coins with their spent height
+    pub spent: Vec<(Bytes32, u32)>,
+    /// Coins the peer reports unspent or does not know
+    pub unspent: Vec<Bytes32>,
+    /// Coins answered from tombstones without asking the peer
+    pub tombstone_hits: usize,
+}
+
+/// Persisted tombstones of one wallet, keyed by coin id
+pub struct SpentTombstones {
+    cache: FileCache<SpentTombstone>,
+    config: WalletConfig,
+}
+
+impl SpentTombstones {
+    /// Open the tombstones of a wallet under the configured `.dig` directory
+    pub fn open(wallet_name: &str, config: &WalletConfig) -> Result<Self, WalletError> {
+        Ok(Self {
+            cache: FileCache::from_config(&format!("tombstones/{}", wallet_name), config)?,
+            config: config.clone(),
+        })
+    }
+
+    /// The tombstone of a coin, if it is known spent
+    pub fn get(&self, coin_id: Bytes32) -> Result<Option<SpentTombstone>, WalletError> {
+        self.cache.get(&hex::encode(coin_id))
+    }
+
+    /// Record a coin as spent at `spent_height`
+    pub async fn record(&self, coin_id: Bytes32, spent_height: u32) -> Result<(), WalletError> {
+        self.cache
+            .set(
+                &hex::encode(coin_id),
+                &SpentTombstone {
+                    coin_id: hex::encode(coin_id),
+                    spent_height,
+                    recorded_at: self.config.unix_timestamp(),
+                },
+            )
+            .await
+    }
+
+    /// Number of stored tombstones
+    pub fn len(&self) -> Result<usize, WalletError> {
+        Ok(self.cache.get_cached_keys()?.len())
+    }
+
+    /// Whether no tombstones are stored
+    pub fn is_empty(&self) -> Result<bool, WalletError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Drop tombstones spent below `height`; returns how many were dropped
+    pub async fn compact(&self, height: u32) -> Result<usize, WalletError> {
+        self.remove_where(|tombstone| tombstone.spent_height < height)
+            .await
+    }
+
+    /// Drop tombstones spent above `height` (all of them for `None`), e.g. after a fork
+    pub async fn rewind_to(&self, height: Option<u32>) -> Result<usize, WalletError> {
+        self.remove_where(|tombstone| height.map_or(true, |h| tombstone.spent_height > h))
+            .await
+    }
+
+    async fn remove_where(
+        &self,
+        f: impl Fn(&SpentTombstone) -> bool,
+    ) -> Result<usize, WalletError> {
+        let mut removed = 0;
+        for key in self.cache.get_cached_keys()? {
+            match self.cache.get(&key)? {
+                Some(tombstone) if f(&tombstone) => {
+                    self.cache.delete(&key).await?;
+                    removed += 1;
+                }
+                _ => {}
+            }
+        }
+        Ok(removed)
+    }
+}
+
+impl Wallet {
+    /// Open the spent-coin tombstones of this wallet
+    pub fn spent_tombstones(&self) -> Result<SpentTombstones, WalletError> {
+        SpentTombstones::open(&self.cache_name(), self.get_config())
+    }
+
+    /// Find which of `coin_ids` are spent, asking the peer only about coins without a tombstone
+    ///
+    /// Spends the peer reports are recorded as tombstones for the next check.
+    pub async fn check_spent_coins(
+        &self,
+        peer: &Peer,
+        coin_ids: &[Bytes32],
+    ) -> Result<SpentCheck, WalletError> {
+        let tombstones = self.spent_tombstones()?;
+        let mut check = SpentCheck::default();
+        let mut unknown = Vec::new();
+
+        for &coin_id in coin_ids {
+            match tombstones.get(coin_id)? {
+                Some(tombstone) => {
+                    check.spent.push((coin_id, tombstone.spent_height));
+                    check.tombstone_hits += 1;
+                }
+                None => unknown.push(coin_id),
+            }
+        }
+
+        if unknown.is_empty() {
+            return Ok(check);
+        }
+
+        let states = fetch_coin_states(peer, unknown.clone()).await?;
+        for coin_id in unknown {
+            let spent_height = states
+                .iter()
+                .find(|state| state.coin.coin_id() == coin_id)
+                .and_then(|state| state.spent_height);
+            match spent_height {
+                Some(spent_height) => {
+                    tombstones.record(coin_id, spent_height).await?;
+                    check.spent.push((coin_id, spent_height));
+                }
+                None => check.unspent.push(coin_id),
+            }
+        }
+
+        Ok(check)
+    }
+
+    /// Drop tombstones older than [`TOMBSTONE_RETENTION_BLOCKS`] below the peer's peak
+    pub async fn compact_spent_tombstones(&self, peer: &Peer) -> Result<usize, WalletError> {
+        let peak_height = fetch_peak_height(peer).await?;
+        self.spent_tombstones()?
+            .compact(peak_height.saturating_sub(TOMBSTONE_RETENTION_BLOCKS))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_tombstones_compact_and_rewind() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let tombstones = SpentTombstones::open("spent", &config).unwrap();
+        assert!(tombstones.is_empty().unwrap());
+
+        for (fill, height) in [(1, 100), (2, 200), (3, 300)] {
+            tombstones
+                .record(Bytes32::new([fill; 32]), height)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            tombstones
+                .get(Bytes32::new([2; 32]))
+                .unwrap()
+                .unwrap()
+                .spent_height,
+            200
+        );
+
+        assert_eq!(tombstones.compact(200).await.unwrap(), 1);
+        assert!(tombstones.get(Bytes32::new([1; 32])).unwrap().is_none());
+
+        assert_eq!(tombstones.rewind_to(Some(250)).await.unwrap(), 1);
+        assert!(tombstones.get(Bytes32::new([3; 32])).unwrap().is_none());
+        assert_eq!(tombstones.len().unwrap(), 1);
+
+        assert_eq!(tombstones.rewind_to(None).await.unwrap(), 1);
+        assert!(tombstones.is_empty().unwrap());
+    }
+}
@@ -0,0 +1,727 @@
+pub use crate::asset::Asset;
+use crate::builder::{Payment, TransactionBuilder};
+use crate::change::burn_dust;
+use crate::coin_records::run_coin_spend;
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::history::{TransactionDirection, TransactionRecord};
+use crate::keys::standard_puzzle_hash;
+use crate::pending::PendingTransaction;
+use crate::wallet::Wallet;
+use chia::bls::PublicKey;
+use chia::puzzles::offer::SettlementPaymentsSolution;
+use chia::puzzles::Memos;
+use chia_wallet_sdk::driver::{
+    Cat, CatSpend, Deltas, DriverError, Layer, Outputs, Relation, SettlementLayer, SpendContext,
+    SpendKind, SpendWithConditions, Spends, StandardLayer,
+};
+use chia_wallet_sdk::types::Conditions;
+use datalayer_driver::{Bytes, Bytes32, Coin, CoinSpend, Peer, SpendBundle};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Mempool inclusion status reported by a full node for a successful submission
+const MEMPOOL_STATUS_SUCCESS: u8 = 1;
+/// Mempool inclusion status reported by a full node for a pending submission
+const MEMPOOL_STATUS_PENDING: u8 = 2;
+/// Coin announcement both halves of a DIG transfer make to bind the fee spend to the CAT spend
+const FEE_BINDING_MESSAGE: &[u8] = b"dig-wallet:fee";
+
+/// How a transfer is assembled: the memos of the recipient's coin and, for DIG or other
+/// CATs, how the separate XCH fee coin is bound
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigSendOptions {
+    bind_fee_spend: bool,
+    memos: Vec<Bytes>,
+}
+
+impl Default for DigSendOptions {
+    fn default() -> Self {
+        Self {
+            bind_fee_spend: true,
+            memos: Vec::new(),
+        }
+    }
+}
+
+impl DigSendOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the CAT and fee spends announce and assert each other (the default)
+    ///
+    /// Unbound, only the fee spend asserts the CAT spend, so the CAT spend can be
+    /// included on its own without paying the fee.
+    pub fn bind_fee_spend(mut self, bind: bool) -> Self {
+        self.bind_fee_spend = bind;
+        self
+    }
+
+    /// Attach `memos` to the recipient's coin, after its puzzle hash hint
+    ///
+    /// Memos are public on chain once the coin is spent into existence, so they suit
+    /// references such as a store id or invoice number, not secrets.
+    pub fn with_memos(mut self, memos: Vec<Bytes>) -> Self {
+        self.memos = memos;
+        self
+    }
+}
+
+/// Coin spends of a transfer before signing, with the coins they consume
+#[derive(Debug, Clone)]
+pub(crate) struct UnsignedTransaction {
+    pub coin_spends: Vec<CoinSpend>,
+    pub spent_coins: Vec<Coin>,
+    /// The CAT coins among `spent_coins`, with their lineage
+    #[cfg_attr(not(feature = "cat"), allow(dead_code))]
+    pub cats: Vec<Cat>,
+    /// Where the change of the spends went
+    pub change_puzzle_hash: Bytes32,
+}
+
+/// A signed spend bundle together with the coins it consumes
+#[derive(Debug, Clone)]
+pub(crate) struct SignedTransaction {
+    pub spend_bundle: SpendBundle,
+    pub spent_coins: Vec<Coin>,
+}
+
+impl SignedTransaction {
+    pub fn spent_coin_ids_hex(&self) -> Vec<String> {
+        self.spent_coins
+            .iter()
+            .map(|coin| hex::encode(coin.coin_id()))
+            .collect()
+    }
+
+    /// XCH fee actually paid: the value of all spent coins minus the value of all created coins
+    ///
+    /// CAT spends conserve value, so only the XCH difference remains.
+    pub fn effective_fee(&self) -> Result<u64, WalletError> {
+        effective_fee(&self.spend_bundle.coin_spends)
+    }
+}
+
+/// Inputs minus outputs of a set of coin spends
+pub(crate) fn effective_fee(coin_spends: &[CoinSpend]) -> Result<u64, WalletError> {
+    let mut ctx = SpendContext::new();
+    let mut removals: u128 = 0;
+    let mut additions: u128 = 0;
+
+    for coin_spend in coin_spends {
+        removals += u128::from(coin_spend.coin.amount);
+        additions += run_coin_spend(&mut ctx, coin_spend)?
+            .into_iter()
+            .filter_map(|condition| condition.into_create_coin())
+            .map(|create_coin| u128::from(create_coin.amount))
+            .sum::<u128>();
+    }
+
+    let fee = removals.checked_sub(additions).ok_or_else(|| {
+        WalletError::TransactionFailed(format!(
+            "Spends create {} mojos more than they consume",
+            additions - removals
+        ))
+    })?;
+    u64::try_from(fee).map_err(|_| WalletError::TransactionFailed("Fee overflows u64".to_string()))
+}
+
+/// Id of the coin announcement `message` made by coin `coin_id`
+pub(crate) fn coin_announcement_id(coin_id: Bytes32, message: &[u8]) -> Bytes32 {
+    let mut hasher = Sha256::new();
+    hasher.update(coin_id);
+    hasher.update(message);
+    Bytes32::new(hasher.finalize().into())
+}
+
+/// Conditions for a CAT coin and a fee coin that make each spend require the other
+///
+/// Each coin announces [`FEE_BINDING_MESSAGE`] and asserts the other's announcement.
+pub(crate) fn fee_binding_conditions(
+    cat_coin_id: Bytes32,
+    fee_coin_id: Bytes32,
+) -> (Conditions, Conditions) {
+    let announce =
+        || Conditions::new().create_coin_announcement(Bytes::from(FEE_BINDING_MESSAGE.to_vec()));
+    (
+        announce().assert_coin_announcement(coin_announcement_id(fee_coin_id, FEE_BINDING_MESSAGE)),
+        announce().assert_coin_announcement(coin_announcement_id(cat_coin_id, FEE_BINDING_MESSAGE)),
+    )
+}
+
+pub(crate) fn driver_error(e: impl fmt::Display) -> WalletError {
+    WalletError::DataLayerError(format!("Failed to build spend: {}", e))
+}
+
+impl Wallet {
+    /// Move funds between two wallets of the same keyring
+    ///
+    /// The destination puzzle hash is derived from the keyring entry of `to_wallet`,
+    /// so no address string is involved. Both wallets record the move as
+    /// [`TransactionDirection::Internal`], which ledgers exclude from income and expense.
+    pub async fn transfer_internal(
+        peer: &Peer,
+        from_wallet: &Wallet,
+        to_wallet: &str,
+        asset: Asset,
+        amount: u64,
+        fee: u64,
+    ) -> Result<TransactionRecord, WalletError> {
+        let config = from_wallet.get_config();
+        let destination = Self::resolve_keyring_puzzle_hash(to_wallet, config).await?;
+
+        let transaction = from_wallet
+            .build_transfer(peer, asset, destination, amount, fee, vec![])
+            .await?;
+        let tx_id = from_wallet
+            .broadcast_transaction(peer, &transaction.spend_bundle)
+            .await?;
+
+        let timestamp = config.unix_timestamp();
+        let source_ph = from_wallet.get_owner_puzzle_hash().await?;
+
+        let outgoing = TransactionRecord {
+            tx_id: hex::encode(tx_id),
+            asset,
+            direction: TransactionDirection::Internal,
+            amount,
+            fee: transaction.effective_fee()?,
+            counterparty_puzzle_hash: Some(hex::encode(destination)),
+            counterparty_wallet: Some(to_wallet.to_string()),
+            spent_coin_ids: transaction.spent_coin_ids_hex(),
+            height: None,
+            timestamp,
+        };
+        from_wallet.transaction_history()?.record(&outgoing).await?;
+
+        let incoming = TransactionRecord {
+            counterparty_puzzle_hash: Some(hex::encode(source_ph)),
+            counterparty_wallet: Some(from_wallet.get_wallet_name().to_string()),
+            spent_coin_ids: vec![],
+            fee: 0,
+            ..outgoing.clone()
+        };
+        crate::history::TransactionHistory::open(to_wallet, config)?
+            .record(&incoming)
+            .await?;
+
+        Ok(outgoing)
+    }
+
+    /// Pay XCH to many addresses of the wallet's chain from one spend bundle
+    ///
+    /// Each recipient is an address, an amount in mojos and an optional memo, attached to
+    /// the recipient's coin after its puzzle hash hint. All payments share one change
+    /// output and one `fee`, so a payout costs far less than a transfer per recipient. The
+    /// history records a single outgoing transaction for the total, without a counterparty.
+    pub async fn send_many(
+        &self,
+        peer: &Peer,
+        recipients: Vec<(String, u64, Option<Bytes>)>,
+        fee: u64,
+    ) -> Result<TransactionRecord, WalletError> {
+        if recipients.is_empty() {
+            return Err(WalletError::InvalidAmount(
+                "No recipients to pay".to_string(),
+            ));
+        }
+        let config = self.get_config();
+        Self::check_fee_floor(peer, fee, config).await?;
+
+        let chain = self.chain_profile().await?;
+        let mut builder = TransactionBuilder::new().with_fee(fee);
+        for (address, amount, memo) in recipients {
+            let puzzle_hash = chain.decode_address(&address)?;
+            builder = builder.pay_with_memos(puzzle_hash, amount, memo.into_iter().collect());
+        }
+        let amount = builder.xch_required()? - fee;
+        let builder = self.fund_transaction(peer, builder, vec![]).await?;
+
+        let unsigned = self.build_transaction(builder).await?;
+        let transaction = SignedTransaction {
+            spend_bundle: self.sign_spend_bundle(unsigned.coin_spends).await?,
+            spent_coins: unsigned.spent_coins,
+        };
+        let tx_id = self
+            .broadcast_transaction(peer, &transaction.spend_bundle)
+            .await?;
+
+        let record = TransactionRecord {
+            tx_id: hex::encode(tx_id),
+            asset: Asset::Xch,
+            direction: TransactionDirection::Outgoing,
+            amount,
+            fee: transaction.effective_fee()?,
+            counterparty_puzzle_hash: None,
+            counterparty_wallet: None,
+            spent_coin_ids: transaction.spent_coin_ids_hex(),
+            height: None,
+            timestamp: config.unix_timestamp(),
+        };
+        self.transaction_history()?.record(&record).await?;
+
+        Ok(record)
+    }
+
+    /// Send `amount` mojos of XCH to an address of the wallet's chain
+    pub async fn send_xch(
+        &self,
+        peer: &Peer,
+        to_address: &str,
+        amount: u64,
+        fee: u64,
+    ) -> Result<TransactionRecord, WalletError> {
+        self.send_xch_with_options(peer, to_address, amount, fee, &DigSendOptions::default())
+            .await
+    }
+
+    /// Send XCH to an address, with the memos of `options` on the recipient's coin
+    pub async fn send_xch_with_options(
+        &self,
+        peer: &Peer,
+        to_address: &str,
+        amount: u64,
+        fee: u64,
+        options: &DigSendOptions,
+    ) -> Result<TransactionRecord, WalletError> {
+        let destination = self.validate_address(to_address).await?;
+        self.send_asset(peer, Asset::Xch, destination, amount, fee, options)
+            .await
+    }
+
+    /// Send `amount` mojos of DIG to an address of the wallet's chain, paying `fee` from a
+    /// separate XCH coin
+    ///
+    /// Amounts typed as DIG convert with [`crate::dig::dig_to_mojos`]. The CAT and fee spends
+    /// are bound with coin announcements, so the bundle is only valid as a whole; see
+    /// [`DigSendOptions::bind_fee_spend`] to opt out.
+    #[cfg(feature = "cat")]
+    pub async fn send_dig(
+        &self,
+        peer: &Peer,
+        to_address: &str,
+        amount: u64,
+        fee: u64,
+    ) -> Result<TransactionRecord, WalletError> {
+        let destination = self.validate_address(to_address).await?;
+        self.send_dig_with_options(peer, destination, amount, fee, &DigSendOptions::default())
+            .await
+    }
+
+    /// Send DIG to `destination`, assembling the spends according to `options`
+    #[cfg(feature = "cat")]
+    pub async fn send_dig_with_options(
+        &self,
+        peer: &Peer,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+        options: &DigSendOptions,
+    ) -> Result<TransactionRecord, WalletError> {
+        self.send_asset(peer, Asset::Dig, destination, amount, fee, options)
+            .await
+    }
+
+    /// Build, broadcast and record a transfer of `asset` to `destination`
+    pub(crate) async fn send_asset(
+        &self,
+        peer: &Peer,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+        options: &DigSendOptions,
+    ) -> Result<TransactionRecord, WalletError> {
+        let config = self.get_config();
+        let transaction = self
+            .build_transfer_with_options(peer, asset, destination, amount, fee, vec![], options)
+            .await?;
+        let tx_id = self
+            .broadcast_transaction(peer, &transaction.spend_bundle)
+            .await?;
+
+        let record = TransactionRecord {
+            tx_id: hex::encode(tx_id),
+            asset,
+            direction: TransactionDirection::Outgoing,
+            amount,
+            fee: transaction.effective_fee()?,
+            counterparty_puzzle_hash: Some(hex::encode(destination)),
+            counterparty_wallet: None,
+            spent_coin_ids: transaction.spent_coin_ids_hex(),
+            height: None,
+            timestamp: config.unix_timestamp(),
+        };
+        self.transaction_history()?.record(&record).await?;
+
+        Ok(record)
+    }
+
+    /// Resolve the receive puzzle hash of a keyring entry without going through an address
+    pub(crate) async fn resolve_keyring_puzzle_hash(
+        wallet_name: &str,
+        config: &WalletConfig,
+    ) -> Result<Bytes32, WalletError> {
+        let wallet = Wallet::load_with_config(Some(wallet_name.to_string()), false, config).await?;
+        wallet.get_owner_puzzle_hash().await
+    }
+
+    /// Select coins and build a signed spend bundle paying `amount` of `asset` to `destination`
+    pub(crate) async fn build_transfer(
+        &self,
+        peer: &Peer,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+        omit_coins: Vec<Coin>,
+    ) -> Result<SignedTransaction, WalletError> {
+        self.build_transfer_with_options(
+            peer,
+            asset,
+            destination,
+            amount,
+            fee,
+            omit_coins,
+            &DigSendOptions::default(),
+        )
+        .await
+    }
+
+    /// Build a transfer, assembling DIG spends according to `options`
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn build_transfer_with_options(
+        &self,
+        peer: &Peer,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+        omit_coins: Vec<Coin>,
+        options: &DigSendOptions,
+    ) -> Result<SignedTransaction, WalletError> {
+        let unsigned = self
+            .build_unsigned_transfer(peer, asset, destination, amount, fee, omit_coins, options)
+            .await?;
+        let spend_bundle = self.sign_spend_bundle(unsigned.coin_spends).await?;
+
+        Ok(SignedTransaction {
+            spend_bundle,
+            spent_coins: unsigned.spent_coins,
+        })
+    }
+
+    /// Select coins and build the coin spends of a transfer without signing them
+    ///
+    /// Only needs the wallet's public key, so it also works for signer-backed wallets.
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(not(feature = "cat"), allow(unused_variables))]
+    pub(crate) async fn build_unsigned_transfer(
+        &self,
+        peer: &Peer,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+        omit_coins: Vec<Coin>,
+        options: &DigSendOptions,
+    ) -> Result<UnsignedTransaction, WalletError> {
+        Self::check_fee_floor(peer, fee, self.get_config()).await?;
+
+        let builder = TransactionBuilder::new()
+            .with_fee(fee)
+            .bind_fee_spend(options.bind_fee_spend);
+        let builder = match asset {
+            Asset::Xch => {
+                let coins = self
+                    .select_unspent_coins(peer, amount, fee, omit_coins)
+                    .await?;
+                builder
+                    .add_coins(coins)
+                    .pay_with_memos(destination, amount, options.memos.clone())
+            }
+            #[cfg(not(feature = "cat"))]
+            Asset::Dig | Asset::Cat(_) => {
+                return Err(WalletError::FeatureDisabled("cat".to_string()))
+            }
+            #[cfg(feature = "cat")]
+            Asset::Dig | Asset::Cat(_) => {
+                let cats: Vec<Cat> = match asset {
+                    Asset::Cat(asset_id) => {
+                        self.select_unspent_cats(peer, asset_id, amount, omit_coins.clone())
+                            .await?
+                    }
+                    _ => self
+                        .select_unspent_dig_coins(peer, amount, omit_coins.clone(), false)
+                        .await?
+                        .iter()
+                        .map(|dig_coin| dig_coin.cat())
+                        .collect(),
+                };
+                let fee_coins = if fee > 0 {
+                    self.select_unspent_coins(peer, 0, fee, omit_coins).await?
+                } else {
+                    vec![]
+                };
+                builder
+                    .add_cats(cats)
+                    .pay_cat_with_memos(destination, amount, options.memos.clone())
+                    .add_coins(fee_coins)
+            }
+        };
+
+        self.build_transaction(builder).await
+    }
+
+    /// Standard layers of every address the wallet has issued
+    pub(crate) async fn spend_keys(&self) -> Result<SpendKeys, WalletError> {
+        Ok(SpendKeys::new(self.issued_synthetic_keys().await?))
+    }
+
+    /// Sign coin spends with the synthetic keys of the wallet's issued addresses
+    pub(crate) async fn sign_spend_bundle(
+        &self,
+        coin_spends: Vec<CoinSpend>,
+    ) -> Result<SpendBundle, WalletError> {
+        let signature = self.sign_coin_spends(&coin_spends).await?;
+        Ok(SpendBundle::new(coin_spends, signature))
+    }
+
+    /// Submit a spend bundle to the mempool, returning its transaction id
+    ///
+    /// The peer is first checked against the configured [`crate::PeakFreshness`]. An
+    /// accepted bundle is recorded in [`Wallet::pending_transactions`] until it confirms.
+    pub(crate) async fn broadcast_transaction(
+        &self,
+        peer: &Peer,
+        spend_bundle: &SpendBundle,
+    ) -> Result<Bytes32, WalletError> {
+        let config = self.get_config();
+        Self::check_peer_freshness(peer, config).await?;
+
+        let tx_id = spend_bundle.name();
+        let ack = peer
+            .send_transaction(spend_bundle.clone())
+            .await
+            .map_err(|e| WalletError::NetworkError(format!("Failed to broadcast: {}", e)))?;
+
+        if ack.status != MEMPOOL_STATUS_SUCCESS && ack.status != MEMPOOL_STATUS_PENDING {
+            return Err(WalletError::TransactionFailed(
+                ack.error
+                    .unwrap_or_else(|| format!("mempool status {}", ack.status)),
+            ));
+        }
+
+        self.pending_transactions()?
+            .track(&PendingTransaction::new(
+                spend_bundle,
+                config.unix_timestamp(),
+            )?)
+            .await?;
+        Ok(tx_id)
+    }
+}
+
+/// Standard layers of a wallet's issued addresses, looked up by puzzle hash
+#[derive(Debug, Clone)]
+pub(crate) struct SpendKeys {
+    layers: HashMap<Bytes32, StandardLayer>,
+}
+
+impl SpendKeys {
+    pub fn new(synthetic_keys: impl IntoIterator<Item = PublicKey>) -> Self {
+        Self {
+            layers: synthetic_keys
+                .into_iter()
+                .map(|key| (standard_puzzle_hash(&key), StandardLayer::new(key)))
+                .collect(),
+        }
+    }
+
+    /// The layer spending coins locked to `puzzle_hash`
+    pub(crate) fn layer(&self, puzzle_hash: Bytes32) -> Result<&StandardLayer, WalletError> {
+        self.layers.get(&puzzle_hash).ok_or_else(|| {
+            WalletError::TransactionFailed(format!(
+                "Coin puzzle hash {} is not an address of this wallet",
+                hex::encode(puzzle_hash)
+            ))
+        })
+    }
+
+    /// Spend everything `spends` holds once its actions are applied: the wallet's coins with
+    /// their standard layers, settlement coins with their notarized payments
+    pub fn finish_spends(
+        &self,
+        ctx: &mut SpendContext,
+        spends: Spends,
+        deltas: &Deltas,
+    ) -> Result<Outputs, WalletError> {
+        spends
+            .finish(
+                ctx,
+                deltas,
+                Relation::AssertConcurrent,
+                |ctx, asset, kind| match kind {
+                    SpendKind::Conditions(spend) => self
+                        .layers
+                        .get(&asset.p2_puzzle_hash())
+                        .ok_or(DriverError::MissingKey)?
+                        .spend_with_conditions(ctx, spend.finish()),
+                    SpendKind::Settlement(spend) => SettlementLayer
+                        .construct_spend(ctx, SettlementPaymentsSolution::new(spend.finish())),
+                },
+            )
+            .map_err(driver_error)
+    }
+}
+
+/// Spend standard XCH coins, attaching `conditions` and change to the first coin
+///
+/// Change below `dust_threshold` is added to the fee instead.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn spend_xch_coins(
+    ctx: &mut SpendContext,
+    keys: &SpendKeys,
+    coins: &[Coin],
+    conditions: Conditions,
+    amount: u64,
+    fee: u64,
+    change_puzzle_hash: Bytes32,
+    dust_threshold: u64,
+) -> Result<(), WalletError> {
+    let total: u64 = coins.iter().map(|coin| coin.amount).sum();
+    let change = total
+        .checked_sub(amount + fee)
+        .ok_or(WalletError::NoUnspentCoins)?;
+    let (fee, change) = burn_dust(fee, change, dust_threshold);
+
+    let mut conditions = conditions;
+    if fee > 0 {
+        conditions = conditions.reserve_fee(fee);
+    }
+    if change > 0 {
+        conditions = conditions.create_coin(change_puzzle_hash, change, Memos::None);
+    }
+
+    let first_coin_id = coins[0].coin_id();
+    for (index, coin) in coins.iter().enumerate() {
+        let coin_conditions = if index == 0 {
+            conditions.clone()
+        } else {
+            Conditions::new().assert_concurrent_spend(first_coin_id)
+        };
+        keys.layer(coin.puzzle_hash)?
+            .spend(ctx, *coin, coin_conditions)
+            .map_err(driver_error)?;
+    }
+
+    Ok(())
+}
+
+/// Spend CAT coins, adding `conditions` to the first coin's inner spend
+pub(crate) fn spend_cats_with_conditions(
+    ctx: &mut SpendContext,
+    keys: &SpendKeys,
+    cats: &[Cat],
+    outputs: &[Payment],
+    change_puzzle_hash: Bytes32,
+    conditions: Conditions,
+) -> Result<(), WalletError> {
+    let total: u64 = cats.iter().map(|cat| cat.coin.amount).sum();
+    let paid = outputs
+        .iter()
+        .try_fold(0u64, |sum, payment| sum.checked_add(payment.amount))
+        .ok_or(WalletError::NoUnspentCoins)?;
+    let change = total.checked_sub(paid).ok_or(WalletError::NoUnspentCoins)?;
+
+    let mut conditions = conditions;
+    for payment in outputs {
+        let memos = payment.create_coin_memos(ctx, true)?;
+        conditions = conditions.create_coin(payment.puzzle_hash, payment.amount, memos);
+    }
+    if change > 0 {
+        let change_hint = ctx.hint(change_puzzle_hash).map_err(driver_error)?;
+        conditions = conditions.create_coin(change_puzzle_hash, change, change_hint);
+    }
+
+    let mut cat_spends = Vec::with_capacity(cats.len());
+    for (index, cat) in cats.iter().enumerate() {
+        let inner_conditions = if index == 0 {
+            conditions.clone()
+        } else {
+            Conditions::new()
+        };
+        let inner_spend = keys
+            .layer(cat.info.p2_puzzle_hash)?
+            .spend_with_conditions(ctx, inner_conditions)
+            .map_err(driver_error)?;
+        cat_spends.push(CatSpend::new(*cat, inner_spend));
+    }
+
+    Cat::spend_all(ctx, &cat_spends).map_err(driver_error)?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_resolve_keyring_puzzle_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+
+        Wallet::create_new_wallet_with_config("receiver", &config)
+            .await
+            .unwrap();
+        let receiver = Wallet::load_with_config(Some("receiver".to_string()), false, &config)
+            .await
+            .unwrap();
+
+        let resolved = Wallet::resolve_keyring_puzzle_hash("receiver", &config)
+            .await
+            .unwrap();
+        assert_eq!(resolved, receiver.get_owner_puzzle_hash().await.unwrap());
+
+        let missing = Wallet::resolve_keyring_puzzle_hash("missing", &config).await;
+        assert!(matches!(missing, Err(WalletError::WalletNotFound(_))));
+    }
+
+    #[test]
+    fn test_fee_binding_requires_both_spends() {
+        let cat_coin_id = Bytes32::new([1; 32]);
+        let fee_coin_id = Bytes32::new([2; 32]);
+        let message = Bytes::from(FEE_BINDING_MESSAGE.to_vec());
+
+        let (cat_conditions, fee_conditions) = fee_binding_conditions(cat_coin_id, fee_coin_id);
+
+        // Each spend announces, and asserts the announcement only the other coin can make
+        let expected_cat = Conditions::new()
+            .create_coin_announcement(message.clone())
+            .assert_coin_announcement(coin_announcement_id(fee_coin_id, FEE_BINDING_MESSAGE));
+        let expected_fee = Conditions::new()
+            .create_coin_announcement(message)
+            .assert_coin_announcement(coin_announcement_id(cat_coin_id, FEE_BINDING_MESSAGE));
+        assert_eq!(
+            format!("{:?}", cat_conditions),
+            format!("{:?}", expected_cat)
+        );
+        assert_eq!(
+            format!("{:?}", fee_conditions),
+            format!("{:?}", expected_fee)
+        );
+
+        assert_ne!(
+            coin_announcement_id(cat_coin_id, FEE_BINDING_MESSAGE),
+            coin_announcement_id(fee_coin_id, FEE_BINDING_MESSAGE)
+        );
+        assert_eq!(
+            coin_announcement_id(cat_coin_id, FEE_BINDING_MESSAGE),
+            Bytes32::new(Sha256::digest([&[1u8; 32][..], FEE_BINDING_MESSAGE].concat()).into())
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use crate::asset::Asset;
+use crate::coin_proof::SerializedCoin;
+use crate::config::WalletConfig;
+use crate::encoding::{bytes32_from_hex, bytes_from_hex};
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::transfer::SignedTransaction;
+use crate::wallet::Wallet;
+use chia::traits::Streamable;
+use datalayer_driver::{Bytes32, Coin, Peer, SpendBundle};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Lifecycle of a locally queued transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueStatus {
+    /// Built and signed, waiting to be broadcast
+    Queued,
+    /// Submitted to the mempool
+    Broadcast,
+    /// Consumes coins already used by another outstanding transaction
+    Conflicted,
+    Cancelled,
+}
+
+/// How [`TxQueue::resolve_conflict`] settles a conflicted transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Drop the conflicted transaction
+    Cancel,
+    /// Rebuild the same transfer from coins no outstanding transaction uses
+    RebuildWithOtherCoins,
+}
+
+/// What a queued transaction is meant to achieve, kept so it can be rebuilt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferIntent {
+    pub asset: Asset,
+    /// Destination puzzle hash as hex
+    pub destination: String,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+/// A signed transaction held in the local queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedTransaction {
+    /// Spend bundle name as hex
+    pub tx_id: String,
+    pub intent: TransferIntent,
+    pub spent_coins: Vec<SerializedCoin>,
+    /// Serialized spend bundle as hex
+    pub spend_bundle: String,
+    pub status: QueueStatus,
+    pub created_at: u64,
+}
+
+impl QueuedTransaction {
+    fn new(
+        intent: TransferIntent,
+        transaction: &SignedTransaction,
+        created_at: u64,
+    ) -> Result<Self, WalletError> {
+        let spend_bundle = transaction.spend_bundle.to_bytes().map_err(|e| {
+            WalletError::SerializationError(format!("Failed to serialize spend bundle: {}", e))
+        })?;
+
+        Ok(Self {
+            tx_id: hex::encode(transaction.spend_bundle.name()),
+            intent,
+            spent_coins: transaction
+                .spent_coins
+                .iter()
+                .map(SerializedCoin::from)
+                .collect(),
+            spend_bundle: hex::encode(spend_bundle),
+            status: QueueStatus::Queued,
+            created_at,
+        })
+    }
+
+    /// Coin ids consumed by this transaction as hex
+    pub fn spent_coin_ids(&self) -> Result<Vec<String>, WalletError> {
+        self.spent_coins
+            .iter()
+            .map(|coin| Ok(hex::encode(coin.to_coin()?.coin_id())))
+            .collect()
+    }
+
+    /// Decode the stored spend bundle
+    pub fn spend_bundle(&self) -> Result<SpendBundle, WalletError> {
+        SpendBundle::from_bytes(&bytes_from_hex(&self.spend_bundle)?).map_err(|e| {
+            WalletError::SerializationError(format!("Failed to deserialize spend bundle: {}", e))
+        })
+    }
+
+    fn is_outstanding(&self) -> bool {
+        matches!(self.status, QueueStatus::Queued | QueueStatus::Broadcast)
+    }
+}
+
+/// Locally persisted queue of outstanding transactions of one wallet, keyed by transaction id
+pub struct TxQueue {
+    cache: FileCache<QueuedTransaction>,
+}
+
+impl TxQueue {
+    /// Open the queue of a wallet under the configured `.dig` directory
+    pub fn open(wallet_name: &str, config: &WalletConfig) -> Result<Self, WalletError> {
+        Ok(Self {
+            cache: FileCache::from_config(&format!("tx_queue/{}", wallet_name), config)?,
+        })
+    }
+
+    /// Get a queued transaction by id
+    pub fn get(&self, tx_id: &str) -> Result<Option<QueuedTransaction>, WalletError> {
+        self.cache.get(tx_id)
+    }
+
+    /// All queued transactions, oldest first
+    pub fn list(&self) -> Result<Vec<QueuedTransaction>, WalletError> {
+        let mut transactions = Vec::new();
+        for key in self.cache.get_cached_keys()? {
+            if let Some(transaction) = self.cache.get(&key)? {
+                transactions.push(transaction);
+            }
+        }
+        transactions.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.tx_id.cmp(&b.tx_id))
+        });
+        Ok(transactions)
+    }
+
+    /// Transactions that are queued or broadcast and still hold their coins
+    pub fn outstanding(&self) -> Result<Vec<QueuedTransaction>, WalletError> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(QueuedTransaction::is_outstanding)
+            .collect())
+    }
+
+    /// Find an outstanding transaction, other than `transaction` itself, consuming one of its coins
+    pub fn find_conflict(
+        &self,
+        transaction: &QueuedTransaction,
+    ) -> Result<Option<WalletError>, WalletError> {
+        let mut spent_by = HashMap::new();
+        for other in self.outstanding()? {
+            if other.tx_id == transaction.tx_id {
+                continue;
+            }
+            for coin_id in other.spent_coin_ids()? {
+                spent_by.insert(coin_id, other.tx_id.clone());
+            }
+        }
+
+        for coin_id in transaction.spent_coin_ids()? {
+            if let Some(other_tx) = spent_by.remove(&coin_id) {
+                return Ok(Some(WalletError::ConflictingSpend { coin_id, other_tx }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Add a transaction to the queue
+    ///
+    /// A transaction overlapping an outstanding one is kept as
+    /// [`QueueStatus::Conflicted`] and [`WalletError::ConflictingSpend`] is returned.
+    pub async fn enqueue(&self, mut transaction: QueuedTransaction) -> Result<(), WalletError> {
+        let conflict = self.find_conflict(&transaction)?;
+        transaction.status = if conflict.is_some() {
+            QueueStatus::Conflicted
+        } else {
+            QueueStatus::Queued
+        };
+        self.cache.set(&transaction.tx_id, &transaction).await?;
+
+        match conflict {
+            Some(conflict) => Err(conflict),
+            None => Ok(()),
+        }
+    }
+
+    /// Mark a transaction as cancelled, releasing its coins
+    pub async fn cancel(&self, tx_id: &str) -> Result<(), WalletError> {
+        self.set_status(tx_id, QueueStatus::Cancelled).await
+    }
+
+    /// Remove a transaction from the queue entirely
+    pub async fn remove(&self, tx_id: &str) -> Result<(), WalletError> {
+        self.cache.delete(tx_id).await
+    }
+
+    /// Settle a conflicted transaction, returning the rebuilt replacement if any
+    pub async fn resolve_conflict(
+        &self,
+        wallet: &Wallet,
+        peer: &Peer,
+        tx_id: &str,
+        strategy: ConflictStrategy,
+    ) -> Result<Option<QueuedTransaction>, WalletError> {
+        let conflicted = self.require(tx_id)?;
+        if conflicted.status != QueueStatus::Conflicted {
+            return Err(WalletError::TransactionFailed(format!(
+                "Transaction {} is not conflicted",
+                tx_id
+            )));
+        }
+
+        self.cancel(tx_id).await?;
+        match strategy {
+            ConflictStrategy::Cancel => Ok(None),
+            ConflictStrategy::RebuildWithOtherCoins => {
+                let mut omit_coins = Vec::new();
+                for other in self.outstanding()? {
+                    for coin in &other.spent_coins {
+                        omit_coins.push(coin.to_coin()?);
+                    }
+                }
+
+                let rebuilt = wallet
+                    .build_queued_transaction(peer, conflicted.intent, omit_coins)
+                    .await?;
+                self.enqueue(rebuilt.clone()).await?;
+                Ok(Some(rebuilt))
+            }
+        }
+    }
+
+    fn require(&self, tx_id: &str) -> Result<QueuedTransaction, WalletError> {
+        self.get(tx_id)?.ok_or_else(|| {
+            WalletError::TransactionFailed(format!("Transaction {} is not queued", tx_id))
+        })
+    }
+
+    async fn set_status(&self, tx_id: &str, status: QueueStatus) -> Result<(), WalletError> {
+        let mut transaction = self.require(tx_id)?;
+        transaction.status = status;
+        self.cache.set(tx_id, &transaction).await
+    }
+}
+
+impl Wallet {
+    /// Open the local transaction queue of this wallet
+    pub fn tx_queue(&self) -> Result<TxQueue, WalletError> {
+        TxQueue::open(&self.cache_name(), self.get_config())
+    }
+
+    /// Build, sign and queue a transfer without broadcasting it
+    pub async fn queue_transfer(
+        &self,
+        peer: &Peer,
+        asset: Asset,
+        destination: Bytes32,
+        amount: u64,
+        fee: u64,
+    ) -> Result<QueuedTransaction, WalletError> {
+        let intent = TransferIntent {
+            asset,
+            destination: hex::encode(destination),
+            amount,
+            fee,
+        };
+        let transaction = self.build_queued_transaction(peer, intent, vec![]).await?;
+        self.tx_queue()?.enqueue(transaction.clone()).await?;
+        Ok(transaction)
+    }
+
+    /// Broadcast a queued transaction, refusing conflicted or cancelled ones
+    pub async fn broadcast_queued(&self, peer: &Peer, tx_id: &str) -> Result<Bytes32, WalletError> {
+        let queue = self.tx_queue()?;
+        let transaction = queue.require(tx_id)?;
+        if transaction.status != QueueStatus::Queued {
+            return Err(WalletError::TransactionFailed(format!(
+                "Transaction {} cannot be broadcast from status {:?}",
+                tx_id, transaction.status
+            )));
+        }
+        if let Some(conflict) = queue.find_conflict(&transaction)? {
+            return Err(conflict);
+        }
+
+        let broadcast_id = self
+            .broadcast_transaction(peer, &transaction.spend_bundle()?)
+            .await?;
+        queue.set_status(tx_id, QueueStatus::Broadcast).await?;
+        Ok(broadcast_id)
+    }
+
+    async fn build_queued_transaction(
+        &self,
+        peer: &Peer,
+        intent: TransferIntent,
+        omit_coins: Vec<Coin>,
+    ) -> Result<QueuedTransaction, WalletError> {
+        let destination = bytes32_from_hex(&intent.destination)?;
+        let transaction = self
+            .build_transfer(
+                peer,
+                intent.asset,
+                destination,
+                intent.amount,
+                intent.fee,
+                omit_coins,
+            )
+            .await?;
+        QueuedTransaction::new(intent, &transaction, self.get_config().unix_timestamp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    fn queued(tx_id: &str, coins: &[Coin]) -> QueuedTransaction {
+        QueuedTransaction {
+            tx_id: tx_id.to_string(),
+            intent: TransferIntent {
+                asset: Asset::Xch,
+                destination: hex::encode([3u8; 32]),
+                amount: 1,
+                fee: 0,
+            },
+            spent_coins: coins.iter().map(SerializedCoin::from).collect(),
+            spend_bundle: String::new(),
+            status: QueueStatus::Queued,
+            created_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_spends_conflict() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let queue = TxQueue::open("queue_test", &config).unwrap();
+
+        let shared = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 10);
+        let other = Coin::new(Bytes32::new([4; 32]), Bytes32::new([2; 32]), 20);
+
+        queue.enqueue(queued("first", &[shared])).await.unwrap();
+        queue.enqueue(queued("unrelated", &[other])).await.unwrap();
+
+        let result = queue.enqueue(queued("second", &[other, shared])).await;
+        match result {
+            Err(WalletError::ConflictingSpend { other_tx, .. }) => {
+                assert!(other_tx == "first" || other_tx == "unrelated")
+            }
+            other => panic!("expected conflict, got {:?}", other),
+        }
+        assert_eq!(
+            queue.get("second").unwrap().unwrap().status,
+            QueueStatus::Conflicted
+        );
+        assert_eq!(queue.outstanding().unwrap().len(), 2);
+
+        // Cancelling the holder frees its coins for new transactions
+        queue.cancel("first").await.unwrap();
+        queue.cancel("unrelated").await.unwrap();
+        queue.enqueue(queued("third", &[shared])).await.unwrap();
+        assert_eq!(queue.outstanding().unwrap().len(), 1);
+    }
+}
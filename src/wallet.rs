@@ -1,82 +1,582 @@
+use crate::accounts::account_secret_key;
+#[cfg(feature = "peer")]
+use crate::alerts::BalanceWatcher;
+#[cfg(feature = "peer")]
+use crate::checkpoint::{ForkEvent, FORK_EVENT_CAPACITY};
+use crate::config::WalletConfig;
+use crate::crypto::{
+    aes_decrypt, aes_encrypt, derive_password_key, hmac_sha256, verify_hmac_sha256, zeroizing_utf8,
+    KDF_ARGON2ID,
+};
 use crate::error::WalletError;
+use crate::keys::{
+    decode_address, encode_address, farmer_secret_key, first_puzzle_hash, local_secret_key,
+    pool_secret_key, sign_message, standard_puzzle_hash, synthetic_public_key,
+    synthetic_public_key_at, synthetic_secret_key, synthetic_secret_key_at, verify_message,
+};
+use crate::memlock::{MnemonicHandle, SecretString};
+use crate::metadata::{ChainProfile, WalletInfo, WalletMetadata};
+use crate::migrations::{MigrationPlan, MigrationStep, Migrations};
+use crate::mnemonic::{parse_mnemonic, validate_mnemonic, MnemonicLength};
+use crate::session::{lock_sessions, WalletSession};
+use crate::signer::Signer;
+use crate::templates::KEY_OWNERSHIP_TEMPLATE;
+use crate::threshold::ThresholdKey;
+use crate::watch_only::WatchOnlyKey;
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{Aead, KeyInit},
     Aes256Gcm, Key, Nonce,
 };
 use base64::{engine::general_purpose, Engine as _};
-use bip39::{Language, Mnemonic};
-use chia::protocol::CoinState;
-use datalayer_driver::{
-    address_to_puzzle_hash, connect_random, get_coin_id, master_public_key_to_first_puzzle_hash,
-    master_public_key_to_wallet_synthetic_key, master_secret_key_to_wallet_synthetic_secret_key,
-    puzzle_hash_to_address, secret_key_to_public_key, sign_message, verify_signature, Bytes,
-    Bytes32, Coin, CoinSpend, DigCoin, NetworkType, Peer, PublicKey, SecretKey, Signature,
-};
+use chia::bls::{PublicKey, SecretKey, Signature};
+use chia::protocol::Bytes32;
+use chia::sha2::Sha256;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::env;
-use std::fs;
-use std::path::PathBuf;
+#[cfg(feature = "cache")]
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "peer")]
+use tokio::sync::broadcast;
+use zeroize::Zeroizing;
 
-const KEYRING_FILE: &str = "keyring.json";
 // Cache duration constant - keeping for potential future use
 #[allow(dead_code)]
 const CACHE_DURATION_MS: u64 = 5 * 60 * 1000; // 5 minutes
 pub const DEFAULT_FEE_COIN_COST: u64 = 64_000_000;
+/// Optimistic keyring writes retried on version conflicts before giving up
+pub(crate) const KEYRING_WRITE_ATTEMPTS: usize = 5;
+/// Keyring format written by this build
+///
+/// - 0: the original unversioned format
+/// - 1: adds `version`; entries may carry a `kdf` when passphrase-protected
+/// - 2: adds `checksum`, verified on every load
+/// - 3: the checksum becomes an HMAC-SHA256, keyed from the keyring passphrase once the
+///   keyring holds passphrase-protected entries
+pub const KEYRING_FORMAT_VERSION: u32 = 3;
+/// First keyring format carrying a checksum
+const KEYRING_CHECKSUM_VERSION: u32 = 2;
+/// First keyring format whose checksum is an HMAC
+const KEYRING_HMAC_VERSION: u32 = 3;
+/// HMAC key of keyrings written without the keyring passphrase, which have no secret to key with
+const KEYRING_UNKEYED_HMAC_KEY: &[u8] = b"dig-wallet keyring";
+/// Passphrase-derived checksum keys kept by [`CHECKSUM_KEYS`]
+const CHECKSUM_KEY_CACHE_CAPACITY: usize = 8;
+/// Checksum keys already derived, with the keyring salt and passphrase they were derived
+/// from, so keyring writes do not each run Argon2id
+#[allow(clippy::type_complexity)]
+static CHECKSUM_KEYS: Mutex<Vec<(Vec<u8>, Zeroizing<String>, Zeroizing<[u8; 32]>)>> =
+    Mutex::new(Vec::new());
+/// Upgrades of the keyring JSON, indexed by the format version they upgrade from
+static KEYRING_STEPS: [MigrationStep; KEYRING_FORMAT_VERSION as usize] = [
+    MigrationStep::new(0, "add the keyring format version", migrate_keyring_v0),
+    MigrationStep::new(1, "add an integrity checksum", migrate_keyring_v1),
+    MigrationStep::new(2, "key the checksum with HMAC-SHA256", migrate_keyring_v2),
+];
+pub(crate) static KEYRING_MIGRATIONS: Migrations = Migrations::new("keyring", &KEYRING_STEPS);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct EncryptedData {
     data: String,
     nonce: String,
     salt: String,
+    /// Key derivation of passphrase-protected entries; absent for legacy entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<String>,
+    #[serde(default, skip_serializing_if = "WalletMetadata::is_empty")]
+    metadata: WalletMetadata,
+    /// Public key of watch-only entries, which have no encrypted mnemonic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    watch_only: Option<WatchOnlyKey>,
+    /// Participants of threshold wallets, which are also watch-only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<ThresholdKey>,
+}
+
+impl EncryptedData {
+    fn is_password_protected(&self) -> bool {
+        self.kdf.is_some()
+    }
+
+    fn watch_only(key: WatchOnlyKey) -> Self {
+        Self {
+            data: String::new(),
+            nonce: String::new(),
+            salt: String::new(),
+            kdf: None,
+            metadata: WalletMetadata::default(),
+            watch_only: Some(key),
+            threshold: None,
+        }
+    }
+
+    /// Encrypt `mnemonic` afresh under `config`, keeping the entry's metadata
+    fn reencrypt(&mut self, mnemonic: &str, config: &WalletConfig) -> Result<(), WalletError> {
+        let metadata = std::mem::take(&mut self.metadata);
+        *self = Wallet::encrypt_data(mnemonic, config)?;
+        self.metadata = metadata;
+        Ok(())
+    }
+}
+
+/// How far the checksum of a loaded keyring could be checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChecksumStatus {
+    /// The checksum matched
+    Verified,
+    /// The keyring is stored in a format without a checksum
+    Absent,
+    /// The checksum is keyed by the keyring passphrase, which the configuration lacks
+    NeedsPassphrase,
+}
+
+/// Argon2id checksum key of the keyring salted with `salt`, derived once per passphrase
+fn checksum_passphrase_key(
+    passphrase: &str,
+    salt: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, WalletError> {
+    let mut keys = CHECKSUM_KEYS.lock().unwrap_or_else(|e| e.into_inner());
+    let cached = keys
+        .iter()
+        .find(|(cached_salt, cached_passphrase, _)| {
+            cached_salt == salt && cached_passphrase.as_str() == passphrase
+        })
+        .map(|(_, _, key)| key.clone());
+    if let Some(key) = cached {
+        return Ok(key);
+    }
+
+    let key = derive_password_key(passphrase, salt)?;
+    if keys.len() >= CHECKSUM_KEY_CACHE_CAPACITY {
+        keys.remove(0);
+    }
+    keys.push((
+        salt.to_vec(),
+        Zeroizing::new(passphrase.to_string()),
+        key.clone(),
+    ));
+    Ok(key)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct KeyringData {
+    #[serde(default)]
+    version: u32,
     wallets: HashMap<String, EncryptedData>,
+    /// Hex HMAC-SHA256 of the version and entries; see [`KeyringData::seal`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
+    /// Base64 salt the checksum key is derived with from the keyring passphrase; only
+    /// present when the checksum is keyed by the passphrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum_salt: Option<String>,
+}
+
+impl KeyringData {
+    fn empty() -> Self {
+        Self {
+            version: KEYRING_FORMAT_VERSION,
+            wallets: HashMap::new(),
+            checksum: None,
+            checksum_salt: None,
+        }
+    }
+
+    /// What the checksum of format `version` covers: the version and the entries in name
+    /// order, so it does not depend on how the file was formatted
+    fn checksum_content(&self, version: u32) -> Result<Vec<u8>, WalletError> {
+        let wallets: BTreeMap<&String, &EncryptedData> = self.wallets.iter().collect();
+        serde_json::to_vec(&(version, wallets))
+            .map_err(|e| WalletError::SerializationError(e.to_string()))
+    }
+
+    /// Plain SHA-256 checksum of format 2 keyrings
+    fn legacy_checksum(&self) -> Result<String, WalletError> {
+        let mut hasher = Sha256::new();
+        hasher.update(b"dig-wallet keyring");
+        hasher.update(self.checksum_content(KEYRING_CHECKSUM_VERSION)?);
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// The salt of the passphrase-derived checksum key, if the checksum is keyed
+    fn checksum_salt(&self) -> Result<Option<Vec<u8>>, WalletError> {
+        self.checksum_salt
+            .as_deref()
+            .map(|salt| {
+                general_purpose::STANDARD.decode(salt).map_err(|_| {
+                    WalletError::KeyringCorrupted("checksum salt is invalid".to_string())
+                })
+            })
+            .transpose()
+    }
+
+    /// The error of writes and wallet loads that need the keyring passphrase
+    fn locked_error(&self) -> WalletError {
+        let protected = self
+            .wallets
+            .iter()
+            .filter(|(_, entry)| entry.is_password_protected())
+            .map(|(name, _)| name)
+            .min();
+        WalletError::KeyringLocked(protected.map_or("keyring", String::as_str).to_string())
+    }
+
+    /// Check the checksum of a keyring stored in `stored_version`
+    ///
+    /// With the keyring passphrase in `config`, only a checksum keyed by it is accepted:
+    /// one written without it fails with [`WalletError::KeyringUnkeyed`] until
+    /// [`Wallet::migrate_keyring`] adopts the keyring, so nobody can swap entries and
+    /// re-sign with the public unkeyed key. Without the passphrase a keyed checksum
+    /// cannot be checked. A wrong passphrase fails with [`WalletError::InvalidPassphrase`],
+    /// a mismatch with [`WalletError::KeyringCorrupted`].
+    fn verify_checksum(
+        &self,
+        stored_version: u32,
+        config: &WalletConfig,
+    ) -> Result<ChecksumStatus, WalletError> {
+        if stored_version < KEYRING_CHECKSUM_VERSION {
+            return Ok(ChecksumStatus::Absent);
+        }
+        let checksum = self
+            .checksum
+            .as_deref()
+            .ok_or_else(|| WalletError::KeyringCorrupted("checksum is missing".to_string()))?;
+        let valid = if stored_version < KEYRING_HMAC_VERSION {
+            checksum == self.legacy_checksum()?
+        } else {
+            let tag = hex::decode(checksum)
+                .map_err(|_| WalletError::KeyringCorrupted("checksum is not hex".to_string()))?;
+            let content = self.checksum_content(self.version)?;
+            match (config.keyring_passphrase(), self.checksum_salt()?) {
+                (Some(passphrase), Some(salt)) => verify_hmac_sha256(
+                    checksum_passphrase_key(passphrase, &salt)?.as_ref(),
+                    &content,
+                    &tag,
+                )?,
+                (Some(_), None) => {
+                    if verify_hmac_sha256(KEYRING_UNKEYED_HMAC_KEY, &content, &tag)? {
+                        return Err(WalletError::KeyringUnkeyed);
+                    }
+                    false
+                }
+                (None, Some(_)) => return Ok(ChecksumStatus::NeedsPassphrase),
+                (None, None) => verify_hmac_sha256(KEYRING_UNKEYED_HMAC_KEY, &content, &tag)?,
+            }
+        };
+        if valid {
+            return Ok(ChecksumStatus::Verified);
+        }
+
+        // A wrong passphrase derives the wrong key; tell it apart by decrypting an entry
+        let protected = self
+            .wallets
+            .values()
+            .find(|entry| entry.is_password_protected());
+        if let Some(entry) = protected {
+            if let Err(WalletError::InvalidPassphrase) = Wallet::decrypt_data(entry, config) {
+                return Err(WalletError::InvalidPassphrase);
+            }
+        }
+        Err(WalletError::KeyringCorrupted(
+            "checksum does not match the content".to_string(),
+        ))
+    }
+
+    /// Serialize the keyring with a fresh checksum
+    ///
+    /// With the keyring passphrase in `config` the checksum is keyed by it. Without it
+    /// only keyrings that were never keyed and hold no passphrase-protected entries can
+    /// be written; others fail with [`WalletError::KeyringLocked`].
+    fn seal(&mut self, config: &WalletConfig) -> Result<Vec<u8>, WalletError> {
+        let content = self.checksum_content(self.version)?;
+        let checksum = match config.keyring_passphrase() {
+            Some(passphrase) => {
+                let salt = match self.checksum_salt()? {
+                    Some(salt) => salt,
+                    None => {
+                        let salt = config.random_bytes::<16>()?.to_vec();
+                        self.checksum_salt = Some(general_purpose::STANDARD.encode(&salt));
+                        salt
+                    }
+                };
+                hmac_sha256(
+                    checksum_passphrase_key(passphrase, &salt)?.as_ref(),
+                    &content,
+                )?
+            }
+            None => {
+                if self.checksum_salt.is_some()
+                    || self
+                        .wallets
+                        .values()
+                        .any(EncryptedData::is_password_protected)
+                {
+                    return Err(self.locked_error());
+                }
+                hmac_sha256(KEYRING_UNKEYED_HMAC_KEY, &content)?
+            }
+        };
+        self.checksum = Some(hex::encode(checksum));
+        serde_json::to_vec_pretty(self).map_err(|e| WalletError::SerializationError(e.to_string()))
+    }
+
+    /// Parse a keyring of any supported format, upgrading it to the current one in memory
+    ///
+    /// Returns the keyring, the format version it was stored in and how far its checksum
+    /// could be checked with `config`; see [`Self::verify_checksum`].
+    fn parse(
+        data: &[u8],
+        config: &WalletConfig,
+    ) -> Result<(Self, u32, ChecksumStatus), WalletError> {
+        let value: serde_json::Value = serde_json::from_slice(data)
+            .map_err(|e| WalletError::KeyringCorrupted(format!("not valid JSON: {}", e)))?;
+        let (value, stored_version) = KEYRING_MIGRATIONS.apply(&value)?;
+
+        let keyring: Self = serde_json::from_value(value)
+            .map_err(|e| WalletError::KeyringCorrupted(e.to_string()))?;
+        let status = keyring.verify_checksum(stored_version, config)?;
+        Ok((keyring, stored_version, status))
+    }
+}
+
+/// Version 0 entries are valid version 1 entries without a `kdf`; only `version` is added
+fn migrate_keyring_v0(_keyring: &mut serde_json::Value) -> Result<(), WalletError> {
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
+/// The checksum is computed whenever the keyring is written, including right after upgrading
+fn migrate_keyring_v1(_keyring: &mut serde_json::Value) -> Result<(), WalletError> {
+    Ok(())
+}
+
+/// The old checksum was verified on load; the HMAC replaces it when the keyring is written
+fn migrate_keyring_v2(_keyring: &mut serde_json::Value) -> Result<(), WalletError> {
+    Ok(())
+}
+
+fn master_secret_key_from_mnemonic(mnemonic_str: &str) -> Result<SecretKey, WalletError> {
+    let seed = Zeroizing::new(parse_mnemonic(mnemonic_str)?.to_seed(""));
+    Ok(SecretKey::from_seed(seed.as_ref()))
+}
+
+/// Result of [`Wallet::verify_keyring`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyringVerification {
+    /// Format the keyring is stored in; older formats are upgraded by
+    /// [`Wallet::apply_keyring_migration`]
+    pub format_version: u32,
+    /// Whether the checksum was verified; not for formats without one, nor without the
+    /// keyring passphrase when the checksum is keyed by it
+    pub checksummed: bool,
+    /// Wallets whose entries were decrypted and checked, sorted by name
+    pub verified: Vec<String>,
+    /// Passphrase-protected wallets skipped for lack of the keyring passphrase
+    pub unverified: Vec<String>,
+}
+
+/// A loaded wallet; each copy of its mnemonic is wiped from memory when dropped
+#[derive(Clone)]
 pub struct Wallet {
-    mnemonic: Option<String>,
+    mnemonic: Option<SecretString>,
+    /// Holder of the synthetic key of wallets without a mnemonic
+    signer: Option<Arc<dyn Signer>>,
+    /// Chain used instead of the one recorded in the keyring
+    chain: Option<ChainProfile>,
+    /// Public key of a watch-only wallet
+    watch_only: Option<WatchOnlyKey>,
+    /// Holder of the mnemonic of auto-locking session wallets
+    session: Option<Arc<WalletSession>>,
+    /// Account whose key tree the wallet uses; 0 is the mnemonic's standard tree
+    account: u32,
     wallet_name: String,
+    config: WalletConfig,
+    #[cfg(feature = "peer")]
+    balance_watcher: Arc<BalanceWatcher>,
+    #[cfg(feature = "peer")]
+    fork_events: broadcast::Sender<ForkEvent>,
+}
+
+impl fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wallet")
+            .field("mnemonic", &self.mnemonic.as_ref().map(|_| ".."))
+            .field("signer", &self.signer)
+            .field("chain", &self.chain)
+            .field("watch_only", &self.watch_only)
+            .field("session", &self.session)
+            .field("account", &self.account)
+            .field("wallet_name", &self.wallet_name)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Wallet {
     /// Create a new Wallet instance
-    fn new(mnemonic: Option<String>, wallet_name: String) -> Self {
+    pub(crate) fn new(mnemonic: Option<String>, wallet_name: String) -> Self {
+        Self::new_with_config(mnemonic, wallet_name, WalletConfig::default())
+    }
+
+    /// Create a new Wallet instance bound to a configuration
+    pub(crate) fn new_with_config(
+        mnemonic: Option<String>,
+        wallet_name: String,
+        config: WalletConfig,
+    ) -> Self {
         Self {
-            mnemonic,
+            mnemonic: mnemonic.map(SecretString::new),
+            signer: None,
+            chain: None,
+            watch_only: None,
+            session: None,
+            account: 0,
             wallet_name,
+            config,
+            #[cfg(feature = "peer")]
+            balance_watcher: Arc::default(),
+            #[cfg(feature = "peer")]
+            fork_events: broadcast::channel(FORK_EVENT_CAPACITY).0,
         }
     }
 
+    /// Delegate synthetic key operations to `signer`
+    pub(crate) fn with_signer(mut self, signer: Arc<dyn Signer>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Use `chain` regardless of the keyring metadata, as wallets outside the keyring need
+    pub fn with_chain(mut self, chain: ChainProfile) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    pub(crate) fn chain_override(&self) -> Option<&ChainProfile> {
+        self.chain.as_ref()
+    }
+
     /// Load a wallet by name, optionally creating one if it doesn't exist
     pub async fn load(
         wallet_name: Option<String>,
         create_on_undefined: bool,
+    ) -> Result<Self, WalletError> {
+        Self::load_with_config(wallet_name, create_on_undefined, &WalletConfig::default()).await
+    }
+
+    /// Load a wallet by name using an explicit configuration
+    pub async fn load_with_config(
+        wallet_name: Option<String>,
+        create_on_undefined: bool,
+        config: &WalletConfig,
     ) -> Result<Self, WalletError> {
         let name = wallet_name.unwrap_or_else(|| "default".to_string());
 
-        if let Some(mnemonic) = Self::get_wallet_from_keyring(&name).await? {
-            return Ok(Self::new(Some(mnemonic), name));
+        if let Some(key) = Self::get_watch_only_from_keyring(&name, config).await? {
+            let mut wallet = Self::new_with_config(None, name, config.clone());
+            wallet.watch_only = Some(key);
+            return Ok(wallet);
+        }
+
+        if let Some(mnemonic) = Self::get_wallet_from_keyring(&name, config).await? {
+            return Ok(Self::new_with_config(
+                Some(mnemonic.to_string()),
+                name,
+                config.clone(),
+            ));
         }
 
         if create_on_undefined {
             // In a real implementation, you'd prompt the user for input
             // For now, we'll generate a new wallet
-            let new_mnemonic = Self::create_new_wallet(&name).await?;
-            return Ok(Self::new(Some(new_mnemonic), name));
+            let new_mnemonic = Self::create_new_wallet_with_config(&name, config).await?;
+            return Ok(Self::new_with_config(
+                Some(new_mnemonic.into_string()),
+                name,
+                config.clone(),
+            ));
         }
 
         Err(WalletError::WalletNotFound(name))
     }
 
+    /// Load a passphrase-protected wallet
+    pub async fn unlock(wallet_name: &str, passphrase: &str) -> Result<Self, WalletError> {
+        Self::unlock_with_config(wallet_name, passphrase, &WalletConfig::default()).await
+    }
+
+    /// Load a passphrase-protected wallet using an explicit configuration
+    pub async fn unlock_with_config(
+        wallet_name: &str,
+        passphrase: &str,
+        config: &WalletConfig,
+    ) -> Result<Self, WalletError> {
+        let config = config.clone().with_keyring_passphrase(passphrase);
+        Self::load_with_config(Some(wallet_name.to_string()), false, &config).await
+    }
+
     /// Get the mnemonic seed phrase
+    ///
+    /// Only available with the `plain-mnemonic` feature; use
+    /// [`Wallet::reveal_mnemonic`] to show the words to a user. Fails with
+    /// [`WalletError::WatchOnly`] for watch-only wallets, and so does every method needing a
+    /// secret key. Session wallets fail with [`WalletError::WalletLocked`].
+    #[cfg(feature = "plain-mnemonic")]
     pub fn get_mnemonic(&self) -> Result<&str, WalletError> {
-        self.mnemonic
-            .as_deref()
-            .ok_or(WalletError::MnemonicNotLoaded)
+        self.mnemonic_phrase()
+    }
+
+    /// The mnemonic, for use inside the crate without the reveal guard
+    ///
+    /// Session wallets never lend out their mnemonic and fail with
+    /// [`WalletError::WalletLocked`]; use [`Wallet::with_mnemonic`] instead.
+    pub(crate) fn mnemonic_phrase(&self) -> Result<&str, WalletError> {
+        match &self.mnemonic {
+            Some(mnemonic) => Ok(mnemonic.as_str()),
+            None if self.watch_only.is_some() => Err(WalletError::WatchOnly),
+            None if self.session.is_some() => Err(WalletError::WalletLocked),
+            None => Err(WalletError::MnemonicNotLoaded),
+        }
+    }
+
+    /// Run `f` on the mnemonic, which session wallets only hold while unlocked
+    pub(crate) fn with_mnemonic<R>(&self, f: impl FnOnce(&str) -> R) -> Result<R, WalletError> {
+        match &self.session {
+            Some(session) => session.with_secret(|mnemonic| f(mnemonic.as_str())),
+            None => self.mnemonic_phrase().map(f),
+        }
+    }
+
+    /// Whether the wallet was stored from a public key only
+    pub fn is_watch_only(&self) -> bool {
+        self.watch_only.is_some()
+    }
+
+    /// Whether the loaded mnemonic is locked into RAM
+    ///
+    /// Always false without the `mlock` feature, or when the OS refused the lock.
+    pub fn is_mnemonic_locked(&self) -> bool {
+        match &self.session {
+            Some(session) => session
+                .with_secret(SecretString::is_locked)
+                .unwrap_or(false),
+            None => self.mnemonic.as_ref().is_some_and(SecretString::is_locked),
+        }
+    }
+
+    /// Attach the session holding this wallet's mnemonic
+    pub(crate) fn with_session(mut self, session: Arc<WalletSession>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub(crate) fn session(&self) -> Option<&Arc<WalletSession>> {
+        self.session.as_ref()
+    }
+
+    #[cfg(feature = "peer")]
+    pub(crate) fn signer(&self) -> Option<&Arc<dyn Signer>> {
+        self.signer.as_ref()
     }
 
     /// Get the wallet name
@@ -84,20 +584,75 @@ impl Wallet {
         &self.wallet_name
     }
 
-    /// Create a new wallet with a generated mnemonic
-    pub async fn create_new_wallet(wallet_name: &str) -> Result<String, WalletError> {
-        let entropy = rand::random::<[u8; 32]>(); // 32 bytes = 256 bits for 24 words
-        let mnemonic = Mnemonic::from_entropy_in(Language::English, &entropy)
-            .map_err(|_| WalletError::CryptoError("Failed to generate mnemonic".to_string()))?;
-        let mnemonic_str = mnemonic.to_string();
-        Self::save_wallet_to_keyring(wallet_name, &mnemonic_str).await?;
-        Ok(mnemonic_str)
+    /// Account whose key tree the wallet uses; 0 unless loaded with
+    /// [`Wallet::load_account`]
+    pub fn account_index(&self) -> u32 {
+        self.account
+    }
+
+    /// Switch to the key tree of `account`
+    pub(crate) fn with_account(mut self, account: u32) -> Self {
+        self.account = account;
+        self
+    }
+
+    /// Name the wallet's file caches are kept under, separate for each account
+    #[cfg(feature = "cache")]
+    pub(crate) fn cache_name(&self) -> Cow<'_, str> {
+        match self.account {
+            0 => Cow::Borrowed(&self.wallet_name),
+            account => Cow::Owned(format!("{}@{}", self.wallet_name, account)),
+        }
+    }
+
+    /// Get the configuration this wallet was loaded with
+    pub fn get_config(&self) -> &WalletConfig {
+        &self.config
+    }
+
+    /// Balance alert rules shared by every clone of this wallet
+    #[cfg(feature = "peer")]
+    pub(crate) fn balance_watcher(&self) -> &BalanceWatcher {
+        &self.balance_watcher
+    }
+
+    /// Publisher of fork events, shared by every clone of this wallet
+    #[cfg(feature = "peer")]
+    pub(crate) fn fork_event_sender(&self) -> &broadcast::Sender<ForkEvent> {
+        &self.fork_events
+    }
+
+    /// Create a new wallet with a generated 24-word mnemonic
+    pub async fn create_new_wallet(wallet_name: &str) -> Result<MnemonicHandle, WalletError> {
+        Self::create_new_wallet_with_config(wallet_name, &WalletConfig::default()).await
+    }
+
+    /// Create a new wallet with a generated mnemonic using an explicit configuration
+    pub async fn create_new_wallet_with_config(
+        wallet_name: &str,
+        config: &WalletConfig,
+    ) -> Result<MnemonicHandle, WalletError> {
+        Self::create_new_wallet_of_length_with_config(
+            wallet_name,
+            MnemonicLength::default(),
+            config,
+        )
+        .await
     }
 
     /// Import a wallet from a provided mnemonic
     pub async fn import_wallet(
         wallet_name: &str,
         seed: Option<&str>,
+    ) -> Result<String, WalletError> {
+        Self::import_wallet_with_config(wallet_name, seed, &WalletConfig::default()).await
+    }
+
+    /// Import a wallet from a provided mnemonic using an explicit configuration
+    pub async fn import_wallet_with_config(
+        wallet_name: &str,
+        seed: Option<&str>,
+        config: &WalletConfig,
     ) -> Result<String, WalletError> {
         let mnemonic_str = match seed {
             Some(s) => s.to_string(),
@@ -107,124 +662,371 @@ impl Wallet {
             }
         };
 
-        // Validate the mnemonic
-        Mnemonic::parse_in_normalized(Language::English, &mnemonic_str)
-            .map_err(|_| WalletError::InvalidMnemonic)?;
+        validate_mnemonic(&mnemonic_str)?;
 
-        Self::save_wallet_to_keyring(wallet_name, &mnemonic_str).await?;
+        Self::save_wallet_to_keyring(wallet_name, &mnemonic_str, config).await?;
         Ok(mnemonic_str)
     }
 
     /// Get the master secret key from the mnemonic
+    ///
+    /// Wallets loaded with [`Wallet::load_account`] get the root key of their account.
     pub async fn get_master_secret_key(&self) -> Result<SecretKey, WalletError> {
-        let mnemonic_str = self.get_mnemonic()?;
-        let mnemonic = Mnemonic::parse_in_normalized(Language::English, mnemonic_str)
-            .map_err(|_| WalletError::InvalidMnemonic)?;
+        let master_sk = self.with_mnemonic(master_secret_key_from_mnemonic)??;
+        Ok(match self.account {
+            0 => master_sk,
+            account => account_secret_key(&master_sk, account),
+        })
+    }
 
-        let seed = mnemonic.to_seed("");
-        let sk = SecretKey::from_seed(&seed);
-        Ok(sk)
+    /// Get the master public key
+    ///
+    /// Watch-only wallets stored from a synthetic key have none and fail with
+    /// [`WalletError::WatchOnly`].
+    pub async fn get_master_public_key(&self) -> Result<PublicKey, WalletError> {
+        if let Some(key) = &self.watch_only {
+            return key.master_public_key().ok_or(WalletError::WatchOnly);
+        }
+        if let Some(public_key) = self
+            .session()
+            .filter(|_| self.account == 0)
+            .and_then(|s| s.master_public_key())
+        {
+            return Ok(public_key);
+        }
+        Ok(self.get_master_secret_key().await?.public_key())
     }
 
     /// Get the public synthetic key
     pub async fn get_public_synthetic_key(&self) -> Result<PublicKey, WalletError> {
-        let master_sk = self.get_master_secret_key().await?;
-        let master_pk = secret_key_to_public_key(&master_sk);
-        Ok(master_public_key_to_wallet_synthetic_key(&master_pk))
+        if let Some(signer) = &self.signer {
+            return signer.synthetic_public_key().await;
+        }
+        if let Some(key) = &self.watch_only {
+            return Ok(key.synthetic_public_key());
+        }
+        Ok(synthetic_public_key(&self.get_master_public_key().await?))
+    }
+
+    /// Sign `message` with the synthetic key, returning the public key alongside
+    ///
+    /// Uses the wallet's [`Signer`] when it has one.
+    pub(crate) async fn sign_with_synthetic_key(
+        &self,
+        message: &[u8],
+    ) -> Result<(PublicKey, Signature), WalletError> {
+        if let Some(signer) = &self.signer {
+            let public_key = signer.synthetic_public_key().await?;
+            return Ok((public_key, signer.sign_message(message).await?));
+        }
+        let synthetic_sk = self.get_private_synthetic_key().await?;
+        Ok((
+            synthetic_sk.public_key(),
+            sign_message(message, &synthetic_sk),
+        ))
     }
 
     /// Get the private synthetic key
     pub async fn get_private_synthetic_key(&self) -> Result<SecretKey, WalletError> {
         let master_sk = self.get_master_secret_key().await?;
-        Ok(master_secret_key_to_wallet_synthetic_secret_key(&master_sk))
+        Ok(synthetic_secret_key(&master_sk))
+    }
+
+    /// Synthetic public key of the standard wallet key at a derivation index
+    ///
+    /// Index 0 is [`Wallet::get_public_synthetic_key`]. Wallets backed by a [`Signer`]
+    /// and watch-only wallets stored from a synthetic key only know index 0.
+    pub async fn get_synthetic_key_at(&self, index: u32) -> Result<PublicKey, WalletError> {
+        if index == 0 {
+            return self.get_public_synthetic_key().await;
+        }
+        self.require_key_tree()?;
+        Ok(synthetic_public_key_at(
+            &self.get_master_public_key().await?,
+            index,
+        ))
+    }
+
+    /// Synthetic secret key of the standard wallet key at a derivation index
+    pub async fn get_private_synthetic_key_at(&self, index: u32) -> Result<SecretKey, WalletError> {
+        self.require_key_tree()?;
+        let master_sk = self.get_master_secret_key().await?;
+        Ok(synthetic_secret_key_at(&master_sk, index))
+    }
+
+    /// Standard puzzle hash of the wallet key at a derivation index
+    pub async fn get_puzzle_hash_at(&self, index: u32) -> Result<Bytes32, WalletError> {
+        Ok(standard_puzzle_hash(
+            &self.get_synthetic_key_at(index).await?,
+        ))
+    }
+
+    /// Sign `message` with the synthetic key at a derivation index, as the Chia wallet's
+    /// `sign_message` does, returning the public key alongside
+    ///
+    /// Index 0 signs like [`Wallet::create_key_ownership_signature`], through the
+    /// wallet's [`Signer`] if it has one.
+    pub async fn sign_message_at(
+        &self,
+        index: u32,
+        message: &[u8],
+    ) -> Result<(PublicKey, Signature), WalletError> {
+        if index == 0 {
+            return self.sign_with_synthetic_key(message).await;
+        }
+        let synthetic_sk = self.get_private_synthetic_key_at(index).await?;
+        Ok((
+            synthetic_sk.public_key(),
+            sign_message(message, &synthetic_sk),
+        ))
+    }
+
+    /// Fail for wallets that only know their first synthetic key
+    pub(crate) fn require_key_tree(&self) -> Result<(), WalletError> {
+        if self.signer.is_some() {
+            return Err(WalletError::CryptoError(
+                "Signer-backed wallets only hold derivation index 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get the farmer secret key (`m/12381/8444/0/0`)
+    pub async fn get_farmer_secret_key(&self) -> Result<SecretKey, WalletError> {
+        let master_sk = self.get_master_secret_key().await?;
+        Ok(farmer_secret_key(&master_sk))
+    }
+
+    /// Get the farmer public key, as shown by `chia keys show`
+    pub async fn get_farmer_public_key(&self) -> Result<PublicKey, WalletError> {
+        Ok(self.get_farmer_secret_key().await?.public_key())
+    }
+
+    /// Get the pool secret key (`m/12381/8444/1/0`)
+    pub async fn get_pool_secret_key(&self) -> Result<SecretKey, WalletError> {
+        let master_sk = self.get_master_secret_key().await?;
+        Ok(pool_secret_key(&master_sk))
+    }
+
+    /// Get the pool public key, as shown by `chia keys show`
+    pub async fn get_pool_public_key(&self) -> Result<PublicKey, WalletError> {
+        Ok(self.get_pool_secret_key().await?.public_key())
+    }
+
+    /// Get the local secret key (`m/12381/8444/3/0`) used by harvesters and full nodes
+    pub async fn get_local_secret_key(&self) -> Result<SecretKey, WalletError> {
+        let master_sk = self.get_master_secret_key().await?;
+        Ok(local_secret_key(&master_sk))
+    }
+
+    /// Get the local public key
+    pub async fn get_local_public_key(&self) -> Result<PublicKey, WalletError> {
+        Ok(self.get_local_secret_key().await?.public_key())
     }
 
     /// Get the owner puzzle hash
     pub async fn get_owner_puzzle_hash(&self) -> Result<Bytes32, WalletError> {
-        let master_sk = self.get_master_secret_key().await?;
-        let master_pk = secret_key_to_public_key(&master_sk);
-        Ok(master_public_key_to_first_puzzle_hash(&master_pk))
+        if let Some(signer) = &self.signer {
+            return Ok(standard_puzzle_hash(&signer.synthetic_public_key().await?));
+        }
+        if let Some(key) = &self.watch_only {
+            return Ok(standard_puzzle_hash(&key.synthetic_public_key()));
+        }
+        Ok(first_puzzle_hash(&self.get_master_public_key().await?))
     }
 
-    /// Get the owner public key as an address
+    /// Get the owner public key as an address of the wallet's chain
     pub async fn get_owner_public_key(&self) -> Result<String, WalletError> {
         let owner_puzzle_hash = self.get_owner_puzzle_hash().await?;
-        // Convert puzzle hash to address (xch format) using DataLayer-Driver
-        puzzle_hash_to_address(owner_puzzle_hash, "xch")
-            .map_err(|e| WalletError::CryptoError(format!("Failed to encode address: {}", e)))
+        self.chain_profile()
+            .await?
+            .encode_address(owner_puzzle_hash)
     }
 
     /// Delete a wallet from the keyring
     pub async fn delete_wallet(wallet_name: &str) -> Result<bool, WalletError> {
-        let keyring_path = Self::get_keyring_path()?;
+        Self::delete_wallet_with_config(wallet_name, &WalletConfig::default()).await
+    }
 
-        if !keyring_path.exists() {
-            return Ok(false);
-        }
+    /// Delete a wallet from the keyring using an explicit configuration
+    pub async fn delete_wallet_with_config(
+        wallet_name: &str,
+        config: &WalletConfig,
+    ) -> Result<bool, WalletError> {
+        Self::modify_keyring(config, |keyring| {
+            let removed = keyring.wallets.remove(wallet_name).is_some();
+            Ok((removed, removed))
+        })
+        .await
+    }
 
-        let content = fs::read_to_string(&keyring_path)
-            .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+    /// List all wallets in the keyring
+    pub async fn list_wallets() -> Result<Vec<String>, WalletError> {
+        Self::list_wallets_with_config(&WalletConfig::default()).await
+    }
 
-        let mut keyring: KeyringData = serde_json::from_str(&content)
-            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+    /// List all wallets in the keyring using an explicit configuration
+    pub async fn list_wallets_with_config(
+        config: &WalletConfig,
+    ) -> Result<Vec<String>, WalletError> {
+        let (keyring, _) = Self::load_keyring(config).await?;
+        Ok(keyring.wallets.keys().cloned().collect())
+    }
 
-        if keyring.wallets.remove(wallet_name).is_some() {
-            let updated_content = serde_json::to_string_pretty(&keyring)
-                .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+    /// Re-encrypt legacy keyring entries under `passphrase`; returns how many were migrated
+    ///
+    /// Entries that are already passphrase-protected are left untouched. A keyring whose
+    /// checksum was written without the passphrase is adopted: once that checksum
+    /// verifies, the keyring is rewritten with a checksum keyed by `passphrase`.
+    pub async fn migrate_keyring(passphrase: &str) -> Result<usize, WalletError> {
+        Self::migrate_keyring_with_config(passphrase, &WalletConfig::default()).await
+    }
 
-            fs::write(&keyring_path, updated_content)
-                .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+    /// Re-encrypt legacy keyring entries under `passphrase` using an explicit configuration
+    pub async fn migrate_keyring_with_config(
+        passphrase: &str,
+        config: &WalletConfig,
+    ) -> Result<usize, WalletError> {
+        let config = config.clone().with_keyring_passphrase(passphrase);
+        let read_config = match Self::load_keyring(&config).await {
+            Err(WalletError::KeyringUnkeyed) => config.clone().without_keyring_passphrase(),
+            result => result.map(|_| config.clone())?,
+        };
 
-            Ok(true)
-        } else {
-            Ok(false)
-        }
+        Self::rekey_keyring(&read_config, &config, |keyring| {
+            let adopting = keyring.checksum_salt.is_none() && !keyring.wallets.is_empty();
+            let mut migrated = 0;
+            for encrypted_data in keyring.wallets.values_mut() {
+                if encrypted_data.is_password_protected() || encrypted_data.watch_only.is_some() {
+                    continue;
+                }
+                let mnemonic = Self::decrypt_data(encrypted_data, &config)?;
+                encrypted_data.reencrypt(&mnemonic, &config)?;
+                migrated += 1;
+            }
+            Ok((migrated, migrated > 0 || adopting))
+        })
+        .await
     }
 
-    /// List all wallets in the keyring
-    pub async fn list_wallets() -> Result<Vec<String>, WalletError> {
-        let keyring_path = Self::get_keyring_path()?;
+    /// Re-encrypt the passphrase-protected keyring entries from `old_passphrase` to
+    /// `new_passphrase`; returns how many were re-encrypted
+    ///
+    /// Every entry gets a fresh salt and the keyring is replaced in a single atomic write,
+    /// so an interrupted change leaves it entirely under the old passphrase. Nothing is
+    /// written unless every protected entry decrypts with `old_passphrase`. Afterwards
+    /// configurations still carrying the old passphrase fail with
+    /// [`WalletError::InvalidPassphrase`] and sessions opened on the keyring are locked
+    /// until unlocked with `new_passphrase`; other wallets already loaded keep their
+    /// mnemonic.
+    pub async fn change_keyring_password(
+        old_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<usize, WalletError> {
+        Self::change_keyring_password_with_config(
+            old_passphrase,
+            new_passphrase,
+            &WalletConfig::default(),
+        )
+        .await
+    }
 
-        if !keyring_path.exists() {
-            return Ok(vec![]);
-        }
+    /// Change the keyring passphrase using an explicit configuration
+    pub async fn change_keyring_password_with_config(
+        old_passphrase: &str,
+        new_passphrase: &str,
+        config: &WalletConfig,
+    ) -> Result<usize, WalletError> {
+        Self::reencrypt_keyring(old_passphrase, new_passphrase, false, config).await
+    }
 
-        let content = fs::read_to_string(&keyring_path)
-            .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+    /// Rotate the keyring password: decrypt every entry and re-encrypt it under
+    /// `new_passphrase` with a fresh salt; returns how many entries were re-encrypted
+    ///
+    /// Unlike [`Wallet::change_keyring_password`], legacy entries are protected too, so
+    /// afterwards the whole keyring needs `new_passphrase`. The keyring is replaced in a
+    /// single atomic write and nothing is written unless every entry decrypts. Watch-only
+    /// entries hold no secret and are left as they are.
+    pub async fn rotate_keyring_password(
+        old_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<usize, WalletError> {
+        Self::rotate_keyring_password_with_config(
+            old_passphrase,
+            new_passphrase,
+            &WalletConfig::default(),
+        )
+        .await
+    }
 
-        let keyring: KeyringData = serde_json::from_str(&content)
-            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+    /// Rotate the keyring password using an explicit configuration
+    pub async fn rotate_keyring_password_with_config(
+        old_passphrase: &str,
+        new_passphrase: &str,
+        config: &WalletConfig,
+    ) -> Result<usize, WalletError> {
+        Self::reencrypt_keyring(old_passphrase, new_passphrase, true, config).await
+    }
 
-        Ok(keyring.wallets.keys().cloned().collect())
+    /// Re-encrypt keyring entries from `old_passphrase` to `new_passphrase` with fresh
+    /// salts in one atomic write, then lock the sessions opened on the keyring
+    ///
+    /// Passphrase-protected entries are always re-encrypted; legacy entries only with
+    /// `include_legacy`. Watch-only entries hold no secret and are left as they are.
+    async fn reencrypt_keyring(
+        old_passphrase: &str,
+        new_passphrase: &str,
+        include_legacy: bool,
+        config: &WalletConfig,
+    ) -> Result<usize, WalletError> {
+        let old_config = config.clone().with_keyring_passphrase(old_passphrase);
+        let new_config = config.clone().with_keyring_passphrase(new_passphrase);
+
+        let reencrypted = Self::rekey_keyring(&old_config, &new_config, |keyring| {
+            let mut reencrypted = 0;
+            for encrypted_data in keyring.wallets.values_mut() {
+                if encrypted_data.watch_only.is_some()
+                    || !(include_legacy || encrypted_data.is_password_protected())
+                {
+                    continue;
+                }
+                let mnemonic = Self::decrypt_data(encrypted_data, &old_config)?;
+                encrypted_data.reencrypt(&mnemonic, &new_config)?;
+                reencrypted += 1;
+            }
+            Ok((reencrypted, reencrypted > 0))
+        })
+        .await?;
+
+        if reencrypted > 0 {
+            lock_sessions(config)?;
+        }
+        Ok(reencrypted)
     }
 
     /// Create a key ownership signature
+    ///
+    /// Signs `nonce` as given. For a replay-protected proof, sign the nonce of
+    /// [`Wallet::new_ownership_challenge`] and check it with
+    /// [`Wallet::verify_ownership_challenge`].
     pub async fn create_key_ownership_signature(&self, nonce: &str) -> Result<String, WalletError> {
-        let message = format!(
-            "Signing this message to prove ownership of key.\n\nNonce: {}",
-            nonce
-        );
-        let private_synthetic_key = self.get_private_synthetic_key().await?;
-
-        let signature = sign_message(
-            &Bytes::from(message.as_bytes().to_vec()),
-            &private_synthetic_key,
-        )
-        .map_err(|e| WalletError::CryptoError(e.to_string()))?;
+        let message = KEY_OWNERSHIP_TEMPLATE.replace("{nonce}", nonce);
+        let (_, signature) = self.sign_with_synthetic_key(message.as_bytes()).await?;
 
         Ok(hex::encode(signature.to_bytes()))
     }
 
     /// Verify a key ownership signature
+    ///
+    /// Only checks the signature: the nonce's freshness and whether it was seen before
+    /// are checked by [`Wallet::verify_ownership_challenge`] or
+    /// [`crate::OwnershipVerifier::verify_nonce`].
     pub async fn verify_key_ownership_signature(
         nonce: &str,
         signature: &str,
         public_key: &str,
     ) -> Result<bool, WalletError> {
-        let message = format!(
-            "Signing this message to prove ownership of key.\n\nNonce: {}",
-            nonce
-        );
+        let message = KEY_OWNERSHIP_TEMPLATE.replace("{nonce}", nonce);
 
         let sig_bytes =
             hex::decode(signature).map_err(|e| WalletError::CryptoError(e.to_string()))?;
@@ -256,354 +1058,441 @@ impl Wallet {
         let signature = Signature::from_bytes(&sig_array)
             .map_err(|e| WalletError::CryptoError(e.to_string()))?;
 
-        verify_signature(
-            Bytes::from(message.as_bytes().to_vec()),
-            public_key,
-            signature,
-        )
-        .map_err(|e| WalletError::CryptoError(e.to_string()))
+        Ok(verify_message(message.as_bytes(), &public_key, &signature))
     }
 
-    /// Get all unspent DIG Token coins
-    pub async fn get_all_unspent_dig_coins(
-        &self,
-        peer: &Peer,
-        omit_coins: Vec<Coin>,
-        verbose: bool,
-    ) -> Result<Vec<DigCoin>, WalletError> {
-        let owner_puzzle_hash = self.get_owner_puzzle_hash().await?;
-        let dig_ph = DigCoin::puzzle_hash(owner_puzzle_hash);
-
-        // Get unspent coin states from the DataLayer-Driver async API
-        let unspent_coin_states = datalayer_driver::async_api::get_all_unspent_coins(
-            peer,
-            dig_ph,
-            None, // previous_height - start from genesis
-            datalayer_driver::constants::get_mainnet_genesis_challenge(), // Use mainnet for now
-        )
-        .await
-        .map_err(|e| WalletError::NetworkError(format!("Failed to get unspent coins: {}", e)))?;
-
-        // Convert coin states to coins and filter out omitted coins
-        let omit_coin_ids: Vec<Bytes32> = omit_coins.iter().map(get_coin_id).collect();
-        let available_coin_states: Vec<CoinState> = unspent_coin_states
-            .coin_states
-            .into_iter()
-            .filter(|coin_state| !omit_coin_ids.contains(&get_coin_id(&coin_state.coin)))
-            .collect();
-
-        let mut proved_dig_cats: Vec<DigCoin> = vec![];
-
-        for coin_state in &available_coin_states {
-            //Parse CAT to prove lineage
-            let cat_parse_result = DigCoin::from_coin_state(peer, coin_state).await;
-            match cat_parse_result {
-                Ok(parsed_cat) => {
-                    // lineage proved. append coin in question
-                    proved_dig_cats.push(parsed_cat);
-                }
-                Err(error) => {
-                    if verbose {
-                        eprintln!(
-                            "ERROR: coin_id {} | {}",
-                            coin_state.coin.coin_id(),
-                            WalletError::CoinSetError(format!(
-                                "Failed to parse CAT and prove lineage: {}",
-                                error
-                            ))
-                        );
-                    }
-                    continue;
-                }
-            }
-        }
+    /// Convert an address to a puzzle hash
+    pub fn address_to_puzzle_hash(address: &str) -> Result<Bytes32, WalletError> {
+        decode_address(address)
+    }
 
-        Ok(proved_dig_cats)
+    /// Convert a puzzle hash to an address
+    pub fn puzzle_hash_to_address(
+        puzzle_hash: Bytes32,
+        prefix: &str,
+    ) -> Result<String, WalletError> {
+        encode_address(puzzle_hash, prefix)
     }
 
-    pub async fn select_unspent_dig_coins(
-        &self,
-        peer: &Peer,
-        coin_amount: u64,
-        omit_coins: Vec<Coin>,
-        verbose: bool,
-    ) -> Result<Vec<DigCoin>, WalletError> {
-        let available_dig_cats = self
-            .get_all_unspent_dig_coins(peer, omit_coins, verbose)
-            .await?;
-
-        let dig_coins = available_dig_cats
-            .iter()
-            .map(|dig_coin| dig_coin.cat().coin)
-            .collect::<Vec<_>>();
+    // Private helper methods
 
-        // Use the DataLayer-Driver's select_coins function
-        let selected_coins = datalayer_driver::select_coins(&dig_coins, coin_amount)
-            .map_err(|e| WalletError::DataLayerError(format!("Coin selection failed: {}", e)))?;
+    pub(crate) async fn get_wallet_from_keyring(
+        wallet_name: &str,
+        config: &WalletConfig,
+    ) -> Result<Option<Zeroizing<String>>, WalletError> {
+        let keyring = Self::load_checked_keyring(wallet_name, config).await?;
 
-        if selected_coins.is_empty() {
-            return Err(WalletError::NoUnspentCoins);
+        if let Some(encrypted_data) = keyring.wallets.get(wallet_name) {
+            if encrypted_data.watch_only.is_some() {
+                return Err(WalletError::WatchOnly);
+            }
+            if encrypted_data.is_password_protected() && config.keyring_passphrase().is_none() {
+                return Err(WalletError::KeyringLocked(wallet_name.to_string()));
+            }
+            let decrypted = Self::decrypt_data(encrypted_data, config)?;
+            Ok(Some(decrypted))
+        } else {
+            Ok(None)
         }
+    }
 
-        let selected_coins_ids: HashSet<Bytes32> = selected_coins.iter().map(get_coin_id).collect();
-        let dig_coin = available_dig_cats
-            .into_iter()
-            .filter(|dig_coin| selected_coins_ids.contains(&dig_coin.cat().coin.coin_id()))
-            .collect::<Vec<_>>();
+    async fn get_watch_only_from_keyring(
+        wallet_name: &str,
+        config: &WalletConfig,
+    ) -> Result<Option<WatchOnlyKey>, WalletError> {
+        let keyring = Self::load_checked_keyring(wallet_name, config).await?;
+        Ok(keyring
+            .wallets
+            .get(wallet_name)
+            .and_then(|entry| entry.watch_only))
+    }
 
-        Ok(dig_coin)
+    pub(crate) async fn get_threshold_key_from_keyring(
+        wallet_name: &str,
+        config: &WalletConfig,
+    ) -> Result<Option<ThresholdKey>, WalletError> {
+        let keyring = Self::load_checked_keyring(wallet_name, config).await?;
+        Ok(keyring
+            .wallets
+            .get(wallet_name)
+            .and_then(|entry| entry.threshold.clone()))
     }
 
-    pub async fn get_dig_balance(&self, peer: &Peer, verbose: bool) -> Result<u64, WalletError> {
-        let dig_cats = self
-            .get_all_unspent_dig_coins(peer, vec![], verbose)
-            .await?;
-        let dig_balance = dig_cats
-            .iter()
-            .map(|dig_coin| dig_coin.cat().coin.amount)
-            .sum::<u64>();
-        Ok(dig_balance)
+    pub(crate) async fn save_watch_only_to_keyring(
+        wallet_name: &str,
+        key: WatchOnlyKey,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        Self::save_public_entry_to_keyring(wallet_name, EncryptedData::watch_only(key), config)
+            .await
     }
 
-    pub async fn get_all_unspent_xch_coins(
-        &self,
-        peer: &Peer,
-        omit_coins: Vec<Coin>,
-    ) -> Result<Vec<Coin>, WalletError> {
-        let owner_puzzle_hash = self.get_owner_puzzle_hash().await?;
+    pub(crate) async fn save_threshold_key_to_keyring(
+        wallet_name: &str,
+        key: &ThresholdKey,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        let mut entry = EncryptedData::watch_only(key.watch_only_key());
+        entry.threshold = Some(key.clone());
+        Self::save_public_entry_to_keyring(wallet_name, entry, config).await
+    }
 
-        let coin_states = datalayer_driver::async_api::get_all_unspent_coins(
-            peer,
-            owner_puzzle_hash,
-            None, // previous_height - start from genesis
-            datalayer_driver::constants::get_mainnet_genesis_challenge(), // Use mainnet for now
-        )
+    /// Store an entry without a mnemonic, replacing only other such entries
+    async fn save_public_entry_to_keyring(
+        wallet_name: &str,
+        mut entry: EncryptedData,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        entry.metadata.created_at = Some(config.unix_timestamp());
+
+        Self::modify_keyring(config, |keyring| {
+            match keyring.wallets.get(wallet_name) {
+                Some(existing) if existing.watch_only.is_none() => {
+                    return Err(WalletError::WalletExists(wallet_name.to_string()));
+                }
+                Some(existing) => entry.metadata = existing.metadata.clone(),
+                None => {}
+            }
+            keyring
+                .wallets
+                .insert(wallet_name.to_string(), entry.clone());
+            Ok(((), true))
+        })
         .await
-        .map_err(|e| WalletError::NetworkError(format!("Failed to get unspent coins: {}", e)))?;
-
-        // Convert coin states to coins and filter out omitted coins
-        let omit_coin_ids: Vec<Bytes32> = omit_coins.iter().map(get_coin_id).collect();
-
-        Ok(coin_states
-            .coin_states
-            .into_iter()
-            .map(|cs| cs.coin)
-            .filter(|coin| !omit_coin_ids.contains(&get_coin_id(coin)))
-            .collect())
     }
 
-    /// Select unspent coins for spending
-    pub async fn select_unspent_coins(
-        &self,
-        peer: &Peer,
-        coin_amount: u64,
-        fee: u64,
-        omit_coins: Vec<Coin>,
-    ) -> Result<Vec<Coin>, WalletError> {
-        let total_needed = coin_amount + fee;
-
-        let available_coins = self.get_all_unspent_xch_coins(peer, omit_coins).await?;
-
-        // Use the DataLayer-Driver's select_coins function
-        let selected_coins = datalayer_driver::select_coins(&available_coins, total_needed)
-            .map_err(|e| WalletError::DataLayerError(format!("Coin selection failed: {}", e)))?;
-
-        if selected_coins.is_empty() {
-            return Err(WalletError::NoUnspentCoins);
-        }
-
-        Ok(selected_coins)
+    pub(crate) async fn save_wallet_to_keyring(
+        wallet_name: &str,
+        mnemonic: &str,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        let fingerprint = master_secret_key_from_mnemonic(mnemonic)?
+            .public_key()
+            .get_fingerprint();
+        let mut encrypted_data = Self::encrypt_data(mnemonic, config)?;
+        encrypted_data.metadata.created_at = Some(config.unix_timestamp());
+        encrypted_data.metadata.fingerprint = Some(fingerprint);
+
+        Self::modify_keyring(config, |keyring| {
+            if !config.allow_duplicate_mnemonics() {
+                let existing =
+                    Self::wallets_with_fingerprint(keyring, wallet_name, fingerprint, config);
+                if !existing.is_empty() {
+                    return Err(WalletError::DuplicateMnemonic {
+                        fingerprint,
+                        existing,
+                    });
+                }
+            }
+            // Replacing a wallet keeps its label, network and creation time
+            let entry = match keyring.wallets.get(wallet_name) {
+                Some(existing) => EncryptedData {
+                    metadata: WalletMetadata {
+                        fingerprint: Some(fingerprint),
+                        ..existing.metadata.clone()
+                    },
+                    ..encrypted_data.clone()
+                },
+                None => encrypted_data.clone(),
+            };
+            keyring.wallets.insert(wallet_name.to_string(), entry);
+            Ok(((), true))
+        })
+        .await
     }
 
-    pub async fn get_xch_balance(&self, peer: &Peer) -> Result<u64, WalletError> {
-        let xch_coins = self.get_all_unspent_xch_coins(peer, vec![]).await?;
-        let xch_balance = xch_coins.iter().map(|c| c.amount).sum::<u64>();
-        Ok(xch_balance)
+    /// Sorted names of the wallets other than `wallet_name` holding the key of `fingerprint`
+    ///
+    /// Entries stored before fingerprints were recorded are decrypted to compare; those
+    /// `config` cannot decrypt are skipped.
+    fn wallets_with_fingerprint(
+        keyring: &KeyringData,
+        wallet_name: &str,
+        fingerprint: u32,
+        config: &WalletConfig,
+    ) -> Vec<String> {
+        let mut names: Vec<String> = keyring
+            .wallets
+            .iter()
+            .filter(|(name, entry)| *name != wallet_name && entry.watch_only.is_none())
+            .filter(|(_, entry)| match entry.metadata.fingerprint {
+                Some(stored) => stored == fingerprint,
+                None => Self::decrypt_data(entry, config)
+                    .and_then(|mnemonic| master_secret_key_from_mnemonic(&mnemonic))
+                    .is_ok_and(|sk| sk.public_key().get_fingerprint() == fingerprint),
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
     }
 
-    /// Calculate fee for coin spends
-    pub async fn calculate_fee_for_coin_spends(
-        _peer: &Peer,
-        _coin_spends: Option<&[CoinSpend]>,
-    ) -> Result<u64, WalletError> {
-        // Simplified fee calculation - in practice this would be more complex
-        Ok(1_000_000) // 1 million mojos
+    /// Check the keyring for corruption without changing it
+    pub async fn verify_keyring() -> Result<KeyringVerification, WalletError> {
+        Self::verify_keyring_with_config(&WalletConfig::default()).await
     }
 
-    /// Check if a coin is spendable
-    pub async fn is_coin_spendable(peer: &Peer, coin_id: &Bytes32) -> Result<bool, WalletError> {
-        // Check if coin is spent using the DataLayer-Driver API
-        let is_spent = datalayer_driver::is_coin_spent(
-            peer,
-            *coin_id,
-            None,                                                         // last_height
-            datalayer_driver::constants::get_mainnet_genesis_challenge(), // Use mainnet for now
-        )
-        .await
-        .map_err(|e| WalletError::NetworkError(format!("Failed to check coin status: {}", e)))?;
+    /// Check the keyring of `config` for corruption without changing it
+    ///
+    /// Verifies the checksum and that every entry decrypts to a valid mnemonic, failing
+    /// with [`WalletError::KeyringCorrupted`] otherwise. Passphrase-protected entries,
+    /// and the checksum of a keyring holding any, are only checked when `config` holds
+    /// the keyring passphrase.
+    pub async fn verify_keyring_with_config(
+        config: &WalletConfig,
+    ) -> Result<KeyringVerification, WalletError> {
+        let Some(blob) = config.keyring_backend()?.load().await? else {
+            return Ok(KeyringVerification {
+                format_version: KEYRING_FORMAT_VERSION,
+                checksummed: false,
+                verified: Vec::new(),
+                unverified: Vec::new(),
+            });
+        };
+        let (keyring, format_version, status) = KeyringData::parse(&blob.data, config)?;
 
-        // Return true if coin is NOT spent (i.e., is spendable)
-        Ok(!is_spent)
+        let mut verification = KeyringVerification {
+            format_version,
+            checksummed: status == ChecksumStatus::Verified,
+            verified: Vec::new(),
+            unverified: Vec::new(),
+        };
+        let entries: BTreeMap<&String, &EncryptedData> = keyring.wallets.iter().collect();
+        for (name, entry) in entries {
+            if entry.watch_only.is_none() {
+                if entry.is_password_protected() && config.keyring_passphrase().is_none() {
+                    verification.unverified.push(name.clone());
+                    continue;
+                }
+                let mnemonic = Self::decrypt_data(entry, config).map_err(|e| match e {
+                    WalletError::InvalidPassphrase => e,
+                    e => WalletError::KeyringCorrupted(format!("wallet {}: {}", name, e)),
+                })?;
+                validate_mnemonic(&mnemonic).map_err(|_| {
+                    WalletError::KeyringCorrupted(format!(
+                        "wallet {} does not hold a valid mnemonic",
+                        name
+                    ))
+                })?;
+            }
+            verification.verified.push(name.clone());
+        }
+        Ok(verification)
     }
 
-    /// Connect to a random peer on the specified network
-    pub async fn connect_random_peer(
-        network: NetworkType,
-        cert_path: &str,
-        key_path: &str,
-    ) -> Result<Peer, WalletError> {
-        connect_random(network, cert_path, key_path)
-            .await
-            .map_err(|e| WalletError::NetworkError(format!("Failed to connect to peer: {}", e)))
-    }
-
-    /// Connect to a random mainnet peer using default Chia SSL paths
-    pub async fn connect_mainnet_peer() -> Result<Peer, WalletError> {
-        let home_dir = dirs::home_dir().ok_or_else(|| {
-            WalletError::FileSystemError("Could not find home directory".to_string())
-        })?;
-
-        let ssl_dir = home_dir
-            .join(".chia")
-            .join("mainnet")
-            .join("config")
-            .join("ssl")
-            .join("wallet");
-        let cert_path = ssl_dir.join("wallet_node.crt");
-        let key_path = ssl_dir.join("wallet_node.key");
-
-        Self::connect_random_peer(
-            NetworkType::Mainnet,
-            cert_path
-                .to_str()
-                .ok_or_else(|| WalletError::FileSystemError("Invalid cert path".to_string()))?,
-            key_path
-                .to_str()
-                .ok_or_else(|| WalletError::FileSystemError("Invalid key path".to_string()))?,
-        )
-        .await
+    /// Name, metadata and protection of every keyring entry, sorted by name
+    pub(crate) async fn keyring_entries(
+        config: &WalletConfig,
+    ) -> Result<Vec<WalletInfo>, WalletError> {
+        let (keyring, _) = Self::load_keyring(config).await?;
+        let mut entries: Vec<WalletInfo> = keyring
+            .wallets
+            .into_iter()
+            .map(|(name, entry)| WalletInfo {
+                password_protected: entry.is_password_protected(),
+                watch_only: entry.watch_only.is_some(),
+                metadata: entry.metadata,
+                name,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
     }
 
-    /// Connect to a random testnet peer using default Chia SSL paths
-    pub async fn connect_testnet_peer() -> Result<Peer, WalletError> {
-        let home_dir = dirs::home_dir().ok_or_else(|| {
-            WalletError::FileSystemError("Could not find home directory".to_string())
-        })?;
-
-        let ssl_dir = home_dir
-            .join(".chia")
-            .join("testnet11")
-            .join("config")
-            .join("ssl")
-            .join("wallet");
-        let cert_path = ssl_dir.join("wallet_node.crt");
-        let key_path = ssl_dir.join("wallet_node.key");
-
-        Self::connect_random_peer(
-            NetworkType::Testnet11,
-            cert_path
-                .to_str()
-                .ok_or_else(|| WalletError::FileSystemError("Invalid cert path".to_string()))?,
-            key_path
-                .to_str()
-                .ok_or_else(|| WalletError::FileSystemError("Invalid key path".to_string()))?,
-        )
+    /// Apply `f` to the metadata of a stored wallet
+    pub(crate) async fn update_metadata(
+        wallet_name: &str,
+        config: &WalletConfig,
+        f: impl Fn(&mut WalletMetadata),
+    ) -> Result<(), WalletError> {
+        Self::modify_metadata(wallet_name, config, |metadata| {
+            f(metadata);
+            Ok(())
+        })
         .await
     }
 
-    /// Convert an address to a puzzle hash
-    pub fn address_to_puzzle_hash(address: &str) -> Result<Bytes32, WalletError> {
-        address_to_puzzle_hash(address)
-            .map_err(|e| WalletError::CryptoError(format!("Failed to decode address: {}", e)))
+    /// Update a wallet's metadata, returning what `f` returns
+    ///
+    /// `f` may run more than once if the keyring changes concurrently.
+    pub(crate) async fn modify_metadata<R>(
+        wallet_name: &str,
+        config: &WalletConfig,
+        mut f: impl FnMut(&mut WalletMetadata) -> Result<R, WalletError>,
+    ) -> Result<R, WalletError> {
+        Self::modify_keyring(config, |keyring| {
+            let entry = keyring
+                .wallets
+                .get_mut(wallet_name)
+                .ok_or_else(|| WalletError::WalletNotFound(wallet_name.to_string()))?;
+            let before = entry.metadata.clone();
+            let result = f(&mut entry.metadata)?;
+            Ok((result, entry.metadata != before))
+        })
+        .await
     }
 
-    /// Convert a puzzle hash to an address
-    pub fn puzzle_hash_to_address(
-        puzzle_hash: Bytes32,
-        prefix: &str,
-    ) -> Result<String, WalletError> {
-        puzzle_hash_to_address(puzzle_hash, prefix)
-            .map_err(|e| WalletError::CryptoError(format!("Failed to encode address: {}", e)))
+    /// Read the keyring from the configured backend along with its version token
+    ///
+    /// Keyrings in an older format are upgraded in memory only; nothing is written.
+    async fn load_keyring(
+        config: &WalletConfig,
+    ) -> Result<(KeyringData, Option<String>), WalletError> {
+        let (keyring, _, _, version) = Self::load_stored_keyring(config).await?;
+        Ok((keyring, version))
     }
 
-    // Private helper methods
-
-    async fn get_wallet_from_keyring(wallet_name: &str) -> Result<Option<String>, WalletError> {
-        let keyring_path = Self::get_keyring_path()?;
-
-        if !keyring_path.exists() {
-            return Ok(None);
-        }
-
-        let content = fs::read_to_string(&keyring_path)
-            .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
-
-        let keyring: KeyringData = serde_json::from_str(&content)
-            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
-
-        if let Some(encrypted_data) = keyring.wallets.get(wallet_name) {
-            let decrypted = Self::decrypt_data(encrypted_data)?;
-            Ok(Some(decrypted))
-        } else {
-            Ok(None)
+    /// Read the keyring to load `wallet_name` from it
+    ///
+    /// Fails with [`WalletError::KeyringLocked`] when the checksum is keyed by the keyring
+    /// passphrase and `config` lacks it, since the entries could then have been swapped
+    /// by anyone able to write the file.
+    async fn load_checked_keyring(
+        wallet_name: &str,
+        config: &WalletConfig,
+    ) -> Result<KeyringData, WalletError> {
+        let (keyring, _, status, _) = Self::load_stored_keyring(config).await?;
+        if status == ChecksumStatus::NeedsPassphrase {
+            return Err(WalletError::KeyringLocked(wallet_name.to_string()));
         }
+        Ok(keyring)
     }
 
-    async fn save_wallet_to_keyring(wallet_name: &str, mnemonic: &str) -> Result<(), WalletError> {
-        let keyring_path = Self::get_keyring_path()?;
-
-        // Ensure the directory exists
-        if let Some(parent) = keyring_path.parent() {
-            fs::create_dir_all(parent).map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+    /// [`Self::load_keyring`], also returning the format version the keyring is stored in
+    /// and how far its checksum could be checked
+    async fn load_stored_keyring(
+        config: &WalletConfig,
+    ) -> Result<(KeyringData, u32, ChecksumStatus, Option<String>), WalletError> {
+        match config.keyring_backend()?.load().await? {
+            Some(blob) => {
+                let (keyring, stored_version, status) = KeyringData::parse(&blob.data, config)?;
+                Ok((keyring, stored_version, status, Some(blob.version)))
+            }
+            None => Ok((
+                KeyringData::empty(),
+                KEYRING_FORMAT_VERSION,
+                ChecksumStatus::Verified,
+                None,
+            )),
         }
+    }
 
-        let mut keyring = if keyring_path.exists() {
-            let content = fs::read_to_string(&keyring_path)
-                .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
-            serde_json::from_str(&content)
-                .map_err(|e| WalletError::SerializationError(e.to_string()))?
-        } else {
-            KeyringData {
-                wallets: HashMap::new(),
+    /// Upgrade a keyring stored in an older format, after the backend has kept a backup
+    ///
+    /// Returns the plan that was run, which is a no-op for a current keyring.
+    pub(crate) async fn upgrade_keyring(
+        config: &WalletConfig,
+    ) -> Result<MigrationPlan, WalletError> {
+        let backend = config.keyring_backend()?;
+
+        for _ in 0..KEYRING_WRITE_ATTEMPTS {
+            let Some(blob) = backend.load().await? else {
+                return KEYRING_MIGRATIONS
+                    .plan(&serde_json::json!({ "version": KEYRING_FORMAT_VERSION }));
+            };
+            let document: serde_json::Value = serde_json::from_slice(&blob.data)
+                .map_err(|e| WalletError::KeyringCorrupted(format!("not valid JSON: {}", e)))?;
+            let plan = KEYRING_MIGRATIONS.plan(&document)?;
+            if plan.is_noop() {
+                return Ok(plan);
             }
-        };
-
-        let encrypted_data = Self::encrypt_data(mnemonic)?;
-
-        keyring
-            .wallets
-            .insert(wallet_name.to_string(), encrypted_data);
 
-        let content = serde_json::to_string_pretty(&keyring)
-            .map_err(|e| WalletError::SerializationError(e.to_string()))?;
+            let (mut keyring, stored_version, _) = KeyringData::parse(&blob.data, config)?;
+            backend.backup(&blob.data, stored_version).await?;
+            let content = keyring.seal(config)?;
+            match backend.store(content, Some(&blob.version)).await {
+                Ok(_) => return Ok(plan),
+                // Changed meanwhile, possibly upgraded by another process; read it again
+                Err(WalletError::KeyringConflict(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
 
-        fs::write(&keyring_path, content)
-            .map_err(|e| WalletError::FileSystemError(e.to_string()))?;
+        Err(WalletError::KeyringConflict(format!(
+            "gave up upgrading the keyring after {} concurrent updates",
+            KEYRING_WRITE_ATTEMPTS
+        )))
+    }
 
-        Ok(())
+    /// Apply `f` to the keyring and store it, retrying when another writer got there first
+    ///
+    /// `f` returns its result and whether the keyring changed; unchanged keyrings are not written.
+    /// Keyrings in an older format are not written either: they fail with
+    /// [`WalletError::KeyringMigrationRequired`] until [`Self::apply_keyring_migration`]
+    /// has upgraded them. Keyrings holding passphrase-protected entries or keyed by the
+    /// keyring passphrase need it in `config` to be written; see [`KeyringData::seal`].
+    async fn modify_keyring<R>(
+        config: &WalletConfig,
+        f: impl FnMut(&mut KeyringData) -> Result<(R, bool), WalletError>,
+    ) -> Result<R, WalletError> {
+        Self::rekey_keyring(config, config, f).await
     }
 
-    fn get_keyring_path() -> Result<PathBuf, WalletError> {
-        // Check if we're in test mode by looking for TEST_KEYRING_PATH env var
-        if let Ok(test_path) = env::var("TEST_KEYRING_PATH") {
-            return Ok(PathBuf::from(test_path));
-        }
+    /// [`Self::modify_keyring`], reading the keyring with `read_config` and writing it
+    /// with `write_config`, for changes of the keyring passphrase
+    async fn rekey_keyring<R>(
+        read_config: &WalletConfig,
+        write_config: &WalletConfig,
+        mut f: impl FnMut(&mut KeyringData) -> Result<(R, bool), WalletError>,
+    ) -> Result<R, WalletError> {
+        let backend = read_config.keyring_backend()?;
+
+        for _ in 0..KEYRING_WRITE_ATTEMPTS {
+            let (mut keyring, stored_version, status, version) =
+                Self::load_stored_keyring(read_config).await?;
+            let (result, changed) = f(&mut keyring)?;
+            if !changed {
+                return Ok(result);
+            }
+            if status == ChecksumStatus::NeedsPassphrase {
+                return Err(keyring.locked_error());
+            }
+            if stored_version != KEYRING_FORMAT_VERSION {
+                return Err(WalletError::KeyringMigrationRequired {
+                    found: stored_version,
+                    current: KEYRING_FORMAT_VERSION,
+                });
+            }
 
-        let home_dir = dirs::home_dir().ok_or_else(|| {
-            WalletError::FileSystemError("Could not find home directory".to_string())
-        })?;
+            let content = keyring.seal(write_config)?;
+            match backend.store(content, version.as_deref()).await {
+                Ok(_) => return Ok(result),
+                Err(WalletError::KeyringConflict(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
 
-        Ok(home_dir.join(".dig").join(KEYRING_FILE))
+        Err(WalletError::KeyringConflict(format!(
+            "gave up after {} concurrent updates",
+            KEYRING_WRITE_ATTEMPTS
+        )))
     }
 
     /// Encrypt data using AES-256-GCM
-    fn encrypt_data(data: &str) -> Result<EncryptedData, WalletError> {
+    ///
+    /// With a keyring passphrase configured the key is derived from it with Argon2id;
+    /// otherwise the legacy fixed-password derivation is used.
+    fn encrypt_data(data: &str, config: &WalletConfig) -> Result<EncryptedData, WalletError> {
         // Generate a random salt
-        let salt = rand::random::<[u8; 16]>();
+        let salt = config.random_bytes::<16>()?;
+
+        if let Some(passphrase) = config.keyring_passphrase() {
+            let key_bytes = derive_password_key(passphrase, &salt)?;
+            let (ciphertext, nonce) = aes_encrypt(&key_bytes, data.as_bytes(), config)?;
+            return Ok(EncryptedData {
+                data: general_purpose::STANDARD.encode(ciphertext),
+                nonce: general_purpose::STANDARD.encode(nonce),
+                salt: general_purpose::STANDARD.encode(salt),
+                kdf: Some(KDF_ARGON2ID.to_string()),
+                metadata: WalletMetadata::default(),
+                watch_only: None,
+                threshold: None,
+            });
+        }
 
         // Derive key from a fixed password and salt using a simple method
         // In production, you'd want to use a proper key derivation function like PBKDF2
-        let mut key_bytes = [0u8; 32];
+        let mut key_bytes = Zeroizing::new([0u8; 32]);
         let password = b"mnemonic-seed"; // This should be derived from user input in practice
 
         // Simple key derivation (not cryptographically secure - use PBKDF2 in production)
@@ -611,91 +1500,124 @@ impl Wallet {
             key_bytes[i] = password[i % password.len()] ^ salt[i % salt.len()];
         }
 
-        let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
+        let key = Key::<Aes256Gcm>::from_slice(key_bytes.as_ref());
         let cipher = Aes256Gcm::new(key);
 
         // Generate a random nonce
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let nonce_bytes = config.random_bytes::<12>()?;
+        let nonce = Nonce::from_slice(&nonce_bytes);
 
         // Encrypt the data
         let ciphertext = cipher
-            .encrypt(&nonce, data.as_bytes())
+            .encrypt(nonce, data.as_bytes())
             .map_err(|e| WalletError::CryptoError(format!("Encryption failed: {}", e)))?;
 
         Ok(EncryptedData {
             data: general_purpose::STANDARD.encode(&ciphertext),
             nonce: general_purpose::STANDARD.encode(nonce),
             salt: general_purpose::STANDARD.encode(salt),
+            kdf: None,
+            metadata: WalletMetadata::default(),
+            watch_only: None,
+            threshold: None,
         })
     }
 
     /// Decrypt data using AES-256-GCM
-    fn decrypt_data(encrypted_data: &EncryptedData) -> Result<String, WalletError> {
-        let ciphertext = general_purpose::STANDARD
-            .decode(&encrypted_data.data)
-            .map_err(|e| WalletError::CryptoError(format!("Failed to decode ciphertext: {}", e)))?;
+    ///
+    /// Passphrase-protected entries need the keyring passphrase of `config`.
+    fn decrypt_data(
+        encrypted_data: &EncryptedData,
+        config: &WalletConfig,
+    ) -> Result<Zeroizing<String>, WalletError> {
+        let decode = |value: &str, what: &str| {
+            general_purpose::STANDARD.decode(value).map_err(|e| {
+                WalletError::KeyringCorrupted(format!("Failed to decode {}: {}", what, e))
+            })
+        };
+        let ciphertext = decode(&encrypted_data.data, "ciphertext")?;
+        let nonce_bytes = decode(&encrypted_data.nonce, "nonce")?;
+        let salt = decode(&encrypted_data.salt, "salt")?;
+        if nonce_bytes.len() != 12 || salt.is_empty() {
+            return Err(WalletError::KeyringCorrupted(
+                "invalid nonce or salt length".to_string(),
+            ));
+        }
 
-        let nonce_bytes = general_purpose::STANDARD
-            .decode(&encrypted_data.nonce)
-            .map_err(|e| WalletError::CryptoError(format!("Failed to decode nonce: {}", e)))?;
+        let plaintext = match encrypted_data.kdf.as_deref() {
+            // Keyed by a fixed password, so a failure can only mean a damaged entry
+            None => Self::legacy_decrypt(&salt, &nonce_bytes, &ciphertext)
+                .map_err(|e| WalletError::KeyringCorrupted(e.to_string()))?,
+            Some(KDF_ARGON2ID) => {
+                let passphrase = config.keyring_passphrase().ok_or_else(|| {
+                    WalletError::CryptoError("Keyring passphrase required".to_string())
+                })?;
+                let key_bytes = derive_password_key(passphrase, &salt)?;
+                aes_decrypt(&key_bytes, &nonce_bytes, &ciphertext)
+                    .map_err(|_| WalletError::InvalidPassphrase)?
+            }
+            Some(other) => {
+                return Err(WalletError::CryptoError(format!(
+                    "Unsupported key derivation {}",
+                    other
+                )))
+            }
+        };
 
-        let salt = general_purpose::STANDARD
-            .decode(&encrypted_data.salt)
-            .map_err(|e| WalletError::CryptoError(format!("Failed to decode salt: {}", e)))?;
+        zeroizing_utf8(plaintext)
+    }
 
+    /// Decrypt an entry written before passphrase protection, keyed by the fixed password
+    fn legacy_decrypt(
+        salt: &[u8],
+        nonce_bytes: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, WalletError> {
         // Derive the same key using the salt
-        let mut key_bytes = [0u8; 32];
+        let mut key_bytes = Zeroizing::new([0u8; 32]);
         let password = b"mnemonic-seed";
 
         for i in 0..32 {
             key_bytes[i] = password[i % password.len()] ^ salt[i % salt.len()];
         }
 
-        let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
+        let key = Key::<Aes256Gcm>::from_slice(key_bytes.as_ref());
         let cipher = Aes256Gcm::new(key);
 
-        let nonce = Nonce::from_slice(&nonce_bytes);
+        let nonce = Nonce::from_slice(nonce_bytes);
 
         // Decrypt the data
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext.as_ref())
-            .map_err(|e| WalletError::CryptoError(format!("Decryption failed: {}", e)))?;
-
-        String::from_utf8(plaintext).map_err(|e| {
-            WalletError::CryptoError(format!("Failed to convert decrypted data to string: {}", e))
-        })
+        cipher
+            .decrypt(nonce, ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|e| WalletError::CryptoError(format!("Decryption failed: {}", e)))
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "keyring-file"))]
 mod tests {
     use super::*;
-    use std::env;
+    use crate::environment::SandboxEnvironment;
+    use bip39::Language;
     use tempfile::TempDir;
 
-    // Test helper to set up a temporary directory for tests
-    fn setup_test_env() -> TempDir {
+    // Test helper giving each test its own sandboxed home directory
+    fn setup_test_env() -> (TempDir, WalletConfig) {
         let temp_dir = TempDir::new().unwrap();
-
-        // Set up isolated keyring path for this test
-        let keyring_path = temp_dir.path().join("test_keyring.json");
-        env::set_var(
-            "TEST_KEYRING_PATH",
-            keyring_path.to_string_lossy().to_string(),
-        );
-
-        // Also set HOME for any other path operations
-        env::set_var("HOME", temp_dir.path());
-
-        temp_dir
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        (temp_dir, config)
     }
 
     #[tokio::test]
     async fn test_wallet_creation() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Create a new wallet
-        let mnemonic = Wallet::create_new_wallet("test_wallet").await.unwrap();
+        let handle = Wallet::create_new_wallet_with_config("test_wallet", &config)
+            .await
+            .unwrap();
+        assert_eq!(format!("{:?}", handle), "MnemonicHandle(..)");
+        let mnemonic = handle.reveal(|words| words.to_string());
 
         // Verify mnemonic is valid BIP39
         assert!(bip39::Mnemonic::parse_in_normalized(Language::English, &mnemonic).is_ok());
@@ -704,64 +1626,68 @@ mod tests {
         assert_eq!(mnemonic.split_whitespace().count(), 24);
 
         // Verify wallet appears in list
-        let wallets = Wallet::list_wallets().await.unwrap();
+        let wallets = Wallet::list_wallets_with_config(&config).await.unwrap();
         assert!(wallets.contains(&"test_wallet".to_string()));
     }
 
     #[tokio::test]
     async fn test_wallet_import() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Known valid 24-word mnemonic
         let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
 
         // Import the wallet
-        let imported_mnemonic = Wallet::import_wallet("imported_wallet", Some(test_mnemonic))
-            .await
-            .unwrap();
+        let imported_mnemonic =
+            Wallet::import_wallet_with_config("imported_wallet", Some(test_mnemonic), &config)
+                .await
+                .unwrap();
 
         // Verify the mnemonic matches
         assert_eq!(imported_mnemonic, test_mnemonic);
 
         // Load the wallet and verify mnemonic
-        let wallet = Wallet::load(Some("imported_wallet".to_string()), false)
+        let wallet = Wallet::load_with_config(Some("imported_wallet".to_string()), false, &config)
             .await
             .unwrap();
-        assert_eq!(wallet.get_mnemonic().unwrap(), test_mnemonic);
+        assert_eq!(wallet.mnemonic_phrase().unwrap(), test_mnemonic);
     }
 
     #[tokio::test]
     async fn test_wallet_import_invalid_mnemonic() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Invalid mnemonic
         let invalid_mnemonic = "invalid mnemonic phrase that should fail validation";
 
         // Should fail with InvalidMnemonic error
-        let result = Wallet::import_wallet("invalid_wallet", Some(invalid_mnemonic)).await;
+        let result =
+            Wallet::import_wallet_with_config("invalid_wallet", Some(invalid_mnemonic), &config)
+                .await;
         assert!(matches!(result, Err(WalletError::InvalidMnemonic)));
     }
 
     #[tokio::test]
     async fn test_wallet_load_nonexistent() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Try to load non-existent wallet without creating
-        let result = Wallet::load(Some("nonexistent".to_string()), false).await;
+        let result =
+            Wallet::load_with_config(Some("nonexistent".to_string()), false, &config).await;
         assert!(matches!(result, Err(WalletError::WalletNotFound(_))));
     }
 
     #[tokio::test]
     async fn test_wallet_load_with_creation() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Load wallet with auto-creation
-        let wallet = Wallet::load(Some("auto_created".to_string()), true)
+        let wallet = Wallet::load_with_config(Some("auto_created".to_string()), true, &config)
             .await
             .unwrap();
 
         // Verify wallet was created and has valid mnemonic
-        let mnemonic = wallet.get_mnemonic().unwrap();
+        let mnemonic = wallet.mnemonic_phrase().unwrap();
         assert!(bip39::Mnemonic::parse_in_normalized(Language::English, mnemonic).is_ok());
 
         // Verify wallet name
@@ -770,15 +1696,15 @@ mod tests {
 
     #[tokio::test]
     async fn test_key_derivation() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Use known mnemonic for deterministic testing
         let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
 
-        Wallet::import_wallet("key_test", Some(test_mnemonic))
+        Wallet::import_wallet_with_config("key_test", Some(test_mnemonic), &config)
             .await
             .unwrap();
-        let wallet = Wallet::load(Some("key_test".to_string()), false)
+        let wallet = Wallet::load_with_config(Some("key_test".to_string()), false, &config)
             .await
             .unwrap();
 
@@ -789,32 +1715,68 @@ mod tests {
         let puzzle_hash = wallet.get_owner_puzzle_hash().await.unwrap();
 
         // Verify keys are consistent
-        assert_eq!(
-            secret_key_to_public_key(&private_synthetic_key),
-            public_synthetic_key
-        );
+        assert_eq!(private_synthetic_key.public_key(), public_synthetic_key);
 
         // Verify puzzle hash is 32 bytes
         assert_eq!(puzzle_hash.as_ref().len(), 32);
 
         // Test that keys are deterministic (same mnemonic = same keys)
-        let wallet2 = Wallet::load(Some("key_test".to_string()), false)
+        let wallet2 = Wallet::load_with_config(Some("key_test".to_string()), false, &config)
             .await
             .unwrap();
         let master_sk2 = wallet2.get_master_secret_key().await.unwrap();
         assert_eq!(master_sk.to_bytes(), master_sk2.to_bytes());
     }
 
+    #[tokio::test]
+    async fn test_keys_at_derivation_indices() {
+        let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let wallet = Wallet::new(Some(test_mnemonic.to_string()), "indices".to_string());
+
+        assert_eq!(
+            wallet.get_synthetic_key_at(0).await.unwrap(),
+            wallet.get_public_synthetic_key().await.unwrap()
+        );
+        assert_eq!(
+            wallet.get_puzzle_hash_at(0).await.unwrap(),
+            wallet.get_owner_puzzle_hash().await.unwrap()
+        );
+
+        let public_key = wallet.get_synthetic_key_at(7).await.unwrap();
+        assert_ne!(public_key, wallet.get_synthetic_key_at(0).await.unwrap());
+        assert_eq!(
+            wallet
+                .get_private_synthetic_key_at(7)
+                .await
+                .unwrap()
+                .public_key(),
+            public_key
+        );
+        assert_eq!(
+            wallet.get_puzzle_hash_at(7).await.unwrap(),
+            standard_puzzle_hash(&public_key)
+        );
+
+        let (signer, signature) = wallet.sign_message_at(7, b"hello").await.unwrap();
+        assert_eq!(signer, public_key);
+        assert!(verify_message(b"hello", &public_key, &signature));
+        assert!(!verify_message(
+            b"hello",
+            &wallet.get_synthetic_key_at(0).await.unwrap(),
+            &signature
+        ));
+    }
+
     #[tokio::test]
     async fn test_address_generation() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
 
-        Wallet::import_wallet("address_test", Some(test_mnemonic))
+        Wallet::import_wallet_with_config("address_test", Some(test_mnemonic), &config)
             .await
             .unwrap();
-        let wallet = Wallet::load(Some("address_test".to_string()), false)
+        let wallet = Wallet::load_with_config(Some("address_test".to_string()), false, &config)
             .await
             .unwrap();
 
@@ -835,14 +1797,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_signature_creation_and_verification() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
 
-        Wallet::import_wallet("sig_test", Some(test_mnemonic))
+        Wallet::import_wallet_with_config("sig_test", Some(test_mnemonic), &config)
             .await
             .unwrap();
-        let wallet = Wallet::load(Some("sig_test".to_string()), false)
+        let wallet = Wallet::load_with_config(Some("sig_test".to_string()), false, &config)
             .await
             .unwrap();
 
@@ -873,39 +1835,51 @@ mod tests {
 
     #[tokio::test]
     async fn test_wallet_deletion() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Create wallet
-        Wallet::create_new_wallet("delete_test").await.unwrap();
+        Wallet::create_new_wallet_with_config("delete_test", &config)
+            .await
+            .unwrap();
 
         // Verify it exists
-        let wallets_before = Wallet::list_wallets().await.unwrap();
+        let wallets_before = Wallet::list_wallets_with_config(&config).await.unwrap();
         assert!(wallets_before.contains(&"delete_test".to_string()));
 
         // Delete wallet
-        let deleted = Wallet::delete_wallet("delete_test").await.unwrap();
+        let deleted = Wallet::delete_wallet_with_config("delete_test", &config)
+            .await
+            .unwrap();
         assert!(deleted);
 
         // Verify it's gone
-        let wallets_after = Wallet::list_wallets().await.unwrap();
+        let wallets_after = Wallet::list_wallets_with_config(&config).await.unwrap();
         assert!(!wallets_after.contains(&"delete_test".to_string()));
 
         // Try to delete non-existent wallet
-        let not_deleted = Wallet::delete_wallet("nonexistent").await.unwrap();
+        let not_deleted = Wallet::delete_wallet_with_config("nonexistent", &config)
+            .await
+            .unwrap();
         assert!(!not_deleted);
     }
 
     #[tokio::test]
     async fn test_multiple_wallets() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Create multiple wallets
-        Wallet::create_new_wallet("wallet1").await.unwrap();
-        Wallet::create_new_wallet("wallet2").await.unwrap();
-        Wallet::create_new_wallet("wallet3").await.unwrap();
+        Wallet::create_new_wallet_with_config("wallet1", &config)
+            .await
+            .unwrap();
+        Wallet::create_new_wallet_with_config("wallet2", &config)
+            .await
+            .unwrap();
+        Wallet::create_new_wallet_with_config("wallet3", &config)
+            .await
+            .unwrap();
 
         // List wallets
-        let mut wallets = Wallet::list_wallets().await.unwrap();
+        let mut wallets = Wallet::list_wallets_with_config(&config).await.unwrap();
         wallets.sort(); // Sort for consistent testing
 
         assert_eq!(wallets.len(), 3);
@@ -914,19 +1888,19 @@ mod tests {
         assert!(wallets.contains(&"wallet3".to_string()));
 
         // Load each wallet and verify they have different mnemonics
-        let w1 = Wallet::load(Some("wallet1".to_string()), false)
+        let w1 = Wallet::load_with_config(Some("wallet1".to_string()), false, &config)
             .await
             .unwrap();
-        let w2 = Wallet::load(Some("wallet2".to_string()), false)
+        let w2 = Wallet::load_with_config(Some("wallet2".to_string()), false, &config)
             .await
             .unwrap();
-        let w3 = Wallet::load(Some("wallet3".to_string()), false)
+        let w3 = Wallet::load_with_config(Some("wallet3".to_string()), false, &config)
             .await
             .unwrap();
 
-        assert_ne!(w1.get_mnemonic().unwrap(), w2.get_mnemonic().unwrap());
-        assert_ne!(w2.get_mnemonic().unwrap(), w3.get_mnemonic().unwrap());
-        assert_ne!(w1.get_mnemonic().unwrap(), w3.get_mnemonic().unwrap());
+        assert_ne!(w1.mnemonic_phrase().unwrap(), w2.mnemonic_phrase().unwrap());
+        assert_ne!(w2.mnemonic_phrase().unwrap(), w3.mnemonic_phrase().unwrap());
+        assert_ne!(w1.mnemonic_phrase().unwrap(), w3.mnemonic_phrase().unwrap());
     }
 
     #[tokio::test]
@@ -934,7 +1908,7 @@ mod tests {
         // Test encryption/decryption directly
         let test_data = "test mnemonic phrase for encryption";
 
-        let encrypted = Wallet::encrypt_data(test_data).unwrap();
+        let encrypted = Wallet::encrypt_data(test_data, &WalletConfig::default()).unwrap();
 
         // Verify encrypted data is different from original
         assert_ne!(encrypted.data, test_data);
@@ -942,8 +1916,8 @@ mod tests {
         assert!(!encrypted.salt.is_empty());
 
         // Decrypt and verify
-        let decrypted = Wallet::decrypt_data(&encrypted).unwrap();
-        assert_eq!(decrypted, test_data);
+        let decrypted = Wallet::decrypt_data(&encrypted, &WalletConfig::default()).unwrap();
+        assert_eq!(decrypted.as_str(), test_data);
     }
 
     #[tokio::test]
@@ -951,8 +1925,8 @@ mod tests {
         let test_data = "same data";
 
         // Encrypt same data twice
-        let encrypted1 = Wallet::encrypt_data(test_data).unwrap();
-        let encrypted2 = Wallet::encrypt_data(test_data).unwrap();
+        let encrypted1 = Wallet::encrypt_data(test_data, &WalletConfig::default()).unwrap();
+        let encrypted2 = Wallet::encrypt_data(test_data, &WalletConfig::default()).unwrap();
 
         // Should produce different ciphertexts due to random salt/nonce
         assert_ne!(encrypted1.data, encrypted2.data);
@@ -960,22 +1934,22 @@ mod tests {
         assert_ne!(encrypted1.nonce, encrypted2.nonce);
 
         // But both should decrypt to same data
-        let decrypted1 = Wallet::decrypt_data(&encrypted1).unwrap();
-        let decrypted2 = Wallet::decrypt_data(&encrypted2).unwrap();
-        assert_eq!(decrypted1, test_data);
-        assert_eq!(decrypted2, test_data);
+        let decrypted1 = Wallet::decrypt_data(&encrypted1, &WalletConfig::default()).unwrap();
+        let decrypted2 = Wallet::decrypt_data(&encrypted2, &WalletConfig::default()).unwrap();
+        assert_eq!(decrypted1.as_str(), test_data);
+        assert_eq!(decrypted2.as_str(), test_data);
     }
 
     #[tokio::test]
     async fn test_invalid_signature_verification() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Create wallet
         let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
-        Wallet::import_wallet("invalid_sig_test", Some(test_mnemonic))
+        Wallet::import_wallet_with_config("invalid_sig_test", Some(test_mnemonic), &config)
             .await
             .unwrap();
-        let wallet = Wallet::load(Some("invalid_sig_test".to_string()), false)
+        let wallet = Wallet::load_with_config(Some("invalid_sig_test".to_string()), false, &config)
             .await
             .unwrap();
 
@@ -1016,7 +1990,7 @@ mod tests {
         let wallet = Wallet::new(None, "empty_wallet".to_string());
 
         // Should fail when trying to get mnemonic
-        let result = wallet.get_mnemonic();
+        let result = wallet.mnemonic_phrase();
         assert!(matches!(result, Err(WalletError::MnemonicNotLoaded)));
 
         // Should fail when trying to derive keys
@@ -1024,16 +1998,456 @@ mod tests {
         assert!(matches!(result, Err(WalletError::MnemonicNotLoaded)));
     }
 
+    #[test]
+    fn test_debug_output_redacts_mnemonic() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        let wallet = Wallet::new(Some(mnemonic.to_string()), "redacted".to_string());
+
+        let debug = format!("{:?}", wallet);
+        assert!(debug.contains("redacted"));
+        assert!(!debug.contains("abandon"));
+        assert_eq!(wallet.mnemonic_phrase().unwrap(), mnemonic);
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_mnemonic_stored_under_another_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let mnemonic = Wallet::create_new_wallet_with_config("main", &config)
+            .await
+            .unwrap()
+            .into_string();
+
+        // Entries stored before fingerprints were recorded are compared too
+        Wallet::update_metadata("main", &config, |metadata| metadata.fingerprint = None)
+            .await
+            .unwrap();
+        match Wallet::import_wallet_with_config("copy", Some(&mnemonic), &config).await {
+            Err(WalletError::DuplicateMnemonic { existing, .. }) => {
+                assert_eq!(existing, vec!["main".to_string()])
+            }
+            other => panic!("expected a duplicate mnemonic error, got {:?}", other),
+        }
+        Wallet::import_wallet_with_config("main", Some(&mnemonic), &config)
+            .await
+            .unwrap();
+
+        let config = config.with_allow_duplicate_mnemonics(true);
+        Wallet::import_wallet_with_config("fork", Some(&mnemonic), &config)
+            .await
+            .unwrap();
+        let infos = Wallet::list_wallet_info_with_config(&config).await.unwrap();
+        assert_eq!(infos[0].metadata.fingerprint, infos[1].metadata.fingerprint);
+        assert!(infos[0].metadata.fingerprint.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_tampered_or_truncated_keyring_is_reported_as_corrupted() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("alpha", &config)
+            .await
+            .unwrap();
+        let verification = Wallet::verify_keyring_with_config(&config).await.unwrap();
+        assert!(verification.checksummed);
+        assert_eq!(verification.verified, vec!["alpha".to_string()]);
+
+        let keyring_path = config.keyring_path().unwrap();
+        let original = std::fs::read(&keyring_path).unwrap();
+        let mut keyring: serde_json::Value = serde_json::from_slice(&original).unwrap();
+        keyring["wallets"]["alpha"]["metadata"]["label"] = "edited".into();
+        std::fs::write(&keyring_path, keyring.to_string()).unwrap();
+        assert!(matches!(
+            Wallet::list_wallets_with_config(&config).await,
+            Err(WalletError::KeyringCorrupted(_))
+        ));
+
+        std::fs::write(&keyring_path, &original[..original.len() / 2]).unwrap();
+        assert!(matches!(
+            Wallet::verify_keyring_with_config(&config).await,
+            Err(WalletError::KeyringCorrupted(_))
+        ));
+
+        // A damaged entry under a valid checksum is caught by decrypting it
+        std::fs::write(&keyring_path, &original).unwrap();
+        let mut keyring: KeyringData = serde_json::from_slice(&original).unwrap();
+        keyring.wallets.get_mut("alpha").unwrap().data =
+            general_purpose::STANDARD.encode([0u8; 48]);
+        std::fs::write(&keyring_path, keyring.seal(&config).unwrap()).unwrap();
+        assert!(Wallet::list_wallets_with_config(&config).await.is_ok());
+        assert!(matches!(
+            Wallet::verify_keyring_with_config(&config).await,
+            Err(WalletError::KeyringCorrupted(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_protected_keyring_checksum_is_keyed_by_the_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let protected = config.clone().with_keyring_passphrase("secret");
+        Wallet::create_new_wallet_with_config("alpha", &protected)
+            .await
+            .unwrap();
+        assert!(
+            Wallet::verify_keyring_with_config(&protected)
+                .await
+                .unwrap()
+                .checksummed
+        );
+        assert!(
+            !Wallet::verify_keyring_with_config(&config)
+                .await
+                .unwrap()
+                .checksummed
+        );
+        assert!(matches!(
+            Wallet::create_new_wallet_with_config("beta", &config).await,
+            Err(WalletError::KeyringLocked(name)) if name == "alpha"
+        ));
+
+        // Resealing an edit without the passphrase is caught by anyone holding it
+        let keyring_path = config.keyring_path().unwrap();
+        let original = std::fs::read(&keyring_path).unwrap();
+        let mut keyring: KeyringData = serde_json::from_slice(&original).unwrap();
+        keyring.wallets.get_mut("alpha").unwrap().metadata.label = Some("edited".to_string());
+        let content = keyring.checksum_content(keyring.version).unwrap();
+        keyring.checksum = Some(hex::encode(
+            hmac_sha256(KEYRING_UNKEYED_HMAC_KEY, &content).unwrap(),
+        ));
+        std::fs::write(&keyring_path, serde_json::to_vec(&keyring).unwrap()).unwrap();
+        assert!(matches!(
+            Wallet::list_wallets_with_config(&protected).await,
+            Err(WalletError::KeyringCorrupted(_))
+        ));
+
+        // So is dropping the salt to pass the keyring off as one written without it
+        keyring.checksum_salt = None;
+        keyring.wallets.get_mut("alpha").unwrap().kdf = None;
+        let content = keyring.checksum_content(keyring.version).unwrap();
+        keyring.checksum = Some(hex::encode(
+            hmac_sha256(KEYRING_UNKEYED_HMAC_KEY, &content).unwrap(),
+        ));
+        std::fs::write(&keyring_path, serde_json::to_vec(&keyring).unwrap()).unwrap();
+        assert!(matches!(
+            Wallet::list_wallets_with_config(&protected).await,
+            Err(WalletError::KeyringUnkeyed)
+        ));
+
+        std::fs::write(&keyring_path, &original).unwrap();
+        assert!(matches!(
+            Wallet::get_wallet_from_keyring("alpha", &config).await,
+            Err(WalletError::KeyringLocked(name)) if name == "alpha"
+        ));
+        assert!(matches!(
+            Wallet::list_wallets_with_config(&config.clone().with_keyring_passphrase("wrong"))
+                .await,
+            Err(WalletError::InvalidPassphrase)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_migration_adopts_a_keyring_written_without_the_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let protected = config.clone().with_keyring_passphrase("secret");
+        Wallet::create_new_wallet_with_config("plain", &config)
+            .await
+            .unwrap();
+        assert!(matches!(
+            Wallet::create_new_wallet_with_config("alpha", &protected).await,
+            Err(WalletError::KeyringUnkeyed)
+        ));
+
+        assert_eq!(
+            Wallet::migrate_keyring_with_config("secret", &config)
+                .await
+                .unwrap(),
+            1
+        );
+        Wallet::create_new_wallet_with_config("alpha", &protected)
+            .await
+            .unwrap();
+        assert!(
+            Wallet::verify_keyring_with_config(&protected)
+                .await
+                .unwrap()
+                .checksummed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_keyring_is_only_upgraded_by_migration() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("old", &config)
+            .await
+            .unwrap();
+
+        let keyring_path = config.keyring_path().unwrap();
+        let mut keyring: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&keyring_path).unwrap()).unwrap();
+        keyring.as_object_mut().unwrap().remove("version");
+        std::fs::write(&keyring_path, keyring.to_string()).unwrap();
+
+        let plan = Wallet::plan_keyring_migration_with_config(&config)
+            .await
+            .unwrap();
+        assert_eq!(
+            (plan.from_version, plan.steps.len()),
+            (0, KEYRING_FORMAT_VERSION as usize)
+        );
+        // Reading leaves the old keyring as it is; writing waits for the migration
+        assert_eq!(
+            Wallet::list_wallets_with_config(&config).await.unwrap(),
+            vec!["old".to_string()]
+        );
+        let unchanged: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&keyring_path).unwrap()).unwrap();
+        assert_eq!(unchanged, keyring);
+        assert!(!keyring_path.with_extension("json.v0.bak").exists());
+        assert!(matches!(
+            Wallet::create_new_wallet_with_config("new", &config).await,
+            Err(WalletError::KeyringMigrationRequired { found: 0, .. })
+        ));
+
+        let applied = Wallet::apply_keyring_migration_with_config(&config)
+            .await
+            .unwrap();
+        assert_eq!(applied, plan);
+        let upgraded: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&keyring_path).unwrap()).unwrap();
+        assert_eq!(upgraded["version"], KEYRING_FORMAT_VERSION);
+        assert!(keyring_path.with_extension("json.v0.bak").exists());
+        assert!(Wallet::apply_keyring_migration_with_config(&config)
+            .await
+            .unwrap()
+            .is_noop());
+        Wallet::create_new_wallet_with_config("new", &config)
+            .await
+            .unwrap();
+
+        Wallet::rollback_keyring_with_config(0, &config)
+            .await
+            .unwrap();
+        let restored: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&keyring_path).unwrap()).unwrap();
+        assert_eq!(restored, keyring);
+
+        keyring["version"] = 99.into();
+        std::fs::write(&keyring_path, keyring.to_string()).unwrap();
+        assert!(matches!(
+            Wallet::list_wallets_with_config(&config).await,
+            Err(WalletError::UnsupportedKeyringVersion { found: 99, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sandboxed_configs_are_isolated() {
+        let dir_a = TempDir::new().unwrap();
+        let dir_b = TempDir::new().unwrap();
+        let config_a = WalletConfig::new().with_environment(SandboxEnvironment::new(dir_a.path()));
+        let config_b = WalletConfig::new().with_environment(SandboxEnvironment::new(dir_b.path()));
+
+        Wallet::create_new_wallet_with_config("sandboxed", &config_a)
+            .await
+            .unwrap();
+
+        assert!(dir_a.path().join(".dig").join("keyring.json").exists());
+        assert_eq!(
+            Wallet::list_wallets_with_config(&config_a).await.unwrap(),
+            vec!["sandboxed".to_string()]
+        );
+        assert!(Wallet::list_wallets_with_config(&config_b)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let wallet = Wallet::load_with_config(Some("sandboxed".to_string()), false, &config_a)
+            .await
+            .unwrap();
+        assert!(wallet
+            .get_config()
+            .keyring_path()
+            .unwrap()
+            .starts_with(dir_a.path()));
+
+        let result =
+            Wallet::load_with_config(Some("sandboxed".to_string()), false, &config_b).await;
+        assert!(matches!(result, Err(WalletError::WalletNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_keyring_passphrase_migration_and_unlock() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let mnemonic = Wallet::create_new_wallet_with_config("legacy", &config)
+            .await
+            .unwrap()
+            .into_string();
+
+        assert_eq!(
+            Wallet::migrate_keyring_with_config("correct horse", &config)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            Wallet::migrate_keyring_with_config("correct horse", &config)
+                .await
+                .unwrap(),
+            0
+        );
+
+        let locked = Wallet::load_with_config(Some("legacy".to_string()), false, &config).await;
+        assert!(matches!(locked, Err(WalletError::KeyringLocked(name)) if name == "legacy"));
+        let wrong = Wallet::unlock_with_config("legacy", "battery staple", &config).await;
+        assert!(matches!(wrong, Err(WalletError::InvalidPassphrase)));
+
+        let wallet = Wallet::unlock_with_config("legacy", "correct horse", &config)
+            .await
+            .unwrap();
+        assert_eq!(wallet.mnemonic_phrase().unwrap(), mnemonic);
+    }
+
+    #[tokio::test]
+    async fn test_change_keyring_password_is_all_or_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let first = config.clone().with_keyring_passphrase("first");
+        let mnemonic = Wallet::create_new_wallet_with_config("a", &first)
+            .await
+            .unwrap()
+            .into_string();
+
+        // A second entry under another passphrase makes the change fail midway
+        let other_dir = TempDir::new().unwrap();
+        let other = WalletConfig::new()
+            .with_environment(SandboxEnvironment::new(other_dir.path()))
+            .with_keyring_passphrase("other");
+        Wallet::create_new_wallet_with_config("b", &other)
+            .await
+            .unwrap();
+        let (other_keyring, _) = Wallet::load_keyring(&other).await.unwrap();
+        let (mut keyring, _) = Wallet::load_keyring(&first).await.unwrap();
+        keyring
+            .wallets
+            .insert("b".to_string(), other_keyring.wallets["b"].clone());
+        std::fs::write(
+            config.keyring_path().unwrap(),
+            keyring.seal(&first).unwrap(),
+        )
+        .unwrap();
+        // A temporary file left behind by a crashed writer must not get in the way
+        let mut temp_path = config.keyring_path().unwrap().into_os_string();
+        temp_path.push(".tmp");
+        std::fs::write(&temp_path, b"{").unwrap();
+
+        let failed = Wallet::change_keyring_password_with_config("first", "second", &config).await;
+        assert!(matches!(failed, Err(WalletError::InvalidPassphrase)));
+        assert!(Wallet::unlock_with_config("a", "first", &config)
+            .await
+            .is_ok());
+
+        Wallet::delete_wallet_with_config("b", &first)
+            .await
+            .unwrap();
+        assert_eq!(
+            Wallet::change_keyring_password_with_config("first", "second", &config)
+                .await
+                .unwrap(),
+            1
+        );
+        let stale = Wallet::unlock_with_config("a", "first", &config).await;
+        assert!(matches!(stale, Err(WalletError::InvalidPassphrase)));
+        let wallet = Wallet::unlock_with_config("a", "second", &config)
+            .await
+            .unwrap();
+        assert_eq!(wallet.mnemonic_phrase().unwrap(), mnemonic);
+    }
+
+    #[tokio::test]
+    async fn test_change_keyring_password_locks_open_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config(
+            "a",
+            &config.clone().with_keyring_passphrase("first"),
+        )
+        .await
+        .unwrap();
+        let session = Wallet::open_session_with_config("a", Some("first"), &config)
+            .await
+            .unwrap();
+
+        Wallet::change_keyring_password_with_config("first", "second", &config)
+            .await
+            .unwrap();
+        assert!(session.is_locked());
+        assert!(matches!(
+            session.unlock_session(Some("first")).await,
+            Err(WalletError::InvalidPassphrase)
+        ));
+        session.unlock_session(Some("second")).await.unwrap();
+        assert!(!session.is_locked());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_keyring_password_protects_every_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let first = config.clone().with_keyring_passphrase("first");
+        Wallet::create_new_wallet_with_config("protected", &first)
+            .await
+            .unwrap();
+        // A legacy entry left next to protected ones by an earlier release
+        let legacy = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+        Wallet::modify_keyring(&first, |keyring| {
+            let entry = Wallet::encrypt_data(legacy, &config)?;
+            keyring.wallets.insert("legacy".to_string(), entry);
+            Ok(((), true))
+        })
+        .await
+        .unwrap();
+
+        let failed = Wallet::rotate_keyring_password_with_config("wrong", "second", &config).await;
+        assert!(matches!(failed, Err(WalletError::InvalidPassphrase)));
+        assert!(
+            Wallet::load_with_config(Some("legacy".to_string()), false, &first)
+                .await
+                .is_ok()
+        );
+
+        assert_eq!(
+            Wallet::rotate_keyring_password_with_config("first", "second", &config)
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(
+            Wallet::load_with_config(Some("legacy".to_string()), false, &config)
+                .await
+                .is_err()
+        );
+        let wallet = Wallet::unlock_with_config("legacy", "second", &config)
+            .await
+            .unwrap();
+        assert_eq!(wallet.mnemonic_phrase().unwrap(), legacy);
+        assert!(Wallet::unlock_with_config("protected", "second", &config)
+            .await
+            .is_ok());
+    }
+
     #[tokio::test]
     async fn test_default_wallet_name() {
-        let _temp_dir = setup_test_env();
+        let (_temp_dir, config) = setup_test_env();
 
         // Load wallet without specifying name (should use "default")
-        let wallet = Wallet::load(None, true).await.unwrap();
+        let wallet = Wallet::load_with_config(None, true, &config).await.unwrap();
         assert_eq!(wallet.get_wallet_name(), "default");
 
         // Verify it appears in wallet list
-        let wallets = Wallet::list_wallets().await.unwrap();
+        let wallets = Wallet::list_wallets_with_config(&config).await.unwrap();
         assert!(wallets.contains(&"default".to_string()));
     }
 }
@@ -0,0 +1,466 @@
+use crate::asset::Asset;
+use crate::cancel::CancellationToken;
+use crate::checkpoint::SyncCheckpoint;
+use crate::coin_proof::SerializedCoin;
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::file_cache::FileCache;
+use crate::runtime::TaskSet;
+use crate::wallet::Wallet;
+use datalayer_driver::{Bytes32, Peer};
+#[cfg(feature = "cat")]
+use datalayer_driver::{CoinState, DigCoin};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Default number of concurrent peer requests while warming caches
+pub const DEFAULT_WARMUP_PARALLELISM: usize = 8;
+/// Confirmation targets, in seconds, for which fee estimates are cached
+pub const FEE_ESTIMATE_TARGETS: [u64; 3] = [60, 300, 600];
+
+const PEAK_KEY: &str = "latest";
+
+/// Chain peak observed while syncing the wallet's coin set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedPeak {
+    pub height: u32,
+    pub header_hash: String,
+    pub fetched_at: u64,
+}
+
+/// Unspent coins of one asset at the time of caching
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedCoinSet {
+    pub asset: Asset,
+    pub coins: Vec<SerializedCoin>,
+    pub fetched_at: u64,
+}
+
+/// Lineage proof of a DIG coin, as hex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedLineage {
+    pub coin_id: String,
+    pub parent_parent_coin_info: String,
+    pub parent_inner_puzzle_hash: String,
+    pub parent_amount: u64,
+}
+
+/// Fee estimate for a confirmation target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedFeeEstimate {
+    pub target_seconds: u64,
+    pub fee: u64,
+    pub fetched_at: u64,
+}
+
+/// Outcome of [`Wallet::warm_caches`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarmupReport {
+    pub duration: Duration,
+    /// Number of cache entries written
+    pub entries_refreshed: usize,
+    /// Human-readable description of every fetch that failed
+    pub failures: Vec<String>,
+}
+
+/// Startup caches of one wallet under the configured `.dig` directory
+pub struct WarmCache {
+    peaks: FileCache<CachedPeak>,
+    coin_sets: FileCache<CachedCoinSet>,
+    lineages: FileCache<CachedLineage>,
+    fees: FileCache<CachedFeeEstimate>,
+}
+
+impl WarmCache {
+    /// Open the warm caches of a wallet
+    pub fn open(wallet_name: &str, config: &WalletConfig) -> Result<Self, WalletError> {
+        let base = format!("warm/{}", wallet_name);
+        Ok(Self {
+            peaks: FileCache::from_config(&format!("{}/peak", base), config)?,
+            coin_sets: FileCache::from_config(&format!("{}/coin_sets", base), config)?,
+            lineages: FileCache::from_config(&format!("{}/lineage", base), config)?,
+            fees: FileCache::from_config(&format!("{}/fees", base), config)?,
+        })
+    }
+
+    /// The last cached peak
+    pub fn peak(&self) -> Result<Option<CachedPeak>, WalletError> {
+        self.peaks.get(PEAK_KEY)
+    }
+
+    /// The last cached unspent coin set of an asset
+    pub fn coin_set(&self, asset: Asset) -> Result<Option<CachedCoinSet>, WalletError> {
+        self.coin_sets.get(&asset.to_string())
+    }
+
+    /// The cached lineage proof of a DIG coin
+    pub fn lineage(&self, coin_id: Bytes32) -> Result<Option<CachedLineage>, WalletError> {
+        self.lineages.get(&hex::encode(coin_id))
+    }
+
+    /// The cached fee estimate for a confirmation target
+    pub fn fee_estimate(
+        &self,
+        target_seconds: u64,
+    ) -> Result<Option<CachedFeeEstimate>, WalletError> {
+        self.fees.get(&target_seconds.to_string())
+    }
+
+    /// Drop the cached peak, coin sets and lineage proofs; fee estimates stay valid
+    pub async fn invalidate(&self) -> Result<(), WalletError> {
+        self.peaks.clear().await?;
+        self.coin_sets.clear().await?;
+        self.lineages.clear().await
+    }
+}
+
+/// Entries written and failures observed by one warming step
+#[derive(Default)]
+struct StepOutcome {
+    refreshed: usize,
+    failures: Vec<String>,
+}
+
+impl StepOutcome {
+    fn fail(&mut self, what: &str, error: impl std::fmt::Display) {
+        self.failures.push(format!("{}: {}", what, error));
+    }
+
+    fn merge(&mut self, other: StepOutcome) {
+        self.refreshed += other.refreshed;
+        self.failures.extend(other.failures);
+    }
+}
+
+impl Wallet {
+    /// Open the startup caches of this wallet
+    pub fn warm_cache(&self) -> Result<WarmCache, WalletError> {
+        WarmCache::open(&self.cache_name(), self.get_config())
+    }
+
+    /// Pre-fetch peak height, coin sets, DIG lineage proofs and fee estimates
+    pub async fn warm_caches(&self, peer: &Peer) -> Result<WarmupReport, WalletError> {
+        self.warm_caches_with_parallelism(peer, DEFAULT_WARMUP_PARALLELISM)
+            .await
+    }
+
+    /// Warm caches with at most `parallelism` concurrent peer requests per step
+    ///
+    /// Individual fetch failures are collected in the report instead of aborting
+    /// the warmup; only local cache or key errors are returned as `Err`.
+    pub async fn warm_caches_with_parallelism(
+        &self,
+        peer: &Peer,
+        parallelism: usize,
+    ) -> Result<WarmupReport, WalletError> {
+        self.warm_caches_with_cancellation(peer, parallelism, &CancellationToken::new())
+            .await
+    }
+
+    /// Warm caches until done or until `cancellation` fires
+    ///
+    /// A cancelled step writes nothing, so every cache entry stays a complete snapshot.
+    /// Sync checkpoints are verified first, so caches built on a reorged branch are dropped.
+    pub async fn warm_caches_with_cancellation(
+        &self,
+        peer: &Peer,
+        parallelism: usize,
+        cancellation: &CancellationToken,
+    ) -> Result<WarmupReport, WalletError> {
+        let started = Instant::now();
+        let cache = self.warm_cache()?;
+        let owner_puzzle_hash = self.get_owner_puzzle_hash().await?;
+        let parallelism = parallelism.max(1);
+
+        let mut outcome = StepOutcome::default();
+        match cancellation.run(self.resume_sync(peer)).await {
+            Ok(_) => {}
+            Err(WalletError::Cancelled) => return Err(WalletError::Cancelled),
+            Err(e) => outcome.fail("sync checkpoints", e),
+        }
+
+        #[cfg(feature = "cat")]
+        let dig = self.warm_dig(peer, &cache, owner_puzzle_hash, parallelism, cancellation);
+        #[cfg(not(feature = "cat"))]
+        let dig = async { Ok::<_, WalletError>(StepOutcome::default()) };
+
+        let (xch, dig, fees) = tokio::join!(
+            self.warm_xch(peer, &cache, owner_puzzle_hash, cancellation),
+            dig,
+            self.warm_fees(peer, &cache, parallelism, cancellation),
+        );
+
+        outcome.merge(xch?);
+        outcome.merge(dig?);
+        outcome.merge(fees?);
+
+        Ok(WarmupReport {
+            duration: started.elapsed(),
+            entries_refreshed: outcome.refreshed,
+            failures: outcome.failures,
+        })
+    }
+
+    /// Fetch the XCH coin set, recording the peak the peer synced it at
+    async fn warm_xch(
+        &self,
+        peer: &Peer,
+        cache: &WarmCache,
+        owner_puzzle_hash: Bytes32,
+        cancellation: &CancellationToken,
+    ) -> Result<StepOutcome, WalletError> {
+        let mut outcome = StepOutcome::default();
+        let now = self.get_config().unix_timestamp();
+
+        let fetched = cancellation
+            .run(async {
+                Ok(datalayer_driver::async_api::get_all_unspent_coins(
+                    peer,
+                    owner_puzzle_hash,
+                    None,
+                    crate::chain::genesis_challenge(),
+                )
+                .await)
+            })
+            .await?;
+        let unspent = match fetched {
+            Ok(unspent) => unspent,
+            Err(e) => {
+                outcome.fail("xch coin set", e);
+                return Ok(outcome);
+            }
+        };
+
+        cache
+            .peaks
+            .set(
+                PEAK_KEY,
+                &CachedPeak {
+                    height: unspent.last_height,
+                    header_hash: hex::encode(unspent.last_header_hash),
+                    fetched_at: now,
+                },
+            )
+            .await?;
+        self.sync_checkpoints()?
+            .record(SyncCheckpoint {
+                height: unspent.last_height,
+                header_hash: hex::encode(unspent.last_header_hash),
+                recorded_at: now,
+            })
+            .await?;
+        cache
+            .coin_sets
+            .set(
+                &Asset::Xch.to_string(),
+                &CachedCoinSet {
+                    asset: Asset::Xch,
+                    coins: unspent
+                        .coin_states
+                        .iter()
+                        .map(|state| SerializedCoin::from(&state.coin))
+                        .collect(),
+                    fetched_at: now,
+                },
+            )
+            .await?;
+        outcome.refreshed += 2;
+
+        Ok(outcome)
+    }
+
+    /// Fetch DIG coin states and prove each coin's lineage concurrently
+    #[cfg(feature = "cat")]
+    async fn warm_dig(
+        &self,
+        peer: &Peer,
+        cache: &WarmCache,
+        owner_puzzle_hash: Bytes32,
+        parallelism: usize,
+        cancellation: &CancellationToken,
+    ) -> Result<StepOutcome, WalletError> {
+        let mut outcome = StepOutcome::default();
+        let now = self.get_config().unix_timestamp();
+
+        let fetched = cancellation
+            .run(async {
+                Ok(datalayer_driver::async_api::get_all_unspent_coins(
+                    peer,
+                    DigCoin::puzzle_hash(owner_puzzle_hash),
+                    None,
+                    crate::chain::genesis_challenge(),
+                )
+                .await)
+            })
+            .await?;
+        let unspent = match fetched {
+            Ok(unspent) => unspent,
+            Err(e) => {
+                outcome.fail("dig coin set", e);
+                return Ok(outcome);
+            }
+        };
+
+        let mut tasks = TaskSet::new(self.get_config().runtime()?);
+        let mut pending: Vec<CoinState> = unspent.coin_states;
+        let mut proven = Vec::new();
+
+        while !pending.is_empty() || !tasks.is_empty() {
+            while tasks.len() < parallelism {
+                let Some(coin_state) = pending.pop() else {
+                    break;
+                };
+                let peer = peer.clone();
+                tasks.spawn(async move {
+                    let result = DigCoin::from_coin_state(&peer, &coin_state).await;
+                    (coin_state.coin.coin_id(), result)
+                });
+            }
+
+            // Dropping `tasks` on cancellation aborts the fetches still in flight
+            match cancellation
+                .run(async { Ok(tasks.join_next().await) })
+                .await?
+            {
+                Some(Ok((_, Ok(dig_coin)))) => proven.push(dig_coin.cat()),
+                Some(Ok((coin_id, Err(e)))) => outcome.fail(&format!("dig lineage {}", coin_id), e),
+                Some(Err(e)) => outcome.fail("dig lineage task", e),
+                None => {}
+            }
+        }
+
+        for cat in &proven {
+            let Some(lineage_proof) = cat.lineage_proof else {
+                continue;
+            };
+            cache
+                .lineages
+                .set(
+                    &hex::encode(cat.coin.coin_id()),
+                    &CachedLineage {
+                        coin_id: hex::encode(cat.coin.coin_id()),
+                        parent_parent_coin_info: hex::encode(lineage_proof.parent_parent_coin_info),
+                        parent_inner_puzzle_hash: hex::encode(
+                            lineage_proof.parent_inner_puzzle_hash,
+                        ),
+                        parent_amount: lineage_proof.parent_amount,
+                    },
+                )
+                .await?;
+            outcome.refreshed += 1;
+        }
+
+        cache
+            .coin_sets
+            .set(
+                &Asset::Dig.to_string(),
+                &CachedCoinSet {
+                    asset: Asset::Dig,
+                    coins: proven
+                        .iter()
+                        .map(|cat| SerializedCoin::from(&cat.coin))
+                        .collect(),
+                    fetched_at: now,
+                },
+            )
+            .await?;
+        outcome.refreshed += 1;
+
+        Ok(outcome)
+    }
+
+    async fn warm_fees(
+        &self,
+        peer: &Peer,
+        cache: &WarmCache,
+        parallelism: usize,
+        cancellation: &CancellationToken,
+    ) -> Result<StepOutcome, WalletError> {
+        let mut outcome = StepOutcome::default();
+        let now = self.get_config().unix_timestamp();
+
+        let mut tasks = TaskSet::new(self.get_config().runtime()?);
+        let mut targets = FEE_ESTIMATE_TARGETS.to_vec();
+
+        while !targets.is_empty() || !tasks.is_empty() {
+            while tasks.len() < parallelism {
+                let Some(target_seconds) = targets.pop() else {
+                    break;
+                };
+                let peer = peer.clone();
+                tasks.spawn(async move {
+                    let result = datalayer_driver::get_fee_estimate(&peer, target_seconds).await;
+                    (target_seconds, result)
+                });
+            }
+
+            // Dropping `tasks` on cancellation aborts the fetches still in flight
+            match cancellation
+                .run(async { Ok(tasks.join_next().await) })
+                .await?
+            {
+                Some(Ok((target_seconds, Ok(fee)))) => {
+                    cache
+                        .fees
+                        .set(
+                            &target_seconds.to_string(),
+                            &CachedFeeEstimate {
+                                target_seconds,
+                                fee,
+                                fetched_at: now,
+                            },
+                        )
+                        .await?;
+                    outcome.refreshed += 1;
+                }
+                Some(Ok((target_seconds, Err(e)))) => {
+                    outcome.fail(&format!("fee estimate {}s", target_seconds), e)
+                }
+                Some(Err(e)) => outcome.fail("fee estimate task", e),
+                None => {}
+            }
+        }
+
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_warm_cache_accessors() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        let cache = WarmCache::open("warm_test", &config).unwrap();
+
+        assert!(cache.peak().unwrap().is_none());
+        assert!(cache.coin_set(Asset::Dig).unwrap().is_none());
+
+        let peak = CachedPeak {
+            height: 42,
+            header_hash: hex::encode([1u8; 32]),
+            fetched_at: 7,
+        };
+        cache.peaks.set(PEAK_KEY, &peak).await.unwrap();
+        cache
+            .fees
+            .set(
+                "300",
+                &CachedFeeEstimate {
+                    target_seconds: 300,
+                    fee: 5,
+                    fetched_at: 7,
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(cache.peak().unwrap(), Some(peak));
+        assert_eq!(cache.fee_estimate(300).unwrap().unwrap().fee, 5);
+        assert!(cache.fee_estimate(60).unwrap().is_none());
+    }
+}
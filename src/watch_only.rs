@@ -0,0 +1,178 @@
+//! Watch-only wallets, stored as a public key without a mnemonic.
+//!
+//! A watch-only keyring entry answers balance queries, coin listings and address lookups
+//! like any other wallet, but every method that needs a secret key fails with
+//! [`WalletError::WatchOnly`]. Entries made from a master public key can also derive
+//! further receive addresses; entries made from a synthetic public key only know the
+//! wallet's first address.
+
+use crate::config::WalletConfig;
+use crate::error::WalletError;
+use crate::keys::synthetic_public_key;
+use crate::wallet::Wallet;
+use chia::bls::PublicKey;
+use serde::{Deserialize, Serialize};
+
+/// The public key a watch-only wallet is built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "StoredWatchOnlyKey", try_from = "StoredWatchOnlyKey")]
+pub enum WatchOnlyKey {
+    /// Master public key, as shown by `chia keys show`
+    Master(PublicKey),
+    /// Synthetic public key of the first wallet address
+    Synthetic(PublicKey),
+}
+
+impl WatchOnlyKey {
+    /// The master public key, if this key is one
+    pub fn master_public_key(&self) -> Option<PublicKey> {
+        match self {
+            WatchOnlyKey::Master(public_key) => Some(*public_key),
+            WatchOnlyKey::Synthetic(_) => None,
+        }
+    }
+
+    /// Synthetic public key of the first wallet address
+    pub fn synthetic_public_key(&self) -> PublicKey {
+        match self {
+            WatchOnlyKey::Master(public_key) => synthetic_public_key(public_key),
+            WatchOnlyKey::Synthetic(public_key) => *public_key,
+        }
+    }
+}
+
+/// Keyring form of a [`WatchOnlyKey`]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StoredKind {
+    Master,
+    Synthetic,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredWatchOnlyKey {
+    kind: StoredKind,
+    public_key: String,
+}
+
+impl From<WatchOnlyKey> for StoredWatchOnlyKey {
+    fn from(key: WatchOnlyKey) -> Self {
+        let (kind, public_key) = match key {
+            WatchOnlyKey::Master(public_key) => (StoredKind::Master, public_key),
+            WatchOnlyKey::Synthetic(public_key) => (StoredKind::Synthetic, public_key),
+        };
+        Self {
+            kind,
+            public_key: hex::encode(public_key.to_bytes()),
+        }
+    }
+}
+
+impl TryFrom<StoredWatchOnlyKey> for WatchOnlyKey {
+    type Error = String;
+
+    fn try_from(stored: StoredWatchOnlyKey) -> Result<Self, Self::Error> {
+        let bytes: [u8; 48] = hex::decode(&stored.public_key)
+            .map_err(|e| e.to_string())?
+            .try_into()
+            .map_err(|_| "public key must be 48 bytes".to_string())?;
+        let public_key = PublicKey::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        Ok(match stored.kind {
+            StoredKind::Master => WatchOnlyKey::Master(public_key),
+            StoredKind::Synthetic => WatchOnlyKey::Synthetic(public_key),
+        })
+    }
+}
+
+impl Wallet {
+    /// Store a watch-only wallet for `key` under `wallet_name`
+    pub async fn import_watch_only(
+        wallet_name: &str,
+        key: WatchOnlyKey,
+    ) -> Result<(), WalletError> {
+        Self::import_watch_only_with_config(wallet_name, key, &WalletConfig::default()).await
+    }
+
+    /// Store a watch-only wallet using an explicit configuration
+    ///
+    /// Replaces an existing watch-only entry of the same name, but fails with
+    /// [`WalletError::WalletExists`] rather than overwrite a wallet holding a mnemonic.
+    pub async fn import_watch_only_with_config(
+        wallet_name: &str,
+        key: WatchOnlyKey,
+        config: &WalletConfig,
+    ) -> Result<(), WalletError> {
+        Self::save_watch_only_to_keyring(wallet_name, key, config).await
+    }
+}
+
+#[cfg(all(test, feature = "keyring-file"))]
+mod tests {
+    use super::*;
+    use crate::environment::SandboxEnvironment;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_watch_only_wallet_reads_but_cannot_sign() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+        Wallet::create_new_wallet_with_config("hot", &config)
+            .await
+            .unwrap();
+        let hot = Wallet::load_with_config(Some("hot".to_string()), false, &config)
+            .await
+            .unwrap();
+        let master_pk = hot.get_master_public_key().await.unwrap();
+
+        Wallet::import_watch_only_with_config("cold", WatchOnlyKey::Master(master_pk), &config)
+            .await
+            .unwrap();
+        let watch = Wallet::load_with_config(Some("cold".to_string()), false, &config)
+            .await
+            .unwrap();
+        assert!(watch.is_watch_only());
+        assert_eq!(
+            watch.get_owner_public_key().await.unwrap(),
+            hot.get_owner_public_key().await.unwrap()
+        );
+        assert_eq!(
+            watch.get_fingerprint().await.unwrap(),
+            hot.get_fingerprint().await.unwrap()
+        );
+        assert!(matches!(
+            watch.mnemonic_phrase(),
+            Err(WalletError::WatchOnly)
+        ));
+        assert!(matches!(
+            watch.create_key_ownership_signature("nonce").await,
+            Err(WalletError::WatchOnly)
+        ));
+
+        let infos = Wallet::list_wallet_info_with_config(&config).await.unwrap();
+        assert!(infos
+            .iter()
+            .any(|info| info.name == "cold" && info.watch_only));
+
+        // A synthetic key only knows the first address
+        let synthetic = WatchOnlyKey::Synthetic(hot.get_public_synthetic_key().await.unwrap());
+        Wallet::import_watch_only_with_config("first", synthetic, &config)
+            .await
+            .unwrap();
+        let first = Wallet::load_with_config(Some("first".to_string()), false, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            first.get_owner_puzzle_hash().await.unwrap(),
+            hot.get_owner_puzzle_hash().await.unwrap()
+        );
+        assert!(matches!(
+            first.get_master_public_key().await,
+            Err(WalletError::WatchOnly)
+        ));
+
+        assert!(matches!(
+            Wallet::import_watch_only_with_config("hot", synthetic, &config).await,
+            Err(WalletError::WalletExists(_))
+        ));
+    }
+}
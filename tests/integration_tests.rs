@@ -1,36 +1,40 @@
-use dig_wallet::{Wallet, WalletError};
-use std::env;
+#![cfg(all(feature = "peer", feature = "keyring-file"))]
+
+use dig_wallet::{SandboxEnvironment, Wallet, WalletConfig, WalletError};
 use tempfile::TempDir;
 
-// Test helper to set up isolated test environment
-fn setup_integration_test_env() -> TempDir {
+// Test helper to set up an isolated, sandboxed wallet configuration
+fn setup_integration_test_env() -> (TempDir, WalletConfig) {
     let temp_dir = TempDir::new().unwrap();
+    let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+    (temp_dir, config)
+}
 
-    // Set up isolated keyring path for this test
-    let keyring_path = temp_dir.path().join("integration_keyring.json");
-    env::set_var(
-        "TEST_KEYRING_PATH",
-        keyring_path.to_string_lossy().to_string(),
-    );
-    env::set_var("HOME", temp_dir.path());
-
-    temp_dir
+// Reveal a wallet's mnemonic, confirming the prompt
+async fn reveal(wallet: &Wallet) -> String {
+    wallet
+        .reveal_mnemonic(None, |_| true, |words| words.to_string())
+        .await
+        .unwrap()
 }
 
 #[tokio::test]
 async fn test_full_wallet_lifecycle() {
-    let _temp_dir = setup_integration_test_env();
+    let (_temp_dir, config) = setup_integration_test_env();
 
     // 1. Create a new wallet
-    let mnemonic = Wallet::create_new_wallet("lifecycle_test").await.unwrap();
+    let mnemonic = Wallet::create_new_wallet_with_config("lifecycle_test", &config)
+        .await
+        .unwrap()
+        .into_string();
     assert_eq!(mnemonic.split_whitespace().count(), 24);
 
     // 2. Load the wallet
-    let wallet = Wallet::load(Some("lifecycle_test".to_string()), false)
+    let wallet = Wallet::load_with_config(Some("lifecycle_test".to_string()), false, &config)
         .await
         .unwrap();
     assert_eq!(wallet.get_wallet_name(), "lifecycle_test");
-    assert_eq!(wallet.get_mnemonic().unwrap(), mnemonic);
+    assert_eq!(reveal(&wallet).await, mnemonic);
 
     // 3. Generate keys and address
     let _master_sk = wallet.get_master_secret_key().await.unwrap();
@@ -62,48 +66,57 @@ async fn test_full_wallet_lifecycle() {
     assert_eq!(address, converted_address);
 
     // 7. Verify wallet is in list
-    let wallets = Wallet::list_wallets().await.unwrap();
+    let wallets = Wallet::list_wallets_with_config(&config).await.unwrap();
     assert!(wallets.contains(&"lifecycle_test".to_string()));
 
     // 8. Delete wallet
-    let deleted = Wallet::delete_wallet("lifecycle_test").await.unwrap();
+    let deleted = Wallet::delete_wallet_with_config("lifecycle_test", &config)
+        .await
+        .unwrap();
     assert!(deleted);
 
     // 9. Verify wallet is gone
-    let wallets_after = Wallet::list_wallets().await.unwrap();
+    let wallets_after = Wallet::list_wallets_with_config(&config).await.unwrap();
     assert!(!wallets_after.contains(&"lifecycle_test".to_string()));
 
     // 10. Try to load deleted wallet (should fail)
-    let result = Wallet::load(Some("lifecycle_test".to_string()), false).await;
+    let result = Wallet::load_with_config(Some("lifecycle_test".to_string()), false, &config).await;
     assert!(matches!(result, Err(WalletError::WalletNotFound(_))));
 }
 
 #[tokio::test]
 async fn test_wallet_import_and_consistency() {
-    let _temp_dir = setup_integration_test_env();
+    let (_temp_dir, config) = setup_integration_test_env();
 
     // Known test mnemonic that should produce consistent results
     let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
 
-    // Import wallet twice with different names
-    Wallet::import_wallet("consistent1", Some(test_mnemonic))
+    // Import wallet twice with different names, which must be allowed explicitly
+    Wallet::import_wallet_with_config("consistent1", Some(test_mnemonic), &config)
         .await
         .unwrap();
-    Wallet::import_wallet("consistent2", Some(test_mnemonic))
+    let result =
+        Wallet::import_wallet_with_config("consistent2", Some(test_mnemonic), &config).await;
+    assert!(matches!(
+        result,
+        Err(WalletError::DuplicateMnemonic { ref existing, .. }) if existing == &["consistent1"]
+    ));
+    let duplicates = config.clone().with_allow_duplicate_mnemonics(true);
+    Wallet::import_wallet_with_config("consistent2", Some(test_mnemonic), &duplicates)
         .await
         .unwrap();
 
     // Load both wallets
-    let wallet1 = Wallet::load(Some("consistent1".to_string()), false)
+    let wallet1 = Wallet::load_with_config(Some("consistent1".to_string()), false, &config)
         .await
         .unwrap();
-    let wallet2 = Wallet::load(Some("consistent2".to_string()), false)
+    let wallet2 = Wallet::load_with_config(Some("consistent2".to_string()), false, &config)
         .await
         .unwrap();
 
     // Both should have the same mnemonic
-    assert_eq!(wallet1.get_mnemonic().unwrap(), test_mnemonic);
-    assert_eq!(wallet2.get_mnemonic().unwrap(), test_mnemonic);
+    assert_eq!(reveal(&wallet1).await, test_mnemonic);
+    assert_eq!(reveal(&wallet2).await, test_mnemonic);
 
     // Both should generate the same keys
     let sk1 = wallet1.get_master_secret_key().await.unwrap();
@@ -127,14 +140,17 @@ async fn test_wallet_import_and_consistency() {
 
 #[tokio::test]
 async fn test_multiple_wallet_isolation() {
-    let _temp_dir = setup_integration_test_env();
+    let (_temp_dir, config) = setup_integration_test_env();
 
     // Create multiple wallets
     let wallets_to_create = vec!["isolation1", "isolation2", "isolation3", "isolation4"];
     let mut created_mnemonics = Vec::new();
 
     for wallet_name in &wallets_to_create {
-        let mnemonic = Wallet::create_new_wallet(wallet_name).await.unwrap();
+        let mnemonic = Wallet::create_new_wallet_with_config(wallet_name, &config)
+            .await
+            .unwrap()
+            .into_string();
         created_mnemonics.push(mnemonic);
     }
 
@@ -148,7 +164,7 @@ async fn test_multiple_wallet_isolation() {
     // Load all wallets and verify their addresses are different
     let mut addresses = Vec::new();
     for wallet_name in &wallets_to_create {
-        let wallet = Wallet::load(Some(wallet_name.to_string()), false)
+        let wallet = Wallet::load_with_config(Some(wallet_name.to_string()), false, &config)
             .await
             .unwrap();
         let address = wallet.get_owner_public_key().await.unwrap();
@@ -163,7 +179,7 @@ async fn test_multiple_wallet_isolation() {
     }
 
     // Verify all wallets are listed
-    let wallet_list = Wallet::list_wallets().await.unwrap();
+    let wallet_list = Wallet::list_wallets_with_config(&config).await.unwrap();
     for wallet_name in &wallets_to_create {
         assert!(wallet_list.contains(&wallet_name.to_string()));
     }
@@ -172,13 +188,13 @@ async fn test_multiple_wallet_isolation() {
 
 #[tokio::test]
 async fn test_signature_verification_edge_cases() {
-    let _temp_dir = setup_integration_test_env();
+    let (_temp_dir, config) = setup_integration_test_env();
 
     let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
-    Wallet::import_wallet("signature_test", Some(test_mnemonic))
+    Wallet::import_wallet_with_config("signature_test", Some(test_mnemonic), &config)
         .await
         .unwrap();
-    let wallet = Wallet::load(Some("signature_test".to_string()), false)
+    let wallet = Wallet::load_with_config(Some("signature_test".to_string()), false, &config)
         .await
         .unwrap();
 
@@ -241,12 +257,12 @@ async fn test_address_encoding_edge_cases() {
     assert!(result.is_err());
 
     // Test 4: Valid address roundtrip
-    let _temp_dir = setup_integration_test_env();
+    let (_temp_dir, config) = setup_integration_test_env();
     let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
-    Wallet::import_wallet("address_edge_test", Some(test_mnemonic))
+    Wallet::import_wallet_with_config("address_edge_test", Some(test_mnemonic), &config)
         .await
         .unwrap();
-    let wallet = Wallet::load(Some("address_edge_test".to_string()), false)
+    let wallet = Wallet::load_with_config(Some("address_edge_test".to_string()), false, &config)
         .await
         .unwrap();
 
@@ -264,7 +280,7 @@ async fn test_address_encoding_edge_cases() {
 
 #[tokio::test]
 async fn test_encryption_robustness() {
-    let _temp_dir = setup_integration_test_env();
+    let (_temp_dir, config) = setup_integration_test_env();
 
     // Test encryption with various data sizes and types
     let test_cases = vec![
@@ -289,15 +305,16 @@ async fn test_encryption_robustness() {
         if test_data.len() > 10 {
             // Use a real mnemonic for longer test cases
             let real_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
-            Wallet::import_wallet(&wallet_name, Some(real_mnemonic))
+            let duplicates = config.clone().with_allow_duplicate_mnemonics(true);
+            Wallet::import_wallet_with_config(&wallet_name, Some(real_mnemonic), &duplicates)
                 .await
                 .unwrap();
 
             // Load and verify
-            let wallet = Wallet::load(Some(wallet_name.clone()), false)
+            let wallet = Wallet::load_with_config(Some(wallet_name.clone()), false, &config)
                 .await
                 .unwrap();
-            assert_eq!(wallet.get_mnemonic().unwrap(), real_mnemonic);
+            assert_eq!(reveal(&wallet).await, real_mnemonic);
 
             // Verify the wallet can perform crypto operations
             let signature = wallet.create_key_ownership_signature("test").await.unwrap();
@@ -308,22 +325,27 @@ async fn test_encryption_robustness() {
 
 #[tokio::test]
 async fn test_concurrent_wallet_operations() {
-    let _temp_dir = setup_integration_test_env();
+    let (_temp_dir, config) = setup_integration_test_env();
 
     // Create multiple wallets concurrently (though they'll run sequentially in single-threaded test)
     let wallet_names = vec!["concurrent1", "concurrent2", "concurrent3"];
 
     // Create wallets
     for name in &wallet_names {
-        Wallet::create_new_wallet(name).await.unwrap();
+        Wallet::create_new_wallet_with_config(name, &config)
+            .await
+            .unwrap();
     }
 
     // Load all wallets and perform operations
     let mut handles = Vec::new();
 
     for name in wallet_names {
+        let config = config.clone();
         let handle = tokio::spawn(async move {
-            let wallet = Wallet::load(Some(name.to_string()), false).await.unwrap();
+            let wallet = Wallet::load_with_config(Some(name.to_string()), false, &config)
+                .await
+                .unwrap();
 
             // Perform various operations
             let _master_key = wallet.get_master_secret_key().await.unwrap();
@@ -1,40 +1,46 @@
 // Test that verifies all public API exports are accessible
 // This simulates importing the crate from another Rust project
+#![cfg(all(feature = "peer", feature = "keyring-file"))]
 
 use dig_wallet::{
-    Bytes32, Coin, CoinSpend, FileCache, NetworkType, Peer, PublicKey, SecretKey, Signature,
-    Wallet, WalletError, VERSION,
+    Bytes32, Coin, CoinSpend, FileCache, NetworkType, Peer, PublicKey, SandboxEnvironment,
+    SecretKey, Signature, Wallet, WalletConfig, WalletError, VERSION,
 };
-use std::env;
 use tempfile::TempDir;
 
-// Test helper to set up isolated test environment
-fn setup_api_test_env() -> TempDir {
+// Test helper to set up an isolated, sandboxed wallet configuration
+fn setup_api_test_env() -> (TempDir, WalletConfig) {
     let temp_dir = TempDir::new().unwrap();
-    let keyring_path = temp_dir.path().join("api_test_keyring.json");
-    env::set_var(
-        "TEST_KEYRING_PATH",
-        keyring_path.to_string_lossy().to_string(),
-    );
-    env::set_var("HOME", temp_dir.path());
-    temp_dir
+    let config = WalletConfig::new().with_environment(SandboxEnvironment::new(temp_dir.path()));
+    (temp_dir, config)
+}
+
+// Reveal a wallet's mnemonic, confirming the prompt
+async fn reveal(wallet: &Wallet) -> String {
+    wallet
+        .reveal_mnemonic(None, |_| true, |words| words.to_string())
+        .await
+        .unwrap()
 }
 
 #[tokio::test]
 async fn test_public_api_wallet_exports() {
-    let _temp_dir = setup_api_test_env();
+    let (_temp_dir, config) = setup_api_test_env();
 
     // Test that all Wallet methods are accessible
 
     // 1. Wallet creation and management
-    let mnemonic = Wallet::create_new_wallet("api_test_wallet").await.unwrap();
+    let mnemonic = Wallet::create_new_wallet_with_config("api_test_wallet", &config)
+        .await
+        .unwrap()
+        .into_string();
     assert_eq!(mnemonic.split_whitespace().count(), 24);
 
-    let wallet = Wallet::load(Some("api_test_wallet".to_string()), false)
+    let wallet = Wallet::load_with_config(Some("api_test_wallet".to_string()), false, &config)
         .await
         .unwrap();
     assert_eq!(wallet.get_wallet_name(), "api_test_wallet");
-    assert_eq!(wallet.get_mnemonic().unwrap(), mnemonic);
+    assert_eq!(reveal(&wallet).await, mnemonic);
 
     // 2. Key operations
     let _master_sk = wallet.get_master_secret_key().await.unwrap();
@@ -62,10 +68,12 @@ async fn test_public_api_wallet_exports() {
     assert!(is_valid);
 
     // 5. Wallet management
-    let wallets = Wallet::list_wallets().await.unwrap();
+    let wallets = Wallet::list_wallets_with_config(&config).await.unwrap();
     assert!(wallets.contains(&"api_test_wallet".to_string()));
 
-    let deleted = Wallet::delete_wallet("api_test_wallet").await.unwrap();
+    let deleted = Wallet::delete_wallet_with_config("api_test_wallet", &config)
+        .await
+        .unwrap();
     assert!(deleted);
 }
 
@@ -77,14 +85,17 @@ async fn test_public_api_file_cache_exports() {
     let cache: FileCache<String> = FileCache::new("test_cache", Some(temp_dir.path())).unwrap();
 
     // Test cache operations
-    cache.set("test_key", &"test_value".to_string()).unwrap();
+    cache
+        .set("test_key", &"test_value".to_string())
+        .await
+        .unwrap();
     let value = cache.get("test_key").unwrap().unwrap();
     assert_eq!(value, "test_value");
 
     let keys = cache.get_cached_keys().unwrap();
     assert!(keys.contains(&"test_key".to_string()));
 
-    cache.delete("test_key").unwrap();
+    cache.delete("test_key").await.unwrap();
     let deleted_value = cache.get("test_key").unwrap();
     assert!(deleted_value.is_none());
 }
@@ -118,12 +129,13 @@ fn test_public_api_type_exports() {
 
 #[tokio::test]
 async fn test_public_api_error_handling() {
-    let _temp_dir = setup_api_test_env();
+    let (_temp_dir, config) = setup_api_test_env();
 
     // Test that all error types are accessible and can be matched
 
     // Test WalletNotFound error
-    let result = Wallet::load(Some("nonexistent_wallet".to_string()), false).await;
+    let result =
+        Wallet::load_with_config(Some("nonexistent_wallet".to_string()), false, &config).await;
     match result {
         Err(WalletError::WalletNotFound(name)) => {
             assert_eq!(name, "nonexistent_wallet");
@@ -132,7 +144,8 @@ async fn test_public_api_error_handling() {
     }
 
     // Test InvalidMnemonic error
-    let result = Wallet::import_wallet("invalid_test", Some("invalid mnemonic")).await;
+    let result =
+        Wallet::import_wallet_with_config("invalid_test", Some("invalid mnemonic"), &config).await;
     match result {
         Err(WalletError::InvalidMnemonic) => {
             // Expected
@@ -141,7 +154,7 @@ async fn test_public_api_error_handling() {
     }
 
     // Test MnemonicRequired error
-    let result = Wallet::import_wallet("empty_test", None).await;
+    let result = Wallet::import_wallet_with_config("empty_test", None, &config).await;
     match result {
         Err(WalletError::MnemonicRequired) => {
             // Expected
@@ -161,13 +174,14 @@ fn test_public_api_constants() {
 
 #[tokio::test]
 async fn test_external_crate_usage_simulation() {
-    let _temp_dir = setup_api_test_env();
+    let (_temp_dir, config) = setup_api_test_env();
 
     // This test simulates how an external crate would use dig-wallet
     // It only uses the public API as it would be available to external users
 
     // Step 1: Create a wallet (as external crate would)
-    let wallet_result = Wallet::load(Some("external_test".to_string()), true).await;
+    let wallet_result =
+        Wallet::load_with_config(Some("external_test".to_string()), true, &config).await;
     assert!(wallet_result.is_ok());
     let wallet = wallet_result.unwrap();
 
@@ -190,13 +204,13 @@ async fn test_external_crate_usage_simulation() {
     assert!(puzzle_hash_result.is_ok());
 
     // Step 5: List wallets (as external crate would)
-    let wallets_result = Wallet::list_wallets().await;
+    let wallets_result = Wallet::list_wallets_with_config(&config).await;
     assert!(wallets_result.is_ok());
     let wallets = wallets_result.unwrap();
     assert!(wallets.contains(&"external_test".to_string()));
 
     // Step 6: Clean up (as external crate would)
-    let delete_result = Wallet::delete_wallet("external_test").await;
+    let delete_result = Wallet::delete_wallet_with_config("external_test", &config).await;
     assert!(delete_result.is_ok());
     assert!(delete_result.unwrap());
 }